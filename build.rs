@@ -0,0 +1,13 @@
+//! Records the git commit and build time of this build for `crate::build_info`
+//!
+//! Builds outside a git checkout, such as a Docker build context without `.git`, still succeed;
+//! the commit is then reported as unknown.
+
+use vergen_gitcl::{BuildBuilder, Emitter, GitclBuilder};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let build = BuildBuilder::default().build_timestamp(true).build()?;
+    let git = GitclBuilder::default().sha(false).build()?;
+    Emitter::default().add_instructions(&build)?.add_instructions(&git)?.emit()?;
+    Ok(())
+}
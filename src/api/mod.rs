@@ -1,414 +1,11083 @@
 use axum::{
-    extract::{Path, State, Query},
-    response::Json,
-    http::StatusCode,
+    body::Bytes,
+    extract::{
+        multipart::{Multipart, MultipartError, MultipartRejection},
+        Extension, FromRequest, MatchedPath, OriginalUri, Path, Request, State, Query,
+    },
+    middleware::Next,
+    response::{IntoResponse, Json, Redirect, Response},
+    http::{header, uri::PathAndQuery, HeaderMap, Method, StatusCode, Uri},
 };
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use tokio::sync::RwLock;
 use uuid::Uuid;
-use serde::Deserialize;
+use serde::{de::DeserializeOwned, Deserialize};
+#[cfg(not(feature = "verifier-only"))]
+use sha2::{Digest, Sha256};
+use tower_http::compression::{
+    predicate::{NotForContentType, Predicate, SizeAbove},
+    CompressionLayer,
+};
 
 use crate::{
-    key_generation::generate_key_pair,
-    key_storage::KeyStorage,
-    key_verification::{sign_document as sign_doc, verify_signature as verify_sig, sign_document_content},
+    archive_verification::{hash_entries, ArchiveLimits, ArchiveManifest, ArchiveReport, EntryCheck, EntryStatus},
+    api_version::{
+        is_unversioned_path, split_version, sunset_header, ApiUsage, ApiVersion, RequestedVersion,
+        UNVERSIONED_DEPRECATED_AT, UNVERSIONED_LABEL,
+    },
+    build_info::SignerInfo,
+    bundle::{verify_bundle, BundleSubject},
+    capabilities::{About, HashAlgorithm, ServiceCapabilities},
+    capacity::KeystoreCapacity,
+    certification::CertificationStore,
+    clock::Clock,
+    config::Config,
+    deadline::{CancelOnDrop, Deadline, RequestDeadlines},
+    delegation::{DelegationStore, DELEGATION_TOKEN_HEADER},
+    dsse::VerifyMode,
+    entropy::EntropyMonitor,
+    event_log::OperationEvent,
+    kdf_stats::KdfTimings,
+    export::{encode_jwk, encode_pem},
+    federation::{KeyResolver, RemoteKey},
+    field_case::{apply_field_case, FieldCase, PreserveFieldCase, FIELD_CASE_HEADER},
+    i18n::{localize_body, Locale},
+    integrity::KeystoreLoadSummary,
+    key_disclosure::{self, UsableKeysOnly, CONCEALED_CODES, CONCEALED_FAILURE_FLOOR, CONCEALED_MESSAGE},
+    key_formats::{normalize_public_key, parse_public_key as parse_supplied_public_key},
+    key_status::{key_statuses, KeyStatusDocument, MAX_STATUS_BATCH},
+    key_storage::{KeyCursor, KeyFilter, KeyPage, KeyStorage},
+    key_verification::{
+        create_document_hash, decode_public_key, is_signature_window_expired, load_signing_key, normalize_context,
+        content_bytes, resolve_document_hash, validate_context, validate_verify_input,
+    },
+    limits::OperationLimits,
+    minisign,
+    metrics::{self, render_metrics, ServiceMetrics},
+    pinset::{PinFormat, Pinset},
+    profile::DeploymentProfile,
     models::*,
+    rate_limit::ClientRateLimiter,
+    receipts::ReceiptStore,
+    request_auth::{has_admin_scope, RequestAuthenticator, SignedRequest, CLIENT_ID_HEADER, SIGNATURE_HEADER, TIMESTAMP_HEADER},
+    shares::ShareStore,
+    sign_policy::SignPolicy,
+    self_test::SelfTestReport,
+    signing_backend::SigningBackend,
+    slo::{SloOperation, SloTracker},
+    sshsig,
+    storage_breaker,
+    sweeper::TaskStatus,
+    usage_report::UsageReportCache,
+    utils::{compact_fingerprint, public_key_to_fingerprint},
+    verification_cache::{cache_key, VerificationCache},
+};
+// Signing, generation, export, re-encryption and the admin routes, which verifier-only builds
+// compile out
+#[cfg(not(feature = "verifier-only"))]
+use crate::{
+    bundle::{Bundle, BundleBody, BundleKeyStatus, BUNDLE_SCHEMA, BUNDLE_VERSION},
+    certification::{Certification, CertificationPayload},
+    config::calibrate_kdf,
+    delegation::{DelegationGrant, DelegationInfo, MAX_DELEGATION_USES},
+    dsse,
+    environment,
+    event_log::{DEFAULT_REPLAY_LIMIT, MAX_REPLAY_LIMIT},
+    kdf_stats::{self, KeyProtection},
+    export::{build_export, parse_encodings, stream_archive, ArchiveFormat},
+    file_manifest::FileManifest,
+    key_comparison::{compare_keys as compare_manifests, verify_export_manifest, KeyManifest, KeyManifestEntry},
+    key_generation::{generate_hsm_key_pair, generate_key_pair_from_seed, upgrade_legacy_private_key, KdfTiming, validate_generate_request, validate_update_request, MIN_PASSWORD_LENGTH},
+    key_pool::KeyPool,
+    key_transport::{wrap_key, TransportKey},
+    lifecycle::Lifecycle,
+    key_verification::{
+        bindable_signing_time, check_message_encoding, derive_signature_id, encode_signing_message, load_signing_key_timed,
+        sign_document_hash,
+    },
+    migration::{import_legacy_records, MAX_LEGACY_IMPORT_RECORDS},
+    overview::{
+        AdminOverview, BackgroundTasks, ExpiringKey, KeyStats, OverviewSources, RecentSignature, ServiceFlags,
+        EXPIRING_SOON_DAYS, MAX_EXPIRING_KEYS, MAX_RECENT_SIGNATURES, SECTION_TIMEOUT,
+    },
+    receipts::ReceiptFailurePolicy,
+    reencryption::{rewrap_private_key, scan_envelopes, EnvelopeRevision},
+    secret::SecretString,
+    sign_policy::PolicyRequest,
+    self_test::run_self_test,
+    signing_backend::{ensure_known_key_type, load_signer, KeySigner},
+    slo::SloReport,
+    usage_report::{check_range, GroupBy, UsageReport, CSV_CONTENT_TYPE},
 };
 
 /// Shared state for the application
 pub struct AppState {
     pub storage: Arc<KeyStorage>,
+    pub clock: Arc<dyn Clock>,
+    pub config: Arc<Config>,
+    pub receipts: Arc<ReceiptStore>,
+    pub certifications: Arc<CertificationStore>,
+    /// Refuse mutations; starts from `config.read_only` and can be switched at runtime
+    pub read_only: AtomicBool,
+    /// Result of the most recent self-test
+    pub self_test: RwLock<Option<SelfTestReport>>,
+    /// Validation of the keystore as it was loaded; only the owning instance validates it
+    pub keystore_load: Option<KeystoreLoadSummary>,
+    /// Entropy source new keys are generated from, and its health
+    pub entropy: Arc<EntropyMonitor>,
+    /// Backend holding HSM keys, if one is configured
+    pub hsm: Option<Arc<dyn SigningBackend>>,
+    /// Another instance owns the keystore, so read-only mode cannot be switched off
+    pub follower: bool,
+    /// Concurrency limits on key generation and encrypted-key signing
+    pub limits: OperationLimits,
+    /// Published verification links
+    pub shares: Arc<ShareStore>,
+    /// Signing delegations handed to CI jobs
+    pub delegations: Arc<DelegationStore>,
+    /// Recent cryptographic verification results
+    pub verification_cache: VerificationCache,
+    /// Client secrets for HMAC-signed requests
+    pub request_auth: RequestAuthenticator,
+    /// Soft and hard limits on the number of stored keys
+    pub capacity: KeystoreCapacity,
+    /// Latencies of private key unlocks, by KDF
+    pub kdf_timings: KdfTimings,
+    /// Verification requests per unauthenticated client address
+    pub verify_rate_limit: ClientRateLimiter,
+    /// Service consulted before each signature
+    pub sign_policy: Arc<SignPolicy>,
+    /// Partner instances keys unknown here are resolved from
+    pub key_resolver: Arc<KeyResolver>,
+    /// X25519 key pair other instances wrap exported keys for
+    #[cfg(not(feature = "verifier-only"))]
+    pub transport_key: Arc<TransportKey>,
+    /// Latest run of the background sweeper
+    pub sweeper: Arc<TaskStatus>,
+    /// Time budgets of requests, and the requests that outlasted them
+    pub deadlines: RequestDeadlines,
+    /// Requests by API version
+    pub api_usage: ApiUsage,
+    /// Key pairs drawn ahead for `fast` generation
+    #[cfg(not(feature = "verifier-only"))]
+    pub key_pool: Arc<KeyPool>,
+    /// Usage reports computed in the last minute
+    pub usage_reports: UsageReportCache,
+    /// Latencies of generate, sign and verify requests against their objectives
+    pub slo: SloTracker,
 }
 
-/// Query parameters for listing keys
-#[derive(Debug, Deserialize)]
-pub struct ListKeysQuery {
-    pub active_only: Option<bool>,
-    pub key_type: Option<String>,
-    pub tags: Option<String>,
-    pub search: Option<String>,
+/// Non-GET endpoints that stay available in read-only mode
+pub const READ_ONLY_EXEMPT_PATHS: &[&str] = &["/verify", "/verify/manifest", "/verify/archive", "/verify/dsse", "/keys/compare", "/keys/status/batch", "/admin/read-only", "/admin/reencrypt-scan"];
+
+/// Whether a request may proceed while the service is read-only
+///
+/// Checking a document against a verification link changes nothing, so it stays available too.
+pub fn is_allowed_when_read_only(method: &Method, path: &str) -> bool {
+    let is_share_check = path.strip_prefix("/verifications/")
+        .and_then(|rest| rest.strip_suffix("/check"))
+        .is_some_and(|token| !token.is_empty() && !token.contains('/'));
+    matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS) || READ_ONLY_EXEMPT_PATHS.contains(&path) || is_share_check
 }
 
-/// Generate a new key pair
-pub async fn generate_keys(
-    State(state): State<Arc<AppState>>,
-    Json(request): Json<GenerateKeyRequest>,
-) -> Result<Json<GenerateKeyResponse>, StatusCode> {
-    tracing::info!("DEBUG: generate_keys called with request: {:?}", request);
-    
-    // Validate request
-    if request.name.trim().is_empty() {
-        tracing::warn!("DEBUG: Key name is empty");
-        return Ok(Json(GenerateKeyResponse {
-            success: false,
-            key_pair: None,
-            message: "Key name cannot be empty".to_string(),
-            warnings: vec![],
-        }));
+/// Warning attached to mutations accepted while keystore writes are failing
+pub const PERSISTENCE_DEGRADED_WARNING: &str = "199 inkan \"Change held in memory; keystore persistence is degraded\"";
+
+/// Switches to read-only mode once keystore writes have failed too many times in a row
+fn apply_persistence_policy(state: &AppState) {
+    let Some(limit) = state.config.read_only_after_write_failures else { return };
+    let failures = state.storage.persistence_status().consecutive_failures;
+    if failures >= limit && !state.read_only.swap(true, Ordering::SeqCst) {
+        tracing::error!("Switching to read-only mode after {} consecutive failed keystore writes", failures);
     }
+}
 
-    // Generate the key pair
-    tracing::info!("DEBUG: About to call generate_key_pair");
-    let key_pair = match generate_key_pair(request) {
-        Ok(kp) => {
-            tracing::info!("DEBUG: Key pair generated successfully");
-            kp
-        },
-        Err(e) => {
-            tracing::error!("DEBUG: Key pair generation failed: {:?}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+/// Middleware rejecting mutations with 503 while the service is read-only
+///
+/// Mutations that are let through while keystore persistence is degraded get a `Warning` header,
+/// and handlers' own 503 responses (an operation at capacity) get a `Retry-After` header.
+pub async fn read_only_guard(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    apply_persistence_policy(&state);
+    let allowed = is_allowed_when_read_only(request.method(), request.uri().path());
+    if state.read_only.load(Ordering::SeqCst) && !allowed {
+        return (
+            [(header::RETRY_AFTER, state.config.read_only_retry_after_secs.to_string())],
+            error_response(StatusCode::SERVICE_UNAVAILABLE, ErrorCode::ReadOnly, "Service is in read-only mode"),
+        ).into_response();
+    }
+
+    let mut response = next.run(request).await;
+    if response.status() == StatusCode::SERVICE_UNAVAILABLE && !response.headers().contains_key(header::RETRY_AFTER) {
+        response.headers_mut().insert(header::RETRY_AFTER, state.config.overload_retry_after_secs.into());
+    }
+    if !allowed && state.storage.persistence_status().degraded {
+        response.headers_mut().insert(header::WARNING, header::HeaderValue::from_static(PERSISTENCE_DEGRADED_WARNING));
+    }
+    response
+}
+
+/// Middleware bounding each request by its route's time budget
+///
+/// The handler gets the request's [`Deadline`] as an extension. A request still running when
+/// its budget is spent is dropped and answered `504`, with the `completed` and `total` its loops
+/// last recorded as details, the same as a handler that stops at the deadline itself reports.
+/// Dropping the request, or the client disconnecting, cancels the deadline. Every `504` is
+/// counted against its API version and route for metrics.
+pub async fn deadline_layer(State(state): State<Arc<AppState>>, mut request: Request, next: Next) -> Response {
+    let route = request.extensions().get::<MatchedPath>().map_or("unmatched", MatchedPath::as_str).to_string();
+    let version = request.extensions().get::<RequestedVersion>().map_or(UNVERSIONED_LABEL, |requested| requested.label());
+    let budget = state.deadlines.budget(&route);
+    let deadline = Deadline::after(budget);
+    request.extensions_mut().insert(deadline.clone());
+
+    let mut guard = CancelOnDrop::new(deadline.clone());
+    let response = match tokio::time::timeout(budget, next.run(request)).await {
+        Ok(response) => {
+            guard.disarm();
+            response
+        }
+        Err(_) => {
+            drop(guard);
+            let body = serde_json::json!({
+                "success": false,
+                "code": ErrorCode::DeadlineExceeded,
+                "message": format!("Request did not finish within {} ms", budget.as_millis()),
+                "details": deadline.progress(),
+            });
+            (StatusCode::GATEWAY_TIMEOUT, Json(body)).into_response()
         }
     };
+    if response.status() == StatusCode::GATEWAY_TIMEOUT {
+        state.deadlines.record_timeout(version, &route);
+    }
+    response
+}
 
-    // Store the key pair
-    tracing::info!("DEBUG: About to store key pair");
-    if let Err(e) = state.storage.store_key(key_pair.clone()).await {
-        tracing::error!("DEBUG: Failed to store key pair: {:?}", e);
-        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+/// Middleware answering `404` for everything the deployment profile does not serve
+///
+/// It wraps every other layer, so a route the profile leaves out cannot be told from one that
+/// never existed: no authentication, read-only or breaker response gives it away. Under the
+/// verifier profile that also covers a method the profile leaves out on a path it serves, which
+/// would otherwise be `405`.
+pub async fn profile_layer(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    let profile = state.config.profile;
+    let served = request.extensions().get::<MatchedPath>()
+        .is_some_and(|route| profile.serves(request.method(), route.as_str()));
+    if profile != DeploymentProfile::Full && !served {
+        return StatusCode::NOT_FOUND.into_response();
     }
-    tracing::info!("DEBUG: Key pair stored successfully");
+    next.run(request).await
+}
 
-    let warnings = if key_pair.salt.is_none() {
-        vec!["Private key is not encrypted - not recommended for production".to_string()]
-    } else {
-        vec![]
+/// Middleware failing requests that need the keystore fast while the storage breaker is open
+///
+/// Requests to routes that [`storage_breaker::depends_on_storage`] are refused with
+/// `503 STORAGE_UNAVAILABLE` before the handler runs, with a `Retry-After` of the time left until
+/// the breaker probes the keystore again. Other routes are served as usual; a `503` one of them
+/// answers while the breaker is open, such as `/verify` refusing a lookup by key id, gets the
+/// same `Retry-After`.
+pub async fn storage_breaker_layer(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    let route = request.extensions().get::<MatchedPath>().map_or("unmatched", MatchedPath::as_str).to_string();
+    let breaker = state.storage.breaker();
+    if storage_breaker::depends_on_storage(&route) {
+        if let Err(e) = breaker.admit(&route, state.clock.now()) {
+            return storage_unavailable(&e);
+        }
+        return next.run(request).await;
+    }
+
+    let mut response = next.run(request).await;
+    if response.status() == StatusCode::SERVICE_UNAVAILABLE && !response.headers().contains_key(header::RETRY_AFTER) {
+        if let Some(retry_after) = breaker.retry_after(state.clock.now()) {
+            response.headers_mut().insert(header::RETRY_AFTER, retry_after.into());
+        }
+    }
+    response
+}
+
+/// `503` for a request refused by the storage breaker
+fn storage_unavailable(error: &KeyManagementError) -> Response {
+    let retry_after = match error {
+        KeyManagementError::StorageUnavailable { retry_after_secs } => *retry_after_secs,
+        _ => 1,
     };
+    (
+        [(header::RETRY_AFTER, retry_after.to_string())],
+        key_error_response(StatusCode::SERVICE_UNAVAILABLE, error.to_string(), error),
+    ).into_response()
+}
 
-    tracing::info!("DEBUG: Creating response with key pair");
-    let response = GenerateKeyResponse {
-        success: true,
-        key_pair: Some(key_pair),
-        message: "Key pair generated successfully".to_string(),
-        warnings,
+/// Header carrying the operation event number of a successful mutating request
+pub const EVENT_SEQ_HEADER: &str = "x-event-seq";
+
+/// Middleware numbering every successful mutating request in the operation event log
+///
+/// Requests read-only mode lets through change nothing and are not numbered, apart from
+/// switching read-only mode itself. The number is returned in [`EVENT_SEQ_HEADER`]; a request
+/// whose event cannot be written has still happened, so it succeeds without the header.
+pub async fn event_log_layer(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    let path = request.uri().path().to_string();
+    let mutating = !is_allowed_when_read_only(request.method(), &path) || path == "/admin/read-only";
+    let Some(route) = request.extensions().get::<MatchedPath>().filter(|_| mutating).map(|route| route.as_str().to_string()) else {
+        return next.run(request).await;
     };
-    tracing::info!("DEBUG: Response created successfully: {:?}", response);
-    Ok(Json(response))
+    let operation = format!("{} {}", request.method(), route);
+    let client = request.extensions().get::<AuthenticatedClient>().map(|client| client.0.clone());
+    let key_id = path_key_id(&route, &path);
+
+    let mut response = next.run(request).await;
+    if response.status().is_success() {
+        let event = OperationEvent::request(operation.clone(), key_id, response.status().as_u16(), client, state.clock.now());
+        match state.storage.events().record(event).await {
+            Ok(seq) => {
+                response.headers_mut().insert(header::HeaderName::from_static(EVENT_SEQ_HEADER), seq.into());
+            }
+            Err(e) => tracing::warn!("{} not numbered in the event log: {}", operation, e),
+        }
+    }
+    response
 }
 
-/// List all keys (public information only)
-pub async fn list_keys(
-    State(state): State<Arc<AppState>>,
-    Query(query): Query<ListKeysQuery>,
-) -> Json<ListKeysResponse> {
-    let keys = if let Some(search) = &query.search {
-        state.storage.search_keys(search).await
+/// The `:key_id` segment of a request path, found by the route pattern it matched
+fn path_key_id(route: &str, path: &str) -> Option<Uuid> {
+    route.split('/').zip(path.split('/'))
+        .find(|(pattern, _)| *pattern == ":key_id")
+        .and_then(|(_, segment)| segment.parse().ok())
+}
+
+/// Middleware timing successful generate, sign and verify requests for their latency objectives
+pub async fn slo_layer(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    let operation = request.extensions().get::<MatchedPath>()
+        .and_then(|route| SloOperation::for_route(request.method(), route.as_str()));
+    let Some(operation) = operation else {
+        return next.run(request).await;
+    };
+    let started = std::time::Instant::now();
+    let response = next.run(request).await;
+    if response.status().is_success() {
+        state.slo.record(operation, started.elapsed(), state.clock.now());
+    }
+    response
+}
+
+/// Middleware giving callers without read scope one answer for missing, revoked and expired keys
+///
+/// On the routes that [`key_disclosure::conceals`], a request naming a key the caller may not
+/// learn the state of is refused before the handler runs, so nothing is changed, and a failure
+/// with one of the [`CONCEALED_CODES`] is replaced. Either way the caller gets a plain
+/// `404 KEY_NOT_FOUND` (`200` under the legacy envelope). On the listings that
+/// [`key_disclosure::filters`], the request is marked [`UsableKeysOnly`]. Every answer to such a
+/// caller on these routes, successful or not, is held back until [`CONCEALED_FAILURE_FLOOR`]
+/// after the request arrived.
+pub async fn key_disclosure_layer(State(state): State<Arc<AppState>>, mut request: Request, next: Next) -> Response {
+    let started = tokio::time::Instant::now();
+    let (concealing, filtering) = request.extensions().get::<MatchedPath>()
+        .map_or((false, false), |route| {
+            (key_disclosure::conceals(request.method(), route.as_str()), key_disclosure::filters(request.method(), route.as_str()))
+        });
+    let client = request.extensions().get::<AuthenticatedClient>().map(|client| client.0.as_str());
+    if !(concealing || filtering) || key_disclosure::has_read_scope(&state.config, client) {
+        return next.run(request).await;
+    }
+    let response = if filtering {
+        request.extensions_mut().insert(UsableKeysOnly);
+        next.run(request).await
     } else {
-        state.storage.list_keys().await
+        conceal_key_state(&state, request, next).await
     };
-    
-    let (total, active, expired, _) = state.storage.get_key_stats().await;
-    
-    Json(ListKeysResponse {
-        success: true,
-        keys: keys.clone(),
-        message: format!("Found {} keys", keys.len()),
-        total_count: total,
-        active_count: active,
-        expired_count: expired,
-    })
+    tokio::time::sleep_until(started + CONCEALED_FAILURE_FLOOR).await;
+    response
 }
 
-/// Get public key information
-pub async fn get_public_key(
-    State(state): State<Arc<AppState>>,
-    Path(key_id): Path<Uuid>,
-) -> Result<Json<PublicKeyResponse>, StatusCode> {
-    match state.storage.get_key(key_id).await {
-        Ok(key_pair) => {
-            let key_info = KeyInfo {
-                id: key_pair.id,
-                name: key_pair.name,
-                description: key_pair.description,
-                public_key: key_pair.public_key,
-                created_at: key_pair.created_at,
-                last_used: key_pair.last_used,
-                expires_at: key_pair.expires_at,
-                is_active: key_pair.is_active,
-                tags: key_pair.tags,
-                key_type: key_pair.key_type,
-                key_strength: key_pair.key_strength,
-            };
+/// Runs a concealing route, answering [`concealed_key_failure`] for keys that are not usable
+async fn conceal_key_state(state: &AppState, request: Request, next: Next) -> Response {
+    if let Some(key_id) = key_disclosure::path_key_id(request.uri().path()) {
+        let usable = state.storage.get_key_record(key_id).await
+            .is_ok_and(|key_pair| key_pair.state(state.clock.now()).is_usable());
+        if !usable {
+            return concealed_key_failure(&state.config);
+        }
+    }
 
-            Ok(Json(PublicKeyResponse {
-                success: true,
-                key_info: Some(key_info),
-                message: "Public key retrieved successfully".to_string(),
-            }))
+    let response = next.run(request).await;
+    if !is_json_response(&response) {
+        return response;
+    }
+    let (parts, body) = response.into_parts();
+    let Ok(bytes) = axum::body::to_bytes(body, usize::MAX).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    let code = serde_json::from_slice::<serde_json::Value>(&bytes).ok()
+        .and_then(|body| serde_json::from_value::<ErrorCode>(body.get("code")?.clone()).ok());
+    if code.is_some_and(|code| CONCEALED_CODES.contains(&code)) {
+        return concealed_key_failure(&state.config);
+    }
+    Response::from_parts(parts, axum::body::Body::from(bytes))
+}
+
+/// The one answer for a key the caller may not learn the state of
+fn concealed_key_failure(config: &Config) -> Response {
+    error_response(failure_status(config, ErrorCode::KeyNotFound), ErrorCode::KeyNotFound, CONCEALED_MESSAGE)
+}
+
+/// The request's deadline, or one that never passes for handlers called without [`deadline_layer`]
+#[cfg(not(feature = "verifier-only"))]
+fn request_deadline(deadline: Option<Extension<Deadline>>) -> Deadline {
+    deadline.map_or_else(Deadline::none, |Extension(deadline)| deadline)
+}
+
+/// Largest body a signed request may have, matching the JSON extractor's default limit
+pub const MAX_SIGNED_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+/// Paths that never need a request signature
+///
+/// Health probes and metrics are scraped by infrastructure, and a verification link's token is
+/// its own credential, so opening or checking one stays open too. Public keys served by
+/// fingerprint are fetched by CDNs on behalf of verifiers. `/about` holds nothing sensitive and
+/// is read by deployment tooling.
+pub fn is_public_path(method: &Method, path: &str) -> bool {
+    if matches!(path, "/health" | "/health/ready" | "/metrics" | "/about") {
+        return true;
+    }
+    if let Some(fingerprint) = path.strip_prefix("/public/") {
+        return method == Method::GET && !fingerprint.contains('/');
+    }
+    let Some(rest) = path.strip_prefix("/verifications/") else { return false };
+    match rest.strip_suffix("/check") {
+        Some(token) => method == Method::POST && !token.contains('/'),
+        None => method == Method::GET && !rest.contains('/'),
+    }
+}
+
+/// Paths only admin clients may use once request signing is on: everything under `/admin/`,
+/// usage reports, and event replay
+pub fn is_admin_path(path: &str) -> bool {
+    path.starts_with("/admin/") || matches!(path, "/reports/usage" | "/events/replay")
+}
+
+/// Middleware refusing [admin paths](is_admin_path) to signing clients outside
+/// `INKAN_ADMIN_CLIENTS`
+///
+/// Runs after [`request_auth_guard`] has named the client. With request signing off there are no
+/// clients to tell apart, so every caller may use them.
+pub async fn admin_scope_guard(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    if state.request_auth.is_enabled() && is_admin_path(request.uri().path()) {
+        let client = request.extensions().get::<AuthenticatedClient>().map(|client| client.0.as_str());
+        if !has_admin_scope(&state.config, client) {
+            return error_response(StatusCode::FORBIDDEN, ErrorCode::InsufficientPermissions, "Admin endpoints require an admin client");
         }
-        Err(_) => Ok(Json(PublicKeyResponse {
-            success: false,
-            key_info: None,
-            message: "Key not found".to_string(),
-        })),
     }
+    next.run(request).await
 }
 
-/// Sign a document with a private key
-pub async fn sign_document(
-    State(state): State<Arc<AppState>>,
-    Json(request): Json<SignDocumentRequest>,
-) -> Result<Json<SignDocumentResponse>, StatusCode> {
-    // Get the key pair
-    let key_pair = match state.storage.get_key(request.key_id).await {
-        Ok(kp) => kp,
-        Err(_) => {
-            return Ok(Json(SignDocumentResponse {
-                success: false,
-                signature: None,
-                message: "Key not found or invalid".to_string(),
-                key_id: None,
-                document_hash: None,
-                signing_time: None,
-            }));
+/// Middleware requiring HMAC-signed requests once client secrets are configured
+///
+/// The body is buffered so its signature can be checked, then handed on unchanged.
+pub async fn request_auth_guard(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    if !state.request_auth.is_enabled() || is_public_path(request.method(), request.uri().path()) {
+        return next.run(request).await;
+    }
+    // A delegation token is its own credential, checked when the signature is made
+    let delegated = request.method() == Method::POST
+        && request.uri().path() == "/sign"
+        && request.headers().contains_key(DELEGATION_TOKEN_HEADER);
+    if delegated {
+        return next.run(request).await;
+    }
+
+    let (parts, body) = request.into_parts();
+    let Ok(body) = axum::body::to_bytes(body, MAX_SIGNED_BODY_BYTES).await else {
+        let message = format!("Request body exceeds {} bytes", MAX_SIGNED_BODY_BYTES);
+        return error_response(StatusCode::PAYLOAD_TOO_LARGE, ErrorCode::InvalidRequest, message);
+    };
+    let header = |name: &str| parts.headers.get(name).and_then(|value| value.to_str().ok());
+    let signed = SignedRequest {
+        client_id: header(CLIENT_ID_HEADER),
+        timestamp: header(TIMESTAMP_HEADER),
+        signature: header(SIGNATURE_HEADER),
+    };
+    // Clients sign the path they sent, version prefix included
+    let uri = parts.extensions.get::<OriginalUri>().map_or(&parts.uri, |OriginalUri(uri)| uri);
+    let path = uri.path_and_query().map_or(uri.path(), |path| path.as_str());
+    let client = match state.request_auth.verify(signed, parts.method.as_str(), path, &body, state.clock.now()) {
+        Ok(client_id) => {
+            tracing::debug!("Request {} {} signed by client {}", parts.method, path, client_id);
+            AuthenticatedClient(client_id.to_string())
         }
+        Err(failure) => return error_response(StatusCode::UNAUTHORIZED, failure.code(), failure.to_string()),
     };
+    let mut request = Request::from_parts(parts, axum::body::Body::from(body));
+    request.extensions_mut().insert(client);
+    next.run(request).await
+}
 
-    // Check if key is active
-    if !key_pair.is_active {
-        return Ok(Json(SignDocumentResponse {
-            success: false,
-            signature: None,
-            message: "Key is not active".to_string(),
-            key_id: Some(request.key_id),
-            document_hash: None,
-            signing_time: None,
-        }));
+/// Client that signed a request, added to its extensions by [`request_auth_guard`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthenticatedClient(pub String);
+
+/// Who sent a verification request
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VerifyCaller {
+    /// Peer address of the connection, when the server records it
+    pub ip: Option<std::net::IpAddr>,
+    /// The request passed HMAC request authentication
+    pub authenticated: bool,
+}
+
+#[axum::async_trait]
+impl<S: Send + Sync> axum::extract::FromRequestParts<S> for VerifyCaller {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut axum::http::request::Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self {
+            ip: parts.extensions.get::<axum::extract::ConnectInfo<std::net::SocketAddr>>().map(|info| info.0.ip()),
+            authenticated: parts.extensions.get::<AuthenticatedClient>().is_some(),
+        })
     }
+}
 
-    // Sign the document
-    let signature = if let Some(content) = &request.document_content {
-        // Sign document content directly
-        match sign_document_content(&request, &key_pair.private_key, key_pair.salt.as_deref(), content) {
-            Ok(sig) => sig,
-            Err(_) => {
-                return Ok(Json(SignDocumentResponse {
-                    success: false,
-                    signature: None,
-                    message: "Failed to sign document content".to_string(),
-                    key_id: Some(request.key_id),
-                    document_hash: None,
-                    signing_time: None,
-                }));
-            }
-        }
-    } else if let Some(hash) = &request.document_hash {
-        // Sign document hash
-        let modified_request = SignDocumentRequest {
-            key_id: request.key_id,
-            document_hash: Some(hash.clone()),
-            password: request.password.clone(),
-            document_content: None,
-        };
-        
-        match crate::key_verification::sign_document(&modified_request, &key_pair.private_key, key_pair.salt.as_deref()) {
-            Ok(sig) => sig,
-            Err(_) => {
-                return Ok(Json(SignDocumentResponse {
-                    success: false,
-                    signature: None,
-                    message: "Failed to sign document".to_string(),
-                    key_id: Some(request.key_id),
-                    document_hash: None,
-                    signing_time: None,
-                }));
+/// Middleware rewriting JSON response field names to the casing the client asked for
+///
+/// The `X-Field-Case` header (`snake` or `camel`) wins over the API version's default, which is
+/// `config.field_case` on `v1` and camelCase on `v2`; the casing used is echoed back in the same
+/// header.
+pub async fn field_case_layer(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    let case = match request.headers().get(FIELD_CASE_HEADER) {
+        Some(value) => match value.to_str().ok().and_then(FieldCase::parse) {
+            Some(case) => case,
+            None => {
+                let errors = vec![FieldError::new(FIELD_CASE_HEADER, "must be snake or camel")];
+                return body_rejection(StatusCode::BAD_REQUEST, ErrorCode::InvalidRequest, errors);
             }
-        }
+        },
+        None => request.extensions().get::<RequestedVersion>()
+            .map_or(state.config.field_case, |requested| requested.version.default_field_case(state.config.field_case)),
+    };
+
+    let response = next.run(request).await;
+    let mut response = if case == FieldCase::Camel && response.extensions().get::<PreserveFieldCase>().is_none() {
+        map_json_body(response, |body| apply_field_case(body, case)).await
     } else {
-        return Ok(Json(SignDocumentResponse {
-            success: false,
-            signature: None,
-            message: "Either document_hash or document_content must be provided".to_string(),
-            key_id: Some(request.key_id),
-            document_hash: None,
-            signing_time: None,
-        }));
+        response
     };
+    response.headers_mut().insert(FIELD_CASE_HEADER, header::HeaderValue::from_static(case.as_str()));
+    response
+}
 
-    // Update last used timestamp
-    let _ = state.storage.update_last_used(request.key_id).await;
+/// Middleware translating response messages into the language preferred by `Accept-Language`
+///
+/// Codes stay the same in every language; English leaves responses untouched.
+pub async fn localize_layer(request: Request, next: Next) -> Response {
+    let locale = request.headers().get(header::ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .map(Locale::negotiate)
+        .unwrap_or_default();
 
-    let document_hash = if let Some(content) = &request.document_content {
-        crate::key_verification::create_document_hash(content)
-    } else if let Some(hash) = &request.document_hash {
-        hash.clone()
+    let response = next.run(request).await;
+    let mut response = if locale == Locale::En {
+        response
     } else {
-        return Ok(Json(SignDocumentResponse {
-            success: false,
-            signature: None,
-            message: "Either document_hash or document_content must be provided".to_string(),
-            key_id: Some(request.key_id),
-            document_hash: None,
-            signing_time: None,
-        }));
+        map_json_body(response, |body| localize_body(body, locale)).await
     };
+    response.headers_mut().insert(header::CONTENT_LANGUAGE, header::HeaderValue::from_static(locale.tag()));
+    response.headers_mut().append(header::VARY, header::HeaderValue::from_static("accept-language"));
+    response
+}
 
-    Ok(Json(SignDocumentResponse {
-        success: true,
-        signature: Some(signature),
-        message: "Document signed successfully".to_string(),
-        key_id: Some(request.key_id),
-        document_hash: Some(document_hash.clone()),
-        signing_time: Some(chrono::Utc::now()),
-    }))
+/// Middleware mapping a versioned path onto the one route table, ahead of routing
+///
+/// The version prefix is stripped and the [`RequestedVersion`] added to the request's
+/// extensions; the path as sent stays available as [`OriginalUri`] for request signatures.
+/// Prefixes of versions not in `served` are left in place, so their paths are not found.
+pub async fn route_api_version(State(served): State<Arc<[ApiVersion]>>, mut request: Request, next: Next) -> Response {
+    let original = request.uri().clone();
+    let requested = match split_version(original.path(), &served) {
+        Some((version, path)) => {
+            let path_and_query = match original.query() {
+                Some(query) => format!("{}?{}", path, query),
+                None => path.to_string(),
+            };
+            let mut parts = original.clone().into_parts();
+            parts.path_and_query = path_and_query.parse::<PathAndQuery>().ok();
+            if let Ok(uri) = Uri::from_parts(parts) {
+                *request.uri_mut() = uri;
+            }
+            Some(RequestedVersion { version, deprecated_alias: false })
+        }
+        None if is_unversioned_path(original.path()) => None,
+        None => Some(RequestedVersion { version: ApiVersion::UNVERSIONED_ALIAS, deprecated_alias: true }),
+    };
+
+    if let Some(requested) = requested {
+        request.extensions_mut().insert(requested);
+    }
+    if request.extensions().get::<OriginalUri>().is_none() {
+        request.extensions_mut().insert(OriginalUri(original));
+    }
+    next.run(request).await
 }
 
-/// Verify a document signature
-pub async fn verify_signature(
-    Json(request): Json<VerifySignatureRequest>,
-) -> Json<VerifySignatureResponse> {
-    // Handle document content if provided
-    let document_hash = if let Some(content) = &request.document_content {
-        crate::key_verification::create_document_hash(content)
-    } else if let Some(hash) = &request.document_hash {
-        hash.clone()
-    } else {
-        return Json(VerifySignatureResponse {
-            success: false,
-            is_valid: false,
-            message: "Either document_hash or document_content must be provided".to_string(),
-            key_info: None,
-            verification_time: Some(chrono::Utc::now()),
-            document_hash: None,
-        });
+/// Middleware shaping responses to the API version the request addressed
+///
+/// On versions whose failures carry their status, a `200` envelope with `"success": false` gets
+/// its error code's status. Responses to the deprecated unprefixed paths get `Deprecation`,
+/// `Sunset` and a `Link` to the `/v1` path, and a `deprecation` object in their JSON envelope.
+pub async fn api_version_layer(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    let Some(requested) = request.extensions().get::<RequestedVersion>().copied() else {
+        return next.run(request).await;
     };
+    state.api_usage.record(requested);
+    let successor = requested.deprecated_alias.then(|| {
+        format!("{}{}", requested.version.prefix(), request.uri().path_and_query().map_or("/", PathAndQuery::as_str))
+    });
 
-    // Create modified request with the hash
-    let modified_request = VerifySignatureRequest {
-        document_hash: Some(document_hash.clone()),
-        public_key: request.public_key,
-        signature: request.signature,
-        document_content: None,
+    let mut response = next.run(request).await;
+    if requested.version.failures_carry_status() && response.status() == StatusCode::OK {
+        response = failure_status_from_body(response).await;
+    }
+    let Some(successor) = successor else {
+        return response;
     };
 
-    // Verify the signature
-    let is_valid = match crate::key_verification::verify_signature(&modified_request) {
-        Ok(valid) => valid,
-        Err(_) => false,
+    let sunset = state.config.unversioned_sunset;
+    let deprecation = serde_json::json!({
+        "code": WarningCode::DeprecatedPath,
+        "message": format!("Paths without a version prefix are deprecated; use {}", successor),
+        "successor": successor,
+        "sunset": sunset,
+    });
+    let mut response = map_json_body(response, |mut body| {
+        if let Some(body) = body.as_object_mut() {
+            body.insert("deprecation".to_string(), deprecation);
+        }
+        body
+    }).await;
+    let headers = response.headers_mut();
+    headers.insert("deprecation", header::HeaderValue::from_str(&format!("@{}", UNVERSIONED_DEPRECATED_AT)).expect("digits are a valid header value"));
+    if let Ok(value) = header::HeaderValue::from_str(&sunset_header(sunset)) {
+        headers.insert("sunset", value);
+    }
+    if let Ok(value) = header::HeaderValue::from_str(&format!("<{}>; rel=\"successor-version\"", successor)) {
+        headers.insert(header::LINK, value);
+    }
+    response
+}
+
+/// Gives a `200` failure envelope the status of its error code
+async fn failure_status_from_body(response: Response) -> Response {
+    if !is_json_response(&response) {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    let Ok(bytes) = axum::body::to_bytes(body, usize::MAX).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
     };
+    if let Ok(body) = serde_json::from_slice::<serde_json::Value>(&bytes) {
+        let code = body.get("code").and_then(|code| serde_json::from_value::<ErrorCode>(code.clone()).ok());
+        if let (Some(false), Some(code)) = (body.get("success").and_then(serde_json::Value::as_bool), code) {
+            parts.status = StatusCode::from_u16(code.status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+    Response::from_parts(parts, axum::body::Body::from(bytes))
+}
 
-    let message = if is_valid {
-        "Signature is valid".to_string()
-    } else {
-        "Signature is invalid".to_string()
+fn is_json_response(response: &Response) -> bool {
+    response.headers().get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"))
+}
+
+/// Rewrites a JSON response body; other responses pass through unchanged
+async fn map_json_body(response: Response, map: impl FnOnce(serde_json::Value) -> serde_json::Value) -> Response {
+    if !is_json_response(&response) {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let Ok(bytes) = axum::body::to_bytes(body, usize::MAX).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    let body = match serde_json::from_slice(&bytes) {
+        Ok(value) => serde_json::to_vec(&map(value)).unwrap_or_else(|_| bytes.to_vec()),
+        Err(_) => bytes.to_vec(),
     };
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, axum::body::Body::from(body))
+}
 
-    Json(VerifySignatureResponse {
-        success: true,
-        is_valid,
-        message,
-        key_info: None, // We don't have key info in this context
-        verification_time: Some(chrono::Utc::now()),
-        document_hash: Some(document_hash),
-    })
+/// Layer compressing responses with gzip or brotli for clients that accept it
+///
+/// Bodies smaller than `config.compression_min_bytes` are not worth the overhead. Streamed
+/// responses (validation progress, SSE) are left alone so each chunk still reaches the client
+/// as it is produced, and export archives are compressed already.
+pub fn compression_layer(config: &Config) -> CompressionLayer<impl Predicate> {
+    CompressionLayer::new().compress_when(
+        SizeAbove::new(config.compression_min_bytes)
+            .and(NotForContentType::IMAGES)
+            .and(NotForContentType::SSE)
+            .and(NotForContentType::const_new("application/x-ndjson"))
+            .and(NotForContentType::const_new("application/zip"))
+            .and(NotForContentType::const_new("application/gzip")),
+    )
 }
 
-/// Update key information
-pub async fn update_key(
-    State(state): State<Arc<AppState>>,
-    Path(key_id): Path<Uuid>,
-    Json(request): Json<UpdateKeyRequest>,
-) -> Result<Json<UpdateKeyResponse>, StatusCode> {
-    match state.storage.update_key(key_id, request).await {
-        Ok(key_pair) => {
-            let key_info = KeyInfo {
-                id: key_pair.id,
-                name: key_pair.name,
-                description: key_pair.description,
-                public_key: key_pair.public_key,
-                created_at: key_pair.created_at,
-                last_used: key_pair.last_used,
-                expires_at: key_pair.expires_at,
-                is_active: key_pair.is_active,
-                tags: key_pair.tags,
-                key_type: key_pair.key_type,
-                key_strength: key_pair.key_strength,
-            };
+/// JSON body extractor that names every unknown field when it rejects a request
+///
+/// Request types deny unknown fields so misspelt fields fail instead of being silently
+/// ignored; serde only reports the first, so this keeps removing and collecting them. An
+/// empty body reads as `{}`.
+pub struct StrictJson<T>(pub T);
 
-            Ok(Json(UpdateKeyResponse {
-                success: true,
-                key_info: Some(key_info),
-                message: "Key updated successfully".to_string(),
-            }))
-        }
-        Err(_) => Err(StatusCode::NOT_FOUND),
+#[axum::async_trait]
+impl<T: DeserializeOwned, S: Send + Sync> FromRequest<S> for StrictJson<T> {
+    type Rejection = Response;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let bytes = Bytes::from_request(request, state).await.map_err(IntoResponse::into_response)?;
+        let value = if bytes.iter().all(u8::is_ascii_whitespace) {
+            serde_json::Value::Object(Default::default())
+        } else {
+            serde_json::from_slice(&bytes).map_err(|e| {
+                let errors = vec![FieldError::new("body", format!("Invalid JSON: {}", e))];
+                body_rejection(StatusCode::BAD_REQUEST, ErrorCode::InvalidJson, errors)
+            })?
+        };
+        from_value_strict(value)
+            .map(StrictJson)
+            .map_err(|errors| {
+                let code = if errors.iter().all(|error| error.message == "unknown field") {
+                    ErrorCode::UnknownField
+                } else {
+                    ErrorCode::ValidationFailed
+                };
+                body_rejection(StatusCode::UNPROCESSABLE_ENTITY, code, errors)
+            })
     }
 }
 
-/// Revoke a key
-pub async fn revoke_key(
-    State(state): State<Arc<AppState>>,
-    Path(key_id): Path<Uuid>,
-    Json(request): Json<RevokeKeyRequest>,
-) -> Result<Json<RevokeKeyResponse>, StatusCode> {
-    match state.storage.revoke_key(key_id, request.reason).await {
-        Ok(()) => {
-            // Get the updated key info
-            match state.storage.get_key(key_id).await {
-                Ok(key_pair) => {
-                    let key_info = KeyInfo {
-                        id: key_pair.id,
-                        name: key_pair.name,
-                        description: key_pair.description,
-                        public_key: key_pair.public_key,
-                        created_at: key_pair.created_at,
-                        last_used: key_pair.last_used,
-                        expires_at: key_pair.expires_at,
-                        is_active: key_pair.is_active,
-                        tags: key_pair.tags,
-                        key_type: key_pair.key_type,
-                        key_strength: key_pair.key_strength,
-                    };
-
-                    Ok(Json(RevokeKeyResponse {
-                        success: true,
-                        key_info: Some(key_info),
-                        message: "Key revoked successfully".to_string(),
-                        revocation_time: Some(chrono::Utc::now()),
-                    }))
-                }
-                Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
-            }
-        }
-        Err(_) => Err(StatusCode::NOT_FOUND),
-    }
+fn body_rejection(status: StatusCode, code: ErrorCode, errors: Vec<FieldError>) -> Response {
+    let message = errors.iter()
+        .map(|error| format!("{}: {}", error.field, error.message))
+        .collect::<Vec<_>>()
+        .join("; ");
+    let body = serde_json::json!({
+        "success": false,
+        "code": code,
+        "message": message,
+        "details": field_error_details(&errors),
+        "errors": errors,
+    });
+    (status, Json(body)).into_response()
 }
 
-/// Get key statistics
-pub async fn get_key_stats(
-    State(state): State<Arc<AppState>>,
-) -> Json<KeyStatsResponse> {
-    let (total, active, expired, revoked) = state.storage.get_key_stats().await;
-    let expiring_soon = state.storage.get_keys_expiring_soon(30).await.len();
+/// Details attached to failures that list field errors
+fn field_error_details(errors: &[FieldError]) -> Option<serde_json::Value> {
+    (!errors.is_empty()).then(|| serde_json::json!({ "count": errors.len() }))
+}
 
-    Json(KeyStatsResponse {
-        success: true,
-        total_keys: total,
-        active_keys: active,
-        expired_keys: expired,
-        revoked_keys: revoked,
-        keys_expiring_soon: expiring_soon,
-        message: format!("Retrieved statistics for {} keys", total),
-    })
+/// Builds a JSON error response for endpoints without a typed response body
+pub fn error_response(status: StatusCode, code: ErrorCode, message: impl Into<String>) -> Response {
+    let body = serde_json::json!({ "success": false, "code": code, "message": message.into() });
+    (status, Json(body)).into_response()
 }
 
-/// Search keys
-pub async fn search_keys(
-    State(state): State<Arc<AppState>>,
-    Query(query): Query<ListKeysQuery>,
-) -> Json<ListKeysResponse> {
-    let keys = if let Some(search) = &query.search {
-        state.storage.search_keys(search).await
+/// Status for a signing or verification failure: the error code's, or `200` under the legacy envelope
+fn failure_status(config: &Config, code: ErrorCode) -> StatusCode {
+    if config.legacy_envelope {
+        StatusCode::OK
     } else {
-        state.storage.list_keys().await
-    };
-    
-    let (total, active, expired, _) = state.storage.get_key_stats().await;
-    
-    Json(ListKeysResponse {
-        success: true,
-        keys: keys.clone(),
-        message: format!("Found {} matching keys", keys.len()),
-        total_count: total,
-        active_count: active,
-        expired_count: expired,
-    })
+        StatusCode::from_u16(code.status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
+
+/// Builds a JSON error response for a failed key lookup, carrying the error's code and details
+fn key_error_response(status: StatusCode, message: impl Into<String>, error: &KeyManagementError) -> Response {
+    let body = serde_json::json!({
+        "success": false,
+        "code": error.code(),
+        "message": message.into(),
+        "details": error.details(),
+    });
+    (status, Json(body)).into_response()
+}
+
+/// Describe what this build and configuration of the service support
+pub async fn capabilities(State(state): State<Arc<AppState>>) -> Json<CapabilitiesResponse> {
+    let capabilities = ServiceCapabilities::new(
+        &state.config,
+        state.hsm.is_some(),
+        state.request_auth.is_enabled(),
+        state.read_only.load(Ordering::SeqCst),
+    );
+    Json(CapabilitiesResponse { success: true, capabilities })
+}
+
+/// Describe the build, how it stores keys and authenticates requests, and every endpoint its
+/// deployment profile serves
+pub async fn about(State(state): State<Arc<AppState>>) -> Json<AboutResponse> {
+    let about = About::new(
+        &crate::routes::served_versions(&state.config),
+        state.hsm.is_some(),
+        state.request_auth.is_enabled(),
+        state.config.profile,
+        crate::routes::served_endpoints(state.config.profile),
+    );
+    Json(AboutResponse { success: true, about })
+}
+
+/// Describe the running build, as recorded with each signature
+pub async fn version() -> Json<VersionResponse> {
+    Json(VersionResponse { success: true, build: SignerInfo::current() })
+}
+
+/// List the key templates `/keys/generate` accepts
+#[cfg(not(feature = "verifier-only"))]
+pub async fn list_templates(State(state): State<Arc<AppState>>) -> Json<TemplatesResponse> {
+    let templates = state.config.key_templates.clone();
+    Json(TemplatesResponse { success: true, total_count: templates.len(), templates })
+}
+
+/// List every error code the API may return
+pub async fn error_codes() -> Json<ErrorCatalogResponse> {
+    Json(ErrorCatalogResponse { success: true, errors: error_catalog(), warnings: warning_catalog() })
+}
+
+/// Advisories about a stored key at `now`: revoked or expired, or expiring within the threshold
+fn key_warnings(config: &Config, key_pair: &KeyPair, now: chrono::DateTime<chrono::Utc>) -> Vec<ApiWarning> {
+    let state = key_pair.state(now);
+    if !state.is_usable() {
+        let state = if state == KeyState::Revoked { "revoked" } else { "expired" };
+        return vec![ApiWarning::new(WarningCode::KeyInactive, format!("Key {} is {}", key_pair.id, state))];
+    }
+    let threshold = chrono::Duration::days(config.expiry_warning_days.into());
+    match key_pair.expires_at {
+        Some(expires_at) if config.expiry_warning_days > 0 && expires_at - now <= threshold => vec![ApiWarning::new(
+            WarningCode::KeyExpiringSoon,
+            format!("Key {} expires at {}", key_pair.id, expires_at.to_rfc3339()),
+        )],
+        _ => vec![],
+    }
+}
+
+/// Warning for a change accepted while keystore writes are failing
+#[cfg(not(feature = "verifier-only"))]
+fn persistence_warning(state: &AppState) -> Option<ApiWarning> {
+    state.storage.persistence_status().degraded.then(|| ApiWarning::new(
+        WarningCode::PersistenceDegraded,
+        "Change held in memory; keystore persistence is degraded",
+    ))
+}
+
+/// Advisories for a signature made with `key_pair`
+#[cfg(not(feature = "verifier-only"))]
+fn sign_warnings(state: &AppState, key_pair: &KeyPair, valid_until: Option<chrono::DateTime<chrono::Utc>>) -> Vec<ApiWarning> {
+    let mut warnings = key_warnings(&state.config, key_pair, state.clock.now());
+    if key_pair.key_type == KeyType::Ed25519 && key_pair.hsm.is_none() {
+        warnings.push(ApiWarning::new(WarningCode::UnencryptedPrivateKey, format!("Key {} is stored unencrypted", key_pair.id)));
+    }
+    if let (Some(valid_until), Some(expires_at)) = (valid_until, key_pair.expires_at) {
+        if valid_until > expires_at {
+            let message = format!("valid_until is after the key expires at {}", expires_at.to_rfc3339());
+            warnings.push(ApiWarning::new(WarningCode::ValidityOutlastsKey, message).for_field("valid_until"));
+        }
+    }
+    if let Ok(Some(mismatch)) = environment::check_signing(&state.config, &key_pair.environment) {
+        tracing::warn!("Key {} signed outside its environment: {}", key_pair.id, mismatch);
+        warnings.push(ApiWarning::new(WarningCode::EnvironmentMismatch, mismatch));
+    }
+    warnings.extend(persistence_warning(state));
+    warnings
+}
+
+/// Deserializes a request body, reporting every unknown top-level field rather than the first
+pub fn from_value_strict<T: DeserializeOwned>(mut value: serde_json::Value) -> Result<T, Vec<FieldError>> {
+    let mut unknown = Vec::new();
+    loop {
+        let error = match serde_json::from_value::<T>(value.clone()) {
+            Ok(parsed) if unknown.is_empty() => return Ok(parsed),
+            Ok(_) => return Err(unknown),
+            Err(e) => e,
+        };
+        let field = error.to_string()
+            .strip_prefix("unknown field `")
+            .and_then(|rest| rest.split('`').next())
+            .map(str::to_string);
+        let removed = match (&field, value.as_object_mut()) {
+            (Some(field), Some(object)) => object.remove(field).is_some(),
+            _ => false,
+        };
+        match field {
+            Some(field) if removed => unknown.push(FieldError::new(&field, "unknown field")),
+            _ => {
+                unknown.push(FieldError::new("body", error.to_string()));
+                return Err(unknown);
+            }
+        }
+    }
+}
+
+/// Query parameters for listing keys
+#[derive(Debug, Default, Deserialize)]
+pub struct ListKeysQuery {
+    #[serde(alias = "activeOnly")]
+    pub active_only: Option<bool>,
+    #[serde(alias = "keyType")]
+    pub key_type: Option<String>,
+    pub tags: Option<String>,
+    pub search: Option<String>,
+    pub environment: Option<String>,
+    #[serde(default)]
+    pub offset: usize, // Matching keys to skip, in creation order
+    pub limit: Option<usize>, // Most keys to return
+    pub cursor: Option<String>, // `next_cursor` of the previous page; replaces `offset`
+}
+
+impl ListKeysQuery {
+    /// The storage filter these parameters select
+    ///
+    /// `key_type` is matched case-insensitively; `unknown` selects keys of types this version
+    /// does not know, and a name that is no key type at all is rejected.
+    pub fn filter(&self) -> Result<KeyFilter, KeyManagementError> {
+        let key_type = match self.key_type.as_deref() {
+            None => None,
+            Some(name) => Some(KeyType::from_name(name).ok_or_else(|| {
+                let known: Vec<&str> = KeyType::ALL.iter().map(KeyType::as_str).collect();
+                KeyManagementError::InvalidRequest(format!("Unknown key_type '{}'; expected one of {}", name, known.join(", ")))
+            })?),
+        };
+        Ok(KeyFilter {
+            active_only: self.active_only,
+            key_type,
+            tags: self.tags.as_ref().map(|tags| {
+                tags.split(',').map(str::trim).filter(|tag| !tag.is_empty()).map(str::to_string).collect()
+            }),
+            search: self.search.clone(),
+            environment: self.environment.as_deref().map(str::trim).map(str::to_string),
+            expiring_before: None,
+        })
+    }
+
+    /// The page these parameters select: after `cursor` when one is given, else at `offset`
+    /// The requested page; with `usable_only`, keys that are not usable are left out as if they
+    /// did not exist
+    async fn page(&self, storage: &KeyStorage, usable_only: bool) -> Result<KeyPage, Response> {
+        let invalid = |e: KeyManagementError| error_response(StatusCode::BAD_REQUEST, e.code(), e.to_string());
+        let mut filter = self.filter().map_err(invalid)?;
+        if usable_only {
+            if filter.active_only == Some(false) {
+                return Ok(KeyPage { keys: Vec::new(), matched: 0, next_cursor: None });
+            }
+            filter.active_only = Some(true);
+        }
+        match self.cursor.as_deref() {
+            None => Ok(storage.list_keys_page(&filter, self.offset, self.limit).await),
+            Some(_) if self.offset > 0 => Err(invalid(KeyManagementError::InvalidRequest("Pass either cursor or offset, not both".to_string()))),
+            Some(token) => Ok(storage.list_keys_after(Some(&KeyCursor::decode(token).map_err(invalid)?), self.limit, &filter).await),
+        }
+    }
+}
+
+/// Query parameters for signing
+#[cfg(not(feature = "verifier-only"))]
+#[derive(Debug, Default, Deserialize)]
+pub struct SignQuery {
+    /// Return how long the key took to unlock; honored only for admin clients when
+    /// `INKAN_DEBUG_TIMINGS` is set
+    #[serde(default, alias = "debugTimings")]
+    pub debug_timings: bool,
+}
+
+/// Query parameters of `/sign/raw`: the key and signature options `/sign` takes in its body
+#[cfg(not(feature = "verifier-only"))]
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RawSignQuery {
+    #[serde(alias = "keyId")]
+    pub key_id: Uuid,
+    #[serde(default, alias = "validUntil")]
+    pub valid_until: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default)]
+    pub context: Option<String>,
+    #[serde(default, alias = "bindTimestamp")]
+    pub bind_timestamp: bool,
+    #[serde(default)]
+    pub bundle: bool,
+    #[serde(default)]
+    pub encoding: Option<SignatureEncoding>, // Defaults to the key's default_encoding, then base64
+    #[serde(default, alias = "debugTimings")]
+    pub debug_timings: bool,
+}
+
+/// Query parameters for KDF calibration
+#[cfg(not(feature = "verifier-only"))]
+#[derive(Debug, Deserialize)]
+pub struct KdfCalibrationQuery {
+    #[serde(alias = "targetMs")]
+    pub target_ms: Option<u64>,
+}
+
+/// Query parameters for replaying operation events
+#[cfg(not(feature = "verifier-only"))]
+#[derive(Debug, Deserialize)]
+pub struct EventReplayQuery {
+    /// Events numbered after this one are returned; 0 starts from the first
+    #[serde(default, alias = "afterSeq")]
+    pub after_seq: u64,
+    /// Most events to return, up to 1000
+    pub limit: Option<usize>,
+}
+
+/// Query parameters for usage reports
+#[cfg(not(feature = "verifier-only"))]
+#[derive(Debug, Deserialize)]
+pub struct UsageReportQuery {
+    /// First day counted; defaults to the first day of `to`'s month
+    pub from: Option<chrono::NaiveDate>,
+    /// Last day counted; defaults to today
+    pub to: Option<chrono::NaiveDate>,
+    #[serde(default, alias = "groupBy")]
+    pub group_by: GroupBy,
+}
+
+/// Default derivation time suggested parameters aim for
+#[cfg(not(feature = "verifier-only"))]
+pub const DEFAULT_KDF_TARGET_MS: u64 = 500;
+/// Largest calibration target accepted, to bound the benchmark's cost
+#[cfg(not(feature = "verifier-only"))]
+pub const MAX_KDF_TARGET_MS: u64 = 5_000;
+
+/// Query parameters for public key retrieval
+#[derive(Debug, Deserialize)]
+pub struct PublicKeyQuery {
+    #[serde(default)]
+    pub format: PublicKeyFormat,
+}
+
+/// Query parameters for content-addressed public keys
+#[derive(Debug, Default, Deserialize)]
+pub struct PublicKeyPermalinkQuery {
+    /// Encoding to serve; an extension on the path selects it too
+    pub format: Option<PublicKeyEncoding>,
+}
+
+/// Header reporting the state of a key served by fingerprint
+pub const KEY_STATUS_HEADER: &str = "x-key-status";
+
+/// Request header carrying an encrypted key's password, so it stays out of logged request bodies
+///
+/// The only way to pass a password to `/sign/raw`, whose body is the document.
+pub const KEY_PASSWORD_HEADER: &str = "x-key-password";
+
+/// Request headers whose values are secrets, marked sensitive so they never appear in logs
+pub const SENSITIVE_HEADERS: &[&str] = &[KEY_PASSWORD_HEADER, DELEGATION_TOKEN_HEADER];
+
+/// A key password from [`KEY_PASSWORD_HEADER`], or else from the body's `password` field
+///
+/// The header wins when both are given. A body password, used or ignored, is reported with a
+/// `PASSWORD_IN_BODY` warning.
+#[cfg(not(feature = "verifier-only"))]
+fn request_password(headers: &HeaderMap, body: Option<String>) -> Result<(Option<String>, Option<ApiWarning>), KeyManagementError> {
+    let header = match headers.get(KEY_PASSWORD_HEADER).map(|value| value.to_str()) {
+        Some(Ok(password)) => Some(password.to_string()),
+        Some(Err(_)) => return Err(KeyManagementError::InvalidRequest(format!("{} must be visible ASCII", KEY_PASSWORD_HEADER))),
+        None => None,
+    };
+    let warning = |message: &str| Some(ApiWarning::new(WarningCode::PasswordInBody, message).for_field("password"));
+    Ok(match (header, body) {
+        (Some(header), Some(_)) => (Some(header), warning("password in the body was ignored in favour of the X-Key-Password header; stop sending it")),
+        (Some(header), None) => (Some(header), None),
+        (None, Some(body)) => (Some(body), warning("password in the body is deprecated; send it in the X-Key-Password header")),
+        (None, None) => (None, None),
+    })
+}
+
+/// Puts `warning` first among a response's warnings, whether the request succeeded or not
+#[cfg(not(feature = "verifier-only"))]
+fn with_warning<T, W>(
+    result: Result<Json<T>, (StatusCode, Json<T>)>,
+    warning: Option<W>,
+    warnings: impl Fn(&mut T) -> &mut Vec<W>,
+) -> Result<Json<T>, (StatusCode, Json<T>)> {
+    let Some(warning) = warning else { return result };
+    match result {
+        Ok(Json(mut response)) => {
+            warnings(&mut response).insert(0, warning);
+            Ok(Json(response))
+        }
+        Err((status, Json(mut response))) => {
+            warnings(&mut response).insert(0, warning);
+            Err((status, Json(response)))
+        }
+    }
+}
+
+/// Marks the headers in [`SENSITIVE_HEADERS`] sensitive, so their values print as `Sensitive`
+pub fn mark_sensitive_headers(headers: &mut HeaderMap) {
+    for name in SENSITIVE_HEADERS {
+        if let Some(value) = headers.get_mut(*name) {
+            value.set_sensitive(true);
+        }
+    }
+}
+
+/// Marks secret headers sensitive before any other middleware or handler sees the request
+pub async fn sensitive_headers_layer(mut request: Request, next: Next) -> Response {
+    mark_sensitive_headers(request.headers_mut());
+    next.run(request).await
+}
+
+/// Query parameters for key generation
+#[cfg(not(feature = "verifier-only"))]
+#[derive(Debug, Default, Deserialize)]
+pub struct GenerateKeyQuery {
+    /// Validate the request and report what would be generated without creating a key
+    #[serde(default, alias = "dryRun")]
+    pub dry_run: bool,
+}
+
+/// Generate a new key pair
+#[cfg(not(feature = "verifier-only"))]
+pub async fn generate_keys(
+    state: State<Arc<AppState>>,
+    query: Query<GenerateKeyQuery>,
+    request: Json<GenerateKeyRequest>,
+) -> Result<Json<GenerateKeyResponse>, (StatusCode, Json<GenerateKeyResponse>)> {
+    generate_keys_with_headers(state, query, HeaderMap::new(), request).await
+}
+
+/// Generate a new key pair, taking its password from [`KEY_PASSWORD_HEADER`] when sent
+#[cfg(not(feature = "verifier-only"))]
+pub async fn generate_keys_with_headers(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<GenerateKeyQuery>,
+    headers: HeaderMap,
+    Json(mut request): Json<GenerateKeyRequest>,
+) -> Result<Json<GenerateKeyResponse>, (StatusCode, Json<GenerateKeyResponse>)> {
+    let (password, warning) = match request_password(&headers, request.password.take()) {
+        Ok(resolved) => resolved,
+        Err(e) => return Err((StatusCode::BAD_REQUEST, Json(GenerateKeyResponse {
+            success: false,
+            key_pair: None,
+            message: e.to_string(),
+            code: Some(e.code()),
+            details: None,
+            warnings: vec![],
+            dry_run: query.dry_run,
+            key_type: None,
+            key_strength: None,
+            expires_at: None,
+            expiry_source: None,
+            errors: vec![],
+            generated_password: None,
+        }))),
+    };
+    request.password = password;
+    let result = generate_key_pair_for(state, query, request).await;
+    with_warning(result, warning.map(|warning| warning.message), |response| &mut response.warnings)
+}
+
+/// Generates a key pair for a request whose password has been resolved
+#[cfg(not(feature = "verifier-only"))]
+async fn generate_key_pair_for(
+    state: Arc<AppState>,
+    query: GenerateKeyQuery,
+    mut request: GenerateKeyRequest,
+) -> Result<Json<GenerateKeyResponse>, (StatusCode, Json<GenerateKeyResponse>)> {
+    let failure = |status: StatusCode, code: ErrorCode, message: String, errors: Vec<FieldError>| {
+        (status, Json(GenerateKeyResponse {
+            success: false,
+            key_pair: None,
+            message,
+            code: Some(code),
+            details: field_error_details(&errors),
+            warnings: vec![],
+            dry_run: query.dry_run,
+            key_type: None,
+            key_strength: None,
+            expires_at: None,
+            expiry_source: None,
+            errors,
+            generated_password: None,
+        }))
+    };
+
+    let template = match request.template.as_deref() {
+        Some(name) => match state.config.key_templates.iter().find(|template| template.name == name) {
+            Some(template) => Some(template),
+            None => {
+                let errors = vec![FieldError::new("template", format!("Unknown key template '{}'", name))];
+                let message = format!("template: Unknown key template '{}'", name);
+                return Err(failure(StatusCode::UNPROCESSABLE_ENTITY, ErrorCode::ValidationFailed, message, errors));
+            }
+        },
+        None => None,
+    };
+    let template_warnings = template.map_or_else(Vec::new, |template| template.apply(&mut request, state.clock.now()));
+
+    let existing = state.storage.list_keys().await;
+    let mut validation = match validate_generate_request(&mut request, &existing, &state.config, state.clock.now()) {
+        Ok(validation) => validation,
+        Err(errors) => {
+            let message = errors.iter()
+                .map(|error| format!("{}: {}", error.field, error.message))
+                .collect::<Vec<_>>()
+                .join("; ");
+            return Err(failure(StatusCode::UNPROCESSABLE_ENTITY, ErrorCode::ValidationFailed, message, errors));
+        }
+    };
+    validation.warnings.splice(0..0, template_warnings);
+
+    if query.dry_run {
+        return Ok(Json(GenerateKeyResponse {
+            success: true,
+            key_pair: None,
+            message: "Request is valid; no key was generated".to_string(),
+            code: None,
+            details: None,
+            warnings: validation.warnings,
+            dry_run: true,
+            key_type: Some(validation.key_type),
+            key_strength: Some(validation.key_strength),
+            expires_at: validation.expires_at,
+            expiry_source: validation.expiry_source,
+            errors: vec![],
+            generated_password: None,
+        }));
+    }
+
+    request.expires_at = validation.expires_at;
+
+    // Held until the key is stored, so queued generations wait for the running ones
+    let _permit = state.limits.generation.acquire().await.map_err(|e| {
+        failure(StatusCode::SERVICE_UNAVAILABLE, ErrorCode::Overloaded, e.to_string(), vec![])
+    })?;
+
+    // Archives keys under the eviction policy if one is configured, before refusing at the hard limit
+    if let Err(e) = state.capacity.reserve(&state.storage, state.clock.now()).await {
+        tracing::warn!("Refusing key generation: {}", e);
+        let (code, message) = (e.code(), e.to_string());
+        return Err(failure(StatusCode::from(e), code, message, vec![]));
+    }
+
+    // Lives only in this response; the keystore keeps just the key it encrypted
+    let mut generated_password = None;
+    let key_pair = if let Some(hsm) = request.hsm.clone() {
+        let Some(backend) = &state.hsm else {
+            let errors = vec![FieldError::new("hsm", "No HSM backend is configured")];
+            return Err(failure(StatusCode::UNPROCESSABLE_ENTITY, ErrorCode::ValidationFailed, "hsm: No HSM backend is configured".to_string(), errors));
+        };
+        generate_hsm_key_pair(request, hsm, backend.as_ref(), state.clock.now())
+    } else {
+        // Refused while entropy is degraded; a failed draw degrades it until the next passing check
+        let entropy_failure = |e: KeyManagementError| {
+            failure(StatusCode::INTERNAL_SERVER_ERROR, e.code(), format!("Key generation failed: {}", e), vec![])
+        };
+        if request.generate_password {
+            generated_password = Some(state.entropy.draw_passphrase().map_err(entropy_failure)?);
+            request.password = generated_password.clone();
+        }
+        // A pooled key is finished like any other; an empty pool falls back to a fresh draw
+        let pooled = request.fast.then(|| state.key_pool.claim(&state.entropy, state.clock.now())).flatten();
+        let seed = match pooled {
+            Some(signing_key) => signing_key.to_bytes(),
+            None => state.entropy.draw_seed().map_err(entropy_failure)?,
+        };
+        generate_key_pair_from_seed(request, &state.config.kdf, &seed, state.clock.now())
+    };
+    let mut key_pair = key_pair.map_err(|e| {
+        tracing::error!("Key pair generation failed: {:?}", e);
+        failure(StatusCode::INTERNAL_SERVER_ERROR, e.code(), format!("Key generation failed: {}", e), vec![])
+    })?;
+    if let Some(allowed_contexts) = template.and_then(|template| template.allowed_contexts.clone()) {
+        key_pair.allowed_contexts = Some(allowed_contexts);
+    }
+
+    // Validation saw the names of the keys stored before it ran; the store checks the name again
+    // under its lock, so a generation of the same name that finished meanwhile is a conflict
+    if let Err(e) = state.storage.store_new_key(key_pair.clone()).await {
+        if let KeyManagementError::KeyConflict(_) = e {
+            let errors = vec![FieldError::new("name", format!("An active key named '{}' already exists", key_pair.name))];
+            return Err(failure(StatusCode::CONFLICT, e.code(), format!("Failed to store key: {}", e), errors));
+        }
+        tracing::error!("Failed to store key pair: {:?}", e);
+        return Err(failure(StatusCode::INTERNAL_SERVER_ERROR, e.code(), format!("Failed to store key: {}", e), vec![]));
+    }
+    let mut warnings = validation.warnings;
+    if generated_password.is_some() {
+        warnings.insert(0, "generated_password is shown only in this response and cannot be recovered; store it securely now".to_string());
+    }
+    warnings.extend(state.capacity.status(state.storage.key_count().await).warning());
+
+    Ok(Json(GenerateKeyResponse {
+        success: true,
+        key_type: Some(key_pair.key_type.clone()),
+        key_strength: Some(key_pair.key_strength.clone()),
+        expires_at: key_pair.expires_at,
+        expiry_source: validation.expiry_source,
+        key_pair: Some(KeyInfo::new(key_pair, state.clock.now())),
+        message: "Key pair generated successfully".to_string(),
+        code: None,
+        details: None,
+        warnings,
+        dry_run: false,
+        errors: vec![],
+        generated_password,
+    }))
+}
+
+/// List all keys (public information only)
+///
+/// A caller marked [`UsableKeysOnly`] sees only usable keys, and counts of those alone.
+pub async fn list_keys(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ListKeysQuery>,
+    usable_only: Option<Extension<UsableKeysOnly>>,
+) -> Result<Json<ListKeysResponse>, Response> {
+    let listed = key_listing(&state, &query, usable_only.is_some()).await?;
+    Ok(Json(ListKeysResponse { message: format!("Found {} keys", listed.keys.len()), ..listed }))
+}
+
+/// The page `query` selects, with the keystore's counts
+async fn key_listing(state: &AppState, query: &ListKeysQuery, usable_only: bool) -> Result<ListKeysResponse, Response> {
+    let page = query.page(&state.storage, usable_only).await?;
+    let (total, active, expired, ..) = state.storage.get_key_stats().await;
+    let (total, expired) = if usable_only { (active, 0) } else { (total, expired) };
+
+    Ok(ListKeysResponse {
+        success: true,
+        message: String::new(),
+        keys: page.keys,
+        matched_count: page.matched,
+        next_cursor: page.next_cursor.map(|cursor| cursor.encode()),
+        total_count: total,
+        active_count: active,
+        expired_count: expired,
+    })
+}
+
+/// Get public key information
+pub async fn get_public_key(
+    State(state): State<Arc<AppState>>,
+    Path(key_id): Path<Uuid>,
+) -> Result<Json<PublicKeyResponse>, (StatusCode, Json<PublicKeyResponse>)> {
+    match state.storage.get_key(key_id).await {
+        Ok(key_pair) => {
+            let key_info = KeyInfo::new(key_pair, state.clock.now());
+
+            Ok(Json(PublicKeyResponse {
+                success: true,
+                key_info: Some(key_info),
+                message: "Public key retrieved successfully".to_string(),
+                code: None,
+                details: None,
+            }))
+        }
+        Err(e) => Err((failure_status(&state.config, e.code()), Json(PublicKeyResponse {
+            success: false,
+            key_info: None,
+            message: "Key not found".to_string(),
+            code: Some(e.code()),
+            details: e.details(),
+        }))),
+    }
+}
+
+/// Get a public key in the requested representation
+pub async fn get_public_key_formatted(
+    State(state): State<Arc<AppState>>,
+    Path(key_id): Path<Uuid>,
+    Query(query): Query<PublicKeyQuery>,
+) -> Response {
+    match query.format {
+        PublicKeyFormat::Json => match get_public_key(State(state), Path(key_id)).await {
+            Ok(response) => response.into_response(),
+            Err(failure) => failure.into_response(),
+        },
+        PublicKeyFormat::Minisign => {
+            let public_key = match state.storage.get_key(key_id).await {
+                Ok(key_pair) => decode_public_key(&key_pair.public_key),
+                Err(e) => Err(e),
+            };
+            match public_key {
+                Ok(public_key) => (
+                    [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+                    minisign::encode_public_key(&public_key),
+                ).into_response(),
+                Err(e) => key_error_response(failure_status(&state.config, e.code()), "Key not found", &e),
+            }
+        }
+        PublicKeyFormat::Ssh => {
+            let public_key = match state.storage.get_key(key_id).await {
+                Ok(key_pair) => decode_public_key(&key_pair.public_key)
+                    .map(|public_key| sshsig::encode_public_key(&public_key, &key_pair.name.replace(char::is_whitespace, "_"))),
+                Err(e) => Err(e),
+            };
+            match public_key {
+                Ok(encoded) => (
+                    [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+                    encoded,
+                ).into_response(),
+                Err(e) => key_error_response(failure_status(&state.config, e.code()), "Key not found", &e),
+            }
+        }
+    }
+}
+
+/// Serve a public key at its content-addressed URL, `/public/:fingerprint[.raw|.pem|.jwk]`
+///
+/// A fingerprint always names the same key bytes, so the response is marked immutable. Revoked
+/// and expired keys still resolve; `X-Key-Status` reports the key's state when it was served.
+pub async fn get_public_key_by_fingerprint(
+    State(state): State<Arc<AppState>>,
+    Path(path): Path<String>,
+    Query(query): Query<PublicKeyPermalinkQuery>,
+) -> Response {
+    let error = |status: StatusCode, code: ErrorCode, message: String| {
+        (status, Json(serde_json::json!({ "success": false, "code": code, "message": message }))).into_response()
+    };
+
+    let (fingerprint, extension) = match path.rsplit_once('.') {
+        Some((fingerprint, extension)) => (fingerprint, Some(extension)),
+        None => (path.as_str(), None),
+    };
+    let encoding = match (extension.map(|extension| (extension, PublicKeyEncoding::from_extension(extension))), query.format) {
+        (Some((extension, None)), _) => {
+            return error(StatusCode::NOT_FOUND, ErrorCode::KeyNotFound, format!("Unknown public key extension '.{}'", extension));
+        }
+        (Some((_, Some(from_path))), Some(from_query)) if from_path != from_query => {
+            return error(StatusCode::BAD_REQUEST, ErrorCode::InvalidRequest, "Path extension and format query disagree".to_string());
+        }
+        (Some((_, Some(encoding))), _) | (None, Some(encoding)) => encoding,
+        (None, None) => PublicKeyEncoding::default(),
+    };
+    let Some(fingerprint) = compact_fingerprint(fingerprint) else {
+        return error(StatusCode::BAD_REQUEST, ErrorCode::InvalidRequest, "Fingerprint must be 32 hex digits, optionally colon-separated".to_string());
+    };
+    let Some(key_pair) = state.storage.find_by_fingerprint(&fingerprint).await else {
+        return error(StatusCode::NOT_FOUND, ErrorCode::KeyNotFound, "No key has this fingerprint".to_string());
+    };
+    let public_key = match decode_public_key(&key_pair.public_key) {
+        Ok(public_key) => public_key,
+        Err(e) => return key_error_response(StatusCode::INTERNAL_SERVER_ERROR, "Stored public key is unreadable", &e),
+    };
+
+    let body = match encoding {
+        PublicKeyEncoding::Raw => public_key.as_bytes().to_vec(),
+        PublicKeyEncoding::Pem => encode_pem(&public_key).into_bytes(),
+        PublicKeyEncoding::Jwk => encode_jwk(&public_key, key_pair.id).to_string().into_bytes(),
+    };
+    let cache_control = format!("public, max-age={}, immutable", state.config.public_key_max_age_secs);
+    (
+        [
+            (header::CONTENT_TYPE, encoding.content_type().to_string()),
+            (header::CACHE_CONTROL, cache_control),
+            (header::HeaderName::from_static(KEY_STATUS_HEADER), key_pair.state(state.clock.now()).as_str().to_string()),
+        ],
+        body,
+    ).into_response()
+}
+
+/// Redirect to the content-addressed URL of a key's public key
+///
+/// The redirect is temporary: the key id is mutable state of this keystore, while the URL it
+/// points to is not.
+pub async fn get_public_key_permalink(
+    State(state): State<Arc<AppState>>,
+    Path(key_id): Path<Uuid>,
+    Query(query): Query<PublicKeyPermalinkQuery>,
+) -> Response {
+    let key_pair = match state.storage.get_key_record(key_id).await {
+        Ok(key_pair) => key_pair,
+        Err(e) => return key_error_response(StatusCode::NOT_FOUND, "Key not found", &e),
+    };
+    let Some(fingerprint) = public_key_to_fingerprint(&key_pair.public_key).ok().and_then(|fingerprint| compact_fingerprint(&fingerprint)) else {
+        let e = KeyManagementError::InvalidKeyFormat("stored public key is not valid base64".to_string());
+        return key_error_response(StatusCode::INTERNAL_SERVER_ERROR, "Stored public key is unreadable", &e);
+    };
+    let extension = query.format.map_or(String::new(), |encoding| format!(".{}", encoding.extension()));
+    Redirect::temporary(&format!("/public/{}{}", fingerprint, extension)).into_response()
+}
+
+/// Output format, digest and encoding a signature is made in
+#[cfg(not(feature = "verifier-only"))]
+struct SignatureEnvelope {
+    output_format: SignatureOutputFormat,
+    hash_algorithm: HashAlgorithm,
+    encoding: SignatureEncoding,
+}
+
+/// Fills in what a signing request leaves out from the key's signature defaults
+///
+/// Whatever the request names wins. A default only applies where it fits: the key's default
+/// encoding to raw signatures, and its default digest to formats that sign with it; otherwise
+/// the format's own digest and base64 are used.
+#[cfg(not(feature = "verifier-only"))]
+fn resolve_envelope(request: &SignDocumentRequest, key_pair: &KeyPair) -> Result<SignatureEnvelope, String> {
+    let output_format = request.output_format.or(key_pair.default_output_format).unwrap_or_default();
+    let digests = HashAlgorithm::for_format(output_format);
+    let hash_algorithm = match request.hash_algorithm {
+        Some(hash_algorithm) if !digests.contains(&hash_algorithm) => {
+            return Err(format!("{} output does not sign with {}", output_format.as_str(), hash_algorithm.as_str()));
+        }
+        Some(hash_algorithm) => hash_algorithm,
+        None => key_pair.default_hash_algorithm.filter(|hash_algorithm| digests.contains(hash_algorithm)).unwrap_or(digests[0]),
+    };
+    let encoding = match request.encoding {
+        Some(encoding) => encoding,
+        None if output_format == SignatureOutputFormat::Raw => key_pair.default_encoding.unwrap_or_default(),
+        None => SignatureEncoding::Base64,
+    };
+    Ok(SignatureEnvelope { output_format, hash_algorithm, encoding })
+}
+
+/// Resolves the sshsig namespace, defaulting to `file` like `ssh-keygen -Y sign`
+fn sshsig_namespace(namespace: Option<&str>) -> Result<&str, String> {
+    match namespace {
+        None => Ok(sshsig::DEFAULT_NAMESPACE),
+        Some("") => Err("sshsig namespace must not be empty".to_string()),
+        Some(namespace) => Ok(namespace),
+    }
+}
+
+/// Rewrites a legacy encrypted key as a self-describing envelope after it decrypted successfully
+#[cfg(not(feature = "verifier-only"))]
+async fn upgrade_legacy_key(state: &AppState, key_pair: &KeyPair, password: Option<&str>) {
+    let Some(password) = password.filter(|_| key_pair.hsm.is_none()) else { return };
+    match upgrade_legacy_private_key(key_pair, password) {
+        Ok(Some(envelope)) => {
+            if let Err(e) = state.storage.replace_private_key(key_pair.id, envelope).await {
+                tracing::warn!("Failed to store upgraded key envelope for {}: {}", key_pair.id, e);
+            }
+        }
+        Ok(None) => {}
+        Err(e) => tracing::warn!("Failed to upgrade legacy key {}: {}", key_pair.id, e),
+    }
+}
+
+/// Builds a failed signing response
+#[cfg(not(feature = "verifier-only"))]
+fn sign_failure(code: ErrorCode, message: impl Into<String>, key_id: Option<Uuid>) -> SignDocumentResponse {
+    SignDocumentResponse {
+        success: false,
+        signature: None,
+        message: message.into(),
+        code: Some(code),
+        details: key_id.map(|key_id| serde_json::json!({ "key_id": key_id })),
+        key_id,
+        document_hash: None,
+        signing_time: None,
+        valid_until: None,
+        canonical_hash: None,
+        output_format: SignatureOutputFormat::Raw,
+        signature_encoding: SignatureEncoding::Base64,
+        message_encoding: MessageEncoding::HashRawBytes,
+        signature_id: None,
+        bundle: None,
+        context: None,
+        duplicate: false,
+        timestamp_bound: false,
+        warnings: vec![],
+        timings: None,
+        signer_info: None,
+    }
+}
+
+/// Records a key unlock in the KDF histograms and, when timings were requested, in the response
+#[cfg(not(feature = "verifier-only"))]
+fn record_kdf_timing(state: &AppState, timing: Option<&KdfTiming>, started: Option<std::time::Instant>) -> Option<SignTimings> {
+    if let Some(timing) = timing {
+        state.kdf_timings.record(timing);
+    }
+    let millis = |elapsed: std::time::Duration| elapsed.as_secs_f64() * 1000.0;
+    started.map(|started| SignTimings {
+        kdf: timing.map(|timing| timing.kdf.algorithm),
+        kdf_derivation_ms: timing.map(|timing| millis(timing.derivation)),
+        key_decryption_ms: timing.map(|timing| millis(timing.decryption)),
+        total_ms: millis(started.elapsed()),
+    })
+}
+
+/// Sign a document with a private key
+#[cfg(not(feature = "verifier-only"))]
+pub async fn sign_document(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<SignDocumentRequest>,
+) -> Result<Json<SignDocumentResponse>, (StatusCode, Json<SignDocumentResponse>)> {
+    sign(state, request, None, None).await
+}
+
+/// Sign a document, including request timings in the response when an admin client asks for them
+/// and they are enabled
+///
+/// The key's password may come from [`KEY_PASSWORD_HEADER`] instead of the body. A request
+/// carrying a [`DELEGATION_TOKEN_HEADER`] signs under that delegation instead of a password;
+/// the signing policy then sees the delegation's issuer unless the request was also signed by
+/// a client.
+#[cfg(not(feature = "verifier-only"))]
+pub async fn sign_document_with_query(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<SignQuery>,
+    headers: HeaderMap,
+    client: Option<Extension<AuthenticatedClient>>,
+    Json(mut request): Json<SignDocumentRequest>,
+) -> Result<Json<SignDocumentResponse>, (StatusCode, Json<SignDocumentResponse>)> {
+    let client = client.map(|Extension(client)| client.0);
+    let started = debug_timer(&state.config, query.debug_timings, client.as_deref());
+    let (password, warning) = request_password(&headers, request.password.take())
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(sign_failure(e.code(), e.to_string(), Some(request.key_id)))))?;
+    request.password = password;
+    let mut requester = client;
+    if let Some(token) = headers.get(DELEGATION_TOKEN_HEADER) {
+        let delegation = redeem_delegation(&state, token, &mut request).await?;
+        requester = requester.or(delegation.issued_by);
+    }
+    with_warning(sign(state, request, started, requester).await, warning, |response| &mut response.warnings)
+}
+
+/// Sign a document sent as the raw request body, hashing it as it arrives
+///
+/// The key and signature options come from the query string, and an encrypted key's password
+/// from [`KEY_PASSWORD_HEADER`]. The signature is the one `/sign` makes for the same bytes sent
+/// as `document_content`, but the document is never held in memory whole, so it may be as large
+/// as `INKAN_RAW_SIGN_MAX_CONTENT_BYTES`.
+#[cfg(not(feature = "verifier-only"))]
+pub async fn sign_raw(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<RawSignQuery>,
+    headers: HeaderMap,
+    client: Option<Extension<AuthenticatedClient>>,
+    body: axum::body::Body,
+) -> Result<Json<SignDocumentResponse>, (StatusCode, Json<SignDocumentResponse>)> {
+    let fail = |e: KeyManagementError| (failure_status(&state.config, e.code()), Json(SignDocumentResponse {
+        details: e.details(),
+        ..sign_failure(e.code(), e.to_string(), Some(query.key_id))
+    }));
+
+    // Nothing is read for a key that cannot sign, or a body declared larger than the limit
+    if let Err(e) = state.storage.get_key(query.key_id).await {
+        return Err((failure_status(&state.config, e.code()), Json(SignDocumentResponse {
+            details: e.details(),
+            ..sign_failure(e.code(), "Key not found or invalid", None)
+        })));
+    }
+    let limit = u64::from(state.config.raw_sign_max_content_bytes);
+    let declared = headers.get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+    if declared.is_some_and(|length| length > limit) {
+        return Err(fail(KeyManagementError::ContentTooLarge { field: "document", limit }));
+    }
+    let (password, _) = request_password(&headers, None).map_err(fail)?;
+
+    let document_hash = hash_body(body, limit).await.map_err(fail)?;
+    let request = SignDocumentRequest {
+        key_id: query.key_id,
+        document_hash: Some(document_hash),
+        password,
+        valid_until: query.valid_until,
+        context: query.context,
+        bind_timestamp: query.bind_timestamp,
+        bundle: query.bundle,
+        output_format: Some(SignatureOutputFormat::Raw),
+        encoding: query.encoding,
+        ..Default::default()
+    };
+    let client = client.map(|Extension(client)| client.0);
+    let started = debug_timer(&state.config, query.debug_timings, client.as_deref());
+    sign(state, request, started, client).await
+}
+
+/// Starts timing a signature when `requested`, the operator enabled debug timings, and the
+/// signing client has admin scope, since unlock times hint at how each key is protected
+#[cfg(not(feature = "verifier-only"))]
+fn debug_timer(config: &Config, requested: bool, client: Option<&str>) -> Option<std::time::Instant> {
+    (requested && config.debug_timings && has_admin_scope(config, client)).then(std::time::Instant::now)
+}
+
+/// SHA-256 of a request body, read a chunk at a time and refused once it passes `limit` bytes
+///
+/// Equal to [`create_document_hash`] of the same bytes.
+#[cfg(not(feature = "verifier-only"))]
+async fn hash_body(body: axum::body::Body, limit: u64) -> Result<String, KeyManagementError> {
+    let mut hasher = Sha256::new();
+    let mut received = 0u64;
+    let mut chunks = body.into_data_stream();
+    while let Some(chunk) = tokio_stream::StreamExt::next(&mut chunks).await {
+        let chunk = chunk.map_err(|e| KeyManagementError::InvalidRequest(format!("Failed to read the request body: {}", e)))?;
+        received += chunk.len() as u64;
+        if received > limit {
+            return Err(KeyManagementError::ContentTooLarge { field: "document", limit });
+        }
+        hasher.update(&chunk);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Takes a permit for signing with `key_pair` if unlocking it runs the KDF
+///
+/// Unlocking an encrypted key runs the KDF, so those signatures share a concurrency limit.
+#[cfg(not(feature = "verifier-only"))]
+async fn signing_permit<'a>(
+    state: &'a AppState,
+    key_pair: &KeyPair,
+) -> Result<Option<tokio::sync::SemaphorePermit<'a>>, (StatusCode, Json<SignDocumentResponse>)> {
+    if key_pair.hsm.is_some() || key_pair.key_type != KeyType::Ed25519Encrypted {
+        return Ok(None);
+    }
+    state.limits.signing.acquire().await.map_err(|e| {
+        (StatusCode::SERVICE_UNAVAILABLE, Json(sign_failure(ErrorCode::Overloaded, e.to_string(), Some(key_pair.id))))
+    })
+}
+
+/// Asks the signing policy whether `key_pair` may sign `document_hash`
+///
+/// The policy service learns the key, the hash, the context and the requesting client, never
+/// the document content or any key material.
+#[cfg(not(feature = "verifier-only"))]
+async fn check_sign_policy(
+    state: &AppState,
+    key_pair: &KeyPair,
+    document_hash: &str,
+    context: Option<&str>,
+    requester: Option<&str>,
+) -> Result<(), (StatusCode, Json<SignDocumentResponse>)> {
+    let request = PolicyRequest {
+        key_id: key_pair.id,
+        key_fingerprint: key_pair.fingerprint.clone().or_else(|| public_key_to_fingerprint(&key_pair.public_key).ok()),
+        document_hash: document_hash.to_string(),
+        context: context.map(str::to_string),
+        requester: requester.map(str::to_string),
+    };
+    state.sign_policy.check(&request, state.clock.now()).await
+        .map_err(|e| (failure_status(&state.config, e.code()), Json(sign_failure(e.code(), e.to_string(), Some(key_pair.id)))))
+}
+
+/// Signs a document; `started` is set when the response should carry timings measured from it,
+/// and `requester` names the authenticated client, if any, to the signing policy
+#[cfg(not(feature = "verifier-only"))]
+async fn sign(
+    state: Arc<AppState>,
+    request: SignDocumentRequest,
+    started: Option<std::time::Instant>,
+    requester: Option<String>,
+) -> Result<Json<SignDocumentResponse>, (StatusCode, Json<SignDocumentResponse>)> {
+    // Larger documents go to `/sign/raw`, which hashes them without holding them whole
+    let limit = u64::from(state.config.sign_max_content_bytes);
+    if request.document_content.as_ref().is_some_and(|content| content.len() as u64 > limit) {
+        let e = KeyManagementError::ContentTooLarge { field: "document_content", limit };
+        let details = serde_json::json!({ "key_id": request.key_id, "limit_bytes": limit, "raw_endpoint": "/sign/raw" });
+        return Err((failure_status(&state.config, e.code()), Json(SignDocumentResponse {
+            details: Some(details),
+            ..sign_failure(e.code(), format!("{}; sign larger documents with POST /sign/raw", e), Some(request.key_id))
+        })));
+    }
+
+    // Get the key pair
+    let key_pair = match state.storage.get_key(request.key_id).await {
+        Ok(kp) => kp,
+        Err(e) => {
+            return Err((failure_status(&state.config, e.code()), Json(SignDocumentResponse {
+                details: e.details(),
+                ..sign_failure(e.code(), "Key not found or invalid", None)
+            })));
+        }
+    };
+
+    // A key signs outside its deployment environment only when the mismatch policy allows it
+    if let Err(e) = environment::check_signing(&state.config, &key_pair.environment) {
+        return Err((failure_status(&state.config, e.code()), Json(SignDocumentResponse {
+            details: e.details(),
+            ..sign_failure(e.code(), e.to_string(), Some(request.key_id))
+        })));
+    }
+
+    // A validity window that has already closed would produce a signature that never verifies
+    if is_signature_window_expired(request.valid_until, state.clock.now()) {
+        return Err((failure_status(&state.config, ErrorCode::ValidationFailed), Json(SignDocumentResponse {
+            valid_until: request.valid_until,
+            ..sign_failure(ErrorCode::ValidationFailed, "valid_until must be in the future", Some(request.key_id))
+        })));
+    }
+    if let Err(e) = validate_context(request.context.as_deref()) {
+        return Err((StatusCode::UNPROCESSABLE_ENTITY, Json(sign_failure(e.code(), e.to_string(), Some(request.key_id)))));
+    }
+    let context = normalize_context(request.context.as_deref());
+
+    // A key issued for particular purposes only signs for the contexts on its allow-list
+    if let Some(allowed) = &key_pair.allowed_contexts {
+        if !context.is_some_and(|context| allowed.iter().any(|entry| entry == context)) {
+            let message = format!("Key {} may only sign with context {}", key_pair.id, allowed.join(", "));
+            return Err((StatusCode::FORBIDDEN, Json(SignDocumentResponse {
+                details: Some(serde_json::json!({ "key_id": key_pair.id, "allowed_contexts": allowed })),
+                ..sign_failure(ErrorCode::InsufficientPermissions, message, Some(request.key_id))
+            })));
+        }
+    }
+
+    let envelope = match resolve_envelope(&request, &key_pair) {
+        Ok(envelope) => envelope,
+        Err(message) => {
+            return Err((StatusCode::UNPROCESSABLE_ENTITY, Json(sign_failure(ErrorCode::ValidationFailed, message, Some(request.key_id)))));
+        }
+    };
+    if envelope.output_format != SignatureOutputFormat::Raw {
+        if envelope.encoding != SignatureEncoding::Base64 {
+            return Err((StatusCode::UNPROCESSABLE_ENTITY, Json(sign_failure(
+                ErrorCode::ValidationFailed,
+                "encoding applies to raw signatures only",
+                Some(request.key_id),
+            ))));
+        }
+        if !request.message_encoding.is_default() {
+            return Err((StatusCode::UNPROCESSABLE_ENTITY, Json(sign_failure(
+                ErrorCode::ValidationFailed,
+                "message_encoding applies to raw signatures only",
+                Some(request.key_id),
+            ))));
+        }
+        return sign_file_format(&state, &request, &envelope, &key_pair, started, requester.as_deref()).await;
+    }
+
+    // Resolve the hash to sign, canonicalizing structured content first
+    let document_hash = match resolve_document_hash(
+        request.document_hash.as_deref(),
+        request.document_content.as_deref(),
+        request.content_type,
+    ) {
+        Ok(hash) => hash,
+        Err(KeyManagementError::ValidationFailed(message)) => {
+            return Err((
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(sign_failure(ErrorCode::ValidationFailed, message, Some(request.key_id))),
+            ));
+        }
+        Err(_) => {
+            return Err((failure_status(&state.config, ErrorCode::InvalidRequest), Json(sign_failure(
+                ErrorCode::InvalidRequest,
+                "Either document_hash or document_content must be provided",
+                Some(request.key_id),
+            ))));
+        }
+    };
+
+    // Encodings other tools can check bind nothing beyond the document
+    let bound_now = request.bind_timestamp.then(|| state.clock.now());
+    if let Err(e) = check_message_encoding(request.message_encoding, request.document_content.as_deref().map(str::len), request.valid_until, context, bound_now) {
+        return Err((failure_status(&state.config, e.code()), Json(sign_failure(e.code(), e.to_string(), Some(request.key_id)))));
+    }
+
+    // Said plainly rather than as the generic failure below: the operator has to migrate the record
+    if let Err(e) = ensure_known_key_type(&key_pair) {
+        return Err((failure_status(&state.config, e.code()), Json(sign_failure(e.code(), e.to_string(), Some(request.key_id)))));
+    }
+    check_sign_policy(&state, &key_pair, &document_hash, context, requester.as_deref()).await?;
+    let _permit = signing_permit(&state, &key_pair).await?;
+
+    let (signer, kdf_timing) = match load_signer(&key_pair, request.password.as_deref(), state.hsm.as_deref()) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            let message = if request.document_content.is_some() {
+                "Failed to sign document content"
+            } else {
+                "Failed to sign document"
+            };
+            return Err((failure_status(&state.config, e.code()), Json(sign_failure(e.code(), message, Some(request.key_id)))));
+        }
+    };
+
+    // Sign the document hash, binding the signing time if requested
+    let signing_time = if request.bind_timestamp {
+        bindable_signing_time(state.clock.now())
+    } else {
+        state.clock.now()
+    };
+    let bound_time = request.bind_timestamp.then_some(signing_time);
+    let content = match (request.message_encoding, request.document_content.as_deref()) {
+        (MessageEncoding::RawMessage, Some(content)) => content_bytes(content, request.content_type).ok(),
+        _ => None,
+    };
+    let signed = DocumentHash::from_hex(&document_hash).and_then(|hash| {
+        let message = encode_signing_message(request.message_encoding, &hash, content.as_deref(), request.valid_until, context, bound_time)?;
+        Ok((hash, SignatureBytes::from(signer.sign_message(&message)?)))
+    });
+    let (hash, signature) = match signed {
+        Ok(signed) => signed,
+        Err(e) => return Err((failure_status(&state.config, e.code()), Json(sign_failure(e.code(), "Failed to sign document", Some(request.key_id))))),
+    };
+
+    // Record a receipt so the verification bundle can be fetched later, before the signature
+    // is released; a repeat signature of the same document gets the existing receipt back
+    let bundle = build_bundle(&state, &key_pair, signer.as_ref(), &hash, &signature, signing_time, &request).await;
+    let (bundle, duplicate, receipt_warning) = match record_receipt(&state, bundle).await {
+        Ok(recorded) => recorded,
+        Err(e) => {
+            let message = "Signature withheld because its receipt could not be recorded";
+            return Err((failure_status(&state.config, e.code()), Json(sign_failure(e.code(), message, Some(request.key_id)))));
+        }
+    };
+
+    // Count the signature and update the last used timestamp; a repeat signature was counted
+    // when its id was first issued
+    let usage_warning = if duplicate { None } else { record_sign_usage(&state, request.key_id, signing_time).await };
+    upgrade_legacy_key(&state, &key_pair, request.password.as_deref()).await;
+
+    let signature_id = bundle.as_ref().map(|bundle| bundle.body.signature_id);
+    let signing_time = bundle.as_ref().filter(|_| duplicate).map_or(signing_time, |bundle| bundle.body.signing_time);
+
+    let canonical_hash = match request.content_type {
+        DocumentContentType::JsonJcs => Some(document_hash.clone()),
+        DocumentContentType::Text => None,
+    };
+
+    let message = if duplicate {
+        "Document was already signed with this key; returning the existing signature"
+    } else {
+        "Document signed successfully"
+    };
+
+    // Receipts keep the standard base64 signature; only the response uses the requested encoding
+    Ok(Json(SignDocumentResponse {
+        success: true,
+        signature: Some(signature.encode(envelope.encoding)),
+        message: message.to_string(),
+        code: None,
+        details: None,
+        key_id: Some(request.key_id),
+        document_hash: Some(document_hash),
+        signing_time: Some(signing_time),
+        valid_until: request.valid_until,
+        canonical_hash,
+        output_format: SignatureOutputFormat::Raw,
+        signature_encoding: envelope.encoding,
+        message_encoding: request.message_encoding,
+        signature_id,
+        bundle: if request.bundle { bundle } else { None },
+        context: context.map(str::to_string),
+        duplicate,
+        timestamp_bound: request.bind_timestamp,
+        warnings: sign_warnings(&state, &key_pair, request.valid_until).into_iter().chain(receipt_warning).chain(usage_warning).collect(),
+        timings: record_kdf_timing(&state, kdf_timing.as_ref(), started),
+        signer_info: Some(SignerInfo::current()),
+    }))
+}
+
+/// Records the receipt of a new signature before the signature is released
+///
+/// Returns the receipt, whether it was already recorded for a repeat signature, and the warning
+/// to release the signature with when no receipt could be built or written and
+/// `INKAN_RECEIPT_FAILURE=warn`. Otherwise such a failure withholds the signature.
+#[cfg(not(feature = "verifier-only"))]
+async fn record_receipt(
+    state: &AppState,
+    bundle: Result<Bundle, KeyManagementError>,
+) -> Result<(Option<Bundle>, bool, Option<ApiWarning>), KeyManagementError> {
+    let (bundle, error) = match bundle {
+        Ok(bundle) => {
+            let recorded = if state.config.dedupe_signatures {
+                state.receipts.record_if_absent(bundle.clone()).await
+            } else {
+                state.receipts.record(bundle.clone()).await.map(|()| None)
+            };
+            match recorded {
+                Ok(Some(existing)) => return Ok((Some(existing), true, None)),
+                Ok(None) => {
+                    record_signature_event(state, &bundle.body).await;
+                    return Ok((Some(bundle), false, None));
+                }
+                Err(e) => (Some(bundle), e),
+            }
+        }
+        Err(e) => (None, e),
+    };
+    tracing::warn!("Failed to record signature receipt: {}", error);
+    match state.config.receipt_failure {
+        ReceiptFailurePolicy::Reject => Err(KeyManagementError::ReceiptNotRecorded(error.to_string())),
+        ReceiptFailurePolicy::Warn => {
+            if let Some(bundle) = &bundle {
+                record_signature_event(state, &bundle.body).await;
+            }
+            let warning = ApiWarning::new(WarningCode::ReceiptNotRecorded, format!("Signature released without a stored receipt: {}", error));
+            Ok((bundle, false, Some(warning)))
+        }
+    }
+}
+
+/// Numbers a signature about to be released in the operation event log
+///
+/// Like usage, this is best effort: a signature whose event cannot be written is still released.
+#[cfg(not(feature = "verifier-only"))]
+async fn record_signature_event(state: &AppState, body: &BundleBody) {
+    let event = OperationEvent::signature(body.key_id, body.signature_id, body.context.clone(), state.clock.now());
+    if let Err(e) = state.storage.events().record(event).await {
+        tracing::warn!("Signature {} not numbered in the event log: {}", body.signature_id, e);
+    }
+}
+
+/// Counts a signature against its key, returning a warning if the update was dropped
+///
+/// Usage is best effort: counters are held in memory and written by the background flusher,
+/// which retries while the keystore is failing, so only an update for a key no longer stored
+/// is lost.
+#[cfg(not(feature = "verifier-only"))]
+async fn record_sign_usage(state: &AppState, key_id: Uuid, signing_time: chrono::DateTime<chrono::Utc>) -> Option<ApiWarning> {
+    let error = state.storage.record_sign(key_id, signing_time).await.err()?;
+    tracing::warn!("Usage of key {} not recorded: {}", key_id, error);
+    Some(ApiWarning::new(WarningCode::UsageNotRecorded, format!("Usage of key {} was not recorded", key_id)))
+}
+
+/// Default name of the tombstone kept for a single-use key
+#[cfg(not(feature = "verifier-only"))]
+pub const EPHEMERAL_KEY_NAME: &str = "Ephemeral signing key";
+
+/// Sign with a brand new single-use key, returning the signature with the key's public half
+///
+/// The key goes through the same generation policy as `/keys/generate`, including the key quota
+/// and lifetime checks. Its private key never outlives the request: without `tombstone` nothing
+/// about the key is stored, and with it the keystore keeps a revoked entry holding only the
+/// public key, so the signature stays attributable. A receipt is recorded either way.
+#[cfg(not(feature = "verifier-only"))]
+pub async fn sign_ephemeral(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<EphemeralSignRequest>,
+) -> Result<Json<EphemeralSignResponse>, (StatusCode, Json<EphemeralSignResponse>)> {
+    let failure = |status: StatusCode, code: ErrorCode, message: String, details: Option<serde_json::Value>| {
+        (status, Json(EphemeralSignResponse {
+            success: false,
+            message,
+            code: Some(code),
+            details,
+            signature: None,
+            key_id: None,
+            public_key: None,
+            key_fingerprint: None,
+            document_hash: None,
+            signing_time: None,
+            valid_until: request.valid_until,
+            context: None,
+            timestamp_bound: false,
+            signature_id: None,
+            bundle: None,
+            tombstone: false,
+        }))
+    };
+    let unprocessable = |message: String| failure(StatusCode::UNPROCESSABLE_ENTITY, ErrorCode::ValidationFailed, message, None);
+
+    if is_signature_window_expired(request.valid_until, state.clock.now()) {
+        return Err(unprocessable("valid_until must be in the future".to_string()));
+    }
+    validate_context(request.context.as_deref()).map_err(|e| unprocessable(e.to_string()))?;
+    let context = normalize_context(request.context.as_deref());
+    let document_hash = match resolve_document_hash(request.document_hash.as_deref(), request.document_content.as_deref(), request.content_type) {
+        Ok(hash) => hash,
+        Err(KeyManagementError::ValidationFailed(message)) => return Err(unprocessable(message)),
+        Err(_) => return Err(failure(
+            StatusCode::BAD_REQUEST,
+            ErrorCode::InvalidRequest,
+            "Either document_hash or document_content must be provided".to_string(),
+            None,
+        )),
+    };
+
+    let mut generate = GenerateKeyRequest {
+        name: request.name.clone().unwrap_or_else(|| EPHEMERAL_KEY_NAME.to_string()),
+        tags: Some(vec!["ephemeral".to_string()]),
+        ..Default::default()
+    };
+    let existing = state.storage.list_keys().await;
+    if let Err(errors) = validate_generate_request(&mut generate, &existing, &state.config, state.clock.now()) {
+        let message = errors.iter().map(|error| format!("{}: {}", error.field, error.message)).collect::<Vec<_>>().join("; ");
+        return Err(failure(StatusCode::UNPROCESSABLE_ENTITY, ErrorCode::ValidationFailed, message, field_error_details(&errors)));
+    }
+
+    let _permit = state.limits.generation.acquire().await.map_err(|e| {
+        failure(StatusCode::SERVICE_UNAVAILABLE, ErrorCode::Overloaded, e.to_string(), None)
+    })?;
+    if request.tombstone {
+        if let Err(e) = state.capacity.reserve(&state.storage, state.clock.now()).await {
+            let (code, message) = (e.code(), e.to_string());
+            return Err(failure(StatusCode::from(e), code, message, None));
+        }
+    }
+
+    let internal = |e: KeyManagementError| failure(StatusCode::INTERNAL_SERVER_ERROR, e.code(), format!("Ephemeral signing failed: {}", e), None);
+    let seed = state.entropy.draw_seed().map_err(internal)?;
+    let mut key_pair = generate_key_pair_from_seed(generate, &state.config.kdf, &seed, state.clock.now()).map_err(internal)?;
+    let signing_key = ed25519_dalek::SigningKey::from_bytes(&seed);
+
+    let signing_time = if request.bind_timestamp {
+        bindable_signing_time(state.clock.now())
+    } else {
+        state.clock.now()
+    };
+    let bound_time = request.bind_timestamp.then_some(signing_time);
+    let hash = DocumentHash::from_hex(&document_hash).map_err(internal)?;
+    let signature = sign_document_hash(&signing_key, &hash, request.valid_until, context, bound_time).map_err(internal)?;
+    let sign_request = SignDocumentRequest {
+        key_id: key_pair.id,
+        valid_until: request.valid_until,
+        content_type: request.content_type,
+        context: request.context.clone(),
+        bind_timestamp: request.bind_timestamp,
+        ..Default::default()
+    };
+    let bundle = build_bundle(&state, &key_pair, &signing_key, &hash, &signature, signing_time, &sign_request).await.map_err(internal)?;
+    drop(signing_key);
+    if let Err(e) = state.receipts.record(bundle.clone()).await {
+        tracing::warn!("Failed to record signature receipt {}: {}", bundle.body.signature_id, e);
+        if state.config.receipt_failure == ReceiptFailurePolicy::Reject {
+            let message = "Signature withheld because its receipt could not be recorded".to_string();
+            return Err(failure(StatusCode::SERVICE_UNAVAILABLE, ErrorCode::ReceiptNotRecorded, message, None));
+        }
+    }
+    record_signature_event(&state, &bundle.body).await;
+
+    // The tombstone is revoked as of the signature and holds no private key
+    key_pair.private_key = SecretString::default();
+    key_pair.key_type = KeyType::Ed25519Ephemeral;
+    key_pair.lifecycle = Lifecycle::Revoked;
+    key_pair.expires_at = Some(signing_time);
+    key_pair.last_used = Some(signing_time);
+    key_pair.usage.sign_count = 1;
+    key_pair.usage.last_sign_at = Some(signing_time);
+    if request.tombstone {
+        state.storage.store_key(key_pair.clone()).await.map_err(internal)?;
+    }
+    tracing::info!(
+        "Ephemeral key {} ({}) signed {}; {}",
+        key_pair.id,
+        bundle.body.key_fingerprint,
+        bundle.body.signature_id,
+        if request.tombstone { "kept as a revoked tombstone" } else { "discarded" },
+    );
+
+    Ok(Json(EphemeralSignResponse {
+        success: true,
+        message: "Document signed successfully".to_string(),
+        code: None,
+        details: None,
+        signature: Some(signature.to_string()),
+        key_id: request.tombstone.then_some(key_pair.id),
+        public_key: Some(key_pair.public_key),
+        key_fingerprint: Some(bundle.body.key_fingerprint.clone()),
+        document_hash: Some(document_hash),
+        signing_time: Some(signing_time),
+        valid_until: request.valid_until,
+        context: context.map(str::to_string),
+        timestamp_bound: request.bind_timestamp,
+        signature_id: Some(bundle.body.signature_id),
+        bundle: request.bundle.then_some(bundle),
+        tombstone: request.tombstone,
+    }))
+}
+
+/// Builds the verification bundle for a raw signature, counter-signed by the notary key if configured
+#[cfg(not(feature = "verifier-only"))]
+async fn build_bundle(
+    state: &AppState,
+    key_pair: &KeyPair,
+    signer: &dyn KeySigner,
+    document_hash: &DocumentHash,
+    signature: &SignatureBytes,
+    signing_time: chrono::DateTime<chrono::Utc>,
+    request: &SignDocumentRequest,
+) -> Result<Bundle, KeyManagementError> {
+    let key_fingerprint = public_key_to_fingerprint(&key_pair.public_key).map_err(KeyManagementError::InvalidKeyFormat)?;
+    let context = normalize_context(request.context.as_deref());
+    let body = BundleBody {
+        schema: BUNDLE_SCHEMA.to_string(),
+        version: BUNDLE_VERSION,
+        signature_id: derive_signature_id(
+            &key_fingerprint,
+            request.message_encoding,
+            document_hash,
+            request.valid_until,
+            context,
+            request.bind_timestamp.then_some(signing_time),
+        ),
+        key_id: key_pair.id,
+        document_hash: document_hash.to_string(),
+        hash_algorithm: "sha-256".to_string(),
+        content_type: request.content_type,
+        signature: signature.to_string(),
+        signature_algorithm: "ed25519".to_string(),
+        signing_time,
+        valid_until: request.valid_until,
+        context: context.map(str::to_string),
+        timestamp_bound: request.bind_timestamp,
+        message_encoding: request.message_encoding,
+        public_key: key_pair.public_key.clone(),
+        key_fingerprint,
+        key_status: BundleKeyStatus {
+            active: key_pair.state(signing_time).is_usable(),
+            expires_at: key_pair.expires_at,
+        },
+        signer_info: Some(SignerInfo::current()),
+    };
+    let mut bundle = Bundle::new(body, signer)?;
+
+    if let Some(notary_key_id) = state.config.notary_key_id {
+        match load_notary_key(state, notary_key_id).await {
+            Ok(notary_key) => bundle.notarize(notary_key_id, &notary_key)?,
+            Err(e) => tracing::warn!("Notary key {} unavailable, bundle left without counter-signature: {}", notary_key_id, e),
+        }
+    }
+
+    Ok(bundle)
+}
+
+/// Loads the unencrypted notary key used to counter-sign bundles and export manifests
+async fn load_notary_key(state: &AppState, notary_key_id: Uuid) -> Result<ed25519_dalek::SigningKey, KeyManagementError> {
+    let notary = state.storage.get_key(notary_key_id).await?;
+    let (private_key, salt) = notary.signing_secrets();
+    load_signing_key(private_key, salt, &notary.kdf.unwrap_or_default(), None)
+}
+
+/// Query parameters for public key export
+#[cfg(not(feature = "verifier-only"))]
+#[derive(Debug, Deserialize)]
+pub struct ExportKeysQuery {
+    #[serde(default)]
+    pub format: ArchiveFormat,
+    /// Comma-separated key file encodings: `pem`, `jwk`
+    #[serde(default = "default_export_include")]
+    pub include: String,
+    /// Also export revoked keys
+    #[serde(default, alias = "includeRevoked")]
+    pub include_revoked: bool,
+}
+
+#[cfg(not(feature = "verifier-only"))]
+fn default_export_include() -> String {
+    "pem".to_string()
+}
+
+/// Stream an archive of every public key, with a manifest signed by the notary key if configured
+#[cfg(not(feature = "verifier-only"))]
+pub async fn export_keys(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ExportKeysQuery>,
+    deadline: Option<Extension<Deadline>>,
+) -> Response {
+    let encodings = match parse_encodings(&query.include) {
+        Ok(encodings) => encodings,
+        Err(e) => return error_response(StatusCode::UNPROCESSABLE_ENTITY, e.code(), e.to_string()),
+    };
+
+    // Built from one snapshot, so writes made meanwhile never leave the archive half-changed
+    let snapshot = state.storage.begin_snapshot().await;
+    let now = snapshot.taken_at();
+    let keys: Vec<KeyPair> = snapshot.key_pairs()
+        .into_iter()
+        .filter(|key_pair| query.include_revoked || key_pair.state(now) != KeyState::Revoked)
+        .collect();
+
+    let notary = manifest_notary(&state, "export manifest").await;
+
+    let notary = notary.as_ref().map(|(key_id, key)| (*key_id, key));
+    let files = match build_export(&keys, &encodings, now, notary, &request_deadline(deadline)) {
+        Ok(files) => files,
+        Err(e @ KeyManagementError::DeadlineExceeded { .. }) => return key_error_response(StatusCode::GATEWAY_TIMEOUT, e.to_string(), &e),
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.code(), e.to_string()),
+    };
+    let filename = format!("inkan-public-keys-{}.{}", now.format("%Y%m%dT%H%M%SZ"), query.format.extension());
+    (
+        [
+            (header::CONTENT_TYPE, query.format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+        ],
+        axum::body::Body::from_stream(stream_archive(query.format, files, now)),
+    ).into_response()
+}
+
+/// Loads the notary key for signing exported manifests, or `None` with a warning if unavailable
+async fn manifest_notary(state: &AppState, what: &str) -> Option<(Uuid, ed25519_dalek::SigningKey)> {
+    let notary_key_id = state.config.notary_key_id?;
+    match load_notary_key(state, notary_key_id).await {
+        Ok(notary_key) => Some((notary_key_id, notary_key)),
+        Err(e) => {
+            tracing::warn!("Notary key {} unavailable, {} left unsigned: {}", notary_key_id, what, e);
+            None
+        }
+    }
+}
+
+/// Fingerprint, name, state and expiry of every stored key, signed by the notary key if configured
+#[cfg(not(feature = "verifier-only"))]
+pub async fn get_key_manifest(State(state): State<Arc<AppState>>) -> Response {
+    let snapshot = state.storage.begin_snapshot().await;
+    let manifest = KeyManifest::new(&snapshot.key_pairs(), snapshot.taken_at());
+    let notary = manifest_notary(&state, "key manifest").await;
+    match manifest.sign(notary.as_ref().map(|(key_id, key)| (*key_id, key))) {
+        Ok(signed) => Json(signed).into_response(),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e.code(), e.to_string()),
+    }
+}
+
+/// Query parameters for a pin set
+#[derive(Debug, Deserialize)]
+pub struct PinsetQuery {
+    /// Comma-separated tags every pinned key must carry
+    pub tags: Option<String>,
+    #[serde(default)]
+    pub format: PinFormat,
+}
+
+/// The usable keys carrying every requested tag, as JSON signed by the notary key if configured
+/// or as Rust, Swift or Kotlin source
+pub async fn get_pinset(State(state): State<Arc<AppState>>, Query(query): Query<PinsetQuery>) -> Response {
+    let tags: Vec<String> = query.tags.as_deref()
+        .map(|tags| tags.split(',').map(str::to_string).collect())
+        .unwrap_or_default();
+    let snapshot = state.storage.begin_snapshot().await;
+    let pinset = Pinset::new(&snapshot.key_pairs(), &tags, snapshot.taken_at());
+    let notary = manifest_notary(&state, "pin set").await;
+    let signed = match pinset.sign(notary.as_ref().map(|(key_id, key)| (*key_id, key))) {
+        Ok(signed) => signed,
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.code(), e.to_string()),
+    };
+    match signed.render(query.format) {
+        Some(source) => (
+            [
+                (header::CONTENT_TYPE, query.format.content_type().to_string()),
+                (header::CONTENT_DISPOSITION, format!("attachment; filename=\"inkan-pinset.{}\"", query.format.extension())),
+            ],
+            source,
+        ).into_response(),
+        None => Json(signed).into_response(),
+    }
+}
+
+/// Compare the local keystore against the keys another instance holds
+///
+/// Manifests with a notary signature are checked before use, and a bad signature fails the
+/// request. Only fingerprints, names, states and expiries are returned.
+#[cfg(not(feature = "verifier-only"))]
+pub async fn compare_keys(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<CompareKeysRequest>,
+) -> Response {
+    // Bare fingerprints carry no signature
+    let mut signed = request.fingerprints.is_empty();
+    if let Some(manifest) = &request.manifest {
+        match manifest.verify() {
+            Ok(verified) => signed &= verified,
+            Err(e) => return error_response(StatusCode::UNPROCESSABLE_ENTITY, e.code(), e.to_string()),
+        }
+    }
+    if let Some(manifest) = &request.export_manifest {
+        match verify_export_manifest(manifest, request.export_signature.as_ref()) {
+            Ok(verified) => signed &= verified,
+            Err(e) => return error_response(StatusCode::UNPROCESSABLE_ENTITY, e.code(), e.to_string()),
+        }
+    }
+
+    let mut expected: Vec<(String, Option<KeyManifestEntry>)> = request.fingerprints.into_iter().map(|fingerprint| (fingerprint, None)).collect();
+    if let Some(manifest) = request.manifest {
+        expected.extend(manifest.manifest.keys.into_iter().map(|entry| (entry.fingerprint.clone(), Some(entry))));
+    }
+    if let Some(manifest) = request.export_manifest {
+        expected.extend(manifest.keys.into_iter().map(|entry| (entry.fingerprint, None)));
+    }
+    if expected.is_empty() {
+        return error_response(StatusCode::BAD_REQUEST, ErrorCode::InvalidRequest, "Provide fingerprints, a manifest or an export manifest to compare");
+    }
+
+    let snapshot = state.storage.begin_snapshot().await;
+    let comparison = compare_manifests(&KeyManifest::new(&snapshot.key_pairs(), snapshot.taken_at()), &expected);
+    Json(CompareKeysResponse { success: true, comparison, signed }).into_response()
+}
+
+/// Query parameters for a key status
+#[derive(Debug, Default, Deserialize)]
+pub struct KeyStatusQuery {
+    /// Have the notary key sign the status
+    #[serde(default)]
+    pub signed: bool,
+}
+
+/// Statuses of `key_ids`, signed by the notary key if asked to
+///
+/// Unlike manifests, a status asked to be signed is not served unsigned: without a configured
+/// notary key the request is refused.
+async fn signed_key_statuses(
+    state: &AppState,
+    key_ids: &[Uuid],
+    signed: bool,
+) -> Result<Vec<Option<KeyStatusDocument>>, Response> {
+    let notary = match (signed, state.config.notary_key_id) {
+        (false, _) => None,
+        (true, None) => {
+            return Err(error_response(StatusCode::BAD_REQUEST, ErrorCode::InvalidRequest, "Signed statuses need a notary key, and none is configured"));
+        }
+        (true, Some(notary_key_id)) => match load_notary_key(state, notary_key_id).await {
+            Ok(notary_key) => Some((notary_key_id, notary_key)),
+            Err(e) => return Err(key_error_response(StatusCode::INTERNAL_SERVER_ERROR, "Notary key unavailable", &e)),
+        },
+    };
+    let statuses = key_statuses(&state.storage, key_ids, state.clock.now()).await
+        .map_err(|e| key_error_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to read key statuses", &e))?;
+    statuses.into_iter()
+        .map(|status| status.map(|status| status.sign(notary.as_ref().map(|(key_id, key)| (*key_id, key)))).transpose())
+        .collect::<Result<_, _>>()
+        .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e.code(), e.to_string()))
+}
+
+/// Whether a key is still good right now, for caches holding its public key
+///
+/// Deleted and archived keys report the state they were left in; only ids no key ever had are
+/// not found. The response may be cached for `key_status_max_age_secs`.
+pub async fn get_key_status(
+    State(state): State<Arc<AppState>>,
+    Path(key_id): Path<Uuid>,
+    Query(query): Query<KeyStatusQuery>,
+) -> Response {
+    let status = match signed_key_statuses(&state, &[key_id], query.signed).await {
+        Ok(mut statuses) => statuses.pop().flatten(),
+        Err(response) => return response,
+    };
+    let Some(status) = status else {
+        return error_response(StatusCode::NOT_FOUND, ErrorCode::KeyNotFound, "No key ever had this id");
+    };
+    (
+        [(header::CACHE_CONTROL, format!("public, max-age={}", state.config.key_status_max_age_secs))],
+        Json(status),
+    ).into_response()
+}
+
+/// Status of up to `MAX_STATUS_BATCH` keys at once
+pub async fn key_status_batch(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<KeyStatusBatchRequest>,
+) -> Response {
+    if request.key_ids.is_empty() || request.key_ids.len() > MAX_STATUS_BATCH {
+        return error_response(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorCode::ValidationFailed,
+            format!("Ask for between 1 and {} keys at once", MAX_STATUS_BATCH),
+        );
+    }
+    let statuses = match signed_key_statuses(&state, &request.key_ids, request.signed).await {
+        Ok(statuses) => statuses,
+        Err(response) => return response,
+    };
+    let mut known = Vec::new();
+    let mut unknown = Vec::new();
+    for (key_id, status) in request.key_ids.iter().zip(statuses) {
+        match status {
+            Some(status) => known.push(status),
+            None => unknown.push(*key_id),
+        }
+    }
+    Json(KeyStatusBatchResponse { success: true, statuses: known, unknown }).into_response()
+}
+
+/// Query parameters for bundle retrieval
+#[derive(Debug, Deserialize)]
+pub struct BundleQuery {
+    #[serde(default)]
+    pub format: BundleFormat,
+}
+
+/// Get the verification bundle recorded for a signature
+pub async fn get_signature_bundle(
+    State(state): State<Arc<AppState>>,
+    Path(signature_id): Path<Uuid>,
+    Query(query): Query<BundleQuery>,
+) -> Response {
+    let Some(bundle) = state.receipts.get(signature_id).await else {
+        return error_response(StatusCode::NOT_FOUND, ErrorCode::SignatureNotFound, "Signature not found");
+    };
+    match query.format {
+        BundleFormat::Json => (axum::Extension(PreserveFieldCase), Json(bundle)).into_response(),
+        BundleFormat::Cbor => match bundle.to_cbor() {
+            Ok(bytes) => ([(header::CONTENT_TYPE, "application/cbor")], bytes).into_response(),
+            Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e.code(), e.to_string()),
+        },
+    }
+}
+
+/// Look up a recorded signature by its signature id
+pub async fn get_signature_record(
+    State(state): State<Arc<AppState>>,
+    Path(signature_id): Path<Uuid>,
+) -> Response {
+    match state.receipts.get(signature_id).await {
+        Some(bundle) => Json(SignatureRecordResponse {
+            success: true,
+            message: "Signature found".to_string(),
+            record: bundle.body,
+        }).into_response(),
+        None => error_response(StatusCode::NOT_FOUND, ErrorCode::SignatureNotFound, "Signature not found"),
+    }
+}
+
+/// Counts a request against a verification link, refusing it with 429 once over the limit
+async fn share_rate_limit(state: &AppState, token: &str, now: chrono::DateTime<chrono::Utc>) -> Option<Response> {
+    let retry_after = state.shares.take_request(token, now, state.config.share_requests_per_minute).await.err()?;
+    Some((
+        [(header::RETRY_AFTER, retry_after.to_string())],
+        error_response(StatusCode::TOO_MANY_REQUESTS, ErrorCode::RateLimited, "Too many requests for this verification link"),
+    ).into_response())
+}
+
+fn share_not_found() -> Response {
+    error_response(StatusCode::NOT_FOUND, ErrorCode::ShareNotFound, "Verification link not found or no longer open")
+}
+
+/// Publish a recorded signature, or a complete bundle, behind a random verification link
+#[cfg(not(feature = "verifier-only"))]
+pub async fn create_share(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<CreateShareRequest>,
+) -> Response {
+    let unprocessable = |message: String| error_response(StatusCode::UNPROCESSABLE_ENTITY, ErrorCode::ValidationFailed, message);
+    let now = state.clock.now();
+
+    let ttl_secs = request.ttl_secs.unwrap_or(state.config.share_ttl_secs);
+    if ttl_secs == 0 || ttl_secs > state.config.share_max_ttl_secs {
+        return unprocessable(format!("ttl_secs must be between 1 and {}", state.config.share_max_ttl_secs));
+    }
+
+    let bundle = match (request.signature_id, request.bundle) {
+        (Some(signature_id), None) => match state.receipts.get(signature_id).await {
+            Some(bundle) => bundle,
+            None => return error_response(StatusCode::NOT_FOUND, ErrorCode::SignatureNotFound, "Signature not found"),
+        },
+        // A supplied bundle is only published if it verifies, so a link cannot vouch for a forgery
+        (None, Some(bundle)) => match verify_bundle(&bundle, BundleSubject::Hash(&bundle.body.document_hash)) {
+            Ok(verification) if verification.valid => bundle,
+            Ok(_) => return unprocessable("bundle does not verify".to_string()),
+            Err(e) => return unprocessable(e.to_string()),
+        },
+        _ => return unprocessable("Exactly one of signature_id or bundle must be provided".to_string()),
+    };
+
+    match state.shares.create(bundle, now, chrono::Duration::seconds(ttl_secs.into())).await {
+        Ok((token, share)) => Json(CreateShareResponse {
+            success: true,
+            message: "Verification link created".to_string(),
+            token,
+            signature_id: share.bundle.body.signature_id,
+            expires_at: share.expires_at,
+        }).into_response(),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e.code(), e.to_string()),
+    }
+}
+
+/// Public verification data behind a link: who signed, when, and the hash a document must have
+pub async fn get_share(
+    State(state): State<Arc<AppState>>,
+    Path(token): Path<String>,
+) -> Response {
+    let now = state.clock.now();
+    if let Some(response) = share_rate_limit(&state, &token, now).await {
+        return response;
+    }
+    let Some(share) = state.shares.get(&token, now).await else {
+        return share_not_found();
+    };
+    let key_name = state.storage.get_key_record(share.bundle.body.key_id).await.ok().map(|key_pair| key_pair.name);
+    Json(SharedSignatureResponse {
+        success: true,
+        message: "Verification link found".to_string(),
+        record: share.bundle.body,
+        key_name,
+        link_expires_at: share.expires_at,
+    }).into_response()
+}
+
+/// Check a document, or its hash, against the signature behind a link
+pub async fn check_share(
+    State(state): State<Arc<AppState>>,
+    Path(token): Path<String>,
+    Json(request): Json<CheckShareRequest>,
+) -> Response {
+    let now = state.clock.now();
+    if let Some(response) = share_rate_limit(&state, &token, now).await {
+        return response;
+    }
+    let Some(share) = state.shares.get(&token, now).await else {
+        return share_not_found();
+    };
+
+    let subject = match (&request.document_hash, &request.document_content) {
+        (Some(hash), None) => BundleSubject::Hash(hash),
+        (None, Some(content)) => BundleSubject::Content(content),
+        _ => {
+            return error_response(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                ErrorCode::ValidationFailed,
+                "Exactly one of document_hash or document_content must be provided",
+            );
+        }
+    };
+    let verification = match verify_bundle(&share.bundle, subject) {
+        Ok(verification) => verification,
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.code(), e.to_string()),
+    };
+
+    let message = if verification.valid {
+        "Document matches the shared signature"
+    } else {
+        "Document does not match the shared signature"
+    };
+    Json(CheckShareResponse {
+        success: true,
+        message: message.to_string(),
+        matches: verification.valid,
+        document_matches: verification.document_matches,
+        signature_valid: verification.signature_valid,
+        expected_hash: share.bundle.body.document_hash,
+    }).into_response()
+}
+
+/// Close a verification link before it expires
+#[cfg(not(feature = "verifier-only"))]
+pub async fn revoke_share(
+    State(state): State<Arc<AppState>>,
+    Path(token): Path<String>,
+) -> Response {
+    let now = state.clock.now();
+    if let Some(response) = share_rate_limit(&state, &token, now).await {
+        return response;
+    }
+    match state.shares.revoke(&token, now).await {
+        Ok(true) => Json(serde_json::json!({ "success": true, "message": "Verification link revoked" })).into_response(),
+        Ok(false) => share_not_found(),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e.code(), e.to_string()),
+    }
+}
+
+/// Issue a token that signs with the key on a CI job's behalf
+///
+/// The caller shows it may sign with the key itself: an encrypted key's password must open it.
+/// The token is returned only here. Issue, use and revocation are logged under the
+/// `inkan::delegation` target.
+#[cfg(not(feature = "verifier-only"))]
+pub async fn delegate_key(
+    State(state): State<Arc<AppState>>,
+    Path(key_id): Path<Uuid>,
+    client: Option<AuthenticatedClient>,
+    Json(request): Json<DelegateKeyRequest>,
+) -> Response {
+    let unprocessable = |message: String| error_response(StatusCode::UNPROCESSABLE_ENTITY, ErrorCode::ValidationFailed, message);
+    let fail = |e: KeyManagementError| {
+        let (code, message) = (e.code(), e.to_string());
+        error_response(e.into(), code, message)
+    };
+
+    let max_ttl_secs = state.config.delegation_max_ttl_secs;
+    if request.ttl_secs == 0 || request.ttl_secs > max_ttl_secs {
+        return unprocessable(format!("ttl_secs must be between 1 and {}", max_ttl_secs));
+    }
+    let max_uses = request.max_uses.unwrap_or(1);
+    if max_uses == 0 || max_uses > MAX_DELEGATION_USES {
+        return unprocessable(format!("max_uses must be between 1 and {}", MAX_DELEGATION_USES));
+    }
+    if let Some(contexts) = &request.contexts {
+        if contexts.is_empty() || contexts.iter().any(String::is_empty) {
+            return unprocessable("contexts must name at least one context, and none may be empty".to_string());
+        }
+        if let Some(e) = contexts.iter().find_map(|context| validate_context(Some(context)).err()) {
+            return unprocessable(e.to_string());
+        }
+    }
+
+    let key_pair = match state.storage.get_key(key_id).await {
+        Ok(key_pair) => key_pair,
+        Err(e) => return fail(e),
+    };
+    if let Err(e) = ensure_known_key_type(&key_pair) {
+        return fail(e);
+    }
+    // Opening the key proves the password; only an encrypted key's password is kept
+    if let Err(e) = load_signer(&key_pair, request.password.as_deref(), state.hsm.as_deref()) {
+        return fail(e);
+    }
+    let needs_password = key_pair.hsm.is_none() && key_pair.key_type == KeyType::Ed25519Encrypted;
+
+    let issued_by = client.map(|AuthenticatedClient(client_id)| client_id);
+    let grant = DelegationGrant {
+        key_id,
+        issued_by: issued_by.clone(),
+        ttl: chrono::Duration::seconds(request.ttl_secs.into()),
+        max_uses,
+        contexts: request.contexts,
+        password: request.password.filter(|_| needs_password),
+    };
+    match state.delegations.issue(grant, state.clock.now()).await {
+        Ok((token, delegation)) => {
+            tracing::info!(
+                target: "inkan::delegation",
+                delegation_id = %delegation.id,
+                key_id = %key_id,
+                issued_by = issued_by.as_deref().unwrap_or("-"),
+                max_uses,
+                expires_at = %delegation.expires_at.to_rfc3339(),
+                "Delegation issued",
+            );
+            Json(DelegateKeyResponse {
+                success: true,
+                message: format!("Delegation issued for {} signature(s)", max_uses),
+                token,
+                delegation: DelegationInfo::from(&delegation),
+            }).into_response()
+        }
+        Err(e) => fail(e),
+    }
+}
+
+/// List a key's delegations that can still sign
+#[cfg(not(feature = "verifier-only"))]
+pub async fn list_delegations(
+    State(state): State<Arc<AppState>>,
+    Path(key_id): Path<Uuid>,
+) -> Response {
+    if let Err(e) = state.storage.get_key_record(key_id).await {
+        let (code, message) = (e.code(), e.to_string());
+        return error_response(e.into(), code, message);
+    }
+    let delegations: Vec<DelegationInfo> = state.delegations.outstanding(key_id, state.clock.now()).await
+        .iter()
+        .map(DelegationInfo::from)
+        .collect();
+    Json(ListDelegationsResponse {
+        success: true,
+        message: format!("Found {} outstanding delegations", delegations.len()),
+        key_id,
+        delegations,
+    }).into_response()
+}
+
+/// Revoke a delegation before it expires or is used up
+#[cfg(not(feature = "verifier-only"))]
+pub async fn revoke_delegation(
+    State(state): State<Arc<AppState>>,
+    Path(delegation_id): Path<Uuid>,
+    client: Option<AuthenticatedClient>,
+) -> Response {
+    match state.delegations.revoke(delegation_id, state.clock.now()).await {
+        Ok(Some(delegation)) => {
+            tracing::info!(
+                target: "inkan::delegation",
+                delegation_id = %delegation_id,
+                key_id = %delegation.key_id,
+                revoked_by = client.as_ref().map_or("-", |client| client.0.as_str()),
+                uses = delegation.uses,
+                "Delegation revoked",
+            );
+            Json(serde_json::json!({ "success": true, "message": "Delegation revoked" })).into_response()
+        }
+        Ok(None) => error_response(StatusCode::NOT_FOUND, ErrorCode::DelegationNotFound, "Delegation not found or no longer outstanding"),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e.code(), e.to_string()),
+    }
+}
+
+/// Spends a use of the delegation token a `/sign` request carries, giving the request the
+/// delegated key's password
+#[cfg(not(feature = "verifier-only"))]
+async fn redeem_delegation(
+    state: &AppState,
+    token: &axum::http::HeaderValue,
+    request: &mut SignDocumentRequest,
+) -> Result<DelegationInfo, (StatusCode, Json<SignDocumentResponse>)> {
+    let key_id = request.key_id;
+    let fail = |e: KeyManagementError| (failure_status(&state.config, e.code()), Json(sign_failure(e.code(), e.to_string(), Some(key_id))));
+    let token = token.to_str()
+        .map_err(|_| fail(KeyManagementError::InvalidRequest(format!("{} must be visible ASCII", DELEGATION_TOKEN_HEADER))))?;
+    if request.password.is_some() {
+        return Err((StatusCode::UNPROCESSABLE_ENTITY, Json(sign_failure(
+            ErrorCode::ValidationFailed,
+            "password cannot be combined with a delegation token",
+            Some(key_id),
+        ))));
+    }
+
+    let context = normalize_context(request.context.as_deref());
+    let redeemed = match state.delegations.redeem(token, key_id, context, state.clock.now()).await {
+        Ok(redeemed) => redeemed,
+        Err(e) => {
+            tracing::warn!(target: "inkan::delegation", key_id = %key_id, "Delegated signature refused: {}", e);
+            return Err(fail(e));
+        }
+    };
+    tracing::info!(
+        target: "inkan::delegation",
+        delegation_id = %redeemed.delegation.id,
+        key_id = %key_id,
+        uses = redeemed.delegation.uses,
+        max_uses = redeemed.delegation.max_uses,
+        "Delegation used to sign",
+    );
+    request.password = redeemed.password;
+    Ok(DelegationInfo::from(&redeemed.delegation))
+}
+
+/// Signs document content as a minisign or sshsig signature file
+#[cfg(not(feature = "verifier-only"))]
+async fn sign_file_format(
+    state: &AppState,
+    request: &SignDocumentRequest,
+    envelope: &SignatureEnvelope,
+    key_pair: &KeyPair,
+    started: Option<std::time::Instant>,
+    requester: Option<&str>,
+) -> Result<Json<SignDocumentResponse>, (StatusCode, Json<SignDocumentResponse>)> {
+    let unprocessable = |message: String| {
+        (StatusCode::UNPROCESSABLE_ENTITY, Json(sign_failure(ErrorCode::ValidationFailed, message, Some(request.key_id))))
+    };
+
+    let format = envelope.output_format.as_str();
+
+    // File formats sign the content itself, so a bare hash is not enough
+    let Some(content) = &request.document_content else {
+        return Err(unprocessable(format!("{} output requires document_content", format)));
+    };
+    if request.valid_until.is_some() {
+        return Err(unprocessable(format!("{} output does not support valid_until", format)));
+    }
+    if normalize_context(request.context.as_deref()).is_some() {
+        return Err(unprocessable(format!("{} output does not support context", format)));
+    }
+    if request.bind_timestamp {
+        return Err(unprocessable(format!("{} output does not support bind_timestamp", format)));
+    }
+    if key_pair.hsm.is_some() {
+        return Err(unprocessable(format!("{} output is not available for HSM keys", format)));
+    }
+    let namespace = sshsig_namespace(request.namespace.as_deref()).map_err(unprocessable)?;
+    let bytes = content_bytes(content, request.content_type).map_err(|e| unprocessable(e.to_string()))?;
+    let document_hash = create_document_hash(&String::from_utf8_lossy(&bytes));
+
+    check_sign_policy(state, key_pair, &document_hash, None, requester).await?;
+    let _permit = signing_permit(state, key_pair).await?;
+
+    let (private_key, salt) = key_pair.signing_secrets();
+    let loaded = ensure_known_key_type(key_pair)
+        .and_then(|_| load_signing_key_timed(private_key, salt, &key_pair.kdf.unwrap_or_default(), request.password.as_deref()));
+    let (signing_key, kdf_timing) = match loaded {
+        Ok(loaded) => loaded,
+        Err(e) => return Err((failure_status(&state.config, e.code()), Json(sign_failure(e.code(), "Failed to sign document content", Some(request.key_id))))),
+    };
+
+    let signing_time = state.clock.now();
+    let signature = if envelope.output_format == SignatureOutputFormat::Sshsig {
+        let hash_algorithm = match envelope.hash_algorithm {
+            HashAlgorithm::Sha256 => sshsig::HashAlgorithm::Sha256,
+            _ => sshsig::HashAlgorithm::Sha512,
+        };
+        sshsig::sign(&signing_key, namespace, hash_algorithm, &bytes)
+    } else {
+        let trusted_comment = format!("timestamp:{}\tkey:{}", signing_time.timestamp(), key_pair.id);
+        minisign::sign(&signing_key, &bytes, &trusted_comment)
+    };
+
+    // Count the signature and update the last used timestamp
+    let usage_warning = record_sign_usage(state, request.key_id, signing_time).await;
+    upgrade_legacy_key(state, key_pair, request.password.as_deref()).await;
+
+    let canonical_hash = match request.content_type {
+        DocumentContentType::JsonJcs => Some(document_hash.clone()),
+        DocumentContentType::Text => None,
+    };
+
+    Ok(Json(SignDocumentResponse {
+        success: true,
+        signature: Some(signature),
+        message: "Document signed successfully".to_string(),
+        code: None,
+        details: None,
+        key_id: Some(request.key_id),
+        document_hash: Some(document_hash),
+        signing_time: Some(signing_time),
+        valid_until: None,
+        canonical_hash,
+        output_format: envelope.output_format,
+        signature_encoding: SignatureEncoding::Base64,
+        message_encoding: MessageEncoding::HashRawBytes,
+        signature_id: None,
+        bundle: None,
+        context: None,
+        duplicate: false,
+        timestamp_bound: false,
+        warnings: sign_warnings(state, key_pair, None).into_iter().chain(usage_warning).collect(),
+        timings: record_kdf_timing(state, kdf_timing.as_ref(), started),
+        signer_info: Some(SignerInfo::current()),
+    }))
+}
+
+/// Builds a failed verification response
+fn verify_failure(code: ErrorCode, message: impl Into<String>, now: chrono::DateTime<chrono::Utc>) -> VerifySignatureResponse {
+    VerifySignatureResponse {
+        success: false,
+        is_valid: false,
+        message: message.into(),
+        code: Some(code),
+        details: None,
+        key_info: None,
+        verification_time: Some(now),
+        document_hash: None,
+        cryptographically_valid: false,
+        expired_signature: false,
+        valid_until: None,
+        canonical_hash: None,
+        context: None,
+        certification_chain: None,
+        matched_candidate: None,
+        signing_time: None,
+        warnings: vec![],
+        key_source: None,
+        remote_key: None,
+        public_key_format: None,
+    }
+}
+
+/// Most candidate keys one verification request may name
+pub const MAX_VERIFY_CANDIDATES: usize = 16;
+
+/// Verify a document signature
+pub async fn verify_signature(
+    State(state): State<Arc<AppState>>,
+    Json(mut request): Json<VerifySignatureRequest>,
+) -> Result<Json<VerifySignatureResponse>, (StatusCode, Json<VerifySignatureResponse>)> {
+    let now = state.clock.now();
+    let unprocessable = |message: &str| (StatusCode::UNPROCESSABLE_ENTITY, Json(verify_failure(ErrorCode::ValidationFailed, message, now)));
+
+    // Size and shape are checked before anything is decoded, hashed or looked up
+    let max_content_bytes = state.config.verify_max_content_bytes as usize;
+    if request.document_content.as_ref().is_some_and(|content| content.len() > max_content_bytes) {
+        let message = format!("document_content exceeds {} bytes", max_content_bytes);
+        return Err((StatusCode::PAYLOAD_TOO_LARGE, Json(verify_failure(ErrorCode::ValidationFailed, message, now))));
+    }
+    if let Err(e) = validate_verify_input(&request) {
+        return Err((StatusCode::UNPROCESSABLE_ENTITY, Json(verify_failure(e.code(), e.to_string(), now))));
+    }
+
+    // Verifying against a given public key needs no keystore, so only lookups by id wait for it
+    if request.key_id.is_some() || !request.key_ids.is_empty() {
+        if let Err(e) = state.storage.breaker().admit("/verify", now) {
+            return Err((StatusCode::SERVICE_UNAVAILABLE, Json(VerifySignatureResponse { details: e.details(), ..verify_failure(e.code(), e.to_string(), now) })));
+        }
+    }
+
+    if !request.key_ids.is_empty() || !request.public_keys.is_empty() {
+        return verify_against_candidates(&state, request, now).await;
+    }
+
+    // Resolve key_id-based verifications to the stored public key, or to a peer's when the key
+    // is not held here
+    let key = match request.key_id {
+        Some(key_id) => {
+            let key = match find_verification_key(&state, key_id, now).await {
+                Ok(key_pair) => VerificationKey::Stored(Box::new(key_pair)),
+                Err(unknown) => VerificationKey::Remote(state.key_resolver.resolve(key_id, now).await.ok_or(unknown)?),
+            };
+            let supplied = (!request.public_key.is_empty()).then(|| normalize_public_key(&request.public_key).map(|(public_key, _)| public_key));
+            if supplied.is_some_and(|supplied| supplied.as_deref().ok() != Some(key.public_key())) {
+                return Err(unprocessable("public_key does not match the key identified by key_id"));
+            }
+            request.public_key = key.public_key().to_string();
+            Some(key)
+        }
+        None if request.include_chain => return Err(unprocessable("include_chain requires key_id")),
+        None => None,
+    };
+    let include_chain = request.include_chain;
+
+    let Json(mut response) = verify_with_public_key(&state, request, now).await?;
+    match key {
+        Some(VerificationKey::Stored(key_pair)) => attach_verification_key(&state, &mut response, *key_pair, include_chain, now).await?,
+        Some(VerificationKey::Remote(remote)) => attach_remote_key(&mut response, remote),
+        None => {}
+    }
+    Ok(Json(response))
+}
+
+/// The key a verification by `key_id` is checked against
+enum VerificationKey {
+    Stored(Box<KeyPair>),
+    Remote(RemoteKey),
+}
+
+impl VerificationKey {
+    fn public_key(&self) -> &str {
+        match self {
+            VerificationKey::Stored(key_pair) => &key_pair.public_key,
+            VerificationKey::Remote(remote) => &remote.public_key,
+        }
+    }
+}
+
+/// Verify a document signature for a caller that may be unauthenticated
+///
+/// Unauthenticated callers are rate limited by address, and are told only that a public key or
+/// signature is malformed, never which one or why. Authenticated callers get the full detail of
+/// [`verify_signature`].
+pub async fn verify_signature_from(
+    State(state): State<Arc<AppState>>,
+    caller: VerifyCaller,
+    Json(request): Json<VerifySignatureRequest>,
+) -> Response {
+    if let Some(refused) = verify_rate_limited(&state, caller).await {
+        return refused;
+    }
+    let (status, Json(response)) = verify_as(&state, caller, request).await;
+    (status, Json(response)).into_response()
+}
+
+/// Refuses an unauthenticated caller past its verification rate limit
+async fn verify_rate_limited(state: &AppState, caller: VerifyCaller) -> Option<Response> {
+    if caller.authenticated {
+        return None;
+    }
+    let ip = caller.ip?;
+    let retry_after = state.verify_rate_limit.take(ip, state.clock.now()).await.err()?;
+    Some((
+        [(header::RETRY_AFTER, retry_after.to_string())],
+        error_response(StatusCode::TOO_MANY_REQUESTS, ErrorCode::RateLimited, "Too many verification requests"),
+    ).into_response())
+}
+
+/// Verifies a signature, hiding malformed-input detail from unauthenticated callers
+async fn verify_as(state: &Arc<AppState>, caller: VerifyCaller, request: VerifySignatureRequest) -> (StatusCode, Json<VerifySignatureResponse>) {
+    let (status, Json(mut response)) = match verify_signature(State(state.clone()), Json(request)).await {
+        Ok(response) => (StatusCode::OK, response),
+        Err(failure) => failure,
+    };
+    if !caller.authenticated && matches!(response.code, Some(ErrorCode::InvalidKeyFormat | ErrorCode::AmbiguousPublicKey | ErrorCode::InvalidSignatureFormat)) {
+        response.code = Some(ErrorCode::MalformedInput);
+        response.details = None;
+        response.public_key_format = None;
+        if !response.success {
+            response.message = "Public key or signature is malformed".to_string();
+        }
+    }
+    (status, Json(response))
+}
+
+/// Sign a manifest of files as one document
+///
+/// The manifest is rebuilt from the entries, sorted by path, and its canonical hash is signed
+/// through the same path as `/sign`, so key policy, receipts and bundles all apply. Only raw
+/// signatures are produced.
+#[cfg(not(feature = "verifier-only"))]
+pub async fn sign_manifest(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    client: Option<Extension<AuthenticatedClient>>,
+    Json(mut request): Json<SignManifestRequest>,
+) -> Result<Json<SignManifestResponse>, (StatusCode, Json<SignManifestResponse>)> {
+    let failure = |status: StatusCode, e: KeyManagementError| (status, Json(SignManifestResponse {
+        signed: sign_failure(e.code(), e.to_string(), Some(request.key_id)),
+        manifest: None,
+    }));
+    let invalid = |e: KeyManagementError| failure(StatusCode::UNPROCESSABLE_ENTITY, e);
+    let (password, warning) = request_password(&headers, request.password.take())
+        .map_err(|e| failure(StatusCode::BAD_REQUEST, e))?;
+    let manifest = FileManifest::new(request.files.clone()).map_err(invalid)?;
+    let document_hash = manifest.document_hash().map_err(invalid)?;
+
+    let signing = SignDocumentRequest {
+        key_id: request.key_id,
+        document_hash: Some(document_hash),
+        password,
+        valid_until: request.valid_until,
+        bundle: request.bundle,
+        context: request.context,
+        bind_timestamp: request.bind_timestamp,
+        ..Default::default()
+    };
+    let result = match sign(state, signing, None, client.map(|Extension(client)| client.0)).await {
+        Ok(Json(signed)) => Ok(Json(SignManifestResponse { signed, manifest: Some(manifest) })),
+        Err((status, Json(signed))) => Err((status, Json(SignManifestResponse { signed, manifest: None }))),
+    };
+    with_warning(result, warning, |response| &mut response.signed.warnings)
+}
+
+/// Verify a manifest signature and, given recomputed file hashes, check the files against it
+///
+/// The signature result and the file checks are reported separately: a signature over the
+/// original manifest still verifies when a file has since changed.
+pub async fn verify_manifest(
+    State(state): State<Arc<AppState>>,
+    caller: VerifyCaller,
+    Json(request): Json<VerifyManifestRequest>,
+) -> Response {
+    if let Some(refused) = verify_rate_limited(&state, caller).await {
+        return refused;
+    }
+    let now = state.clock.now();
+    let invalid = |e: KeyManagementError| (
+        StatusCode::UNPROCESSABLE_ENTITY,
+        Json(verify_failure(e.code(), e.to_string(), now)),
+    ).into_response();
+    let manifest = match request.manifest.normalized() {
+        Ok(manifest) => manifest,
+        Err(e) => return invalid(e),
+    };
+    let document_hash = match manifest.document_hash() {
+        Ok(document_hash) => document_hash,
+        Err(e) => return invalid(e),
+    };
+    let comparison = match request.files.map(|files| manifest.compare(files)).transpose() {
+        Ok(comparison) => comparison,
+        Err(e) => return invalid(e),
+    };
+
+    let verification = VerifySignatureRequest {
+        public_key: request.public_key,
+        key_id: request.key_id,
+        signature: request.signature,
+        document_hash: Some(document_hash),
+        valid_until: request.valid_until,
+        context: request.context,
+        signing_time: request.signing_time,
+        ..Default::default()
+    };
+    let (status, Json(verification)) = verify_as(&state, caller, verification).await;
+    let files_match = comparison.as_ref().map(|comparison| comparison.all_match());
+    (status, Json(VerifyManifestResponse { verification, comparison, files_match })).into_response()
+}
+
+/// Verify every entry of an uploaded zip archive against a manifest of signatures
+///
+/// The `multipart/form-data` body carries the archive as `archive` and the manifest as
+/// `manifest`. Each entry's SHA-256 is checked as `/verify` checks a `document_hash`, against
+/// the entry's `public_key` or `key_id`. The upload size, entry count and expansion limits refuse
+/// the archive as a whole; anything else is reported per entry.
+pub async fn verify_archive(
+    State(state): State<Arc<AppState>>,
+    caller: VerifyCaller,
+    headers: HeaderMap,
+    multipart: Result<Multipart, MultipartRejection>,
+) -> Response {
+    if let Some(refused) = verify_rate_limited(&state, caller).await {
+        return refused;
+    }
+    let now = state.clock.now();
+    let fail = |e: KeyManagementError| key_error_response(failure_status(&state.config, e.code()), e.to_string(), &e);
+
+    let limit = u64::from(state.config.verify_archive_max_bytes);
+    let declared = headers.get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+    if declared.is_some_and(|length| length > limit) {
+        return fail(KeyManagementError::ContentTooLarge { field: "archive", limit });
+    }
+    let mut multipart = match multipart {
+        Ok(multipart) => multipart,
+        Err(e) => return fail(KeyManagementError::InvalidRequest(format!("Expected a multipart/form-data body: {}", e))),
+    };
+    let (archive, manifest) = match read_archive_upload(&mut multipart, limit).await {
+        Ok(parts) => parts,
+        Err(e) => return fail(e),
+    };
+    let limits = ArchiveLimits {
+        max_entries: state.config.verify_archive_max_entries as usize,
+        max_ratio: u64::from(state.config.verify_archive_max_ratio),
+        max_uncompressed_bytes: u64::from(state.config.verify_archive_max_uncompressed_bytes),
+    };
+    let manifest = match ArchiveManifest::parse(&manifest, limits.max_entries) {
+        Ok(manifest) => manifest,
+        Err(e) => return fail(e),
+    };
+    // Decompression is CPU bound, so it runs off the async workers
+    let digests = tokio::task::spawn_blocking(move || hash_entries(&archive, limits)).await
+        .unwrap_or_else(|e| Err(KeyManagementError::InternalError(format!("Archive hashing failed: {}", e))));
+    let mut digests = match digests {
+        Ok(digests) => digests,
+        Err(e) => return fail(e),
+    };
+
+    let mut checks = Vec::with_capacity(manifest.entries.len());
+    for (path, signed) in manifest.entries {
+        let unchecked = |status: EntryStatus, sha256: Option<String>, message: Option<String>| EntryCheck {
+            path: path.clone(),
+            status,
+            sha256,
+            code: message.is_some().then_some(ErrorCode::ValidationFailed),
+            message,
+        };
+        let check = match digests.remove(&path) {
+            None => unchecked(EntryStatus::Missing, None, None),
+            Some(Err(reason)) => unchecked(EntryStatus::Error, None, Some(reason)),
+            Some(Ok(sha256)) if signed.hash_algorithm != HashAlgorithm::Sha256 => {
+                unchecked(EntryStatus::Error, Some(sha256), Some("hash_algorithm must be sha256".to_string()))
+            }
+            Some(Ok(sha256)) => {
+                let request = VerifySignatureRequest {
+                    public_key: signed.public_key,
+                    key_id: signed.key_id,
+                    signature: signed.signature,
+                    document_hash: Some(sha256.clone()),
+                    valid_until: signed.valid_until,
+                    context: signed.context,
+                    signing_time: signed.signing_time,
+                    ..Default::default()
+                };
+                let (_, Json(response)) = verify_as(&state, caller, request).await;
+                let status = match (response.is_valid, response.success) {
+                    (true, _) => EntryStatus::Valid,
+                    (false, true) => EntryStatus::Invalid,
+                    (false, false) => EntryStatus::Error,
+                };
+                let failed = !response.is_valid;
+                EntryCheck {
+                    path,
+                    status,
+                    sha256: Some(sha256),
+                    code: response.code.filter(|_| failed),
+                    message: failed.then_some(response.message),
+                }
+            }
+        };
+        checks.push(check);
+    }
+    let report = ArchiveReport::new(checks, digests.into_keys().collect());
+    Json(VerifyArchiveResponse { success: true, all_valid: report.all_valid(), report, verification_time: now }).into_response()
+}
+
+/// Reads the `archive` and `manifest` parts of an archive upload, refusing it once they pass
+/// `limit` bytes together
+async fn read_archive_upload(multipart: &mut Multipart, limit: u64) -> Result<(Vec<u8>, Vec<u8>), KeyManagementError> {
+    let malformed = |e: MultipartError| KeyManagementError::InvalidRequest(format!("Malformed multipart body: {}", e));
+    let (mut archive, mut manifest) = (None, None);
+    let mut received = 0u64;
+    while let Some(mut field) = multipart.next_field().await.map_err(malformed)? {
+        let part = match field.name() {
+            Some("archive") => &mut archive,
+            Some("manifest") => &mut manifest,
+            name => return Err(KeyManagementError::ValidationFailed(format!(
+                "Unexpected part '{}'; an upload has only archive and manifest", name.unwrap_or_default(),
+            ))),
+        };
+        if part.is_some() {
+            return Err(KeyManagementError::ValidationFailed("archive and manifest may each be sent once".to_string()));
+        }
+        let mut bytes = Vec::new();
+        while let Some(chunk) = field.chunk().await.map_err(malformed)? {
+            received += chunk.len() as u64;
+            if received > limit {
+                return Err(KeyManagementError::ContentTooLarge { field: "archive", limit });
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+        *part = Some(bytes);
+    }
+    match (archive, manifest) {
+        (Some(archive), Some(manifest)) => Ok((archive, manifest)),
+        _ => Err(KeyManagementError::ValidationFailed("An upload needs both an archive and a manifest part".to_string())),
+    }
+}
+
+/// Sign a payload into a DSSE envelope
+///
+/// The PAE of the payload type and payload is built here and signed as it is, without a
+/// receipt or bundle. The envelope's keyid is the key's fingerprint. DSSE binds no context, so
+/// keys limited to particular contexts cannot sign envelopes.
+#[cfg(not(feature = "verifier-only"))]
+pub async fn sign_dsse(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    client: Option<Extension<AuthenticatedClient>>,
+    Json(mut request): Json<SignDsseRequest>,
+) -> Result<Json<SignDsseResponse>, (StatusCode, Json<SignDsseResponse>)> {
+    let (password, warning) = request_password(&headers, request.password.take())
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(SignDsseResponse { signed: sign_failure(e.code(), e.to_string(), Some(request.key_id)), envelope: None })))?;
+    request.password = password;
+    with_warning(sign_dsse_envelope(state, client, request).await, warning, |response| &mut response.signed.warnings)
+}
+
+/// Signs a DSSE envelope for a request whose password has been resolved
+#[cfg(not(feature = "verifier-only"))]
+async fn sign_dsse_envelope(
+    state: Arc<AppState>,
+    client: Option<Extension<AuthenticatedClient>>,
+    request: SignDsseRequest,
+) -> Result<Json<SignDsseResponse>, (StatusCode, Json<SignDsseResponse>)> {
+    let fail = |status: StatusCode, signed: SignDocumentResponse| (status, Json(SignDsseResponse { signed, envelope: None }));
+    let unprocessable = |message: String| fail(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        sign_failure(ErrorCode::ValidationFailed, message, Some(request.key_id)),
+    );
+
+    let key_pair = match state.storage.get_key(request.key_id).await {
+        Ok(key_pair) => key_pair,
+        Err(e) => return Err(fail(failure_status(&state.config, e.code()), SignDocumentResponse {
+            details: e.details(),
+            ..sign_failure(e.code(), "Key not found or invalid", None)
+        })),
+    };
+    if let Err(e) = environment::check_signing(&state.config, &key_pair.environment) {
+        return Err(fail(failure_status(&state.config, e.code()), SignDocumentResponse {
+            details: e.details(),
+            ..sign_failure(e.code(), e.to_string(), Some(request.key_id))
+        }));
+    }
+    if let Some(allowed) = &key_pair.allowed_contexts {
+        let message = format!("Key {} may only sign with context {}, which DSSE envelopes do not carry", key_pair.id, allowed.join(", "));
+        return Err(fail(StatusCode::FORBIDDEN, SignDocumentResponse {
+            details: Some(serde_json::json!({ "key_id": key_pair.id, "allowed_contexts": allowed })),
+            ..sign_failure(ErrorCode::InsufficientPermissions, message, Some(request.key_id))
+        }));
+    }
+
+    if request.payload_type.is_empty() {
+        return Err(unprocessable("payload_type must not be empty".to_string()));
+    }
+    let limit = u64::from(state.config.sign_max_content_bytes);
+    let payload = dsse::decode_base64(&request.payload_b64, "payload_b64").map_err(|e| unprocessable(e.to_string()))?;
+    if payload.len() as u64 > limit {
+        let e = KeyManagementError::ContentTooLarge { field: "payload_b64", limit };
+        return Err(fail(failure_status(&state.config, e.code()), sign_failure(e.code(), e.to_string(), Some(request.key_id))));
+    }
+    let document_hash = hex::encode(Sha256::digest(dsse::pae(&request.payload_type, &payload)));
+
+    let requester = client.map(|Extension(client)| client.0);
+    check_sign_policy(&state, &key_pair, &document_hash, None, requester.as_deref()).await
+        .map_err(|(status, Json(signed))| fail(status, signed))?;
+    let _permit = signing_permit(&state, &key_pair).await.map_err(|(status, Json(signed))| fail(status, signed))?;
+
+    let (signer, kdf_timing) = match load_signer(&key_pair, request.password.as_deref(), state.hsm.as_deref()) {
+        Ok(loaded) => loaded,
+        Err(e) => return Err(fail(failure_status(&state.config, e.code()), sign_failure(e.code(), "Failed to sign envelope", Some(request.key_id)))),
+    };
+    let keyid = key_pair.fingerprint.clone().or_else(|| public_key_to_fingerprint(&key_pair.public_key).ok()).unwrap_or_default();
+    let envelope = match dsse::sign(signer.as_ref(), &request.payload_type, &payload, &keyid) {
+        Ok(envelope) => envelope,
+        Err(e) => return Err(fail(failure_status(&state.config, e.code()), sign_failure(e.code(), "Failed to sign envelope", Some(request.key_id)))),
+    };
+
+    let signing_time = state.clock.now();
+    let usage_warning = record_sign_usage(&state, request.key_id, signing_time).await;
+    upgrade_legacy_key(&state, &key_pair, request.password.as_deref()).await;
+
+    Ok(Json(SignDsseResponse {
+        signed: SignDocumentResponse {
+            success: true,
+            signature: envelope.signatures.first().map(|signature| signature.sig.clone()),
+            message: "Envelope signed successfully".to_string(),
+            code: None,
+            details: None,
+            key_id: Some(request.key_id),
+            document_hash: Some(document_hash),
+            signing_time: Some(signing_time),
+            valid_until: None,
+            canonical_hash: None,
+            output_format: SignatureOutputFormat::Raw,
+            signature_encoding: SignatureEncoding::Base64,
+            message_encoding: MessageEncoding::HashRawBytes,
+            signature_id: None,
+            bundle: None,
+            context: None,
+            duplicate: false,
+            timestamp_bound: false,
+            warnings: sign_warnings(&state, &key_pair, None).into_iter().chain(usage_warning).collect(),
+            timings: record_kdf_timing(&state, kdf_timing.as_ref(), None),
+            signer_info: Some(SignerInfo::current()),
+        },
+        envelope: Some(envelope),
+    }))
+}
+
+/// Builds a failed DSSE verification response
+fn dsse_failure(code: ErrorCode, message: impl Into<String>, now: chrono::DateTime<chrono::Utc>) -> VerifyDsseResponse {
+    VerifyDsseResponse {
+        success: false,
+        is_valid: false,
+        message: message.into(),
+        code: Some(code),
+        mode: None,
+        payload_type: None,
+        keys: vec![],
+        verification_time: now,
+        warnings: vec![],
+    }
+}
+
+/// Verify a DSSE envelope against stored or supplied keys
+///
+/// Every signature is checked against every key given, whatever its keyid. With several
+/// signatures or several keys the caller must say whether `any` or `all` of the keys must have
+/// signed; a single signature checked against a single key needs no mode.
+pub async fn verify_dsse(
+    State(state): State<Arc<AppState>>,
+    caller: VerifyCaller,
+    Json(request): Json<VerifyDsseRequest>,
+) -> Response {
+    if let Some(refused) = verify_rate_limited(&state, caller).await {
+        return refused;
+    }
+    let now = state.clock.now();
+    let refuse = |status: StatusCode, code: ErrorCode, message: String| (status, Json(dsse_failure(code, message, now))).into_response();
+    let unprocessable = |message: String| refuse(StatusCode::UNPROCESSABLE_ENTITY, ErrorCode::ValidationFailed, message);
+
+    let max_content_bytes = state.config.verify_max_content_bytes as usize;
+    if request.envelope.payload.len() > max_content_bytes {
+        return refuse(StatusCode::PAYLOAD_TOO_LARGE, ErrorCode::ValidationFailed, format!("envelope payload exceeds {} bytes", max_content_bytes));
+    }
+    let key_ids: Vec<Uuid> = request.key_id.into_iter().chain(request.key_ids).collect();
+    let public_keys: Vec<String> = request.public_key.into_iter().chain(request.public_keys).collect();
+    let count = key_ids.len() + public_keys.len();
+    if count == 0 {
+        return unprocessable("At least one of key_id, key_ids, public_key or public_keys is required".to_string());
+    }
+    if count > MAX_VERIFY_CANDIDATES {
+        return unprocessable(format!("At most {} keys may be given, got {}", MAX_VERIFY_CANDIDATES, count));
+    }
+    let mode = match request.mode {
+        Some(mode) => mode,
+        None if count == 1 && request.envelope.signatures.len() == 1 => VerifyMode::Any,
+        None => return unprocessable("mode (any or all) is required when the envelope has several signatures or several keys are given".to_string()),
+    };
+
+    // Stored keys come first, in the order given
+    let mut stored = Vec::with_capacity(key_ids.len());
+    for key_id in key_ids {
+        match state.storage.get_key_record(key_id).await {
+            Ok(key_pair) => stored.push(key_pair),
+            Err(e) => return (StatusCode::NOT_FOUND, Json(dsse_failure(e.code(), e.to_string(), now))).into_response(),
+        }
+    }
+    let mut keys = Vec::with_capacity(count);
+    for public_key in stored.iter().map(|key_pair| &key_pair.public_key).chain(&public_keys) {
+        match decode_public_key(public_key) {
+            Ok(key) => keys.push(key),
+            Err(_) if !caller.authenticated => return unprocessable("Public key is malformed".to_string()),
+            Err(e) => return refuse(StatusCode::UNPROCESSABLE_ENTITY, e.code(), e.to_string()),
+        }
+    }
+    let matches = match request.envelope.verify(&keys) {
+        Ok(matches) => matches,
+        Err(e) => return refuse(StatusCode::UNPROCESSABLE_ENTITY, e.code(), e.to_string()),
+    };
+
+    let mut warnings = vec![];
+    let mut results = Vec::with_capacity(count);
+    for (index, public_key) in stored.iter().map(|key_pair| &key_pair.public_key).chain(&public_keys).enumerate() {
+        let key_pair = stored.get(index);
+        if let Some(key_pair) = key_pair {
+            if matches[index].is_some() {
+                if let Err(e) = state.storage.record_verify(key_pair.id).await {
+                    tracing::warn!("Verification with key {} not recorded: {}", key_pair.id, e);
+                }
+            }
+            warnings.extend(key_warnings(&state.config, key_pair, now));
+        }
+        results.push(DsseKeyResult {
+            key_id: key_pair.map(|key_pair| key_pair.id),
+            fingerprint: public_key_to_fingerprint(public_key).unwrap_or_default(),
+            signature_index: matches[index],
+            state: key_pair.map(|key_pair| key_pair.state(now)),
+        });
+    }
+
+    let is_valid = mode.is_satisfied(&matches);
+    let message = match (is_valid, mode) {
+        (true, _) => "Envelope verified",
+        (false, VerifyMode::Any) => "No given key signed the envelope",
+        (false, VerifyMode::All) => "Not every given key signed the envelope",
+    };
+    Json(VerifyDsseResponse {
+        success: true,
+        is_valid,
+        message: message.to_string(),
+        code: None,
+        mode: Some(mode),
+        payload_type: Some(request.envelope.payload_type),
+        keys: results,
+        verification_time: now,
+        warnings,
+    }).into_response()
+}
+
+/// Looks up a stored key named by a verification request
+async fn find_verification_key(
+    state: &AppState,
+    key_id: Uuid,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<KeyPair, (StatusCode, Json<VerifySignatureResponse>)> {
+    state.storage.get_key_record(key_id).await
+        .map_err(|e| (StatusCode::NOT_FOUND, Json(VerifySignatureResponse {
+            details: e.details(),
+            ..verify_failure(e.code(), e.to_string(), now)
+        })))
+}
+
+/// Counts a verification against a stored key and adds its details, and its certification chain
+/// if requested, to the response
+async fn attach_verification_key(
+    state: &AppState,
+    response: &mut VerifySignatureResponse,
+    mut key_pair: KeyPair,
+    include_chain: bool,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<(), (StatusCode, Json<VerifySignatureResponse>)> {
+    if state.storage.record_verify(key_pair.id).await.is_ok() {
+        key_pair.usage.record_verify(now);
+    }
+    if include_chain {
+        let fingerprint = public_key_to_fingerprint(&key_pair.public_key)
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(verify_failure(ErrorCode::InvalidKeyFormat, e, now))))?;
+        response.certification_chain = Some(state.certifications.chain_for(&fingerprint, now).await);
+    }
+    response.warnings = key_warnings(&state.config, &key_pair, now);
+    response.key_info = Some(KeyInfo::new(key_pair, now));
+    Ok(())
+}
+
+/// Reports the peer a key was resolved from, and its state there, on the response
+///
+/// Certification chains are kept per instance, so none is attached for a remote key.
+fn attach_remote_key(response: &mut VerifySignatureResponse, remote: RemoteKey) {
+    if !remote.status.state.is_usable() {
+        let message = format!("Key {} is {} at {}", remote.status.key_id, remote.status.state.as_str(), remote.peer);
+        response.warnings.push(ApiWarning::new(WarningCode::KeyInactive, message));
+    }
+    response.key_source = Some(remote.source());
+    response.remote_key = Some(remote.status);
+}
+
+/// Verifies a signature against each candidate key in turn, reporting the first that validates
+///
+/// Stored `key_ids` are tried before `public_keys`. Only the matching stored key, if any, has
+/// its verification counted.
+async fn verify_against_candidates(
+    state: &AppState,
+    mut request: VerifySignatureRequest,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<Json<VerifySignatureResponse>, (StatusCode, Json<VerifySignatureResponse>)> {
+    let unprocessable = |message: String| (StatusCode::UNPROCESSABLE_ENTITY, Json(verify_failure(ErrorCode::ValidationFailed, message, now)));
+
+    if !request.public_key.is_empty() || request.key_id.is_some() {
+        return Err(unprocessable("key_ids and public_keys cannot be combined with public_key or key_id".to_string()));
+    }
+    let count = request.key_ids.len() + request.public_keys.len();
+    if count > MAX_VERIFY_CANDIDATES {
+        return Err(unprocessable(format!("At most {} candidate keys may be given, got {}", MAX_VERIFY_CANDIDATES, count)));
+    }
+    if request.include_chain && request.key_ids.is_empty() {
+        return Err(unprocessable("include_chain requires key_id or key_ids".to_string()));
+    }
+
+    let mut candidates = Vec::with_capacity(count);
+    for key_id in std::mem::take(&mut request.key_ids) {
+        let key_pair = find_verification_key(state, key_id, now).await?;
+        candidates.push((key_pair.public_key.clone(), Some(key_pair)));
+    }
+    candidates.extend(std::mem::take(&mut request.public_keys).into_iter().map(|public_key| (public_key, None)));
+
+    let mut unmatched = None;
+    for (index, (public_key, key_pair)) in candidates.into_iter().enumerate() {
+        let candidate = VerifySignatureRequest { public_key: public_key.clone(), ..request.clone() };
+        let Json(mut response) = verify_with_public_key(state, candidate, now).await?;
+        if !response.cryptographically_valid {
+            unmatched.get_or_insert(response);
+            continue;
+        }
+
+        response.matched_candidate = Some(MatchedCandidate {
+            index,
+            key_id: key_pair.as_ref().map(|key_pair| key_pair.id),
+            fingerprint: normalize_public_key(&public_key).ok().and_then(|(public_key, _)| public_key_to_fingerprint(&public_key).ok()),
+        });
+        if let Some(key_pair) = key_pair {
+            attach_verification_key(state, &mut response, key_pair, request.include_chain, now).await?;
+        }
+        return Ok(Json(response));
+    }
+
+    // The list is non-empty, so at least one candidate was tried
+    let response = unmatched.expect("at least one candidate");
+    Ok(Json(VerifySignatureResponse {
+        message: format!("Signature does not match any of the {} candidate keys", count),
+        code: None,
+        details: None,
+        ..response
+    }))
+}
+
+/// Verifies a signature against the public key supplied in the request
+async fn verify_with_public_key(
+    state: &AppState,
+    request: VerifySignatureRequest,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<Json<VerifySignatureResponse>, (StatusCode, Json<VerifySignatureResponse>)> {
+    if let Err(e) = validate_context(request.context.as_deref()) {
+        return Err((StatusCode::UNPROCESSABLE_ENTITY, Json(verify_failure(e.code(), e.to_string(), now))));
+    }
+    if minisign::is_minisign_signature(&request.signature) {
+        return verify_file_format(state, request, now, SignatureOutputFormat::Minisign).await;
+    }
+    if sshsig::is_sshsig_signature(&request.signature) {
+        return verify_file_format(state, request, now, SignatureOutputFormat::Sshsig).await;
+    }
+
+    // Handle document content if provided
+    let document_hash = match resolve_document_hash(
+        request.document_hash.as_deref(),
+        request.document_content.as_deref(),
+        request.content_type,
+    ) {
+        Ok(hash) => hash,
+        Err(KeyManagementError::ValidationFailed(message)) => {
+            return Err((StatusCode::UNPROCESSABLE_ENTITY, Json(verify_failure(ErrorCode::ValidationFailed, message, now))));
+        }
+        Err(_) => {
+            return Err((failure_status(&state.config, ErrorCode::InvalidRequest), Json(VerifySignatureResponse {
+                valid_until: request.valid_until,
+                ..verify_failure(ErrorCode::InvalidRequest, "Either document_hash or document_content must be provided", now)
+            })));
+        }
+    };
+
+    // The key may be supplied as PEM, an OpenSSH line or a JWK; it is checked as its raw bytes
+    let (public_key, public_key_format, key_error) = match normalize_public_key(&request.public_key) {
+        Ok((public_key, format)) => (public_key, Some(format), None),
+        Err(e) => (request.public_key, None, Some(e)),
+    };
+
+    // Create modified request with the hash
+    let modified_request = VerifySignatureRequest {
+        document_hash: Some(document_hash.clone()),
+        public_key,
+        signature: request.signature,
+        // A raw-message signature is checked against the content itself
+        document_content: request.document_content.filter(|_| request.message_encoding == MessageEncoding::RawMessage),
+        valid_until: request.valid_until,
+        content_type: request.content_type,
+        namespace: None,
+        key_id: None,
+        include_chain: false,
+        context: request.context.clone(),
+        key_ids: Vec::new(),
+        public_keys: Vec::new(),
+        signing_time: request.signing_time,
+        signature_encoding: request.signature_encoding,
+        message_encoding: request.message_encoding,
+    };
+
+    // Verify the signature, or reuse the result of an identical recent verification; every input
+    // the signed message binds is part of the cache key
+    // A malformed signature is reported as invalid, with a code saying why
+    let binding = |time: Option<chrono::DateTime<chrono::Utc>>| time.map(|time| time.to_rfc3339()).unwrap_or_default();
+    let key = cache_key("raw", &[
+        modified_request.public_key.as_bytes(),
+        document_hash.as_bytes(),
+        modified_request.signature.as_bytes(),
+        normalize_context(modified_request.context.as_deref()).unwrap_or_default().as_bytes(),
+        binding(modified_request.valid_until).as_bytes(),
+        binding(modified_request.signing_time).as_bytes(),
+        modified_request.signature_encoding.map(SignatureEncoding::as_str).unwrap_or_default().as_bytes(),
+        modified_request.message_encoding.as_str().as_bytes(),
+    ]);
+    let verified = match key_error {
+        Some(e) => Err(e),
+        None => state.verification_cache.get_or_verify(key, now, || crate::key_verification::verify_signature(&modified_request)),
+    };
+    let (cryptographically_valid, format_error) = match verified {
+        Ok(valid) => (valid, None),
+        Err(e @ (KeyManagementError::InvalidSignatureFormat(_) | KeyManagementError::InvalidKeyFormat(_) | KeyManagementError::AmbiguousPublicKey(_))) => (false, Some(e)),
+        Err(_) => (false, None),
+    };
+    let expired_signature = cryptographically_valid && is_signature_window_expired(request.valid_until, now);
+    let is_valid = cryptographically_valid && !expired_signature;
+
+    let message = if is_valid {
+        "Signature is valid".to_string()
+    } else if expired_signature {
+        "Signature is authentic but its validity window has expired".to_string()
+    } else {
+        "Signature is invalid".to_string()
+    };
+
+    let canonical_hash = match request.content_type {
+        DocumentContentType::JsonJcs => Some(document_hash.clone()),
+        DocumentContentType::Text => None,
+    };
+
+    Ok(Json(VerifySignatureResponse {
+        success: true,
+        is_valid,
+        message,
+        code: format_error.as_ref().map(KeyManagementError::code),
+        details: format_error.map(|e| serde_json::json!({ "reason": e.to_string() })),
+        key_info: None, // We don't have key info in this context
+        verification_time: Some(now),
+        document_hash: Some(document_hash),
+        cryptographically_valid,
+        expired_signature,
+        valid_until: request.valid_until,
+        canonical_hash,
+        context: normalize_context(request.context.as_deref()).map(str::to_string),
+        certification_chain: None,
+        matched_candidate: None,
+        signing_time: request.signing_time,
+        warnings: vec![],
+        key_source: None,
+        remote_key: None,
+        public_key_format,
+    }))
+}
+
+/// Verifies a minisign or sshsig signature file against document content
+async fn verify_file_format(
+    state: &AppState,
+    request: VerifySignatureRequest,
+    now: chrono::DateTime<chrono::Utc>,
+    format: SignatureOutputFormat,
+) -> Result<Json<VerifySignatureResponse>, (StatusCode, Json<VerifySignatureResponse>)> {
+    let unprocessable = |message: String| (StatusCode::UNPROCESSABLE_ENTITY, Json(verify_failure(ErrorCode::ValidationFailed, message, now)));
+
+    let Some(content) = &request.document_content else {
+        return Err(unprocessable(format!("{} signatures require document_content", format.as_str())));
+    };
+    if request.valid_until.is_some() {
+        return Err(unprocessable(format!("{} signatures do not support valid_until", format.as_str())));
+    }
+    if normalize_context(request.context.as_deref()).is_some() {
+        return Err(unprocessable(format!("{} signatures do not support context", format.as_str())));
+    }
+    if request.signing_time.is_some() {
+        return Err(unprocessable(format!("{} signatures do not support signing_time", format.as_str())));
+    }
+    if !request.message_encoding.is_default() {
+        return Err(unprocessable(format!("{} signatures do not support message_encoding", format.as_str())));
+    }
+    let namespace = sshsig_namespace(request.namespace.as_deref()).map_err(unprocessable)?;
+    let bytes = content_bytes(content, request.content_type).map_err(|e| unprocessable(e.to_string()))?;
+
+    // Accept the public key in minisign framing (which pins the key id), or in any format /verify takes
+    let (public_key, public_key_format) = if minisign::is_minisign_public_key(&request.public_key) {
+        (minisign::parse_public_key(&request.public_key).map(|(key, id)| (key, Some(id))), None)
+    } else {
+        match parse_supplied_public_key(&request.public_key) {
+            Ok((key, format)) => (Ok((key, None)), Some(format)),
+            Err(e) => (Err(e), None),
+        }
+    };
+    // Reuse the result of an identical recent verification; malformed input is never cached
+    let key = cache_key(format.as_str(), &[request.public_key.as_bytes(), namespace.as_bytes(), &bytes, request.signature.as_bytes()]);
+    let is_valid = state.verification_cache.get_or_verify(key, now, || match (public_key, format) {
+        (Ok((public_key, _)), SignatureOutputFormat::Sshsig) => sshsig::verify(&public_key, namespace, &bytes, &request.signature),
+        (Ok((public_key, key_id)), _) => minisign::verify(&public_key, key_id, &bytes, &request.signature),
+        (Err(e), _) => Err(e),
+    }).unwrap_or(false);
+
+    let document_hash = create_document_hash(&String::from_utf8_lossy(&bytes));
+    let canonical_hash = match request.content_type {
+        DocumentContentType::JsonJcs => Some(document_hash.clone()),
+        DocumentContentType::Text => None,
+    };
+
+    Ok(Json(VerifySignatureResponse {
+        success: true,
+        is_valid,
+        message: if is_valid { "Signature is valid" } else { "Signature is invalid" }.to_string(),
+        code: None,
+        details: None,
+        key_info: None,
+        verification_time: Some(now),
+        document_hash: Some(document_hash),
+        cryptographically_valid: is_valid,
+        expired_signature: false,
+        valid_until: None,
+        canonical_hash,
+        context: None,
+        certification_chain: None,
+        matched_candidate: None,
+        signing_time: None,
+        warnings: vec![],
+        key_source: None,
+        remote_key: None,
+        public_key_format,
+    }))
+}
+
+/// Benchmark the host and suggest KDF parameters for a target derivation time
+#[cfg(not(feature = "verifier-only"))]
+pub async fn kdf_calibration(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<KdfCalibrationQuery>,
+) -> Result<Json<KdfCalibrationResponse>, (StatusCode, Json<KdfCalibrationResponse>)> {
+    let target_ms = query.target_ms.unwrap_or(DEFAULT_KDF_TARGET_MS);
+    let current = state.config.kdf;
+    let failure = |code: ErrorCode, message: String| KdfCalibrationResponse {
+        success: false,
+        target_ms,
+        current,
+        suggested: None,
+        measured_ms: None,
+        message,
+        code: Some(code),
+        details: None,
+    };
+
+    if target_ms == 0 || target_ms > MAX_KDF_TARGET_MS {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(failure(ErrorCode::ValidationFailed, format!("target_ms must be between 1 and {}", MAX_KDF_TARGET_MS))),
+        ));
+    }
+
+    // Benchmarking burns CPU for up to target_ms, so keep it off the async workers
+    let result = tokio::task::spawn_blocking(move || {
+        let suggested = calibrate_kdf(current, target_ms)?;
+        let started = std::time::Instant::now();
+        suggested.derive_key(b"inkan-kdf-calibration", &[0u8; 32])?;
+        Ok::<_, KeyManagementError>((suggested, started.elapsed().as_millis() as u64))
+    }).await;
+
+    match result {
+        Ok(Ok((suggested, measured_ms))) => Ok(Json(KdfCalibrationResponse {
+            success: true,
+            target_ms,
+            current,
+            suggested: Some(suggested),
+            measured_ms: Some(measured_ms),
+            message: "Calibration completed".to_string(),
+            code: None,
+            details: None,
+        })),
+        Ok(Err(e)) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(failure(e.code(), e.to_string())))),
+        Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, Json(failure(ErrorCode::InternalError, "Calibration task failed".to_string())))),
+    }
+}
+
+/// Group stored keys by the KDF parameters protecting them
+///
+/// Keys still encrypted under older, cheaper parameters show up outside the `current` group.
+#[cfg(not(feature = "verifier-only"))]
+pub async fn kdf_report(State(state): State<Arc<AppState>>) -> Json<KdfReportResponse> {
+    let keys: Vec<KeyPair> = state.storage.entries().await.into_iter().map(|(_, key_pair)| key_pair).collect();
+    let groups = kdf_stats::kdf_report(&keys, &state.config.kdf);
+    let outdated_keys = groups.iter()
+        .filter(|group| !group.current && matches!(group.protection, KeyProtection::Envelope | KeyProtection::Legacy))
+        .map(|group| group.key_count)
+        .sum();
+    Json(KdfReportResponse {
+        success: true,
+        current: state.config.kdf,
+        outdated_keys,
+        message: format!("{} of {} keys use outdated KDF parameters", outdated_keys, keys.len()),
+        groups,
+    })
+}
+
+/// Find encrypted keys with shared or short salts, the legacy layout, or outdated KDF parameters
+#[cfg(not(feature = "verifier-only"))]
+pub async fn reencrypt_scan(State(state): State<Arc<AppState>>) -> Response {
+    let keys: Vec<KeyPair> = state.storage.entries().await.into_iter().map(|(_, key_pair)| key_pair).collect();
+    let (scanned, affected) = scan_envelopes(&keys, &state.config.kdf);
+    Json(ReencryptScanResponse {
+        success: true,
+        scanned,
+        current: state.config.kdf,
+        message: format!("{} of {} encrypted keys should be re-encrypted", affected.len(), scanned),
+        affected,
+    }).into_response()
+}
+
+/// Re-encrypt the keys a scan finds with a fresh salt and nonce under the current KDF parameters
+///
+/// Only keys given a password are rewrapped; each rewrap keeps the key material, is recorded in
+/// the key's envelope history and is logged with the client that requested it. A wrong password
+/// leaves that key as it was and the others are still rewrapped.
+#[cfg(not(feature = "verifier-only"))]
+pub async fn reencrypt_keys(
+    State(state): State<Arc<AppState>>,
+    client: Option<AuthenticatedClient>,
+    Json(request): Json<ReencryptRequest>,
+) -> Response {
+    let current = state.config.kdf;
+    let keys: Vec<KeyPair> = state.storage.entries().await.into_iter().map(|(_, key_pair)| key_pair).collect();
+    let (_, affected) = scan_envelopes(&keys, &current);
+    let mut response = ReencryptResponse {
+        success: true,
+        rewrapped: vec![],
+        failed: vec![],
+        missing_password: vec![],
+        not_affected: request.passwords.keys().filter(|key_id| !affected.iter().any(|finding| finding.key_id == **key_id)).copied().collect(),
+        message: String::new(),
+    };
+    let actor = client.map(|client| client.0);
+
+    for finding in &affected {
+        let Some(password) = request.passwords.get(&finding.key_id).cloned() else {
+            response.missing_password.push(finding.key_id);
+            continue;
+        };
+        let failure = |e: KeyManagementError| ReencryptFailure { key_id: finding.key_id, code: e.code(), message: e.to_string() };
+        let key_pair = match state.storage.get_key_record(finding.key_id).await {
+            Ok(key_pair) => key_pair,
+            Err(e) => {
+                response.failed.push(failure(e));
+                continue;
+            }
+        };
+        let version = key_pair.envelope_version() + 1;
+        // Each key costs a full KDF derivation to open and another to seal
+        let rewrapped = tokio::task::spawn_blocking(move || rewrap_private_key(&key_pair, &password, &current)).await
+            .unwrap_or_else(|_| Err(KeyManagementError::InternalError("Re-encryption task failed".to_string())));
+        let revision = EnvelopeRevision {
+            version,
+            rewrapped_at: state.clock.now(),
+            actor: actor.clone(),
+            weaknesses: finding.weaknesses.clone(),
+            previous_kdf: finding.kdf.unwrap_or_default(),
+            kdf: current,
+        };
+        let stored = match rewrapped {
+            Ok(private_key) => state.storage.rewrap_private_key(finding.key_id, private_key, revision).await,
+            Err(e) => Err(e),
+        };
+        match stored {
+            Ok(_) => {
+                tracing::info!(
+                    "Key {} re-encrypted into envelope version {} by {} for {:?}",
+                    finding.key_id,
+                    version,
+                    actor.as_deref().unwrap_or("an unauthenticated caller"),
+                    finding.weaknesses,
+                );
+                response.rewrapped.push(RewrappedKey { key_id: finding.key_id, version, weaknesses: finding.weaknesses.clone() });
+            }
+            Err(e) => {
+                tracing::warn!("Failed to re-encrypt key {}: {}", finding.key_id, e);
+                response.failed.push(failure(e));
+            }
+        }
+    }
+
+    response.success = response.failed.is_empty();
+    response.message = format!(
+        "{} of {} affected keys re-encrypted, {} failed, {} without a password",
+        response.rewrapped.len(),
+        affected.len(),
+        response.failed.len(),
+        response.missing_password.len(),
+    );
+    Json(response).into_response()
+}
+
+/// Validate the keystore and optionally repair it
+///
+/// With `stream: true` the response is newline-delimited JSON: one `progress` line per key
+/// followed by a final `summary` line.
+#[cfg(not(feature = "verifier-only"))]
+pub async fn validate_keystore(
+    State(state): State<Arc<AppState>>,
+    deadline: Option<Extension<Deadline>>,
+    Json(request): Json<ValidateKeystoreRequest>,
+) -> Response {
+    let deadline = request_deadline(deadline);
+    if !request.stream {
+        return match crate::integrity::validate_keystore(&state.storage, request.repair, &deadline, |_, _, _| {}).await {
+            Ok(response) => Json(response).into_response(),
+            Err(e) => {
+                let (status, checked) = match e {
+                    KeyManagementError::DeadlineExceeded { completed, .. } => (StatusCode::GATEWAY_TIMEOUT, completed),
+                    _ => (StatusCode::INTERNAL_SERVER_ERROR, 0),
+                };
+                (status, Json(ValidateKeystoreResponse {
+                    success: false,
+                    checked,
+                    errors: 0,
+                    warnings: 0,
+                    repaired: 0,
+                    quarantined: 0,
+                    reports: vec![],
+                    message: e.to_string(),
+                    code: Some(e.code()),
+                    details: e.details(),
+                })).into_response()
+            }
+        };
+    }
+
+    // The stream outlives the request's handler, so a client that stops reading cancels it here
+    let (sender, receiver) = tokio::sync::mpsc::unbounded_channel::<String>();
+    tokio::spawn(async move {
+        let progress_sender = sender.clone();
+        let result = crate::integrity::validate_keystore(&state.storage, request.repair, &deadline, |checked, total, report| {
+            let line = serde_json::json!({ "type": "progress", "checked": checked, "total": total, "report": report });
+            if progress_sender.send(format!("{}\n", line)).is_err() {
+                deadline.cancel();
+            }
+        }).await;
+        let line = match result {
+            Ok(summary) => serde_json::json!({ "type": "summary", "result": summary }),
+            Err(e) => serde_json::json!({ "type": "error", "code": e.code(), "message": e.to_string(), "details": e.details() }),
+        };
+        let _ = sender.send(format!("{}\n", line));
+    });
+
+    let body = tokio_stream::StreamExt::map(
+        tokio_stream::wrappers::UnboundedReceiverStream::new(receiver),
+        Ok::<_, std::convert::Infallible>,
+    );
+    (
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        axum::body::Body::from_stream(body),
+    ).into_response()
+}
+
+/// Update key information
+///
+/// `client` is the client that signed the request, when request signing is enabled. Changing the
+/// signing contexts a key is limited to takes an admin client, like the admin routes.
+#[cfg(not(feature = "verifier-only"))]
+pub async fn update_key(
+    State(state): State<Arc<AppState>>,
+    Path(key_id): Path<Uuid>,
+    Json(mut request): Json<UpdateKeyRequest>,
+    client: Option<AuthenticatedClient>,
+) -> Result<Json<UpdateKeyResponse>, (StatusCode, Json<UpdateKeyResponse>)> {
+    let failure = |status: StatusCode, code: ErrorCode, message: String, errors: Vec<FieldError>| {
+        (status, Json(UpdateKeyResponse {
+            success: false,
+            key_info: None,
+            message,
+            code: Some(code),
+            details: field_error_details(&errors),
+            errors,
+            warnings: vec![],
+        }))
+    };
+    let key_failure = |e: KeyManagementError| {
+        let details = e.details();
+        let (status, json) = failure(StatusCode::NOT_FOUND, e.code(), e.to_string(), vec![]);
+        // Lifecycle and export refusals keep their own status; any other failure means the key was not found
+        let status = if matches!(e, KeyManagementError::InvalidTransition { .. } | KeyManagementError::InsufficientPermissions(_)) { StatusCode::from(e) } else { status };
+        (status, Json(UpdateKeyResponse { details, ..json.0 }))
+    };
+
+    let current = state.storage.get_key_record(key_id).await.map_err(key_failure)?;
+    // With request signing off there are no clients to tell apart, so every caller is an admin
+    let client = client.as_ref().map(|AuthenticatedClient(client_id)| client_id.as_str());
+    let admin_scope = !state.request_auth.is_enabled() || has_admin_scope(&state.config, client);
+    if request.allowed_contexts.is_some() && !admin_scope {
+        let message = format!("Only admin clients may change the signing contexts of key {}", key_id);
+        return Err(failure(StatusCode::FORBIDDEN, ErrorCode::InsufficientPermissions, message, vec![]));
+    }
+    // An update that sets nothing leaves the key, and the keystore file, untouched
+    if request.is_empty() {
+        return Ok(Json(UpdateKeyResponse {
+            success: true,
+            key_info: Some(KeyInfo::new(current, state.clock.now())),
+            message: "Nothing to update".to_string(),
+            code: None,
+            details: None,
+            errors: vec![],
+            warnings: vec![],
+        }));
+    }
+    if let Err(errors) = validate_update_request(&mut request, &current, &state.config, state.clock.now()) {
+        let message = errors.iter()
+            .map(|error| format!("{}: {}", error.field, error.message))
+            .collect::<Vec<_>>()
+            .join("; ");
+        return Err(failure(StatusCode::UNPROCESSABLE_ENTITY, ErrorCode::ValidationFailed, message, errors));
+    }
+
+    let expiry_changed = request.expires_at.is_some();
+    match state.storage.update_key(key_id, request, admin_scope).await {
+        Ok(key_pair) => {
+            let mut warnings = key_warnings(&state.config, &key_pair, state.clock.now());
+            // A new expiry the key now warns about is tied to the field that set it
+            for warning in warnings.iter_mut().filter(|warning| expiry_changed && warning.code == WarningCode::KeyExpiringSoon) {
+                warning.field = Some("expires_at".to_string());
+            }
+            warnings.extend(persistence_warning(&state));
+            let key_info = KeyInfo::new(key_pair, state.clock.now());
+
+            Ok(Json(UpdateKeyResponse {
+                success: true,
+                key_info: Some(key_info),
+                message: "Key updated successfully".to_string(),
+                code: None,
+                details: None,
+                errors: vec![],
+                warnings,
+            }))
+        }
+        Err(e) => Err(key_failure(e)),
+    }
+}
+
+/// Revoke a key, either now or at a scheduled time
+///
+/// `revoked_by` is the client that signed the request, when request signing is enabled.
+#[cfg(not(feature = "verifier-only"))]
+pub async fn revoke_key(
+    State(state): State<Arc<AppState>>,
+    Path(key_id): Path<Uuid>,
+    Json(request): Json<RevokeKeyRequest>,
+    revoked_by: Option<AuthenticatedClient>,
+) -> Result<Json<RevokeKeyResponse>, (StatusCode, Json<RevokeKeyResponse>)> {
+    let failure = |status: StatusCode, code: ErrorCode, message: String| {
+        (status, Json(RevokeKeyResponse {
+            success: false,
+            key_info: None,
+            message,
+            code: Some(code),
+            details: Some(serde_json::json!({ "key_id": key_id })),
+            revocation_time: None,
+            scheduled: false,
+        }))
+    };
+
+    if request.key_id != key_id {
+        return Err(failure(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorCode::ValidationFailed,
+            format!("Body key_id {} does not match path key {}", request.key_id, key_id),
+        ));
+    }
+
+    let now = state.clock.now();
+    let (key_pair, revocation_time, scheduled) = if request.immediate {
+        let actor = revoked_by.map(|AuthenticatedClient(client_id)| client_id);
+        let (key_pair, _) = state.storage.transition_key(key_id, KeyState::Revoked, actor, request.reason).await
+            .map_err(|e| {
+                let details = e.details();
+                let (status, json) = failure(StatusCode::NOT_FOUND, e.code(), e.to_string());
+                let status = if matches!(e, KeyManagementError::InvalidTransition { .. }) { StatusCode::CONFLICT } else { status };
+                (status, Json(RevokeKeyResponse { details, ..json.0 }))
+            })?;
+        (key_pair, now, false)
+    } else {
+        let Some(effective_at) = request.effective_at else {
+            return Err(failure(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                ErrorCode::ValidationFailed,
+                "effective_at is required when immediate is false".to_string(),
+            ));
+        };
+        if effective_at <= now {
+            return Err(failure(StatusCode::UNPROCESSABLE_ENTITY, ErrorCode::ValidationFailed, "effective_at must be in the future".to_string()));
+        }
+
+        let current = state.storage.get_key_record(key_id).await
+            .map_err(|e| failure(StatusCode::NOT_FOUND, e.code(), e.to_string()))?;
+        if current.state(now) == KeyState::Revoked {
+            let (status, json) = failure(StatusCode::CONFLICT, ErrorCode::KeyAlreadyRevoked, format!("Key {} is already revoked", key_id));
+            let details = serde_json::json!({ "key_id": key_id, "key_name": current.name });
+            return Err((status, Json(RevokeKeyResponse { details: Some(details), ..json.0 })));
+        }
+
+        let key_pair = state.storage.schedule_revocation(key_id, effective_at).await
+            .map_err(|e| failure(StatusCode::INTERNAL_SERVER_ERROR, e.code(), e.to_string()))?;
+        (key_pair, effective_at, true)
+    };
+
+    Ok(Json(RevokeKeyResponse {
+        success: true,
+        key_info: Some(KeyInfo::new(key_pair, state.clock.now())),
+        message: if scheduled {
+            "Key revocation scheduled".to_string()
+        } else {
+            "Key revoked successfully".to_string()
+        },
+        code: None,
+        details: None,
+        revocation_time: Some(revocation_time),
+        scheduled,
+    }))
+}
+
+/// Soft-delete a key: it leaves the keystore for the deleted-keys file and can be restored
+///
+/// `deleted_by` is the client that signed the request, when request signing is enabled.
+#[cfg(not(feature = "verifier-only"))]
+pub async fn delete_key(
+    State(state): State<Arc<AppState>>,
+    Path(key_id): Path<Uuid>,
+    deleted_by: Option<AuthenticatedClient>,
+) -> Result<Json<KeyRemovalResponse>, (StatusCode, Json<KeyRemovalResponse>)> {
+    let deleted_by = deleted_by.map(|AuthenticatedClient(client_id)| client_id);
+    match state.storage.soft_delete_key(key_id, deleted_by).await {
+        Ok(deleted) => {
+            tracing::info!(
+                "Key {} deleted by {}; restorable from {}",
+                key_id,
+                deleted.deleted_by.as_deref().unwrap_or("an unauthenticated caller"),
+                state.storage.deleted_path(),
+            );
+            Ok(Json(KeyRemovalResponse {
+                success: true,
+                key_info: Some(KeyInfo::new(deleted.key_pair, state.clock.now())),
+                message: "Key deleted successfully".to_string(),
+                code: None,
+                details: None,
+                deleted_at: Some(deleted.deleted_at),
+                deleted_by: deleted.deleted_by,
+            }))
+        }
+        Err(e) => Err(removal_failure(e, key_id)),
+    }
+}
+
+/// Restore a soft-deleted key with its original id, fingerprint, state and usage
+///
+/// Refused if the key would now break the keystore's constraints: an active key has taken its
+/// name, or the keystore is at its key quota or hard limit.
+#[cfg(not(feature = "verifier-only"))]
+pub async fn restore_key(
+    State(state): State<Arc<AppState>>,
+    Path(key_id): Path<Uuid>,
+) -> Result<Json<KeyRemovalResponse>, (StatusCode, Json<KeyRemovalResponse>)> {
+    let fail = |e: KeyManagementError| removal_failure(e, key_id);
+    let now = state.clock.now();
+
+    let deleted = state.storage.deleted_keys().await.map_err(fail)?
+        .into_iter()
+        .rfind(|record| record.key_pair.id == key_id)
+        .ok_or(KeyManagementError::KeyNotFound(key_id))
+        .map_err(fail)?;
+
+    let stored = state.storage.list_keys().await;
+    let name = deleted.key_pair.name.trim();
+    let lifecycle = deleted.key_pair.state_before_deletion().and_then(Lifecycle::for_target).unwrap_or(deleted.key_pair.lifecycle);
+    let restoring = KeyPair { lifecycle, ..deleted.key_pair.clone() };
+    if restoring.state(now).is_usable() && stored.iter().any(|key| key.is_active && key.name.trim().eq_ignore_ascii_case(name)) {
+        return Err(fail(KeyManagementError::RestoreConflict(format!("an active key named '{}' now exists", name))));
+    }
+    if let Some(limit) = state.config.max_keys.filter(|limit| stored.len() >= *limit) {
+        return Err(fail(KeyManagementError::KeystoreFull(format!("the key quota of {} keys is reached", limit))));
+    }
+    state.capacity.reserve(&state.storage, now).await.map_err(fail)?;
+
+    let restored = state.storage.restore_deleted_key(key_id).await.map_err(fail)?;
+    tracing::info!("Key {} restored; it was deleted at {}", key_id, restored.deleted_at.to_rfc3339());
+    Ok(Json(KeyRemovalResponse {
+        success: true,
+        key_info: Some(KeyInfo::new(restored.key_pair, now)),
+        message: "Key restored successfully".to_string(),
+        code: None,
+        details: None,
+        deleted_at: Some(restored.deleted_at),
+        deleted_by: restored.deleted_by,
+    }))
+}
+
+/// Suspend a key: it cannot sign until resumed
+///
+/// `suspended_by` is the client that signed the request, when request signing is enabled.
+#[cfg(not(feature = "verifier-only"))]
+pub async fn suspend_key(
+    State(state): State<Arc<AppState>>,
+    Path(key_id): Path<Uuid>,
+    Json(request): Json<KeyTransitionRequest>,
+    suspended_by: Option<AuthenticatedClient>,
+) -> Result<Json<KeyTransitionResponse>, (StatusCode, Json<KeyTransitionResponse>)> {
+    move_key(&state, key_id, KeyState::Suspended, request, suspended_by, "Key suspended successfully").await
+}
+
+/// Resume a suspended key
+///
+/// `resumed_by` is the client that signed the request, when request signing is enabled.
+#[cfg(not(feature = "verifier-only"))]
+pub async fn resume_key(
+    State(state): State<Arc<AppState>>,
+    Path(key_id): Path<Uuid>,
+    Json(request): Json<KeyTransitionRequest>,
+    resumed_by: Option<AuthenticatedClient>,
+) -> Result<Json<KeyTransitionResponse>, (StatusCode, Json<KeyTransitionResponse>)> {
+    move_key(&state, key_id, KeyState::Active, request, resumed_by, "Key resumed successfully").await
+}
+
+#[cfg(not(feature = "verifier-only"))]
+async fn move_key(
+    state: &AppState,
+    key_id: Uuid,
+    to: KeyState,
+    request: KeyTransitionRequest,
+    client: Option<AuthenticatedClient>,
+    message: &str,
+) -> Result<Json<KeyTransitionResponse>, (StatusCode, Json<KeyTransitionResponse>)> {
+    let actor = client.map(|AuthenticatedClient(client_id)| client_id);
+    match state.storage.transition_key(key_id, to, actor, request.reason).await {
+        Ok((key_pair, event)) => Ok(Json(KeyTransitionResponse {
+            success: true,
+            key_info: Some(KeyInfo::new(key_pair, state.clock.now())),
+            message: message.to_string(),
+            code: None,
+            details: None,
+            transition: Some(event),
+        })),
+        Err(e) => {
+            let (code, message, details) = (e.code(), e.to_string(), e.details());
+            Err((StatusCode::from(e), Json(KeyTransitionResponse {
+                success: false,
+                key_info: None,
+                message,
+                code: Some(code),
+                details,
+                transition: None,
+            })))
+        }
+    }
+}
+
+#[cfg(not(feature = "verifier-only"))]
+fn removal_failure(e: KeyManagementError, key_id: Uuid) -> (StatusCode, Json<KeyRemovalResponse>) {
+    let (code, message) = (e.code(), e.to_string());
+    (StatusCode::from(e), Json(KeyRemovalResponse {
+        success: false,
+        key_info: None,
+        message,
+        code: Some(code),
+        details: Some(serde_json::json!({ "key_id": key_id })),
+        deleted_at: None,
+        deleted_by: None,
+    }))
+}
+
+/// Seal a key's private half to another instance's transport key
+///
+/// The envelope is imported on the destination with `POST /keys/import-wrapped`. A
+/// password-protected key is unlocked with `password`; HSM keys and ephemeral tombstones have
+/// no private key to export.
+#[cfg(not(feature = "verifier-only"))]
+pub async fn export_wrapped_key(
+    State(state): State<Arc<AppState>>,
+    Path(key_id): Path<Uuid>,
+    Json(request): Json<ExportWrappedKeyRequest>,
+) -> Result<Json<WrappedKeyResponse>, (StatusCode, Json<WrappedKeyResponse>)> {
+    let fail = |e: KeyManagementError| {
+        let (code, message) = (e.code(), e.to_string());
+        (StatusCode::from(e), Json(WrappedKeyResponse {
+            success: false,
+            message,
+            code: Some(code),
+            details: Some(serde_json::json!({ "key_id": key_id })),
+            envelope: None,
+        }))
+    };
+
+    let key_pair = state.storage.get_key_record(key_id).await.map_err(fail)?;
+    if key_pair.hsm.is_some() || matches!(key_pair.key_type, KeyType::Ed25519Hsm | KeyType::Ed25519Ephemeral) {
+        return Err(fail(KeyManagementError::ValidationFailed(format!("Key {} has no exportable private key", key_id))));
+    }
+    if !key_pair.exportable {
+        return Err(fail(KeyManagementError::InsufficientPermissions(format!("Key {} is not exportable", key_id))));
+    }
+    ensure_known_key_type(&key_pair).map_err(fail)?;
+    let (private_key, salt) = key_pair.signing_secrets();
+    let signing_key = load_signing_key(
+        private_key,
+        salt,
+        &key_pair.kdf.unwrap_or_default(),
+        request.password.as_deref(),
+    ).map_err(fail)?;
+    let envelope = wrap_key(&key_pair, &signing_key, &request.transport_public_key, state.clock.now()).map_err(fail)?;
+
+    tracing::info!("Key {} exported wrapped for transport key {}", key_id, request.transport_public_key);
+    Ok(Json(WrappedKeyResponse {
+        success: true,
+        message: "Key wrapped for export".to_string(),
+        code: None,
+        details: None,
+        envelope: Some(envelope),
+    }))
+}
+
+/// Store a key wrapped for this instance, keeping its original id and fingerprint
+///
+/// Refused when a stored or soft-deleted key already has the id or public key, or when the
+/// keystore is full. A key that was password-protected at the source must be given a password
+/// here, which encrypts it under this instance's KDF settings.
+#[cfg(not(feature = "verifier-only"))]
+pub async fn import_wrapped_key(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<ImportWrappedKeyRequest>,
+) -> Result<Json<ImportWrappedKeyResponse>, (StatusCode, Json<ImportWrappedKeyResponse>)> {
+    let key_id = request.envelope.metadata.id;
+    let fail_with = |e: KeyManagementError, details: serde_json::Value| {
+        let (code, message) = (e.code(), e.to_string());
+        (StatusCode::from(e), Json(ImportWrappedKeyResponse {
+            success: false,
+            message,
+            code: Some(code),
+            details: Some(details),
+            key_info: None,
+        }))
+    };
+    let fail = |e: KeyManagementError| fail_with(e, serde_json::json!({ "key_id": key_id }));
+    let now = state.clock.now();
+
+    let signing_key = state.transport_key.open(&request.envelope).map_err(fail)?;
+    let metadata = request.envelope.metadata;
+    match &request.password {
+        None if metadata.password_protected => {
+            return Err(fail(KeyManagementError::ValidationFailed(
+                "The key was password-protected at the source; give a password to protect it here".to_string(),
+            )));
+        }
+        Some(password) if password.chars().count() < MIN_PASSWORD_LENGTH => {
+            return Err(fail(KeyManagementError::ValidationFailed(format!("Password must be at least {} characters", MIN_PASSWORD_LENGTH))));
+        }
+        _ => {}
+    }
+
+    let deleted = state.storage.deleted_keys().await.map_err(fail)?;
+    if state.storage.key_exists(key_id).await || deleted.iter().any(|record| record.key_pair.id == key_id) {
+        return Err(fail(KeyManagementError::KeyConflict(format!("a key with id {} is already stored", key_id))));
+    }
+    if let Some(existing) = state.storage.find_by_fingerprint(&metadata.fingerprint).await {
+        return Err(fail_with(
+            KeyManagementError::KeyConflict(format!("key {} already holds this public key", existing.id)),
+            serde_json::json!({ "key_id": key_id, "existing_key_id": existing.id }),
+        ));
+    }
+    let key_count = state.storage.key_count().await;
+    if let Some(limit) = state.config.max_keys.filter(|limit| key_count >= *limit) {
+        return Err(fail(KeyManagementError::KeystoreFull(format!("the key quota of {} keys is reached", limit))));
+    }
+    state.capacity.reserve(&state.storage, now).await.map_err(fail)?;
+
+    let key_pair = metadata.into_key_pair(&signing_key, request.password, &state.config.kdf).map_err(fail)?;
+    state.storage.store_key(key_pair.clone()).await.map_err(fail)?;
+
+    tracing::info!("Key {} imported from a wrapped export", key_id);
+    Ok(Json(ImportWrappedKeyResponse {
+        success: true,
+        message: "Key imported successfully".to_string(),
+        code: None,
+        details: None,
+        key_info: Some(KeyInfo::from_key_pair(&key_pair, now)),
+    }))
+}
+
+/// Store keys from a legacy JSON export, reporting what happened to each record
+///
+/// Records that cannot be mapped, and keys already stored, are reported and the rest imported.
+/// The whole request is refused if the keys it could add would overrun the key quota.
+#[cfg(not(feature = "verifier-only"))]
+pub async fn import_legacy_keys(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<ImportLegacyKeysRequest>,
+) -> Result<Json<ImportLegacyKeysResponse>, (StatusCode, Json<ImportLegacyKeysResponse>)> {
+    let fail = |e: KeyManagementError| {
+        let (code, message) = (e.code(), e.to_string());
+        (StatusCode::from(e), Json(ImportLegacyKeysResponse { success: false, message, code: Some(code), report: None }))
+    };
+    let now = state.clock.now();
+    if request.records.is_empty() || request.records.len() > MAX_LEGACY_IMPORT_RECORDS {
+        return Err(fail(KeyManagementError::ValidationFailed(format!(
+            "records must hold between 1 and {} key records", MAX_LEGACY_IMPORT_RECORDS,
+        ))));
+    }
+    let key_count = state.storage.key_count().await;
+    if let Some(limit) = state.config.max_keys.filter(|limit| key_count + request.records.len() > *limit) {
+        return Err(fail(KeyManagementError::KeystoreFull(format!("the key quota of {} keys would be exceeded", limit))));
+    }
+    state.capacity.reserve(&state.storage, now).await.map_err(fail)?;
+
+    let report = import_legacy_records(&state.storage, &request.records, "records", request.created_at.unwrap_or(now)).await.map_err(fail)?;
+    tracing::info!("Legacy import: {}", report.summary());
+    Ok(Json(ImportLegacyKeysResponse {
+        success: true,
+        message: report.summary(),
+        code: None,
+        report: Some(report),
+    }))
+}
+
+/// This instance's transport public key, for other instances exporting keys to it
+#[cfg(not(feature = "verifier-only"))]
+pub async fn get_transport_key(State(state): State<Arc<AppState>>) -> Json<TransportKeyResponse> {
+    Json(TransportKeyResponse {
+        success: true,
+        algorithm: "x25519".to_string(),
+        public_key: state.transport_key.public_key(),
+        created_at: state.transport_key.created_at(),
+    })
+}
+
+/// List soft-deleted keys, most recently deleted first
+#[cfg(not(feature = "verifier-only"))]
+pub async fn list_deleted_keys(State(state): State<Arc<AppState>>) -> Response {
+    let now = state.clock.now();
+    match state.storage.deleted_keys().await {
+        Ok(records) => {
+            let keys: Vec<DeletedKeyInfo> = records.into_iter().rev()
+                .map(|record| DeletedKeyInfo {
+                    key_info: KeyInfo::new(record.key_pair, now),
+                    deleted_at: record.deleted_at,
+                    deleted_by: record.deleted_by,
+                })
+                .collect();
+            Json(DeletedKeysResponse { success: true, total_count: keys.len(), keys }).into_response()
+        }
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e.code(), e.to_string()),
+    }
+}
+
+/// List keys moved to the archive, most recently archived first
+#[cfg(not(feature = "verifier-only"))]
+pub async fn list_archived_keys(State(state): State<Arc<AppState>>) -> Response {
+    let now = state.clock.now();
+    match state.storage.archived_keys().await {
+        Ok(records) => {
+            let keys: Vec<ArchivedKeyInfo> = records.into_iter().rev()
+                .map(|record| ArchivedKeyInfo {
+                    key_info: KeyInfo::new(record.key_pair, now),
+                    archived_at: record.archived_at,
+                    reason: record.reason,
+                })
+                .collect();
+            Json(ArchivedKeysResponse { success: true, total_count: keys.len(), keys }).into_response()
+        }
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e.code(), e.to_string()),
+    }
+}
+
+/// Cancel a key's pending scheduled revocation
+#[cfg(not(feature = "verifier-only"))]
+pub async fn cancel_scheduled_revocation(
+    State(state): State<Arc<AppState>>,
+    Path(key_id): Path<Uuid>,
+) -> Result<Json<RevokeKeyResponse>, (StatusCode, Json<RevokeKeyResponse>)> {
+    let failure = |status: StatusCode, code: ErrorCode, message: String| {
+        (status, Json(RevokeKeyResponse {
+            success: false,
+            key_info: None,
+            message,
+            code: Some(code),
+            details: Some(serde_json::json!({ "key_id": key_id })),
+            revocation_time: None,
+            scheduled: false,
+        }))
+    };
+
+    match state.storage.cancel_scheduled_revocation(key_id).await {
+        Ok(true) => Ok(Json(RevokeKeyResponse {
+            success: true,
+            key_info: None,
+            message: "Scheduled revocation cancelled".to_string(),
+            code: None,
+            details: None,
+            revocation_time: None,
+            scheduled: false,
+        })),
+        Ok(false) => Err(failure(StatusCode::CONFLICT, ErrorCode::NoScheduledRevocation, format!("Key {} has no pending scheduled revocation", key_id))),
+        Err(e) => Err(failure(StatusCode::NOT_FOUND, e.code(), e.to_string())),
+    }
+}
+
+/// Certify another key's public key with this key
+#[cfg(not(feature = "verifier-only"))]
+pub async fn certify_key(
+    State(state): State<Arc<AppState>>,
+    Path(key_id): Path<Uuid>,
+    Json(request): Json<CertifyKeyRequest>,
+) -> Result<Json<CertifyKeyResponse>, (StatusCode, Json<CertifyKeyResponse>)> {
+    let failure = |status: StatusCode, code: ErrorCode, message: String| {
+        (status, Json(CertifyKeyResponse {
+            success: false,
+            certification: None,
+            message,
+            code: Some(code),
+            details: None,
+        }))
+    };
+    let key_failure = |e: KeyManagementError| {
+        let (code, message, details) = (e.code(), e.to_string(), e.details());
+        let (status, json) = failure(e.into(), code, message);
+        (status, Json(CertifyKeyResponse { details, ..json.0 }))
+    };
+    let now = state.clock.now();
+
+    // Revoked or expired keys may not vouch for anything
+    let certifier = state.storage.get_key(key_id).await
+        .map_err(key_failure)?;
+
+    let (target_key_id, target_public_key) = match (request.target_key_id, request.target_public_key) {
+        (Some(target_key_id), None) => {
+            let target = state.storage.get_key(target_key_id).await
+                .map_err(key_failure)?;
+            (Some(target_key_id), target.public_key)
+        }
+        (None, Some(public_key)) => {
+            decode_public_key(&public_key).map_err(|e| failure(StatusCode::UNPROCESSABLE_ENTITY, e.code(), e.to_string()))?;
+            (None, public_key)
+        }
+        _ => {
+            return Err(failure(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                ErrorCode::ValidationFailed,
+                "Exactly one of target_key_id or target_public_key must be provided".to_string(),
+            ));
+        }
+    };
+    if target_public_key == certifier.public_key {
+        return Err(failure(StatusCode::UNPROCESSABLE_ENTITY, ErrorCode::ValidationFailed, "A key cannot certify itself".to_string()));
+    }
+    if request.valid_until.is_some_and(|valid_until| valid_until <= now) {
+        return Err(failure(StatusCode::UNPROCESSABLE_ENTITY, ErrorCode::ValidationFailed, "valid_until must be in the future".to_string()));
+    }
+
+    let certifier_fingerprint = public_key_to_fingerprint(&certifier.public_key)
+        .map_err(|e| failure(StatusCode::UNPROCESSABLE_ENTITY, ErrorCode::InvalidKeyFormat, e))?;
+    let target_fingerprint = public_key_to_fingerprint(&target_public_key)
+        .map_err(|e| failure(StatusCode::UNPROCESSABLE_ENTITY, ErrorCode::InvalidKeyFormat, e))?;
+    let payload = CertificationPayload {
+        certifier_key_id: certifier.id,
+        certifier_fingerprint,
+        certifier_public_key: certifier.public_key.clone(),
+        target_key_id,
+        target_fingerprint,
+        target_public_key,
+        issued_at: now,
+        valid_until: request.valid_until,
+    };
+
+    let (signer, kdf_timing) = load_signer(&certifier, request.password.as_deref(), state.hsm.as_deref())
+        .map_err(key_failure)?;
+    if let Some(timing) = &kdf_timing {
+        state.kdf_timings.record(timing);
+    }
+    let certification = Certification::issue(payload, signer.as_ref())
+        .map_err(|e| failure(StatusCode::INTERNAL_SERVER_ERROR, e.code(), e.to_string()))?;
+    state.certifications.record(certification.clone()).await
+        .map_err(|e| failure(StatusCode::INTERNAL_SERVER_ERROR, e.code(), e.to_string()))?;
+
+    Ok(Json(CertifyKeyResponse {
+        success: true,
+        certification: Some(certification),
+        message: "Key certified successfully".to_string(),
+        code: None,
+        details: None,
+    }))
+}
+
+/// List the certifications a key has issued and received
+pub async fn get_key_certifications(
+    State(state): State<Arc<AppState>>,
+    Path(key_id): Path<Uuid>,
+) -> Result<Json<KeyCertificationsResponse>, Response> {
+    let key_pair = state.storage.get_key_record(key_id).await
+        .map_err(|e| key_error_response(StatusCode::NOT_FOUND, e.to_string(), &e))?;
+    let fingerprint = public_key_to_fingerprint(&key_pair.public_key)
+        .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, ErrorCode::InvalidKeyFormat, e))?;
+
+    let issued = state.certifications.issued_by(key_id).await;
+    let received = state.certifications.received_by(&fingerprint).await;
+    Ok(Json(KeyCertificationsResponse {
+        success: true,
+        key_id,
+        message: format!("Found {} issued and {} received certifications", issued.len(), received.len()),
+        issued,
+        received,
+    }))
+}
+
+/// Switch read-only mode at runtime
+///
+/// The switch lasts until restart; `INKAN_READ_ONLY` sets the mode the service starts in. A
+/// follower refuses to leave read-only mode with `503 READ_ONLY`.
+#[cfg(not(feature = "verifier-only"))]
+pub async fn set_read_only(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<ReadOnlyRequest>,
+) -> (StatusCode, Json<ReadOnlyResponse>) {
+    if state.follower && !request.enabled {
+        return (failure_status(&state.config, ErrorCode::ReadOnly), Json(ReadOnlyResponse {
+            success: false,
+            read_only: true,
+            message: "Read-only mode cannot be disabled while another instance owns the keystore".to_string(),
+            code: Some(ErrorCode::ReadOnly),
+        }));
+    }
+
+    let previous = state.read_only.swap(request.enabled, Ordering::SeqCst);
+    if previous != request.enabled {
+        tracing::warn!("Read-only mode {}", if request.enabled { "enabled" } else { "disabled" });
+    }
+
+    (StatusCode::OK, Json(ReadOnlyResponse {
+        success: true,
+        read_only: request.enabled,
+        message: format!("Read-only mode {}", if request.enabled { "enabled" } else { "disabled" }),
+        code: None,
+    }))
+}
+
+/// Report readiness, the current operating mode, the latest self-test, and entropy health
+pub async fn readiness(State(state): State<Arc<AppState>>) -> (StatusCode, Json<ReadinessResponse>) {
+    let self_test = state.self_test.read().await.clone();
+    let entropy = state.entropy.status();
+    let ready = self_test.as_ref().is_none_or(|report| report.passed) && !entropy.degraded;
+
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    let key_count = state.storage.key_count().await;
+    (status, Json(ReadinessResponse {
+        ready,
+        read_only: state.read_only.load(Ordering::SeqCst),
+        follower: state.follower,
+        key_count,
+        self_test,
+        keystore_load: state.keystore_load.clone(),
+        persistence: state.storage.persistence_status(),
+        entropy,
+        capacity: state.capacity.status(key_count),
+        storage_breaker: state.storage.breaker().status(state.clock.now()),
+    }))
+}
+
+/// Run the self-test on demand and record it for the readiness report
+#[cfg(not(feature = "verifier-only"))]
+pub async fn self_test(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<SelfTestReport>, (StatusCode, Json<SelfTestReport>)> {
+    let report = run_self_test(&state.storage, &state.config.kdf).await;
+    *state.self_test.write().await = Some(report.clone());
+
+    if report.passed {
+        Ok(Json(report))
+    } else {
+        tracing::error!("Self-test failed: {}", report.failure_summary());
+        Err((StatusCode::INTERNAL_SERVER_ERROR, Json(report)))
+    }
+}
+
+/// Export keystore and per-key usage metrics in the Prometheus text format
+pub async fn metrics(State(state): State<Arc<AppState>>) -> Response {
+    let keys = state.storage.list_keys().await;
+    let service = ServiceMetrics {
+        persistence: state.storage.persistence_status(),
+        entropy: state.entropy.status(),
+        limits: state.limits.stats(),
+        verification_cache: state.verification_cache.stats(),
+        capacity: state.capacity.status(keys.len()),
+        kdf_timings: state.kdf_timings.snapshot(),
+        request_timeouts: state.deadlines.timeouts(),
+        api_requests: state.api_usage.snapshot(),
+        #[cfg(not(feature = "verifier-only"))]
+        key_pool: state.key_pool.stats(),
+        receipt_write_failures: state.receipts.failed_writes(),
+        usage_updates_dropped: state.storage.dropped_usage_updates(),
+        storage_breaker: state.storage.breaker().status(state.clock.now()).state,
+        storage_breaker_transitions: state.storage.breaker().transitions_into(),
+        storage_breaker_rejections: state.storage.breaker().rejections(),
+    };
+    let body = render_metrics(&keys, &service, state.config.metrics_max_key_labels);
+    ([(header::CONTENT_TYPE, metrics::CONTENT_TYPE)], body).into_response()
+}
+
+/// Latency percentiles of generate, sign and verify requests over the last 5 and 60 minutes,
+/// with the requests slower than each objective
+#[cfg(not(feature = "verifier-only"))]
+pub async fn slo_report(State(state): State<Arc<AppState>>) -> Json<SloReport> {
+    Json(state.slo.report(state.clock.now()))
+}
+
+/// One-call summary for operations dashboards, restricted to admin clients
+///
+/// Admin clients are enforced by [`admin_scope_guard`]. The response is never cached, since it
+/// describes the service at this moment.
+#[cfg(not(feature = "verifier-only"))]
+pub async fn admin_overview(State(state): State<Arc<AppState>>) -> Response {
+    let now = state.clock.now();
+    let service = ServiceFlags {
+        read_only: state.read_only.load(Ordering::SeqCst),
+        follower: state.follower,
+        persistence: state.storage.persistence_status(),
+        entropy: state.entropy.status(),
+    };
+    let sources = OverviewSources {
+        stats: async {
+            let (total, active, expired, revoked, suspended) = state.storage.get_key_stats().await;
+            let keys = state.storage.list_keys().await;
+            Ok(KeyStats {
+                total_keys: total,
+                active_keys: active,
+                expired_keys: expired,
+                revoked_keys: revoked,
+                suspended_keys: suspended,
+                keys_expiring_soon: state.storage.get_keys_expiring_soon(EXPIRING_SOON_DAYS).await.len(),
+                total_sign_count: keys.iter().map(|key| key.usage.sign_count).sum(),
+                total_verify_count: keys.iter().map(|key| key.usage.verify_count).sum(),
+                capacity: state.capacity.status(total),
+            })
+        },
+        expiring_soon: async {
+            let mut expiring: Vec<ExpiringKey> = state.storage.get_keys_expiring_soon(EXPIRING_SOON_DAYS).await
+                .into_iter()
+                .filter(|key| key.state.is_usable())
+                .filter_map(|key| Some(ExpiringKey {
+                    fingerprint: public_key_to_fingerprint(&key.public_key).ok(),
+                    expires_at: key.expires_at?,
+                    key_id: key.id,
+                    name: key.name,
+                }))
+                .collect();
+            expiring.sort_by_key(|key| (key.expires_at, key.key_id));
+            expiring.truncate(MAX_EXPIRING_KEYS);
+            Ok(expiring)
+        },
+        recent_signatures: async {
+            Ok(state.receipts.recent(MAX_RECENT_SIGNATURES).await
+                .into_iter()
+                .map(|bundle| RecentSignature {
+                    signature_id: bundle.body.signature_id,
+                    key_id: bundle.body.key_id,
+                    signing_time: bundle.body.signing_time,
+                    document_hash: bundle.body.document_hash,
+                    context: bundle.body.context,
+                })
+                .collect())
+        },
+        background: async { Ok(BackgroundTasks { sweeper: state.sweeper.last_run() }) },
+    };
+    let overview = AdminOverview::assemble(now, service, sources, SECTION_TIMEOUT).await;
+    (
+        [(header::CACHE_CONTROL, "no-store")],
+        Json(AdminOverviewResponse { success: true, overview }),
+    ).into_response()
+}
+
+/// Usage between two dates by tenant, owner or key, restricted to admin clients
+///
+/// Answers CSV when the `Accept` header asks for `text/csv`. A report is served from cache for
+/// a minute after it is computed, so dashboards polling it do not rescan the archives.
+#[cfg(not(feature = "verifier-only"))]
+pub async fn usage_report(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<UsageReportQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let now = state.clock.now();
+    let to = query.to.unwrap_or_else(|| now.date_naive());
+    let from = query.from.unwrap_or_else(|| chrono::Datelike::with_day(&to, 1).unwrap_or(to));
+    if let Err(e) = check_range(from, to) {
+        return error_response(StatusCode::UNPROCESSABLE_ENTITY, e.code(), e.to_string());
+    }
+
+    let report = match state.usage_reports.get(from, to, query.group_by, now) {
+        Some(report) => report,
+        None => {
+            let (archived, deleted) = match (state.storage.archived_keys().await, state.storage.deleted_keys().await) {
+                (Ok(archived), Ok(deleted)) => (archived, deleted),
+                (Err(e), _) | (_, Err(e)) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.code(), e.to_string()),
+            };
+            let keys: Vec<KeyPair> = state.storage.entries().await.into_iter().map(|(_, key_pair)| key_pair)
+                .chain(deleted.into_iter().map(|deleted| deleted.key_pair))
+                .chain(archived.into_iter().map(|archived| archived.key_pair))
+                .collect();
+            let report = UsageReport::aggregate(&keys, from, to, query.group_by, now);
+            state.usage_reports.insert(report.clone());
+            report
+        }
+    };
+
+    let wants_csv = headers.get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.split(',').any(|media| media.split(';').next().is_some_and(|media| media.trim() == "text/csv")));
+    if wants_csv {
+        let disposition = format!("attachment; filename=\"usage-{}-{}.csv\"", report.from, report.to);
+        (
+            [(header::CONTENT_TYPE, CSV_CONTENT_TYPE.to_string()), (header::CONTENT_DISPOSITION, disposition)],
+            report.to_csv(),
+        ).into_response()
+    } else {
+        Json(UsageReportResponse { success: true, report }).into_response()
+    }
+}
+
+/// Operation events numbered after `after_seq`, for consumers backfilling missed webhooks
+///
+/// A page that would start past events no longer retained is refused with `410
+/// EVENTS_NOT_RETAINED` rather than served with a silent gap.
+#[cfg(not(feature = "verifier-only"))]
+pub async fn replay_events(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<EventReplayQuery>,
+) -> Response {
+    let limit = query.limit.unwrap_or(DEFAULT_REPLAY_LIMIT);
+    if limit == 0 || limit > MAX_REPLAY_LIMIT {
+        let message = format!("limit must be between 1 and {}", MAX_REPLAY_LIMIT);
+        return error_response(StatusCode::UNPROCESSABLE_ENTITY, ErrorCode::ValidationFailed, message);
+    }
+
+    match state.storage.events().replay(query.after_seq, limit).await {
+        Ok(replay) => Json(EventReplayResponse { success: true, replay }).into_response(),
+        Err(e) => key_error_response(StatusCode::GONE, e.to_string(), &e),
+    }
+}
+
+/// Get key statistics
+pub async fn get_key_stats(
+    State(state): State<Arc<AppState>>,
+) -> Json<KeyStatsResponse> {
+    let (total, active, expired, revoked, suspended) = state.storage.get_key_stats().await;
+    let expiring_soon = state.storage.get_keys_expiring_soon(30).await.len();
+    let keys = state.storage.list_keys().await;
+
+    Json(KeyStatsResponse {
+        success: true,
+        total_keys: total,
+        active_keys: active,
+        expired_keys: expired,
+        revoked_keys: revoked,
+        suspended_keys: suspended,
+        keys_expiring_soon: expiring_soon,
+        total_sign_count: keys.iter().map(|key| key.usage.sign_count).sum(),
+        total_verify_count: keys.iter().map(|key| key.usage.verify_count).sum(),
+        capacity: state.capacity.status(total),
+        keys_by_environment: keys.iter().fold(BTreeMap::new(), |mut counts, key| {
+            *counts.entry(key.environment.clone()).or_default() += 1;
+            counts
+        }),
+        keys_by_type: keys.iter().fold(BTreeMap::new(), |mut counts, key| {
+            *counts.entry(key.key_type.as_str().to_string()).or_default() += 1;
+            counts
+        }),
+        message: format!("Retrieved statistics for {} keys", total),
+    })
+}
+
+/// Search keys
+pub async fn search_keys(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ListKeysQuery>,
+    usable_only: Option<Extension<UsableKeysOnly>>,
+) -> Result<Json<ListKeysResponse>, Response> {
+    let listed = key_listing(&state, &query, usable_only.is_some()).await?;
+    Ok(Json(ListKeysResponse { message: format!("Found {} matching keys", listed.keys.len()), ..listed }))
+}
+
+// Most routes are compiled out of verifier-only builds; `verifier_tests` covers what they serve
+#[cfg(all(test, not(feature = "verifier-only")))]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+    use crate::key_generation::{generate_seeded_test_key_pair, generate_test_key_pair};
+    use chrono::{Duration, Timelike, Utc};
+    use tempfile::tempdir;
+
+    fn test_state(dir: &tempfile::TempDir, clock: Arc<MockClock>) -> Arc<AppState> {
+        let storage_path = dir.path().join("keys.json");
+        Arc::new(AppState {
+            storage: Arc::new(KeyStorage::new(storage_path.to_str().unwrap()).with_clock(clock.clone())),
+            clock,
+            config: Arc::new(Config { profile: DeploymentProfile::Full, ..Config::default() }),
+            receipts: Arc::new(ReceiptStore::new(dir.path().join("receipts.json").to_str().unwrap())),
+            certifications: Arc::new(CertificationStore::new(dir.path().join("certifications.json").to_str().unwrap())),
+            read_only: AtomicBool::new(false),
+            self_test: RwLock::new(None),
+            keystore_load: None,
+            entropy: Arc::new(EntropyMonitor::os()),
+            hsm: None,
+            follower: false,
+            limits: OperationLimits::default(),
+            shares: Arc::new(ShareStore::new(dir.path().join("shares.json").to_str().unwrap())),
+            delegations: Arc::new(DelegationStore::new(dir.path().join("delegations.json").to_str().unwrap())),
+            verification_cache: VerificationCache::disabled(),
+            request_auth: RequestAuthenticator::disabled(),
+            capacity: KeystoreCapacity::unlimited(),
+            kdf_timings: KdfTimings::new(),
+            verify_rate_limit: ClientRateLimiter::unlimited(),
+            sign_policy: Arc::new(SignPolicy::allow_all()),
+            key_resolver: Arc::new(KeyResolver::none()),
+            transport_key: Arc::new(TransportKey::generate(Utc::now())),
+            sweeper: Arc::new(TaskStatus::default()),
+            deadlines: RequestDeadlines::from_config(&Config::default()),
+            api_usage: ApiUsage::default(),
+            key_pool: Arc::new(KeyPool::from_config(&Config::default())),
+            usage_reports: UsageReportCache::default(),
+            slo: SloTracker::from_config(&Config::default()),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_verify_reports_expired_signature_window() {
+        let dir = tempdir().unwrap();
+        let start = Utc::now();
+        let clock = Arc::new(MockClock::new(start));
+        let state = test_state(&dir, clock.clone());
+
+        let key_pair = generate_test_key_pair("Window Key").unwrap();
+        let public_key = key_pair.public_key.clone();
+        state.storage.store_key(key_pair.clone()).await.unwrap();
+
+        let valid_until = start + Duration::hours(1);
+        let signed = sign_document(State(state.clone()), Json(SignDocumentRequest {
+            key_id: key_pair.id,
+            document_hash: None,
+            password: None,
+            document_content: Some("download manifest".to_string()),
+            valid_until: Some(valid_until),
+            ..Default::default()
+        })).await.unwrap().0;
+        assert!(signed.success);
+        assert_eq!(signed.valid_until, Some(valid_until));
+
+        let verify = |valid_until| VerifySignatureRequest {
+            public_key: public_key.clone(),
+            document_hash: None,
+            signature: signed.signature.clone().unwrap(),
+            document_content: Some("download manifest".to_string()),
+            valid_until,
+            ..Default::default()
+        };
+
+        // Exactly at the boundary the signature is still valid
+        clock.set(valid_until);
+        let response = verify_signature(State(state.clone()), Json(verify(Some(valid_until)))).await.unwrap().0;
+        assert!(response.is_valid);
+        assert!(!response.expired_signature);
+
+        // One millisecond later it is authentic but expired
+        clock.advance(Duration::milliseconds(1));
+        let response = verify_signature(State(state.clone()), Json(verify(Some(valid_until)))).await.unwrap().0;
+        assert!(!response.is_valid);
+        assert!(response.cryptographically_valid);
+        assert!(response.expired_signature);
+
+        // A tampered window fails cryptographically
+        let response = verify_signature(State(state.clone()), Json(verify(Some(valid_until + Duration::days(365))))).await.unwrap().0;
+        assert!(!response.cryptographically_valid);
+        assert!(!response.expired_signature);
+    }
+
+    #[tokio::test]
+    async fn test_sign_rejects_past_valid_until() {
+        let dir = tempdir().unwrap();
+        let clock = Arc::new(MockClock::new(Utc::now()));
+        let state = test_state(&dir, clock.clone());
+
+        let key_pair = generate_test_key_pair("Window Key").unwrap();
+        state.storage.store_key(key_pair.clone()).await.unwrap();
+
+        let (status, Json(response)) = sign_document(State(state.clone()), Json(SignDocumentRequest {
+            key_id: key_pair.id,
+            document_hash: None,
+            password: None,
+            document_content: Some("late".to_string()),
+            valid_until: Some(clock.now() - Duration::seconds(1)),
+            ..Default::default()
+        })).await.unwrap_err();
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(!response.success);
+        assert!(response.signature.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_json_jcs_signatures_ignore_formatting() {
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir, Arc::new(MockClock::new(Utc::now())));
+
+        let key_pair = generate_test_key_pair("JCS Key").unwrap();
+        state.storage.store_key(key_pair.clone()).await.unwrap();
+
+        let sign = |content: &str| SignDocumentRequest {
+            key_id: key_pair.id,
+            document_hash: None,
+            password: None,
+            document_content: Some(content.to_string()),
+            valid_until: None,
+            content_type: DocumentContentType::JsonJcs,
+            ..Default::default()
+        };
+
+        let compact = sign_document(State(state.clone()), Json(sign(r#"{"b":[1,2],"a":"x"}"#))).await.unwrap().0;
+        let pretty = sign_document(State(state.clone()), Json(sign("{\n  \"a\": \"x\",\n  \"b\": [1.0, 2]\n}"))).await.unwrap().0;
+        assert!(compact.success && pretty.success);
+        assert_eq!(compact.signature, pretty.signature);
+        assert_eq!(compact.canonical_hash, pretty.canonical_hash);
+        assert_eq!(compact.canonical_hash, compact.document_hash);
+
+        // The verifier may format the document differently too
+        let response = verify_signature(State(state.clone()), Json(VerifySignatureRequest {
+            public_key: key_pair.public_key.clone(),
+            document_hash: None,
+            signature: compact.signature.clone().unwrap(),
+            document_content: Some(r#"{ "b": [1, 2.0], "a": "x" }"#.to_string()),
+            valid_until: None,
+            content_type: DocumentContentType::JsonJcs,
+            ..Default::default()
+        })).await.unwrap().0;
+        assert!(response.is_valid);
+        assert_eq!(response.canonical_hash, compact.canonical_hash);
+    }
+
+    #[tokio::test]
+    async fn test_json_jcs_rejects_invalid_json_with_location() {
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir, Arc::new(MockClock::new(Utc::now())));
+
+        let key_pair = generate_test_key_pair("JCS Key").unwrap();
+        state.storage.store_key(key_pair.clone()).await.unwrap();
+
+        let (status, Json(response)) = sign_document(State(state.clone()), Json(SignDocumentRequest {
+            key_id: key_pair.id,
+            document_hash: None,
+            password: None,
+            document_content: Some("{\"a\": }".to_string()),
+            valid_until: None,
+            content_type: DocumentContentType::JsonJcs,
+    ..Default::default()
+        })).await.unwrap_err();
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(response.message.contains("line 1, column"), "{}", response.message);
+    }
+
+    #[tokio::test]
+    async fn test_minisign_output_verifies_with_exported_public_key() {
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir, Arc::new(MockClock::new(Utc::now())));
+
+        let key_pair = generate_test_key_pair("Release Key").unwrap();
+        state.storage.store_key(key_pair.clone()).await.unwrap();
+
+        let content = "release-1.4.2.tar.gz contents";
+        let signed = sign_document(State(state.clone()), Json(SignDocumentRequest {
+            key_id: key_pair.id,
+            document_content: Some(content.to_string()),
+            output_format: Some(SignatureOutputFormat::Minisign),
+            ..Default::default()
+        })).await.unwrap().0;
+        assert!(signed.success);
+        assert_eq!(signed.output_format, SignatureOutputFormat::Minisign);
+        let signature = signed.signature.unwrap();
+
+        let response = get_public_key_formatted(
+            State(state.clone()),
+            Path(key_pair.id),
+            Query(PublicKeyQuery { format: PublicKeyFormat::Minisign }),
+        ).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let exported = String::from_utf8(body.to_vec()).unwrap();
+
+        // The stock minisign verifier accepts our output
+        let public_key = minisign_verify::PublicKey::decode(&exported).unwrap();
+        let decoded = minisign_verify::Signature::decode(&signature).unwrap();
+        public_key.verify(content.as_bytes(), &decoded, false).unwrap();
+
+        // And so does our own /verify endpoint
+        let verify = |document_content: &str| VerifySignatureRequest {
+            public_key: exported.clone(),
+            signature: signature.clone(),
+            document_content: Some(document_content.to_string()),
+            ..Default::default()
+        };
+        let response = verify_signature(State(state.clone()), Json(verify(content))).await.unwrap().0;
+        assert!(response.is_valid);
+        let response = verify_signature(State(state.clone()), Json(verify("tampered"))).await.unwrap().0;
+        assert!(!response.is_valid);
+    }
+
+    #[tokio::test]
+    async fn test_raw_sign_streams_documents_larger_than_the_inline_limit() {
+        use axum::body::Body;
+        use tower::ServiceExt;
+
+        let dir = tempdir().unwrap();
+        let state = Arc::new(AppState {
+            config: Arc::new(Config { sign_max_content_bytes: 1024, raw_sign_max_content_bytes: 64 * 1024, ..Config::default() }),
+            ..Arc::into_inner(test_state(&dir, Arc::new(MockClock::new(Utc::now())))).unwrap()
+        });
+        let key_pair = generate_test_key_pair("Raw Signer").unwrap();
+        state.storage.store_key(key_pair.clone()).await.unwrap();
+        let document: Vec<u8> = (0..48 * 1024).map(|i| (i % 251) as u8).collect();
+
+        // Inline content over its limit is refused with a pointer to the raw route
+        let (status, Json(inline)) = sign_document(State(state.clone()), Json(SignDocumentRequest {
+            key_id: key_pair.id,
+            document_content: Some("x".repeat(1025)),
+            ..Default::default()
+        })).await.unwrap_err();
+        assert_eq!((status, inline.code), (StatusCode::PAYLOAD_TOO_LARGE, Some(ErrorCode::ContentTooLarge)));
+        assert_eq!(inline.details.unwrap()["raw_endpoint"], "/sign/raw");
+
+        let app = crate::routes::router_with_versions(state.clone(), ApiVersion::ALL);
+        let call = |query: String, chunks: Vec<Vec<u8>>, length: Option<usize>| {
+            let app = app.clone();
+            async move {
+                // Streamed in chunks with no declared length unless one is given
+                let stream = tokio_stream::iter(chunks.into_iter().map(Ok::<_, std::io::Error>));
+                let mut request = axum::http::Request::builder().method(Method::POST).uri(format!("/v1/sign/raw?{}", query));
+                if let Some(length) = length {
+                    request = request.header(header::CONTENT_LENGTH, length);
+                }
+                let response = app.oneshot(request.body(Body::from_stream(stream)).unwrap()).await.unwrap();
+                let status = response.status();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                (status, serde_json::from_slice::<serde_json::Value>(&body).unwrap())
+            }
+        };
+        let chunks = || document.chunks(4096).map(<[u8]>::to_vec).collect::<Vec<_>>();
+
+        let (status, signed) = call(format!("key_id={}&context=invoice", key_pair.id), chunks(), None).await;
+        assert_eq!(status, StatusCode::OK, "{}", signed);
+        let document_hash = hex::encode(Sha256::digest(&document));
+        assert_eq!(signed["document_hash"], document_hash);
+        let verified = verify_signature(State(state.clone()), Json(VerifySignatureRequest {
+            key_id: Some(key_pair.id),
+            document_hash: Some(document_hash),
+            signature: signed["signature"].as_str().unwrap().to_string(),
+            context: Some("invoice".to_string()),
+            ..Default::default()
+        })).await.unwrap().0;
+        assert!(verified.is_valid);
+
+        // The raw route has its own limit, checked as the body arrives and against a declared length
+        let mut oversized = chunks();
+        oversized.extend(chunks());
+        let (status, refused) = call(format!("key_id={}", key_pair.id), oversized, None).await;
+        assert_eq!((status, &refused["code"]), (StatusCode::PAYLOAD_TOO_LARGE, &serde_json::json!("CONTENT_TOO_LARGE")));
+        assert_eq!(refused["details"]["limit_bytes"], 64 * 1024);
+        let (status, refused) = call(format!("key_id={}", key_pair.id), vec![], Some(64 * 1024 + 1)).await;
+        assert_eq!((status, &refused["code"]), (StatusCode::PAYLOAD_TOO_LARGE, &serde_json::json!("CONTENT_TOO_LARGE")));
+        let (status, _) = call(format!("key_id={}", Uuid::new_v4()), chunks(), None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_minisign_output_requires_document_content() {
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir, Arc::new(MockClock::new(Utc::now())));
+
+        let key_pair = generate_test_key_pair("Release Key").unwrap();
+        state.storage.store_key(key_pair.clone()).await.unwrap();
+
+        let (status, Json(response)) = sign_document(State(state), Json(SignDocumentRequest {
+            key_id: key_pair.id,
+            document_hash: Some(crate::key_verification::create_document_hash("x")),
+            output_format: Some(SignatureOutputFormat::Minisign),
+            ..Default::default()
+        })).await.unwrap_err();
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(!response.success);
+    }
+
+    #[tokio::test]
+    async fn test_sshsig_output_verifies_with_exported_public_key() {
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir, Arc::new(MockClock::new(Utc::now())));
+
+        let key_pair = generate_test_key_pair("Git Signing").unwrap();
+        state.storage.store_key(key_pair.clone()).await.unwrap();
+
+        let content = "tree 4b825dc642cb6eb9a060e54bf8d69288fbee4904\n";
+        let signed = sign_document(State(state.clone()), Json(SignDocumentRequest {
+            key_id: key_pair.id,
+            document_content: Some(content.to_string()),
+            output_format: Some(SignatureOutputFormat::Sshsig),
+            namespace: Some("git".to_string()),
+            ..Default::default()
+        })).await.unwrap().0;
+        assert!(signed.success);
+        assert_eq!(signed.output_format, SignatureOutputFormat::Sshsig);
+        let signature = signed.signature.unwrap();
+        assert!(signature.starts_with("-----BEGIN SSH SIGNATURE-----"));
+
+        let response = get_public_key_formatted(
+            State(state.clone()),
+            Path(key_pair.id),
+            Query(PublicKeyQuery { format: PublicKeyFormat::Ssh }),
+        ).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let exported = String::from_utf8(body.to_vec()).unwrap();
+        assert!(exported.starts_with("ssh-ed25519 AAAA"));
+        assert!(exported.trim_end().ends_with(" Git_Signing"));
+
+        let verify = |namespace: Option<&str>| VerifySignatureRequest {
+            public_key: exported.clone(),
+            signature: signature.clone(),
+            document_content: Some(content.to_string()),
+            namespace: namespace.map(str::to_string),
+            ..Default::default()
+        };
+        let response = verify_signature(State(state.clone()), Json(verify(Some("git")))).await.unwrap().0;
+        assert!(response.is_valid);
+
+        // The namespace defaults to "file", which this signature was not made for
+        let response = verify_signature(State(state.clone()), Json(verify(None))).await.unwrap().0;
+        assert!(!response.is_valid);
+    }
+
+    #[tokio::test]
+    async fn test_generation_uses_configured_kdf_and_old_keys_still_sign() {
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir, Arc::new(MockClock::new(Utc::now())));
+        let request = |name: &str| GenerateKeyRequest {
+            name: name.to_string(),
+            description: None,
+            password: Some("hunter22".to_string()),
+            expires_at: None,
+            tags: None,
+            key_strength: None,
+            hsm: None,
+            generate_password: false,
+            template: None,
+            environment: None,
+            fast: false,
+            exportable: None,
+            default_output_format: None,
+            default_hash_algorithm: None,
+            default_encoding: None,
+        };
+        let old_key = generate_keys(State(state.clone()), Query(GenerateKeyQuery::default()), Json(request("Old"))).await.unwrap().0.key_pair.unwrap();
+
+        // Reconfigure the service as a restart with new settings would
+        let tuned = crate::config::KdfParams::pbkdf2(20_000);
+        let state = Arc::new(AppState {
+            storage: state.storage.clone(),
+            clock: state.clock.clone(),
+            config: Arc::new(Config { kdf: tuned, ..Default::default() }),
+            receipts: state.receipts.clone(),
+            certifications: state.certifications.clone(),
+            read_only: AtomicBool::new(false),
+            self_test: RwLock::new(None),
+            keystore_load: None,
+            entropy: Arc::new(EntropyMonitor::os()),
+            hsm: None,
+            follower: false,
+            limits: OperationLimits::default(),
+            shares: state.shares.clone(),
+            delegations: state.delegations.clone(),
+            verification_cache: VerificationCache::disabled(),
+            request_auth: RequestAuthenticator::disabled(),
+            capacity: KeystoreCapacity::unlimited(),
+            kdf_timings: KdfTimings::new(),
+            verify_rate_limit: ClientRateLimiter::unlimited(),
+            sign_policy: Arc::new(SignPolicy::allow_all()),
+            key_resolver: Arc::new(KeyResolver::none()),
+            transport_key: Arc::new(TransportKey::generate(Utc::now())),
+            sweeper: Arc::new(TaskStatus::default()),
+            deadlines: RequestDeadlines::from_config(&Config::default()),
+            api_usage: ApiUsage::default(),
+            key_pool: Arc::new(KeyPool::from_config(&Config::default())),
+            usage_reports: UsageReportCache::default(),
+            slo: SloTracker::from_config(&Config::default()),
+        });
+        let new_key = generate_keys(State(state.clone()), Query(GenerateKeyQuery::default()), Json(request("New"))).await.unwrap().0.key_pair.unwrap();
+
+        assert_eq!(state.storage.get_key_record(old_key.id).await.unwrap().kdf, Some(crate::config::KdfParams::default()));
+        assert_eq!(state.storage.get_key_record(new_key.id).await.unwrap().kdf, Some(tuned));
+
+        for key_id in [old_key.id, new_key.id] {
+            let signed = sign_document(State(state.clone()), Json(SignDocumentRequest {
+                key_id,
+                password: Some("hunter22".to_string()),
+                document_content: Some("payload".to_string()),
+                ..Default::default()
+            })).await.unwrap().0;
+            assert!(signed.success, "{}", signed.message);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_kdf_calibration_rejects_out_of_range_targets() {
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir, Arc::new(MockClock::new(Utc::now())));
+
+        let (status, Json(response)) = kdf_calibration(
+            State(state.clone()),
+            Query(KdfCalibrationQuery { target_ms: Some(MAX_KDF_TARGET_MS + 1) }),
+        ).await.unwrap_err();
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(response.suggested.is_none());
+
+        let response = kdf_calibration(State(state), Query(KdfCalibrationQuery { target_ms: Some(20) })).await.unwrap().0;
+        assert!(response.success);
+        assert!(response.suggested.unwrap().iterations >= crate::config::MIN_PBKDF2_ITERATIONS);
+    }
+
+    #[tokio::test]
+    async fn test_signing_upgrades_legacy_key_to_envelope() {
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir, Arc::new(MockClock::new(Utc::now())));
+
+        let legacy = crate::key_generation::generate_legacy_test_key_pair("hunter22");
+        state.storage.store_key(legacy.clone()).await.unwrap();
+
+        let sign = || SignDocumentRequest {
+            key_id: legacy.id,
+            password: Some("hunter22".to_string()),
+            document_content: Some("payload".to_string()),
+            ..Default::default()
+        };
+        let first = sign_document(State(state.clone()), Json(sign())).await.unwrap().0;
+        assert!(first.success, "{}", first.message);
+
+        let stored = state.storage.get_key(legacy.id).await.unwrap();
+        assert!(stored.salt.is_none());
+        assert!(!crate::key_generation::is_legacy_encrypted_key(&stored));
+
+        // The rewritten key still produces the same signature
+        let second = sign_document(State(state.clone()), Json(sign())).await.unwrap().0;
+        assert_eq!(first.signature, second.signature);
+    }
+
+    #[tokio::test]
+    async fn test_validate_keystore_streams_progress() {
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir, Arc::new(MockClock::new(Utc::now())));
+
+        let healthy = generate_test_key_pair("Healthy").unwrap();
+        let mut stale = generate_test_key_pair("Stale Fingerprint").unwrap();
+        stale.fingerprint = None;
+        state.storage.store_key(healthy).await.unwrap();
+        state.storage.store_key(stale.clone()).await.unwrap();
+
+        let response = validate_keystore(State(state.clone()), None, Json(ValidateKeystoreRequest { repair: true, stream: true })).await;
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/x-ndjson");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let lines: Vec<serde_json::Value> = String::from_utf8(body.to_vec()).unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["type"], "progress");
+        assert_eq!(lines[1]["checked"], 2);
+        assert_eq!(lines[2]["type"], "summary");
+        assert_eq!(lines[2]["result"]["repaired"], 1);
+
+        let stored = state.storage.get_key(stale.id).await.unwrap();
+        assert!(stored.fingerprint.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_sign_returns_notarized_bundle_and_records_receipt() {
+        let dir = tempdir().unwrap();
+        let base = test_state(&dir, Arc::new(MockClock::new(Utc::now())));
+        let notary = generate_test_key_pair("Notary").unwrap();
+        let key_pair = generate_test_key_pair("Contracts").unwrap();
+        base.storage.store_key(notary.clone()).await.unwrap();
+        base.storage.store_key(key_pair.clone()).await.unwrap();
+        let state = Arc::new(AppState {
+            storage: base.storage.clone(),
+            clock: base.clock.clone(),
+            config: Arc::new(Config { notary_key_id: Some(notary.id), ..Default::default() }),
+            receipts: base.receipts.clone(),
+            certifications: base.certifications.clone(),
+            read_only: AtomicBool::new(false),
+            self_test: RwLock::new(None),
+            keystore_load: None,
+            entropy: Arc::new(EntropyMonitor::os()),
+            hsm: None,
+            follower: false,
+            limits: OperationLimits::default(),
+            shares: base.shares.clone(),
+            delegations: base.delegations.clone(),
+            verification_cache: VerificationCache::disabled(),
+            request_auth: RequestAuthenticator::disabled(),
+            capacity: KeystoreCapacity::unlimited(),
+            kdf_timings: KdfTimings::new(),
+            verify_rate_limit: ClientRateLimiter::unlimited(),
+            sign_policy: Arc::new(SignPolicy::allow_all()),
+            key_resolver: Arc::new(KeyResolver::none()),
+            transport_key: Arc::new(TransportKey::generate(Utc::now())),
+            sweeper: Arc::new(TaskStatus::default()),
+            deadlines: RequestDeadlines::from_config(&Config::default()),
+            api_usage: ApiUsage::default(),
+            key_pool: Arc::new(KeyPool::from_config(&Config::default())),
+            usage_reports: UsageReportCache::default(),
+            slo: SloTracker::from_config(&Config::default()),
+        });
+
+        let signed = sign_document(State(state.clone()), Json(SignDocumentRequest {
+            key_id: key_pair.id,
+            document_content: Some("master services agreement".to_string()),
+            bundle: true,
+            ..Default::default()
+        })).await.unwrap().0;
+        let bundle = signed.bundle.unwrap();
+        assert_eq!(Some(bundle.body.signature_id), signed.signature_id);
+        assert_eq!(bundle.notary.as_ref().unwrap().key_id, notary.id);
+
+        let result = crate::bundle::verify_bundle(&bundle, crate::bundle::BundleSubject::Content("master services agreement")).unwrap();
+        assert!(result.valid, "{:?}", result);
+        assert_eq!(result.notary_valid, Some(true));
+
+        // The receipt serves the same bundle as JSON and CBOR
+        let fetch = |format| get_signature_bundle(State(state.clone()), Path(bundle.body.signature_id), Query(BundleQuery { format }));
+        let body = axum::body::to_bytes(fetch(BundleFormat::Json).await.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(serde_json::from_slice::<Bundle>(&body).unwrap(), bundle);
+        let response = fetch(BundleFormat::Cbor).await;
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/cbor");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(Bundle::from_cbor(&body).unwrap(), bundle);
+
+        let missing = get_signature_bundle(State(state), Path(Uuid::new_v4()), Query(BundleQuery { format: BundleFormat::Json })).await;
+        assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_signatures_record_the_build_that_made_them() {
+        use axum::body::Body;
+        use tower::ServiceExt;
+
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir, Arc::new(MockClock::new(Utc::now())));
+        let key_pair = generate_test_key_pair("Provenance").unwrap();
+        state.storage.store_key(key_pair.clone()).await.unwrap();
+
+        let signed = sign_document(State(state.clone()), Json(SignDocumentRequest {
+            key_id: key_pair.id,
+            document_content: Some("build provenance".to_string()),
+            bundle: true,
+            ..Default::default()
+        })).await.unwrap().0;
+        let signer_info = signed.signer_info.clone().unwrap();
+        assert_eq!(signer_info.version, env!("CARGO_PKG_VERSION"));
+        assert!(signer_info.built_at.is_some());
+
+        // The bundle carries it through the offline verifier, and the receipt keeps it
+        let bundle: Bundle = serde_json::from_str(&serde_json::to_string(&signed.bundle.unwrap()).unwrap()).unwrap();
+        assert_eq!(bundle.body.signer_info.as_ref(), Some(&signer_info));
+        assert!(crate::bundle::verify_bundle(&bundle, crate::bundle::BundleSubject::Content("build provenance")).unwrap().valid);
+        let record = get_signature_record(State(state.clone()), Path(signed.signature_id.unwrap())).await;
+        let body = axum::body::to_bytes(record.into_body(), usize::MAX).await.unwrap();
+        let record: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(record["signer_info"]["version"], env!("CARGO_PKG_VERSION"));
+
+        // Provenance is not part of the signed message
+        let verify = VerifySignatureRequest {
+            public_key: key_pair.public_key.clone(),
+            signature: signed.signature.unwrap(),
+            document_hash: signed.document_hash,
+            ..Default::default()
+        };
+        assert!(crate::key_verification::verify_signature(&verify).unwrap());
+
+        let app = crate::routes::router_with_versions(state, ApiVersion::ALL);
+        let response = app.oneshot(axum::http::Request::builder().uri("/v1/version").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let version: VersionResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(version.build, signer_info);
+    }
+
+    #[tokio::test]
+    async fn test_generate_dry_run_validates_without_persisting() {
+        let dir = tempdir().unwrap();
+        let state = Arc::new(AppState {
+            config: Arc::new(Config { max_keys: Some(1), ..Default::default() }),
+            ..Arc::into_inner(test_state(&dir, Arc::new(MockClock::new(Utc::now())))).unwrap()
+        });
+        let request = |name: &str| GenerateKeyRequest {
+            name: name.to_string(),
+            description: None,
+            password: Some("hunter22".to_string()),
+            expires_at: None,
+            tags: Some(vec!["ci".to_string()]),
+            key_strength: Some(KeyStrength::High),
+            hsm: None,
+            generate_password: false,
+            template: None,
+            environment: None,
+            fast: false,
+            exportable: None,
+            default_output_format: None,
+            default_hash_algorithm: None,
+            default_encoding: None,
+        };
+        let dry_run = || Query(GenerateKeyQuery { dry_run: true });
+
+        let response = generate_keys(State(state.clone()), dry_run(), Json(request("Release"))).await.unwrap().0;
+        assert!(response.success && response.dry_run);
+        assert!(response.key_pair.is_none());
+        assert_eq!(response.key_type, Some(KeyType::Ed25519Encrypted));
+        assert_eq!(response.key_strength, Some(KeyStrength::High));
+        assert_eq!(state.storage.key_count().await, 0);
+        assert!(!dir.path().join("keys.json").exists());
+
+        let created = generate_keys(State(state.clone()), Query(GenerateKeyQuery::default()), Json(request("Release"))).await.unwrap().0;
+        assert!(created.key_pair.is_some() && !created.dry_run);
+
+        let (status, Json(response)) = generate_keys(State(state.clone()), dry_run(), Json(request("release"))).await.unwrap_err();
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(response.dry_run);
+        assert!(response.errors.iter().any(|error| error.field == "name" && error.message.contains("already exists")));
+        assert!(response.errors.iter().any(|error| error.message.contains("quota")));
+
+        let (status, _) = generate_keys(State(state.clone()), Query(GenerateKeyQuery::default()), Json(request("Other"))).await.unwrap_err();
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(state.storage.key_count().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_generations_of_one_name_store_one_key() {
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir, Arc::new(MockClock::new(Utc::now())));
+        let generate = |name: &str| generate_keys(State(state.clone()), Query(GenerateKeyQuery::default()), Json(GenerateKeyRequest {
+            name: name.to_string(),
+            ..Default::default()
+        }));
+
+        // Whichever generation stores second is refused, whether its validation ran before the
+        // first was stored or after
+        let (first, second) = tokio::join!(generate("Release"), generate("release"));
+        let (stored, (status, Json(refused))) = match (first, second) {
+            (Ok(Json(stored)), Err(refused)) | (Err(refused), Ok(Json(stored))) => (stored, refused),
+            _ => panic!("expected exactly one generation to store its key"),
+        };
+        assert!(stored.success);
+        assert!(matches!(status, StatusCode::CONFLICT | StatusCode::UNPROCESSABLE_ENTITY), "{}", status);
+        assert!(refused.errors.iter().any(|error| error.field == "name" && error.message.contains("already exists")));
+        assert_eq!(state.storage.key_count().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_expiry_rules_apply_to_generate_and_update() {
+        let dir = tempdir().unwrap();
+        let now = Utc::now();
+        let clock = Arc::new(MockClock::new(now));
+        let state = test_state(&dir, clock.clone());
+        let request = |expires_at| GenerateKeyRequest {
+            name: "Temporal".to_string(),
+            description: None,
+            password: None,
+            expires_at: Some(expires_at),
+            tags: None,
+            key_strength: None,
+            hsm: None,
+            generate_password: false,
+            template: None,
+            environment: None,
+            fast: false,
+            exportable: None,
+            default_output_format: None,
+            default_hash_algorithm: None,
+            default_encoding: None,
+        };
+
+        let (status, Json(response)) = generate_keys(
+            State(state.clone()),
+            Query(GenerateKeyQuery::default()),
+            Json(request(now - Duration::seconds(60))),
+        ).await.unwrap_err();
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(response.errors[0].field, "expires_at");
+
+        let key = generate_keys(
+            State(state.clone()),
+            Query(GenerateKeyQuery::default()),
+            Json(request(now + Duration::days(10))),
+        ).await.unwrap().0.key_pair.unwrap();
+
+        let update = |expires_at| UpdateKeyRequest {
+            name: None,
+            description: None,
+            tags: None,
+            expires_at: Some(expires_at),
+            is_active: None,
+            allowed_contexts: None,
+            environment: None,
+            exportable: None,
+            default_output_format: None,
+            default_hash_algorithm: None,
+            default_encoding: None,
+        };
+
+        // The service clock moves on; an expiry that was fine at creation is now in the past
+        clock.advance(Duration::days(20));
+        let (status, Json(response)) = update_key(State(state.clone()), Path(key.id), Json(update(now + Duration::days(15))), None)
+            .await.unwrap_err();
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(response.errors[0].message.contains("future"));
+        assert!(update_key(State(state.clone()), Path(key.id), Json(update(now + Duration::days(25))), None).await.is_ok());
+
+        state.storage.revoke_key(key.id, None).await.unwrap();
+        let revoked_at = state.storage.get_key_record(key.id).await.unwrap().expires_at.unwrap();
+        let (status, Json(response)) = update_key(State(state.clone()), Path(key.id), Json(update(now + Duration::days(40))), None)
+            .await.unwrap_err();
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(response.errors[0].message.contains("revoked"));
+        assert!(update_key(State(state.clone()), Path(key.id), Json(update(revoked_at - Duration::hours(1))), None).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_generate_applies_lifetime_policy() {
+        let dir = tempdir().unwrap();
+        let now = crate::timestamps::normalize(Utc::now());
+        let state = Arc::new(AppState {
+            config: Arc::new(Config {
+                default_key_lifetime_days: Some(30),
+                max_key_lifetime_days: Some(365),
+                ..Default::default()
+            }),
+            ..Arc::into_inner(test_state(&dir, Arc::new(MockClock::new(now)))).unwrap()
+        });
+        let request = |name: &str, expires_at| GenerateKeyRequest {
+            name: name.to_string(),
+            description: None,
+            password: None,
+            expires_at,
+            tags: None,
+            key_strength: None,
+            hsm: None,
+            generate_password: false,
+            template: None,
+            environment: None,
+            fast: false,
+            exportable: None,
+            default_output_format: None,
+            default_hash_algorithm: None,
+            default_encoding: None,
+        };
+
+        let defaulted = generate_keys(State(state.clone()), Query(GenerateKeyQuery::default()), Json(request("Defaulted", None)))
+            .await.unwrap().0;
+        assert_eq!(defaulted.expiry_source, Some(ExpirySource::Defaulted));
+        assert_eq!(defaulted.key_pair.unwrap().expires_at, Some(now + Duration::days(30)));
+
+        let clamped = generate_keys(
+            State(state.clone()),
+            Query(GenerateKeyQuery::default()),
+            Json(request("Clamped", Some(now + Duration::days(1000)))),
+        ).await.unwrap().0;
+        assert_eq!(clamped.expiry_source, Some(ExpirySource::Clamped));
+        assert_eq!(clamped.expires_at, Some(now + Duration::days(365)));
+        let stored = state.storage.get_key(clamped.key_pair.unwrap().id).await.unwrap();
+        assert_eq!(stored.expires_at, Some(now + Duration::days(365)));
+    }
+
+    #[tokio::test]
+    async fn test_scheduled_revocation_lifecycle() {
+        let dir = tempdir().unwrap();
+        let now = crate::timestamps::normalize(Utc::now());
+        let clock = Arc::new(MockClock::new(now));
+        let state = test_state(&dir, clock.clone());
+        let first = generate_test_key_pair("First").unwrap();
+        let second = generate_test_key_pair("Second").unwrap();
+        state.storage.store_key(first.clone()).await.unwrap();
+        state.storage.store_key(second.clone()).await.unwrap();
+        let schedule = |key_id, effective_at| RevokeKeyRequest {
+            key_id,
+            reason: Some("rotation".to_string()),
+            immediate: false,
+            effective_at: Some(effective_at),
+        };
+
+        let (status, _) = revoke_key(State(state.clone()), Path(first.id), Json(schedule(second.id, now + Duration::hours(1))), None)
+            .await.unwrap_err();
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let (status, _) = revoke_key(State(state.clone()), Path(first.id), Json(RevokeKeyRequest { effective_at: None, ..schedule(first.id, now) }), None)
+            .await.unwrap_err();
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let effective_at = now + Duration::hours(1);
+        for key in [&first, &second] {
+            let response = revoke_key(State(state.clone()), Path(key.id), Json(schedule(key.id, effective_at)), None).await.unwrap().0;
+            assert!(response.scheduled);
+            assert_eq!(response.revocation_time, Some(effective_at));
+        }
+
+        // Scheduled keys stay usable and listings show the pending revocation
+        assert!(state.storage.get_key(first.id).await.is_ok());
+        let listed = state.storage.list_keys().await;
+        assert!(listed.iter().all(|key| key.is_active && key.revocation_scheduled_at == Some(effective_at)));
+
+        assert!(cancel_scheduled_revocation(State(state.clone()), Path(second.id)).await.unwrap().0.success);
+        let (status, _) = cancel_scheduled_revocation(State(state.clone()), Path(second.id)).await.unwrap_err();
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let report = crate::sweeper::sweep(&state.storage, clock.as_ref(), None).await.unwrap();
+        assert!(report.revoked.is_empty());
+
+        clock.advance(Duration::hours(2));
+        let report = crate::sweeper::sweep(&state.storage, clock.as_ref(), None).await.unwrap();
+        assert_eq!(report.revoked, vec![first.id]);
+
+        let revoked = state.storage.get_key_record(first.id).await.unwrap();
+        assert_eq!(revoked.lifecycle, Lifecycle::Revoked);
+        assert_eq!(revoked.expires_at, Some(effective_at));
+        assert_eq!(revoked.revocation_scheduled_at, None);
+        assert_eq!(state.storage.get_key_record(second.id).await.unwrap().lifecycle, Lifecycle::Active);
+    }
+
+    #[tokio::test]
+    async fn test_certification_chain_verifies_offline() {
+        let dir = tempdir().unwrap();
+        let now = Utc::now();
+        let state = test_state(&dir, Arc::new(MockClock::new(now)));
+        let keys: Vec<KeyPair> = ["Root", "Intermediate", "Release"].iter()
+            .map(|name| generate_test_key_pair(name).unwrap())
+            .collect();
+        for key in &keys {
+            state.storage.store_key(key.clone()).await.unwrap();
+        }
+
+        for pair in keys.windows(2) {
+            let response = certify_key(State(state.clone()), Path(pair[0].id), Json(CertifyKeyRequest {
+                target_key_id: Some(pair[1].id),
+                valid_until: Some(now + Duration::days(365)),
+                ..Default::default()
+            })).await.unwrap().0;
+            assert!(response.success, "{}", response.message);
+        }
+
+        let listed = get_key_certifications(State(state.clone()), Path(keys[1].id)).await.unwrap().0;
+        assert_eq!((listed.issued.len(), listed.received.len()), (1, 1));
+
+        let signed = sign_document(State(state.clone()), Json(SignDocumentRequest {
+            key_id: keys[2].id,
+            document_content: Some("release notes".to_string()),
+            ..Default::default()
+        })).await.unwrap().0;
+        let verified = verify_signature(State(state.clone()), Json(VerifySignatureRequest {
+            key_id: Some(keys[2].id),
+            include_chain: true,
+            signature: signed.signature.unwrap(),
+            document_content: Some("release notes".to_string()),
+            ..Default::default()
+        })).await.unwrap().0;
+        assert!(verified.is_valid);
+        assert_eq!(verified.key_info.unwrap().id, keys[2].id);
+
+        // A verifier that only trusts the root key can follow the chain without the service
+        let chain = verified.certification_chain.unwrap();
+        assert_eq!(chain.len(), 2);
+        crate::certification::verify_chain(&chain, &keys[0].public_key, &keys[2].public_key, now).unwrap();
+
+        state.storage.revoke_key(keys[0].id, None).await.unwrap();
+        let (status, _) = certify_key(State(state.clone()), Path(keys[0].id), Json(CertifyKeyRequest {
+            target_public_key: Some(keys[2].public_key.clone()),
+            ..Default::default()
+        })).await.unwrap_err();
+        assert_eq!(status, StatusCode::GONE);
+    }
+
+    #[tokio::test]
+    async fn test_read_only_mode_blocks_mutations_only() {
+        use axum::body::Body;
+        use axum::routing::{get, post, put};
+        use tower::ServiceExt;
+
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir, Arc::new(MockClock::new(Utc::now())));
+        let key_pair = generate_test_key_pair("Maintenance").unwrap();
+        state.storage.store_key(key_pair.clone()).await.unwrap();
+
+        let app = axum::Router::new()
+            .route("/keys", get(list_keys))
+            .route("/keys/generate", post(generate_keys))
+            .route("/keys/:key_id", put(|state, path, Json(request)| async move { update_key(state, path, Json(request), None).await }))
+            .route("/keys/:key_id/revoke", post(|state, path, Json(request)| async move { revoke_key(state, path, Json(request), None).await }))
+            .route("/sign", post(sign_document))
+            .route("/verify", post(verify_signature))
+            .route("/admin/read-only", post(set_read_only))
+            .layer(axum::middleware::from_fn_with_state(state.clone(), read_only_guard))
+            .with_state(state.clone());
+        let call = |method: Method, uri: String, body: serde_json::Value| {
+            let request = axum::http::Request::builder()
+                .method(method)
+                .uri(uri)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap();
+            app.clone().oneshot(request)
+        };
+
+        let response = call(Method::POST, "/admin/read-only".to_string(), serde_json::json!({ "enabled": true })).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(readiness(State(state.clone())).await.1.read_only);
+
+        let denied = [
+            (Method::POST, "/keys/generate".to_string(), serde_json::json!({ "name": "Blocked" })),
+            (Method::PUT, format!("/keys/{}", key_pair.id), serde_json::json!({ "name": "Renamed" })),
+            (Method::POST, format!("/keys/{}/revoke", key_pair.id), serde_json::json!({ "key_id": key_pair.id, "immediate": true })),
+            (Method::POST, "/sign".to_string(), serde_json::json!({ "key_id": key_pair.id, "document_content": "x" })),
+        ];
+        for (method, uri, body) in denied.clone() {
+            let response = call(method, uri.clone(), body).await.unwrap();
+            assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE, "{}", uri);
+            assert_eq!(response.headers()[header::RETRY_AFTER], "300");
+        }
+
+        assert_eq!(call(Method::GET, "/keys".to_string(), serde_json::Value::Null).await.unwrap().status(), StatusCode::OK);
+        let verify = serde_json::json!({ "key_id": key_pair.id, "signature": "AAAA", "document_content": "x" });
+        assert_eq!(call(Method::POST, "/verify".to_string(), verify).await.unwrap().status(), StatusCode::OK);
+        assert_eq!(state.storage.key_count().await, 1);
+
+        call(Method::POST, "/admin/read-only".to_string(), serde_json::json!({ "enabled": false })).await.unwrap();
+        for (method, uri, body) in denied {
+            let response = call(method, uri.clone(), body).await.unwrap();
+            assert_ne!(response.status(), StatusCode::SERVICE_UNAVAILABLE, "{}", uri);
+        }
+        assert!(!readiness(State(state.clone())).await.1.read_only);
+    }
+
+    #[tokio::test]
+    async fn test_on_demand_self_test_is_reported_by_readiness() {
+        let dir = tempdir().unwrap();
+        let state = Arc::new(AppState {
+            config: Arc::new(Config { kdf: crate::config::KdfParams::pbkdf2(crate::config::MIN_PBKDF2_ITERATIONS), ..Default::default() }),
+            ..Arc::into_inner(test_state(&dir, Arc::new(MockClock::new(Utc::now())))).unwrap()
+        });
+        let (status, Json(before)) = readiness(State(state.clone())).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(before.self_test.is_none());
+
+        let report = self_test(State(state.clone())).await.unwrap().0;
+        assert!(report.checks.iter().all(|check| check.passed && check.duration_ms >= 0.0));
+
+        let (status, Json(after)) = readiness(State(state.clone())).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(after.self_test, Some(report));
+        assert_eq!(after.key_count, 0);
+    }
+
+    #[tokio::test]
+    async fn test_usage_counters_survive_restart() {
+        let dir = tempdir().unwrap();
+        let clock = Arc::new(MockClock::new(Utc::now()));
+        let state = test_state(&dir, clock.clone());
+        let key_pair = generate_test_key_pair("Busy Key").unwrap();
+        state.storage.store_key(key_pair.clone()).await.unwrap();
+
+        let sign = |content: String, valid_until| SignDocumentRequest {
+            key_id: key_pair.id,
+            document_content: Some(content),
+            valid_until,
+            ..Default::default()
+        };
+        let mut signature = None;
+        for round in 0..3 {
+            let signed = sign_document(State(state.clone()), Json(sign(format!("hot path {}", round), None))).await.unwrap().0;
+            assert!(signed.success);
+            signature = signed.signature;
+        }
+        // Failed attempts are not counted
+        let (_, Json(failed)) = sign_document(State(state.clone()), Json(sign("hot path".to_string(), Some(clock.now() - Duration::seconds(1))))).await.unwrap_err();
+        assert!(!failed.success);
+
+        let verified = verify_signature(State(state.clone()), Json(VerifySignatureRequest {
+            key_id: Some(key_pair.id),
+            signature: signature.unwrap(),
+            document_content: Some("hot path 2".to_string()),
+            ..Default::default()
+        })).await.unwrap().0;
+        assert_eq!(verified.key_info.unwrap().usage.verify_count, 1);
+
+        assert!(state.storage.flush().await.unwrap());
+        assert!(!state.storage.flush().await.unwrap());
+
+        let restarted = KeyStorage::new(dir.path().join("keys.json").to_str().unwrap());
+        restarted.load_from_disk().await.unwrap();
+        let usage = restarted.get_key(key_pair.id).await.unwrap().usage;
+        assert_eq!((usage.sign_count, usage.verify_count), (3, 1));
+        assert_eq!(usage.last_sign_at, Some(clock.now()));
+    }
+
+    #[tokio::test]
+    async fn test_degraded_persistence_warns_and_switches_to_read_only() {
+        use axum::body::Body;
+        use axum::routing::{get, put};
+        use tower::ServiceExt;
+
+        let dir = tempdir().unwrap();
+        let storage_dir = dir.path().join("unavailable");
+        let state = Arc::new(AppState {
+            storage: Arc::new(KeyStorage::new(storage_dir.join("keys.json").to_str().unwrap())),
+            config: Arc::new(Config { read_only_after_write_failures: Some(2), ..Default::default() }),
+            ..Arc::into_inner(test_state(&dir, Arc::new(MockClock::new(Utc::now())))).unwrap()
+        });
+        let key_pair = generate_test_key_pair("Fragile").unwrap();
+        state.storage.store_key(key_pair.clone()).await.unwrap();
+
+        let app = axum::Router::new()
+            .route("/keys", get(list_keys))
+            .route("/keys/:key_id", put(|state, path, Json(request)| async move { update_key(state, path, Json(request), None).await }))
+            .layer(axum::middleware::from_fn_with_state(state.clone(), read_only_guard))
+            .with_state(state.clone());
+        let rename = |name: &str| {
+            let request = axum::http::Request::builder()
+                .method(Method::PUT)
+                .uri(format!("/keys/{}", key_pair.id))
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(serde_json::json!({ "name": name }).to_string()))
+                .unwrap();
+            app.clone().oneshot(request)
+        };
+
+        // The update succeeds in memory but is flagged
+        let response = rename("Renamed").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::WARNING], PERSISTENCE_DEGRADED_WARNING);
+        assert_eq!(state.storage.get_key(key_pair.id).await.unwrap().name, "Renamed");
+
+        let (_, Json(ready)) = readiness(State(state.clone())).await;
+        assert!(ready.persistence.degraded);
+        assert_eq!(ready.persistence.consecutive_failures, 2);
+
+        // Two failed writes reach the configured limit
+        let response = rename("Again").await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert!(state.read_only.load(Ordering::SeqCst));
+
+        std::fs::create_dir_all(&storage_dir).unwrap();
+        assert!(state.storage.flush().await.unwrap());
+        let (_, Json(ready)) = readiness(State(state.clone())).await;
+        assert!(!ready.persistence.degraded);
+        let metrics = metrics(State(state.clone())).await;
+        let body = axum::body::to_bytes(metrics.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8_lossy(&body).contains("inkan_persistence_degraded 0"));
+    }
+
+    #[tokio::test]
+    async fn test_sign_withholds_signatures_whose_receipt_is_not_recorded() {
+        let dir = tempdir().unwrap();
+        let receipts_path = dir.path().join("unavailable").join("receipts.json");
+        let with_policy = |receipt_failure| Arc::new(AppState {
+            receipts: Arc::new(ReceiptStore::new(receipts_path.to_str().unwrap())),
+            config: Arc::new(Config { receipt_failure, ..Default::default() }),
+            ..Arc::into_inner(test_state(&dir, Arc::new(MockClock::new(Utc::now())))).unwrap()
+        });
+        let request = |key_id| SignDocumentRequest {
+            key_id,
+            document_content: Some("unreceipted".to_string()),
+            ..Default::default()
+        };
+
+        // By default the signature is never released, and the key is not charged for it
+        let state = with_policy(ReceiptFailurePolicy::Reject);
+        let key_pair = generate_test_key_pair("Receipts Down").unwrap();
+        state.storage.store_key(key_pair.clone()).await.unwrap();
+        let (status, Json(response)) = sign_document(State(state.clone()), Json(request(key_pair.id))).await.unwrap_err();
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.code, Some(ErrorCode::ReceiptNotRecorded));
+        assert!(response.signature.is_none() && response.bundle.is_none());
+        assert_eq!(state.receipts.count().await, 0);
+        assert_eq!(state.receipts.failed_writes(), 1);
+        assert_eq!(state.storage.get_key(key_pair.id).await.unwrap().usage.sign_count, 0);
+        let metrics = metrics(State(state.clone())).await;
+        let body = axum::body::to_bytes(metrics.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8_lossy(&body).contains("inkan_receipt_write_failures_total 1"));
+
+        // Allowed by policy, it is released with a warning but still not served as a receipt
+        let state = with_policy(ReceiptFailurePolicy::Warn);
+        state.storage.store_key(key_pair.clone()).await.unwrap();
+        let response = sign_document(State(state.clone()), Json(request(key_pair.id))).await.unwrap().0;
+        assert!(response.success && response.signature.is_some());
+        assert!(response.warnings.iter().any(|warning| warning.code == WarningCode::ReceiptNotRecorded));
+        assert!(state.receipts.get(response.signature_id.unwrap()).await.is_none());
+
+        // Once the directory is back, receipts are written whole through a temporary file
+        std::fs::create_dir_all(receipts_path.parent().unwrap()).unwrap();
+        let response = sign_document(State(state.clone()), Json(request(key_pair.id))).await.unwrap().0;
+        assert!(response.warnings.iter().all(|warning| warning.code != WarningCode::ReceiptNotRecorded), "{:?}", response.warnings);
+        let reloaded = ReceiptStore::new(receipts_path.to_str().unwrap());
+        reloaded.load_from_disk().await.unwrap();
+        assert!(reloaded.get(response.signature_id.unwrap()).await.is_some());
+        assert!(!dir.path().join("unavailable").join("receipts.json.tmp").exists());
+    }
+
+    #[tokio::test]
+    async fn test_sign_succeeds_with_a_warning_while_usage_cannot_be_written() {
+        let dir = tempdir().unwrap();
+        let storage_dir = dir.path().join("unavailable");
+        let keys_path = storage_dir.join("keys.json");
+        let state = Arc::new(AppState {
+            storage: Arc::new(KeyStorage::new(keys_path.to_str().unwrap())),
+            ..Arc::into_inner(test_state(&dir, Arc::new(MockClock::new(Utc::now())))).unwrap()
+        });
+        let key_pair = generate_test_key_pair("Counters Down").unwrap();
+        state.storage.store_key(key_pair.clone()).await.unwrap();
+
+        let response = sign_document(State(state.clone()), Json(SignDocumentRequest {
+            key_id: key_pair.id,
+            document_content: Some("counted later".to_string()),
+            ..Default::default()
+        })).await.unwrap().0;
+        assert!(response.success && response.signature.is_some());
+        assert!(response.warnings.iter().any(|warning| warning.code == WarningCode::PersistenceDegraded));
+        assert!(state.receipts.get(response.signature_id.unwrap()).await.is_some());
+
+        // The counters wait in memory for the flusher's retry
+        std::fs::create_dir_all(&storage_dir).unwrap();
+        assert!(state.storage.flush().await.unwrap());
+        let reloaded = KeyStorage::new(keys_path.to_str().unwrap());
+        reloaded.load_from_disk().await.unwrap();
+        assert_eq!(reloaded.get_key(key_pair.id).await.unwrap().usage.sign_count, 1);
+
+        // An update for a key that is gone is dropped and counted
+        assert!(state.storage.record_sign(Uuid::new_v4(), Utc::now()).await.is_err());
+        assert_eq!(state.storage.dropped_usage_updates(), 1);
+        let metrics = metrics(State(state.clone())).await;
+        let body = axum::body::to_bytes(metrics.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8_lossy(&body).contains("inkan_usage_updates_dropped_total 1"));
+    }
+
+    #[tokio::test]
+    async fn test_export_archives_verify_offline() {
+        use crate::export::{verify_manifest_signature, ExportManifest, MANIFEST_FILE, MANIFEST_SIGNATURE_FILE};
+        use base64::Engine;
+        use std::collections::HashMap;
+        use std::io::Read;
+
+        let dir = tempdir().unwrap();
+        let notary = generate_test_key_pair("Notary").unwrap();
+        let state = Arc::new(AppState {
+            config: Arc::new(Config { notary_key_id: Some(notary.id), ..Default::default() }),
+            ..Arc::into_inner(test_state(&dir, Arc::new(MockClock::new(Utc::now())))).unwrap()
+        });
+        let keys: Vec<KeyPair> = ["Release", "Retired"].iter().map(|name| generate_test_key_pair(name).unwrap()).collect();
+        for key in keys.iter().chain([&notary]) {
+            state.storage.store_key(key.clone()).await.unwrap();
+        }
+        state.storage.revoke_key(keys[1].id, None).await.unwrap();
+
+        let download = |format: ArchiveFormat, include_revoked: bool| {
+            let state = state.clone();
+            async move {
+                let query = ExportKeysQuery { format, include: "pem,jwk".to_string(), include_revoked };
+                let response = export_keys(State(state), Query(query), None).await;
+                assert_eq!(response.status(), StatusCode::OK);
+                assert_eq!(response.headers()[header::CONTENT_TYPE], format.content_type());
+                axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap().to_vec()
+            }
+        };
+
+        let mut unpacked: Vec<HashMap<String, Vec<u8>>> = Vec::new();
+        let zip_bytes = download(ArchiveFormat::Zip, false).await;
+        let mut zip = zip::ZipArchive::new(std::io::Cursor::new(zip_bytes)).unwrap();
+        let mut files = HashMap::new();
+        for index in 0..zip.len() {
+            let mut file = zip.by_index(index).unwrap();
+            let mut content = Vec::new();
+            file.read_to_end(&mut content).unwrap();
+            files.insert(file.name().to_string(), content);
+        }
+        unpacked.push(files);
+
+        let tar_bytes = download(ArchiveFormat::TarGz, true).await;
+        let mut tar = tar::Archive::new(flate2::read::GzDecoder::new(tar_bytes.as_slice()));
+        let mut files = HashMap::new();
+        for entry in tar.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let mut content = Vec::new();
+            entry.read_to_end(&mut content).unwrap();
+            files.insert(entry.path().unwrap().display().to_string(), content);
+        }
+        unpacked.push(files);
+
+        for (files, expected_keys) in unpacked.iter().zip([2, 3]) {
+            let manifest_bytes = &files[MANIFEST_FILE];
+            let signature = serde_json::from_slice(&files[MANIFEST_SIGNATURE_FILE]).unwrap();
+            verify_manifest_signature(manifest_bytes, &signature).unwrap();
+            let mut tampered = manifest_bytes.clone();
+            tampered[0] = b' ';
+            assert!(verify_manifest_signature(&tampered, &signature).is_err());
+
+            let manifest: ExportManifest = serde_json::from_slice(manifest_bytes).unwrap();
+            assert_eq!(manifest.keys.len(), expected_keys);
+            assert_eq!(files.len(), expected_keys * 2 + 2);
+            for entry in &manifest.keys {
+                let pem = String::from_utf8(files[&entry.files[0]].clone()).unwrap();
+                let der: String = pem.lines().filter(|line| !line.starts_with("-----")).collect();
+                let der = base64::engine::general_purpose::STANDARD.decode(der).unwrap();
+                let from_pem = ed25519_dalek::VerifyingKey::from_bytes(der[12..].try_into().unwrap()).unwrap();
+
+                let jwk: serde_json::Value = serde_json::from_slice(&files[&entry.files[1]]).unwrap();
+                let x = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(jwk["x"].as_str().unwrap()).unwrap();
+                assert_eq!(from_pem.as_bytes().as_slice(), x.as_slice());
+                assert_eq!(from_pem, decode_public_key(&entry.public_key).unwrap());
+            }
+        }
+        let revoked_stem = public_key_to_fingerprint(&keys[1].public_key).unwrap().replace(':', "");
+        assert!(!unpacked[0].keys().any(|path| path.contains(&revoked_stem)));
+        assert!(unpacked[1].keys().any(|path| path.contains(&revoked_stem)));
+    }
+
+    #[tokio::test]
+    async fn test_patch_rejects_unknown_fields_and_skips_empty_updates() {
+        use axum::body::Body;
+        use axum::routing::put;
+        use tower::ServiceExt;
+
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir, Arc::new(MockClock::new(Utc::now())));
+        let key_pair = generate_test_key_pair("Strict").unwrap();
+        state.storage.store_key(key_pair.clone()).await.unwrap();
+
+        let update = |state: State<Arc<AppState>>, path: Path<Uuid>, StrictJson(request): StrictJson<UpdateKeyRequest>| async move {
+            update_key(state, path, Json(request), None).await.into_response()
+        };
+        let app = axum::Router::new()
+            .route("/keys/:key_id", put(update).patch(update))
+            .with_state(state.clone());
+        let call = |method: Method, body: &str| {
+            let request = axum::http::Request::builder()
+                .method(method)
+                .uri(format!("/keys/{}", key_pair.id))
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap();
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(request).await.unwrap();
+                let status = response.status();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                (status, serde_json::from_slice::<serde_json::Value>(&body).unwrap())
+            }
+        };
+
+        let (status, body) = call(Method::PATCH, r#"{"name": "Typo", "expire_at": "2030-01-01T00:00:00Z", "enabled": false}"#).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        let fields: Vec<&str> = body["errors"].as_array().unwrap().iter().map(|error| error["field"].as_str().unwrap()).collect();
+        assert_eq!(fields, vec!["enabled", "expire_at"]);
+        assert_eq!(state.storage.get_key(key_pair.id).await.unwrap().name, "Strict");
+
+        let (status, body) = call(Method::PATCH, r#"{"name": "Patched"}"#).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["key_info"]["name"], "Patched");
+        let (status, body) = call(Method::PUT, r#"{"description": "Put still works"}"#).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!((body["key_info"]["name"].as_str(), body["key_info"]["description"].as_str()), (Some("Patched"), Some("Put still works")));
+
+        // No-op updates answer with the current key and do not rewrite the keystore
+        let storage_path = dir.path().join("keys.json");
+        std::fs::remove_file(&storage_path).unwrap();
+        for body in ["", "{}", r#"{"name": null, "tags": null}"#] {
+            let (status, response) = call(Method::PATCH, body).await;
+            assert_eq!(status, StatusCode::OK, "{}", body);
+            assert_eq!(response["message"], "Nothing to update");
+            assert_eq!(response["key_info"]["name"], "Patched");
+        }
+        assert!(!storage_path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_field_case_round_trips_in_both_casings() {
+        use axum::body::Body;
+        use axum::routing::post;
+        use tower::ServiceExt;
+
+        let dir = tempdir().unwrap();
+        let state = AppState {
+            config: Arc::new(Config { field_case: FieldCase::Camel, ..Config::default() }),
+            ..Arc::into_inner(test_state(&dir, Arc::new(MockClock::new(Utc::now())))).unwrap()
+        };
+        let state = Arc::new(state);
+
+        let app = axum::Router::new()
+            .route("/keys/generate", post(|state: State<Arc<AppState>>, query: Query<GenerateKeyQuery>, StrictJson(request): StrictJson<GenerateKeyRequest>| async move {
+                generate_keys(state, query, Json(request)).await.into_response()
+            }))
+            .route("/sign", post(|state: State<Arc<AppState>>, StrictJson(request): StrictJson<SignDocumentRequest>| async move {
+                sign_document(state, Json(request)).await.into_response()
+            }))
+            .route("/verify", post(|state: State<Arc<AppState>>, StrictJson(request): StrictJson<VerifySignatureRequest>| async move {
+                verify_signature(state, Json(request)).await.into_response()
+            }))
+            .layer(axum::middleware::from_fn_with_state(state.clone(), field_case_layer))
+            .with_state(state.clone());
+        let call = |uri: &str, case: Option<&str>, body: serde_json::Value| {
+            let mut request = axum::http::Request::builder()
+                .method(Method::POST)
+                .uri(uri)
+                .header(header::CONTENT_TYPE, "application/json");
+            if let Some(case) = case {
+                request = request.header(FIELD_CASE_HEADER, case);
+            }
+            let request = request.body(Body::from(body.to_string())).unwrap();
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(request).await.unwrap();
+                let status = response.status();
+                let echoed = response.headers().get(FIELD_CASE_HEADER).map(|value| value.to_str().unwrap().to_string()).unwrap_or_default();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                (status, echoed, serde_json::from_slice::<serde_json::Value>(&body).unwrap())
+            }
+        };
+
+        // camelCase in, camelCase out (the configured default); enum variants keep their names
+        let (status, echoed, generated) = call("/keys/generate", None, serde_json::json!({
+            "name": "Camel", "keyStrength": "High", "expiresAt": "2099-01-01T00:00:00Z",
+        })).await;
+        assert_eq!((status, echoed.as_str()), (StatusCode::OK, "camel"));
+        assert_eq!(generated["keyPair"]["keyStrength"], "High");
+        assert_eq!(generated["keyPair"]["keyType"], "Ed25519");
+        assert_eq!(generated["dryRun"], false);
+        assert!(generated.get("key_pair").is_none());
+        let key_id = generated["keyPair"]["id"].as_str().unwrap().to_string();
+        let public_key = generated["keyPair"]["publicKey"].as_str().unwrap().to_string();
+
+        let (status, _, signed) = call("/sign", None, serde_json::json!({
+            "keyId": key_id, "documentContent": "camel document", "bundle": true,
+        })).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(signed["keyId"], key_id.as_str());
+        // Bundles are signed over their own field names and are passed through untouched
+        assert_eq!(signed["bundle"]["key_id"], key_id.as_str());
+        let bundle: Bundle = serde_json::from_value(signed["bundle"].clone()).unwrap();
+        assert!(crate::bundle::verify_bundle(&bundle, crate::bundle::BundleSubject::Content("camel document")).unwrap().valid);
+
+        // snake_case in, snake_case out when the header asks for it
+        let (status, echoed, verified) = call("/verify", Some("snake"), serde_json::json!({
+            "public_key": public_key, "signature": signed["signature"], "document_content": "camel document",
+        })).await;
+        assert_eq!((status, echoed.as_str()), (StatusCode::OK, "snake"));
+        assert_eq!(verified["is_valid"], true);
+        assert!(verified.get("isValid").is_none());
+
+        // Mixed request casing is accepted; responses follow the header
+        let (_, _, verified) = call("/verify", Some("camel"), serde_json::json!({
+            "keyId": key_id, "signature": signed["signature"], "document_content": "camel document",
+        })).await;
+        assert_eq!(verified["isValid"], true);
+        assert_eq!(verified["keyInfo"]["keyStrength"], "High");
+        assert_eq!(verified["keyInfo"]["usage"]["verifyCount"], 1);
+
+        let (status, _, rejected) = call("/verify", Some("kebab"), serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(rejected["errors"][0]["field"], FIELD_CASE_HEADER);
+    }
+
+    #[tokio::test]
+    async fn test_error_paths_return_documented_codes() {
+        use axum::body::Body;
+        use axum::routing::post;
+        use tower::ServiceExt;
+
+        let dir = tempdir().unwrap();
+        let clock = Arc::new(MockClock::new(Utc::now()));
+        let state = test_state(&dir, clock.clone());
+        let code = |value: serde_json::Value| value["code"].as_str().map(str::to_string);
+        fn json<T: serde::Serialize>(value: &T) -> serde_json::Value {
+            serde_json::to_value(value).unwrap()
+        }
+
+        let encrypted = crate::key_generation::generate_key_pair_with_kdf(GenerateKeyRequest {
+            name: "Encrypted".to_string(),
+            description: None,
+            password: Some("hunter22".to_string()),
+            expires_at: Some(clock.now() + Duration::days(1)),
+            tags: None,
+            key_strength: None,
+            hsm: None,
+            generate_password: false,
+            template: None,
+            environment: None,
+            fast: false,
+            exportable: None,
+            default_output_format: None,
+            default_hash_algorithm: None,
+            default_encoding: None,
+        }, &crate::config::KdfParams::pbkdf2(crate::config::MIN_PBKDF2_ITERATIONS)).unwrap();
+        state.storage.store_key(encrypted.clone()).await.unwrap();
+        let sign = |key_id: Uuid, password: Option<&str>| SignDocumentRequest {
+            key_id,
+            password: password.map(str::to_string),
+            document_content: Some("coded".to_string()),
+            ..Default::default()
+        };
+
+        let missing = Uuid::new_v4();
+        let (status, Json(response)) = sign_document(State(state.clone()), Json(sign(missing, None))).await.unwrap_err();
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(code(json(&response)).as_deref(), Some("KEY_NOT_FOUND"));
+        assert_eq!(response.details, Some(serde_json::json!({ "key_id": missing })));
+        let (status, Json(response)) = sign_document(State(state.clone()), Json(sign(encrypted.id, None))).await.unwrap_err();
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(code(json(&response)).as_deref(), Some("PASSWORD_REQUIRED"));
+        let (status, Json(response)) = sign_document(State(state.clone()), Json(sign(encrypted.id, Some("wrong")))).await.unwrap_err();
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(code(json(&response)).as_deref(), Some("DECRYPTION_FAILED"));
+        let response = sign_document(State(state.clone()), Json(sign(encrypted.id, Some("hunter22")))).await.unwrap().0;
+        assert!(response.success);
+        assert_eq!(code(json(&response)), None);
+
+        let response = verify_signature(State(state.clone()), Json(VerifySignatureRequest {
+            key_id: Some(encrypted.id),
+            signature: "AAAA".to_string(),
+            document_content: Some("coded".to_string()),
+            ..Default::default()
+        })).await.unwrap().0;
+        assert!(!response.is_valid);
+        assert_eq!(code(json(&response)).as_deref(), Some("INVALID_SIGNATURE_FORMAT"));
+
+        let (status, response) = revoke_key(State(state.clone()), Path(encrypted.id), Json(RevokeKeyRequest {
+            key_id: encrypted.id,
+            reason: None,
+            immediate: false,
+            effective_at: None,
+        }), None).await.unwrap_err();
+        assert_eq!((status, code(json(&response.0)).as_deref()), (StatusCode::UNPROCESSABLE_ENTITY, Some("VALIDATION_FAILED")));
+        let (status, response) = cancel_scheduled_revocation(State(state.clone()), Path(encrypted.id)).await.unwrap_err();
+        assert_eq!((status, code(json(&response.0)).as_deref()), (StatusCode::CONFLICT, Some("NO_SCHEDULED_REVOCATION")));
+
+        let mut expired = generate_test_key_pair("Expired").unwrap();
+        expired.expires_at = Some(Utc::now() - Duration::days(1));
+        state.storage.store_key(expired.clone()).await.unwrap();
+        let (status, Json(response)) = sign_document(State(state.clone()), Json(sign(expired.id, None))).await.unwrap_err();
+        assert_eq!((status, code(json(&response)).as_deref()), (StatusCode::GONE, Some("KEY_EXPIRED")));
+
+        let (status, response) = generate_keys(State(state.clone()), Query(GenerateKeyQuery::default()), Json(GenerateKeyRequest {
+            name: " ".to_string(),
+            description: None,
+            password: None,
+            expires_at: None,
+            tags: None,
+            key_strength: None,
+            hsm: None,
+            generate_password: false,
+            template: None,
+            environment: None,
+            fast: false,
+            exportable: None,
+            default_output_format: None,
+            default_hash_algorithm: None,
+            default_encoding: None,
+        })).await.unwrap_err();
+        assert_eq!((status, code(json(&response.0)).as_deref()), (StatusCode::UNPROCESSABLE_ENTITY, Some("VALIDATION_FAILED")));
+        assert_eq!(response.0.details, Some(serde_json::json!({ "count": 1 })));
+
+        let response = get_signature_bundle(State(state.clone()), Path(Uuid::new_v4()), Query(BundleQuery { format: BundleFormat::Json })).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(code(serde_json::from_slice(&body).unwrap()).as_deref(), Some("SIGNATURE_NOT_FOUND"));
+
+        // Errors raised before a handler runs carry codes too
+        state.read_only.store(true, Ordering::SeqCst);
+        let app = axum::Router::new()
+            .route("/sign", post(|state: State<Arc<AppState>>, StrictJson(request): StrictJson<SignDocumentRequest>| async move {
+                sign_document(state, Json(request)).await.into_response()
+            }))
+            .route("/verify", post(|state: State<Arc<AppState>>, StrictJson(request): StrictJson<VerifySignatureRequest>| async move {
+                verify_signature(state, Json(request)).await.into_response()
+            }))
+            .layer(axum::middleware::from_fn_with_state(state.clone(), read_only_guard))
+            .with_state(state.clone());
+        let call = |uri: &str, body: &str| {
+            let request = axum::http::Request::builder()
+                .method(Method::POST)
+                .uri(uri)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap();
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(request).await.unwrap();
+                let status = response.status();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                (status, code(serde_json::from_slice(&body).unwrap()))
+            }
+        };
+        assert_eq!(call("/sign", "{}").await, (StatusCode::SERVICE_UNAVAILABLE, Some("READ_ONLY".to_string())));
+        assert_eq!(call("/verify", r#"{"signature": "x", "keyid": 1}"#).await, (StatusCode::UNPROCESSABLE_ENTITY, Some("UNKNOWN_FIELD".to_string())));
+        assert_eq!(call("/verify", "{").await, (StatusCode::BAD_REQUEST, Some("INVALID_JSON".to_string())));
+
+        let catalog = error_codes().await.0;
+        assert!(catalog.errors.iter().any(|entry| entry.code == ErrorCode::RateLimited && entry.status == 429));
+    }
+
+    #[tokio::test]
+    async fn test_messages_follow_accept_language_and_codes_do_not() {
+        use axum::body::Body;
+        use axum::routing::post;
+        use tower::ServiceExt;
+
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir, Arc::new(MockClock::new(Utc::now())));
+        let mut revoked = generate_test_key_pair("Billing").unwrap();
+        revoked.lifecycle = Lifecycle::Revoked;
+        state.storage.store_key(revoked.clone()).await.unwrap();
+
+        let app = axum::Router::new()
+            .route("/keys/generate", post(|state: State<Arc<AppState>>, query: Query<GenerateKeyQuery>, StrictJson(request): StrictJson<GenerateKeyRequest>| async move {
+                generate_keys(state, query, Json(request)).await.into_response()
+            }))
+            .route("/sign", post(|state: State<Arc<AppState>>, StrictJson(request): StrictJson<SignDocumentRequest>| async move {
+                sign_document(state, Json(request)).await.into_response()
+            }))
+            .route("/keys/:key_id/revoke", post(|state: State<Arc<AppState>>, path: Path<Uuid>, StrictJson(request): StrictJson<RevokeKeyRequest>| async move {
+                revoke_key(state, path, Json(request), None).await.into_response()
+            }))
+            .layer(axum::middleware::from_fn(localize_layer))
+            .with_state(state.clone());
+        let call = |uri: String, language: Option<&'static str>, body: serde_json::Value| {
+            let mut request = axum::http::Request::builder()
+                .method(Method::POST)
+                .uri(uri)
+                .header(header::CONTENT_TYPE, "application/json");
+            if let Some(language) = language {
+                request = request.header(header::ACCEPT_LANGUAGE, language);
+            }
+            let request = request.body(Body::from(body.to_string())).unwrap();
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(request).await.unwrap();
+                let language = response.headers()[header::CONTENT_LANGUAGE].to_str().unwrap().to_string();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                (language, body["code"].clone(), body["message"].as_str().unwrap().to_string())
+            }
+        };
+
+        let missing = Uuid::new_v4();
+        let sign = serde_json::json!({ "key_id": missing, "document_content": "x" });
+        let cases = [
+            (None, "en", "Key not found or invalid".to_string()),
+            (Some("en-GB"), "en", "Key not found or invalid".to_string()),
+            (Some("fr-FR,fr;q=0.9"), "fr", format!("Clé {} introuvable", missing)),
+            (Some("de;q=0.9, ar;q=0.8"), "ar", format!("المفتاح {} غير موجود", missing)),
+        ];
+        for (language, expected_language, expected_message) in cases {
+            let (content_language, code, message) = call("/sign".to_string(), language, sign.clone()).await;
+            assert_eq!(content_language, expected_language);
+            assert_eq!(code, "KEY_NOT_FOUND");
+            assert_eq!(message, expected_message);
+        }
+
+        // Parameters: counts and key names
+        let invalid = serde_json::json!({ "name": "", "description": "d".repeat(10_000) });
+        let (_, code, message) = call("/keys/generate".to_string(), Some("fr"), invalid.clone()).await;
+        assert_eq!((code, message.as_str()), (serde_json::json!("VALIDATION_FAILED"), "Champs invalides : 2"));
+        let (_, code, message) = call("/keys/generate".to_string(), Some("ar"), invalid).await;
+        assert_eq!((code, message.as_str()), (serde_json::json!("VALIDATION_FAILED"), "عدد الحقول غير الصالحة: 2"));
+
+        let revoke = serde_json::json!({ "key_id": revoked.id, "immediate": false, "effective_at": Utc::now() + Duration::days(1) });
+        let (_, code, message) = call(format!("/keys/{}/revoke", revoked.id), Some("fr"), revoke.clone()).await;
+        assert_eq!((code, message.as_str()), (serde_json::json!("KEY_ALREADY_REVOKED"), "La clé « Billing » est déjà révoquée"));
+        let (_, code, message) = call(format!("/keys/{}/revoke", revoked.id), None, revoke).await;
+        assert_eq!((code, message), (serde_json::json!("KEY_ALREADY_REVOKED"), format!("Key {} is already revoked", revoked.id)));
+
+        // Success messages are translated too, with their parameters
+        let (_, code, message) = call("/keys/generate".to_string(), Some("fr"), serde_json::json!({ "name": "Fresh" })).await;
+        assert_eq!((code, message.as_str()), (serde_json::Value::Null, "Paire de clés générée avec succès"));
+    }
+
+    /// OS entropy that can be switched to failing reads, as a starved RNG device would
+    struct SwitchableEntropy(AtomicBool);
+
+    impl crate::entropy::EntropySource for SwitchableEntropy {
+        fn fill(&self, dest: &mut [u8]) -> Result<(), String> {
+            if self.0.load(Ordering::SeqCst) {
+                return Err("getrandom: device not ready".to_string());
+            }
+            crate::entropy::OsEntropy.fill(dest)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_entropy_failure_disables_generation_but_not_verification() {
+        let dir = tempdir().unwrap();
+        let source = Arc::new(SwitchableEntropy(AtomicBool::new(false)));
+        let state = Arc::new(AppState {
+            entropy: Arc::new(EntropyMonitor::new(source.clone())),
+            ..Arc::into_inner(test_state(&dir, Arc::new(MockClock::new(Utc::now())))).unwrap()
+        });
+        let generate = |name: &str| generate_keys(State(state.clone()), Query(GenerateKeyQuery::default()), Json(GenerateKeyRequest {
+            name: name.to_string(),
+            description: None,
+            password: None,
+            expires_at: None,
+            tags: None,
+            key_strength: None,
+            hsm: None,
+            generate_password: false,
+            template: None,
+            environment: None,
+            fast: false,
+            exportable: None,
+            default_output_format: None,
+            default_hash_algorithm: None,
+            default_encoding: None,
+        }));
+
+        let key_pair = generate("Before").await.unwrap().0.key_pair.unwrap();
+        let signed = sign_document(State(state.clone()), Json(SignDocumentRequest {
+            key_id: key_pair.id,
+            document_content: Some("release notes".to_string()),
+            ..Default::default()
+        })).await.unwrap().0;
+        assert!(readiness(State(state.clone())).await.1.ready);
+
+        // A failed periodic check flips generation off without a panic
+        source.0.store(true, Ordering::SeqCst);
+        assert!(state.entropy.check(state.clock.now()).degraded);
+        let (status, Json(refused)) = generate("During").await.unwrap_err();
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(refused.code, Some(ErrorCode::InternalError));
+        assert!(refused.message.contains("device not ready"), "{}", refused.message);
+        assert_eq!(state.storage.key_count().await, 1);
+
+        let (status, Json(ready)) = readiness(State(state.clone())).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert!(!ready.ready);
+        assert!(ready.entropy.degraded);
+        let body = render_metrics(&[], &ServiceMetrics { entropy: state.entropy.status(), ..Default::default() }, 10);
+        assert!(body.contains("inkan_entropy_degraded 1"));
+
+        // Existing keys still verify
+        let verified = verify_signature(State(state.clone()), Json(VerifySignatureRequest {
+            public_key: key_pair.public_key.clone(),
+            signature: signed.signature.unwrap(),
+            document_content: Some("release notes".to_string()),
+            ..Default::default()
+        })).await.unwrap().0;
+        assert!(verified.is_valid);
+
+        // The next passing check re-enables generation
+        source.0.store(false, Ordering::SeqCst);
+        assert!(!state.entropy.check(state.clock.now()).degraded);
+        assert!(generate("After").await.is_ok());
+        assert_eq!(readiness(State(state.clone())).await.0, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_signing_context_prevents_cross_document_replay() {
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir, Arc::new(MockClock::new(Utc::now())));
+        let key_pair = generate_test_key_pair("Context Key").unwrap();
+        state.storage.store_key(key_pair.clone()).await.unwrap();
+
+        let signed = sign_document(State(state.clone()), Json(SignDocumentRequest {
+            key_id: key_pair.id,
+            document_content: Some("INV-2024-0042 total 1200 EUR".to_string()),
+            context: Some("invoice".to_string()),
+            ..Default::default()
+        })).await.unwrap().0;
+        assert!(signed.success);
+        assert_eq!(signed.context.as_deref(), Some("invoice"));
+
+        let verify = |context: Option<&str>| verify_signature(State(state.clone()), Json(VerifySignatureRequest {
+            public_key: key_pair.public_key.clone(),
+            signature: signed.signature.clone().unwrap(),
+            document_content: Some("INV-2024-0042 total 1200 EUR".to_string()),
+            context: context.map(str::to_string),
+            ..Default::default()
+        }));
+        let verified = verify(Some("invoice")).await.unwrap().0;
+        assert!(verified.is_valid);
+        assert_eq!(verified.context.as_deref(), Some("invoice"));
+        assert!(!verify(Some("contract")).await.unwrap().0.is_valid);
+        assert!(!verify(None).await.unwrap().0.is_valid);
+        assert!(!verify(Some("")).await.unwrap().0.is_valid);
+
+        // The receipt's bundle carries the context and still verifies offline
+        let bundle = state.receipts.get(signed.signature_id.unwrap()).await.unwrap();
+        assert_eq!(bundle.body.context.as_deref(), Some("invoice"));
+        assert!(crate::bundle::verify_bundle(&bundle, crate::bundle::BundleSubject::Content("INV-2024-0042 total 1200 EUR")).unwrap().valid);
+
+        // An empty context signs exactly as before
+        let plain = sign_document(State(state.clone()), Json(SignDocumentRequest {
+            key_id: key_pair.id,
+            document_content: Some("INV-2024-0042 total 1200 EUR".to_string()),
+            context: Some(String::new()),
+            ..Default::default()
+        })).await.unwrap().0;
+        assert_eq!(plain.context, None);
+        let verified = verify_signature(State(state.clone()), Json(VerifySignatureRequest {
+            public_key: key_pair.public_key.clone(),
+            signature: plain.signature.unwrap(),
+            document_content: Some("INV-2024-0042 total 1200 EUR".to_string()),
+            ..Default::default()
+        })).await.unwrap().0;
+        assert!(verified.is_valid);
+
+        let (status, Json(too_long)) = sign_document(State(state.clone()), Json(SignDocumentRequest {
+            key_id: key_pair.id,
+            document_content: Some("anything".to_string()),
+            context: Some("x".repeat(crate::key_verification::MAX_CONTEXT_LENGTH + 1)),
+            ..Default::default()
+        })).await.unwrap_err();
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(too_long.code, Some(ErrorCode::ValidationFailed));
+    }
+
+    /// In-memory stand-in for an HSM, holding keys by slot and label
+    #[derive(Default)]
+    struct MockHsm {
+        keys: std::sync::Mutex<std::collections::HashMap<HsmKeyRef, ed25519_dalek::SigningKey>>,
+    }
+
+    impl SigningBackend for MockHsm {
+        fn name(&self) -> &str {
+            "mock"
+        }
+
+        fn generate(&self, key: &HsmKeyRef) -> Result<ed25519_dalek::VerifyingKey, KeyManagementError> {
+            let signing_key = ed25519_dalek::SigningKey::generate(&mut rand_core::OsRng);
+            let public_key = signing_key.verifying_key();
+            self.keys.lock().unwrap().insert(key.clone(), signing_key);
+            Ok(public_key)
+        }
+
+        fn signer(&self, key: &HsmKeyRef) -> Result<Box<dyn KeySigner>, KeyManagementError> {
+            let keys = self.keys.lock().unwrap();
+            let signing_key = keys.get(key).ok_or_else(|| KeyManagementError::InternalError(format!("No HSM object '{}'", key.label)))?;
+            Ok(Box::new(signing_key.clone()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hsm_keys_sign_without_exposing_private_keys() {
+        let dir = tempdir().unwrap();
+        let state = Arc::new(AppState {
+            hsm: Some(Arc::new(MockHsm::default())),
+            ..Arc::into_inner(test_state(&dir, Arc::new(MockClock::new(Utc::now())))).unwrap()
+        });
+        let hsm = HsmKeyRef { slot: 0, label: "root-2024".to_string() };
+        let request = |password: Option<&str>| GenerateKeyRequest {
+            name: "HSM Root".to_string(),
+            description: None,
+            password: password.map(str::to_string),
+            expires_at: None,
+            tags: None,
+            key_strength: None,
+            hsm: Some(hsm.clone()),
+            generate_password: false,
+            template: None,
+            environment: None,
+            fast: false,
+            exportable: None,
+            default_output_format: None,
+            default_hash_algorithm: None,
+            default_encoding: None,
+        };
+
+        // HSM keys take the device PIN, never a request password
+        let (status, Json(rejected)) = generate_keys(State(state.clone()), Query(GenerateKeyQuery::default()), Json(request(Some("hunter22!")))).await.unwrap_err();
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(rejected.errors[0].field, "password");
+
+        let key_pair = generate_keys(State(state.clone()), Query(GenerateKeyQuery::default()), Json(request(None))).await.unwrap().0.key_pair.unwrap();
+        assert_eq!(key_pair.key_type, KeyType::Ed25519Hsm);
+        assert!(state.storage.get_key_record(key_pair.id).await.unwrap().private_key.is_empty());
+        let stored = state.storage.list_keys().await;
+        assert_eq!(stored[0].hsm, Some(hsm.clone()));
+
+        let sign = || SignDocumentRequest {
+            key_id: key_pair.id,
+            document_content: Some("root key ceremony".to_string()),
+            password: Some("ignored".to_string()),
+            ..Default::default()
+        };
+        let signed = sign_document(State(state.clone()), Json(sign())).await.unwrap().0;
+        assert!(signed.success, "{}", signed.message);
+        let verified = verify_signature(State(state.clone()), Json(VerifySignatureRequest {
+            key_id: Some(key_pair.id),
+            signature: signed.signature.clone().unwrap(),
+            document_content: Some("root key ceremony".to_string()),
+            ..Default::default()
+        })).await.unwrap().0;
+        assert!(verified.is_valid);
+        let bundle = state.receipts.get(signed.signature_id.unwrap()).await.unwrap();
+        assert!(crate::bundle::verify_bundle(&bundle, crate::bundle::BundleSubject::Content("root key ceremony")).unwrap().valid);
+
+        // File formats need the key bytes, so they are refused
+        let (status, _) = sign_document(State(state.clone()), Json(SignDocumentRequest {
+            output_format: Some(SignatureOutputFormat::Minisign),
+            ..sign()
+        })).await.unwrap_err();
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        // Without a backend the key stays listed and verifiable but cannot sign
+        let detached = Arc::new(AppState { hsm: None, ..Arc::into_inner(state).unwrap() });
+        let (status, Json(failed)) = sign_document(State(detached.clone()), Json(sign())).await.unwrap_err();
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(failed.code, Some(ErrorCode::InternalError));
+
+        let revoked = revoke_key(State(detached.clone()), Path(key_pair.id), Json(RevokeKeyRequest {
+            key_id: key_pair.id,
+            reason: None,
+            immediate: true,
+            effective_at: None,
+        }), None).await.unwrap().0;
+        assert!(revoked.success);
+        assert!(!detached.storage.list_keys().await[0].is_active);
+    }
+
+    #[tokio::test]
+    async fn test_verify_reports_which_candidate_key_matched() {
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir, Arc::new(MockClock::new(Utc::now())));
+        let previous = generate_test_key_pair("Release 2023").unwrap();
+        let current = generate_test_key_pair("Release 2024").unwrap();
+        let unrelated = generate_test_key_pair("Unrelated").unwrap();
+        for key_pair in [&previous, &current, &unrelated] {
+            state.storage.store_key(key_pair.clone()).await.unwrap();
+        }
+
+        let sign = |key_id: Uuid| sign_document(State(state.clone()), Json(SignDocumentRequest {
+            key_id,
+            document_content: Some("release-1.4.0.tar.gz".to_string()),
+            ..Default::default()
+        }));
+        let signature = sign(current.id).await.unwrap().0.signature.unwrap();
+        let verify = |signature: String, key_ids: Vec<Uuid>, public_keys: Vec<String>| {
+            verify_signature(State(state.clone()), Json(VerifySignatureRequest {
+                signature,
+                document_content: Some("release-1.4.0.tar.gz".to_string()),
+                key_ids,
+                public_keys,
+                ..Default::default()
+            }))
+        };
+
+        let by_public_key = verify(signature.clone(), vec![], vec![previous.public_key.clone(), current.public_key.clone()]).await.unwrap().0;
+        assert!(by_public_key.is_valid);
+        assert_eq!(by_public_key.matched_candidate, Some(MatchedCandidate {
+            index: 1,
+            key_id: None,
+            fingerprint: Some(public_key_to_fingerprint(&current.public_key).unwrap()),
+        }));
+        assert!(by_public_key.key_info.is_none());
+
+        let by_key_id = verify(signature, vec![previous.id, current.id], vec![]).await.unwrap().0;
+        assert!(by_key_id.is_valid);
+        assert_eq!(by_key_id.matched_candidate.unwrap().key_id, Some(current.id));
+        assert_eq!(by_key_id.key_info.unwrap().id, current.id);
+        assert_eq!(state.storage.get_key_record(current.id).await.unwrap().usage.verify_count, 1);
+        assert_eq!(state.storage.get_key_record(previous.id).await.unwrap().usage.verify_count, 0);
+
+        // A signature from a key outside the candidates fails against all of them
+        let stranger = sign(unrelated.id).await.unwrap().0.signature.unwrap();
+        let rejected = verify(stranger, vec![previous.id], vec![current.public_key.clone()]).await.unwrap().0;
+        assert!(!rejected.is_valid);
+        assert_eq!(rejected.matched_candidate, None);
+        assert_eq!(rejected.message, "Signature does not match any of the 2 candidate keys");
+
+        let too_many = vec![current.public_key.clone(); MAX_VERIFY_CANDIDATES + 1];
+        let (status, _) = verify(String::new(), vec![], too_many).await.unwrap_err();
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn test_repeat_signatures_share_a_derived_id() {
+        let dir = tempdir().unwrap();
+        let start = Utc::now();
+        let clock = Arc::new(MockClock::new(start));
+        let state = test_state(&dir, clock.clone());
+        let key_pair = generate_test_key_pair("Dedupe Key").unwrap();
+        state.storage.store_key(key_pair.clone()).await.unwrap();
+
+        let sign = |content: &str| sign_document(State(state.clone()), Json(SignDocumentRequest {
+            key_id: key_pair.id,
+            document_content: Some(content.to_string()),
+            ..Default::default()
+        }));
+        let first = sign("purchase order 7731").await.unwrap().0;
+        assert!(!first.duplicate);
+
+        clock.advance(chrono::Duration::minutes(5));
+        let repeat = sign("purchase order 7731").await.unwrap().0;
+        assert!(repeat.duplicate);
+        assert_eq!(repeat.signature_id, first.signature_id);
+        assert_eq!(repeat.signature, first.signature);
+        assert_eq!(repeat.signing_time, Some(start));
+        assert_eq!(state.receipts.count().await, 1);
+        // Only the signature first issued under the id counts as a use of the key
+        let usage = state.storage.get_key_record(key_pair.id).await.unwrap().usage;
+        assert_eq!((usage.sign_count, usage.last_sign_at), (1, Some(start)));
+
+        let response = get_signature_record(State(state.clone()), Path(first.signature_id.unwrap())).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let record: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(record["signature"], first.signature.clone().unwrap());
+        assert_eq!(record["key_id"], key_pair.id.to_string());
+
+        // One changed byte is a different document, with a different id
+        let changed = sign("purchase order 7732").await.unwrap().0;
+        assert!(!changed.duplicate);
+        assert_ne!(changed.signature_id, first.signature_id);
+        assert_eq!(state.receipts.count().await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_busy_operations_queue_or_are_refused() {
+        use axum::body::Body;
+        use axum::routing::post;
+        use tower::ServiceExt;
+
+        let dir = tempdir().unwrap();
+        let config = Config {
+            max_concurrent_generations: Some(1),
+            max_concurrent_signs: Some(1),
+            overload_policy: crate::limits::OverloadPolicy::Reject,
+            overload_retry_after_secs: 3,
+            ..Default::default()
+        };
+        let state = Arc::new(AppState {
+            limits: OperationLimits {
+                signing: crate::limits::OperationLimiter::new(
+                    "sign", Some(1), crate::limits::OverloadPolicy::Queue, 4, std::time::Duration::from_secs(5),
+                ),
+                ..OperationLimits::from_config(&config)
+            },
+            config: Arc::new(config),
+            ..Arc::into_inner(test_state(&dir, Arc::new(MockClock::new(Utc::now())))).unwrap()
+        });
+        let app = axum::Router::new()
+            .route("/keys/generate", post(generate_keys))
+            .layer(axum::middleware::from_fn_with_state(state.clone(), read_only_guard))
+            .with_state(state.clone());
+        let generate = |name: &str| {
+            let request = axum::http::Request::builder()
+                .method(Method::POST)
+                .uri("/keys/generate")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(serde_json::json!({ "name": name }).to_string()))
+                .unwrap();
+            app.clone().oneshot(request)
+        };
+
+        // With a generation already running, another one is refused straight away
+        let running = state.limits.generation.acquire().await.unwrap();
+        let response = generate("Second").await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "3");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "OVERLOADED");
+        drop(running);
+        assert_eq!(generate("Second").await.unwrap().status(), StatusCode::OK);
+
+        // Encrypted-key signatures wait their turn instead
+        let encrypted = crate::key_generation::generate_key_pair_with_kdf(GenerateKeyRequest {
+            name: "Encrypted".to_string(),
+            description: None,
+            password: Some("hunter22".to_string()),
+            expires_at: None,
+            tags: None,
+            key_strength: None,
+            hsm: None,
+            generate_password: false,
+            template: None,
+            environment: None,
+            fast: false,
+            exportable: None,
+            default_output_format: None,
+            default_hash_algorithm: None,
+            default_encoding: None,
+        }, &crate::config::KdfParams::pbkdf2(crate::config::MIN_PBKDF2_ITERATIONS)).unwrap();
+        state.storage.store_key(encrypted.clone()).await.unwrap();
+        let running = state.limits.signing.acquire().await.unwrap();
+        let queued = tokio::spawn(sign_document(State(state.clone()), Json(SignDocumentRequest {
+            key_id: encrypted.id,
+            password: Some("hunter22".to_string()),
+            document_content: Some("queued".to_string()),
+            ..Default::default()
+        })));
+        while state.limits.signing.stats().queued == 0 {
+            tokio::task::yield_now().await;
+        }
+        drop(running);
+        assert!(queued.await.unwrap().unwrap().0.success);
+
+        let metrics = metrics(State(state.clone())).await.into_response();
+        let body = axum::body::to_bytes(metrics.into_body(), usize::MAX).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains("inkan_operation_rejections_total{operation=\"generate\"} 1"));
+        assert!(body.contains("inkan_operation_queue_depth{operation=\"sign\"} 0"));
+    }
+
+    #[tokio::test]
+    async fn test_large_listings_are_compressed_and_streams_are_not() {
+        use axum::body::Body;
+        use axum::routing::{get, post};
+        use std::io::Read;
+        use tower::ServiceExt;
+
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir, Arc::new(MockClock::new(Utc::now())));
+        for index in 0..200 {
+            state.storage.store_key(generate_test_key_pair(&format!("Listed Key {}", index)).unwrap()).await.unwrap();
+        }
+        let app = axum::Router::new()
+            .route("/keys", get(list_keys))
+            .route("/admin/validate", post(validate_keystore))
+            .layer(compression_layer(&state.config))
+            .with_state(state.clone());
+        let call = |method: Method, uri: &str, encoding: &str, body: &str| {
+            let request = axum::http::Request::builder()
+                .method(method)
+                .uri(uri)
+                .header(header::ACCEPT_ENCODING, encoding)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap();
+            app.clone().oneshot(request)
+        };
+
+        let plain = call(Method::GET, "/keys", "identity", "").await.unwrap();
+        assert!(plain.headers().get(header::CONTENT_ENCODING).is_none());
+        let plain = axum::body::to_bytes(plain.into_body(), usize::MAX).await.unwrap();
+
+        let gzipped = call(Method::GET, "/keys", "gzip", "").await.unwrap();
+        assert_eq!(gzipped.headers()[header::CONTENT_ENCODING], "gzip");
+        let gzipped = axum::body::to_bytes(gzipped.into_body(), usize::MAX).await.unwrap();
+        assert!(gzipped.len() < plain.len() / 2);
+        let mut decompressed = Vec::new();
+        flate2::read::GzDecoder::new(gzipped.as_ref()).read_to_end(&mut decompressed).unwrap();
+        assert_eq!(decompressed, plain);
+
+        // Validation progress is streamed line by line, uncompressed
+        let streamed = call(Method::POST, "/admin/validate", "gzip, br", r#"{"stream": true}"#).await.unwrap();
+        assert!(streamed.headers().get(header::CONTENT_ENCODING).is_none());
+        let mut chunks = streamed.into_body().into_data_stream();
+        let first = tokio_stream::StreamExt::next(&mut chunks).await.unwrap().unwrap();
+        let first: serde_json::Value = serde_json::from_slice(&first).unwrap();
+        assert_eq!(first["type"], "progress");
+    }
+
+    #[tokio::test]
+    async fn test_bound_signing_time_must_match_to_verify() {
+        let dir = tempdir().unwrap();
+        // Sub-millisecond precision is dropped, since the binding cannot carry it
+        let start = Utc::now().with_nanosecond(123_456_789).unwrap();
+        let clock = Arc::new(MockClock::new(start));
+        let state = test_state(&dir, clock.clone());
+        let key_pair = generate_test_key_pair("Timestamp Key").unwrap();
+        state.storage.store_key(key_pair.clone()).await.unwrap();
+        let content = r#"{"total": 120, "currency": "EUR"}"#;
+
+        let signed = sign_document(State(state.clone()), Json(SignDocumentRequest {
+            key_id: key_pair.id,
+            document_content: Some(content.to_string()),
+            content_type: DocumentContentType::JsonJcs,
+            bind_timestamp: true,
+            bundle: true,
+            ..Default::default()
+        })).await.unwrap().0;
+        assert!(signed.timestamp_bound);
+        let signing_time = signed.signing_time.unwrap();
+        assert_eq!(signing_time.timestamp_subsec_nanos(), 123_000_000);
+        let bundle = signed.bundle.unwrap();
+        assert!(bundle.body.timestamp_bound);
+        assert_eq!(bundle.body.signing_time, signing_time);
+        assert!(crate::bundle::verify_bundle(&bundle, crate::bundle::BundleSubject::Content(content)).unwrap().valid);
+
+        // The canonical hash is signed, so reordered JSON still verifies with the bound time
+        let verify = |content: &str, signing_time| verify_signature(State(state.clone()), Json(VerifySignatureRequest {
+            public_key: key_pair.public_key.clone(),
+            signature: signed.signature.clone().unwrap(),
+            document_content: Some(content.to_string()),
+            content_type: DocumentContentType::JsonJcs,
+            signing_time,
+            ..Default::default()
+        }));
+        let verified = verify(r#"{"currency": "EUR", "total": 120}"#, Some(signing_time)).await.unwrap().0;
+        assert!(verified.is_valid);
+        assert_eq!(verified.signing_time, Some(signing_time));
+
+        assert!(!verify(content, Some(signing_time + Duration::milliseconds(1))).await.unwrap().0.is_valid);
+        assert!(!verify(content, None).await.unwrap().0.is_valid);
+        assert!(!verify(r#"{"total": 121, "currency": "EUR"}"#, Some(signing_time)).await.unwrap().0.is_valid);
+
+        let mut backdated = bundle.clone();
+        backdated.body.signing_time -= Duration::days(1);
+        assert!(!crate::bundle::verify_bundle(&backdated, crate::bundle::BundleSubject::Content(content)).unwrap().signature_valid);
+
+        // Unbound signatures are unchanged and do not depend on the time
+        clock.advance(Duration::minutes(1));
+        let unbound = sign_document(State(state.clone()), Json(SignDocumentRequest {
+            key_id: key_pair.id,
+            document_content: Some(content.to_string()),
+            content_type: DocumentContentType::JsonJcs,
+            ..Default::default()
+        })).await.unwrap().0;
+        assert!(!unbound.timestamp_bound);
+        assert_ne!(unbound.signature, signed.signature);
+        assert_ne!(unbound.signature_id, signed.signature_id);
+    }
+
+    #[tokio::test]
+    async fn test_verification_links_check_documents_and_close() {
+        let dir = tempdir().unwrap();
+        let clock = Arc::new(MockClock::new(Utc::now()));
+        let state = Arc::new(AppState {
+            config: Arc::new(Config { share_requests_per_minute: 5, ..Default::default() }),
+            ..Arc::into_inner(test_state(&dir, clock.clone())).unwrap()
+        });
+        let key_pair = generate_test_key_pair("Contracts").unwrap();
+        state.storage.store_key(key_pair.clone()).await.unwrap();
+        let signed = sign_document(State(state.clone()), Json(SignDocumentRequest {
+            key_id: key_pair.id,
+            document_content: Some("lease agreement, unit 4B".to_string()),
+            ..Default::default()
+        })).await.unwrap().0;
+        let signature_id = signed.signature_id.unwrap();
+
+        async fn json(response: Response) -> (StatusCode, serde_json::Value) {
+            let status = response.status();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (status, serde_json::from_slice(&body).unwrap())
+        }
+        let share = |signature_id, ttl_secs| create_share(State(state.clone()), Json(CreateShareRequest {
+            signature_id: Some(signature_id),
+            ttl_secs,
+            ..Default::default()
+        }));
+        let check = |token: String, content: &str| check_share(State(state.clone()), Path(token), Json(CheckShareRequest {
+            document_content: Some(content.to_string()),
+            ..Default::default()
+        }));
+
+        let (status, created) = json(share(signature_id, None).await).await;
+        assert_eq!(status, StatusCode::OK);
+        let token = created["token"].as_str().unwrap().to_string();
+        assert_eq!(state.shares.count().await, 1);
+        assert!(!std::fs::read_to_string(dir.path().join("shares.json")).unwrap().contains(&token));
+
+        let (status, public) = json(get_share(State(state.clone()), Path(token.clone())).await).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(public["key_name"], "Contracts");
+        assert_eq!(public["document_hash"], signed.document_hash.clone().unwrap());
+        assert_eq!(public["key_fingerprint"], key_pair.fingerprint.clone().unwrap());
+        assert!(!public.to_string().contains(key_pair.private_key.expose_for_persistence()));
+
+        let (_, matched) = json(check(token.clone(), "lease agreement, unit 4B").await).await;
+        assert_eq!(matched["matches"], true);
+        let (_, mismatched) = json(check(token.clone(), "lease agreement, unit 4C").await).await;
+        assert_eq!(mismatched["matches"], false);
+        assert_eq!(mismatched["document_matches"], false);
+        assert_eq!(mismatched["expected_hash"], signed.document_hash.clone().unwrap());
+
+        // Each link gets five requests a minute
+        let _ = check(token.clone(), "guess").await;
+        let _ = check(token.clone(), "guess").await;
+        let limited = check(token.clone(), "guess").await;
+        assert_eq!(limited.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(limited.headers().contains_key(header::RETRY_AFTER));
+
+        clock.advance(Duration::seconds(61));
+        assert_eq!(revoke_share(State(state.clone()), Path(token.clone())).await.status(), StatusCode::OK);
+        let (status, closed) = json(get_share(State(state.clone()), Path(token.clone())).await).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(closed["code"], "SHARE_NOT_FOUND");
+
+        // Links close on their own once their TTL passes
+        let (_, created) = json(share(signature_id, Some(60)).await).await;
+        let expiring = created["token"].as_str().unwrap().to_string();
+        assert_eq!(check(expiring.clone(), "lease agreement, unit 4B").await.status(), StatusCode::OK);
+        clock.advance(Duration::seconds(60));
+        assert_eq!(check(expiring, "lease agreement, unit 4B").await.status(), StatusCode::NOT_FOUND);
+
+        // A bundle is only published if it verifies
+        let mut forged = state.receipts.get(signature_id).await.unwrap();
+        forged.body.signing_time -= Duration::days(30);
+        let response = create_share(State(state.clone()), Json(CreateShareRequest { bundle: Some(forged), ..Default::default() })).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(share(Uuid::new_v4(), None).await.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_sign_and_verify_failures_use_error_statuses() {
+        for legacy_envelope in [false, true] {
+            let dir = tempdir().unwrap();
+            let clock = Arc::new(MockClock::new(Utc::now()));
+            let state = Arc::new(AppState {
+                config: Arc::new(Config { legacy_envelope, ..Default::default() }),
+                ..Arc::into_inner(test_state(&dir, clock.clone())).unwrap()
+            });
+
+            let active = generate_test_key_pair("Active").unwrap();
+            let inactive = generate_test_key_pair("Inactive").unwrap();
+            let encrypted = crate::key_generation::generate_key_pair_with_kdf(GenerateKeyRequest {
+                name: "Encrypted".to_string(),
+                description: None,
+                password: Some("hunter22".to_string()),
+                expires_at: None,
+                tags: None,
+                key_strength: None,
+                hsm: None,
+                generate_password: false,
+                template: None,
+                environment: None,
+                fast: false,
+                exportable: None,
+                default_output_format: None,
+                default_hash_algorithm: None,
+                default_encoding: None,
+            }, &crate::config::KdfParams::pbkdf2(crate::config::MIN_PBKDF2_ITERATIONS)).unwrap();
+            for key_pair in [&active, &inactive, &encrypted] {
+                state.storage.store_key(key_pair.clone()).await.unwrap();
+            }
+            state.storage.deactivate_key(inactive.id).await.unwrap();
+
+            let sign = |key_id: Uuid| SignDocumentRequest {
+                key_id,
+                document_content: Some("terms".to_string()),
+                ..Default::default()
+            };
+            let cases = [
+                (sign(Uuid::new_v4()), StatusCode::NOT_FOUND, ErrorCode::KeyNotFound),
+                (sign(inactive.id), StatusCode::GONE, ErrorCode::KeyRevoked),
+                (SignDocumentRequest { valid_until: Some(clock.now() - Duration::seconds(1)), ..sign(active.id) }, StatusCode::UNPROCESSABLE_ENTITY, ErrorCode::ValidationFailed),
+                (SignDocumentRequest { document_content: None, ..sign(active.id) }, StatusCode::BAD_REQUEST, ErrorCode::InvalidRequest),
+                (sign(encrypted.id), StatusCode::UNAUTHORIZED, ErrorCode::PasswordRequired),
+            ];
+            for (request, status, code) in cases {
+                let (actual, Json(response)) = sign_document(State(state.clone()), Json(request)).await.unwrap_err();
+                let expected = if legacy_envelope { StatusCode::OK } else { status };
+                assert_eq!((actual, response.code), (expected, Some(code)));
+                assert!(!response.success);
+            }
+
+            let (status, Json(response)) = verify_signature(State(state.clone()), Json(VerifySignatureRequest {
+                public_key: active.public_key.clone(),
+                signature: "AAAA".to_string(),
+                ..Default::default()
+            })).await.unwrap_err();
+            assert_eq!(status, if legacy_envelope { StatusCode::OK } else { StatusCode::BAD_REQUEST });
+            assert_eq!(response.code, Some(ErrorCode::InvalidRequest));
+
+            // GET /keys/:id answers a missing key alike in every format
+            let missing = Uuid::new_v4();
+            let expected = if legacy_envelope { StatusCode::OK } else { StatusCode::NOT_FOUND };
+            let (status, Json(response)) = get_public_key(State(state.clone()), Path(missing)).await.unwrap_err();
+            assert_eq!((status, response.code), (expected, Some(ErrorCode::KeyNotFound)));
+            assert!(!response.success);
+            for format in [PublicKeyFormat::Json, PublicKeyFormat::Minisign, PublicKeyFormat::Ssh] {
+                let response = get_public_key_formatted(State(state.clone()), Path(missing), Query(PublicKeyQuery { format })).await;
+                assert_eq!(response.status(), expected);
+            }
+
+            // A follower refuses to leave read-only mode
+            let follower = Arc::new(AppState { follower: true, ..Arc::into_inner(state).unwrap() });
+            let (status, Json(response)) = set_read_only(State(follower.clone()), Json(ReadOnlyRequest { enabled: false })).await;
+            let expected = if legacy_envelope { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+            assert_eq!((status, response.code), (expected, Some(ErrorCode::ReadOnly)));
+            assert!(!response.success && response.read_only);
+            let state = follower;
+
+            // An invalid signature is an answer, not an error
+            let verified = verify_signature(State(state.clone()), Json(VerifySignatureRequest {
+                public_key: active.public_key.clone(),
+                signature: "AAAA".to_string(),
+                document_content: Some("terms".to_string()),
+                ..Default::default()
+            })).await.unwrap().0;
+            assert!(verified.success && !verified.is_valid);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_keys_limited_to_contexts_refuse_other_signatures() {
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir, Arc::new(MockClock::new(Utc::now())));
+        let key_pair = generate_test_key_pair("Partner").unwrap();
+        state.storage.store_key(key_pair.clone()).await.unwrap();
+
+        let restrict = |allowed_contexts: Vec<&str>| update_key(State(state.clone()), Path(key_pair.id), Json(UpdateKeyRequest {
+            name: None,
+            description: None,
+            tags: None,
+            expires_at: None,
+            is_active: None,
+            allowed_contexts: Some(allowed_contexts.into_iter().map(str::to_string).collect()),
+            environment: None,
+            exportable: None,
+            default_output_format: None,
+            default_hash_algorithm: None,
+            default_encoding: None,
+        }), None);
+        let updated = restrict(vec!["invoice"]).await.unwrap().0;
+        assert_eq!(updated.key_info.unwrap().allowed_contexts, Some(vec!["invoice".to_string()]));
+        let listed = state.storage.list_keys().await;
+        assert_eq!(listed[0].allowed_contexts, Some(vec!["invoice".to_string()]));
+        let (status, _) = restrict(vec![""]).await.unwrap_err();
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let sign = |context: Option<&str>| sign_document(State(state.clone()), Json(SignDocumentRequest {
+            key_id: key_pair.id,
+            document_content: Some("invoice #1042".to_string()),
+            context: context.map(str::to_string),
+            ..Default::default()
+        }));
+        let signed = sign(Some("invoice")).await.unwrap().0;
+        let receipt = state.receipts.get(signed.signature_id.unwrap()).await.unwrap();
+        assert_eq!(receipt.body.context.as_deref(), Some("invoice"));
+        let events = state.storage.events().replay(0, 100).await.unwrap().events;
+        let event = events.iter().find(|event| event.signature_id == signed.signature_id).unwrap();
+        assert_eq!(event.context.as_deref(), Some("invoice"));
+
+        for context in [Some("contract"), None] {
+            let (status, Json(refused)) = sign(context).await.unwrap_err();
+            assert_eq!((status, refused.code), (StatusCode::FORBIDDEN, Some(ErrorCode::InsufficientPermissions)));
+        }
+
+        // An empty list lifts the restriction
+        assert_eq!(restrict(vec![]).await.unwrap().0.key_info.unwrap().allowed_contexts, None);
+        assert!(sign(None).await.unwrap().0.success);
+    }
+
+    #[tokio::test]
+    async fn test_only_admin_clients_change_signing_contexts() {
+        let dir = tempdir().unwrap();
+        let clients = [("ops", "s3cret"), ("partner", "s3cret")].into_iter().map(|(id, secret)| (id.to_string(), secret.to_string())).collect();
+        let state = Arc::new(AppState {
+            request_auth: RequestAuthenticator::new(clients, Duration::seconds(300)),
+            config: Arc::new(Config { admin_clients: ["ops".to_string()].into_iter().collect(), ..Config::default() }),
+            ..Arc::into_inner(test_state(&dir, Arc::new(MockClock::new(Utc::now())))).unwrap()
+        });
+        let key_pair = generate_test_key_pair("Partner").unwrap();
+        state.storage.store_key(key_pair.clone()).await.unwrap();
+
+        let update = |allowed_contexts: Option<Vec<&str>>, client: &str| update_key(State(state.clone()), Path(key_pair.id), Json(UpdateKeyRequest {
+            name: Some("Partner (billing)".to_string()),
+            description: None,
+            tags: None,
+            expires_at: None,
+            is_active: None,
+            allowed_contexts: allowed_contexts.map(|contexts| contexts.into_iter().map(str::to_string).collect()),
+            environment: None,
+            exportable: None,
+            default_output_format: None,
+            default_hash_algorithm: None,
+            default_encoding: None,
+        }), Some(AuthenticatedClient(client.to_string())));
+
+        // A non-admin client cannot set or lift the list, and nothing else in its update applies
+        for allowed_contexts in [vec!["invoice"], vec![]] {
+            let (status, Json(refused)) = update(Some(allowed_contexts), "partner").await.unwrap_err();
+            assert_eq!((status, refused.code), (StatusCode::FORBIDDEN, Some(ErrorCode::InsufficientPermissions)));
+        }
+        let unchanged = state.storage.get_key_record(key_pair.id).await.unwrap();
+        assert_eq!((unchanged.name.as_str(), unchanged.allowed_contexts), ("Partner", None));
+        assert!(matches!(
+            state.storage.update_key(key_pair.id, UpdateKeyRequest { allowed_contexts: Some(vec!["invoice".to_string()]), ..Default::default() }, false).await,
+            Err(KeyManagementError::InsufficientPermissions(_))
+        ));
+
+        // It may still update the rest of the key, and an admin client may set the list
+        assert!(update(None, "partner").await.unwrap().0.success);
+        let updated = update(Some(vec!["invoice"]), "ops").await.unwrap().0;
+        assert_eq!(updated.key_info.unwrap().allowed_contexts, Some(vec!["invoice".to_string()]));
+    }
+
+    #[tokio::test]
+    async fn test_repeat_verifications_are_answered_from_cache() {
+        let dir = tempdir().unwrap();
+        let clock = Arc::new(MockClock::new(Utc::now()));
+        let state = Arc::new(AppState {
+            verification_cache: VerificationCache::new(16, Duration::seconds(60)),
+            ..Arc::into_inner(test_state(&dir, clock.clone())).unwrap()
+        });
+        let key_pair = generate_test_key_pair("Viewer").unwrap();
+        state.storage.store_key(key_pair.clone()).await.unwrap();
+        let signed = sign_document(State(state.clone()), Json(SignDocumentRequest {
+            key_id: key_pair.id,
+            document_content: Some("annual report".to_string()),
+            ..Default::default()
+        })).await.unwrap().0;
+
+        let verify = |document: &str, context: Option<&str>| verify_signature(State(state.clone()), Json(VerifySignatureRequest {
+            key_id: Some(key_pair.id),
+            signature: signed.signature.clone().unwrap(),
+            document_content: Some(document.to_string()),
+            context: context.map(str::to_string),
+            ..Default::default()
+        }));
+        let stats = || state.verification_cache.stats();
+
+        assert!(verify("annual report", None).await.unwrap().0.is_valid);
+        assert_eq!((stats().hits, stats().misses), (0, 1));
+        assert!(verify("annual report", None).await.unwrap().0.is_valid);
+        assert_eq!((stats().hits, stats().misses), (1, 1));
+
+        // Any differing input is checked afresh
+        assert!(!verify("annual report (draft)", None).await.unwrap().0.is_valid);
+        assert!(!verify("annual report", Some("invoice")).await.unwrap().0.is_valid);
+        assert_eq!((stats().hits, stats().misses), (1, 3));
+
+        // Key status is never cached: a revoked key shows as revoked on a cache hit
+        state.storage.revoke_key(key_pair.id, None).await.unwrap();
+        let revoked = verify("annual report", None).await.unwrap().0;
+        assert!(!revoked.key_info.unwrap().is_active);
+        assert_eq!(stats().hits, 2);
+
+        clock.advance(Duration::seconds(60));
+        assert!(verify("annual report", None).await.unwrap().0.is_valid);
+        assert_eq!((stats().hits, stats().misses), (2, 4));
+        let body = render_metrics(&[], &ServiceMetrics { verification_cache: stats(), ..Default::default() }, 10);
+        assert!(body.contains("inkan_verify_cache_hits_total 2"));
+    }
+
+    #[tokio::test]
+    async fn test_signed_requests_are_checked_once_and_within_skew() {
+        use axum::body::Body;
+        use axum::routing::{get, post};
+        use tower::ServiceExt;
+
+        let dir = tempdir().unwrap();
+        let clock = Arc::new(MockClock::new(Utc::now()));
+        let clients = [("billing".to_string(), "s3cret".to_string())].into_iter().collect();
+        let state = Arc::new(AppState {
+            request_auth: RequestAuthenticator::new(clients, Duration::seconds(300)),
+            ..Arc::into_inner(test_state(&dir, clock.clone())).unwrap()
+        });
+        let app = axum::Router::new()
+            .route("/health", get(|| async { "ok" }))
+            .route("/echo", post(|body: String| async move { body }))
+            .layer(axum::middleware::from_fn_with_state(state.clone(), request_auth_guard))
+            .with_state(state.clone());
+        let call = |uri: &str, body: &str, headers: Vec<(&'static str, String)>| {
+            let mut request = axum::http::Request::builder().method(Method::POST).uri(uri);
+            for (name, value) in headers {
+                request = request.header(name, value);
+            }
+            let request = request.body(Body::from(body.to_string())).unwrap();
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(request).await.unwrap();
+                let status = response.status();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                (status, String::from_utf8(body.to_vec()).unwrap())
+            }
+        };
+        let signed = |path: &str, body: &str, timestamp: i64| {
+            vec![
+                (CLIENT_ID_HEADER, "billing".to_string()),
+                (TIMESTAMP_HEADER, timestamp.to_string()),
+                (SIGNATURE_HEADER, crate::utils::sign_request(b"s3cret", "POST", path, timestamp, body.as_bytes())),
+            ]
+        };
+        let now = clock.now().timestamp();
+
+        // The handler sees the body the signature covered
+        let headers = signed("/echo?x=1", "hello", now);
+        assert_eq!(call("/echo?x=1", "hello", headers.clone()).await, (StatusCode::OK, "hello".to_string()));
+        let (status, body) = call("/echo?x=1", "hello", headers).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(body.contains("REQUEST_REPLAYED"));
+
+        let (status, body) = call("/echo", "tampered", signed("/echo", "hello", now)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(body.contains("INVALID_REQUEST_SIGNATURE"));
+        let (status, body) = call("/echo", "hello", signed("/echo", "hello", now - 301)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(body.contains("REQUEST_TIMESTAMP_EXPIRED"));
+        let (status, body) = call("/echo", "hello", Vec::new()).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(body.contains("INVALID_REQUEST_SIGNATURE"));
+
+        // Once the skew window has passed, the remembered signature is forgotten along with it
+        assert_eq!(call("/echo", "hello", signed("/echo", "hello", now + 299)).await.0, StatusCode::OK);
+        clock.advance(Duration::seconds(600));
+        assert_eq!(call("/echo", "hello", signed("/echo", "hello", now + 599)).await.0, StatusCode::OK);
+
+        let request = axum::http::Request::builder().uri("/health").body(Body::empty()).unwrap();
+        assert_eq!(app.clone().oneshot(request).await.unwrap().status(), StatusCode::OK);
+        assert!(is_public_path(&Method::GET, "/verifications/abc"));
+        assert!(is_public_path(&Method::POST, "/verifications/abc/check"));
+        assert!(!is_public_path(&Method::POST, "/verifications/share"));
+        assert!(!is_public_path(&Method::DELETE, "/verifications/abc"));
+        assert!(is_public_path(&Method::GET, "/public/3f2a9c1b7d4e8f6012ab34cd56ef7890.pem"));
+        assert!(!is_public_path(&Method::GET, "/keys/3f2a9c1b/public"));
+    }
+
+    #[tokio::test]
+    async fn test_keystore_limits_warn_block_and_archive() {
+        use crate::capacity::{ArchiveLongRevoked, CapacityLevel};
+
+        let dir = tempdir().unwrap();
+        let clock = Arc::new(MockClock::new(Utc::now()));
+        let state = Arc::new(AppState {
+            capacity: KeystoreCapacity::new(Some(1), Some(2)),
+            ..Arc::into_inner(test_state(&dir, clock.clone())).unwrap()
+        });
+        let request = |name: &str| GenerateKeyRequest {
+            name: name.to_string(),
+            description: None,
+            password: None,
+            expires_at: None,
+            tags: None,
+            key_strength: None,
+            hsm: None,
+            generate_password: false,
+            template: None,
+            environment: None,
+            fast: false,
+            exportable: None,
+            default_output_format: None,
+            default_hash_algorithm: None,
+            default_encoding: None,
+        };
+        let generate = |state: Arc<AppState>, name: &str| {
+            let request = request(name);
+            async move { generate_keys(State(state), Query(GenerateKeyQuery::default()), Json(request)).await }
+        };
+
+        let first = generate(state.clone(), "First").await.unwrap().0;
+        assert!(first.warnings.iter().any(|warning| warning.contains("soft limit of 1")));
+        assert!(generate(state.clone(), "Second").await.unwrap().0.success);
+        let (status, Json(refused)) = generate(state.clone(), "Third").await.unwrap_err();
+        assert_eq!(status, StatusCode::INSUFFICIENT_STORAGE);
+        assert_eq!(refused.code, Some(ErrorCode::KeystoreFull));
+        assert_eq!(state.storage.key_count().await, 2);
+
+        let stats = get_key_stats(State(state.clone())).await.0;
+        assert_eq!((stats.capacity.level, stats.capacity.headroom), (CapacityLevel::Full, Some(0)));
+        assert_eq!(readiness(State(state.clone())).await.1.capacity.level, CapacityLevel::Full);
+
+        // With auto-archival, keys revoked long enough ago make room; recently revoked ones stay
+        let state = Arc::new(AppState {
+            capacity: KeystoreCapacity::new(Some(1), Some(2)).with_eviction(ArchiveLongRevoked { min_revoked_age: Duration::days(30) }),
+            ..Arc::into_inner(state).unwrap()
+        });
+        let first_id = first.key_pair.unwrap().id;
+        state.storage.revoke_key(first_id, None).await.unwrap();
+        assert_eq!(generate(state.clone(), "Third").await.unwrap_err().0, StatusCode::INSUFFICIENT_STORAGE);
+
+        clock.advance(Duration::days(31));
+        let third = generate(state.clone(), "Third").await.unwrap().0;
+        assert!(third.warnings.iter().any(|warning| warning.contains("hard limit of 2")));
+        assert!(!state.storage.key_exists(first_id).await);
+        let archive: Vec<serde_json::Value> = serde_json::from_str(&std::fs::read_to_string(state.storage.archive_path()).unwrap()).unwrap();
+        assert_eq!(archive.len(), 1);
+        assert_eq!(archive[0]["key_pair"]["id"], first_id.to_string());
+        assert_eq!(archive[0]["reason"], "revoked");
+
+        let body = render_metrics(&[], &ServiceMetrics { capacity: stats.capacity, ..Default::default() }, 10);
+        assert!(body.contains("inkan_keystore_limit{kind=\"hard\"} 2"));
+        assert!(body.contains("inkan_keystore_headroom 0"));
+    }
+
+    #[tokio::test]
+    async fn test_responses_carry_structured_warnings() {
+        let dir = tempdir().unwrap();
+        let clock = Arc::new(MockClock::new(Utc::now()));
+        let state = test_state(&dir, clock.clone());
+        let codes = |warnings: &[ApiWarning]| warnings.iter().map(|warning| warning.code).collect::<Vec<_>>();
+        let key_pair = KeyPair {
+            expires_at: Some(clock.now() + Duration::days(2)),
+            ..generate_test_key_pair("Short-lived").unwrap()
+        };
+        state.storage.store_key(key_pair.clone()).await.unwrap();
+
+        let signed = sign_document(State(state.clone()), Json(SignDocumentRequest {
+            key_id: key_pair.id,
+            document_hash: Some(create_document_hash("contract")),
+            valid_until: Some(clock.now() + Duration::days(10)),
+            ..Default::default()
+        })).await.unwrap().0;
+        assert_eq!(codes(&signed.warnings), [WarningCode::KeyExpiringSoon, WarningCode::UnencryptedPrivateKey, WarningCode::ValidityOutlastsKey]);
+        assert_eq!(signed.warnings[2].field.as_deref(), Some("valid_until"));
+
+        // Verifying against a revoked key still works, with a warning
+        state.storage.revoke_key(key_pair.id, None).await.unwrap();
+        let verified = verify_signature(State(state.clone()), Json(VerifySignatureRequest {
+            key_id: Some(key_pair.id),
+            document_hash: Some(create_document_hash("contract")),
+            signature: signed.signature.unwrap(),
+            valid_until: signed.valid_until,
+            ..Default::default()
+        })).await.unwrap().0;
+        assert!(verified.cryptographically_valid);
+        assert_eq!(codes(&verified.warnings), [WarningCode::KeyInactive]);
+
+        let fresh = generate_test_key_pair("Renewed").unwrap();
+        state.storage.store_key(fresh.clone()).await.unwrap();
+        let update = |expires_at| UpdateKeyRequest {
+            name: None,
+            description: None,
+            tags: None,
+            expires_at,
+            is_active: None,
+            allowed_contexts: None,
+            environment: None,
+            exportable: None,
+            default_output_format: None,
+            default_hash_algorithm: None,
+            default_encoding: None,
+        };
+        let updated = update_key(State(state.clone()), Path(fresh.id), Json(update(Some(clock.now() + Duration::days(3)))), None).await.unwrap().0;
+        assert_eq!(codes(&updated.warnings), [WarningCode::KeyExpiringSoon]);
+        assert_eq!(updated.warnings[0].field.as_deref(), Some("expires_at"));
+        let quiet = update_key(State(state.clone()), Path(fresh.id), Json(update(Some(clock.now() + Duration::days(30)))), None).await.unwrap().0;
+        assert!(quiet.warnings.is_empty());
+        assert!(!serde_json::to_string(&quiet).unwrap().contains("warnings"));
+
+        // A change held in memory says so
+        let degraded = Arc::new(AppState {
+            storage: Arc::new(KeyStorage::new(dir.path().join("missing").join("keys.json").to_str().unwrap())),
+            ..Arc::into_inner(test_state(&dir, clock.clone())).unwrap()
+        });
+        degraded.storage.store_key(fresh.clone()).await.unwrap();
+        let renamed = update_key(State(degraded.clone()), Path(fresh.id), Json(UpdateKeyRequest { name: Some("Held".to_string()), ..update(None) }), None).await.unwrap().0;
+        assert_eq!(codes(&renamed.warnings), [WarningCode::PersistenceDegraded]);
+
+        let catalog = error_codes().await.0;
+        assert!(catalog.warnings.iter().any(|entry| entry.code == WarningCode::ValidityOutlastsKey));
+    }
+
+    #[tokio::test]
+    async fn test_key_state_agrees_across_list_get_stats_and_sign() {
+        let dir = tempdir().unwrap();
+        let start = Utc::now();
+        let clock = Arc::new(MockClock::new(start));
+        let state = test_state(&dir, clock.clone());
+
+        let key = |name: &str, update: fn(&mut KeyPair, chrono::DateTime<Utc>)| {
+            let mut key_pair = generate_test_key_pair(name).unwrap();
+            update(&mut key_pair, start);
+            key_pair
+        };
+        let keys = [
+            (key("Active", |_, _| {}), KeyState::Active),
+            (key("Scheduled", |k, now| k.revocation_scheduled_at = Some(now + Duration::hours(1))), KeyState::ScheduledRevocation),
+            (key("Expired", |k, now| k.expires_at = Some(now - Duration::hours(1))), KeyState::Expired),
+            (key("Revoked", |k, now| { k.lifecycle = Lifecycle::Revoked; k.expires_at = Some(now - Duration::hours(1)) }), KeyState::Revoked),
+            // Due but not yet swept, and revocation outranks the expiry it would have stamped
+            (key("Due", |k, now| { k.revocation_scheduled_at = Some(now - Duration::minutes(1)); k.expires_at = Some(now - Duration::hours(1)) }), KeyState::Revoked),
+            (key("Suspended", |k, _| k.lifecycle = Lifecycle::Suspended), KeyState::Suspended),
+        ];
+        for (key_pair, _) in &keys {
+            state.storage.store_key(key_pair.clone()).await.unwrap();
+        }
+
+        let query = ListKeysQuery { active_only: None, key_type: None, tags: None, search: None, environment: None, offset: 0, limit: None, cursor: None };
+        let listed = list_keys(State(state.clone()), Query(query), None).await.unwrap().0;
+        let stats = get_key_stats(State(state.clone())).await.0;
+        assert_eq!((stats.total_keys, stats.active_keys, stats.expired_keys, stats.revoked_keys, stats.suspended_keys), (6, 2, 1, 2, 1));
+        assert_eq!((listed.active_count, listed.expired_count), (2, 1));
+
+        for (key_pair, expected) in &keys {
+            assert_eq!(key_pair.state(start), *expected, "{}", key_pair.name);
+            let info = listed.keys.iter().find(|info| info.id == key_pair.id).unwrap();
+            assert_eq!((info.state, info.is_active), (*expected, expected.is_usable()), "{}", key_pair.name);
+
+            let fetched = match get_public_key(State(state.clone()), Path(key_pair.id)).await {
+                Ok(Json(fetched)) => fetched,
+                Err((status, Json(fetched))) => {
+                    assert!(status.is_client_error(), "{}", key_pair.name);
+                    fetched
+                }
+            };
+            assert_eq!(fetched.success, expected.is_usable(), "{}", key_pair.name);
+            if let Some(info) = fetched.key_info {
+                assert_eq!(info.state, *expected);
+            }
+
+            let signed = sign_document(State(state.clone()), Json(SignDocumentRequest {
+                key_id: key_pair.id,
+                document_content: Some("state".to_string()),
+                ..Default::default()
+            })).await;
+            match expected {
+                KeyState::Active | KeyState::ScheduledRevocation => assert!(signed.unwrap().0.success),
+                KeyState::Expired => assert_eq!(signed.unwrap_err().1.0.code, Some(ErrorCode::KeyExpired)),
+                KeyState::Revoked => assert_eq!(signed.unwrap_err().1.0.code, Some(ErrorCode::KeyRevoked)),
+                KeyState::Suspended => assert_eq!(signed.unwrap_err().1.0.code, Some(ErrorCode::KeySuspended)),
+                KeyState::Deleted => unreachable!("deleted keys are not stored"),
+            }
+        }
+
+        // Once its revocation is due the scheduled key is revoked everywhere, before any sweep
+        clock.advance(Duration::hours(2));
+        let scheduled = keys[1].0.id;
+        let info = state.storage.list_keys().await.into_iter().find(|info| info.id == scheduled).unwrap();
+        assert_eq!((info.state, info.is_active), (KeyState::Revoked, false));
+        assert_eq!(state.storage.get_key(scheduled).await.unwrap_err().code(), ErrorCode::KeyRevoked);
+        assert_eq!(state.storage.get_key_stats().await, (6, 1, 1, 3, 1));
+    }
+
+    #[tokio::test]
+    async fn test_reencrypt_rewraps_weak_envelopes_and_keeps_signatures_valid() {
+        use crate::config::{KdfParams, MIN_PBKDF2_ITERATIONS};
+        use crate::key_generation::generate_salted_test_key_pair;
+        use crate::reencryption::EnvelopeWeakness;
+
+        let dir = tempdir().unwrap();
+        let current = KdfParams::pbkdf2(MIN_PBKDF2_ITERATIONS);
+        let state = Arc::new(AppState {
+            config: Arc::new(Config { kdf: current, ..Config::default() }),
+            ..Arc::into_inner(test_state(&dir, Arc::new(MockClock::new(Utc::now())))).unwrap()
+        });
+        let salt = [5u8; 32];
+        let first = generate_salted_test_key_pair("First", "first-pass", &salt, &current);
+        let second = generate_salted_test_key_pair("Second", "second-pass", &salt, &current);
+        let short = generate_salted_test_key_pair("Short", "short-pass", &[5u8; 8], &current);
+        let healthy = generate_salted_test_key_pair("Healthy", "healthy-pass", &rand::random::<[u8; 32]>(), &current);
+        for key_pair in [&first, &second, &short, &healthy] {
+            state.storage.store_key(key_pair.clone()).await.unwrap();
+        }
+
+        let sign = |key_id: Uuid, password: &str| {
+            let state = state.clone();
+            let request = SignDocumentRequest { key_id, password: Some(password.to_string()), document_content: Some("firmware".to_string()), ..Default::default() };
+            async move { sign_document(State(state), Json(request)).await.unwrap().0.signature.unwrap() }
+        };
+        let before = sign(first.id, "first-pass").await;
+
+        let response = reencrypt_scan(State(state.clone())).await;
+        let scan: serde_json::Value = serde_json::from_slice(&axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(scan["scanned"], 4);
+        let affected: Vec<&str> = scan["affected"].as_array().unwrap().iter().map(|finding| finding["name"].as_str().unwrap()).collect();
+        assert_eq!(affected, ["First", "Second", "Short"]);
+        assert_eq!(scan["affected"][2]["weaknesses"], serde_json::json!(["short_salt"]));
+        assert!(!scan.to_string().contains("\"salt\""));
+
+        let passwords = [(first.id, "first-pass"), (second.id, "wrong-pass"), (healthy.id, "healthy-pass")]
+            .into_iter()
+            .map(|(key_id, password)| (key_id, password.to_string()))
+            .collect();
+        let response = reencrypt_keys(State(state.clone()), Some(AuthenticatedClient("ops".to_string())), Json(ReencryptRequest { passwords })).await;
+        let result: ReencryptResponse = serde_json::from_slice(&axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert!(!result.success);
+        assert_eq!(result.rewrapped.iter().map(|key| (key.key_id, key.version)).collect::<Vec<_>>(), [(first.id, 2)]);
+        assert_eq!(result.rewrapped[0].weaknesses, [EnvelopeWeakness::SharedSalt]);
+        assert_eq!((result.failed[0].key_id, result.failed[0].code), (second.id, ErrorCode::DecryptionFailed));
+        assert_eq!((result.missing_password.clone(), result.not_affected.clone()), (vec![short.id], vec![healthy.id]));
+
+        // The key material is unchanged: old signatures verify and new ones match them
+        let rewrapped = state.storage.get_key_record(first.id).await.unwrap();
+        assert_eq!(rewrapped.envelope_history[0].actor.as_deref(), Some("ops"));
+        assert_ne!(rewrapped.private_key.expose_for_persistence(), first.private_key.expose_for_persistence());
+        assert_eq!(sign(first.id, "first-pass").await, before);
+        let verified = verify_signature(State(state.clone()), Json(VerifySignatureRequest {
+            public_key: first.public_key.clone(),
+            signature: before,
+            document_content: Some("firmware".to_string()),
+            ..Default::default()
+        })).await.unwrap().0;
+        assert!(verified.is_valid);
+
+        // The failed key is left as it was, and the salt it shared is no longer shared
+        let response = reencrypt_scan(State(state.clone())).await;
+        let scan: serde_json::Value = serde_json::from_slice(&axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        let affected: Vec<&str> = scan["affected"].as_array().unwrap().iter().map(|finding| finding["name"].as_str().unwrap()).collect();
+        assert_eq!(affected, ["Short"]);
+        assert_eq!(state.storage.get_key_record(second.id).await.unwrap().private_key.expose_for_persistence(), second.private_key.expose_for_persistence());
+    }
+
+    #[tokio::test]
+    async fn test_kdf_report_and_unlock_timings() {
+        use crate::config::{KdfAlgorithm, KdfParams, MIN_PBKDF2_ITERATIONS};
+        use crate::kdf_stats::KeyProtection;
+
+        let dir = tempdir().unwrap();
+        let current = KdfParams::pbkdf2(MIN_PBKDF2_ITERATIONS);
+        let config = Config {
+            kdf: current,
+            debug_timings: true,
+            admin_clients: ["ops".to_string()].into_iter().collect(),
+            ..Config::default()
+        };
+        let state = Arc::new(AppState {
+            config: Arc::new(config),
+            ..Arc::into_inner(test_state(&dir, Arc::new(MockClock::new(Utc::now())))).unwrap()
+        });
+
+        let legacy = crate::key_generation::generate_legacy_test_key_pair("hunter22");
+        let upgraded = crate::key_generation::generate_key_pair_with_kdf(GenerateKeyRequest {
+            name: "Current".to_string(),
+            description: None,
+            password: Some("hunter22".to_string()),
+            expires_at: None,
+            tags: None,
+            key_strength: None,
+            hsm: None,
+            generate_password: false,
+            template: None,
+            environment: None,
+            fast: false,
+            exportable: None,
+            default_output_format: None,
+            default_hash_algorithm: None,
+            default_encoding: None,
+        }, &current).unwrap();
+        let plain = generate_test_key_pair("Plain").unwrap();
+        for key_pair in [&legacy, &upgraded, &plain] {
+            state.storage.store_key(key_pair.clone()).await.unwrap();
+        }
+
+        let group = |report: &KdfReportResponse, key_id: Uuid| {
+            report.groups.iter().find(|group| group.key_ids.contains(&key_id)).cloned().unwrap()
+        };
+        let report = kdf_report(State(state.clone())).await.0;
+        assert_eq!(report.outdated_keys, 1);
+        assert_eq!(report.groups.len(), 3);
+        let legacy_group = group(&report, legacy.id);
+        assert_eq!((legacy_group.protection, legacy_group.kdf, legacy_group.current), (KeyProtection::Legacy, Some(KdfParams::default()), false));
+        let current_group = group(&report, upgraded.id);
+        assert_eq!((current_group.protection, current_group.kdf, current_group.current), (KeyProtection::Envelope, Some(current), true));
+        assert_eq!((group(&report, plain.id).protection, group(&report, plain.id).kdf), (KeyProtection::Unencrypted, None));
+
+        // Signing with the legacy key rewrites it as an envelope, keeping its older parameters
+        let sign_as = |client: &'static str, key_id: Uuid, debug_timings: bool| {
+            let state = state.clone();
+            let client = Some(Extension(AuthenticatedClient(client.to_string())));
+            async move {
+                sign_document_with_query(State(state), Query(SignQuery { debug_timings }), HeaderMap::new(), client, Json(SignDocumentRequest {
+                    key_id,
+                    password: Some("hunter22".to_string()),
+                    document_content: Some("timed".to_string()),
+                    ..Default::default()
+                })).await.unwrap().0
+            }
+        };
+        let sign = |key_id: Uuid, debug_timings: bool| sign_as("ops", key_id, debug_timings);
+        let timings = sign(legacy.id, true).await.timings.unwrap();
+        assert_eq!(timings.kdf, Some(KdfAlgorithm::Pbkdf2Sha256));
+        assert!(timings.kdf_derivation_ms.unwrap() <= timings.key_decryption_ms.unwrap());
+        assert!(timings.key_decryption_ms.unwrap() <= timings.total_ms);
+        assert!(sign(upgraded.id, false).await.timings.is_none());
+        assert_eq!(sign(plain.id, true).await.timings.unwrap().kdf, None);
+        // Only admin clients get timings
+        assert!(sign_as("billing", plain.id, true).await.timings.is_none());
+
+        let report = kdf_report(State(state.clone())).await.0;
+        let legacy_group = group(&report, legacy.id);
+        assert_eq!((legacy_group.protection, legacy_group.kdf, legacy_group.current), (KeyProtection::Envelope, Some(KdfParams::default()), false));
+        assert_eq!(report.outdated_keys, 1);
+        assert_eq!(group(&report, upgraded.id).key_ids, vec![upgraded.id]);
+
+        // Only encrypted keys are timed; requests without the flag still count
+        let body = metrics(State(state.clone())).await;
+        let body = String::from_utf8(axum::body::to_bytes(body.into_body(), usize::MAX).await.unwrap().to_vec()).unwrap();
+        assert!(body.contains("inkan_kdf_derivation_seconds_count{kdf=\"pbkdf2-sha256\"} 2"));
+        assert!(body.contains("inkan_private_key_decryption_seconds_bucket{kdf=\"pbkdf2-sha256\",le=\"+Inf\"} 2"));
+
+        // Timings stay off unless the operator enables them
+        let state = Arc::new(AppState {
+            config: Arc::new(Config { kdf: current, ..Config::default() }),
+            ..Arc::into_inner(test_state(&dir, Arc::new(MockClock::new(Utc::now())))).unwrap()
+        });
+        state.storage.store_key(plain.clone()).await.unwrap();
+        let signed = sign_document_with_query(State(state), Query(SignQuery { debug_timings: true }), HeaderMap::new(), None, Json(SignDocumentRequest {
+            key_id: plain.id,
+            document_content: Some("timed".to_string()),
+            ..Default::default()
+        })).await.unwrap().0;
+        assert!(signed.timings.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_generated_password_is_returned_once() {
+        let dir = tempdir().unwrap();
+        let state = Arc::new(AppState {
+            config: Arc::new(Config { kdf: crate::config::KdfParams::pbkdf2(crate::config::MIN_PBKDF2_ITERATIONS), ..Default::default() }),
+            ..Arc::into_inner(test_state(&dir, Arc::new(MockClock::new(Utc::now())))).unwrap()
+        });
+        let request = |name: &str, password: Option<&str>| GenerateKeyRequest {
+            name: name.to_string(),
+            description: None,
+            password: password.map(str::to_string),
+            expires_at: None,
+            tags: None,
+            key_strength: None,
+            hsm: None,
+            generate_password: true,
+            template: None,
+            environment: None,
+            fast: false,
+            exportable: None,
+            default_output_format: None,
+            default_hash_algorithm: None,
+            default_encoding: None,
+        };
+        let generate = |request: GenerateKeyRequest, dry_run: bool| {
+            generate_keys(State(state.clone()), Query(GenerateKeyQuery { dry_run }), Json(request))
+        };
+
+        let rejected = generate(request("Mixed", Some("hunter22")), false).await.unwrap_err().1.0;
+        assert_eq!(rejected.errors[0].field, "generate_password");
+        let dry = generate(request("Managed", None), true).await.unwrap().0;
+        assert_eq!((dry.key_type, dry.generated_password), (Some(KeyType::Ed25519Encrypted), None));
+
+        let created = generate(request("Managed", None), false).await.unwrap().0;
+        let password = created.generated_password.clone().unwrap();
+        assert!(password.len() >= 43);
+        assert!(created.warnings[0].contains("shown only in this response"));
+        let key_pair = created.key_pair.unwrap();
+        assert_eq!(key_pair.key_type, KeyType::Ed25519Encrypted);
+        let second = generate(request("Managed Too", None), false).await.unwrap().0;
+        assert_ne!(second.generated_password.unwrap(), password);
+
+        // The password unlocks the key, which refuses to sign without it
+        let sign = |password: Option<&str>| SignDocumentRequest {
+            key_id: key_pair.id,
+            password: password.map(str::to_string),
+            document_content: Some("managed".to_string()),
+            ..Default::default()
+        };
+        assert!(sign_document(State(state.clone()), Json(sign(Some(&password)))).await.unwrap().0.success);
+        assert!(sign_document(State(state.clone()), Json(sign(None))).await.is_err());
+
+        // Nothing kept by the service, or returned later, holds the password
+        let stored = state.storage.get_key_record(key_pair.id).await.unwrap();
+        assert!(!stored.private_key.expose_for_persistence().contains(&password));
+        assert!(!std::fs::read_to_string(dir.path().join("keys.json")).unwrap().contains(&password));
+        let fetched = get_public_key(State(state.clone()), Path(key_pair.id)).await.unwrap().0;
+        assert!(!serde_json::to_string(&fetched).unwrap().contains(&password));
+        let listed = list_keys(State(state.clone()), Query(ListKeysQuery { active_only: None, key_type: None, tags: None, search: None, environment: None, offset: 0, limit: None, cursor: None }), None).await.unwrap().0;
+        assert!(!serde_json::to_string(&listed).unwrap().contains(&password));
+    }
+
+    #[tokio::test]
+    async fn test_public_key_served_by_fingerprint_is_immutable() {
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir, Arc::new(MockClock::new(Utc::now())));
+        let key_pair = generate_seeded_test_key_pair("CDN Key", 0);
+        state.storage.store_key(key_pair.clone()).await.unwrap();
+        let public_key = decode_public_key(&key_pair.public_key).unwrap();
+        let fingerprint = compact_fingerprint(&public_key_to_fingerprint(&key_pair.public_key).unwrap()).unwrap();
+        assert_eq!(fingerprint, "8d6470fa2d00d290d7eb1df2c16964ae");
+
+        let fetch = |path: String, format: Option<PublicKeyEncoding>| {
+            let state = state.clone();
+            async move {
+                let response = get_public_key_by_fingerprint(State(state), Path(path), Query(PublicKeyPermalinkQuery { format })).await;
+                let status = response.status();
+                let headers = response.headers().clone();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                (status, headers, body)
+            }
+        };
+
+        let (status, headers, body) = fetch(fingerprint.clone(), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body.as_ref(), public_key.as_bytes());
+        assert_eq!(headers[header::CONTENT_TYPE], "application/octet-stream");
+        assert_eq!(headers[header::CACHE_CONTROL], "public, max-age=31536000, immutable");
+        assert_eq!(headers[KEY_STATUS_HEADER], "active");
+
+        let (_, headers, body) = fetch(format!("{}.pem", fingerprint), None).await;
+        assert_eq!(headers[header::CONTENT_TYPE], "application/x-pem-file");
+        assert_eq!(body.as_ref(), encode_pem(&public_key).as_bytes());
+        // The colon-separated form and the query parameter resolve to the same content
+        let (_, headers, body) = fetch(public_key_to_fingerprint(&key_pair.public_key).unwrap().to_uppercase(), Some(PublicKeyEncoding::Jwk)).await;
+        assert_eq!(headers[header::CONTENT_TYPE], "application/jwk+json");
+        let jwk: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(jwk, encode_jwk(&public_key, key_pair.id));
+
+        assert_eq!(fetch(format!("{}.pem", fingerprint), Some(PublicKeyEncoding::Jwk)).await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(fetch(format!("{}.der", fingerprint), None).await.0, StatusCode::NOT_FOUND);
+        assert_eq!(fetch("not-a-fingerprint".to_string(), None).await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(fetch("0".repeat(32), None).await.0, StatusCode::NOT_FOUND);
+
+        let response = get_public_key_permalink(
+            State(state.clone()),
+            Path(key_pair.id),
+            Query(PublicKeyPermalinkQuery { format: Some(PublicKeyEncoding::Pem) }),
+        ).await;
+        assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+        assert_eq!(response.headers()[header::LOCATION], format!("/public/{}.pem", fingerprint).as_str());
+        let missing = get_public_key_permalink(State(state.clone()), Path(Uuid::new_v4()), Query(PublicKeyPermalinkQuery::default())).await;
+        assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+
+        // Revocation changes the status header, never the content
+        state.storage.revoke_key(key_pair.id, None).await.unwrap();
+        let (status, headers, body) = fetch(format!("{}.pem", fingerprint), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body.as_ref(), encode_pem(&public_key).as_bytes());
+        assert_eq!(headers[header::CACHE_CONTROL], "public, max-age=31536000, immutable");
+        assert_eq!(headers[KEY_STATUS_HEADER], "revoked");
+        let response = get_public_key_permalink(State(state.clone()), Path(key_pair.id), Query(PublicKeyPermalinkQuery::default())).await;
+        assert_eq!(response.headers()[header::LOCATION], format!("/public/{}", fingerprint).as_str());
+    }
+
+    #[tokio::test]
+    async fn test_verify_bounds_hostile_input_before_decoding() {
+        use rand::{Rng, SeedableRng};
+
+        let dir = tempdir().unwrap();
+        let clock = Arc::new(MockClock::new(Utc::now()));
+        let state = Arc::new(AppState {
+            config: Arc::new(Config { verify_max_content_bytes: 4096, ..Default::default() }),
+            verify_rate_limit: ClientRateLimiter::new(3),
+            sign_policy: Arc::new(SignPolicy::allow_all()),
+            key_resolver: Arc::new(KeyResolver::none()),
+            transport_key: Arc::new(TransportKey::generate(Utc::now())),
+            sweeper: Arc::new(TaskStatus::default()),
+            deadlines: RequestDeadlines::from_config(&Config::default()),
+            api_usage: ApiUsage::default(),
+            key_pool: Arc::new(KeyPool::from_config(&Config::default())),
+            usage_reports: UsageReportCache::default(),
+            ..Arc::into_inner(test_state(&dir, clock)).unwrap()
+        });
+        let key_pair = generate_test_key_pair("Public Verifier").unwrap();
+        state.storage.store_key(key_pair.clone()).await.unwrap();
+        let content = "invoice 2291";
+        let signature = sign_document(State(state.clone()), Json(SignDocumentRequest {
+            key_id: key_pair.id,
+            document_content: Some(content.to_string()),
+            ..Default::default()
+        })).await.unwrap().0.signature.unwrap();
+
+        let anonymous = VerifyCaller { ip: None, authenticated: false };
+        let verify = |caller: VerifyCaller, request: VerifySignatureRequest| {
+            let state = state.clone();
+            async move {
+                let response = verify_signature_from(State(state), caller, Json(request)).await;
+                let status = response.status();
+                let retry_after = response.headers().get(header::RETRY_AFTER).cloned();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                (status, serde_json::from_slice::<serde_json::Value>(&body).unwrap(), retry_after)
+            }
+        };
+        let request = |public_key: &str, signature: &str| VerifySignatureRequest {
+            public_key: public_key.to_string(),
+            signature: signature.to_string(),
+            document_content: Some(content.to_string()),
+            ..Default::default()
+        };
+
+        // Oversized input is refused up front, without decoding it
+        let started = std::time::Instant::now();
+        let huge = "A".repeat(16 * 1024 * 1024);
+        for oversized in [request(&huge, &signature), request(&key_pair.public_key, &huge)] {
+            let (status, body, _) = verify(anonymous, oversized).await;
+            assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+            assert_eq!(body["message"], "Validation failed: public keys and signatures must be at most 8192 characters");
+        }
+        let (status, _, _) = verify(anonymous, VerifySignatureRequest {
+            document_content: Some("x".repeat(4097)),
+            ..request(&key_pair.public_key, &signature)
+        }).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+        for hash in ["ab\u{0}cd".to_string(), "ab\ncd".to_string(), "a".repeat(129)] {
+            let (status, _, _) = verify(anonymous, VerifySignatureRequest {
+                document_hash: Some(hash),
+                document_content: None,
+                ..request(&key_pair.public_key, &signature)
+            }).await;
+            assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        }
+
+        // Anonymous callers cannot tell a bad key encoding from a bad signature encoding
+        let bad_key = request("not base64!", &signature);
+        let bad_signature = request(&key_pair.public_key, "not base64!");
+        let (_, key_body, _) = verify(anonymous, bad_key.clone()).await;
+        let (_, signature_body, _) = verify(anonymous, bad_signature.clone()).await;
+        assert_eq!(key_body["code"], "MALFORMED_INPUT");
+        assert_eq!(key_body, signature_body.clone());
+        assert!(key_body.get("details").is_none_or(serde_json::Value::is_null));
+        let authenticated = VerifyCaller { ip: None, authenticated: true };
+        assert_eq!(verify(authenticated, bad_key).await.1["code"], "INVALID_KEY_FORMAT");
+        assert_eq!(verify(authenticated, bad_signature).await.1["code"], "INVALID_SIGNATURE_FORMAT");
+        let (status, body, _) = verify(anonymous, request(&key_pair.public_key, &signature)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["is_valid"], true);
+
+        // Random malformed input never panics and always gets a bounded answer
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1660);
+        let alphabet: Vec<char> = "ABCxyz019+/=-_ \t\n\u{0}\u{7f}é😀{}[]\":,".chars().collect();
+        let garbage = |rng: &mut rand::rngs::StdRng| -> String {
+            let len = rng.gen_range(0..200);
+            (0..len).map(|_| alphabet[rng.gen_range(0..alphabet.len())]).collect()
+        };
+        let started = std::time::Instant::now();
+        for _ in 0..500 {
+            let fuzzed = VerifySignatureRequest {
+                public_key: if rng.gen_bool(0.3) { key_pair.public_key.clone() } else { garbage(&mut rng) },
+                signature: if rng.gen_bool(0.3) { signature.clone() } else { garbage(&mut rng) },
+                document_hash: rng.gen_bool(0.5).then(|| garbage(&mut rng)),
+                document_content: rng.gen_bool(0.5).then(|| garbage(&mut rng)),
+                content_type: if rng.gen_bool(0.5) { DocumentContentType::JsonJcs } else { DocumentContentType::Text },
+                context: rng.gen_bool(0.2).then(|| garbage(&mut rng)),
+                public_keys: if rng.gen_bool(0.1) { (0..rng.gen_range(1..4)).map(|_| garbage(&mut rng)).collect() } else { vec![] },
+                ..Default::default()
+            };
+            let (status, body, _) = verify(anonymous, fuzzed).await;
+            assert!(matches!(status.as_u16(), 200 | 400 | 422), "{} {}", status, body);
+            assert!(!matches!(body["code"].as_str(), Some("INVALID_KEY_FORMAT" | "INVALID_SIGNATURE_FORMAT")), "{}", body);
+        }
+        assert!(started.elapsed() < std::time::Duration::from_secs(30));
+
+        // Each anonymous address gets its own small budget; signed callers are not counted
+        let from = |ip: &str| VerifyCaller { ip: Some(ip.parse().unwrap()), authenticated: false };
+        for _ in 0..3 {
+            assert_eq!(verify(from("198.51.100.4"), request(&key_pair.public_key, &signature)).await.0, StatusCode::OK);
+        }
+        let (status, body, retry_after) = verify(from("198.51.100.4"), request(&key_pair.public_key, &signature)).await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(body["code"], "RATE_LIMITED");
+        assert_eq!(retry_after.unwrap(), "60");
+        assert_eq!(verify(from("198.51.100.5"), request(&key_pair.public_key, &signature)).await.0, StatusCode::OK);
+        let signed = VerifyCaller { authenticated: true, ..from("198.51.100.4") };
+        assert_eq!(verify(signed, request(&key_pair.public_key, &signature)).await.0, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_capabilities_snapshot() {
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir, Arc::new(MockClock::new(Utc::now())));
+        let Json(response) = capabilities(State(state.clone())).await;
+        assert_eq!(serde_json::to_value(&response).unwrap(), serde_json::json!({
+            "success": true,
+            "capabilities": {
+                "version": env!("CARGO_PKG_VERSION"),
+                "key_types": ["Ed25519", "Ed25519Encrypted"],
+                "signature_schemes": ["ed25519"],
+                "hash_algorithms": ["sha256", "sha512", "blake2b-512"],
+                "output_formats": ["raw", "minisign", "sshsig"],
+                "content_types": ["text", "json-jcs"],
+                "message_encodings": ["hash-raw-bytes", "hash-hex-bytes", "raw-message"],
+                "public_key_formats": ["json", "minisign", "ssh"],
+                "public_key_encodings": ["raw", "pem", "jwk"],
+                "bundle_formats": ["json", "cbor"],
+                "kdf_algorithms": ["pbkdf2-sha256", "argon2id"],
+                "hsm": false,
+                "request_signing": false,
+                "read_only": false,
+                "profile": "full",
+                "features": {
+                    "webhook_notifications": cfg!(feature = "webhook"),
+                    "email_notifications": cfg!(feature = "email"),
+                    "keystore_watch": cfg!(feature = "watch"),
+                    "shared_rate_limits": cfg!(feature = "redis"),
+                    "federation": cfg!(feature = "federation"),
+                    "verifier_only": cfg!(feature = "verifier-only"),
+                },
+                "pagination": {
+                    "cursor_endpoints": ["/keys", "/keys/search"],
+                    "key_order": ["created_at", "id"],
+                    "offset": true
+                },
+                "limits": {
+                    "max_key_name_length": 100,
+                    "max_description_length": 1000,
+                    "min_password_length": 8,
+                    "max_tags": 20,
+                    "max_tag_length": 50,
+                    "max_allowed_contexts": 20,
+                    "max_context_length": 255,
+                    "max_document_hash_length": 128,
+                    "max_verify_encoded_length": 8192,
+                    "max_raw_message_bytes": 65536,
+                    "verify_max_content_bytes": 1048576,
+                    "sign_max_content_bytes": 1048576,
+                    "raw_sign_max_content_bytes": 268435456,
+                    "verify_requests_per_minute": 60,
+                    "max_keys": null,
+                    "max_key_lifetime_days": null,
+                },
+            },
+        }));
+
+        // A key's capabilities travel with its public information
+        let key_pair = generate_test_key_pair("Capable Key").unwrap();
+        state.storage.store_key(key_pair.clone()).await.unwrap();
+        let info = serde_json::to_value(KeyInfo::new(key_pair, state.clock.now())).unwrap();
+        assert_eq!(info["capabilities"]["output_formats"], serde_json::json!(["raw", "minisign", "sshsig"]));
+        assert_eq!(info["capabilities"]["requires_password"], false);
+    }
+
+    #[tokio::test]
+    async fn test_deleted_key_lists_and_restores_intact() {
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir, Arc::new(MockClock::new(Utc::now())));
+        let key_pair = generate_seeded_test_key_pair("Restorable Key", 1);
+        state.storage.store_key(key_pair.clone()).await.unwrap();
+        let sign = |state: Arc<AppState>| sign_document(State(state), Json(SignDocumentRequest {
+            key_id: key_pair.id,
+            document_hash: Some("ab".repeat(32)),
+            ..Default::default()
+        }));
+        assert!(sign(state.clone()).await.is_ok());
+
+        let Json(deleted) = delete_key(State(state.clone()), Path(key_pair.id), Some(AuthenticatedClient("ops".to_string()))).await.unwrap();
+        assert_eq!(deleted.deleted_by.as_deref(), Some("ops"));
+        assert_eq!(sign(state.clone()).await.unwrap_err().0, StatusCode::NOT_FOUND);
+        assert_eq!(delete_key(State(state.clone()), Path(key_pair.id), None).await.unwrap_err().0, StatusCode::NOT_FOUND);
+
+        let response = list_deleted_keys(State(state.clone())).await;
+        let listed: serde_json::Value = serde_json::from_slice(&axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(listed["total_count"], 1);
+        assert_eq!(listed["keys"][0]["key_info"]["id"], key_pair.id.to_string());
+        assert_eq!(listed["keys"][0]["deleted_by"], "ops");
+        assert_eq!(listed["keys"][0]["key_info"]["usage"]["sign_count"], 1);
+
+        let Json(restored) = restore_key(State(state.clone()), Path(key_pair.id)).await.unwrap();
+        assert_eq!(restored.deleted_at, Some(deleted.deleted_at.unwrap()));
+        let stored = state.storage.get_key_record(key_pair.id).await.unwrap();
+        assert_eq!(stored.public_key, key_pair.public_key);
+        assert_eq!(stored.fingerprint.as_deref(), Some("f07815cb:38d0a5cd:0519604d:319662b5"));
+        assert_eq!(stored.usage.sign_count, 1);
+        assert!(sign(state.clone()).await.is_ok());
+        assert!(state.storage.deleted_keys().await.unwrap().is_empty());
+        assert_eq!(restore_key(State(state.clone()), Path(key_pair.id)).await.unwrap_err().0, StatusCode::NOT_FOUND);
+
+        // A new active key with the same name blocks the restore until it is gone
+        assert!(delete_key(State(state.clone()), Path(key_pair.id), None).await.is_ok());
+        let replacement = generate_test_key_pair("restorable key").unwrap();
+        state.storage.store_key(replacement.clone()).await.unwrap();
+        let (status, Json(conflict)) = restore_key(State(state.clone()), Path(key_pair.id)).await.unwrap_err();
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(conflict.code, Some(ErrorCode::RestoreConflict));
+        state.storage.revoke_key(replacement.id, None).await.unwrap();
+
+        // So does a key quota reached since the deletion
+        let limited = Arc::new(AppState {
+            config: Arc::new(Config { max_keys: Some(1), ..Config::default() }),
+            ..Arc::into_inner(state).unwrap()
+        });
+        let (status, Json(full)) = restore_key(State(limited.clone()), Path(key_pair.id)).await.unwrap_err();
+        assert_eq!(status, StatusCode::INSUFFICIENT_STORAGE);
+        assert_eq!(full.code, Some(ErrorCode::KeystoreFull));
+
+        // Archived keys are listed with the policy that moved them
+        limited.storage.archive_keys(&[replacement.id], "long-revoked").await.unwrap();
+        assert!(restore_key(State(limited.clone()), Path(key_pair.id)).await.is_ok());
+        let response = list_archived_keys(State(limited.clone())).await;
+        let archived: serde_json::Value = serde_json::from_slice(&axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(archived["total_count"], 1);
+        assert_eq!(archived["keys"][0]["key_info"]["id"], replacement.id.to_string());
+        assert_eq!(archived["keys"][0]["reason"], "long-revoked");
+    }
+
+    #[tokio::test]
+    async fn test_suspended_key_resumes_and_its_lifecycle_is_recorded() {
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir, Arc::new(MockClock::new(Utc::now())));
+        let key_pair = generate_test_key_pair("Suspendable Key").unwrap();
+        state.storage.store_key(key_pair.clone()).await.unwrap();
+        let ops = || Some(AuthenticatedClient("ops".to_string()));
+        let reason = |reason: &str| Json(KeyTransitionRequest { reason: Some(reason.to_string()) });
+        let sign = |state: Arc<AppState>| sign_document(State(state), Json(SignDocumentRequest {
+            key_id: key_pair.id,
+            document_hash: Some("ab".repeat(32)),
+            ..Default::default()
+        }));
+
+        let Json(suspended) = suspend_key(State(state.clone()), Path(key_pair.id), reason("investigating"), ops()).await.unwrap();
+        assert_eq!(suspended.key_info.unwrap().state, KeyState::Suspended);
+        let transition = suspended.transition.unwrap();
+        assert_eq!((transition.from, transition.to, transition.actor.as_deref()), (KeyState::Active, KeyState::Suspended, Some("ops")));
+        let (status, Json(refused)) = sign(state.clone()).await.unwrap_err();
+        assert_eq!((status, refused.code), (StatusCode::LOCKED, Some(ErrorCode::KeySuspended)));
+
+        // Moves the lifecycle does not allow are refused with both states
+        let (status, Json(again)) = suspend_key(State(state.clone()), Path(key_pair.id), reason("again"), None).await.unwrap_err();
+        assert_eq!((status, again.code), (StatusCode::CONFLICT, Some(ErrorCode::InvalidTransition)));
+        assert_eq!(again.details.unwrap()["from"], "suspended");
+        let (status, _) = resume_key(State(state.clone()), Path(Uuid::new_v4()), reason("missing"), None).await.unwrap_err();
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let Json(resumed) = resume_key(State(state.clone()), Path(key_pair.id), reason("cleared"), ops()).await.unwrap();
+        assert_eq!(resumed.key_info.unwrap().state, KeyState::Active);
+        assert!(sign(state.clone()).await.is_ok());
+
+        // Revoking through an update is final, and a restore returns the key revoked
+        let update = UpdateKeyRequest { is_active: Some(false), ..Default::default() };
+        assert!(update_key(State(state.clone()), Path(key_pair.id), Json(update), None).await.unwrap().0.success);
+        let (status, _) = resume_key(State(state.clone()), Path(key_pair.id), reason("undo"), None).await.unwrap_err();
+        assert_eq!(status, StatusCode::CONFLICT);
+        let update = UpdateKeyRequest { is_active: Some(true), ..Default::default() };
+        let (status, Json(reactivated)) = update_key(State(state.clone()), Path(key_pair.id), Json(update), None).await.unwrap_err();
+        assert_eq!((status, reactivated.code), (StatusCode::CONFLICT, Some(ErrorCode::InvalidTransition)));
+        assert!(delete_key(State(state.clone()), Path(key_pair.id), ops()).await.unwrap().0.success);
+        let Json(restored) = restore_key(State(state.clone()), Path(key_pair.id)).await.unwrap();
+        let key_info = restored.key_info.unwrap();
+        assert_eq!(key_info.state, KeyState::Revoked);
+        let moves: Vec<_> = key_info.lifecycle_history.iter().map(|event| (event.from.as_str(), event.to.as_str())).collect();
+        assert_eq!(moves, [
+            ("active", "suspended"),
+            ("suspended", "active"),
+            ("active", "revoked"),
+            ("revoked", "deleted"),
+            ("deleted", "revoked"),
+        ]);
+        assert_eq!(key_info.lifecycle_history[0].reason.as_deref(), Some("investigating"));
+        assert_eq!(key_info.lifecycle_history[3].actor.as_deref(), Some("ops"));
+    }
+
+    #[tokio::test]
+    async fn test_ephemeral_signature_verifies_and_leaves_only_a_tombstone() {
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir, Arc::new(MockClock::new(Utc::now())));
+        let request = |tombstone: bool| EphemeralSignRequest {
+            document_content: Some("one-off release manifest".to_string()),
+            context: Some("release".to_string()),
+            bundle: true,
+            tombstone,
+            ..Default::default()
+        };
+
+        // Without a tombstone nothing about the key is stored, but the receipt is
+        let Json(signed) = sign_ephemeral(State(state.clone()), Json(request(false))).await.unwrap();
+        assert!(signed.key_id.is_none());
+        assert!(state.storage.list_keys().await.is_empty());
+        assert_eq!(signed.bundle.as_ref().unwrap().body.key_fingerprint, signed.key_fingerprint.clone().unwrap());
+        let verify = VerifySignatureRequest {
+            public_key: signed.public_key.clone().unwrap(),
+            signature: signed.signature.clone().unwrap(),
+            document_hash: signed.document_hash.clone(),
+            context: Some("release".to_string()),
+            ..Default::default()
+        };
+        assert!(crate::key_verification::verify_signature(&verify).unwrap());
+        let record = get_signature_record(State(state.clone()), Path(signed.signature_id.unwrap())).await;
+        assert_eq!(record.status(), StatusCode::OK);
+
+        // With one, a revoked record holding only the public key remains
+        let Json(kept) = sign_ephemeral(State(state.clone()), Json(request(true))).await.unwrap();
+        assert_ne!(kept.public_key, signed.public_key);
+        let key_id = kept.key_id.unwrap();
+        let tombstone = state.storage.get_key_record(key_id).await.unwrap();
+        assert_eq!(tombstone.key_type, KeyType::Ed25519Ephemeral);
+        assert_eq!(tombstone.public_key, kept.public_key.unwrap());
+        assert!(tombstone.private_key.is_empty());
+        assert_eq!(tombstone.lifecycle, Lifecycle::Revoked);
+        assert_eq!(tombstone.usage.sign_count, 1);
+        assert!(state.storage.get_key(key_id).await.is_err());
+        let check = crate::integrity::check_key(key_id, &tombstone, &std::collections::HashSet::from([key_id]));
+        assert!(check.issues.is_empty(), "{:?}", check.issues);
+
+        // Ephemeral keys count against the key quota like any other
+        let limited = Arc::new(AppState {
+            config: Arc::new(Config { max_keys: Some(1), ..Config::default() }),
+            ..Arc::into_inner(state).unwrap()
+        });
+        let (status, Json(refused)) = sign_ephemeral(State(limited), Json(request(true))).await.unwrap_err();
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(refused.code, Some(ErrorCode::ValidationFailed));
+    }
+
+    #[tokio::test]
+    async fn test_generation_merges_template_defaults() {
+        let dir = tempdir().unwrap();
+        let templates_file = dir.path().join("templates.json");
+        std::fs::write(&templates_file, r#"[{
+            "name": "release-signing",
+            "summary": "Keys that sign production releases",
+            "tags": ["env:prod", "team:platform", "cost-center:4410"],
+            "description_prefix": "[release] ",
+            "lifetime_days": 90,
+            "allowed_contexts": ["release"]
+        }]"#).unwrap();
+        let path = templates_file.to_str().unwrap().to_string();
+        let config = Config::from_lookup(|name| (name == "INKAN_KEY_TEMPLATES_FILE").then(|| path.clone())).unwrap();
+        let now = crate::timestamps::normalize(Utc::now());
+        let state = Arc::new(AppState {
+            config: Arc::new(config),
+            ..Arc::into_inner(test_state(&dir, Arc::new(MockClock::new(now)))).unwrap()
+        });
+        let generate = |name: &str, template: Option<&str>| GenerateKeyRequest {
+            name: name.to_string(),
+            description: Some("Signs artifacts".to_string()),
+            tags: Some(vec!["team:security".to_string()]),
+            template: template.map(str::to_string),
+            environment: None,
+            fast: false,
+            ..Default::default()
+        };
+
+        let Json(plain) = generate_keys(State(state.clone()), Query(GenerateKeyQuery::default()), Json(generate("Plain", None))).await.unwrap();
+        let plain = plain.key_pair.unwrap();
+        assert_eq!(plain.tags, ["team:security"]);
+        assert_eq!(plain.description.as_deref(), Some("Signs artifacts"));
+        assert_eq!(plain.expires_at, None);
+        assert_eq!(plain.allowed_contexts, None);
+
+        let Json(templated) = generate_keys(State(state.clone()), Query(GenerateKeyQuery::default()), Json(generate("Release", Some("release-signing")))).await.unwrap();
+        assert_eq!(templated.warnings[0], "Tag 'team:security' overrides 'team:platform' from template 'release-signing'");
+        let templated = templated.key_pair.unwrap();
+        assert_eq!(templated.tags, ["env:prod", "cost-center:4410", "team:security"]);
+        assert_eq!(templated.description.as_deref(), Some("[release] Signs artifacts"));
+        assert_eq!(templated.expires_at, Some(now + Duration::days(90)));
+        assert_eq!(templated.allowed_contexts, Some(vec!["release".to_string()]));
+
+        let (status, Json(unknown)) = generate_keys(State(state.clone()), Query(GenerateKeyQuery::default()), Json(generate("Other", Some("nightly")))).await.unwrap_err();
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(unknown.errors[0].field, "template");
+
+        let Json(listed) = list_templates(State(state)).await;
+        assert_eq!(listed.total_count, 1);
+        assert_eq!(listed.templates[0].name, "release-signing");
+        assert_eq!(listed.templates[0].summary.as_deref(), Some("Keys that sign production releases"));
+    }
+
+    #[tokio::test]
+    async fn test_compare_reports_drift_against_another_instance() {
+        let (staging_dir, production_dir) = (tempdir().unwrap(), tempdir().unwrap());
+        let clock = Arc::new(MockClock::new(Utc::now()));
+        let notary = generate_test_key_pair("Staging Notary").unwrap();
+        let staging = test_state(&staging_dir, clock.clone());
+        staging.storage.store_key(notary.clone()).await.unwrap();
+        let staging = Arc::new(AppState {
+            config: Arc::new(Config { notary_key_id: Some(notary.id), ..Config::default() }),
+            ..Arc::into_inner(staging).unwrap()
+        });
+        let production = test_state(&production_dir, clock);
+        let [root, release, archive] = [("Root", 0), ("Release", 1), ("Archive", 2)].map(|(name, seed)| generate_seeded_test_key_pair(name, seed));
+        for key_pair in [&root, &release, &archive] {
+            staging.storage.store_key(key_pair.clone()).await.unwrap();
+        }
+
+        // Production: same root under another name, release revoked, archive missing, one extra
+        production.storage.store_key(KeyPair { name: "Production Root".to_string(), ..root.clone() }).await.unwrap();
+        production.storage.store_key(release.clone()).await.unwrap();
+        production.storage.revoke_key(release.id, None).await.unwrap();
+        let extra = generate_test_key_pair("Hotfix").unwrap();
+        production.storage.store_key(extra.clone()).await.unwrap();
+
+        let response = get_key_manifest(State(staging.clone())).await;
+        let manifest: crate::key_comparison::SignedKeyManifest = serde_json::from_slice(&axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(manifest.manifest.keys.len(), 4);
+        assert!(manifest.verify().unwrap());
+
+        let compare = |request: CompareKeysRequest| {
+            let production = production.clone();
+            async move {
+                let response = compare_keys(State(production), Json(request)).await;
+                let status = response.status();
+                let body: serde_json::Value = serde_json::from_slice(&axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+                (status, body)
+            }
+        };
+        let (status, diff) = compare(CompareKeysRequest { manifest: Some(manifest.clone()), ..Default::default() }).await;
+        assert_eq!(status, StatusCode::OK);
+        let fingerprint = |key_pair: &KeyPair| public_key_to_fingerprint(&key_pair.public_key).unwrap();
+        assert_eq!(diff["present"][0]["fingerprint"], fingerprint(&root));
+        assert_eq!(diff["present"][0]["differences"], serde_json::json!(["name"]));
+        assert_eq!(diff["revoked"][0]["fingerprint"], fingerprint(&release));
+        assert_eq!(diff["revoked"][0]["differences"], serde_json::json!(["state", "expires_at"]));
+        assert_eq!(diff["absent"], serde_json::json!([fingerprint(&notary), fingerprint(&archive)]));
+        assert_eq!(diff["local_only"], serde_json::json!([{ "fingerprint": fingerprint(&extra), "name": "Hotfix", "state": "active", "expires_at": null }]));
+        assert_eq!(diff["in_sync"], false);
+        assert_eq!(diff["signed"], true);
+        assert!(!diff.to_string().contains(root.private_key.expose_for_persistence()));
+
+        // Bare fingerprints match in compact form and are compared by presence alone
+        let (_, diff) = compare(CompareKeysRequest { fingerprints: vec![fingerprint(&root).replace(':', "").to_uppercase()], ..Default::default() }).await;
+        assert_eq!(diff["present"][0]["differences"], serde_json::Value::Null);
+        assert_eq!(diff["signed"], false);
+
+        let mut tampered = manifest;
+        tampered.manifest.keys.retain(|entry| entry.fingerprint != fingerprint(&archive));
+        let (status, _) = compare(CompareKeysRequest { manifest: Some(tampered), ..Default::default() }).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(compare(CompareKeysRequest::default()).await.0, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_pinset_lists_tagged_keys_as_json_or_source() {
+        use tower::ServiceExt;
+
+        let dir = tempdir().unwrap();
+        let notary = generate_test_key_pair("Pinset Notary").unwrap();
+        let state = test_state(&dir, Arc::new(MockClock::new(Utc::now())));
+        state.storage.store_key(notary.clone()).await.unwrap();
+        let state = Arc::new(AppState {
+            config: Arc::new(Config { notary_key_id: Some(notary.id), ..Config::default() }),
+            ..Arc::into_inner(state).unwrap()
+        });
+        let [release, revoked, internal] = [("Release", 0), ("Old Release", 1), ("Internal", 2)].map(|(name, seed)| generate_seeded_test_key_pair(name, seed));
+        for key_pair in [&release, &revoked] {
+            state.storage.store_key(KeyPair { tags: vec!["release".to_string()], ..key_pair.clone() }).await.unwrap();
+        }
+        state.storage.store_key(internal).await.unwrap();
+        state.storage.revoke_key(revoked.id, None).await.unwrap();
+
+        let app = crate::routes::router_with_versions(state, ApiVersion::ALL);
+        let get = |query: &str| {
+            let request = axum::http::Request::get(format!("/v1/keys/pinset?{}", query)).body(axum::body::Body::empty()).unwrap();
+            let app = app.clone();
+            async move { app.oneshot(request).await.unwrap() }
+        };
+
+        let response = get("tags=Release").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let signed: crate::pinset::SignedPinset = serde_json::from_slice(&axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert!(signed.verify().unwrap());
+        assert_eq!(signed.pinset.keys.iter().map(|key| key.key_id).collect::<Vec<_>>(), [release.id]);
+        assert_eq!(signed.pinset.keys[0].fingerprint, "8d6470fa:2d00d290:d7eb1df2:c16964ae");
+
+        let response = get("tags=release&format=kotlin").await;
+        assert_eq!(response.headers()[header::CONTENT_DISPOSITION], "attachment; filename=\"inkan-pinset.kt\"");
+        let source = String::from_utf8(axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap().to_vec()).unwrap();
+        assert!(source.contains(&format!("// Notary key {} (", notary.id)));
+        assert!(source.contains(&format!("keyId = \"{}\"", release.id)));
+        assert!(!source.contains(&revoked.id.to_string()));
+        assert!(!source.contains(release.private_key.expose_for_persistence()));
+
+        assert_eq!(get("format=java").await.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_slo_report_counts_successful_requests_against_their_targets() {
+        use tower::ServiceExt;
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir, Arc::new(MockClock::new(Utc::now())));
+        // A zero target makes every signature a violation
+        let config = Config { slo_sign_target_ms: 0, ..Config::default() };
+        let state = Arc::new(AppState {
+            slo: SloTracker::from_config(&config),
+            config: Arc::new(config),
+            ..Arc::into_inner(state).unwrap()
+        });
+        let key_pair = generate_seeded_test_key_pair("Latency Key", 4);
+        state.storage.store_key(key_pair.clone()).await.unwrap();
+
+        let app = crate::routes::router_with_versions(state, ApiVersion::ALL);
+        let send = |request: axum::http::Request<axum::body::Body>| {
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(request).await.unwrap();
+                let status = response.status();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                (status, serde_json::from_slice::<serde_json::Value>(&body).unwrap())
+            }
+        };
+        let sign = |key_id: Uuid| send(axum::http::Request::post("/v1/sign")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(axum::body::Body::from(serde_json::json!({ "key_id": key_id, "document_hash": create_document_hash("slo") }).to_string()))
+            .unwrap());
+        assert_eq!(sign(key_pair.id).await.0, StatusCode::OK);
+        // Failures and untracked routes are not timed
+        assert_eq!(sign(Uuid::new_v4()).await.0, StatusCode::NOT_FOUND);
+        assert_eq!(send(axum::http::Request::get("/v1/keys").body(axum::body::Body::empty()).unwrap()).await.0, StatusCode::OK);
+
+        let (status, report) = send(axum::http::Request::get("/v1/admin/slo").body(axum::body::Body::empty()).unwrap()).await;
+        assert_eq!(status, StatusCode::OK);
+        let operations = report["operations"].as_array().unwrap();
+        assert_eq!(operations.iter().map(|operation| operation["operation"].as_str().unwrap()).collect::<Vec<_>>(), ["generate", "sign", "verify"]);
+        let sign_windows = &operations[1]["windows"];
+        assert_eq!(operations[1]["target_ms"], 0);
+        for window in sign_windows.as_array().unwrap() {
+            assert_eq!((window["count"].as_u64(), window["violations"].as_u64(), window["breached"].as_bool()), (Some(1), Some(1), Some(true)));
+            assert!(window["p99_ms"].as_f64().is_some());
+        }
+        assert_eq!(operations[0]["windows"][0]["count"], 0);
+        assert_eq!(operations[0]["windows"][0]["p99_ms"], serde_json::Value::Null);
+    }
+
+    #[tokio::test]
+    async fn test_key_status_answers_for_every_key_that_ever_existed() {
+        use crate::key_status::KeyStatusDocument;
+        use tower::ServiceExt;
+
+        let dir = tempdir().unwrap();
+        let now = Utc::now();
+        let notary = generate_test_key_pair("Status Notary").unwrap();
+        let state = test_state(&dir, Arc::new(MockClock::new(now)));
+        state.storage.store_key(notary.clone()).await.unwrap();
+        let state = Arc::new(AppState {
+            config: Arc::new(Config { notary_key_id: Some(notary.id), ..Config::default() }),
+            ..Arc::into_inner(state).unwrap()
+        });
+        let [active, suspended, expired, revoked, deleted, archived] = ["Active", "Suspended", "Expired", "Revoked", "Deleted", "Archived"]
+            .map(|name| generate_test_key_pair(name).unwrap());
+        for key_pair in [&active, &suspended, &revoked, &deleted, &archived] {
+            state.storage.store_key(key_pair.clone()).await.unwrap();
+        }
+        state.storage.store_key(KeyPair { expires_at: Some(now - chrono::Duration::days(1)), ..expired.clone() }).await.unwrap();
+        state.storage.transition_key(suspended.id, KeyState::Suspended, None, None).await.unwrap();
+        state.storage.revoke_key(revoked.id, Some("compromised".to_string())).await.unwrap();
+        state.storage.soft_delete_key(deleted.id, None).await.unwrap();
+        state.storage.archive_keys(&[archived.id], "retired").await.unwrap();
+
+        let app = crate::routes::router_with_versions(state, ApiVersion::ALL);
+        let send = |request: axum::http::Request<axum::body::Body>| {
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(request).await.unwrap();
+                let status = response.status();
+                let cache_control = response.headers().get(header::CACHE_CONTROL).cloned();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                (status, cache_control, serde_json::from_slice::<serde_json::Value>(&body).unwrap())
+            }
+        };
+        let get = |path: String| send(axum::http::Request::get(path).body(axum::body::Body::empty()).unwrap());
+
+        for (key_pair, expected) in [
+            (&active, KeyState::Active),
+            (&suspended, KeyState::Suspended),
+            (&expired, KeyState::Expired),
+            (&revoked, KeyState::Revoked),
+            (&deleted, KeyState::Deleted),
+            (&archived, KeyState::Active),
+        ] {
+            let (status, cache_control, body) = get(format!("/v1/keys/{}/status", key_pair.id)).await;
+            assert_eq!(status, StatusCode::OK, "{}", key_pair.name);
+            assert_eq!(cache_control.unwrap(), "public, max-age=60");
+            let document: KeyStatusDocument = serde_json::from_value(body).unwrap();
+            assert_eq!((document.status.key_id, document.status.state), (key_pair.id, expected));
+            assert_eq!(Some(document.status.fingerprint), public_key_to_fingerprint(&key_pair.public_key).ok());
+            assert!(document.notary.is_none());
+        }
+        let (status, _, body) = get(format!("/v1/keys/{}/status", Uuid::new_v4())).await;
+        assert_eq!((status, body["code"].as_str()), (StatusCode::NOT_FOUND, Some("KEY_NOT_FOUND")));
+
+        // A signed status verifies from its JSON alone
+        let (_, _, body) = get(format!("/v1/keys/{}/status?signed=true", revoked.id)).await;
+        let document: KeyStatusDocument = serde_json::from_value(body).unwrap();
+        assert_eq!(document.notary.as_ref().map(|notary| notary.key_id), Some(notary.id));
+        assert!(document.verify().unwrap());
+
+        let batch = |body: serde_json::Value| send(
+            axum::http::Request::post("/v1/keys/status/batch")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(axum::body::Body::from(body.to_string()))
+                .unwrap(),
+        );
+        let unknown = Uuid::new_v4();
+        let (status, _, body) = batch(serde_json::json!({ "key_ids": [deleted.id, unknown, active.id], "signed": true })).await;
+        assert_eq!(status, StatusCode::OK);
+        let response: KeyStatusBatchResponse = serde_json::from_value(body).unwrap();
+        assert_eq!(response.unknown, [unknown]);
+        assert_eq!(response.statuses.iter().map(|document| (document.status.key_id, document.status.state)).collect::<Vec<_>>(), [
+            (deleted.id, KeyState::Deleted),
+            (active.id, KeyState::Active),
+        ]);
+        assert!(response.statuses.iter().all(|document| document.verify().unwrap()));
+
+        let too_many: Vec<Uuid> = (0..=MAX_STATUS_BATCH).map(|_| Uuid::new_v4()).collect();
+        let (status, _, _) = batch(serde_json::json!({ "key_ids": too_many })).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn test_manifest_signature_reports_per_file_mismatches() {
+        use crate::file_manifest::FileEntry;
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir, Arc::new(MockClock::new(Utc::now())));
+        let key_pair = generate_seeded_test_key_pair("Release Key", 1);
+        state.storage.store_key(key_pair.clone()).await.unwrap();
+        let entry = |path: &str, content: &str| FileEntry { path: path.to_string(), sha256: create_document_hash(content) };
+        let files = vec![entry("bin/inkan", "binary"), entry("README.md", "readme"), entry("LICENSE", "license")];
+
+        let Json(signed) = sign_manifest(State(state.clone()), HeaderMap::new(), None, Json(SignManifestRequest {
+            key_id: key_pair.id,
+            files: files.clone(),
+            context: Some("release".to_string()),
+            ..Default::default()
+        })).await.unwrap();
+        let manifest = signed.manifest.unwrap();
+        assert_eq!(manifest.files.iter().map(|file| file.path.as_str()).collect::<Vec<_>>(), ["LICENSE", "README.md", "bin/inkan"]);
+        assert_eq!(signed.signed.document_hash, Some(manifest.document_hash().unwrap()));
+        let record = get_signature_record(State(state.clone()), Path(signed.signed.signature_id.unwrap())).await;
+        assert_eq!(record.status(), StatusCode::OK);
+
+        let verify = |files: Option<Vec<FileEntry>>| {
+            let state = state.clone();
+            let request = VerifyManifestRequest {
+                manifest: manifest.clone(),
+                signature: signed.signed.signature.clone().unwrap(),
+                public_key: key_pair.public_key.clone(),
+                key_id: None,
+                valid_until: None,
+                context: Some("release".to_string()),
+                signing_time: None,
+                files,
+            };
+            async move {
+                let response = verify_manifest(State(state), VerifyCaller::default(), Json(request)).await;
+                let status = response.status();
+                let body: serde_json::Value = serde_json::from_slice(&axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+                (status, body)
+            }
+        };
+
+        let (status, intact) = verify(Some(files.clone())).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(intact["is_valid"], true);
+        assert_eq!(intact["files_match"], true);
+
+        // A changed file, a lost one and a stray one; the signature itself still verifies
+        let tampered = vec![entry("bin/inkan", "patched binary"), entry("README.md", "readme"), entry("NOTES", "notes")];
+        let (status, result) = verify(Some(tampered)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(result["is_valid"], true);
+        assert_eq!(result["files_match"], false);
+        assert_eq!(result["files"], serde_json::json!([
+            { "path": "README.md", "status": "match" },
+            {
+                "path": "bin/inkan",
+                "status": "mismatch",
+                "expected_sha256": create_document_hash("binary"),
+                "actual_sha256": create_document_hash("patched binary"),
+            },
+        ]));
+        assert_eq!(result["missing"], serde_json::json!(["LICENSE"]));
+        assert_eq!(result["extra"], serde_json::json!(["NOTES"]));
+
+        // Without files only the signature is checked; a changed manifest no longer verifies
+        let (_, unchecked) = verify(None).await;
+        assert_eq!(unchecked["is_valid"], true);
+        assert!(unchecked.get("files_match").is_none());
+        let mut altered = manifest.clone();
+        altered.files[2].sha256 = create_document_hash("patched binary");
+        let response = verify_manifest(State(state.clone()), VerifyCaller::default(), Json(VerifyManifestRequest {
+            manifest: altered,
+            signature: signed.signed.signature.clone().unwrap(),
+            public_key: key_pair.public_key.clone(),
+            key_id: None,
+            valid_until: None,
+            context: Some("release".to_string()),
+            signing_time: None,
+            files: None,
+        })).await;
+        let body: serde_json::Value = serde_json::from_slice(&axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(body["is_valid"], false);
+    }
+
+    #[tokio::test]
+    async fn test_dsse_envelope_signs_and_verifies_with_any_or_all() {
+        use crate::dsse::IN_TOTO_PAYLOAD_TYPE;
+        use base64::Engine;
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir, Arc::new(MockClock::new(Utc::now())));
+        let builder = generate_seeded_test_key_pair("Builder", 1);
+        let reviewer = generate_seeded_test_key_pair("Reviewer", 2);
+        let outsider = generate_seeded_test_key_pair("Outsider", 3);
+        state.storage.store_key(builder.clone()).await.unwrap();
+        state.storage.store_key(reviewer.clone()).await.unwrap();
+        let payload = br#"{"_type":"https://in-toto.io/Statement/v1"}"#;
+
+        let sign_with = |key_pair: &KeyPair| {
+            let request = SignDsseRequest {
+                key_id: key_pair.id,
+                payload_type: IN_TOTO_PAYLOAD_TYPE.to_string(),
+                payload_b64: base64::engine::general_purpose::STANDARD.encode(payload),
+                password: None,
+            };
+            sign_dsse(State(state.clone()), HeaderMap::new(), None, Json(request))
+        };
+        let Json(signed) = sign_with(&builder).await.unwrap();
+        let mut envelope = signed.envelope.unwrap();
+        assert_eq!(envelope.signatures[0].keyid, public_key_to_fingerprint(&builder.public_key).unwrap());
+        assert_eq!(signed.signed.document_hash, Some(hex::encode(Sha256::digest(dsse::pae(IN_TOTO_PAYLOAD_TYPE, payload)))));
+
+        let verify = |envelope: &dsse::DsseEnvelope, key_ids: Vec<Uuid>, public_keys: Vec<String>, mode: Option<VerifyMode>| {
+            let request = VerifyDsseRequest { envelope: envelope.clone(), key_id: None, public_key: None, key_ids, public_keys, mode };
+            let state = state.clone();
+            async move {
+                let response = verify_dsse(State(state), VerifyCaller::default(), Json(request)).await;
+                let status = response.status();
+                let body: serde_json::Value = serde_json::from_slice(&axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+                (status, body)
+            }
+        };
+
+        // One signature against one stored key needs no mode
+        let (status, body) = verify(&envelope, vec![builder.id], vec![], None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!((body["is_valid"].clone(), body["mode"].clone()), (serde_json::json!(true), serde_json::json!("any")));
+        assert_eq!(body["keys"][0]["signature_index"], 0);
+        assert_eq!(state.storage.get_key_record(builder.id).await.unwrap().usage.verify_count, 1);
+
+        // A second signature makes the mode required
+        let Json(countersigned) = sign_with(&reviewer).await.unwrap();
+        envelope.signatures.extend(countersigned.envelope.unwrap().signatures);
+        let (status, _) = verify(&envelope, vec![builder.id], vec![], None).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        let (_, both) = verify(&envelope, vec![reviewer.id, builder.id], vec![], Some(VerifyMode::All)).await;
+        assert_eq!(both["is_valid"], true);
+        assert_eq!(both["keys"].as_array().unwrap().iter().map(|key| key["signature_index"].clone()).collect::<Vec<_>>(), [1, 0]);
+
+        // A supplied key that did not sign fails all but not any
+        let outsider_key = vec![outsider.public_key.clone()];
+        let (_, all) = verify(&envelope, vec![builder.id], outsider_key.clone(), Some(VerifyMode::All)).await;
+        assert_eq!(all["is_valid"], false);
+        assert_eq!(all["keys"][1]["signature_index"], serde_json::Value::Null);
+        let (_, any) = verify(&envelope, vec![builder.id], outsider_key, Some(VerifyMode::Any)).await;
+        assert_eq!(any["is_valid"], true);
+
+        // The payload type is bound into every signature
+        let retyped = dsse::DsseEnvelope { payload_type: "text/plain".to_string(), ..envelope.clone() };
+        let (_, body) = verify(&retyped, vec![], vec![builder.public_key.clone()], Some(VerifyMode::Any)).await;
+        assert_eq!(body["is_valid"], false);
+    }
+
+    #[tokio::test]
+    async fn test_sign_policy_vetoes_signatures() {
+        use crate::config::SignPolicyConfig;
+        use crate::sign_policy::{PolicyDecision, PolicyEngine};
+
+        /// Denies documents whose hash starts with `0` and stalls on `f`, recording each request
+        #[derive(Default)]
+        struct MockPolicy {
+            requests: std::sync::Mutex<Vec<PolicyRequest>>,
+        }
+
+        #[axum::async_trait]
+        impl PolicyEngine for MockPolicy {
+            fn name(&self) -> &str {
+                "mock"
+            }
+
+            async fn evaluate(&self, request: &PolicyRequest) -> Result<PolicyDecision, KeyManagementError> {
+                self.requests.lock().unwrap().push(request.clone());
+                if request.document_hash.starts_with('f') {
+                    tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+                }
+                Ok(if request.document_hash.starts_with('0') { PolicyDecision::deny("release freeze") } else { PolicyDecision::allow() })
+            }
+        }
+
+        let dir = tempdir().unwrap();
+        let key_pair = generate_seeded_test_key_pair("Release Key", 1);
+        let engine = Arc::new(MockPolicy::default());
+        let storage = test_state(&dir, Arc::new(MockClock::new(Utc::now()))).storage.clone();
+        storage.store_key(key_pair.clone()).await.unwrap();
+        let with_policy = |fail_open: bool| {
+            let config = SignPolicyConfig { timeout_ms: 50, fail_open, ..SignPolicyConfig::default() };
+            Arc::new(AppState {
+                storage: storage.clone(),
+                sign_policy: Arc::new(SignPolicy::new(engine.clone(), &config)),
+                ..Arc::into_inner(test_state(&dir, Arc::new(MockClock::new(Utc::now())))).unwrap()
+            })
+        };
+        let state = with_policy(false);
+        let sign = |state: Arc<AppState>, document_hash: &str| sign_document_with_query(
+            State(state),
+            Query(SignQuery::default()),
+            HeaderMap::new(),
+            Some(Extension(AuthenticatedClient("billing".to_string()))),
+            Json(SignDocumentRequest { key_id: key_pair.id, document_hash: Some(document_hash.to_string()), ..Default::default() }),
+        );
+        let allowed = "a".repeat(64);
+        let denied = "0".repeat(64);
+        let stalled = "f".repeat(64);
+
+        // Allowed, then answered from the cache
+        assert!(sign(state.clone(), &allowed).await.unwrap().0.success);
+        assert!(sign(state.clone(), &allowed).await.unwrap().0.success);
+        assert_eq!(*engine.requests.lock().unwrap(), [PolicyRequest {
+            key_id: key_pair.id,
+            key_fingerprint: public_key_to_fingerprint(&key_pair.public_key).ok(),
+            document_hash: allowed.clone(),
+            context: None,
+            requester: Some("billing".to_string()),
+        }]);
+
+        let (status, Json(refused)) = sign(state.clone(), &denied).await.unwrap_err();
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(refused.code, Some(ErrorCode::PolicyDenied));
+        assert_eq!(refused.message, "Signing policy denied: release freeze");
+
+        // A policy service that does not answer refuses the signature unless configured to fail open
+        let (status, Json(timed_out)) = sign(state.clone(), &stalled).await.unwrap_err();
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(timed_out.code, Some(ErrorCode::PolicyDenied));
+        assert!(sign(with_policy(true), &stalled).await.unwrap().0.success);
+        assert_eq!(sign(with_policy(true), &denied).await.unwrap_err().0, StatusCode::FORBIDDEN);
+        // The second signature of `allowed` is the one already issued and is not counted again
+        assert_eq!(storage.get_key_record(key_pair.id).await.unwrap().usage.sign_count, 2);
+    }
+
+    #[tokio::test]
+    async fn test_wrapped_key_moves_between_instances() {
+        use crate::config::{KdfParams, MIN_PBKDF2_ITERATIONS};
+
+        let clock = Arc::new(MockClock::new(Utc::now()));
+        let (source_dir, destination_dir, other_dir) = (tempdir().unwrap(), tempdir().unwrap(), tempdir().unwrap());
+        let source = test_state(&source_dir, clock.clone());
+        let destination = test_state(&destination_dir, clock.clone());
+        let other = test_state(&other_dir, clock.clone());
+        let key_pair = crate::key_generation::generate_key_pair_with_kdf(GenerateKeyRequest {
+            name: "Release Key".to_string(),
+            password: Some("hunter22".to_string()),
+            tags: Some(vec!["env:prod".to_string()]),
+            ..Default::default()
+        }, &KdfParams::pbkdf2(MIN_PBKDF2_ITERATIONS)).unwrap();
+        source.storage.store_key(key_pair.clone()).await.unwrap();
+
+        let export = |password: Option<&str>| export_wrapped_key(State(source.clone()), Path(key_pair.id), Json(ExportWrappedKeyRequest {
+            transport_public_key: destination.transport_key.public_key(),
+            password: password.map(str::to_string),
+        }));
+        assert_eq!(export(Some("wrong-password")).await.unwrap_err().0, StatusCode::UNAUTHORIZED);
+        let envelope = export(Some("hunter22")).await.unwrap().0.envelope.unwrap();
+        assert!(!serde_json::to_string(&envelope).unwrap().contains(key_pair.private_key.expose_for_persistence()));
+
+        let import = |state: Arc<AppState>, password: Option<&str>| import_wrapped_key(State(state), Json(ImportWrappedKeyRequest {
+            envelope: envelope.clone(),
+            password: password.map(str::to_string),
+        }));
+        // Only the instance the key was wrapped for can open it, and a protected key stays protected
+        assert!(import(other.clone(), Some("hunter22")).await.is_err());
+        assert_eq!(import(destination.clone(), None).await.unwrap_err().0, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let key_info = import(destination.clone(), Some("correct horse")).await.unwrap().0.key_info.unwrap();
+        assert_eq!(key_info.id, key_pair.id);
+        assert_eq!(key_info.public_key, key_pair.public_key);
+        let imported = destination.storage.get_key(key_pair.id).await.unwrap();
+        assert_eq!(imported.fingerprint, key_pair.fingerprint);
+        assert_eq!(imported.created_at, key_pair.created_at);
+        assert_eq!(imported.tags, key_pair.tags);
+
+        // Both instances now make the same signatures with the same key
+        let sign = |state: Arc<AppState>, password: &str| sign_document_with_query(State(state), Query(SignQuery::default()), HeaderMap::new(), None, Json(SignDocumentRequest {
+            key_id: key_pair.id,
+            document_hash: Some("a".repeat(64)),
+            password: Some(password.to_string()),
+            ..Default::default()
+        }));
+        let at_source = sign(source.clone(), "hunter22").await.unwrap().0.signature.unwrap();
+        let at_destination = sign(destination.clone(), "correct horse").await.unwrap().0.signature.unwrap();
+        assert_eq!(at_source, at_destination);
+
+        let (status, Json(conflict)) = import(destination.clone(), Some("correct horse")).await.unwrap_err();
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(conflict.code, Some(ErrorCode::KeyConflict));
+        assert_eq!(destination.storage.key_count().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_signature_encodings_verify_interchangeably() {
+        use crate::key_verification::{decode_signature, encode_signature};
+
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir, Arc::new(MockClock::new(Utc::now())));
+        let key_pair = generate_seeded_test_key_pair("Release Key", 1);
+        state.storage.store_key(key_pair.clone()).await.unwrap();
+        let document_hash = create_document_hash("release 1.4.0");
+        let sign = |encoding: SignatureEncoding| sign_document(State(state.clone()), Json(SignDocumentRequest {
+            key_id: key_pair.id,
+            document_hash: Some(document_hash.clone()),
+            encoding: Some(encoding),
+            ..Default::default()
+        }));
+        let verify = |signature: String, signature_encoding: Option<SignatureEncoding>| verify_signature(State(state.clone()), Json(VerifySignatureRequest {
+            public_key: key_pair.public_key.clone(),
+            document_hash: Some(document_hash.clone()),
+            signature,
+            signature_encoding,
+            ..Default::default()
+        }));
+
+        let base64 = sign(SignatureEncoding::Base64).await.unwrap().0;
+        let base64url = sign(SignatureEncoding::Base64Url).await.unwrap().0;
+        let hex = sign(SignatureEncoding::Hex).await.unwrap().0;
+        assert_eq!(hex.signature_encoding, SignatureEncoding::Hex);
+        let bytes = decode_signature(base64.signature.as_deref().unwrap(), Some(SignatureEncoding::Base64)).unwrap();
+        assert_eq!(base64url.signature.clone().unwrap(), encode_signature(&bytes, SignatureEncoding::Base64Url));
+        assert_eq!(hex.signature.clone().unwrap(), encode_signature(&bytes, SignatureEncoding::Hex));
+
+        // Receipts hold the same standard base64 signature whichever encoding was returned
+        for signed in [&base64, &base64url, &hex] {
+            let bundle = state.receipts.get(signed.signature_id.unwrap()).await.unwrap();
+            assert_eq!(bundle.body.signature, base64.signature.clone().unwrap());
+        }
+
+        for (signed, encoding) in [(&base64, SignatureEncoding::Base64), (&base64url, SignatureEncoding::Base64Url), (&hex, SignatureEncoding::Hex)] {
+            assert!(verify(signed.signature.clone().unwrap(), None).await.unwrap().0.is_valid);
+            assert!(verify(signed.signature.clone().unwrap(), Some(encoding)).await.unwrap().0.is_valid);
+        }
+
+        // A base64url signature converted to hex verifies; the hex of different bytes does not
+        let url_bytes = decode_signature(base64url.signature.as_deref().unwrap(), Some(SignatureEncoding::Base64Url)).unwrap();
+        assert!(verify(encode_signature(&url_bytes, SignatureEncoding::Hex), Some(SignatureEncoding::Hex)).await.unwrap().0.is_valid);
+        let mut altered = url_bytes;
+        altered[10] ^= 1;
+        assert!(!verify(encode_signature(&altered, SignatureEncoding::Hex), Some(SignatureEncoding::Hex)).await.unwrap().0.is_valid);
+        let mislabeled = verify(base64url.signature.clone().unwrap(), Some(SignatureEncoding::Hex)).await.unwrap().0;
+        assert!(!mislabeled.is_valid);
+        assert_eq!(mislabeled.code, Some(ErrorCode::InvalidSignatureFormat));
+
+        let (status, _) = sign_document(State(state.clone()), Json(SignDocumentRequest {
+            key_id: key_pair.id,
+            document_hash: Some(document_hash.clone()),
+            output_format: Some(SignatureOutputFormat::Minisign),
+            encoding: Some(SignatureEncoding::Hex),
+            ..Default::default()
+        })).await.unwrap_err();
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn test_admin_overview_gathers_every_section() {
+        let dir = tempdir().unwrap();
+        let now = Utc::now();
+        let clock = Arc::new(MockClock::new(now));
+        let clients = [("ops", "s3cret"), ("billing", "s3cret")].into_iter().map(|(id, secret)| (id.to_string(), secret.to_string())).collect();
+        let state = Arc::new(AppState {
+            request_auth: RequestAuthenticator::new(clients, Duration::seconds(300)),
+            config: Arc::new(Config { admin_clients: ["ops".to_string()].into_iter().collect(), ..Config::default() }),
+            ..Arc::into_inner(test_state(&dir, clock.clone())).unwrap()
+        });
+        let soon = KeyPair { expires_at: Some(now + Duration::days(5)), ..generate_seeded_test_key_pair("Soon", 1) };
+        let later = KeyPair { expires_at: Some(now + Duration::days(20)), ..generate_seeded_test_key_pair("Later", 2) };
+        let distant = KeyPair { expires_at: Some(now + Duration::days(90)), ..generate_seeded_test_key_pair("Distant", 3) };
+        for key_pair in [&later, &soon, &distant] {
+            state.storage.store_key(key_pair.clone()).await.unwrap();
+        }
+        let signed = sign_document(State(state.clone()), Json(SignDocumentRequest {
+            key_id: distant.id,
+            document_hash: Some(create_document_hash("release 1.4.0")),
+            context: Some("release".to_string()),
+            ..Default::default()
+        })).await.unwrap().0;
+        state.sweeper.record(now, &Ok::<(), KeyManagementError>(()));
+
+        let response = admin_overview(State(state.clone())).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "no-store");
+        let body: serde_json::Value = serde_json::from_slice(&axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(body["success"], true);
+        assert_eq!(body["stats"]["total_keys"], 3);
+        assert_eq!(body["stats"]["keys_expiring_soon"], 2);
+        assert_eq!(body["stats"]["total_sign_count"], 1);
+        let expiring: Vec<&str> = body["expiring_soon"].as_array().unwrap().iter().map(|key| key["name"].as_str().unwrap()).collect();
+        assert_eq!(expiring, ["Soon", "Later"]);
+        assert_eq!(body["recent_signatures"][0]["signature_id"], signed.signature_id.unwrap().to_string());
+        assert_eq!(body["recent_signatures"][0]["context"], "release");
+        assert_eq!(body["background"]["sweeper"]["succeeded"], true);
+        assert_eq!(body["service"]["read_only"], false);
+        assert_eq!(body["build"]["version"], env!("CARGO_PKG_VERSION"));
+    }
+
+    #[tokio::test]
+    async fn test_usage_report_aggregates_by_owner_and_exports_csv() {
+        let dir = tempdir().unwrap();
+        let now = Utc::now();
+        let clock = Arc::new(MockClock::new(now));
+        let clients = [("ops", "s3cret"), ("billing", "s3cret")].into_iter().map(|(id, secret)| (id.to_string(), secret.to_string())).collect();
+        let state = Arc::new(AppState {
+            request_auth: RequestAuthenticator::new(clients, Duration::seconds(300)),
+            config: Arc::new(Config { admin_clients: ["ops".to_string()].into_iter().collect(), ..Config::default() }),
+            ..Arc::into_inner(test_state(&dir, clock.clone())).unwrap()
+        });
+        let owned = |name, owner: &str, seed| KeyPair { tags: vec![format!("owner:{}", owner)], ..generate_seeded_test_key_pair(name, seed) };
+        let (release, web, retired) = (owned("Release", "release", 1), owned("Web", "web", 2), owned("Retired", "web", 3));
+        for key_pair in [&release, &web, &retired] {
+            state.storage.store_key(key_pair.clone()).await.unwrap();
+        }
+        let sign = |key_id, document: &str| sign_document(State(state.clone()), Json(SignDocumentRequest {
+            key_id,
+            document_content: Some(document.to_string()),
+            ..Default::default()
+        }));
+        let signed = sign(release.id, "one").await.unwrap().0;
+        assert!(sign(release.id, "two").await.unwrap().success);
+        assert!(sign(web.id, "three").await.unwrap().success);
+        let verified = verify_signature(State(state.clone()), Json(VerifySignatureRequest {
+            key_id: Some(release.id),
+            document_content: Some("one".to_string()),
+            signature: signed.signature.unwrap(),
+            ..Default::default()
+        })).await.unwrap().0;
+        assert!(verified.is_valid);
+        let revoke = RevokeKeyRequest { key_id: web.id, reason: None, immediate: true, effective_at: None };
+        assert!(revoke_key(State(state.clone()), Path(web.id), Json(revoke), None).await.unwrap().success);
+        // A deleted key's usage is still reported
+        state.storage.soft_delete_key(retired.id, None).await.unwrap();
+
+        let today = now.date_naive();
+        let report = |group_by, accept: &str| usage_report(
+            State(state.clone()),
+            Query(UsageReportQuery { from: Some(today), to: Some(today), group_by }),
+            [(header::ACCEPT, header::HeaderValue::from_str(accept).unwrap())].into_iter().collect(),
+        );
+
+        let response = report(GroupBy::Owner, "application/json").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(body["rows"], serde_json::json!([
+            { "group": "release", "generations": 1, "signs": 2, "verifies": 1, "revocations": 0 },
+            { "group": "web", "generations": 2, "signs": 1, "verifies": 0, "revocations": 1 },
+        ]));
+        assert_eq!(body["totals"]["signs"], 3);
+
+        let response = report(GroupBy::Owner, "text/html, text/csv;q=0.9").await;
+        assert_eq!(response.headers()[header::CONTENT_TYPE], CSV_CONTENT_TYPE);
+        let csv = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&csv),
+            "owner,generations,signs,verifies,revocations\r\nrelease,1,2,1,0\r\nweb,2,1,0,1\r\ntotal,3,3,1,1\r\n",
+        );
+
+        // Reports are cached briefly, then recomputed
+        assert!(sign(release.id, "four").await.unwrap().success);
+        let signs = |response: Response| async move {
+            let body: serde_json::Value = serde_json::from_slice(&axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+            body["totals"]["signs"].clone()
+        };
+        assert_eq!(signs(report(GroupBy::Owner, "application/json").await).await, 3);
+        clock.advance(Duration::seconds(crate::usage_report::REPORT_CACHE_TTL_SECS));
+        assert_eq!(signs(report(GroupBy::Owner, "application/json").await).await, 4);
+
+        let too_long = usage_report(
+            State(state.clone()),
+            Query(UsageReportQuery { from: Some(today - Duration::days(400)), to: Some(today), group_by: GroupBy::Key }),
+            HeaderMap::new(),
+        ).await;
+        assert_eq!(too_long.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    /// The whole key lifecycle driven through the typed client against the production router
+    #[cfg(feature = "client")]
+    #[tokio::test]
+    async fn test_client_drives_the_key_lifecycle() {
+        use crate::client::{ClientAuth, ClientConfig, ClientError, InkanClient};
+
+        let dir = tempdir().unwrap();
+        let secrets = std::collections::BTreeMap::from([("billing".to_string(), "billing-secret".to_string())]);
+        let state = Arc::new(AppState {
+            request_auth: RequestAuthenticator::new(secrets, Duration::minutes(5)),
+            config: Arc::new(Config { read_clients: ["billing".to_string()].into_iter().collect(), ..Config::default() }),
+            ..Arc::into_inner(test_state(&dir, Arc::new(MockClock::new(Utc::now())))).unwrap()
+        });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let app = crate::routes::router(state.clone()).into_make_service_with_connect_info::<std::net::SocketAddr>();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let auth = ClientAuth::Hmac { client_id: "billing".to_string(), secret: "billing-secret".to_string() };
+        let client = InkanClient::new(ClientConfig { auth: Some(auth), ..ClientConfig::new(&base_url) }).unwrap();
+        assert!(client.health().await.unwrap());
+
+        // Unsigned requests are refused once request signing is configured
+        let anonymous = InkanClient::new(ClientConfig::new(&base_url)).unwrap();
+        let refused = anonymous.key_stats().await.unwrap_err();
+        assert_eq!(refused.code(), Some(ErrorCode::InvalidRequestSignature));
+
+        // Validate, then generate
+        let generate = GenerateKeyRequest {
+            name: "Invoices 2026".to_string(),
+            description: Some("Signs outgoing invoices".to_string()),
+            tags: Some(vec!["billing".to_string()]),
+            ..Default::default()
+        };
+        let dry_run = client.validate_generate(&generate).await.unwrap();
+        assert!(dry_run.dry_run && dry_run.key_pair.is_none());
+        let key_id = client.generate_key(&generate).await.unwrap().key_pair.unwrap().id;
+        let duplicate = client.generate_key(&generate).await.unwrap_err();
+        assert_eq!(duplicate.code(), Some(ErrorCode::ValidationFailed));
+
+        // Find it
+        let query = ListKeysQuery { active_only: None, key_type: None, tags: Some("BILLING".to_string()), search: None, environment: None, offset: 0, limit: None, cursor: None };
+        assert_eq!(client.list_keys(&query).await.unwrap().keys[0].id, key_id);
+        let search = ListKeysQuery { tags: None, search: Some("invoices".to_string()), ..query };
+        assert_eq!(client.search_keys(&search).await.unwrap().matched_count, 1);
+        assert_eq!(client.get_key(key_id).await.unwrap().key_info.unwrap().name, "Invoices 2026");
+        let update = UpdateKeyRequest { description: Some("Signs invoices and credit notes".to_string()), ..Default::default() };
+        let updated = client.update_key(key_id, &update).await.unwrap();
+        assert_eq!(updated.key_info.unwrap().description.as_deref(), Some("Signs invoices and credit notes"));
+
+        // Sign, verify, and look the signature up
+        let signed = client.sign(&SignDocumentRequest {
+            key_id,
+            document_content: Some("invoice #1042".to_string()),
+            ..Default::default()
+        }).await.unwrap();
+        let signature = signed.signature.unwrap();
+        let verified = client.verify(&VerifySignatureRequest {
+            key_id: Some(key_id),
+            document_content: Some("invoice #1042".to_string()),
+            signature: signature.clone(),
+            ..Default::default()
+        }).await.unwrap();
+        assert!(verified.is_valid);
+        let record = client.signature_record(signed.signature_id.unwrap()).await.unwrap();
+        assert_eq!((record.record.key_id, record.record.signature), (key_id, signature));
+        assert_eq!(client.key_stats().await.unwrap().total_sign_count, 1);
+
+        // Schedule a revocation and take it back, then revoke for good
+        let revoke = |immediate, effective_at| RevokeKeyRequest { key_id, reason: None, immediate, effective_at };
+        let scheduled = client.revoke_key(&revoke(false, Some(state.clock.now() + Duration::days(7)))).await.unwrap();
+        assert!(scheduled.scheduled);
+        assert!(client.cancel_scheduled_revocation(key_id).await.unwrap().success);
+        assert!(!client.revoke_key(&revoke(true, None)).await.unwrap().scheduled);
+        let after_revocation = client.sign(&SignDocumentRequest {
+            key_id,
+            document_content: Some("invoice #1043".to_string()),
+            ..Default::default()
+        }).await.unwrap_err();
+        // The client has read scope, so it learns why the key cannot be used
+        assert!(matches!(after_revocation, ClientError::Api { code: Some(ErrorCode::KeyRevoked), .. }), "{:?}", after_revocation);
+
+        // Soft-delete and restore
+        assert!(client.delete_key(key_id).await.unwrap().deleted_at.is_some());
+        assert_eq!(client.get_key(key_id).await.unwrap_err().code(), Some(ErrorCode::KeyNotFound));
+        assert_eq!(client.restore_key(key_id).await.unwrap().key_info.unwrap().id, key_id);
+    }
+
+    #[tokio::test]
+    async fn test_requests_outlasting_their_deadline_report_progress() {
+        use axum::body::Body;
+        use axum::routing::post;
+        use std::sync::atomic::AtomicUsize;
+        use tower::ServiceExt;
+
+        let dir = tempdir().unwrap();
+        let state = Arc::new(AppState {
+            deadlines: RequestDeadlines::new(std::time::Duration::from_millis(100), std::time::Duration::from_secs(5)),
+            ..Arc::into_inner(test_state(&dir, Arc::new(MockClock::new(Utc::now())))).unwrap()
+        });
+
+        // A batch over a slow store, worked through in a blocking task the timeout cannot drop
+        let processed = Arc::new(AtomicUsize::new(0));
+        let slow_batch = {
+            let processed = processed.clone();
+            move |Extension(deadline): Extension<Deadline>| async move {
+                let work = tokio::task::spawn_blocking(move || {
+                    for item in 0..200 {
+                        deadline.check(item, 200)?;
+                        std::thread::sleep(std::time::Duration::from_millis(10));
+                        processed.fetch_add(1, Ordering::SeqCst);
+                    }
+                    Ok::<_, KeyManagementError>(())
+                });
+                match work.await {
+                    Ok(Err(e)) => key_error_response(StatusCode::GATEWAY_TIMEOUT, e.to_string(), &e),
+                    _ => StatusCode::OK.into_response(),
+                }
+            }
+        };
+        let app = axum::Router::new()
+            .route("/batch", post(slow_batch))
+            .layer(axum::middleware::from_fn_with_state(state.clone(), deadline_layer))
+            .with_state(state.clone());
+
+        let request = axum::http::Request::builder().method(Method::POST).uri("/batch").body(Body::empty()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+        let body: serde_json::Value = serde_json::from_slice(&axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(body["code"], "DEADLINE_EXCEEDED");
+        assert_eq!(body["details"]["total"], 200);
+        let completed = body["details"]["completed"].as_u64().unwrap();
+        assert!((1..20).contains(&completed), "{}", body);
+
+        // The blocking work stops at its next check instead of running the batch to the end
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        let stopped_at = processed.load(Ordering::SeqCst);
+        assert!(stopped_at < 30, "{}", stopped_at);
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert_eq!(processed.load(Ordering::SeqCst), stopped_at);
+
+        // Keystore validation stops part way through a slow store
+        for index in 0..6 {
+            state.storage.store_key(generate_test_key_pair(&format!("Key {}", index)).unwrap()).await.unwrap();
+        }
+        let deadline = Deadline::after(std::time::Duration::from_millis(50));
+        let result = crate::integrity::validate_keystore(&state.storage, false, &deadline, |_, _, _| {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }).await;
+        let Err(KeyManagementError::DeadlineExceeded { completed, total: 6 }) = result else {
+            panic!("validation finished: {:?}", result.map(|report| report.checked));
+        };
+        assert!((1..6).contains(&completed), "{}", completed);
+
+        let metrics = metrics(State(state)).await;
+        let text = String::from_utf8(axum::body::to_bytes(metrics.into_body(), usize::MAX).await.unwrap().to_vec()).unwrap();
+        assert!(text.contains("inkan_request_timeouts_total{version=\"unversioned\",route=\"/batch\"} 1"), "{}", text);
+    }
+
+    #[tokio::test]
+    async fn test_unprefixed_paths_alias_v1_and_v2_differs_only_in_shape() {
+        use axum::body::Body;
+        use tower::ServiceExt;
+
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir, Arc::new(MockClock::new(Utc::now())));
+        let key_pair = generate_test_key_pair("Versioned").unwrap();
+        state.storage.store_key(key_pair.clone()).await.unwrap();
+        let call = |app: axum::Router, path: String, field_case: Option<&'static str>| async move {
+            let mut request = axum::http::Request::builder().uri(path);
+            if let Some(field_case) = field_case {
+                request = request.header(FIELD_CASE_HEADER, field_case);
+            }
+            let response = app.oneshot(request.body(Body::empty()).unwrap()).await.unwrap();
+            let (parts, body) = response.into_parts();
+            let body = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+            (parts.status, parts.headers, serde_json::from_slice::<serde_json::Value>(&body).unwrap_or_default())
+        };
+        let app = crate::routes::router_with_versions(state.clone(), ApiVersion::ALL);
+
+        // The unprefixed path answers as /v1 does, flagged as deprecated
+        let (status, headers, v1) = call(app.clone(), format!("/v1/keys/{}", key_pair.id), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(v1["key_info"]["id"], key_pair.id.to_string());
+        assert!(headers.get("deprecation").is_none() && headers.get("sunset").is_none());
+        let (status, headers, mut legacy) = call(app.clone(), format!("/keys/{}", key_pair.id), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers["deprecation"], "@1792108800");
+        assert_eq!(headers["sunset"], "Fri, 30 Apr 2027 00:00:00 GMT");
+        assert_eq!(headers[header::LINK], format!("</v1/keys/{}>; rel=\"successor-version\"", key_pair.id).as_str());
+        let deprecation = legacy.as_object_mut().unwrap().remove("deprecation").unwrap();
+        assert_eq!(deprecation["code"], "DEPRECATED_PATH");
+        assert_eq!(deprecation["successor"], format!("/v1/keys/{}", key_pair.id));
+        assert_eq!(legacy, v1);
+        let (status, headers, _) = call(app.clone(), "/health".to_string(), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(headers.get("deprecation").is_none());
+
+        // v2 names fields in camelCase, and is otherwise the same
+        let (_, _, v2) = call(app.clone(), format!("/v2/keys/{}", key_pair.id), None).await;
+        assert_eq!(v2["keyInfo"]["id"], key_pair.id.to_string());
+        assert!(v2.get("key_info").is_none());
+        let (_, _, v2_snake) = call(app.clone(), format!("/v2/keys/{}", key_pair.id), Some("snake")).await;
+        assert_eq!(v2_snake, v1);
+
+        // A missing key is a failure with its status on both versions
+        let missing = Uuid::new_v4();
+        let (status, _, v1_missing) = call(app.clone(), format!("/v1/keys/{}", missing), None).await;
+        assert_eq!((status, &v1_missing["code"]), (StatusCode::NOT_FOUND, &serde_json::json!("KEY_NOT_FOUND")));
+        let (status, _, v2_missing) = call(app.clone(), format!("/v2/keys/{}", missing), Some("snake")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(v2_missing, v1_missing);
+
+        // Under the legacy envelope, v2 answers a failure with its status where v1 keeps 200
+        let closed = Utc::now() - Duration::hours(1);
+        let sign = |path: &str| {
+            let body = serde_json::json!({ "key_id": key_pair.id, "document_content": "invoice", "valid_until": closed });
+            axum::http::Request::builder().method(Method::POST).uri(path)
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(FIELD_CASE_HEADER, "snake")
+                .body(Body::from(body.to_string())).unwrap()
+        };
+        let legacy_dir = tempdir().unwrap();
+        let legacy_state = Arc::new(AppState {
+            config: Arc::new(Config { legacy_envelope: true, ..(*state.config).clone() }),
+            ..Arc::into_inner(test_state(&legacy_dir, Arc::new(MockClock::new(Utc::now())))).unwrap()
+        });
+        legacy_state.storage.store_key(key_pair.clone()).await.unwrap();
+        let legacy_app = crate::routes::router_with_versions(legacy_state, ApiVersion::ALL);
+        let (status, _, legacy_missing) = call(legacy_app.clone(), format!("/v1/keys/{}", missing), None).await;
+        assert_eq!((status, &legacy_missing["code"]), (StatusCode::OK, &serde_json::json!("KEY_NOT_FOUND")));
+        let (status, _, _) = call(legacy_app.clone(), format!("/v2/keys/{}", missing), None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let v1_refused = legacy_app.clone().oneshot(sign("/v1/sign")).await.unwrap();
+        let v2_refused = legacy_app.oneshot(sign("/v2/sign")).await.unwrap();
+        assert_eq!((v1_refused.status(), v2_refused.status()), (StatusCode::OK, StatusCode::UNPROCESSABLE_ENTITY));
+        let v1_refused = axum::body::to_bytes(v1_refused.into_body(), usize::MAX).await.unwrap();
+        let v2_refused = axum::body::to_bytes(v2_refused.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8_lossy(&v1_refused).contains("VALIDATION_FAILED"));
+        assert_eq!(v1_refused, v2_refused);
+
+        assert_eq!(state.api_usage.snapshot(), [("unversioned", 1), ("v1", 2), ("v2", 3)]);
+        let metrics = metrics(State(state.clone())).await;
+        let text = String::from_utf8(axum::body::to_bytes(metrics.into_body(), usize::MAX).await.unwrap().to_vec()).unwrap();
+        assert!(text.contains("inkan_api_requests_total{version=\"v2\"} 3"), "{}", text);
+
+        // v2 is only served when enabled
+        let v1_only = crate::routes::router_with_versions(state.clone(), &[ApiVersion::V1]);
+        let (status, _, _) = call(v1_only.clone(), "/v2/keys/stats".to_string(), None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _, _) = call(v1_only, "/v1/keys/stats".to_string(), None).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_admin_routes_require_admin_client() {
+        use axum::body::Body;
+        use tower::ServiceExt;
+
+        let dir = tempdir().unwrap();
+        let clock = Arc::new(MockClock::new(Utc::now()));
+        let secrets = std::collections::BTreeMap::from([
+            ("ops".to_string(), "ops-secret".to_string()),
+            ("billing".to_string(), "billing-secret".to_string()),
+        ]);
+        let state = Arc::new(AppState {
+            request_auth: RequestAuthenticator::new(secrets, Duration::minutes(5)),
+            config: Arc::new(Config {
+                admin_clients: ["ops".to_string()].into_iter().collect(),
+                read_clients: ["billing".to_string()].into_iter().collect(),
+                ..Config::default()
+            }),
+            ..Arc::into_inner(test_state(&dir, clock.clone())).unwrap()
+        });
+        let app = crate::routes::router_with_versions(state.clone(), ApiVersion::ALL);
+        let call = |client: &'static str, method: Method, path: String| {
+            let app = app.clone();
+            let timestamp = clock.now().timestamp();
+            async move {
+                let body = if method == Method::GET { "" } else { "{}" };
+                let secret = format!("{}-secret", client);
+                let signature = crate::utils::sign_request(secret.as_bytes(), method.as_str(), &path, timestamp, body.as_bytes());
+                let request = axum::http::Request::builder().method(method).uri(path)
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .header(CLIENT_ID_HEADER, client)
+                    .header(TIMESTAMP_HEADER, timestamp.to_string())
+                    .header(SIGNATURE_HEADER, signature)
+                    .body(Body::from(body)).unwrap();
+                let response = app.oneshot(request).await.unwrap();
+                let status = response.status();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                (status, serde_json::from_slice::<serde_json::Value>(&body).unwrap_or_default())
+            }
+        };
+
+        // Every admin route refuses a client with only read scope, before its handler runs
+        let admin_routes: Vec<_> = crate::routes::endpoint_list().iter().filter(|endpoint| is_admin_path(&endpoint.path)).collect();
+        assert!(admin_routes.iter().any(|endpoint| endpoint.path == "/admin/validate"));
+        assert!(admin_routes.iter().any(|endpoint| endpoint.path == "/events/replay"));
+        for endpoint in &admin_routes {
+            let method: Method = endpoint.method.parse().unwrap();
+            let (status, body) = call("billing", method, format!("/v1{}", endpoint.path)).await;
+            assert_eq!((status, &body["code"]), (StatusCode::FORBIDDEN, &serde_json::json!("INSUFFICIENT_PERMISSIONS")), "{}", endpoint.path);
+        }
+        assert!(!state.read_only.load(Ordering::SeqCst));
+
+        // Admin clients reach the handlers
+        for path in ["/v1/admin/slo", "/v1/admin/overview", "/v1/admin/kdf-report", "/v1/events/replay?after_seq=0"] {
+            let (status, body) = call("ops", Method::GET, path.to_string()).await;
+            assert_eq!(status, StatusCode::OK, "{}: {}", path, body);
+        }
+        let (status, _) = call("billing", Method::GET, "/v1/keys/stats".to_string()).await;
+        assert_eq!(status, StatusCode::OK);
+
+        // Without request signing every caller may use them
+        let open_dir = tempdir().unwrap();
+        let open = crate::routes::router_with_versions(test_state(&open_dir, clock.clone()), ApiVersion::ALL);
+        let request = axum::http::Request::builder().uri("/v1/admin/slo").body(Body::empty()).unwrap();
+        assert_eq!(open.oneshot(request).await.unwrap().status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_key_state_is_concealed_from_callers_without_read_scope() {
+        use axum::body::Body;
+        use tower::ServiceExt;
+
+        let dir = tempdir().unwrap();
+        let clock = Arc::new(MockClock::new(Utc::now()));
+        let secrets = std::collections::BTreeMap::from([
+            ("billing".to_string(), "billing-secret".to_string()),
+            ("audit".to_string(), "audit-secret".to_string()),
+        ]);
+        let state = Arc::new(AppState {
+            request_auth: RequestAuthenticator::new(secrets, Duration::minutes(5)),
+            config: Arc::new(Config { read_clients: ["audit".to_string()].into_iter().collect(), ..Config::default() }),
+            ..Arc::into_inner(test_state(&dir, clock.clone())).unwrap()
+        });
+        let revoked = generate_test_key_pair("Revoked").unwrap();
+        state.storage.store_key(revoked.clone()).await.unwrap();
+        state.storage.revoke_key(revoked.id, None).await.unwrap();
+        let mut expired = generate_test_key_pair("Expired").unwrap();
+        expired.expires_at = Some(clock.now() - Duration::days(1));
+        state.storage.store_key(expired.clone()).await.unwrap();
+        let active = generate_test_key_pair("Active").unwrap();
+        state.storage.store_key(active.clone()).await.unwrap();
+        let app = crate::routes::router_with_versions(state.clone(), ApiVersion::ALL);
+
+        let call = |client: &'static str, method: Method, path: String, body: serde_json::Value| {
+            let app = app.clone();
+            let timestamp = clock.now().timestamp();
+            async move {
+                let body = if body.is_null() { String::new() } else { body.to_string() };
+                let secret = format!("{}-secret", client);
+                let signature = crate::utils::sign_request(secret.as_bytes(), method.as_str(), &path, timestamp, body.as_bytes());
+                let request = axum::http::Request::builder().method(method).uri(path)
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .header(CLIENT_ID_HEADER, client)
+                    .header(TIMESTAMP_HEADER, timestamp.to_string())
+                    .header(SIGNATURE_HEADER, signature)
+                    .body(Body::from(body)).unwrap();
+                let started = std::time::Instant::now();
+                let response = app.oneshot(request).await.unwrap();
+                let status = response.status();
+                let body: serde_json::Value = serde_json::from_slice(&axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+                (status, body, started.elapsed())
+            }
+        };
+        let requests = |key_id: Uuid| [
+            (Method::GET, format!("/v1/keys/{}", key_id), serde_json::Value::Null),
+            (Method::PUT, format!("/v1/keys/{}", key_id), serde_json::json!({ "name": "Renamed" })),
+            (Method::POST, format!("/v1/keys/{}/revoke", key_id), serde_json::json!({ "key_id": key_id, "immediate": true })),
+            (Method::POST, "/v1/sign".to_string(), serde_json::json!({ "key_id": key_id, "document_content": "invoice" })),
+            (Method::DELETE, format!("/v1/keys/{}", key_id), serde_json::Value::Null),
+        ];
+
+        // Missing, revoked and expired keys get the same answer on every route
+        let missing = Uuid::new_v4();
+        for index in 0..5 {
+            let mut answers = Vec::new();
+            for key_id in [missing, revoked.id, expired.id] {
+                let (method, path, body) = requests(key_id)[index].clone();
+                let (status, body, elapsed) = call("billing", method, path, body).await;
+                assert!(elapsed >= key_disclosure::CONCEALED_FAILURE_FLOOR, "{:?}", elapsed);
+                answers.push((status, body));
+            }
+            assert_eq!(answers[0], (StatusCode::NOT_FOUND, serde_json::json!({
+                "success": false, "code": "KEY_NOT_FOUND", "message": "Key not found",
+            })));
+            assert!(answers.iter().all(|answer| *answer == answers[0]), "{:?}", answers);
+        }
+        // Nothing was changed behind the concealed answers
+        assert_eq!(state.storage.get_key_record(revoked.id).await.unwrap().name, "Revoked");
+        assert_eq!(state.storage.get_key_record(expired.id).await.unwrap().state(clock.now()), KeyState::Expired);
+
+        // Listings show only the usable key, and successful answers are held to the floor as well
+        let listed_ids = |body: &serde_json::Value| body["keys"].as_array().unwrap().iter()
+            .map(|key| key["id"].as_str().unwrap().parse::<Uuid>().unwrap())
+            .collect::<Vec<_>>();
+        for path in ["/v1/keys", "/v1/keys/search?search=e"] {
+            let (status, body, elapsed) = call("billing", Method::GET, path.to_string(), serde_json::Value::Null).await;
+            assert_eq!((status, listed_ids(&body)), (StatusCode::OK, vec![active.id]), "{}", path);
+            assert_eq!((&body["total_count"], &body["expired_count"]), (&serde_json::json!(1), &serde_json::json!(0)));
+            assert!(elapsed >= key_disclosure::CONCEALED_FAILURE_FLOOR, "{:?}", elapsed);
+        }
+        let (_, body, _) = call("billing", Method::GET, "/v1/keys?active_only=false".to_string(), serde_json::Value::Null).await;
+        assert_eq!((listed_ids(&body), &body["matched_count"]), (vec![], &serde_json::json!(0)));
+        let (status, _, elapsed) = call("billing", Method::GET, format!("/v1/keys/{}", active.id), serde_json::Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(elapsed >= key_disclosure::CONCEALED_FAILURE_FLOOR, "{:?}", elapsed);
+        let (_, body, _) = call("audit", Method::GET, "/v1/keys".to_string(), serde_json::Value::Null).await;
+        assert_eq!((listed_ids(&body).len(), &body["total_count"]), (3, &serde_json::json!(3)));
+
+        // A client with read scope learns why the key cannot be used
+        let (method, path, body) = requests(revoked.id)[3].clone();
+        let (status, body, _) = call("audit", method, path, body).await;
+        assert_eq!((status, &body["code"]), (StatusCode::GONE, &serde_json::json!("KEY_REVOKED")));
+        let (method, path, body) = requests(expired.id)[1].clone();
+        let (status, body, _) = call("audit", method, path, body).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["key_info"]["name"], "Renamed");
+    }
+
+    #[tokio::test]
+    async fn test_keys_sign_only_in_their_deployment_environment() {
+        let dir = tempdir().unwrap();
+        let clock = Arc::new(MockClock::new(Utc::now()));
+        let prod = Config { environment: Some("prod".to_string()), ..Config::default() };
+        let state = Arc::new(AppState {
+            config: Arc::new(prod.clone()),
+            ..Arc::into_inner(test_state(&dir, clock.clone())).unwrap()
+        });
+        let generate = |name: &str, environment: Option<&str>| generate_keys(State(state.clone()), Query(GenerateKeyQuery::default()), Json(GenerateKeyRequest {
+            name: name.to_string(),
+            environment: environment.map(str::to_string),
+            ..Default::default()
+        }));
+
+        // Generation defaults to the service environment and only accepts configured ones
+        let live = generate("Live", None).await.unwrap().0.key_pair.unwrap();
+        let rehearsal = generate("Rehearsal", Some(" staging ")).await.unwrap().0.key_pair.unwrap();
+        assert_eq!((live.environment.as_str(), rehearsal.environment.as_str()), ("prod", "staging"));
+        let (status, Json(refused)) = generate("Typo", Some("production")).await.unwrap_err();
+        assert_eq!((status, refused.errors[0].field.as_str()), (StatusCode::UNPROCESSABLE_ENTITY, "environment"));
+        let migrated = generate_test_key_pair("Migrated").unwrap();
+        assert_eq!(migrated.environment, crate::environment::UNKNOWN_ENVIRONMENT);
+        state.storage.store_key(migrated.clone()).await.unwrap();
+
+        let sign = |state: Arc<AppState>, key_id: Uuid| sign_document(State(state), Json(SignDocumentRequest {
+            key_id,
+            document_hash: Some(create_document_hash("release")),
+            ..Default::default()
+        }));
+        let codes = |signed: SignDocumentResponse| signed.warnings.iter().map(|warning| warning.code).collect::<Vec<_>>();
+        assert_eq!(codes(sign(state.clone(), live.id).await.unwrap().0), [WarningCode::UnencryptedPrivateKey]);
+        for key_id in [rehearsal.id, migrated.id] {
+            let (status, Json(rejected)) = sign(state.clone(), key_id).await.unwrap_err();
+            assert_eq!((status, rejected.code), (StatusCode::FORBIDDEN, Some(ErrorCode::EnvironmentMismatch)));
+            assert_eq!(rejected.details.unwrap()["service_environment"], "prod");
+        }
+
+        // Warn mode signs anyway and says so
+        let lenient = Arc::new(AppState {
+            config: Arc::new(Config { environment_mismatch: crate::environment::MismatchPolicy::Warn, ..prod }),
+            storage: state.storage.clone(),
+            ..Arc::into_inner(test_state(&dir, clock.clone())).unwrap()
+        });
+        let signed = sign(lenient, rehearsal.id).await.unwrap().0;
+        assert!(signed.success);
+        assert_eq!(codes(signed), [WarningCode::UnencryptedPrivateKey, WarningCode::EnvironmentMismatch]);
+
+        // Moving the migrated key into the service environment lets it sign
+        let moved = update_key(State(state.clone()), Path(migrated.id), Json(UpdateKeyRequest {
+            environment: Some("prod".to_string()),
+            ..Default::default()
+        }), None).await.unwrap().0;
+        assert_eq!(moved.key_info.unwrap().environment, "prod");
+        assert!(sign(state.clone(), migrated.id).await.is_ok());
+
+        let listed = |environment: &str| list_keys(State(state.clone()), Query(ListKeysQuery {
+            environment: Some(environment.to_string()),
+            ..Default::default()
+        }), None);
+        let mut names: Vec<String> = listed("prod").await.unwrap().0.keys.into_iter().map(|key| key.name).collect();
+        names.sort();
+        assert_eq!(names, ["Live", "Migrated"]);
+        assert_eq!(listed("staging").await.unwrap().0.keys.len(), 1);
+        assert!(listed("dev").await.unwrap().0.keys.is_empty());
+        let stats = get_key_stats(State(state.clone())).await.0;
+        assert_eq!(stats.keys_by_environment, BTreeMap::from([("prod".to_string(), 2), ("staging".to_string(), 1)]));
+    }
+
+    #[tokio::test]
+    async fn test_fast_generation_claims_pooled_keys_and_falls_back_when_empty() {
+        let dir = tempdir().unwrap();
+        let clock = Arc::new(MockClock::new(Utc::now()));
+        let state = Arc::new(AppState {
+            key_pool: Arc::new(KeyPool::new(2, 0, Duration::minutes(10))),
+            usage_reports: UsageReportCache::default(),
+            ..Arc::into_inner(test_state(&dir, clock.clone())).unwrap()
+        });
+        let generate = |name: &str, fast: bool| generate_keys(State(state.clone()), Query(GenerateKeyQuery::default()), Json(GenerateKeyRequest {
+            name: name.to_string(),
+            password: Some("correct horse battery".to_string()),
+            tags: Some(vec!["onboarding".to_string()]),
+            fast,
+            ..Default::default()
+        }));
+
+        // Nothing has been drawn yet, so the key is generated on the spot
+        let fallback = generate("Fallback", true).await.unwrap().0.key_pair.unwrap();
+        assert_eq!(state.key_pool.stats().misses, 1);
+
+        state.key_pool.fill(&state.entropy, clock.now()).unwrap();
+        let pooled = generate("Pooled", true).await.unwrap().0.key_pair.unwrap();
+        let regular = generate("Regular", false).await.unwrap().0.key_pair.unwrap();
+        assert_eq!((state.key_pool.stats().hits, state.key_pool.available()), (1, 1));
+        assert_eq!(state.storage.key_count().await, 3);
+
+        // Apart from what is unique to every key, a pooled key is stored like any other
+        let comparable = |key_info: &KeyInfo| {
+            let mut json = serde_json::to_value(key_info).unwrap();
+            for field in ["id", "name", "public_key", "created_at"] {
+                json[field] = serde_json::Value::Null;
+            }
+            json
+        };
+        assert_eq!(comparable(&pooled), comparable(&regular));
+        assert_eq!(comparable(&pooled), comparable(&fallback));
+        for key_info in [&pooled, &regular, &fallback] {
+            let stored = state.storage.get_key_record(key_info.id).await.unwrap();
+            assert_eq!((stored.kdf, stored.salt.is_none()), (Some(state.config.kdf), true));
+        }
+        let public_keys: std::collections::HashSet<_> = [&fallback, &pooled, &regular].iter().map(|key| key.public_key.clone()).collect();
+        assert_eq!(public_keys.len(), 3);
+
+        let signed = sign_document(State(state.clone()), Json(SignDocumentRequest {
+            key_id: pooled.id,
+            document_hash: Some(create_document_hash("welcome")),
+            password: Some("correct horse battery".to_string()),
+            ..Default::default()
+        })).await.unwrap().0;
+        let verified = verify_signature(State(state.clone()), Json(VerifySignatureRequest {
+            public_key: pooled.public_key.clone(),
+            signature: signed.signature.unwrap(),
+            document_hash: Some(create_document_hash("welcome")),
+            ..Default::default()
+        })).await.unwrap().0;
+        assert!(verified.is_valid);
+    }
+
+    #[tokio::test]
+    async fn test_no_api_response_carries_private_key_material() {
+        use axum::body::Body;
+        use tower::ServiceExt;
+
+        fn secret_fields(value: &serde_json::Value, path: &str, found: &mut Vec<String>) {
+            match value {
+                serde_json::Value::Object(fields) => for (name, field) in fields {
+                    if name == "private_key" || name == "salt" {
+                        found.push(format!("{}.{}", path, name));
+                    }
+                    secret_fields(field, &format!("{}.{}", path, name), found);
+                },
+                serde_json::Value::Array(items) => items.iter().for_each(|item| secret_fields(item, path, found)),
+                _ => {}
+            }
+        }
+
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir, Arc::new(MockClock::new(Utc::now())));
+        let legacy = crate::key_generation::generate_legacy_test_key_pair("hunter22");
+        state.storage.store_key(legacy.clone()).await.unwrap();
+        let app = crate::routes::router_with_versions(state.clone(), ApiVersion::ALL);
+        let call = |method: Method, path: String, body: Option<serde_json::Value>| {
+            let app = app.clone();
+            async move {
+                let request = axum::http::Request::builder().method(method).uri(format!("/v1{}", path))
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(body.map_or_else(Body::empty, |body| Body::from(body.to_string()))).unwrap();
+                let response = app.oneshot(request).await.unwrap();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                serde_json::from_slice::<serde_json::Value>(&body).unwrap_or_default()
+            }
+        };
+
+        let plain = call(Method::POST, "/keys/generate".to_string(), Some(serde_json::json!({ "name": "Plain" }))).await;
+        let encrypted = call(Method::POST, "/keys/generate".to_string(), Some(serde_json::json!({ "name": "Encrypted", "password": "hunter22" }))).await;
+        assert_eq!(plain["success"], true);
+        let key_id = plain["key_pair"]["id"].as_str().unwrap().to_string();
+        let signed = call(Method::POST, "/sign".to_string(), Some(serde_json::json!({ "key_id": key_id, "document_content": "memo", "bundle": true }))).await;
+        let signature_id = signed["signature_id"].as_str().unwrap().to_string();
+        let transport = call(Method::GET, "/admin/transport-key".to_string(), None).await;
+
+        let mut responses = vec![("generate", plain), ("generate encrypted", encrypted), ("sign", signed)];
+        for (name, method, path, body) in [
+            ("list", Method::GET, "/keys".to_string(), None),
+            ("search", Method::GET, "/keys/search?q=Plain".to_string(), None),
+            ("stats", Method::GET, "/keys/stats".to_string(), None),
+            ("get", Method::GET, format!("/keys/{}", key_id), None),
+            ("get legacy", Method::GET, format!("/keys/{}", legacy.id), None),
+            ("public", Method::GET, format!("/keys/{}/public", key_id), None),
+            ("update", Method::PATCH, format!("/keys/{}", key_id), Some(serde_json::json!({ "description": "memos" }))),
+            ("manifest", Method::GET, "/keys/manifest".to_string(), None),
+            ("record", Method::GET, format!("/signatures/by-id/{}", signature_id), None),
+            ("bundle", Method::GET, format!("/signatures/{}/bundle", signature_id), None),
+            ("wrap", Method::POST, format!("/keys/{}/export", key_id), Some(serde_json::json!({ "transport_public_key": transport["public_key"] }))),
+            ("validate", Method::POST, "/admin/validate".to_string(), Some(serde_json::json!({}))),
+            ("overview", Method::GET, "/admin/overview".to_string(), None),
+            ("kdf report", Method::GET, "/admin/kdf-report".to_string(), None),
+            ("delete", Method::DELETE, format!("/keys/{}", legacy.id), None),
+            ("deleted", Method::GET, "/keys/deleted".to_string(), None),
+            ("archived", Method::GET, "/keys/archived".to_string(), None),
+        ] {
+            responses.push((name, call(method, path, body).await));
+        }
+
+        for (name, response) in &responses {
+            assert_ne!(response, &serde_json::Value::Null, "{} did not answer with JSON", name);
+            let mut found = Vec::new();
+            secret_fields(response, "", &mut found);
+            assert!(found.is_empty(), "{} returned {:?}", name, found);
+            assert!(!response.to_string().contains(legacy.private_key.expose_for_persistence()), "{}", name);
+        }
+        assert_eq!(responses.iter().find(|(name, _)| *name == "deleted").unwrap().1["total_count"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_keys_of_a_future_type_are_listed_and_flagged_but_never_signed_with() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = test_state(&dir, Arc::new(MockClock::new(Utc::now())));
+        let current = generate_test_key_pair("Current").unwrap();
+        let future = generate_test_key_pair("Future").unwrap();
+
+        // Written by a newer release, and by hand with the names in lower case
+        let mut entries: Vec<serde_json::Value> = serde_json::from_str(&crate::key_storage::serialize_keys([&current, &future].into_iter()).unwrap()).unwrap();
+        entries[0]["key_type"] = serde_json::json!("ed25519");
+        entries[0]["key_strength"] = serde_json::json!("high");
+        entries[1]["key_type"] = serde_json::json!("Ed448");
+        std::fs::write(dir.path().join("keys.json"), serde_json::to_string(&entries).unwrap()).unwrap();
+        let summary = crate::integrity::validate_on_load(&state.storage).await.unwrap();
+        assert_eq!((summary.checked, summary.quarantined, summary.repaired, summary.unresolved), (2, 0, 0, 1));
+        let stored = state.storage.get_key_record(current.id).await.unwrap();
+        assert_eq!((stored.key_type, stored.key_strength), (KeyType::Ed25519, KeyStrength::High));
+        assert_eq!(state.storage.get_key_record(future.id).await.unwrap().key_type, KeyType::Unknown);
+
+        let listed = |key_type: &str| list_keys(State(state.clone()), Query(ListKeysQuery {
+            key_type: Some(key_type.to_string()),
+            ..Default::default()
+        }), None);
+        let ids = |response: ListKeysResponse| response.keys.into_iter().map(|key| key.id).collect::<Vec<_>>();
+        assert_eq!(ids(listed("unknown").await.unwrap().0), [future.id]);
+        assert_eq!(ids(listed("ED25519").await.unwrap().0), [current.id]);
+        assert!(listed("Ed25519Hsm").await.unwrap().0.keys.is_empty());
+        assert_eq!(listed("Ed448").await.unwrap_err().status(), StatusCode::BAD_REQUEST);
+        let stats = get_key_stats(State(state.clone())).await.0;
+        assert_eq!(stats.keys_by_type, BTreeMap::from([("Ed25519".to_string(), 1), ("Unknown".to_string(), 1)]));
+
+        let (status, Json(refused)) = sign_document(State(state.clone()), Json(SignDocumentRequest {
+            key_id: future.id,
+            document_content: Some("release notes".to_string()),
+            ..Default::default()
+        })).await.unwrap_err();
+        assert_eq!((status, refused.code), (StatusCode::BAD_REQUEST, Some(ErrorCode::InvalidKeyFormat)));
+        assert!(refused.message.contains("migrate the record"), "{}", refused.message);
+
+        let response = validate_keystore(State(state.clone()), None, Json(ValidateKeystoreRequest { repair: true, stream: false })).await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let report: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let flagged = report["reports"].as_array().unwrap().iter().find(|report| report["key_id"] == future.id.to_string()).unwrap();
+        assert_eq!(flagged["issues"][0]["code"], "unknown_key_type");
+        assert_eq!((&flagged["quarantined"], &flagged["issues"][0]["repaired"]), (&serde_json::json!(false), &serde_json::json!(false)));
+        assert_eq!(state.storage.key_count().await, 2);
+
+        let request: GenerateKeyRequest = serde_json::from_value(serde_json::json!({ "name": "Strong", "key_strength": "ULTRA" })).unwrap();
+        assert_eq!(request.key_strength, Some(KeyStrength::Ultra));
+    }
+
+    #[tokio::test]
+    async fn test_delegation_token_signs_within_its_uses_and_contexts() {
+        use crate::config::{KdfParams, MIN_PBKDF2_ITERATIONS};
+        use crate::key_generation::generate_salted_test_key_pair;
+        use axum::body::Body;
+        use tower::ServiceExt;
+
+        let dir = tempdir().unwrap();
+        let clock = Arc::new(MockClock::new(Utc::now()));
+        let secrets = std::collections::BTreeMap::from([("release".to_string(), "release-secret".to_string())]);
+        let state = Arc::new(AppState {
+            request_auth: RequestAuthenticator::new(secrets, Duration::minutes(5)),
+            ..Arc::into_inner(test_state(&dir, clock.clone())).unwrap()
+        });
+        let key_pair = generate_salted_test_key_pair("Release", "release-pass", &[8u8; 32], &KdfParams::pbkdf2(MIN_PBKDF2_ITERATIONS));
+        state.storage.store_key(key_pair.clone()).await.unwrap();
+        let app = crate::routes::router_with_versions(state.clone(), ApiVersion::ALL);
+
+        // The key holder signs its requests; the CI job only has the token. Each call is a
+        // second later, so repeated identical requests are not taken for replays.
+        let call = |method: Method, path: String, body: serde_json::Value, token: Option<String>| {
+            let app = app.clone();
+            clock.advance(Duration::seconds(1));
+            let timestamp = clock.now().timestamp();
+            async move {
+                let body = if body.is_null() { String::new() } else { body.to_string() };
+                let mut request = axum::http::Request::builder().method(method.clone()).uri(path.clone())
+                    .header(header::CONTENT_TYPE, "application/json");
+                request = match token {
+                    Some(token) => request.header(DELEGATION_TOKEN_HEADER, token),
+                    None => request
+                        .header(CLIENT_ID_HEADER, "release")
+                        .header(TIMESTAMP_HEADER, timestamp.to_string())
+                        .header(SIGNATURE_HEADER, crate::utils::sign_request(b"release-secret", method.as_str(), &path, timestamp, body.as_bytes())),
+                };
+                let response = app.oneshot(request.body(Body::from(body)).unwrap()).await.unwrap();
+                let status = response.status();
+                let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                (status, serde_json::from_slice::<serde_json::Value>(&bytes).unwrap_or_default())
+            }
+        };
+        let delegate = |password: &str| call(Method::POST, format!("/v1/keys/{}/delegate", key_pair.id), serde_json::json!({
+            "ttl_secs": 3600, "max_uses": 2, "contexts": ["ci-artifact"], "password": password,
+        }), None);
+        let sign = |token: &str, context: &str| call(Method::POST, "/v1/sign".to_string(), serde_json::json!({
+            "key_id": key_pair.id, "document_content": "artifact.tar.gz", "context": context,
+        }), Some(token.to_string()));
+
+        let (status, _) = delegate("wrong-pass").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, issued) = delegate("release-pass").await;
+        assert_eq!(status, StatusCode::OK, "{}", issued);
+        let token = issued["token"].as_str().unwrap().to_string();
+        assert_eq!(issued["delegation"]["issued_by"], "release");
+        assert!(!std::fs::read_to_string(dir.path().join("delegations.json")).unwrap().contains(&token));
+
+        let (status, first) = sign(&token, "ci-artifact").await;
+        assert_eq!(status, StatusCode::OK, "{}", first);
+        assert!(first["signature"].is_string());
+        // Outside its contexts the token is refused, and the attempt spends nothing
+        let (status, refused) = sign(&token, "deploy").await;
+        assert_eq!((status, refused["code"].as_str()), (StatusCode::FORBIDDEN, Some("DELEGATION_DENIED")));
+        let (_, listed) = call(Method::GET, format!("/v1/keys/{}/delegations", key_pair.id), serde_json::Value::Null, None).await;
+        assert_eq!(listed["delegations"][0]["uses"], 1);
+
+        let (status, second) = sign(&token, "ci-artifact").await;
+        assert_eq!(status, StatusCode::OK, "{}", second);
+        let (status, third) = sign(&token, "ci-artifact").await;
+        assert_eq!((status, third["code"].as_str()), (StatusCode::FORBIDDEN, Some("DELEGATION_DENIED")));
+        let (_, listed) = call(Method::GET, format!("/v1/keys/{}/delegations", key_pair.id), serde_json::Value::Null, None).await;
+        assert_eq!(listed["delegations"], serde_json::json!([]));
+
+        // Without a token the request has to be signed, and the password given
+        let (_, signed) = call(Method::POST, "/v1/sign".to_string(), serde_json::json!({ "key_id": key_pair.id, "document_content": "x" }), None).await;
+        assert_eq!(signed["code"], "PASSWORD_REQUIRED");
+        let unsigned = axum::http::Request::builder().method(Method::POST).uri("/v1/sign")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(serde_json::json!({ "key_id": key_pair.id, "document_content": "x" }).to_string())).unwrap();
+        let unsigned = app.clone().oneshot(unsigned).await.unwrap();
+        let unsigned: serde_json::Value = serde_json::from_slice(&axum::body::to_bytes(unsigned.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(unsigned["code"], "INVALID_REQUEST_SIGNATURE");
+
+        // A revoked grant stops signing at once
+        let (_, issued) = delegate("release-pass").await;
+        let revoke = format!("/v1/delegations/{}", issued["delegation"]["id"].as_str().unwrap());
+        let (status, _) = call(Method::DELETE, revoke.clone(), serde_json::Value::Null, None).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = sign(issued["token"].as_str().unwrap(), "ci-artifact").await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, missing) = call(Method::DELETE, revoke, serde_json::Value::Null, None).await;
+        assert_eq!((status, missing["code"].as_str()), (StatusCode::NOT_FOUND, Some("DELEGATION_NOT_FOUND")));
+    }
+
+    #[tokio::test]
+    async fn test_key_password_header_wins_over_the_body_and_stays_out_of_logs() {
+        use crate::config::{KdfParams, MIN_PBKDF2_ITERATIONS};
+        use crate::key_generation::generate_salted_test_key_pair;
+        use axum::body::Body;
+        use std::io::Write;
+        use std::sync::Mutex;
+        use tower::ServiceExt;
+
+        #[derive(Clone, Default)]
+        struct Captured(Arc<Mutex<Vec<u8>>>);
+        impl Write for Captured {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::TRACE)
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        let _logging = tracing::subscriber::set_default(subscriber);
+
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir, Arc::new(MockClock::new(Utc::now())));
+        let key_pair = generate_salted_test_key_pair("Release", "header-pass-1", &[4u8; 32], &KdfParams::pbkdf2(MIN_PBKDF2_ITERATIONS));
+        state.storage.store_key(key_pair.clone()).await.unwrap();
+        let app = crate::routes::router_with_versions(state.clone(), ApiVersion::ALL);
+        let call = |path: &str, body: serde_json::Value, password: Option<&str>| {
+            let mut request = axum::http::Request::builder().method(Method::POST).uri(path)
+                .header(header::CONTENT_TYPE, "application/json");
+            if let Some(password) = password {
+                request = request.header(KEY_PASSWORD_HEADER, password);
+            }
+            let request = request.body(Body::from(body.to_string())).unwrap();
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(request).await.unwrap();
+                let status = response.status();
+                let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                (status, serde_json::from_slice::<serde_json::Value>(&bytes).unwrap())
+            }
+        };
+        let sign = |key_id: Uuid, body_password: Option<&str>, header_password: Option<&str>| {
+            let mut body = serde_json::json!({ "key_id": key_id, "document_content": "release notes" });
+            if let Some(password) = body_password {
+                body["password"] = password.into();
+            }
+            call("/v1/sign", body, header_password)
+        };
+        let password_warnings = |response: &serde_json::Value| response["warnings"].as_array().into_iter().flatten()
+            .filter(|warning| warning["code"] == "PASSWORD_IN_BODY")
+            .map(|warning| warning["message"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>();
+
+        let (status, header_only) = sign(key_pair.id, None, Some("header-pass-1")).await;
+        assert_eq!(status, StatusCode::OK, "{}", header_only);
+        assert!(password_warnings(&header_only).is_empty());
+
+        let (status, body_only) = sign(key_pair.id, Some("header-pass-1"), None).await;
+        assert_eq!(status, StatusCode::OK, "{}", body_only);
+        assert_eq!(body_only["warnings"][0]["field"], "password");
+        assert!(password_warnings(&body_only)[0].contains("deprecated"));
+
+        // The header wins, so a stale body password does not matter, but is still called out
+        let (status, both) = sign(key_pair.id, Some("not-the-pass"), Some("header-pass-1")).await;
+        assert_eq!(status, StatusCode::OK, "{}", both);
+        assert!(password_warnings(&both)[0].contains("ignored"));
+        let (status, wrong) = sign(key_pair.id, Some("header-pass-1"), Some("not-the-pass")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(password_warnings(&wrong).len(), 1);
+
+        // Key generation takes the header too
+        let (status, generated) = call("/v1/keys/generate", serde_json::json!({ "name": "From Header" }), Some("generated-pass-2")).await;
+        assert_eq!(status, StatusCode::OK, "{}", generated);
+        let generated_id: Uuid = serde_json::from_value(generated["key_pair"]["id"].clone()).unwrap();
+        let (status, _) = sign(generated_id, None, None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, signed) = sign(generated_id, None, Some("generated-pass-2")).await;
+        assert_eq!(status, StatusCode::OK, "{}", signed);
+
+        let log = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        assert!(!log.is_empty());
+        for password in ["header-pass-1", "not-the-pass", "generated-pass-2"] {
+            assert!(!log.contains(password), "{} logged", password);
+        }
+
+        // Anything printing the request headers sees the password redacted
+        let mut headers = HeaderMap::new();
+        headers.insert(KEY_PASSWORD_HEADER, header::HeaderValue::from_static("header-pass-1"));
+        mark_sensitive_headers(&mut headers);
+        assert!(!format!("{:?}", headers).contains("header-pass-1"));
+    }
+
+    #[tokio::test]
+    async fn test_replicas_sharing_rate_buckets_enforce_one_limit() {
+        use crate::rate_limit::{MemoryRateBuckets, RequestCounter};
+
+        // Two instances behind a load balancer, counting in the same buckets as they would in Redis
+        let now = Utc::now();
+        let counter = RequestCounter::new(Arc::new(MemoryRateBuckets::default()), Default::default());
+        let replica = |dir: &tempfile::TempDir| Arc::new(AppState {
+            verify_rate_limit: ClientRateLimiter::with_counter(3, counter.clone()),
+            ..Arc::into_inner(test_state(dir, Arc::new(MockClock::new(now)))).unwrap()
+        });
+        let (first_dir, second_dir) = (tempdir().unwrap(), tempdir().unwrap());
+        let (first, second) = (replica(&first_dir), replica(&second_dir));
+
+        let key_pair = generate_test_key_pair("Shared Limits").unwrap();
+        first.storage.store_key(key_pair.clone()).await.unwrap();
+        let signature = sign_document(State(first.clone()), Json(SignDocumentRequest {
+            key_id: key_pair.id,
+            document_content: Some("invoice 77".to_string()),
+            ..Default::default()
+        })).await.unwrap().0.signature.unwrap();
+        let verify = |state: &Arc<AppState>, ip: &str| {
+            let caller = VerifyCaller { ip: Some(ip.parse().unwrap()), authenticated: false };
+            verify_signature_from(State(state.clone()), caller, Json(VerifySignatureRequest {
+                public_key: key_pair.public_key.clone(),
+                signature: signature.clone(),
+                document_content: Some("invoice 77".to_string()),
+                ..Default::default()
+            }))
+        };
+
+        for state in [&first, &second, &first] {
+            assert_eq!(verify(state, "203.0.113.40").await.status(), StatusCode::OK);
+        }
+        let refused = verify(&second, "203.0.113.40").await;
+        assert_eq!(refused.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(refused.headers()[header::RETRY_AFTER], "60");
+        assert_eq!(verify(&second, "203.0.113.41").await.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_legacy_import_yields_keys_that_sign_and_verify() {
+        let dir = tempdir().unwrap();
+        let now = Utc::now();
+        let state = test_state(&dir, Arc::new(MockClock::new(now)));
+        let records: Vec<serde_json::Value> = serde_json::from_str(crate::migration::PROTOTYPE_EXPORT).unwrap();
+        let default_created_at = now - chrono::Duration::days(900);
+
+        let Json(response) = import_legacy_keys(State(state.clone()), Json(ImportLegacyKeysRequest {
+            records: records.clone(),
+            created_at: Some(default_created_at),
+        })).await.unwrap();
+        assert_eq!(response.message, "2 imported, 0 skipped as duplicates, 0 unsupported, 2 unreadable");
+        let report = response.report.unwrap();
+        let key_id = report.entries[0].key_id.unwrap();
+        let stored = state.storage.get_key_record(key_id).await.unwrap();
+        assert_eq!(stored.created_at, default_created_at);
+
+        // The legacy-encrypted key signs with its old password and its signatures verify
+        let signed = sign_document(State(state.clone()), Json(SignDocumentRequest {
+            key_id,
+            document_content: Some("carried over".to_string()),
+            password: Some("prototype-pass".to_string()),
+            ..Default::default()
+        })).await.unwrap().0;
+        let Json(verified) = verify_signature(State(state.clone()), Json(VerifySignatureRequest {
+            public_key: stored.public_key.clone(),
+            signature: signed.signature.unwrap(),
+            document_content: Some("carried over".to_string()),
+            ..Default::default()
+        })).await.unwrap();
+        assert!(verified.is_valid);
+
+        let Err((status, _)) = import_legacy_keys(State(state.clone()), Json(ImportLegacyKeysRequest { records: vec![], created_at: None })).await else {
+            panic!("an empty import was accepted");
+        };
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    /// Answers peer requests from another instance's router, until it is taken down
+    struct RouterTransport {
+        app: axum::Router,
+        requests: std::sync::atomic::AtomicUsize,
+        down: AtomicBool,
+    }
+
+    #[async_trait::async_trait]
+    impl crate::federation::PeerTransport for RouterTransport {
+        async fn get(&self, url: &str) -> Result<(u16, Vec<u8>), KeyManagementError> {
+            use tower::ServiceExt;
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            if self.down.load(Ordering::SeqCst) {
+                return Err(KeyManagementError::InternalError("Connection refused".to_string()));
+            }
+            let request = axum::http::Request::builder()
+                .uri(url.strip_prefix("http://partner.test").unwrap())
+                .body(axum::body::Body::empty())
+                .unwrap();
+            let response = self.app.clone().oneshot(request).await.unwrap();
+            let status = response.status().as_u16();
+            Ok((status, axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap().to_vec()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_verify_resolves_unknown_keys_from_a_federation_peer() {
+        use crate::config::FederationConfig;
+        use crate::federation::FederationPeer;
+
+        let (dir, partner_dir) = (tempdir().unwrap(), tempdir().unwrap());
+        let start = Utc::now();
+        let clock = Arc::new(MockClock::new(start));
+        let partner = test_state(&partner_dir, clock.clone());
+        let key_pair = generate_test_key_pair("Partner Key").unwrap();
+        partner.storage.store_key(key_pair.clone()).await.unwrap();
+        let signed = sign_document(State(partner.clone()), Json(SignDocumentRequest {
+            key_id: key_pair.id,
+            document_content: Some("partner release".to_string()),
+            ..Default::default()
+        })).await.unwrap().0;
+
+        let transport = Arc::new(RouterTransport {
+            app: crate::routes::router_with_versions(partner.clone(), ApiVersion::ALL),
+            requests: Default::default(),
+            down: AtomicBool::new(false),
+        });
+        let federation = FederationConfig {
+            peers: vec![FederationPeer { name: "partner".to_string(), base_url: "http://partner.test/v1".to_string() }],
+            ..FederationConfig::default()
+        };
+        let state = Arc::new(AppState {
+            key_resolver: Arc::new(KeyResolver::new(transport.clone(), &federation)),
+            ..Arc::into_inner(test_state(&dir, clock.clone())).unwrap()
+        });
+        let verify = |key_id| verify_signature(State(state.clone()), Json(VerifySignatureRequest {
+            key_id: Some(key_id),
+            signature: signed.signature.clone().unwrap(),
+            document_content: Some("partner release".to_string()),
+            ..Default::default()
+        }));
+        let requests = || transport.requests.load(Ordering::SeqCst);
+
+        // Status, then public key by fingerprint
+        let response = verify(key_pair.id).await.unwrap().0;
+        assert!(response.is_valid);
+        assert_eq!(response.key_source.as_deref(), Some("remote(partner)"));
+        assert_eq!(response.remote_key.unwrap().state, KeyState::Active);
+        assert!(response.warnings.is_empty());
+        assert_eq!(requests(), 2);
+
+        // Within the TTL the cached key answers without asking the peer
+        partner.storage.revoke_key(key_pair.id, Some("compromised".to_string())).await.unwrap();
+        assert!(verify(key_pair.id).await.unwrap().0.is_valid);
+        assert_eq!(requests(), 2);
+
+        // Once it expires, the revocation at the peer shows
+        clock.set(start + Duration::seconds(federation.cache_ttl_secs.into()) + Duration::seconds(1));
+        let response = verify(key_pair.id).await.unwrap().0;
+        assert_eq!(response.remote_key.unwrap().state, KeyState::Revoked);
+        assert_eq!(response.warnings[0].code, WarningCode::KeyInactive);
+        assert_eq!(requests(), 4);
+
+        // A key the peer never had, and any key while the peer is down, are unknown rather than errors
+        let (status, Json(unknown)) = verify(Uuid::new_v4()).await.unwrap_err();
+        assert_eq!((status, unknown.code), (StatusCode::NOT_FOUND, Some(ErrorCode::KeyNotFound)));
+        transport.down.store(true, Ordering::SeqCst);
+        clock.set(start + Duration::seconds(2 * i64::from(federation.cache_ttl_secs)) + Duration::seconds(2));
+        let (status, Json(down)) = verify(key_pair.id).await.unwrap_err();
+        assert_eq!((status, down.code), (StatusCode::NOT_FOUND, Some(ErrorCode::KeyNotFound)));
+        assert!(down.key_source.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_about_lists_exactly_the_routed_endpoints() {
+        use axum::body::Body;
+        use tower::ServiceExt;
+
+        let dir = tempdir().unwrap();
+        let app = crate::routes::router_with_versions(test_state(&dir, Arc::new(MockClock::new(Utc::now()))), ApiVersion::ALL);
+        let response = app.clone().oneshot(axum::http::Request::get("/v1/about").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(body["auth_mode"], "none");
+        assert_eq!(body["storage_backend"], "json-file");
+        let endpoints: Vec<crate::capabilities::EndpointInfo> = serde_json::from_value(body["endpoints"].clone()).unwrap();
+        assert_eq!(endpoints, crate::routes::endpoint_list());
+
+        // A method a path is not routed for gets 405 with the methods it is routed for, which must
+        // be exactly those listed; no handler runs
+        let mut by_path: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for endpoint in &endpoints {
+            by_path.entry(&endpoint.path).or_default().push(&endpoint.method);
+        }
+        for (path, mut listed) in by_path {
+            let concrete: Vec<String> = path.split('/')
+                .map(|segment| if segment.starts_with(':') { Uuid::nil().to_string() } else { segment.to_string() })
+                .collect();
+            let request = axum::http::Request::builder()
+                .method(Method::TRACE)
+                .uri(format!("/v1{}", concrete.join("/")))
+                .body(Body::empty())
+                .unwrap();
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED, "{}", path);
+            let mut routed: Vec<&str> = response.headers()[header::ALLOW].to_str().unwrap()
+                .split(',')
+                .map(str::trim)
+                .filter(|method| *method != "HEAD")
+                .collect();
+            routed.sort_unstable();
+            listed.sort_unstable();
+            assert_eq!(routed, listed, "{}", path);
+        }
+
+        // The hand-kept list of endpoints in the startup log is gone
+        assert!(!include_str!("../main.rs").contains("info!(\"   GET"));
+    }
+
+    #[tokio::test]
+    async fn test_non_exportable_keys_sign_but_never_leave() {
+        let clock = Arc::new(MockClock::new(Utc::now()));
+        let (dir, destination_dir) = (tempdir().unwrap(), tempdir().unwrap());
+        let state = test_state(&dir, clock.clone());
+        let destination = test_state(&destination_dir, clock);
+        let key_info = generate_keys(State(state.clone()), Query(GenerateKeyQuery::default()), Json(GenerateKeyRequest {
+            name: "Root Key".to_string(),
+            exportable: Some(false),
+            ..Default::default()
+        })).await.unwrap().0.key_pair.unwrap();
+        assert!(!key_info.exportable);
+        assert!(!key_info.capabilities.exportable);
+
+        // Signing and verification are unaffected
+        let signature = sign_document(State(state.clone()), Json(SignDocumentRequest {
+            key_id: key_info.id,
+            document_hash: Some("a".repeat(64)),
+            ..Default::default()
+        })).await.unwrap().0.signature.unwrap();
+        let verified = verify_signature(State(state.clone()), Json(VerifySignatureRequest {
+            key_id: Some(key_info.id),
+            document_hash: Some("a".repeat(64)),
+            signature,
+            ..Default::default()
+        })).await.unwrap().0;
+        assert!(verified.is_valid);
+
+        let (status, Json(refused)) = export_wrapped_key(State(state.clone()), Path(key_info.id), Json(ExportWrappedKeyRequest {
+            transport_public_key: destination.transport_key.public_key(),
+            password: None,
+        })).await.unwrap_err();
+        assert_eq!((status, refused.code), (StatusCode::FORBIDDEN, Some(ErrorCode::InsufficientPermissions)));
+
+        // Backups keep the key's public half only
+        let stored = state.storage.get_key_record(key_info.id).await.unwrap();
+        let backup_path = dir.path().join("backup.json");
+        state.storage.create_backup(backup_path.to_str().unwrap()).await.unwrap();
+        let backup = std::fs::read_to_string(&backup_path).unwrap();
+        assert!(!backup.contains(stored.private_key.expose_for_persistence()));
+        assert!(backup.contains(&stored.public_key));
+
+        // The flag can be tightened but never cleared again
+        let update = |exportable| update_key(State(state.clone()), Path(key_info.id), Json(UpdateKeyRequest {
+            name: Some("Renamed".to_string()),
+            exportable: Some(exportable),
+            ..Default::default()
+        }), None);
+        let (status, Json(refused)) = update(true).await.unwrap_err();
+        assert_eq!((status, refused.code), (StatusCode::FORBIDDEN, Some(ErrorCode::InsufficientPermissions)));
+        let unchanged = state.storage.get_key_record(key_info.id).await.unwrap();
+        assert!(!unchanged.exportable);
+        assert_eq!(unchanged.name, "Root Key");
+        assert!(!update(false).await.unwrap().0.key_info.unwrap().exportable);
+    }
+
+    #[tokio::test]
+    async fn test_message_encodings_sign_verify_and_travel_in_bundles() {
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir, Arc::new(MockClock::new(Utc::now())));
+        let key_pair = generate_seeded_test_key_pair("Partner Key", 3);
+        state.storage.store_key(key_pair.clone()).await.unwrap();
+        let document = "Partner invoice 2024-117";
+        let sign = |message_encoding: MessageEncoding, context: Option<&str>| sign_document(State(state.clone()), Json(SignDocumentRequest {
+            key_id: key_pair.id,
+            document_content: Some(document.to_string()),
+            message_encoding,
+            context: context.map(str::to_string),
+            bundle: true,
+            ..Default::default()
+        }));
+
+        let raw = sign(MessageEncoding::RawMessage, None).await.unwrap().0;
+        assert_eq!(raw.message_encoding, MessageEncoding::RawMessage);
+        let hex = sign(MessageEncoding::HashHexBytes, None).await.unwrap().0;
+        let default = sign(MessageEncoding::HashRawBytes, None).await.unwrap().0;
+        // Three different signatures of one document, each with its own receipt
+        assert_ne!(raw.signature, hex.signature);
+        assert_ne!(hex.signature, default.signature);
+        assert_ne!(raw.signature_id, hex.signature_id);
+        assert!(!serde_json::to_value(default.bundle.as_ref().unwrap()).unwrap().as_object().unwrap().contains_key("message_encoding"));
+
+        // The bundle records the encoding; a raw-message signature needs the content to verify
+        let bundle = raw.bundle.unwrap();
+        assert_eq!(bundle.body.message_encoding, MessageEncoding::RawMessage);
+        assert!(verify_bundle(&bundle, BundleSubject::Content(document)).unwrap().valid);
+        assert!(!verify_bundle(&bundle, BundleSubject::Hash(&create_document_hash(document))).unwrap().signature_valid);
+        assert!(verify_bundle(&hex.bundle.unwrap(), BundleSubject::Hash(&create_document_hash(document))).unwrap().valid);
+
+        let verify = |signature: String, message_encoding: MessageEncoding| verify_signature(State(state.clone()), Json(VerifySignatureRequest {
+            key_id: Some(key_pair.id),
+            document_content: Some(document.to_string()),
+            signature,
+            message_encoding,
+            ..Default::default()
+        }));
+        assert!(verify(raw.signature.clone().unwrap(), MessageEncoding::RawMessage).await.unwrap().0.is_valid);
+        assert!(!verify(raw.signature.unwrap(), MessageEncoding::HashRawBytes).await.unwrap().0.is_valid);
+
+        // Encodings other tools check bind nothing else, and apply to raw signatures only
+        let (status, Json(refused)) = sign(MessageEncoding::HashHexBytes, Some("invoice")).await.unwrap_err();
+        assert_eq!((status, refused.code), (StatusCode::UNPROCESSABLE_ENTITY, Some(ErrorCode::ValidationFailed)));
+        let (status, _) = sign_document(State(state.clone()), Json(SignDocumentRequest {
+            key_id: key_pair.id,
+            document_content: Some(document.to_string()),
+            output_format: Some(SignatureOutputFormat::Minisign),
+            message_encoding: MessageEncoding::RawMessage,
+            ..Default::default()
+        })).await.unwrap_err();
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn test_storage_breaker_fails_fast_and_recovers_through_probes() {
+        use crate::config::StorageBreakerConfig;
+        use crate::storage_breaker::StorageBreaker;
+        use axum::body::Body;
+        use tower::ServiceExt;
+
+        // The keystore path is a directory, so every write fails until it is removed
+        let dir = tempdir().unwrap();
+        let start = Utc::now();
+        let clock = Arc::new(MockClock::new(start));
+        let storage_path = dir.path().join("keys.json");
+        std::fs::create_dir(&storage_path).unwrap();
+        let breaker = StorageBreaker::new(StorageBreakerConfig { failure_percent: 50, slow_ms: 2_000, open_secs: 30, probes: 1 });
+        let state = Arc::new(AppState {
+            storage: Arc::new(KeyStorage::new(storage_path.to_str().unwrap()).with_clock(clock.clone()).with_breaker(breaker)),
+            ..Arc::into_inner(test_state(&dir, clock.clone())).unwrap()
+        });
+        let app = crate::routes::router_with_versions(state.clone(), ApiVersion::ALL);
+        let call = |method: Method, uri: &str, body: Option<serde_json::Value>| {
+            let request = axum::http::Request::builder().method(method).uri(uri).header(header::CONTENT_TYPE, "application/json");
+            let request = request.body(body.map_or_else(Body::empty, |body| Body::from(body.to_string()))).unwrap();
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(request).await.unwrap();
+                let (status, retry_after) = (response.status(), response.headers().get(header::RETRY_AFTER).cloned());
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                (status, retry_after, serde_json::from_slice::<serde_json::Value>(&body).unwrap_or_default())
+            }
+        };
+        let generate = |name: &str| call(Method::POST, "/keys/generate", Some(serde_json::json!({ "name": name })));
+
+        let key_pair = generate_seeded_test_key_pair("Stored Key", 9);
+        state.storage.store_key(key_pair.clone()).await.unwrap();
+        let signed = sign_document(State(state.clone()), Json(SignDocumentRequest {
+            key_id: key_pair.id,
+            document_content: Some("quarterly report".to_string()),
+            ..Default::default()
+        })).await.unwrap().0;
+
+        // Changes are held in memory while writes fail, until the failure rate opens the breaker
+        for index in 0..4 {
+            assert_eq!(generate(&format!("Key {}", index)).await.0, StatusCode::OK);
+        }
+        let (status, retry_after, body) = generate("Refused").await;
+        assert_eq!((status, body["code"].as_str()), (StatusCode::SERVICE_UNAVAILABLE, Some("STORAGE_UNAVAILABLE")));
+        assert_eq!(retry_after.unwrap(), "30");
+        assert_eq!(body["details"]["retry_after_secs"], 30);
+        assert_eq!(call(Method::GET, "/keys", None).await.0, StatusCode::SERVICE_UNAVAILABLE);
+
+        // Verification against a given public key keeps serving; lookups by id wait for the keystore
+        let verify = serde_json::json!({
+            "public_key": key_pair.public_key,
+            "signature": signed.signature,
+            "document_hash": signed.document_hash,
+        });
+        let (status, _, body) = call(Method::POST, "/verify", Some(verify.clone())).await;
+        assert_eq!((status, body["is_valid"].as_bool()), (StatusCode::OK, Some(true)));
+        let by_id = serde_json::json!({ "key_id": key_pair.id, "signature": signed.signature, "document_hash": signed.document_hash });
+        let (status, retry_after, body) = call(Method::POST, "/verify", Some(by_id.clone())).await;
+        assert_eq!((status, body["code"].as_str()), (StatusCode::SERVICE_UNAVAILABLE, Some("STORAGE_UNAVAILABLE")));
+        assert_eq!(retry_after.unwrap(), "30");
+
+        let (status, _, body) = call(Method::GET, "/health/ready", None).await;
+        assert_eq!((status, body["storage_breaker"]["state"].as_str()), (StatusCode::OK, Some("open")));
+        assert_eq!(body["storage_breaker"]["rejected_requests"], 3);
+        let metrics = state.storage.breaker().rejections();
+        assert_eq!(metrics, [("/keys".to_string(), 1), ("/keys/generate".to_string(), 1), ("/verify".to_string(), 1)]);
+
+        // Once the keystore is writable again, a probe closes the breaker
+        std::fs::remove_dir(&storage_path).unwrap();
+        clock.advance(Duration::seconds(30));
+        assert_eq!(generate("Probe").await.0, StatusCode::OK);
+        assert_eq!(call(Method::GET, "/keys", None).await.0, StatusCode::OK);
+        assert_eq!(call(Method::POST, "/verify", Some(by_id)).await.0, StatusCode::OK);
+        let (_, _, body) = call(Method::GET, "/health/ready", None).await;
+        let path: Vec<&str> = body["storage_breaker"]["transitions"].as_array().unwrap().iter().map(|transition| transition["to"].as_str().unwrap()).collect();
+        assert_eq!(path, ["open", "half-open", "closed"]);
+        assert!(!state.storage.persistence_status().degraded);
+    }
+
+    #[tokio::test]
+    async fn test_archive_verification_reports_each_entry() {
+        use std::io::Write;
+        use tower::ServiceExt;
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir, Arc::new(MockClock::new(Utc::now())));
+        let key_pair = generate_seeded_test_key_pair("Archive Key", 4);
+        state.storage.store_key(key_pair.clone()).await.unwrap();
+        let app = crate::routes::router_with_versions(state.clone(), ApiVersion::ALL);
+        let sign = |content: &str| {
+            let state = state.clone();
+            let request = SignDocumentRequest { key_id: key_pair.id, document_content: Some(content.to_string()), ..Default::default() };
+            async move { sign_document(State(state), Json(request)).await.unwrap().0.signature.unwrap() }
+        };
+
+        // One entry intact, one changed after signing, one lost, and one the manifest never listed
+        let manifest = serde_json::json!({
+            "contract.txt": { "signature": sign("contract").await, "public_key": key_pair.public_key },
+            "invoice.txt": { "signature": sign("invoice").await, "key_id": key_pair.id, "hash_algorithm": "sha256" },
+            "receipt.txt": { "signature": sign("receipt").await, "public_key": key_pair.public_key },
+        });
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        for (name, content) in [("contract.txt", "contract"), ("invoice.txt", "invoice, amended"), ("notes.txt", "notes")] {
+            zip.start_file(name, zip::write::SimpleFileOptions::default()).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        let archive = zip.finish().unwrap().into_inner();
+
+        let upload = |app: &axum::Router, archive: &[u8], manifest: &serde_json::Value| {
+            let mut body = b"--boundary\r\nContent-Disposition: form-data; name=\"archive\"; filename=\"docs.zip\"\r\n\r\n".to_vec();
+            body.extend_from_slice(archive);
+            body.extend_from_slice(format!("\r\n--boundary\r\nContent-Disposition: form-data; name=\"manifest\"\r\n\r\n{}\r\n--boundary--\r\n", manifest).as_bytes());
+            let request = axum::http::Request::builder()
+                .method(Method::POST)
+                .uri("/verify/archive")
+                .header(header::CONTENT_TYPE, "multipart/form-data; boundary=boundary")
+                .body(axum::body::Body::from(body))
+                .unwrap();
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(request).await.unwrap();
+                let status = response.status();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                (status, serde_json::from_slice::<serde_json::Value>(&body).unwrap())
+            }
+        };
+
+        let (status, report) = upload(&app, &archive, &manifest).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(report["all_valid"], false);
+        let statuses: Vec<(&str, &str)> = report["entries"].as_array().unwrap().iter()
+            .map(|entry| (entry["path"].as_str().unwrap(), entry["status"].as_str().unwrap()))
+            .collect();
+        assert_eq!(statuses, [("contract.txt", "valid"), ("invoice.txt", "invalid"), ("receipt.txt", "missing")]);
+        assert_eq!(report["entries"][1]["sha256"], create_document_hash("invoice, amended"));
+        assert_eq!(report["uncovered"], serde_json::json!(["notes.txt"]));
+        assert_eq!(report["counts"], serde_json::json!({ "total": 3, "valid": 1, "invalid": 1, "missing": 1, "error": 0, "uncovered": 1 }));
+
+        // Past the upload limit the archive is refused whole
+        let small = Arc::new(AppState {
+            config: Arc::new(Config { verify_archive_max_bytes: 64, ..Config::default() }),
+            ..Arc::into_inner(test_state(&dir, Arc::new(MockClock::new(Utc::now())))).unwrap()
+        });
+        let (status, body) = upload(&crate::routes::router_with_versions(small, ApiVersion::ALL), &archive, &manifest).await;
+        assert_eq!((status, body["code"].as_str()), (StatusCode::PAYLOAD_TOO_LARGE, Some("CONTENT_TOO_LARGE")));
+    }
+
+    #[tokio::test]
+    async fn test_verify_accepts_public_keys_in_pem_ssh_and_jwk() {
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir, Arc::new(MockClock::new(Utc::now())));
+        let key_pair = generate_test_key_pair("Shared Elsewhere").unwrap();
+        state.storage.store_key(key_pair.clone()).await.unwrap();
+        let signed = sign_document(State(state.clone()), Json(SignDocumentRequest {
+            key_id: key_pair.id,
+            document_content: Some("handed over".to_string()),
+            ..Default::default()
+        })).await.unwrap().0;
+        let verify = |public_key: String| verify_signature(State(state.clone()), Json(VerifySignatureRequest {
+            public_key,
+            signature: signed.signature.clone().unwrap(),
+            document_content: Some("handed over".to_string()),
+            ..Default::default()
+        }));
+
+        let public_key = decode_public_key(&key_pair.public_key).unwrap();
+        let jwk = encode_jwk(&public_key, key_pair.id);
+        let inputs = [
+            (key_pair.public_key.clone(), "raw"),
+            (encode_pem(&public_key), "pem"),
+            (sshsig::encode_public_key(&public_key, "release@example"), "ssh"),
+            (jwk.to_string(), "jwk"),
+            (serde_json::json!({ "keys": [jwk.clone()] }).to_string(), "jwk"),
+        ];
+        for (input, format) in inputs {
+            let response = verify(input).await.unwrap().0;
+            assert!(response.is_valid, "{}", format);
+            assert_eq!(serde_json::to_value(response.public_key_format).unwrap(), format);
+        }
+
+        // Base64 of a whole PEM file is the usual mistake, and is explained
+        let encoded_pem = base64::Engine::encode(&base64::engine::general_purpose::STANDARD, encode_pem(&public_key));
+        let response = verify(encoded_pem).await.unwrap().0;
+        assert!(!response.is_valid && response.public_key_format.is_none());
+        assert_eq!(response.code, Some(ErrorCode::InvalidKeyFormat));
+        assert!(response.details.unwrap()["reason"].as_str().unwrap().contains("base64 encoding of a PEM file"));
+
+        // A JWK set of several keys is not guessed at
+        let other = encode_jwk(&decode_public_key(&generate_test_key_pair("Other").unwrap().public_key).unwrap(), Uuid::new_v4());
+        let response = verify(serde_json::json!({ "keys": [jwk, other] }).to_string()).await.unwrap().0;
+        assert!(!response.is_valid);
+        assert_eq!(response.code, Some(ErrorCode::AmbiguousPublicKey));
+
+        // A key_id request may name its key in any format too
+        let response = verify_signature(State(state.clone()), Json(VerifySignatureRequest {
+            key_id: Some(key_pair.id),
+            public_key: encode_pem(&public_key),
+            signature: signed.signature.clone().unwrap(),
+            document_content: Some("handed over".to_string()),
+            ..Default::default()
+        })).await.unwrap().0;
+        assert!(response.is_valid);
+    }
+
+    #[tokio::test]
+    async fn test_operation_events_are_numbered_across_restarts_without_gaps() {
+        use crate::event_log::EventKind;
+        use axum::body::Body;
+        use tower::ServiceExt;
+        let dir = tempdir().unwrap();
+        let clock = Arc::new(MockClock::new(Utc::now()));
+        let call = |state: Arc<AppState>, method: Method, uri: String, body: Option<serde_json::Value>| async move {
+            let request = axum::http::Request::builder()
+                .method(method)
+                .uri(uri)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(body.map_or_else(Body::empty, |body| Body::from(body.to_string())))
+                .unwrap();
+            let response = crate::routes::router_with_versions(state, ApiVersion::ALL).oneshot(request).await.unwrap();
+            let (status, seq) = (response.status(), response.headers().get(EVENT_SEQ_HEADER).map(|seq| seq.to_str().unwrap().parse::<u64>().unwrap()));
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (status, seq, serde_json::from_slice::<serde_json::Value>(&body).unwrap_or_default())
+        };
+
+        let state = test_state(&dir, clock.clone());
+        let (status, seq, body) = call(state.clone(), Method::POST, "/v1/keys/generate".to_string(), Some(serde_json::json!({ "name": "Ledger Key" }))).await;
+        assert_eq!((status, seq), (StatusCode::OK, Some(1)));
+        let key_id: Uuid = body["key_pair"]["id"].as_str().unwrap().parse().unwrap();
+        let (status, seq, signed) = call(state.clone(), Method::POST, "/v1/sign".to_string(), Some(serde_json::json!({
+            "key_id": key_id,
+            "document_content": "ledger entry",
+        }))).await;
+        // The signature is numbered as it is released, then the request that made it
+        assert_eq!((status, seq), (StatusCode::OK, Some(3)));
+        let (_, seq, _) = call(state.clone(), Method::GET, "/v1/keys".to_string(), None).await;
+        assert_eq!(seq, None);
+        let (_, seq, _) = call(state.clone(), Method::POST, "/v1/verify".to_string(), Some(serde_json::json!({
+            "key_id": key_id,
+            "signature": signed["signature"],
+            "document_hash": signed["document_hash"],
+        }))).await;
+        assert_eq!(seq, None);
+        drop(state);
+
+        // A restarted instance continues the sequence from the log beside the keystore
+        let state = test_state(&dir, clock.clone());
+        state.storage.load_from_disk().await.unwrap();
+        let (status, seq, _) = call(state.clone(), Method::POST, format!("/v1/keys/{}/revoke", key_id), Some(serde_json::json!({
+            "key_id": key_id,
+            "immediate": true,
+        }))).await;
+        assert_eq!((status, seq), (StatusCode::OK, Some(4)));
+        assert!(!state.storage.flush().await.unwrap());
+
+        let (status, _, body) = call(state.clone(), Method::GET, "/v1/events/replay?after_seq=0".to_string(), None).await;
+        assert_eq!(status, StatusCode::OK);
+        let events: Vec<OperationEvent> = serde_json::from_value(body["events"].clone()).unwrap();
+        assert_eq!(events.iter().map(|event| event.seq).collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+        let described: Vec<_> = events.iter().map(|event| (event.kind, event.operation.as_str(), event.key_id)).collect();
+        assert_eq!(described, vec![
+            (EventKind::Request, "POST /keys/generate", None),
+            (EventKind::Signature, "signature", Some(key_id)),
+            (EventKind::Request, "POST /sign", None),
+            (EventKind::Request, "POST /keys/:key_id/revoke", Some(key_id)),
+        ]);
+        assert_eq!(events[1].signature_id.map(|id| id.to_string()), signed["signature_id"].as_str().map(str::to_string));
+        assert_eq!((body["high_water"].as_u64(), body["has_more"].as_bool()), (Some(4), Some(false)));
+
+        let (_, _, body) = call(state.clone(), Method::GET, "/v1/events/replay?after_seq=1&limit=2".to_string(), None).await;
+        assert_eq!((body["events"][0]["seq"].as_u64(), body["events"][1]["seq"].as_u64(), body["has_more"].as_bool()), (Some(2), Some(3), Some(true)));
+        let (status, _, _) = call(state, Method::GET, "/v1/events/replay?limit=0".to_string(), None).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn test_key_signature_defaults_apply_unless_the_request_names_its_own() {
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir, Arc::new(MockClock::new(Utc::now())));
+        let generate = |request: serde_json::Value| generate_keys(
+            State(state.clone()),
+            Query(GenerateKeyQuery::default()),
+            Json(serde_json::from_value::<GenerateKeyRequest>(request).unwrap()),
+        );
+
+        // Defaults the key could not honour are refused
+        let (status, Json(refused)) = generate(serde_json::json!({
+            "name": "Mismatched",
+            "default_output_format": "minisign",
+            "default_hash_algorithm": "sha512",
+            "default_encoding": "hex",
+        })).await.unwrap_err();
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        let fields: Vec<&str> = refused.errors.iter().map(|error| error.field.as_str()).collect();
+        assert_eq!(fields, ["default_hash_algorithm", "default_encoding"]);
+
+        let key_info = generate(serde_json::json!({
+            "name": "Git Signing",
+            "default_output_format": "sshsig",
+            "default_hash_algorithm": "sha256",
+        })).await.unwrap().0.key_pair.unwrap();
+        assert_eq!((key_info.default_output_format, key_info.default_hash_algorithm), (Some(SignatureOutputFormat::Sshsig), Some(HashAlgorithm::Sha256)));
+
+        let sign = |output_format, hash_algorithm, encoding| sign_document(State(state.clone()), Json(SignDocumentRequest {
+            key_id: key_info.id,
+            document_content: Some("tree 4b825dc642cb6eb9a060e54bf8d69288fbee4904\n".to_string()),
+            output_format,
+            hash_algorithm,
+            encoding,
+            ..Default::default()
+        }));
+        let signed = sign(None, None, None).await.unwrap().0;
+        assert_eq!(signed.output_format, SignatureOutputFormat::Sshsig);
+        let parsed = sshsig::parse_signature(&signed.signature.unwrap()).unwrap();
+        assert_eq!(parsed.hash_algorithm, sshsig::HashAlgorithm::Sha256);
+        let signed = sign(None, Some(HashAlgorithm::Sha512), None).await.unwrap().0;
+        assert_eq!(sshsig::parse_signature(&signed.signature.unwrap()).unwrap().hash_algorithm, sshsig::HashAlgorithm::Sha512);
+
+        // The request's format wins, and the key's digest is left out where that format cannot take it
+        let signed = sign(Some(SignatureOutputFormat::Minisign), None, None).await.unwrap().0;
+        assert_eq!(signed.output_format, SignatureOutputFormat::Minisign);
+        let (status, _) = sign(Some(SignatureOutputFormat::Raw), Some(HashAlgorithm::Sha512), None).await.unwrap_err();
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let updated = update_key(State(state.clone()), Path(key_info.id), Json(UpdateKeyRequest {
+            default_output_format: Some(SignatureOutputFormat::Raw),
+            default_encoding: Some(SignatureEncoding::Hex),
+            ..Default::default()
+        }), None).await.unwrap().0.key_info.unwrap();
+        assert_eq!((updated.default_output_format, updated.default_encoding), (Some(SignatureOutputFormat::Raw), Some(SignatureEncoding::Hex)));
+        let signed = sign(None, None, None).await.unwrap().0;
+        assert_eq!((signed.output_format, signed.signature_encoding), (SignatureOutputFormat::Raw, SignatureEncoding::Hex));
+        assert_eq!(signed.signature.unwrap().len(), 128);
+        let signed = sign(None, None, Some(SignatureEncoding::Base64)).await.unwrap().0;
+        assert_eq!(signed.signature_encoding, SignatureEncoding::Base64);
+        let signed = sign(Some(SignatureOutputFormat::Sshsig), None, None).await.unwrap().0;
+        assert_eq!(signed.signature_encoding, SignatureEncoding::Base64);
+
+        // Changing one default is checked against the others as they stand
+        let (status, _) = update_key(State(state.clone()), Path(key_info.id), Json(UpdateKeyRequest {
+            default_output_format: Some(SignatureOutputFormat::Minisign),
+            ..Default::default()
+        }), None).await.unwrap_err();
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        let stored = state.storage.get_key_record(key_info.id).await.unwrap();
+        assert_eq!(stored.default_output_format, Some(SignatureOutputFormat::Raw));
+    }
+
+    #[tokio::test]
+    async fn test_injected_storage_and_crypto_failures_surface_and_leave_no_stray_state() {
+        use crate::failpoints::{FailAction, FailScenario, KEYSTORE_WRITE, KEY_DECRYPT, KEY_ENCRYPT, SIGN};
+
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir, Arc::new(MockClock::new(Utc::now())));
+        let scenario = FailScenario::new();
+        let generate = |name: &str, password: Option<&str>| generate_keys(State(state.clone()), Query(GenerateKeyQuery::default()), Json(GenerateKeyRequest {
+            name: name.to_string(),
+            password: password.map(str::to_string),
+            ..Default::default()
+        }));
+
+        // A key whose encryption fails is never stored
+        scenario.arm(KEY_ENCRYPT, FailAction::Fail);
+        let (status, Json(refused)) = generate("Unsealed", Some("hunter22")).await.unwrap_err();
+        assert_eq!((status, refused.code), (StatusCode::INTERNAL_SERVER_ERROR, Some(ErrorCode::InternalError)));
+        assert_eq!(state.storage.key_count().await, 0);
+        scenario.disarm(KEY_ENCRYPT);
+
+        // A key whose save fails is served from memory and written by the next flush
+        scenario.arm(KEYSTORE_WRITE, FailAction::FailNth(1));
+        let held = generate("Held", Some("hunter22")).await.unwrap().0.key_pair.unwrap();
+        assert!(state.storage.persistence_status().degraded);
+        assert!(state.storage.key_exists(held.id).await);
+        assert!(state.storage.flush().await.unwrap());
+        let restarted = KeyStorage::new(state.storage.storage_path());
+        restarted.load_from_disk().await.unwrap();
+        assert!(restarted.key_exists(held.id).await);
+
+        let sign = |document: &str| sign_document(State(state.clone()), Json(SignDocumentRequest {
+            key_id: held.id,
+            document_content: Some(document.to_string()),
+            password: Some("hunter22".to_string()),
+            ..Default::default()
+        }));
+
+        // Damaged ciphertext reads as a failed decryption, and the stored key is untouched
+        scenario.arm(KEY_DECRYPT, FailAction::Corrupt);
+        let (status, Json(refused)) = sign("invoice 1").await.unwrap_err();
+        assert_eq!((status, refused.code), (StatusCode::UNAUTHORIZED, Some(ErrorCode::DecryptionFailed)));
+        scenario.disarm(KEY_DECRYPT);
+
+        // One failed signature in a run fails alone, leaving no receipt or usage behind; each
+        // signature also signs its receipt's attestation, so the third hit is the second document
+        scenario.arm(SIGN, FailAction::FailNth(3));
+        let mut outcomes = Vec::new();
+        for document in ["invoice 1", "invoice 2", "invoice 3"] {
+            outcomes.push(match sign(document).await {
+                Ok(Json(signed)) => Ok(signed.signature_id.unwrap()),
+                Err((status, Json(refused))) => Err((status, refused.code)),
+            });
+        }
+        assert_eq!(outcomes[1], Err((StatusCode::INTERNAL_SERVER_ERROR, Some(ErrorCode::InternalError))));
+        assert!(outcomes[0].is_ok() && outcomes[2].is_ok());
+        assert_eq!(state.receipts.count().await, 2);
+        assert_eq!(state.storage.get_key_record(held.id).await.unwrap().usage.sign_count, 2);
+    }
+
+    #[tokio::test]
+    async fn test_key_cursors_neither_skip_nor_repeat_keys_created_or_deleted_between_pages() {
+        let dir = tempdir().unwrap();
+        let clock = Arc::new(MockClock::new(Utc::now()));
+        let state = test_state(&dir, clock.clone());
+        // Creation times are kept to the millisecond; keys created apart list in creation order
+        let generate = |name: String| {
+            let state = state.clone();
+            clock.advance(Duration::milliseconds(2));
+            async move {
+                generate_keys(State(state), Query(GenerateKeyQuery::default()), Json(GenerateKeyRequest {
+                    name,
+                    tags: Some(vec!["sync".to_string()]),
+                    ..Default::default()
+                })).await
+            }
+        };
+        let mut expected = Vec::new();
+        for i in 0..7 {
+            expected.push(generate(format!("Before {}", i)).await.unwrap().0.key_pair.unwrap().id);
+        }
+        // Keys are stamped by the injected clock, not the system time
+        let last = state.storage.get_key_record(expected[6]).await.unwrap();
+        assert_eq!(last.created_at, crate::timestamps::normalize(clock.now()));
+        let page = |cursor: Option<String>| list_keys(State(state.clone()), Query(ListKeysQuery {
+            tags: Some("sync".to_string()),
+            limit: Some(3),
+            cursor,
+            ..Default::default()
+        }), None);
+
+        // Each page, a listed key is deleted and a key is created, which would shift offsets
+        let mut listed = Vec::new();
+        let mut cursor = None;
+        for round in 0.. {
+            let response = page(cursor).await.unwrap().0;
+            listed.extend(response.keys.iter().map(|key| key.id));
+            cursor = response.next_cursor;
+            if cursor.is_none() {
+                break;
+            }
+            assert!(delete_key(State(state.clone()), Path(listed[round]), None).await.unwrap().success);
+            expected.push(generate(format!("During {}", round)).await.unwrap().0.key_pair.unwrap().id);
+        }
+        assert_eq!(listed, expected);
+
+        let search = search_keys(State(state.clone()), Query(ListKeysQuery { search: Some("during".to_string()), limit: Some(1), ..Default::default() }), None).await.unwrap().0;
+        let next = search_keys(State(state.clone()), Query(ListKeysQuery { search: Some("during".to_string()), cursor: search.next_cursor, ..Default::default() }), None).await.unwrap().0;
+        assert_eq!(search.matched_count, 2);
+        assert_eq!(search.keys.iter().chain(&next.keys).map(|key| key.id).collect::<Vec<_>>(), expected[7..]);
+        assert!(next.next_cursor.is_none());
+
+        for query in [
+            ListKeysQuery { cursor: Some("not a cursor".to_string()), ..Default::default() },
+            ListKeysQuery { cursor: page(None).await.unwrap().0.next_cursor, offset: 3, ..Default::default() },
+        ] {
+            let rejected = list_keys(State(state.clone()), Query(query), None).await.unwrap_err();
+            assert_eq!(rejected.status(), StatusCode::BAD_REQUEST);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_request_timestamps_are_normalized_to_utc_and_naive_ones_refused() {
+        use axum::body::Body;
+        use tower::ServiceExt;
+
+        let dir = tempdir().unwrap();
+        let state = test_state(&dir, Arc::new(MockClock::new(Utc::now())));
+        let app = crate::routes::router_with_versions(state.clone(), ApiVersion::ALL);
+        let call = |method: Method, path: String, body: serde_json::Value| {
+            let app = app.clone();
+            async move {
+                let request = axum::http::Request::builder().method(method).uri(format!("/v1{}", path))
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(body.to_string())).unwrap();
+                let response = app.oneshot(request).await.unwrap();
+                let status = response.status();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                (status, serde_json::from_slice::<serde_json::Value>(&body).unwrap())
+            }
+        };
+        let year = chrono::Datelike::year(&Utc::now()) + 1;
+
+        // A local offset with nanoseconds is stored and returned as the same instant in UTC
+        let (status, generated) = call(Method::POST, "/keys/generate".to_string(), serde_json::json!({
+            "name": "Offset",
+            "expires_at": format!("{}-06-01T10:30:00.123456789+01:00", year),
+        })).await;
+        assert_eq!(status, StatusCode::OK);
+        let expected = format!("{}-06-01T09:30:00.123Z", year);
+        assert_eq!(generated["key_pair"]["expires_at"], expected.as_str());
+        let key_id: Uuid = generated["key_pair"]["id"].as_str().unwrap().parse().unwrap();
+        let (_, updated) = call(Method::PATCH, format!("/keys/{}", key_id), serde_json::json!({
+            "expires_at": format!("{}-06-01T04:30:00.123999-05:00", year),
+        })).await;
+        assert_eq!(updated["key_info"]["expires_at"], expected.as_str());
+        let restarted = KeyStorage::new(state.storage.storage_path());
+        restarted.load_from_disk().await.unwrap();
+        assert_eq!(restarted.get_key_record(key_id).await.unwrap().expires_at, state.storage.get_key_record(key_id).await.unwrap().expires_at);
+
+        // Without an offset the time is ambiguous, and is refused with the expected format
+        for (path, body) in [
+            ("/keys/generate".to_string(), serde_json::json!({ "name": "Naive", "expires_at": format!("{}-06-01T10:30:00", year) })),
+            (format!("/keys/{}", key_id), serde_json::json!({ "expires_at": format!("{}-06-01 10:30:00", year) })),
+            ("/sign".to_string(), serde_json::json!({ "key_id": key_id, "document_content": "memo", "valid_until": format!("{}-06-01T10:30:00.5", year) })),
+        ] {
+            let method = if path == "/keys/generate" || path == "/sign" { Method::POST } else { Method::PATCH };
+            let (status, refused) = call(method, path.clone(), body).await;
+            assert_eq!((status, refused["code"].as_str()), (StatusCode::UNPROCESSABLE_ENTITY, Some("VALIDATION_FAILED")), "{}", path);
+            assert!(refused["message"].as_str().unwrap().contains("has no UTC offset"), "{}", refused);
+        }
+        assert_eq!(state.storage.key_count().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_verifier_started_from_trust_manifest_verifies_and_refuses_tampered_manifests() {
+        use crate::config::TrustManifestConfig;
+        use axum::body::Body;
+        use tower::ServiceExt;
+        let dir = tempdir().unwrap();
+        let clock = Arc::new(MockClock::new(Utc::now()));
+
+        // One instance signs a document and publishes its keys as a pin set signed by the root
+        let root = generate_seeded_test_key_pair("Trust Root", 1);
+        let signer = test_state(&dir, clock.clone());
+        signer.storage.store_key(root.clone()).await.unwrap();
+        let signer = Arc::new(AppState {
+            config: Arc::new(Config { notary_key_id: Some(root.id), ..Config::default() }),
+            ..Arc::into_inner(signer).unwrap()
+        });
+        let key_pair = generate_seeded_test_key_pair("Release Signing", 0);
+        signer.storage.store_key(KeyPair { tags: vec!["release".to_string()], ..key_pair.clone() }).await.unwrap();
+        let signed = sign_document(State(signer.clone()), Json(SignDocumentRequest {
+            key_id: key_pair.id,
+            document_content: Some("firmware 2.4.1".to_string()),
+            ..Default::default()
+        })).await.unwrap().0;
+        let response = crate::routes::router_with_versions(signer, ApiVersion::ALL)
+            .oneshot(axum::http::Request::get("/v1/keys/pinset?tags=release").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let manifest = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let manifest_path = dir.path().join("pinset.json");
+        std::fs::write(&manifest_path, &manifest).unwrap();
+
+        // A verifier with no keystore of its own boots from the manifest
+        let offline = tempdir().unwrap();
+        let manifest_config = TrustManifestConfig {
+            path: manifest_path.to_str().unwrap().to_string(),
+            root_public_key: root.public_key.clone(),
+        };
+        let verifier = Arc::new(AppState {
+            config: Arc::new(Config {
+                profile: DeploymentProfile::Verifier,
+                trust_manifest: Some(manifest_config.clone()),
+                ..Config::default()
+            }),
+            read_only: AtomicBool::new(true),
+            follower: true,
+            ..Arc::into_inner(test_state(&offline, clock.clone())).unwrap()
+        });
+        let installed = crate::trust_manifest::install(&verifier.storage, &manifest_config, None).await.unwrap();
+        assert_eq!(installed.keys.len(), 1);
+        // Only the filter tags travel in the manifest, so the served key carries none
+        assert!(verifier.storage.get_key_record(key_pair.id).await.unwrap().tags.is_empty());
+
+        let app = crate::routes::router_with_versions(verifier.clone(), ApiVersion::ALL);
+        let call = |method: Method, uri: String, body: Option<serde_json::Value>| {
+            let request = axum::http::Request::builder()
+                .method(method)
+                .uri(uri)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(body.map_or_else(Body::empty, |body| Body::from(body.to_string())))
+                .unwrap();
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(request).await.unwrap();
+                let (status, location) = (response.status(), response.headers().get(header::LOCATION).cloned());
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                (status, location, serde_json::from_slice::<serde_json::Value>(&body).unwrap_or_default())
+            }
+        };
+        let (status, _, body) = call(Method::POST, "/v1/verify".to_string(), Some(serde_json::json!({
+            "key_id": key_pair.id,
+            "signature": signed.signature,
+            "document_hash": signed.document_hash,
+        }))).await;
+        assert_eq!((status, body["is_valid"].as_bool()), (StatusCode::OK, Some(true)));
+        let (status, _, body) = call(Method::GET, format!("/v1/keys/{}/status", key_pair.id), None).await;
+        assert_eq!((status, body["state"].as_str()), (StatusCode::OK, Some("active")));
+        let (status, _, body) = call(Method::GET, format!("/v1/keys/{}/public", key_pair.id), None).await;
+        assert_eq!((status, body["key_info"]["public_key"].as_str()), (StatusCode::OK, Some(key_pair.public_key.as_str())));
+        let (status, location, _) = call(Method::GET, format!("/v1/keys/{}/public/permalink?format=jwk", key_pair.id), None).await;
+        assert_eq!(status, StatusCode::TEMPORARY_REDIRECT);
+        let (status, _, jwk) = call(Method::GET, location.unwrap().to_str().unwrap().to_string(), None).await;
+        assert_eq!((status, jwk["kty"].as_str()), (StatusCode::OK, Some("OKP")));
+        // The root only vouches for the manifest; it is not one of the served keys
+        assert_eq!(call(Method::GET, format!("/v1/keys/{}/status", root.id), None).await.0, StatusCode::NOT_FOUND);
+
+        // Mutating routes are not served, and nothing is written next to the verifier
+        let (status, _, _) = call(Method::POST, "/v1/keys/generate".to_string(), Some(serde_json::json!({ "name": "Rogue" }))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _, _) = call(Method::POST, "/v1/sign".to_string(), Some(serde_json::json!({
+            "key_id": key_pair.id,
+            "document_content": "firmware 2.4.2",
+        }))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(call(Method::DELETE, format!("/v1/keys/{}", key_pair.id), None).await.0, StatusCode::NOT_FOUND);
+        assert!(!offline.path().join("keys.json").exists());
+
+        // A tampered manifest is refused and the verified keys stay in place
+        let mut tampered: crate::pinset::SignedPinset = serde_json::from_slice(&manifest).unwrap();
+        tampered.pinset.keys[0].expires_at = None;
+        tampered.pinset.keys[0].name = "Release Signing (extended)".to_string();
+        std::fs::write(&manifest_path, serde_json::to_vec(&tampered).unwrap()).unwrap();
+        let installed_at = installed.generated_at;
+        let installed = Some(installed_at);
+        let error = crate::trust_manifest::install(&verifier.storage, &manifest_config, installed).await.unwrap_err();
+        assert!(matches!(error, KeyManagementError::SignatureVerificationFailed(_)), "{:?}", error);
+        assert_eq!(verifier.storage.get_key_record(key_pair.id).await.unwrap().name, "Release Signing");
+
+        tampered.notary = None;
+        std::fs::write(&manifest_path, serde_json::to_vec(&tampered).unwrap()).unwrap();
+        let error = crate::trust_manifest::install(&verifier.storage, &manifest_config, installed).await.unwrap_err();
+        assert!(error.to_string().contains("not signed"), "{}", error);
+
+        // The manifest already served, or an older one, cannot be replayed over it
+        let root_key = crate::key_verification::load_signing_key(root.private_key.expose_for_signing(), None, &crate::config::KdfParams::default(), None).unwrap();
+        let resign = |generated_at| {
+            let pinset = crate::pinset::Pinset::new(std::slice::from_ref(&key_pair), &[], generated_at);
+            std::fs::write(&manifest_path, serde_json::to_vec(&pinset.sign(Some((root.id, &root_key))).unwrap()).unwrap()).unwrap();
+        };
+        for generated_at in [installed_at, installed_at - chrono::Duration::minutes(5)] {
+            resign(generated_at);
+            let error = crate::trust_manifest::install(&verifier.storage, &manifest_config, installed).await.unwrap_err();
+            assert!(error.to_string().contains("not after the installed manifest"), "{}", error);
+        }
+        resign(installed_at + chrono::Duration::minutes(5));
+        let reloaded = crate::trust_manifest::install(&verifier.storage, &manifest_config, installed).await.unwrap();
+        assert_eq!(reloaded.generated_at, installed_at + chrono::Duration::minutes(5));
+    }
+}
+
+// Runs in every build, so verifier-only builds prove the profile they are compiled for
+#[cfg(test)]
+mod verifier_tests {
+    use super::*;
+    use crate::clock::MockClock;
+    use crate::key_generation::generate_seeded_test_key_pair;
+    use crate::key_verification::{load_signing_key, sign_document_hash};
+    use crate::profile::VERIFIER_ROUTES;
+    use axum::body::Body;
+    use chrono::Utc;
+    use tempfile::tempdir;
+    use tower::ServiceExt;
+
+    fn verifier_state(dir: &tempfile::TempDir, clock: Arc<MockClock>) -> Arc<AppState> {
+        let storage_path = dir.path().join("keys.json");
+        let config = Config { profile: DeploymentProfile::Verifier, ..Config::default() };
+        Arc::new(AppState {
+            storage: Arc::new(KeyStorage::new(storage_path.to_str().unwrap()).with_clock(clock.clone()).with_public_only(true)),
+            clock,
+            receipts: Arc::new(ReceiptStore::new(dir.path().join("receipts.json").to_str().unwrap())),
+            certifications: Arc::new(CertificationStore::new(dir.path().join("certifications.json").to_str().unwrap())),
+            read_only: AtomicBool::new(true),
+            self_test: RwLock::new(None),
+            keystore_load: None,
+            entropy: Arc::new(EntropyMonitor::os()),
+            hsm: None,
+            follower: true,
+            limits: OperationLimits::default(),
+            shares: Arc::new(ShareStore::new(dir.path().join("shares.json").to_str().unwrap())),
+            delegations: Arc::new(DelegationStore::new(dir.path().join("delegations.json").to_str().unwrap())),
+            verification_cache: VerificationCache::disabled(),
+            request_auth: RequestAuthenticator::disabled(),
+            capacity: KeystoreCapacity::unlimited(),
+            kdf_timings: KdfTimings::new(),
+            verify_rate_limit: ClientRateLimiter::unlimited(),
+            sign_policy: Arc::new(SignPolicy::allow_all()),
+            key_resolver: Arc::new(KeyResolver::none()),
+            #[cfg(not(feature = "verifier-only"))]
+            transport_key: Arc::new(TransportKey::generate(Utc::now())),
+            sweeper: Arc::new(TaskStatus::default()),
+            deadlines: RequestDeadlines::from_config(&config),
+            api_usage: ApiUsage::default(),
+            #[cfg(not(feature = "verifier-only"))]
+            key_pool: Arc::new(KeyPool::from_config(&config)),
+            usage_reports: UsageReportCache::default(),
+            slo: SloTracker::from_config(&config),
+            config: Arc::new(config),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_verifier_profile_serves_only_public_routes() {
+        let dir = tempdir().unwrap();
+        let clock = Arc::new(MockClock::new(Utc::now()));
+
+        // A full instance writes the keystore the verifier reads; the signature is made outside
+        // the service, since the verifier cannot sign
+        let key_pair = generate_seeded_test_key_pair("Published Key", 5);
+        let owner = KeyStorage::new(dir.path().join("keys.json").to_str().unwrap());
+        owner.store_key(key_pair.clone()).await.unwrap();
+        let (private_key, salt) = key_pair.signing_secrets();
+        let signing_key = load_signing_key(private_key, salt, &key_pair.kdf.unwrap_or_default(), None).unwrap();
+        let document_hash = DocumentHash::digest(b"press release");
+        let signature = sign_document_hash(&signing_key, &document_hash, None, None, None).unwrap().to_string();
+
+        let verifier = verifier_state(&dir, clock);
+        verifier.storage.load_from_disk().await.unwrap();
+        assert!(verifier.storage.get_key_record(key_pair.id).await.unwrap().private_key.is_empty());
+        verifier.storage.record_verify(key_pair.id).await.unwrap();
+        assert!(verifier.storage.flush().await.is_err());
+
+        let app = crate::routes::router_with_versions(verifier.clone(), ApiVersion::ALL);
+        let call = |method: Method, uri: String, body: Option<serde_json::Value>| {
+            let request = axum::http::Request::builder()
+                .method(method)
+                .uri(uri)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(body.map_or_else(Body::empty, |body| Body::from(body.to_string())))
+                .unwrap();
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(request).await.unwrap();
+                let (status, location) = (response.status(), response.headers().get(header::LOCATION).cloned());
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                (status, location, serde_json::from_slice::<serde_json::Value>(&body).unwrap_or_default())
+            }
+        };
+
+        // Verification, key listing, status and public keys work without the private key
+        let (status, _, body) = call(Method::POST, "/v1/verify".to_string(), Some(serde_json::json!({
+            "key_id": key_pair.id,
+            "signature": signature,
+            "document_hash": document_hash.to_string(),
+        }))).await;
+        assert_eq!((status, body["is_valid"].as_bool()), (StatusCode::OK, Some(true)));
+        let (status, _, body) = call(Method::GET, "/v1/keys".to_string(), None).await;
+        assert_eq!((status, body["keys"][0]["id"].as_str()), (StatusCode::OK, Some(key_pair.id.to_string().as_str())));
+        let (status, _, body) = call(Method::GET, format!("/v1/keys/{}/status", key_pair.id), None).await;
+        assert_eq!((status, body["state"].as_str()), (StatusCode::OK, Some("active")));
+        let (status, _, body) = call(Method::GET, format!("/v1/keys/{}/public", key_pair.id), None).await;
+        assert_eq!((status, body["key_info"]["public_key"].as_str()), (StatusCode::OK, Some(key_pair.public_key.as_str())));
+        let (status, location, _) = call(Method::GET, format!("/v1/keys/{}/public/permalink?format=jwk", key_pair.id), None).await;
+        assert_eq!(status, StatusCode::TEMPORARY_REDIRECT);
+        let (status, _, jwk) = call(Method::GET, location.unwrap().to_str().unwrap().to_string(), None).await;
+        assert_eq!((status, jwk["kty"].as_str()), (StatusCode::OK, Some("OKP")));
+
+        // /about and the capabilities describe the profile and exactly what it serves
+        let (_, _, about) = call(Method::GET, "/v1/about".to_string(), None).await;
+        assert_eq!(about["profile"], "verifier");
+        let endpoints: Vec<crate::capabilities::EndpointInfo> = serde_json::from_value(about["endpoints"].clone()).unwrap();
+        assert_eq!(endpoints.len(), VERIFIER_ROUTES.len());
+        assert_eq!(endpoints, crate::routes::served_endpoints(DeploymentProfile::Verifier));
+        let (_, _, capabilities) = call(Method::GET, "/v1/capabilities".to_string(), None).await;
+        assert_eq!(capabilities["capabilities"]["profile"], "verifier");
+
+        // Every other route, signing, generation, import, backup and export among them, is not found
+        let concrete = |path: &str| path.split('/')
+            .map(|segment| if segment.starts_with(':') { key_pair.id.to_string() } else { segment.to_string() })
+            .collect::<Vec<_>>()
+            .join("/");
+        let excluded: Vec<_> = crate::routes::endpoint_list().iter().filter(|endpoint| !endpoints.contains(endpoint)).collect();
+        assert!(excluded.iter().any(|endpoint| endpoint.path == "/sign"));
+        assert!(excluded.iter().any(|endpoint| endpoint.path == "/keys/:key_id/export"));
+        for endpoint in excluded {
+            let method: Method = endpoint.method.parse().unwrap();
+            let (status, _, _) = call(method, format!("/v1{}", concrete(&endpoint.path)), Some(serde_json::json!({}))).await;
+            assert_eq!(status, StatusCode::NOT_FOUND, "{} {}", endpoint.method, endpoint.path);
+        }
+        assert_eq!(verifier.storage.key_count().await, 1);
+    }
 }
@@ -0,0 +1,154 @@
+//! API versions
+//!
+//! The API is served under a version prefix such as `/v1`. The prefix is stripped before
+//! routing, so every version shares one route table, and the request carries its
+//! [`RequestedVersion`] for the layers that make versions differ. Versions differ only in
+//! response shape:
+//!
+//! - `v1` keeps the configured status policy and field case (`INKAN_LEGACY_ENVELOPE` and
+//!   `INKAN_FIELD_CASE`).
+//! - `v2` answers every failure with its error code's status, never `200`, and names fields in
+//!   camelCase unless `X-Field-Case` asks for snake case. It is served when `INKAN_API_V2` is set.
+//!
+//! Unprefixed paths are deprecated aliases of `/v1`. Their responses carry `Deprecation`,
+//! `Sunset` and a `Link` to the `/v1` path, and JSON envelopes get a `deprecation` object with
+//! warning code `DEPRECATED_PATH`.
+//! Health probes, metrics and public key permalinks under `/public/` are not part of the
+//! versioned API: infrastructure and CDNs fetch them, and published links must keep working.
+//! They are not deprecated, though they are also served under each prefix.
+//!
+//! Adding a version means adding a variant to [`ApiVersion`] and its arms in the policy methods.
+
+use crate::field_case::FieldCase;
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+/// When unprefixed paths were deprecated, 2026-10-16T00:00:00Z
+pub const UNVERSIONED_DEPRECATED_AT: i64 = 1_792_108_800;
+/// When unprefixed paths are due to be removed unless `INKAN_UNVERSIONED_SUNSET` says otherwise,
+/// 2027-04-30T00:00:00Z
+pub const DEFAULT_UNVERSIONED_SUNSET: i64 = 1_809_043_200;
+
+/// Label of requests made through the deprecated unprefixed paths, in metrics
+pub const UNVERSIONED_LABEL: &str = "unversioned";
+
+/// A version of the API surface
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ApiVersion {
+    V1,
+    V2,
+}
+
+impl ApiVersion {
+    /// Every version, oldest first
+    pub const ALL: &'static [ApiVersion] = &[ApiVersion::V1, ApiVersion::V2];
+
+    /// Version the unprefixed paths alias
+    pub const UNVERSIONED_ALIAS: ApiVersion = ApiVersion::V1;
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ApiVersion::V1 => "v1",
+            ApiVersion::V2 => "v2",
+        }
+    }
+
+    /// Path prefix the version is served under
+    pub fn prefix(self) -> &'static str {
+        match self {
+            ApiVersion::V1 => "/v1",
+            ApiVersion::V2 => "/v2",
+        }
+    }
+
+    /// Field case of responses when the request does not ask for one
+    pub fn default_field_case(self, configured: FieldCase) -> FieldCase {
+        match self {
+            ApiVersion::V1 => configured,
+            ApiVersion::V2 => FieldCase::Camel,
+        }
+    }
+
+    /// Whether every failure is answered with its error code's status, whatever the legacy envelope says
+    pub fn failures_carry_status(self) -> bool {
+        match self {
+            ApiVersion::V1 => false,
+            ApiVersion::V2 => true,
+        }
+    }
+}
+
+/// The version a request addressed, and whether it came through a deprecated unprefixed path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestedVersion {
+    pub version: ApiVersion,
+    pub deprecated_alias: bool,
+}
+
+impl RequestedVersion {
+    /// Label of the request in metrics
+    pub fn label(self) -> &'static str {
+        if self.deprecated_alias {
+            UNVERSIONED_LABEL
+        } else {
+            self.version.as_str()
+        }
+    }
+}
+
+/// Splits the prefix of one of `served` off `path`, giving the version and the path within it
+pub fn split_version<'a>(path: &'a str, served: &[ApiVersion]) -> Option<(ApiVersion, &'a str)> {
+    served.iter().find_map(|&version| {
+        let rest = path.strip_prefix(version.prefix())?;
+        match rest {
+            "" => Some((version, "/")),
+            _ if rest.starts_with('/') => Some((version, rest)),
+            _ => None,
+        }
+    })
+}
+
+/// Paths outside the versioned API, which infrastructure and published links reach without a prefix
+pub fn is_unversioned_path(path: &str) -> bool {
+    matches!(path, "/health" | "/health/ready" | "/metrics") || path.starts_with("/public/")
+}
+
+/// `Sunset` header value, an HTTP date
+pub fn sunset_header(sunset: DateTime<Utc>) -> String {
+    sunset.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+/// Requests by the label of the version they addressed, for metrics
+#[derive(Default)]
+pub struct ApiUsage(Mutex<BTreeMap<&'static str, u64>>);
+
+impl ApiUsage {
+    pub fn record(&self, requested: RequestedVersion) {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner()).entry(requested.label()).or_default() += 1;
+    }
+
+    pub fn snapshot(&self) -> Vec<(&'static str, u64)> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).iter().map(|(label, count)| (*label, *count)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_served_prefixes_are_split_off() {
+        let both = ApiVersion::ALL;
+        assert_eq!(split_version("/v1/keys/stats", both), Some((ApiVersion::V1, "/keys/stats")));
+        assert_eq!(split_version("/v2", both), Some((ApiVersion::V2, "/")));
+        assert_eq!(split_version("/v2/keys", &[ApiVersion::V1]), None);
+        assert_eq!(split_version("/v10/keys", both), None);
+        assert_eq!(split_version("/keys/v1", both), None);
+        assert!(is_unversioned_path("/public/0123456789abcdef0123456789abcdef.pem"));
+        assert!(!is_unversioned_path("/keys/stats"));
+
+        let sunset = DateTime::from_timestamp(DEFAULT_UNVERSIONED_SUNSET, 0).unwrap();
+        assert_eq!(sunset_header(sunset), "Fri, 30 Apr 2027 00:00:00 GMT");
+    }
+}
@@ -0,0 +1,240 @@
+//! Verification of a zip archive against a manifest of per-entry signatures
+//!
+//! A manifest maps entry paths to the signature over that entry. Each entry is decompressed a
+//! buffer at a time into its SHA-256, so no entry is ever held whole, and the decompressed bytes
+//! are counted against both a per-entry expansion ratio and a total, which stops a zip bomb at
+//! the limit rather than after it has been inflated. The zip format keeps its directory at the
+//! end, so the archive itself is read from memory.
+
+use crate::capabilities::HashAlgorithm;
+use crate::models::{ErrorCode, KeyManagementError};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::io::Read;
+use uuid::Uuid;
+
+/// Longest path one manifest entry may have
+pub const MAX_ARCHIVE_PATH_LENGTH: usize = 1_024;
+/// Entries up to this many bytes decompressed are not held to the expansion ratio, since small
+/// files of repetitive text legitimately compress very well
+pub const RATIO_EXEMPT_BYTES: u64 = 64 * 1024;
+/// Bytes decompressed per read
+const READ_BUFFER_BYTES: usize = 64 * 1024;
+
+/// The signature over one archive entry
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EntrySignature {
+    pub signature: String,
+    #[serde(default, alias = "publicKey")]
+    pub public_key: String,
+    #[serde(default, alias = "keyId")]
+    pub key_id: Option<Uuid>,
+    #[serde(default = "default_hash_algorithm", alias = "hashAlgorithm")]
+    pub hash_algorithm: HashAlgorithm, // Only sha256, the digest raw signatures cover
+    #[serde(default)]
+    pub context: Option<String>,
+    #[serde(default, alias = "validUntil")]
+    pub valid_until: Option<DateTime<Utc>>,
+    #[serde(default, alias = "signingTime")]
+    pub signing_time: Option<DateTime<Utc>>,
+}
+
+fn default_hash_algorithm() -> HashAlgorithm {
+    HashAlgorithm::Sha256
+}
+
+/// Signatures by entry path, as uploaded alongside an archive
+#[derive(Debug, Clone, Deserialize)]
+#[serde(transparent)]
+pub struct ArchiveManifest {
+    pub entries: BTreeMap<String, EntrySignature>,
+}
+
+impl ArchiveManifest {
+    /// Parses a manifest, refusing empty ones and ones with more than `max_entries` entries
+    pub fn parse(bytes: &[u8], max_entries: usize) -> Result<Self, KeyManagementError> {
+        let manifest: Self = serde_json::from_slice(bytes)
+            .map_err(|e| KeyManagementError::ValidationFailed(format!("Invalid archive manifest: {}", e)))?;
+        if manifest.entries.is_empty() {
+            return Err(KeyManagementError::ValidationFailed("An archive manifest must list at least one entry".to_string()));
+        }
+        if manifest.entries.len() > max_entries {
+            return Err(KeyManagementError::ValidationFailed(format!("An archive manifest may list at most {} entries", max_entries)));
+        }
+        if let Some(path) = manifest.entries.keys().find(|path| path.is_empty() || path.len() > MAX_ARCHIVE_PATH_LENGTH || path.chars().any(char::is_control)) {
+            return Err(KeyManagementError::ValidationFailed(format!(
+                "Entry path '{}' must be 1 to {} bytes without control characters", path.escape_debug(), MAX_ARCHIVE_PATH_LENGTH,
+            )));
+        }
+        Ok(manifest)
+    }
+}
+
+/// How far an archive may expand while it is read
+#[derive(Debug, Clone, Copy)]
+pub struct ArchiveLimits {
+    pub max_entries: usize,
+    pub max_ratio: u64, // Decompressed bytes allowed per compressed byte of one entry
+    pub max_uncompressed_bytes: u64, // Across every entry
+}
+
+/// The SHA-256 of an entry, or why it could not be read
+pub type EntryDigest = Result<String, String>;
+
+/// Hashes every file entry of a zip archive, by path
+///
+/// An archive with too many entries, a duplicate path, or an entry that expands past the limits
+/// is refused as a whole. An entry that cannot be decompressed, such as an encrypted one, is
+/// reported alone.
+pub fn hash_entries(archive: &[u8], limits: ArchiveLimits) -> Result<BTreeMap<String, EntryDigest>, KeyManagementError> {
+    let invalid = |message: String| KeyManagementError::ValidationFailed(message);
+    let mut zip = zip::ZipArchive::new(std::io::Cursor::new(archive))
+        .map_err(|e| invalid(format!("Archive is not a readable zip file: {}", e)))?;
+    if zip.len() > limits.max_entries {
+        return Err(invalid(format!("An archive may hold at most {} entries", limits.max_entries)));
+    }
+
+    let mut digests = BTreeMap::new();
+    let mut remaining = limits.max_uncompressed_bytes;
+    let mut buffer = vec![0u8; READ_BUFFER_BYTES];
+    for index in 0..zip.len() {
+        let name = zip.name_for_index(index).unwrap_or_default().to_string();
+        let digest = match zip.by_index(index) {
+            Ok(entry) if entry.is_dir() => continue,
+            Ok(mut entry) => {
+                let allowed = entry.compressed_size().saturating_mul(limits.max_ratio).max(RATIO_EXEMPT_BYTES);
+                let too_large = |read: u64| if read > allowed {
+                    invalid(format!("'{}' expands more than {} times its compressed size", name, limits.max_ratio))
+                } else {
+                    KeyManagementError::ContentTooLarge { field: "archive", limit: limits.max_uncompressed_bytes }
+                };
+                // Declared sizes are refused up front, and actual sizes as they are read, since
+                // the declared ones may lie
+                if entry.size() > allowed.min(remaining) {
+                    return Err(too_large(entry.size()));
+                }
+                let mut hasher = Sha256::new();
+                let mut read = 0u64;
+                let digest = loop {
+                    match entry.read(&mut buffer) {
+                        Ok(0) => break Ok(hex::encode(hasher.finalize())),
+                        Ok(n) => {
+                            read += n as u64;
+                            if read > allowed.min(remaining) {
+                                return Err(too_large(read));
+                            }
+                            hasher.update(&buffer[..n]);
+                        }
+                        Err(e) => break Err(format!("Entry cannot be decompressed: {}", e)),
+                    }
+                };
+                remaining -= read;
+                digest
+            }
+            Err(e) => Err(format!("Entry cannot be read: {}", e)),
+        };
+        if digests.insert(name.clone(), digest).is_some() {
+            return Err(invalid(format!("'{}' appears more than once in the archive", name)));
+        }
+    }
+    Ok(digests)
+}
+
+/// How one manifest entry fared
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryStatus {
+    Valid,
+    Invalid, // The signature does not verify over the entry
+    Missing, // In the manifest but not the archive
+    Error, // Could not be checked; `code` and `message` say why
+}
+
+/// The result for one manifest entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EntryCheck {
+    pub path: String,
+    pub status: EntryStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>, // Digest of the entry as found in the archive
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<ErrorCode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// Entries of a report by status
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EntryCounts {
+    pub total: usize, // Manifest entries
+    pub valid: usize,
+    pub invalid: usize,
+    pub missing: usize,
+    pub error: usize,
+    pub uncovered: usize, // Archive entries the manifest does not list
+}
+
+/// Everything found checking an archive against its manifest
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ArchiveReport {
+    pub entries: Vec<EntryCheck>, // In manifest path order
+    pub uncovered: Vec<String>,
+    pub counts: EntryCounts,
+}
+
+impl ArchiveReport {
+    /// Builds the report from the checks and the archive entries no check covered
+    pub fn new(entries: Vec<EntryCheck>, uncovered: Vec<String>) -> Self {
+        let count = |status: EntryStatus| entries.iter().filter(|entry| entry.status == status).count();
+        let counts = EntryCounts {
+            total: entries.len(),
+            valid: count(EntryStatus::Valid),
+            invalid: count(EntryStatus::Invalid),
+            missing: count(EntryStatus::Missing),
+            error: count(EntryStatus::Error),
+            uncovered: uncovered.len(),
+        };
+        Self { entries, uncovered, counts }
+    }
+
+    /// Every entry verified and the archive holds nothing else
+    pub fn all_valid(&self) -> bool {
+        self.counts.valid == self.counts.total && self.uncovered.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn archive(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        for (name, content) in entries {
+            zip.start_file(*name, options).unwrap();
+            zip.write_all(content).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_hash_entries_stops_at_expansion_limits() {
+        let limits = ArchiveLimits { max_entries: 10, max_ratio: 100, max_uncompressed_bytes: 1024 * 1024 };
+        let digests = hash_entries(&archive(&[("a.txt", b"alpha"), ("docs/b.txt", b"beta")]), limits).unwrap();
+        assert_eq!(digests["a.txt"], Ok(crate::key_verification::create_document_hash("alpha")));
+        assert_eq!(digests.len(), 2);
+
+        // Zeros deflate about a thousandfold, far past the ratio once over the exempt size
+        let bomb = archive(&[("zeros.bin", &vec![0u8; 512 * 1024])]);
+        assert!(matches!(hash_entries(&bomb, limits), Err(KeyManagementError::ValidationFailed(m)) if m.contains("expands")));
+        let small_total = ArchiveLimits { max_uncompressed_bytes: 4, ..limits };
+        assert!(matches!(hash_entries(&archive(&[("a.txt", b"alpha")]), small_total), Err(KeyManagementError::ContentTooLarge { .. })));
+        let few = ArchiveLimits { max_entries: 1, ..limits };
+        assert!(hash_entries(&archive(&[("a", b"1"), ("b", b"2")]), few).is_err());
+        assert!(hash_entries(b"not a zip", limits).is_err());
+    }
+}
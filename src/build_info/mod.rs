@@ -0,0 +1,63 @@
+//! Build provenance
+//!
+//! For forensics, every signature records which build of the service made it. `build.rs`
+//! captures the git commit and build time; with the crate version they make up [`SignerInfo`],
+//! served at `GET /version`, returned with each signature, and carried in verification bundles
+//! and therefore in signature receipts. It is never part of the signed message, so the same key
+//! signs the same document identically whatever the build. In a bundle it is covered by the
+//! attestation and notary signatures like every other body field.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Name of this crate, identifying the service in [`SignerInfo`]
+pub const SERVICE_NAME: &str = env!("CARGO_PKG_NAME");
+/// Version of this crate
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// What the build script records in place of a value it could not determine, such as the
+/// commit of a build outside a git checkout
+const UNKNOWN_BUILD_VALUE: &str = "VERGEN_IDEMPOTENT_OUTPUT";
+
+/// The build of the service that made a signature
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SignerInfo {
+    pub service: String,
+    pub version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_commit: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub built_at: Option<DateTime<Utc>>,
+}
+
+fn recorded(value: Option<&'static str>) -> Option<&'static str> {
+    value.filter(|value| !value.is_empty() && *value != UNKNOWN_BUILD_VALUE)
+}
+
+impl SignerInfo {
+    /// Provenance of the running build
+    pub fn current() -> Self {
+        Self {
+            service: SERVICE_NAME.to_string(),
+            version: VERSION.to_string(),
+            git_commit: recorded(option_env!("VERGEN_GIT_SHA")).map(str::to_string),
+            built_at: recorded(option_env!("VERGEN_BUILD_TIMESTAMP"))
+                .and_then(|built_at| DateTime::parse_from_rfc3339(built_at).ok())
+                .map(|built_at| built_at.with_timezone(&Utc)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_current_build_is_described() {
+        let info = SignerInfo::current();
+        assert_eq!((info.service.as_str(), info.version.as_str()), ("inkan-key-management-module", env!("CARGO_PKG_VERSION")));
+        assert!(info.built_at.is_some());
+        assert!(info.git_commit.as_deref().is_none_or(|commit| commit.len() == 40 && commit.chars().all(|c| c.is_ascii_hexdigit())));
+        assert_eq!(recorded(Some(UNKNOWN_BUILD_VALUE)), None);
+    }
+}
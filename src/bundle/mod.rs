@@ -0,0 +1,327 @@
+//! Portable verification bundles
+//!
+//! A bundle carries everything a third party needs to verify a signature offline: the document
+//! hash, the signature, the signing key and its status at signing time. The signing key also
+//! attests to the whole bundle body (in RFC 8785 canonical form) so no field can be altered
+//! without detection, and an optional notary key can counter-sign the same body.
+
+use crate::build_info::SignerInfo;
+use crate::canonicalize::{canonicalize_json, canonicalize_value};
+use crate::key_verification::{content_bytes, create_document_hash, decode_public_key, encode_signing_message};
+use crate::models::{DocumentContentType, DocumentHash, KeyManagementError, MessageEncoding};
+use crate::signing_backend::KeySigner;
+use crate::utils::public_key_to_fingerprint;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Schema identifier carried in every bundle
+pub const BUNDLE_SCHEMA: &str = "inkan-verification-bundle";
+/// Current bundle schema version
+pub const BUNDLE_VERSION: u32 = 1;
+/// Domain tag prefixed to the canonical body for the signer's attestation
+pub const ATTESTATION_CONTEXT: &[u8] = b"inkan-bundle-attestation-v1";
+/// Domain tag prefixed to the canonical body for the notary counter-signature
+pub const NOTARY_CONTEXT: &[u8] = b"inkan-bundle-notary-v1";
+
+/// Status of the signing key when the signature was made
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BundleKeyStatus {
+    pub active: bool,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Fields covered by the attestation and notary signatures
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BundleBody {
+    pub schema: String,
+    pub version: u32,
+    pub signature_id: Uuid,
+    pub key_id: Uuid,
+    pub document_hash: String, // Hex encoded
+    pub hash_algorithm: String,
+    pub content_type: DocumentContentType,
+    pub signature: String, // Base64 encoded
+    pub signature_algorithm: String,
+    pub signing_time: DateTime<Utc>,
+    pub valid_until: Option<DateTime<Utc>>,
+    /// Signing context bound into the signature; omitted when empty so older bundles still attest
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+    /// `signing_time` is bound into the signature; omitted when false so older bundles still attest
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub timestamp_bound: bool,
+    /// What the signature covers; omitted for `hash-raw-bytes` so older bundles still attest
+    #[serde(default, skip_serializing_if = "MessageEncoding::is_default")]
+    pub message_encoding: MessageEncoding,
+    pub public_key: String, // Base64 encoded
+    pub key_fingerprint: String,
+    pub key_status: BundleKeyStatus,
+    /// Build of the service that made the signature; omitted when absent so older bundles still attest
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signer_info: Option<SignerInfo>,
+}
+
+/// Counter-signature by a notary key over the bundle body
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct NotarySignature {
+    pub key_id: Uuid,
+    pub public_key: String,
+    pub signature: String,
+}
+
+/// A self-contained verification bundle
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Bundle {
+    #[serde(flatten)]
+    pub body: BundleBody,
+    pub attestation: String, // Signing key's signature over the canonical body
+    pub notary: Option<NotarySignature>,
+}
+
+/// What the bundle is checked against
+#[derive(Debug, Clone, Copy)]
+pub enum BundleSubject<'a> {
+    /// The original document content
+    Content(&'a str),
+    /// A hex SHA-256 document hash
+    Hash(&'a str),
+}
+
+/// Outcome of each check performed by [`verify_bundle`]
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct BundleVerification {
+    pub valid: bool, // Every check below passed
+    pub document_matches: bool,
+    pub fingerprint_matches: bool,
+    pub signature_valid: bool,
+    pub attestation_valid: bool,
+    pub notary_valid: Option<bool>, // None when the bundle has no notary signature
+}
+
+/// Bytes the attestation or notary signature covers
+fn attested_message(context: &[u8], body: &BundleBody) -> Result<Vec<u8>, KeyManagementError> {
+    let value = serde_json::to_value(body)
+        .map_err(|e| KeyManagementError::InternalError(format!("Failed to serialize bundle: {}", e)))?;
+    let mut message = context.to_vec();
+    message.push(0);
+    message.extend_from_slice(canonicalize_value(&value)?.as_bytes());
+    Ok(message)
+}
+
+fn encode_signature(signature: &Signature) -> String {
+    base64::engine::general_purpose::STANDARD.encode(signature.to_bytes())
+}
+
+fn verify_encoded(public_key: &VerifyingKey, message: &[u8], signature_b64: &str) -> bool {
+    let Ok(bytes) = base64::engine::general_purpose::STANDARD.decode(signature_b64) else {
+        return false;
+    };
+    let Ok(bytes) = <[u8; 64]>::try_from(bytes.as_slice()) else {
+        return false;
+    };
+    public_key.verify(message, &Signature::from_bytes(&bytes)).is_ok()
+}
+
+impl Bundle {
+    /// Builds a bundle, attesting to its body with the key that made the document signature
+    pub fn new(body: BundleBody, signer: &dyn KeySigner) -> Result<Self, KeyManagementError> {
+        let attestation = signer.sign_message(&attested_message(ATTESTATION_CONTEXT, &body)?)?;
+        Ok(Self {
+            body,
+            attestation: encode_signature(&attestation),
+            notary: None,
+        })
+    }
+
+    /// Adds a notary counter-signature over the body
+    pub fn notarize(&mut self, notary_key_id: Uuid, notary_key: &SigningKey) -> Result<(), KeyManagementError> {
+        let signature = notary_key.sign(&attested_message(NOTARY_CONTEXT, &self.body)?);
+        self.notary = Some(NotarySignature {
+            key_id: notary_key_id,
+            public_key: base64::engine::general_purpose::STANDARD.encode(notary_key.verifying_key().to_bytes()),
+            signature: encode_signature(&signature),
+        });
+        Ok(())
+    }
+
+    /// Encodes the bundle as CBOR
+    pub fn to_cbor(&self) -> Result<Vec<u8>, KeyManagementError> {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(self, &mut bytes)
+            .map_err(|e| KeyManagementError::InternalError(format!("Failed to encode bundle as CBOR: {}", e)))?;
+        Ok(bytes)
+    }
+
+    /// Decodes a CBOR encoded bundle
+    pub fn from_cbor(bytes: &[u8]) -> Result<Self, KeyManagementError> {
+        ciborium::from_reader(bytes)
+            .map_err(|e| KeyManagementError::InvalidRequest(format!("Invalid CBOR bundle: {}", e)))
+    }
+}
+
+/// Verifies a bundle offline against the document content or its hash
+///
+/// Returns an error only for bundles whose schema or version is not understood; every other
+/// problem is reported through [`BundleVerification`].
+pub fn verify_bundle(bundle: &Bundle, subject: BundleSubject) -> Result<BundleVerification, KeyManagementError> {
+    let body = &bundle.body;
+    if body.schema != BUNDLE_SCHEMA || body.version != BUNDLE_VERSION {
+        return Err(KeyManagementError::InvalidRequest(format!(
+            "Unsupported bundle schema {} version {}",
+            body.schema, body.version
+        )));
+    }
+
+    let document_hash = match (subject, body.content_type) {
+        (BundleSubject::Hash(hash), _) => Some(hash.to_lowercase()),
+        (BundleSubject::Content(content), DocumentContentType::Text) => Some(create_document_hash(content)),
+        (BundleSubject::Content(content), DocumentContentType::JsonJcs) => {
+            canonicalize_json(content).ok().map(|canonical| create_document_hash(&canonical))
+        }
+    };
+    let document_matches = document_hash.as_deref() == Some(body.document_hash.as_str());
+
+    let fingerprint_matches = public_key_to_fingerprint(&body.public_key).ok().as_deref() == Some(body.key_fingerprint.as_str());
+
+    let (signature_valid, attestation_valid) = match decode_public_key(&body.public_key) {
+        Ok(public_key) => {
+            // A raw-message signature covers the content, so it only verifies against the content
+            let content = match subject {
+                BundleSubject::Content(content) if body.message_encoding == MessageEncoding::RawMessage => content_bytes(content, body.content_type).ok(),
+                _ => None,
+            };
+            let signature_valid = DocumentHash::from_hex(&body.document_hash)
+                .and_then(|hash| encode_signing_message(
+                    body.message_encoding,
+                    &hash,
+                    content.as_deref(),
+                    body.valid_until,
+                    body.context.as_deref(),
+                    body.timestamp_bound.then_some(body.signing_time),
+                ))
+                .is_ok_and(|message| verify_encoded(&public_key, &message, &body.signature));
+            let attestation_valid = attested_message(ATTESTATION_CONTEXT, body)
+                .map(|message| verify_encoded(&public_key, &message, &bundle.attestation))
+                .unwrap_or(false);
+            (signature_valid, attestation_valid)
+        }
+        Err(_) => (false, false),
+    };
+
+    let notary_valid = bundle.notary.as_ref().map(|notary| {
+        match (decode_public_key(&notary.public_key), attested_message(NOTARY_CONTEXT, body)) {
+            (Ok(public_key), Ok(message)) => verify_encoded(&public_key, &message, &notary.signature),
+            _ => false,
+        }
+    });
+
+    Ok(BundleVerification {
+        valid: document_matches && fingerprint_matches && signature_valid && attestation_valid && notary_valid != Some(false),
+        document_matches,
+        fingerprint_matches,
+        signature_valid,
+        attestation_valid,
+        notary_valid,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_verification::build_signing_message;
+    use rand_core::OsRng;
+
+    fn signed_bundle(content: &str) -> (Bundle, SigningKey) {
+        let signing_key = SigningKey::generate(&mut OsRng);
+        let public_key = base64::engine::general_purpose::STANDARD.encode(signing_key.verifying_key().to_bytes());
+        let document_hash = create_document_hash(content);
+        let valid_until = Some(Utc::now() + chrono::Duration::days(1));
+        let message = build_signing_message(&hex::decode(&document_hash).unwrap(), valid_until, None, None);
+
+        let body = BundleBody {
+            schema: BUNDLE_SCHEMA.to_string(),
+            version: BUNDLE_VERSION,
+            signature_id: Uuid::new_v4(),
+            key_id: Uuid::new_v4(),
+            document_hash,
+            hash_algorithm: "sha-256".to_string(),
+            content_type: DocumentContentType::Text,
+            signature: encode_signature(&signing_key.sign(&message)),
+            signature_algorithm: "ed25519".to_string(),
+            signing_time: Utc::now(),
+            valid_until,
+            context: None,
+            timestamp_bound: false,
+            message_encoding: MessageEncoding::HashRawBytes,
+            key_fingerprint: public_key_to_fingerprint(&public_key).unwrap(),
+            public_key,
+            key_status: BundleKeyStatus { active: true, expires_at: None },
+            signer_info: Some(SignerInfo::current()),
+        };
+        let mut bundle = Bundle::new(body, &signing_key).unwrap();
+        bundle.notarize(Uuid::new_v4(), &SigningKey::generate(&mut OsRng)).unwrap();
+        (bundle, signing_key)
+    }
+
+    #[test]
+    fn test_bundle_verifies_offline_from_json_and_cbor() {
+        let (bundle, _) = signed_bundle("contract v3");
+
+        let json = serde_json::to_string(&bundle).unwrap();
+        let from_json: Bundle = serde_json::from_str(&json).unwrap();
+        let from_cbor = Bundle::from_cbor(&bundle.to_cbor().unwrap()).unwrap();
+
+        for decoded in [&from_json, &from_cbor] {
+            assert_eq!(*decoded, bundle);
+            let result = verify_bundle(decoded, BundleSubject::Content("contract v3")).unwrap();
+            assert!(result.valid, "{:?}", result);
+            assert_eq!(result.notary_valid, Some(true));
+            assert!(verify_bundle(decoded, BundleSubject::Hash(&bundle.body.document_hash)).unwrap().valid);
+        }
+
+        let result = verify_bundle(&bundle, BundleSubject::Content("contract v4")).unwrap();
+        assert!(!result.valid && !result.document_matches);
+    }
+
+    #[test]
+    fn test_tampering_with_any_field_is_detected() {
+        let (bundle, _) = signed_bundle("contract v3");
+        let other_key = base64::engine::general_purpose::STANDARD.encode(SigningKey::generate(&mut OsRng).verifying_key().to_bytes());
+
+        type Tamper = Box<dyn Fn(&mut Bundle)>;
+        let tampers: Vec<(&str, Tamper)> = vec![
+            ("signature_id", Box::new(|b| b.body.signature_id = Uuid::new_v4())),
+            ("key_id", Box::new(|b| b.body.key_id = Uuid::new_v4())),
+            ("document_hash", Box::new(|b| b.body.document_hash = create_document_hash("other"))),
+            ("hash_algorithm", Box::new(|b| b.body.hash_algorithm = "sha-512".to_string())),
+            ("content_type", Box::new(|b| b.body.content_type = DocumentContentType::JsonJcs)),
+            ("signature", Box::new(|b| b.body.signature = b.attestation.clone())),
+            ("signature_algorithm", Box::new(|b| b.body.signature_algorithm = "rsa".to_string())),
+            ("signing_time", Box::new(|b| b.body.signing_time -= chrono::Duration::days(365))),
+            ("valid_until", Box::new(|b| b.body.valid_until = None)),
+            ("public_key", Box::new(move |b| b.body.public_key = other_key.clone())),
+            ("key_fingerprint", Box::new(|b| b.body.key_fingerprint = "00000000:00000000:00000000:00000000".to_string())),
+            ("key_status", Box::new(|b| b.body.key_status.expires_at = Some(Utc::now()))),
+            ("signer_info", Box::new(|b| b.body.signer_info.as_mut().unwrap().version = "0.0.0".to_string())),
+            ("attestation", Box::new(|b| b.attestation = b.body.signature.clone())),
+            ("notary", Box::new(|b| b.notary.as_mut().unwrap().signature = b.body.signature.clone())),
+        ];
+
+        for (field, tamper) in tampers {
+            let mut tampered = bundle.clone();
+            tamper(&mut tampered);
+            let result = verify_bundle(&tampered, BundleSubject::Content("contract v3")).unwrap();
+            assert!(!result.valid, "tampering with {} went undetected", field);
+        }
+    }
+
+    #[test]
+    fn test_unknown_schema_version_is_rejected() {
+        let (mut bundle, _) = signed_bundle("contract v3");
+        bundle.body.version = 2;
+        assert!(verify_bundle(&bundle, BundleSubject::Content("contract v3")).is_err());
+    }
+}
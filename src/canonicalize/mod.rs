@@ -0,0 +1,229 @@
+//! JSON Canonicalization Scheme (RFC 8785)
+//!
+//! Produces a byte-stable serialization of JSON documents so that semantically equal
+//! documents hash (and therefore sign) identically regardless of whitespace or key order.
+
+use crate::models::KeyManagementError;
+use serde_json::Value;
+use std::fmt::Write;
+
+/// Parses `input` as JSON and returns its RFC 8785 canonical form
+pub fn canonicalize_json(input: &str) -> Result<String, KeyManagementError> {
+    let value: Value = serde_json::from_str(input).map_err(|e| {
+        KeyManagementError::ValidationFailed(format!(
+            "Invalid JSON document at line {}, column {}: {}",
+            e.line(),
+            e.column(),
+            e
+        ))
+    })?;
+
+    canonicalize_value(&value)
+}
+
+/// Serializes an already-parsed JSON value in RFC 8785 canonical form
+pub fn canonicalize_value(value: &Value) -> Result<String, KeyManagementError> {
+    let mut out = String::new();
+    write_value(&mut out, value)?;
+    Ok(out)
+}
+
+fn write_value(out: &mut String, value: &Value) -> Result<(), KeyManagementError> {
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        Value::Number(n) => {
+            // JCS treats every number as an IEEE-754 double
+            let f = n.as_f64().ok_or_else(|| {
+                KeyManagementError::ValidationFailed(format!("Number {} is not representable as a double", n))
+            })?;
+            out.push_str(&format_number(f)?);
+        }
+        Value::String(s) => write_string(out, s),
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_value(out, item)?;
+            }
+            out.push(']');
+        }
+        Value::Object(map) => {
+            // Properties are ordered by their UTF-16 code units, not by UTF-8 bytes
+            let mut entries: Vec<(&String, &Value)> = map.iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+
+            out.push('{');
+            for (i, (key, item)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_string(out, key);
+                out.push(':');
+                write_value(out, item)?;
+            }
+            out.push('}');
+        }
+    }
+
+    Ok(())
+}
+
+fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\u{08}' => out.push_str("\\b"),
+            '\u{09}' => out.push_str("\\t"),
+            '\u{0A}' => out.push_str("\\n"),
+            '\u{0C}' => out.push_str("\\f"),
+            '\u{0D}' => out.push_str("\\r"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Formats a double the way ECMAScript's `Number.prototype.toString` does, as RFC 8785 requires
+pub fn format_number(value: f64) -> Result<String, KeyManagementError> {
+    if !value.is_finite() {
+        return Err(KeyManagementError::ValidationFailed(
+            "NaN and Infinity cannot be canonicalized".to_string(),
+        ));
+    }
+    if value == 0.0 {
+        // Covers negative zero as well
+        return Ok("0".to_string());
+    }
+
+    // Rust's exponential formatting yields the shortest round-tripping digits
+    let formatted = format!("{:e}", value.abs());
+    let (mantissa, exponent) = formatted.split_once('e').expect("exponential format");
+    let digits: String = mantissa.chars().filter(|c| *c != '.').collect();
+    let exponent: i32 = exponent.parse().expect("integer exponent");
+
+    let k = digits.len() as i32;
+    let n = exponent + 1;
+    let mut out = String::new();
+    if value < 0.0 {
+        out.push('-');
+    }
+
+    if k <= n && n <= 21 {
+        out.push_str(&digits);
+        out.push_str(&"0".repeat((n - k) as usize));
+    } else if 0 < n && n <= 21 {
+        out.push_str(&digits[..n as usize]);
+        out.push('.');
+        out.push_str(&digits[n as usize..]);
+    } else if -6 < n && n <= 0 {
+        out.push_str("0.");
+        out.push_str(&"0".repeat((-n) as usize));
+        out.push_str(&digits);
+    } else {
+        out.push_str(&digits[..1]);
+        if k > 1 {
+            out.push('.');
+            out.push_str(&digits[1..]);
+        }
+        out.push('e');
+        out.push(if n > 0 { '+' } else { '-' });
+        let _ = write!(out, "{}", (n - 1).abs());
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rfc8785_primitive_data_types() {
+        // RFC 8785 section 3.2.2
+        let input = r#"{
+          "numbers": [333333333.33333329, 1E30, 4.50, 2e-3, 0.000000000000000000000000001],
+          "string": "\u20ac$\u000F\u000aA'\u0042\u0022\u005c\\\"\/",
+          "literals": [null, true, false]
+        }"#;
+        let expected = r#"{"literals":[null,true,false],"numbers":[333333333.3333333,1e+30,4.5,0.002,1e-27],"string":"€$\u000f\nA'B\"\\\\\"/"}"#;
+
+        assert_eq!(canonicalize_json(input).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_rfc8785_property_sorting() {
+        // RFC 8785 section 3.2.3
+        let input = r#"{
+          "€": "Euro Sign",
+          "\r": "Carriage Return",
+          "דּ": "Hebrew Letter Dalet With Dagesh",
+          "1": "One",
+          "😀": "Emoji: Grinning Face",
+          "\u0080": "Control",
+          "ö": "Latin Small Letter O With Diaeresis"
+        }"#;
+        let canonical = canonicalize_json(input).unwrap();
+        let positions: Vec<usize> = [
+            "Carriage Return",
+            "One",
+            "Control",
+            "Latin Small Letter O With Diaeresis",
+            "Euro Sign",
+            "Emoji: Grinning Face",
+            "Hebrew Letter Dalet With Dagesh",
+        ]
+        .iter()
+        .map(|name| canonical.find(name).unwrap())
+        .collect();
+        assert!(positions.windows(2).all(|w| w[0] < w[1]), "unexpected order: {}", canonical);
+    }
+
+    #[test]
+    fn test_rfc8785_number_serialization() {
+        // RFC 8785 appendix B
+        let vectors: [(u64, &str); 11] = [
+            (0x0000000000000000, "0"),
+            (0x8000000000000000, "0"),
+            (0x0000000000000001, "5e-324"),
+            (0x8000000000000001, "-5e-324"),
+            (0x7fefffffffffffff, "1.7976931348623157e+308"),
+            (0x4340000000000000, "9007199254740992"),
+            (0x4430000000000000, "295147905179352830000"),
+            (0x44b52d02c7e14af5, "9.999999999999997e+22"),
+            (0x44b52d02c7e14af6, "1e+23"),
+            (0x3eb0c6f7a0b5ed8d, "0.000001"),
+            (0x3eb0c6f7a0b5ed8c, "9.999999999999997e-7"),
+        ];
+
+        for (bits, expected) in vectors {
+            assert_eq!(format_number(f64::from_bits(bits)).unwrap(), expected, "bits {:016x}", bits);
+        }
+        assert!(format_number(f64::NAN).is_err());
+        assert!(format_number(f64::INFINITY).is_err());
+    }
+
+    #[test]
+    fn test_equivalent_documents_canonicalize_identically() {
+        let a = r#"{"amount": 10.50, "currency": "EUR", "lines": [{"sku": "A", "qty": 1}]}"#;
+        let b = "{\n  \"lines\" : [ { \"qty\":1.0, \"sku\":\"A\" } ],\n  \"currency\":\"EUR\",\"amount\":1.05e1\n}";
+
+        assert_eq!(canonicalize_json(a).unwrap(), canonicalize_json(b).unwrap());
+    }
+
+    #[test]
+    fn test_invalid_json_reports_location() {
+        let err = canonicalize_json("{\"a\": 1,\n  \"b\": }").unwrap_err();
+        match err {
+            KeyManagementError::ValidationFailed(message) => assert!(message.contains("line 2"), "{}", message),
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+}
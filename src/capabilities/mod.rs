@@ -0,0 +1,339 @@
+//! What a key, and the service as a whole, can do
+//!
+//! Clients feature-detect from these instead of hard-coding which formats or algorithms a
+//! deployment supports. Key capabilities are derived from the key's type and policy, so a
+//! client can tell before signing whether a request will be refused; service capabilities
+//! describe what this build and configuration support, and are served at `GET /capabilities`.
+//!
+//! [`About`] is the operator's view of the same build: how it stores keys and authenticates
+//! requests, and every endpoint the router registered. It is served at `GET /about` and logged
+//! once at startup, so tooling never has to parse a hand-written list.
+
+use crate::api_version::ApiVersion;
+use crate::config::{Config, KdfAlgorithm};
+use crate::key_generation::{MAX_ALLOWED_CONTEXTS, MAX_DESCRIPTION_LENGTH, MAX_KEY_NAME_LENGTH, MAX_TAGS, MAX_TAG_LENGTH, MIN_PASSWORD_LENGTH};
+use crate::key_verification::{MAX_CONTEXT_LENGTH, MAX_DOCUMENT_HASH_LENGTH, MAX_RAW_MESSAGE_BYTES, MAX_VERIFY_ENCODED_LENGTH};
+use crate::models::{BundleFormat, DocumentContentType, KeyPair, KeyState, KeyType, MessageEncoding, PublicKeyEncoding, PublicKeyFormat, SignatureOutputFormat};
+use crate::profile::DeploymentProfile;
+use serde::{Deserialize, Serialize};
+
+/// Signature algorithm a key produces
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SignatureScheme {
+    /// Pure Ed25519 (RFC 8032)
+    Ed25519,
+}
+
+/// Digest applied to a document before or while signing it
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    /// Document hashes for raw signatures
+    #[serde(rename = "sha256")]
+    Sha256,
+    /// Message digest inside sshsig signatures
+    #[serde(rename = "sha512")]
+    Sha512,
+    /// Prehash of minisign signatures
+    #[serde(rename = "blake2b-512")]
+    Blake2b512,
+}
+
+impl HashAlgorithm {
+    /// Name of the algorithm as serialized, e.g. `blake2b-512`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Sha512 => "sha512",
+            HashAlgorithm::Blake2b512 => "blake2b-512",
+        }
+    }
+
+    /// Digests an output format can sign with, the one it uses unless asked otherwise first
+    pub fn for_format(format: SignatureOutputFormat) -> &'static [HashAlgorithm] {
+        match format {
+            SignatureOutputFormat::Raw => &[HashAlgorithm::Sha256],
+            SignatureOutputFormat::Minisign => &[HashAlgorithm::Blake2b512],
+            SignatureOutputFormat::Sshsig => &[HashAlgorithm::Sha512, HashAlgorithm::Sha256],
+        }
+    }
+}
+
+/// What one key can do, from its type and policy
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct KeyCapabilities {
+    pub can_sign: bool, // The key's state allows signing now
+    pub signature_schemes: Vec<SignatureScheme>,
+    pub hash_algorithms: Vec<HashAlgorithm>,
+    pub output_formats: Vec<SignatureOutputFormat>,
+    pub content_types: Vec<DocumentContentType>,
+    pub requires_password: bool, // `/sign` needs the key's password
+    pub requires_context: bool, // `/sign` needs a context from `allowed_contexts`
+    #[serde(default)]
+    pub exportable: bool, // The private key may be wrapped for another instance
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_contexts: Option<Vec<String>>,
+}
+
+impl KeyCapabilities {
+    /// Capabilities of `key_pair` while in `state`
+    pub fn new(key_pair: &KeyPair, state: KeyState) -> Self {
+        let on_hsm = key_pair.hsm.is_some() || key_pair.key_type == KeyType::Ed25519Hsm;
+        let (hash_algorithms, output_formats) = Self::signing_formats(on_hsm);
+        Self {
+            can_sign: state.is_usable(),
+            signature_schemes: vec![SignatureScheme::Ed25519],
+            hash_algorithms,
+            output_formats,
+            content_types: vec![DocumentContentType::Text, DocumentContentType::JsonJcs],
+            requires_password: !on_hsm && key_pair.key_type == KeyType::Ed25519Encrypted,
+            requires_context: key_pair.allowed_contexts.is_some(),
+            exportable: key_pair.exportable && !on_hsm && key_pair.key_type != KeyType::Ed25519Ephemeral,
+            allowed_contexts: key_pair.allowed_contexts.clone(),
+        }
+    }
+
+    /// Digests and output formats a key can sign with, by whether it is held on an HSM
+    pub fn signing_formats(on_hsm: bool) -> (Vec<HashAlgorithm>, Vec<SignatureOutputFormat>) {
+        // minisign and sshsig sign with the private key in process, which an HSM key never is
+        if on_hsm {
+            (vec![HashAlgorithm::Sha256], vec![SignatureOutputFormat::Raw])
+        } else {
+            (
+                vec![HashAlgorithm::Sha256, HashAlgorithm::Sha512, HashAlgorithm::Blake2b512],
+                vec![SignatureOutputFormat::Raw, SignatureOutputFormat::Minisign, SignatureOutputFormat::Sshsig],
+            )
+        }
+    }
+}
+
+/// Optional features compiled into this build
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct CompiledFeatures {
+    pub webhook_notifications: bool,
+    pub email_notifications: bool,
+    pub keystore_watch: bool,
+    pub shared_rate_limits: bool, // Rate limits counted in Redis
+    pub federation: bool, // Keys resolved from partner instances
+    pub verifier_only: bool, // Built to run only the verifier profile
+}
+
+impl CompiledFeatures {
+    /// Features of the running binary
+    pub fn current() -> Self {
+        Self {
+            webhook_notifications: cfg!(feature = "webhook"),
+            email_notifications: cfg!(feature = "email"),
+            keystore_watch: cfg!(feature = "watch"),
+            shared_rate_limits: cfg!(feature = "redis"),
+            federation: cfg!(feature = "federation"),
+            verifier_only: cfg!(feature = "verifier-only"),
+        }
+    }
+}
+
+/// Limits requests are checked against
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ServiceLimits {
+    pub max_key_name_length: usize,
+    pub max_description_length: usize,
+    pub min_password_length: usize,
+    pub max_tags: usize,
+    pub max_tag_length: usize,
+    pub max_allowed_contexts: usize,
+    pub max_context_length: usize,
+    pub max_document_hash_length: usize,
+    pub max_verify_encoded_length: usize, // Public keys and signatures sent to `/verify`
+    pub max_raw_message_bytes: usize, // Documents signed or verified as a `raw-message`
+    pub verify_max_content_bytes: u32,
+    pub sign_max_content_bytes: u32, // Inline `document_content` sent to `/sign`
+    pub raw_sign_max_content_bytes: u32, // Documents streamed to `/sign/raw`
+    pub verify_requests_per_minute: u32, // Per unauthenticated client; 0 is unlimited
+    pub max_keys: Option<usize>,
+    pub max_key_lifetime_days: Option<u32>,
+}
+
+/// How listings page through their results
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct PaginationCapabilities {
+    pub cursor_endpoints: Vec<&'static str>, // Listings taking a `cursor` and returning `next_cursor`
+    pub key_order: Vec<&'static str>, // Fields keys are sorted by, ascending; together unique and unchanging
+    pub offset: bool, // `offset` is still accepted, though pages shift as keys come and go
+}
+
+impl PaginationCapabilities {
+    pub fn current() -> Self {
+        Self {
+            cursor_endpoints: vec!["/keys", "/keys/search"],
+            key_order: vec!["created_at", "id"],
+            offset: true,
+        }
+    }
+}
+
+/// What this build and configuration of the service supports
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ServiceCapabilities {
+    pub version: &'static str,
+    pub key_types: Vec<KeyType>,
+    pub signature_schemes: Vec<SignatureScheme>,
+    pub hash_algorithms: Vec<HashAlgorithm>,
+    pub output_formats: Vec<SignatureOutputFormat>,
+    pub content_types: Vec<DocumentContentType>,
+    pub message_encodings: Vec<MessageEncoding>, // What a raw signature may cover; `hash-raw-bytes` unless asked
+    pub public_key_formats: Vec<PublicKeyFormat>,
+    pub public_key_encodings: Vec<PublicKeyEncoding>,
+    pub bundle_formats: Vec<BundleFormat>,
+    pub kdf_algorithms: Vec<KdfAlgorithm>,
+    pub hsm: bool, // An HSM backend is configured for `hsm` keys
+    pub request_signing: bool, // Requests must be HMAC-signed
+    pub read_only: bool,
+    pub profile: DeploymentProfile, // `verifier` serves verification and public key routes only
+    pub features: CompiledFeatures,
+    pub pagination: PaginationCapabilities,
+    pub limits: ServiceLimits,
+}
+
+impl ServiceCapabilities {
+    pub fn new(config: &Config, hsm: bool, request_signing: bool, read_only: bool) -> Self {
+        let mut key_types = vec![KeyType::Ed25519, KeyType::Ed25519Encrypted];
+        if hsm {
+            key_types.push(KeyType::Ed25519Hsm);
+        }
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            key_types,
+            signature_schemes: vec![SignatureScheme::Ed25519],
+            hash_algorithms: vec![HashAlgorithm::Sha256, HashAlgorithm::Sha512, HashAlgorithm::Blake2b512],
+            output_formats: vec![SignatureOutputFormat::Raw, SignatureOutputFormat::Minisign, SignatureOutputFormat::Sshsig],
+            content_types: vec![DocumentContentType::Text, DocumentContentType::JsonJcs],
+            message_encodings: vec![MessageEncoding::HashRawBytes, MessageEncoding::HashHexBytes, MessageEncoding::RawMessage],
+            public_key_formats: vec![PublicKeyFormat::Json, PublicKeyFormat::Minisign, PublicKeyFormat::Ssh],
+            public_key_encodings: vec![PublicKeyEncoding::Raw, PublicKeyEncoding::Pem, PublicKeyEncoding::Jwk],
+            bundle_formats: vec![BundleFormat::Json, BundleFormat::Cbor],
+            kdf_algorithms: vec![KdfAlgorithm::Pbkdf2Sha256, KdfAlgorithm::Argon2id],
+            hsm,
+            request_signing,
+            read_only,
+            profile: config.profile,
+            features: CompiledFeatures::current(),
+            pagination: PaginationCapabilities::current(),
+            limits: ServiceLimits {
+                max_key_name_length: MAX_KEY_NAME_LENGTH,
+                max_description_length: MAX_DESCRIPTION_LENGTH,
+                min_password_length: MIN_PASSWORD_LENGTH,
+                max_tags: MAX_TAGS,
+                max_tag_length: MAX_TAG_LENGTH,
+                max_allowed_contexts: MAX_ALLOWED_CONTEXTS,
+                max_context_length: MAX_CONTEXT_LENGTH,
+                max_document_hash_length: MAX_DOCUMENT_HASH_LENGTH,
+                max_verify_encoded_length: MAX_VERIFY_ENCODED_LENGTH,
+                max_raw_message_bytes: MAX_RAW_MESSAGE_BYTES,
+                verify_max_content_bytes: config.verify_max_content_bytes,
+                sign_max_content_bytes: config.sign_max_content_bytes,
+                raw_sign_max_content_bytes: config.raw_sign_max_content_bytes,
+                verify_requests_per_minute: config.verify_requests_per_minute,
+                max_keys: config.max_keys,
+                max_key_lifetime_days: config.max_key_lifetime_days,
+            },
+        }
+    }
+}
+
+/// A route the router serves, listed in `/about`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EndpointInfo {
+    pub method: String,
+    pub path: String, // Unprefixed; each served API version mounts it under its prefix
+    pub summary: String,
+}
+
+/// How requests are authenticated
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthMode {
+    /// No credentials are asked for
+    None,
+    /// Requests outside the public paths must be HMAC-signed
+    Hmac,
+}
+
+/// Non-sensitive description of the running service, for operators and their tooling
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct About {
+    pub service: &'static str,
+    pub version: &'static str,
+    pub api_versions: Vec<&'static str>,
+    pub signature_schemes: Vec<SignatureScheme>,
+    pub hash_algorithms: Vec<HashAlgorithm>,
+    pub kdf_algorithms: Vec<KdfAlgorithm>,
+    pub storage_backend: &'static str, // Where key material is kept
+    pub hsm: bool, // An HSM backend is configured for `hsm` keys
+    pub auth_mode: AuthMode,
+    pub profile: DeploymentProfile,
+    pub features: CompiledFeatures,
+    pub endpoints: Vec<EndpointInfo>, // Those the profile serves
+}
+
+impl About {
+    pub fn new(api_versions: &[ApiVersion], hsm: bool, request_signing: bool, profile: DeploymentProfile, endpoints: Vec<EndpointInfo>) -> Self {
+        Self {
+            service: env!("CARGO_PKG_NAME"),
+            version: env!("CARGO_PKG_VERSION"),
+            api_versions: api_versions.iter().map(|version| version.as_str()).collect(),
+            signature_schemes: vec![SignatureScheme::Ed25519],
+            hash_algorithms: vec![HashAlgorithm::Sha256, HashAlgorithm::Sha512, HashAlgorithm::Blake2b512],
+            kdf_algorithms: vec![KdfAlgorithm::Pbkdf2Sha256, KdfAlgorithm::Argon2id],
+            storage_backend: "json-file",
+            hsm,
+            auth_mode: if request_signing { AuthMode::Hmac } else { AuthMode::None },
+            profile,
+            features: CompiledFeatures::current(),
+            endpoints,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_generation::generate_test_key_pair;
+    use crate::models::HsmKeyRef;
+
+    #[test]
+    fn test_key_capabilities_follow_type_and_policy() {
+        let key_pair = generate_test_key_pair("Plain Key").unwrap();
+        assert_eq!(serde_json::to_value(KeyCapabilities::new(&key_pair, KeyState::Active)).unwrap(), serde_json::json!({
+            "can_sign": true,
+            "signature_schemes": ["ed25519"],
+            "hash_algorithms": ["sha256", "sha512", "blake2b-512"],
+            "output_formats": ["raw", "minisign", "sshsig"],
+            "content_types": ["text", "json-jcs"],
+            "requires_password": false,
+            "requires_context": false,
+            "exportable": true,
+        }));
+
+        let restricted = KeyPair {
+            key_type: KeyType::Ed25519Encrypted,
+            allowed_contexts: Some(vec!["invoice".to_string()]),
+            ..key_pair.clone()
+        };
+        let capabilities = KeyCapabilities::new(&restricted, KeyState::Expired);
+        assert!(!capabilities.can_sign);
+        assert!(capabilities.requires_password);
+        assert!(capabilities.requires_context);
+        assert_eq!(capabilities.allowed_contexts, Some(vec!["invoice".to_string()]));
+
+        let on_hsm = KeyPair {
+            key_type: KeyType::Ed25519Hsm,
+            hsm: Some(HsmKeyRef { slot: 0, label: "root".to_string() }),
+            ..key_pair
+        };
+        let capabilities = KeyCapabilities::new(&on_hsm, KeyState::Active);
+        assert_eq!(capabilities.output_formats, [SignatureOutputFormat::Raw]);
+        assert_eq!(capabilities.hash_algorithms, [HashAlgorithm::Sha256]);
+        assert!(!capabilities.requires_password);
+        assert!(!capabilities.exportable);
+    }
+}
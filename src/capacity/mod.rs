@@ -0,0 +1,198 @@
+//! Limits on the number of stored keys
+//!
+//! Every keystore write serializes the whole file, so a store of tens of thousands of keys makes
+//! each save and listing slow. Crossing the soft limit only warns, in generation responses,
+//! readiness, stats and metrics; at the hard limit new keys are refused until keys are removed.
+//! An eviction policy, when configured, runs before a new key is stored and moves keys it selects
+//! to the archive file (see [`KeyStorage::archive_keys`]) to bring the store back under the
+//! limits.
+
+use crate::config::Config;
+use crate::key_storage::KeyStorage;
+use crate::models::{KeyManagementError, KeyPair, KeyState};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// How close the keystore is to its limits
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CapacityLevel {
+    /// Below the soft limit, or unlimited
+    #[default]
+    Ok,
+    /// At or above the soft limit
+    Warning,
+    /// At the hard limit; new keys are refused
+    Full,
+}
+
+/// Size of the keystore relative to its limits
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct CapacityStatus {
+    pub level: CapacityLevel,
+    pub keys: usize,
+    pub soft_limit: Option<usize>,
+    pub hard_limit: Option<usize>,
+    pub headroom: Option<usize>, // Keys that can still be added before the hard limit
+}
+
+impl CapacityStatus {
+    /// Warning to surface to callers, when past the soft limit
+    pub fn warning(&self) -> Option<String> {
+        match (self.level, self.soft_limit, self.hard_limit) {
+            (CapacityLevel::Ok, _, _) => None,
+            (CapacityLevel::Full, _, Some(hard_limit)) => {
+                Some(format!("Keystore holds {} keys and is at its hard limit of {}", self.keys, hard_limit))
+            }
+            (_, Some(soft_limit), _) => Some(format!(
+                "Keystore holds {} keys, past its soft limit of {}{}",
+                self.keys,
+                soft_limit,
+                self.headroom.map_or(String::new(), |headroom| format!("; {} more can be added", headroom)),
+            )),
+            _ => None,
+        }
+    }
+}
+
+/// Chooses keys to move out of the keystore when it grows past its limits
+pub trait EvictionPolicy: Send + Sync {
+    /// Reason recorded with each archived key
+    fn name(&self) -> &str;
+
+    /// Picks up to `count` keys to archive, most expendable first
+    fn select(&self, keys: &[KeyPair], count: usize, now: DateTime<Utc>) -> Vec<Uuid>;
+}
+
+/// Archives the keys revoked longest ago, once they have been revoked for `min_revoked_age`
+///
+/// Revocation stamps a key's expiry with the time it took effect, so that is the age measured.
+pub struct ArchiveLongRevoked {
+    pub min_revoked_age: Duration,
+}
+
+impl EvictionPolicy for ArchiveLongRevoked {
+    fn name(&self) -> &str {
+        "revoked"
+    }
+
+    fn select(&self, keys: &[KeyPair], count: usize, now: DateTime<Utc>) -> Vec<Uuid> {
+        let mut candidates: Vec<(DateTime<Utc>, &KeyPair)> = keys.iter()
+            .filter(|key| key.state(now) == KeyState::Revoked)
+            .filter_map(|key| key.expires_at.map(|revoked_at| (revoked_at, key)))
+            .filter(|(revoked_at, _)| now - *revoked_at >= self.min_revoked_age)
+            .collect();
+        candidates.sort_by_key(|(revoked_at, key)| (*revoked_at, key.created_at, key.id));
+        candidates.into_iter().take(count).map(|(_, key)| key.id).collect()
+    }
+}
+
+/// Soft and hard limits on stored keys, with an optional eviction policy
+#[derive(Default)]
+pub struct KeystoreCapacity {
+    soft_limit: Option<usize>,
+    hard_limit: Option<usize>,
+    eviction: Option<Box<dyn EvictionPolicy>>,
+}
+
+impl KeystoreCapacity {
+    /// Limits without eviction; `None` leaves a limit unset
+    pub fn new(soft_limit: Option<usize>, hard_limit: Option<usize>) -> Self {
+        Self { soft_limit, hard_limit, eviction: None }
+    }
+
+    /// No limits at all
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+
+    /// Limits and archival policy configured in `config`
+    pub fn from_config(config: &Config) -> Self {
+        let capacity = Self::new(config.keystore_soft_limit, config.keystore_hard_limit);
+        match config.archive_revoked_after_days {
+            Some(days) => capacity.with_eviction(ArchiveLongRevoked { min_revoked_age: Duration::days(days.into()) }),
+            None => capacity,
+        }
+    }
+
+    /// Evicts keys chosen by `policy` before new keys would cross a limit
+    pub fn with_eviction(mut self, policy: impl EvictionPolicy + 'static) -> Self {
+        self.eviction = Some(Box::new(policy));
+        self
+    }
+
+    /// Capacity of a keystore holding `keys` keys
+    pub fn status(&self, keys: usize) -> CapacityStatus {
+        let level = if self.hard_limit.is_some_and(|limit| keys >= limit) {
+            CapacityLevel::Full
+        } else if self.soft_limit.is_some_and(|limit| keys >= limit) {
+            CapacityLevel::Warning
+        } else {
+            CapacityLevel::Ok
+        };
+        CapacityStatus {
+            level,
+            keys,
+            soft_limit: self.soft_limit,
+            hard_limit: self.hard_limit,
+            headroom: self.hard_limit.map(|limit| limit.saturating_sub(keys)),
+        }
+    }
+
+    /// Makes room for one more key, archiving keys if a policy is configured
+    ///
+    /// Eviction aims to leave the store below the soft limit once the new key is added, or below
+    /// the hard limit when no soft limit is set. Fails with [`KeyManagementError::KeystoreFull`]
+    /// if the store is still at its hard limit afterwards.
+    pub async fn reserve(&self, storage: &KeyStorage, now: DateTime<Utc>) -> Result<(), KeyManagementError> {
+        let mut keys = storage.key_count().await;
+        if let (Some(policy), Some(target)) = (&self.eviction, self.soft_limit.or(self.hard_limit)) {
+            if keys + 1 > target {
+                let stored: Vec<KeyPair> = storage.entries().await.into_iter().map(|(_, key)| key).collect();
+                let selected = policy.select(&stored, keys + 1 - target, now);
+                if !selected.is_empty() {
+                    let archived = storage.archive_keys(&selected, policy.name()).await?;
+                    tracing::info!("Archived {} keys under the '{}' eviction policy to stay within keystore limits", archived, policy.name());
+                    keys = storage.key_count().await;
+                }
+            }
+        }
+
+        match self.hard_limit {
+            Some(limit) if keys >= limit => Err(KeyManagementError::KeystoreFull(format!(
+                "the keystore holds {} keys, its hard limit; revoke and archive or remove keys before creating more",
+                keys,
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_generation::generate_test_key_pair;
+
+    #[test]
+    fn test_levels_and_long_revoked_selection() {
+        let capacity = KeystoreCapacity::new(Some(2), Some(3));
+        assert_eq!(capacity.status(1).level, CapacityLevel::Ok);
+        assert_eq!(capacity.status(2).level, CapacityLevel::Warning);
+        assert_eq!(capacity.status(2).headroom, Some(1));
+        assert!(capacity.status(2).warning().unwrap().contains("1 more can be added"));
+        assert_eq!(capacity.status(3).level, CapacityLevel::Full);
+        assert_eq!(KeystoreCapacity::unlimited().status(1_000_000).warning(), None);
+
+        let now = Utc::now();
+        let revoked = |days_ago: i64| KeyPair {
+            lifecycle: crate::lifecycle::Lifecycle::Revoked,
+            expires_at: Some(now - Duration::days(days_ago)),
+            ..generate_test_key_pair("revoked").unwrap()
+        };
+        let keys = vec![revoked(10), generate_test_key_pair("active").unwrap(), revoked(100), revoked(50)];
+        let policy = ArchiveLongRevoked { min_revoked_age: Duration::days(30) };
+        assert_eq!(policy.select(&keys, 5, now), vec![keys[2].id, keys[3].id]);
+        assert_eq!(policy.select(&keys, 1, now), vec![keys[2].id]);
+    }
+}
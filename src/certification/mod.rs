@@ -0,0 +1,298 @@
+//! Key certifications
+//!
+//! A certification is one key's signature over another key's public key, so verifiers who
+//! trust the certifier can follow a chain of certifications to a key they have never seen,
+//! for example from a retired release key to its replacement. The certifier signs the
+//! certification payload in RFC 8785 canonical form, prefixed with a domain tag.
+
+use crate::canonicalize::canonicalize_value;
+use crate::key_verification::decode_public_key;
+use crate::models::KeyManagementError;
+use crate::signing_backend::KeySigner;
+use crate::utils::public_key_to_fingerprint;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use ed25519_dalek::{Signature, Verifier};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::Path;
+use tokio::fs;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+/// Domain tag prefixed to the canonical payload before it is signed
+pub const CERTIFICATION_CONTEXT: &[u8] = b"inkan-key-certification-v1";
+/// Longest chain followed when collecting certifications for a key
+pub const MAX_CHAIN_LENGTH: usize = 16;
+
+/// Statement signed by the certifying key
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CertificationPayload {
+    pub certifier_key_id: Uuid,
+    pub certifier_public_key: String,
+    pub certifier_fingerprint: String,
+    /// Keystore id of the certified key, when it is held by this service
+    pub target_key_id: Option<Uuid>,
+    pub target_public_key: String,
+    pub target_fingerprint: String,
+    pub issued_at: DateTime<Utc>,
+    pub valid_until: Option<DateTime<Utc>>,
+}
+
+/// Signed certification of one key by another
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Certification {
+    pub id: Uuid,
+    #[serde(flatten)]
+    pub payload: CertificationPayload,
+    /// Base64 encoded Ed25519 signature by the certifier over the payload
+    pub signature: String,
+}
+
+fn signed_message(payload: &CertificationPayload) -> Result<Vec<u8>, KeyManagementError> {
+    let value = serde_json::to_value(payload)
+        .map_err(|e| KeyManagementError::InternalError(format!("Failed to serialize certification: {}", e)))?;
+    let mut message = CERTIFICATION_CONTEXT.to_vec();
+    message.push(0);
+    message.extend_from_slice(canonicalize_value(&value)?.as_bytes());
+    Ok(message)
+}
+
+impl Certification {
+    /// Signs the payload with the certifying key
+    pub fn issue(payload: CertificationPayload, certifier: &dyn KeySigner) -> Result<Self, KeyManagementError> {
+        let signature = certifier.sign_message(&signed_message(&payload)?)?;
+        Ok(Self {
+            id: Uuid::new_v4(),
+            payload,
+            signature: base64::engine::general_purpose::STANDARD.encode(signature.to_bytes()),
+        })
+    }
+
+    /// Whether the certification's validity window covers `at`
+    pub fn is_valid_at(&self, at: DateTime<Utc>) -> bool {
+        self.payload.issued_at <= at && self.payload.valid_until.is_none_or(|valid_until| at <= valid_until)
+    }
+}
+
+/// Verifies a single certification offline
+///
+/// Checks both fingerprints against their public keys, the certifier's signature, and that
+/// the certification is valid at `at`.
+pub fn verify_certification(certification: &Certification, at: DateTime<Utc>) -> Result<(), KeyManagementError> {
+    let payload = &certification.payload;
+    let invalid = |reason: &str| KeyManagementError::SignatureVerificationFailed(format!("Certification {}: {}", certification.id, reason));
+
+    if public_key_to_fingerprint(&payload.certifier_public_key).ok().as_deref() != Some(payload.certifier_fingerprint.as_str()) {
+        return Err(invalid("certifier fingerprint does not match its public key"));
+    }
+    if public_key_to_fingerprint(&payload.target_public_key).ok().as_deref() != Some(payload.target_fingerprint.as_str()) {
+        return Err(invalid("target fingerprint does not match its public key"));
+    }
+
+    let certifier = decode_public_key(&payload.certifier_public_key).map_err(|_| invalid("certifier public key is malformed"))?;
+    let signature = base64::engine::general_purpose::STANDARD.decode(&certification.signature)
+        .ok()
+        .and_then(|bytes| <[u8; 64]>::try_from(bytes.as_slice()).ok())
+        .map(|bytes| Signature::from_bytes(&bytes))
+        .ok_or_else(|| invalid("signature is malformed"))?;
+    certifier.verify(&signed_message(payload)?, &signature)
+        .map_err(|_| invalid("signature is invalid"))?;
+
+    if !certification.is_valid_at(at) {
+        return Err(invalid("not valid at the verification time"));
+    }
+    Ok(())
+}
+
+/// Verifies a chain of certifications leading from a trusted root key to a target key
+///
+/// The chain is ordered from the root: the first certification must be issued by `root_public_key`,
+/// each following one by the key the previous one certified, and the last must certify
+/// `target_public_key`. Every link is checked with [`verify_certification`].
+pub fn verify_chain(
+    chain: &[Certification],
+    root_public_key: &str,
+    target_public_key: &str,
+    at: DateTime<Utc>,
+) -> Result<(), KeyManagementError> {
+    if chain.is_empty() {
+        return Err(KeyManagementError::SignatureVerificationFailed("Certification chain is empty".to_string()));
+    }
+
+    let mut expected_certifier = root_public_key;
+    for certification in chain {
+        if certification.payload.certifier_public_key != expected_certifier {
+            return Err(KeyManagementError::SignatureVerificationFailed(format!(
+                "Certification {} is not issued by the previously certified key",
+                certification.id
+            )));
+        }
+        verify_certification(certification, at)?;
+        expected_certifier = &certification.payload.target_public_key;
+    }
+
+    if expected_certifier != target_public_key {
+        return Err(KeyManagementError::SignatureVerificationFailed(
+            "Certification chain does not end at the target key".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// File-backed store of issued certifications
+pub struct CertificationStore {
+    certifications: Mutex<Vec<Certification>>,
+    storage_path: String,
+}
+
+impl CertificationStore {
+    /// Creates a new certification store persisted at `storage_path`
+    pub fn new(storage_path: &str) -> Self {
+        Self {
+            certifications: Mutex::new(Vec::new()),
+            storage_path: storage_path.to_string(),
+        }
+    }
+
+    /// Records a newly issued certification
+    pub async fn record(&self, certification: Certification) -> Result<(), KeyManagementError> {
+        self.certifications.lock().await.push(certification);
+        self.save_to_disk().await
+    }
+
+    /// Certifications issued by a key
+    pub async fn issued_by(&self, key_id: Uuid) -> Vec<Certification> {
+        let certifications = self.certifications.lock().await;
+        certifications.iter()
+            .filter(|certification| certification.payload.certifier_key_id == key_id)
+            .cloned()
+            .collect()
+    }
+
+    /// Certifications received by the key with the given fingerprint
+    pub async fn received_by(&self, fingerprint: &str) -> Vec<Certification> {
+        let certifications = self.certifications.lock().await;
+        certifications.iter()
+            .filter(|certification| certification.payload.target_fingerprint == fingerprint)
+            .cloned()
+            .collect()
+    }
+
+    /// Collects the chain of certifications leading to the key with the given fingerprint
+    ///
+    /// Walks backwards through certifiers, preferring the most recently issued certification that
+    /// verifies at `at`, and returns the chain ordered from its root. Empty when the key has no
+    /// valid certification.
+    pub async fn chain_for(&self, fingerprint: &str, at: DateTime<Utc>) -> Vec<Certification> {
+        let certifications = self.certifications.lock().await;
+        let mut chain = Vec::new();
+        let mut visited = HashSet::from([fingerprint.to_string()]);
+        let mut current = fingerprint.to_string();
+
+        while chain.len() < MAX_CHAIN_LENGTH {
+            let next = certifications.iter()
+                .filter(|certification| certification.payload.target_fingerprint == current)
+                .filter(|certification| !visited.contains(&certification.payload.certifier_fingerprint))
+                .filter(|certification| verify_certification(certification, at).is_ok())
+                .max_by_key(|certification| certification.payload.issued_at);
+            let Some(certification) = next else { break };
+
+            current = certification.payload.certifier_fingerprint.clone();
+            visited.insert(current.clone());
+            chain.push(certification.clone());
+        }
+
+        chain.reverse();
+        chain
+    }
+
+    /// Loads certifications from disk on startup
+    pub async fn load_from_disk(&self) -> Result<(), KeyManagementError> {
+        let path = Path::new(&self.storage_path);
+        if !path.exists() {
+            return Ok(());
+        }
+
+        let content = fs::read_to_string(path).await
+            .map_err(|e| KeyManagementError::StorageError(format!("Failed to read certifications file: {}", e)))?;
+        if content.is_empty() {
+            return Ok(());
+        }
+
+        let loaded: Vec<Certification> = serde_json::from_str(&content)
+            .map_err(|e| KeyManagementError::StorageError(format!("Failed to parse certifications file: {}", e)))?;
+        *self.certifications.lock().await = loaded;
+        Ok(())
+    }
+
+    async fn save_to_disk(&self) -> Result<(), KeyManagementError> {
+        let certifications = self.certifications.lock().await;
+        let content = serde_json::to_string_pretty(&*certifications)
+            .map_err(|e| KeyManagementError::StorageError(format!("Failed to serialize certifications: {}", e)))?;
+        fs::write(&self.storage_path, content).await
+            .map_err(|e| KeyManagementError::StorageError(format!("Failed to write certifications file: {}", e)))?;
+        Ok(())
+    }
+}
+
+/// Creates a certification store at `CERTIFICATIONS_PATH` (default `certifications.json`)
+pub fn create_default_certification_store() -> CertificationStore {
+    let storage_path = std::env::var("CERTIFICATIONS_PATH").unwrap_or_else(|_| "certifications.json".to_string());
+    CertificationStore::new(&storage_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::SigningKey;
+    use rand_core::OsRng;
+
+    fn encode(signing_key: &SigningKey) -> String {
+        base64::engine::general_purpose::STANDARD.encode(signing_key.verifying_key().to_bytes())
+    }
+
+    fn certify(certifier: &SigningKey, target: &SigningKey, issued_at: DateTime<Utc>) -> Certification {
+        let payload = CertificationPayload {
+            certifier_key_id: Uuid::new_v4(),
+            certifier_public_key: encode(certifier),
+            certifier_fingerprint: public_key_to_fingerprint(&encode(certifier)).unwrap(),
+            target_key_id: None,
+            target_public_key: encode(target),
+            target_fingerprint: public_key_to_fingerprint(&encode(target)).unwrap(),
+            issued_at,
+            valid_until: Some(issued_at + chrono::Duration::days(30)),
+        };
+        Certification::issue(payload, certifier).unwrap()
+    }
+
+    #[test]
+    fn test_tampered_certifications_are_rejected() {
+        let now = Utc::now();
+        let (certifier, target, other) = (SigningKey::generate(&mut OsRng), SigningKey::generate(&mut OsRng), SigningKey::generate(&mut OsRng));
+        let certification = certify(&certifier, &target, now);
+        assert!(verify_certification(&certification, now).is_ok());
+        assert!(verify_certification(&certification, now + chrono::Duration::days(31)).is_err());
+
+        let mut swapped = certification.clone();
+        swapped.payload.target_public_key = encode(&other);
+        swapped.payload.target_fingerprint = public_key_to_fingerprint(&encode(&other)).unwrap();
+        assert!(verify_certification(&swapped, now).is_err());
+
+        let mut extended = certification;
+        extended.payload.valid_until = None;
+        assert!(verify_certification(&extended, now).is_err());
+    }
+
+    #[test]
+    fn test_chain_must_link_root_to_target() {
+        let now = Utc::now();
+        let keys: Vec<SigningKey> = (0..3).map(|_| SigningKey::generate(&mut OsRng)).collect();
+        let chain = vec![certify(&keys[0], &keys[1], now), certify(&keys[1], &keys[2], now)];
+
+        assert!(verify_chain(&chain, &encode(&keys[0]), &encode(&keys[2]), now).is_ok());
+        assert!(verify_chain(&chain, &encode(&keys[1]), &encode(&keys[2]), now).is_err());
+        assert!(verify_chain(&chain[1..], &encode(&keys[0]), &encode(&keys[2]), now).is_err());
+        assert!(verify_chain(&[chain[1].clone(), chain[0].clone()], &encode(&keys[0]), &encode(&keys[2]), now).is_err());
+    }
+}
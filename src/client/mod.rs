@@ -0,0 +1,497 @@
+//! Typed client for the HTTP API
+//!
+//! Rust services call the key management service through [`InkanClient`] rather than
+//! hand-rolled requests. Requests and responses are the [`crate::models`] types the server
+//! itself uses, so the two cannot drift apart, and are sent to the `/v1` paths those types
+//! describe. Requests can be HMAC-signed as described in [`crate::request_auth`], or carry a
+//! bearer token for deployments behind an authenticating gateway.
+//!
+//! Key passwords set on signing and generation requests are sent in the `X-Key-Password`
+//! header rather than the body, which gateways may log. A password that cannot be a header
+//! value, one with characters outside visible ASCII, stays in the body.
+//!
+//! A signature covers the request and the second it was signed in, so an identical request
+//! signed again within that second would be refused as a replay; the client waits for the
+//! next second before sending one.
+//!
+//! Failures surface as [`ClientError::Api`] with the service's error code, including those
+//! reported as `success: false` in a `200` response.
+//!
+//! Requests refused with `429 Too Many Requests` or `503 Service Unavailable` were turned away
+//! before any work was done, so they are retried with exponential backoff, waiting as long as
+//! `Retry-After` asks when the service sends it.
+
+use crate::api::{ListKeysQuery, KEY_PASSWORD_HEADER};
+use crate::api_version::ApiVersion;
+use crate::models::{
+    ErrorCode, GenerateKeyRequest, GenerateKeyResponse, KeyRemovalResponse, KeyStatsResponse, ListKeysResponse, PublicKeyResponse,
+    RevokeKeyRequest, RevokeKeyResponse, SignDocumentRequest, SignDocumentResponse, SignatureRecordResponse,
+    UpdateKeyRequest, UpdateKeyResponse, VerifySignatureRequest, VerifySignatureResponse, VersionResponse,
+};
+// Key transport and legacy import, which verifier-only builds compile out
+#[cfg(not(feature = "verifier-only"))]
+use crate::models::{
+    ExportWrappedKeyRequest, ImportLegacyKeysRequest, ImportLegacyKeysResponse, ImportWrappedKeyRequest, ImportWrappedKeyResponse,
+    TransportKeyResponse, WrappedKeyResponse,
+};
+use crate::request_auth::{CLIENT_ID_HEADER, SIGNATURE_HEADER, TIMESTAMP_HEADER};
+use crate::utils::sign_request;
+use reqwest::header::{HeaderValue, AUTHORIZATION, CONTENT_TYPE, RETRY_AFTER};
+use reqwest::{Method, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Default time allowed for one request, retries excluded
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+/// Default number of retries after a `429` or `503`
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+/// Default wait before the first retry when the service sends no `Retry-After`; doubled each retry
+pub const DEFAULT_RETRY_BACKOFF: Duration = Duration::from_secs(1);
+/// Default longest wait before a retry; a longer `Retry-After` is returned as an error instead
+pub const DEFAULT_MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// How requests prove who sent them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientAuth {
+    /// HMAC request signing with a secret listed in the service's `INKAN_HMAC_CLIENTS`
+    Hmac { client_id: String, secret: String },
+    /// `Authorization: Bearer` token, checked by a gateway in front of the service
+    Bearer(String),
+}
+
+/// Where the service is and how to talk to it
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    /// Service root, such as `http://localhost:3002`
+    pub base_url: String,
+    pub auth: Option<ClientAuth>,
+    pub timeout: Duration,
+    pub max_retries: u32,
+    pub retry_backoff: Duration,
+    pub max_retry_delay: Duration,
+}
+
+impl ClientConfig {
+    /// Defaults for the service at `base_url`, without authentication
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into(),
+            auth: None,
+            timeout: DEFAULT_TIMEOUT,
+            max_retries: DEFAULT_MAX_RETRIES,
+            retry_backoff: DEFAULT_RETRY_BACKOFF,
+            max_retry_delay: DEFAULT_MAX_RETRY_DELAY,
+        }
+    }
+}
+
+/// Why a call failed
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+    /// The service could not be reached, or did not answer in time
+    #[error("Request failed: {0}")]
+    Transport(#[from] reqwest::Error),
+    /// The service answered with an error status
+    #[error("Service returned {status}: {message}")]
+    Api {
+        status: StatusCode,
+        /// Stable error code from the response body, when it carried one
+        code: Option<ErrorCode>,
+        message: String,
+        /// The whole response body, for fields specific to the endpoint
+        body: Option<serde_json::Value>,
+    },
+    /// A successful response did not have the expected shape
+    #[error("Malformed response: {0}")]
+    Decode(String),
+}
+
+impl ClientError {
+    /// Stable error code of an API error
+    pub fn code(&self) -> Option<ErrorCode> {
+        match self {
+            ClientError::Api { code, .. } => *code,
+            _ => None,
+        }
+    }
+}
+
+/// Typed client with a method for each key lifecycle, signing and verification endpoint
+#[derive(Debug, Clone)]
+pub struct InkanClient {
+    config: ClientConfig,
+    http: reqwest::Client,
+    /// Signatures sent in the current second, by the second
+    signed: Arc<Mutex<(i64, HashSet<String>)>>,
+}
+
+impl InkanClient {
+    pub fn new(config: ClientConfig) -> Result<Self, ClientError> {
+        let http = reqwest::Client::builder().timeout(config.timeout).build()?;
+        Ok(Self { config, http, signed: Arc::default() })
+    }
+
+    /// Whether `GET /health` answers
+    pub async fn health(&self) -> Result<bool, ClientError> {
+        let response = self.http.get(self.url("/health")).send().await?;
+        Ok(response.status().is_success())
+    }
+
+    /// `GET /version`
+    pub async fn version(&self) -> Result<VersionResponse, ClientError> {
+        self.call(Method::GET, "/version", &[], None::<&()>).await
+    }
+
+    /// `POST /keys/generate`
+    pub async fn generate_key(&self, request: &GenerateKeyRequest) -> Result<GenerateKeyResponse, ClientError> {
+        let (body, password) = detach_password(request)?;
+        self.call_with_password(Method::POST, "/keys/generate", &[], Some(&body), password.as_deref()).await
+    }
+
+    /// `POST /keys/generate?dry_run=true`: validates the request without creating a key
+    pub async fn validate_generate(&self, request: &GenerateKeyRequest) -> Result<GenerateKeyResponse, ClientError> {
+        let (body, password) = detach_password(request)?;
+        self.call_with_password(Method::POST, "/keys/generate", &[("dry_run", "true".to_string())], Some(&body), password.as_deref()).await
+    }
+
+    /// `GET /keys`
+    pub async fn list_keys(&self, query: &ListKeysQuery) -> Result<ListKeysResponse, ClientError> {
+        self.call(Method::GET, "/keys", &listing_query(query), None::<&()>).await
+    }
+
+    /// `GET /keys/search`
+    pub async fn search_keys(&self, query: &ListKeysQuery) -> Result<ListKeysResponse, ClientError> {
+        self.call(Method::GET, "/keys/search", &listing_query(query), None::<&()>).await
+    }
+
+    /// `GET /keys/stats`
+    pub async fn key_stats(&self) -> Result<KeyStatsResponse, ClientError> {
+        self.call(Method::GET, "/keys/stats", &[], None::<&()>).await
+    }
+
+    /// `GET /keys/:key_id`
+    pub async fn get_key(&self, key_id: Uuid) -> Result<PublicKeyResponse, ClientError> {
+        self.call(Method::GET, &format!("/keys/{}", key_id), &[], None::<&()>).await
+    }
+
+    /// `PATCH /keys/:key_id`
+    pub async fn update_key(&self, key_id: Uuid, request: &UpdateKeyRequest) -> Result<UpdateKeyResponse, ClientError> {
+        self.call(Method::PATCH, &format!("/keys/{}", key_id), &[], Some(request)).await
+    }
+
+    /// `POST /keys/:key_id/revoke`, now or at `effective_at`
+    pub async fn revoke_key(&self, request: &RevokeKeyRequest) -> Result<RevokeKeyResponse, ClientError> {
+        self.call(Method::POST, &format!("/keys/{}/revoke", request.key_id), &[], Some(request)).await
+    }
+
+    /// `DELETE /keys/:key_id/revoke-schedule`
+    pub async fn cancel_scheduled_revocation(&self, key_id: Uuid) -> Result<RevokeKeyResponse, ClientError> {
+        self.call(Method::DELETE, &format!("/keys/{}/revoke-schedule", key_id), &[], None::<&()>).await
+    }
+
+    /// `DELETE /keys/:key_id`: soft-deletes the key
+    pub async fn delete_key(&self, key_id: Uuid) -> Result<KeyRemovalResponse, ClientError> {
+        self.call(Method::DELETE, &format!("/keys/{}", key_id), &[], None::<&()>).await
+    }
+
+    /// `POST /keys/:key_id/restore`
+    pub async fn restore_key(&self, key_id: Uuid) -> Result<KeyRemovalResponse, ClientError> {
+        self.call(Method::POST, &format!("/keys/{}/restore", key_id), &[], None::<&()>).await
+    }
+
+    /// `POST /sign`
+    pub async fn sign(&self, request: &SignDocumentRequest) -> Result<SignDocumentResponse, ClientError> {
+        let (body, password) = detach_password(request)?;
+        self.call_with_password(Method::POST, "/sign", &[], Some(&body), password.as_deref()).await
+    }
+
+    /// `POST /verify`
+    pub async fn verify(&self, request: &VerifySignatureRequest) -> Result<VerifySignatureResponse, ClientError> {
+        self.call(Method::POST, "/verify", &[], Some(request)).await
+    }
+
+    /// `GET /signatures/by-id/:signature_id`
+    pub async fn signature_record(&self, signature_id: Uuid) -> Result<SignatureRecordResponse, ClientError> {
+        self.call(Method::GET, &format!("/signatures/by-id/{}", signature_id), &[], None::<&()>).await
+    }
+
+    /// `GET /admin/transport-key`
+    #[cfg(not(feature = "verifier-only"))]
+    pub async fn transport_key(&self) -> Result<TransportKeyResponse, ClientError> {
+        self.call(Method::GET, "/admin/transport-key", &[], None::<&()>).await
+    }
+
+    /// `POST /keys/:key_id/export`: wraps the key for another instance's transport key
+    #[cfg(not(feature = "verifier-only"))]
+    pub async fn export_wrapped_key(&self, key_id: Uuid, request: &ExportWrappedKeyRequest) -> Result<WrappedKeyResponse, ClientError> {
+        self.call(Method::POST, &format!("/keys/{}/export", key_id), &[], Some(request)).await
+    }
+
+    /// `POST /keys/import-wrapped`
+    #[cfg(not(feature = "verifier-only"))]
+    pub async fn import_wrapped_key(&self, request: &ImportWrappedKeyRequest) -> Result<ImportWrappedKeyResponse, ClientError> {
+        self.call(Method::POST, "/keys/import-wrapped", &[], Some(request)).await
+    }
+
+    /// `POST /keys/import-legacy`
+    #[cfg(not(feature = "verifier-only"))]
+    pub async fn import_legacy_keys(&self, request: &ImportLegacyKeysRequest) -> Result<ImportLegacyKeysResponse, ClientError> {
+        self.call(Method::POST, "/keys/import-legacy", &[], Some(request)).await
+    }
+
+    /// URL of `path` in the API version the client speaks
+    fn url(&self, path: &str) -> String {
+        format!("{}{}{}", self.config.base_url.trim_end_matches('/'), ApiVersion::V1.prefix(), path)
+    }
+
+    /// Sends a request, retrying refusals for capacity, and decodes the JSON answer
+    async fn call<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        query: &[(&str, String)],
+        body: Option<&impl Serialize>,
+    ) -> Result<T, ClientError> {
+        self.call_with_password(method, path, query, body, None).await
+    }
+
+    /// Sends a request like [`InkanClient::call`], with `password` in the key password header
+    async fn call_with_password<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        query: &[(&str, String)],
+        body: Option<&impl Serialize>,
+        password: Option<&str>,
+    ) -> Result<T, ClientError> {
+        let body = match body {
+            Some(body) => Some(serde_json::to_vec(body).map_err(|e| ClientError::Decode(format!("Failed to encode request: {}", e)))?),
+            None => None,
+        };
+
+        let mut attempt = 0;
+        loop {
+            let response = self.http.execute(self.build(method.clone(), path, query, body.as_deref(), password).await?).await?;
+            let status = response.status();
+            if status.is_success() {
+                // Some endpoints report a failure with `success: false` in a 200 response
+                let bytes = response.bytes().await?;
+                let value: serde_json::Value = serde_json::from_slice(&bytes).map_err(|e| ClientError::Decode(e.to_string()))?;
+                if value.get("success") == Some(&serde_json::Value::Bool(false)) {
+                    return Err(api_error(status, &bytes));
+                }
+                return serde_json::from_value(value).map_err(|e| ClientError::Decode(e.to_string()));
+            }
+
+            let retry_after = response.headers().get(RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.trim().parse::<u64>().ok())
+                .map(Duration::from_secs);
+            let error = api_error(status, response.bytes().await.unwrap_or_default().as_ref());
+            if !matches!(status, StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE) || attempt >= self.config.max_retries {
+                return Err(error);
+            }
+            let delay = retry_after.unwrap_or_else(|| self.config.retry_backoff.saturating_mul(1 << attempt.min(16)));
+            if delay > self.config.max_retry_delay {
+                return Err(error);
+            }
+            tracing::debug!("{} {} returned {}, retrying in {:?}", method, path, status, delay);
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Builds one attempt, signed at the current time
+    async fn build(
+        &self,
+        method: Method,
+        path: &str,
+        query: &[(&str, String)],
+        body: Option<&[u8]>,
+        password: Option<&str>,
+    ) -> Result<reqwest::Request, ClientError> {
+        let mut builder = self.http.request(method.clone(), self.url(path)).query(query);
+        if let Some(body) = body {
+            builder = builder.header(CONTENT_TYPE, "application/json").body(body.to_vec());
+        }
+        let mut request = builder.build()?;
+        if let Some(password) = password {
+            let mut value = header_value(password)?;
+            value.set_sensitive(true);
+            request.headers_mut().insert(KEY_PASSWORD_HEADER, value);
+        }
+
+        match &self.config.auth {
+            Some(ClientAuth::Hmac { client_id, secret }) => {
+                let url = request.url();
+                let signed_path = match url.query() {
+                    Some(query) => format!("{}?{}", url.path(), query),
+                    None => url.path().to_string(),
+                };
+                let (timestamp, signature) = loop {
+                    let now = chrono::Utc::now();
+                    let timestamp = now.timestamp();
+                    let signature = sign_request(secret.as_bytes(), method.as_str(), &signed_path, timestamp, body.unwrap_or_default());
+                    {
+                        let mut signed = self.signed.lock().unwrap_or_else(|e| e.into_inner());
+                        if signed.0 != timestamp {
+                            *signed = (timestamp, HashSet::new());
+                        }
+                        if signed.1.insert(signature.clone()) {
+                            break (timestamp, signature);
+                        }
+                    }
+                    let next_second = 1_000 - u64::from(now.timestamp_subsec_millis().min(999));
+                    tokio::time::sleep(Duration::from_millis(next_second)).await;
+                };
+                let headers = request.headers_mut();
+                headers.insert(CLIENT_ID_HEADER, header_value(client_id)?);
+                headers.insert(TIMESTAMP_HEADER, header_value(&timestamp.to_string())?);
+                headers.insert(SIGNATURE_HEADER, header_value(&signature)?);
+            }
+            Some(ClientAuth::Bearer(token)) => {
+                request.headers_mut().insert(AUTHORIZATION, header_value(&format!("Bearer {}", token))?);
+            }
+            None => {}
+        }
+        Ok(request)
+    }
+}
+
+fn header_value(value: &str) -> Result<HeaderValue, ClientError> {
+    HeaderValue::from_str(value).map_err(|e| ClientError::Decode(format!("Invalid header value: {}", e)))
+}
+
+/// A request as JSON, with its `password` taken out to be sent in the key password header
+///
+/// Only visible ASCII passwords, which the service accepts in the header, are taken out; any
+/// other password is left in the body.
+fn detach_password(request: &impl Serialize) -> Result<(serde_json::Value, Option<String>), ClientError> {
+    let mut body = serde_json::to_value(request).map_err(|e| ClientError::Decode(format!("Failed to encode request: {}", e)))?;
+    let password = body.get("password")
+        .and_then(serde_json::Value::as_str)
+        .filter(|password| password.bytes().all(|b| (0x20..0x7f).contains(&b)))
+        .map(str::to_string);
+    if password.is_some() || body.get("password").is_some_and(serde_json::Value::is_null) {
+        if let Some(body) = body.as_object_mut() {
+            body.remove("password");
+        }
+    }
+    Ok((body, password))
+}
+
+/// Query parameters of a listing, leaving out those that are unset
+fn listing_query(query: &ListKeysQuery) -> Vec<(&'static str, String)> {
+    let mut pairs = Vec::new();
+    if let Some(active_only) = query.active_only {
+        pairs.push(("active_only", active_only.to_string()));
+    }
+    if let Some(key_type) = &query.key_type {
+        pairs.push(("key_type", key_type.clone()));
+    }
+    if let Some(tags) = &query.tags {
+        pairs.push(("tags", tags.clone()));
+    }
+    if let Some(search) = &query.search {
+        pairs.push(("search", search.clone()));
+    }
+    if query.offset > 0 {
+        pairs.push(("offset", query.offset.to_string()));
+    }
+    if let Some(limit) = query.limit {
+        pairs.push(("limit", limit.to_string()));
+    }
+    if let Some(cursor) = &query.cursor {
+        pairs.push(("cursor", cursor.clone()));
+    }
+    pairs
+}
+
+/// The error an unsuccessful response describes, from its `code` and `message` fields
+fn api_error(status: StatusCode, body: &[u8]) -> ClientError {
+    let body: Option<serde_json::Value> = serde_json::from_slice(body).ok();
+    let field = |name: &str| body.as_ref().and_then(|body| body.get(name)).cloned();
+    ClientError::Api {
+        status,
+        code: field("code").and_then(|code| serde_json::from_value(code).ok()),
+        message: field("message")
+            .and_then(|message| message.as_str().map(str::to_string))
+            .unwrap_or_else(|| status.canonical_reason().unwrap_or("Request failed").to_string()),
+        body,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode as ServerStatus;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    /// Serves `/v1/keys/stats`, refusing the first `refusals` requests with `503`
+    async fn spawn_flaky_server(refusals: u32, retry_after: &'static str) -> (String, Arc<AtomicU32>) {
+        let requests = Arc::new(AtomicU32::new(0));
+        let counter = requests.clone();
+        let app = axum::Router::new().route("/v1/keys/stats", axum::routing::get(move || {
+            let counter = counter.clone();
+            async move {
+                if counter.fetch_add(1, Ordering::SeqCst) < refusals {
+                    let body = serde_json::json!({ "success": false, "code": "OVERLOADED", "message": "Too many signing operations" });
+                    return (ServerStatus::SERVICE_UNAVAILABLE, [("retry-after", retry_after)], axum::Json(body));
+                }
+                let stats = serde_json::json!({
+                    "success": true, "total_keys": 0, "active_keys": 0, "expired_keys": 0, "revoked_keys": 0,
+                    "keys_expiring_soon": 0, "total_sign_count": 0, "total_verify_count": 0,
+                    "capacity": { "level": "ok", "keys": 0, "soft_limit": null, "hard_limit": null, "headroom": null },
+                    "message": "Key statistics",
+                });
+                (ServerStatus::OK, [("retry-after", "0")], axum::Json(stats))
+            }
+        }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{}", address), requests)
+    }
+
+    #[tokio::test]
+    async fn test_refusals_for_capacity_are_retried() {
+        let (base_url, requests) = spawn_flaky_server(2, "0").await;
+        let client = InkanClient::new(ClientConfig { retry_backoff: Duration::from_millis(1), ..ClientConfig::new(&base_url) }).unwrap();
+        assert!(client.key_stats().await.unwrap().success);
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+
+        // Out of retries, the refusal is returned with its code
+        let (base_url, requests) = spawn_flaky_server(5, "0").await;
+        let client = InkanClient::new(ClientConfig { max_retries: 1, ..ClientConfig::new(&base_url) }).unwrap();
+        let error = client.key_stats().await.unwrap_err();
+        assert_eq!(error.code(), Some(ErrorCode::Overloaded));
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+        // A Retry-After beyond the longest wait is not waited out
+        let (base_url, requests) = spawn_flaky_server(1, "300").await;
+        let client = InkanClient::new(ClientConfig::new(&base_url)).unwrap();
+        assert!(matches!(client.key_stats().await, Err(ClientError::Api { status: StatusCode::SERVICE_UNAVAILABLE, .. })));
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_passwords_are_detached_for_the_header() {
+        let request = |password: Option<&str>| SignDocumentRequest { password: password.map(str::to_string), ..Default::default() };
+        let (body, password) = detach_password(&request(Some("hunter22"))).unwrap();
+        assert_eq!(password.as_deref(), Some("hunter22"));
+        assert!(body.get("password").is_none());
+
+        let (body, password) = detach_password(&request(None)).unwrap();
+        assert!(password.is_none() && body.get("password").is_none());
+
+        // Not a valid header value, so it stays where it was
+        let (body, password) = detach_password(&request(Some("p\u{e4}sswort"))).unwrap();
+        assert!(password.is_none());
+        assert_eq!(body["password"], "p\u{e4}sswort");
+    }
+}
@@ -0,0 +1,1351 @@
+//! Service configuration
+//!
+//! Values are read from `INKAN_*` environment variables at startup and fall back to the
+//! defaults the service has always used.
+
+use crate::api_version::DEFAULT_UNVERSIONED_SUNSET;
+use crate::environment::{MismatchPolicy, DEFAULT_ENVIRONMENTS, UNKNOWN_ENVIRONMENT};
+use crate::federation::FederationPeer;
+use crate::field_case::FieldCase;
+use crate::key_formats::normalize_public_key;
+use crate::key_storage::NonExportableBackup;
+use crate::limits::OverloadPolicy;
+use crate::models::KeyManagementError;
+use crate::profile::DeploymentProfile;
+use crate::rate_limit::{BackendFailure, BackendFailureModes};
+use crate::receipts::ReceiptFailurePolicy;
+use crate::request_auth::parse_clients;
+use crate::storage_lock::LockConflict;
+use crate::templates::{parse_templates, KeyTemplate};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Instant;
+use uuid::Uuid;
+
+/// Iteration count used before KDF parameters were configurable; keys without stored
+/// parameters were encrypted with it
+pub const LEGACY_PBKDF2_ITERATIONS: u32 = 100_000;
+
+/// Lower bounds below which suggested parameters are never allowed to fall
+pub const MIN_PBKDF2_ITERATIONS: u32 = 10_000;
+pub const MIN_ARGON2_ITERATIONS: u32 = 1;
+
+/// Upper bounds on work factors, so parameters read from a stored key cannot make a single
+/// unlock run for hours or allocate more memory than a host has
+pub const MAX_PBKDF2_ITERATIONS: u32 = 100_000_000;
+pub const MAX_ARGON2_ITERATIONS: u32 = 1_000;
+pub const MAX_ARGON2_MEMORY_KIB: u32 = 4 * 1024 * 1024;
+pub const MAX_ARGON2_PARALLELISM: u32 = 64;
+
+/// Password-based key derivation function used to protect private keys
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum KdfAlgorithm {
+    #[default]
+    Pbkdf2Sha256,
+    Argon2id,
+}
+
+impl KdfAlgorithm {
+    /// Name used in metrics labels, matching the serialized form
+    pub fn as_str(self) -> &'static str {
+        match self {
+            KdfAlgorithm::Pbkdf2Sha256 => "pbkdf2-sha256",
+            KdfAlgorithm::Argon2id => "argon2id",
+        }
+    }
+}
+
+/// Work factors for a key derivation
+///
+/// For PBKDF2 only `iterations` applies. For Argon2id `iterations` is the time cost and
+/// `memory_kib`/`parallelism` set the memory and lane counts.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct KdfParams {
+    pub algorithm: KdfAlgorithm,
+    pub iterations: u32,
+    #[serde(default)]
+    pub memory_kib: u32,
+    #[serde(default)]
+    pub parallelism: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self::pbkdf2(LEGACY_PBKDF2_ITERATIONS)
+    }
+}
+
+impl KdfParams {
+    pub fn pbkdf2(iterations: u32) -> Self {
+        Self {
+            algorithm: KdfAlgorithm::Pbkdf2Sha256,
+            iterations,
+            memory_kib: 0,
+            parallelism: 0,
+        }
+    }
+
+    pub fn argon2id(iterations: u32, memory_kib: u32, parallelism: u32) -> Self {
+        Self {
+            algorithm: KdfAlgorithm::Argon2id,
+            iterations,
+            memory_kib,
+            parallelism,
+        }
+    }
+
+    /// Derives a 32-byte encryption key from a password and salt
+    pub fn derive_key(&self, password: &[u8], salt: &[u8]) -> Result<[u8; 32], KeyManagementError> {
+        self.check_bounds()?;
+        let mut key = [0u8; 32];
+        match self.algorithm {
+            KdfAlgorithm::Pbkdf2Sha256 => {
+                pbkdf2::pbkdf2::<hmac::Hmac<sha2::Sha256>>(password, salt, self.iterations, &mut key)
+                    .map_err(|_| KeyManagementError::InternalError("PBKDF2 key derivation failed".to_string()))?;
+            }
+            KdfAlgorithm::Argon2id => {
+                let params = argon2::Params::new(self.memory_kib, self.iterations, self.parallelism, Some(key.len()))
+                    .map_err(|e| KeyManagementError::InternalError(format!("Invalid Argon2 parameters: {}", e)))?;
+                argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params)
+                    .hash_password_into(password, salt, &mut key)
+                    .map_err(|e| KeyManagementError::InternalError(format!("Argon2 key derivation failed: {}", e)))?;
+            }
+        }
+        Ok(key)
+    }
+
+    /// Checks the work factors are within the bounds any key may use
+    pub fn check_bounds(&self) -> Result<(), KeyManagementError> {
+        let too_large = |what: &str, max: u32| {
+            Err(KeyManagementError::ValidationFailed(format!("{} must be at most {}", what, max)))
+        };
+        match self.algorithm {
+            KdfAlgorithm::Pbkdf2Sha256 if self.iterations > MAX_PBKDF2_ITERATIONS => too_large("PBKDF2 iterations", MAX_PBKDF2_ITERATIONS),
+            KdfAlgorithm::Argon2id if self.iterations > MAX_ARGON2_ITERATIONS => too_large("Argon2 iterations", MAX_ARGON2_ITERATIONS),
+            KdfAlgorithm::Argon2id if self.memory_kib > MAX_ARGON2_MEMORY_KIB => too_large("Argon2 memory", MAX_ARGON2_MEMORY_KIB),
+            KdfAlgorithm::Argon2id if self.parallelism > MAX_ARGON2_PARALLELISM => too_large("Argon2 parallelism", MAX_ARGON2_PARALLELISM),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the parameters are usable before they are applied to new keys
+    pub fn validate(&self) -> Result<(), KeyManagementError> {
+        self.check_bounds()?;
+        match self.algorithm {
+            KdfAlgorithm::Pbkdf2Sha256 if self.iterations < MIN_PBKDF2_ITERATIONS => Err(KeyManagementError::ValidationFailed(
+                format!("PBKDF2 iterations must be at least {}", MIN_PBKDF2_ITERATIONS),
+            )),
+            KdfAlgorithm::Pbkdf2Sha256 => Ok(()),
+            KdfAlgorithm::Argon2id => argon2::Params::new(self.memory_kib, self.iterations, self.parallelism, Some(32))
+                .map(|_| ())
+                .map_err(|e| KeyManagementError::ValidationFailed(format!("Invalid Argon2 parameters: {}", e))),
+        }
+    }
+}
+
+/// Clock difference tolerated between clients and the service when checking timestamps
+pub const DEFAULT_CLOCK_SKEW_SECS: u32 = 60;
+
+/// Seconds between background sweeps of the keystore
+pub const DEFAULT_SWEEP_INTERVAL_SECS: u32 = 60;
+
+/// Seconds between periodic checks of the entropy source behind key generation
+pub const DEFAULT_ENTROPY_CHECK_INTERVAL_SECS: u32 = 3_600;
+
+/// Seconds without a heartbeat after which another instance's keystore lock counts as abandoned
+pub const DEFAULT_LOCK_STALE_SECS: u32 = 30;
+
+/// Requests that may wait for a slot when an expensive operation is at its concurrency limit
+pub const DEFAULT_OVERLOAD_QUEUE_DEPTH: u32 = 16;
+
+/// Seconds a queued request waits for a slot before it is refused
+pub const DEFAULT_OVERLOAD_QUEUE_TIMEOUT_SECS: u32 = 10;
+
+/// `Retry-After` seconds sent with requests refused because an operation is at capacity
+pub const DEFAULT_OVERLOAD_RETRY_AFTER_SECS: u32 = 1;
+
+/// Seconds an ordinary request may run before it is answered `504`
+pub const DEFAULT_REQUEST_TIMEOUT_SECS: u32 = 30;
+
+/// Seconds a request to one of the long routes, such as the keystore export, may run
+pub const DEFAULT_LONG_REQUEST_TIMEOUT_SECS: u32 = 300;
+
+/// Seconds clients are asked to wait before retrying a mutation refused in read-only mode
+pub const DEFAULT_READ_ONLY_RETRY_AFTER_SECS: u32 = 300;
+
+/// Days before expiry at which key owners are notified
+pub const DEFAULT_NOTIFY_THRESHOLDS_DAYS: [u32; 3] = [30, 7, 1];
+
+/// Keys given their own label in per-key metrics before the rest are aggregated
+pub const DEFAULT_METRICS_MAX_KEY_LABELS: usize = 50;
+
+/// Smallest response body, in bytes, that is compressed for clients accepting it
+pub const DEFAULT_COMPRESSION_MIN_BYTES: u16 = 1024;
+
+/// Seconds a verification link stays open when the request does not say
+pub const DEFAULT_SHARE_TTL_SECS: u32 = 7 * 24 * 60 * 60;
+
+/// Longest lifetime, in seconds, a verification link may be given
+pub const DEFAULT_SHARE_MAX_TTL_SECS: u32 = 30 * 24 * 60 * 60;
+
+/// Requests allowed against one verification link per minute
+pub const DEFAULT_SHARE_REQUESTS_PER_MINUTE: u32 = 30;
+
+/// Longest lifetime, in seconds, a signing delegation may be given
+pub const DEFAULT_DELEGATION_MAX_TTL_SECS: u32 = 24 * 60 * 60;
+
+/// Verification results kept in the verification cache
+pub const DEFAULT_VERIFY_CACHE_SIZE: u32 = 10_000;
+
+/// Seconds a cached verification result is reused
+pub const DEFAULT_VERIFY_CACHE_TTL_SECS: u32 = 60;
+
+/// Largest `document_content`, in bytes, `/verify` accepts
+pub const DEFAULT_VERIFY_MAX_CONTENT_BYTES: u32 = 1024 * 1024;
+
+/// Largest `document_content`, in bytes, `/sign` accepts inline in its JSON body
+pub const DEFAULT_SIGN_MAX_CONTENT_BYTES: u32 = 1024 * 1024;
+
+/// Largest document, in bytes, `/sign/raw` streams into its hash
+pub const DEFAULT_RAW_SIGN_MAX_CONTENT_BYTES: u32 = 256 * 1024 * 1024;
+
+/// Largest upload, archive and manifest together, in bytes, `/verify/archive` accepts
+pub const DEFAULT_VERIFY_ARCHIVE_MAX_BYTES: u32 = 64 * 1024 * 1024;
+
+/// Most entries one archive sent to `/verify/archive` may hold
+pub const DEFAULT_VERIFY_ARCHIVE_MAX_ENTRIES: u32 = 1000;
+
+/// Most times an archive entry may expand over its compressed size
+pub const DEFAULT_VERIFY_ARCHIVE_MAX_RATIO: u32 = 100;
+
+/// Most bytes, in total, an archive's entries may decompress to
+pub const DEFAULT_VERIFY_ARCHIVE_MAX_UNCOMPRESSED_BYTES: u32 = 1024 * 1024 * 1024;
+
+/// Most recent events the operation event log keeps for `/events/replay`
+pub const DEFAULT_EVENT_LOG_RETAIN: u32 = 100_000;
+
+/// Verification requests one unauthenticated client address may make per minute
+pub const DEFAULT_VERIFY_REQUESTS_PER_MINUTE: u32 = 60;
+
+/// Seconds a signed request's timestamp may differ from the service clock
+pub const DEFAULT_HMAC_MAX_SKEW_SECS: u32 = 300;
+
+/// Days before expiry from which responses warn that a key expires soon
+pub const DEFAULT_EXPIRY_WARNING_DAYS: u32 = 7;
+
+/// Seconds caches may keep a public key served at its content-addressed URL (one year)
+pub const DEFAULT_PUBLIC_KEY_MAX_AGE_SECS: u32 = 365 * 24 * 60 * 60;
+
+/// Seconds caches may keep a key status, which changes when the key is revoked
+pub const DEFAULT_KEY_STATUS_MAX_AGE_SECS: u32 = 60;
+
+/// p99 latency objective of key generation, in milliseconds
+pub const DEFAULT_SLO_GENERATE_TARGET_MS: u32 = 1_000;
+
+/// p99 latency objective of signing, in milliseconds
+pub const DEFAULT_SLO_SIGN_TARGET_MS: u32 = 250;
+
+/// p99 latency objective of verification, in milliseconds
+pub const DEFAULT_SLO_VERIFY_TARGET_MS: u32 = 100;
+
+/// Milliseconds the signing policy service has to answer before it counts as unavailable
+pub const DEFAULT_SIGN_POLICY_TIMEOUT_MS: u32 = 2_000;
+
+/// Seconds a signing policy decision is reused for the same key, document, context and client
+pub const DEFAULT_SIGN_POLICY_CACHE_TTL_SECS: u32 = 30;
+
+/// Milliseconds a federation peer has to answer each request of a key lookup
+pub const DEFAULT_FEDERATION_TIMEOUT_MS: u32 = 2_000;
+
+/// Seconds a key resolved from a federation peer is reused (five minutes)
+pub const DEFAULT_FEDERATION_CACHE_TTL_SECS: u32 = 5 * 60;
+
+/// Key lookups per minute that may reach federation peers, across all callers
+pub const DEFAULT_FEDERATION_LOOKUPS_PER_MINUTE: u32 = 60;
+
+/// Seconds a pre-generated key waits in the key pool before it is discarded (one hour)
+pub const DEFAULT_KEY_POOL_MAX_AGE_SECS: u32 = 60 * 60;
+
+/// Percentage of recent keystore operations that must fail or be slow to open the storage breaker
+pub const DEFAULT_STORAGE_BREAKER_FAILURE_PERCENT: u32 = 50;
+
+/// Milliseconds after which a keystore operation counts against the storage breaker
+pub const DEFAULT_STORAGE_BREAKER_SLOW_MS: u32 = 2_000;
+
+/// Seconds the storage breaker stays open before it lets probe requests through
+pub const DEFAULT_STORAGE_BREAKER_OPEN_SECS: u32 = 30;
+
+/// Keystore operations in a row that must succeed while probing to close the storage breaker
+pub const DEFAULT_STORAGE_BREAKER_PROBES: u32 = 3;
+
+/// Channels and schedule for expiring-key notifications
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct NotificationConfig {
+    /// Days before expiry at which each key is notified, once per threshold
+    pub thresholds_days: Vec<u32>,
+    /// URL notices are POSTed to as JSON (requires the `webhook` feature)
+    pub webhook_url: Option<String>,
+    /// SMTP relay used to email notices (requires the `email` feature)
+    pub smtp_host: Option<String>,
+    pub smtp_username: Option<String>,
+    #[serde(skip)]
+    pub smtp_password: Option<String>,
+    pub email_from: Option<String>,
+    pub email_to: Option<String>,
+}
+
+impl Default for NotificationConfig {
+    fn default() -> Self {
+        Self {
+            thresholds_days: DEFAULT_NOTIFY_THRESHOLDS_DAYS.to_vec(),
+            webhook_url: None,
+            smtp_host: None,
+            smtp_username: None,
+            smtp_password: None,
+            email_from: None,
+            email_to: None,
+        }
+    }
+}
+
+/// External service that approves signatures before they are made
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct SignPolicyConfig {
+    /// URL signature summaries are POSTed to for a decision (requires the `webhook` feature);
+    /// unset allows every signature
+    pub url: Option<String>,
+    /// Milliseconds the service has to answer
+    pub timeout_ms: u32,
+    /// Allow signatures when the service fails or times out, instead of refusing them
+    pub fail_open: bool,
+    /// Seconds a decision is reused; 0 disables the cache
+    pub cache_ttl_secs: u32,
+}
+
+impl Default for SignPolicyConfig {
+    fn default() -> Self {
+        Self {
+            url: None,
+            timeout_ms: DEFAULT_SIGN_POLICY_TIMEOUT_MS,
+            fail_open: false,
+            cache_ttl_secs: DEFAULT_SIGN_POLICY_CACHE_TTL_SECS,
+        }
+    }
+}
+
+/// Partner instances keys this instance does not hold are resolved from
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct FederationConfig {
+    /// Peers asked in turn, in configuration order (requires the `federation` feature); empty
+    /// resolves nothing remotely
+    pub peers: Vec<FederationPeer>,
+    /// Milliseconds a peer has to answer each request
+    pub timeout_ms: u32,
+    /// Seconds a resolved key is reused; 0 disables the cache
+    pub cache_ttl_secs: u32,
+    /// Lookups per minute that may reach the peers; 0 disables the limit
+    pub lookups_per_minute: u32,
+}
+
+/// Signed manifest a verifier takes its keys from instead of a keystore
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct TrustManifestConfig {
+    /// Path of the signed pin set, as served by `GET /keys/pinset`
+    pub path: String,
+    /// Base64 raw Ed25519 public key the manifest must be signed by
+    pub root_public_key: String,
+}
+
+/// PKCS#11 module that holds the keys generated with an `hsm` reference
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Pkcs11Config {
+    /// Path of the module's shared library
+    pub module: String,
+    /// User PIN sessions log in with; request passwords are never used for these keys
+    #[serde(skip)]
+    pub pin: String,
+}
+
+/// When storage-dependent requests are refused because the keystore is failing or slow
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct StorageBreakerConfig {
+    /// Percentage of recent keystore operations failing or slow that opens the breaker; 0 disables it
+    pub failure_percent: u32,
+    /// Milliseconds after which a keystore operation counts as slow
+    pub slow_ms: u32,
+    /// Seconds the breaker stays open before probing the keystore
+    pub open_secs: u32,
+    /// Successful keystore operations while probing that close the breaker
+    pub probes: u32,
+}
+
+impl Default for StorageBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_percent: DEFAULT_STORAGE_BREAKER_FAILURE_PERCENT,
+            slow_ms: DEFAULT_STORAGE_BREAKER_SLOW_MS,
+            open_secs: DEFAULT_STORAGE_BREAKER_OPEN_SECS,
+            probes: DEFAULT_STORAGE_BREAKER_PROBES,
+        }
+    }
+}
+
+impl Default for FederationConfig {
+    fn default() -> Self {
+        Self {
+            peers: Vec::new(),
+            timeout_ms: DEFAULT_FEDERATION_TIMEOUT_MS,
+            cache_ttl_secs: DEFAULT_FEDERATION_CACHE_TTL_SECS,
+            lookups_per_minute: DEFAULT_FEDERATION_LOOKUPS_PER_MINUTE,
+        }
+    }
+}
+
+/// Service-wide configuration
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Config {
+    /// Parameters applied when encrypting newly generated or re-encrypted keys
+    pub kdf: KdfParams,
+    /// Unencrypted key that counter-signs verification bundles, if any
+    pub notary_key_id: Option<Uuid>,
+    /// Maximum number of keys the keystore may hold, if limited
+    pub max_keys: Option<usize>,
+    /// Stored keys past which responses, readiness and metrics warn, if set
+    pub keystore_soft_limit: Option<usize>,
+    /// Stored keys at which new keys are refused until others are removed, if set
+    pub keystore_hard_limit: Option<usize>,
+    /// Days after revocation a key may be moved to the archive file to stay within the limits;
+    /// unset disables auto-archival
+    pub archive_revoked_after_days: Option<u32>,
+    /// Seconds a requested expiry may lag the service clock and still count as future
+    pub clock_skew_secs: u32,
+    /// Shortest lifetime, in seconds from now, a key expiry may grant
+    pub min_key_lifetime_secs: u32,
+    /// Longest lifetime, in days from now, a key expiry may grant, if limited
+    pub max_key_lifetime_days: Option<u32>,
+    /// Lifetime, in days, given to generated keys that request no expiry
+    pub default_key_lifetime_days: Option<u32>,
+    /// Reject generation requests beyond the maximum lifetime instead of clamping them
+    pub strict_key_lifetime: bool,
+    /// Seconds between background sweeps that execute scheduled revocations
+    pub sweep_interval_secs: u32,
+    /// Seconds between entropy checks after the one made at startup
+    pub entropy_check_interval_secs: u32,
+    /// What to do when another live instance holds the keystore lock
+    pub lock_conflict: LockConflict,
+    /// Seconds without a heartbeat after which a keystore lock is broken; the owner refreshes
+    /// it every third of this
+    pub lock_stale_secs: u32,
+    /// Reload the keystore when its file is modified outside the service (requires the `watch` feature)
+    pub watch_keystore: bool,
+    /// Most key generations running at once, if limited
+    pub max_concurrent_generations: Option<u32>,
+    /// Most signatures with encrypted keys running at once, if limited
+    pub max_concurrent_signs: Option<u32>,
+    /// Whether requests beyond a concurrency limit queue or are refused
+    pub overload_policy: OverloadPolicy,
+    /// Most requests queued per limited operation
+    pub overload_queue_depth: u32,
+    /// Seconds a queued request waits before it is refused
+    pub overload_queue_timeout_secs: u32,
+    /// `Retry-After` seconds sent with requests refused for capacity
+    pub overload_retry_after_secs: u32,
+    /// Seconds an ordinary request may run
+    pub request_timeout_secs: u32,
+    /// Seconds a request to one of the [long-running routes](crate::routes::long_running_routes) may run
+    pub long_request_timeout_secs: u32,
+    /// Answer a repeat signature of the same document with its existing receipt
+    pub dedupe_signatures: bool,
+    /// Start in read-only mode, refusing every mutation
+    pub read_only: bool,
+    /// `Retry-After` seconds sent with requests refused in read-only mode
+    pub read_only_retry_after_secs: u32,
+    /// Switch to read-only mode after this many consecutive failed keystore writes, if set
+    pub read_only_after_write_failures: Option<u32>,
+    /// Run the self-test before serving traffic and refuse to start if it fails
+    pub startup_self_test: bool,
+    /// Most keys labelled individually in per-key metrics; busier keys are labelled first
+    pub metrics_max_key_labels: usize,
+    /// Smallest response body compressed with gzip or brotli
+    pub compression_min_bytes: u16,
+    /// Default lifetime of a verification link
+    pub share_ttl_secs: u32,
+    /// Longest lifetime a verification link may be given
+    pub share_max_ttl_secs: u32,
+    /// Requests allowed against one verification link per minute
+    pub share_requests_per_minute: u32,
+    /// Longest lifetime a signing delegation may be given
+    pub delegation_max_ttl_secs: u32,
+    /// Most verification results cached; 0 disables the cache
+    pub verify_cache_size: u32,
+    /// Seconds a cached verification result is reused
+    pub verify_cache_ttl_secs: u32,
+    /// Largest `document_content`, in bytes, `/verify` accepts
+    pub verify_max_content_bytes: u32,
+    /// Largest `document_content`, in bytes, `/sign` accepts inline
+    pub sign_max_content_bytes: u32,
+    /// Largest document, in bytes, `/sign/raw` accepts
+    pub raw_sign_max_content_bytes: u32,
+    /// Largest upload, in bytes, `/verify/archive` accepts
+    pub verify_archive_max_bytes: u32,
+    /// Most entries an archive sent to `/verify/archive` may hold
+    pub verify_archive_max_entries: u32,
+    /// Most times an archive entry may expand over its compressed size
+    pub verify_archive_max_ratio: u32,
+    /// Most bytes an archive's entries may decompress to in total
+    pub verify_archive_max_uncompressed_bytes: u32,
+    /// Verification requests one unauthenticated client address may make per minute; 0 disables
+    pub verify_requests_per_minute: u32,
+    /// Redis server rate limits are counted on, shared between replicas (requires the `redis`
+    /// feature); unset counts them in memory
+    #[serde(skip)]
+    pub redis_url: Option<String>,
+    /// Whether rate-limited requests are allowed or refused while Redis cannot be reached, by limit
+    pub rate_limit_backend_failure: BackendFailureModes,
+    /// Shared secrets of clients that sign requests, by client id; empty disables request signing
+    #[serde(skip)]
+    pub hmac_clients: BTreeMap<String, String>,
+    /// Seconds a signed request's timestamp may differ from the service clock
+    pub hmac_max_skew_secs: u32,
+    /// Signing clients allowed on admin-scoped endpoints such as `/admin/overview`
+    pub admin_clients: BTreeSet<String>,
+    /// Signing clients told whether a key they cannot use is missing, revoked or expired;
+    /// admin clients are too
+    pub read_clients: BTreeSet<String>,
+    /// Days before expiry from which responses carry a `KEY_EXPIRING_SOON` warning; 0 disables it
+    pub expiry_warning_days: u32,
+    /// Honor `?debug_timings=true` on signing requests from admin clients, returning how long the
+    /// key took to unlock
+    pub debug_timings: bool,
+    /// `max-age` of public keys served by fingerprint, whose content never changes
+    pub public_key_max_age_secs: u32,
+    /// `max-age` of key status documents, bounding how long a revocation takes to reach caches
+    pub key_status_max_age_secs: u32,
+    /// p99 latency objectives of generate, sign and verify requests, in milliseconds
+    pub slo_generate_target_ms: u32,
+    pub slo_sign_target_ms: u32,
+    pub slo_verify_target_ms: u32,
+    /// Log a warning when the last 5 minutes of an operation breach its latency objective
+    pub slo_breach_warnings: bool,
+    /// Expiring-key notification channels, driven by the sweeper
+    pub notifications: NotificationConfig,
+    /// Casing of response field names when a request sends no `X-Field-Case` header
+    pub field_case: FieldCase,
+    /// Answer signing, verification and key lookup failures with `200`, as before statuses
+    /// followed the error
+    pub legacy_envelope: bool,
+    /// Serve the `/v2` API alongside `/v1`
+    pub api_v2: bool,
+    /// When the deprecated unprefixed paths are due to be removed, announced in `Sunset`
+    pub unversioned_sunset: DateTime<Utc>,
+    /// Defaults generation requests can select by name with `template`
+    pub key_templates: Vec<KeyTemplate>,
+    /// Service that may veto signatures
+    pub sign_policy: SignPolicyConfig,
+    /// Deployment environments keys may belong to
+    pub environments: Vec<String>,
+    /// Environment the service runs in; unset, keys of any environment sign
+    pub environment: Option<String>,
+    /// What signing with a key of another environment does
+    pub environment_mismatch: MismatchPolicy,
+    /// Key pairs drawn ahead for `fast` generation; 0 disables the pool
+    pub key_pool_size: u32,
+    /// Keys left in the pool at or below which a claim wakes the refill
+    pub key_pool_refill_below: u32,
+    /// Seconds a pooled key may wait before it is discarded unused
+    pub key_pool_max_age_secs: u32,
+    /// What `/sign` does with a signature whose receipt cannot be written
+    pub receipt_failure: ReceiptFailurePolicy,
+    /// Partner instances unknown keys are resolved from
+    pub federation: FederationConfig,
+    /// What backups hold of keys that may not be exported
+    pub non_exportable_backup: NonExportableBackup,
+    /// When storage-dependent requests fail fast instead of waiting on the keystore
+    pub storage_breaker: StorageBreakerConfig,
+    /// Which routes are served and whether private keys are loaded
+    pub profile: DeploymentProfile,
+    /// Signed manifest the verifier's keys come from; unset, they are read from the keystore
+    pub trust_manifest: Option<TrustManifestConfig>,
+    /// Most recent operation events kept for replay
+    pub event_log_retain: u32,
+    /// Token that holds HSM keys (requires the `pkcs11` feature); unset, they cannot sign
+    pub pkcs11: Option<Pkcs11Config>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            kdf: KdfParams::default(),
+            notary_key_id: None,
+            max_keys: None,
+            keystore_soft_limit: None,
+            keystore_hard_limit: None,
+            archive_revoked_after_days: None,
+            clock_skew_secs: DEFAULT_CLOCK_SKEW_SECS,
+            min_key_lifetime_secs: 0,
+            max_key_lifetime_days: None,
+            default_key_lifetime_days: None,
+            strict_key_lifetime: false,
+            sweep_interval_secs: DEFAULT_SWEEP_INTERVAL_SECS,
+            entropy_check_interval_secs: DEFAULT_ENTROPY_CHECK_INTERVAL_SECS,
+            lock_conflict: LockConflict::default(),
+            lock_stale_secs: DEFAULT_LOCK_STALE_SECS,
+            watch_keystore: false,
+            dedupe_signatures: true,
+            max_concurrent_generations: None,
+            max_concurrent_signs: None,
+            overload_policy: OverloadPolicy::default(),
+            overload_queue_depth: DEFAULT_OVERLOAD_QUEUE_DEPTH,
+            overload_queue_timeout_secs: DEFAULT_OVERLOAD_QUEUE_TIMEOUT_SECS,
+            overload_retry_after_secs: DEFAULT_OVERLOAD_RETRY_AFTER_SECS,
+            request_timeout_secs: DEFAULT_REQUEST_TIMEOUT_SECS,
+            long_request_timeout_secs: DEFAULT_LONG_REQUEST_TIMEOUT_SECS,
+            read_only: false,
+            read_only_retry_after_secs: DEFAULT_READ_ONLY_RETRY_AFTER_SECS,
+            read_only_after_write_failures: None,
+            startup_self_test: true,
+            metrics_max_key_labels: DEFAULT_METRICS_MAX_KEY_LABELS,
+            compression_min_bytes: DEFAULT_COMPRESSION_MIN_BYTES,
+            share_ttl_secs: DEFAULT_SHARE_TTL_SECS,
+            share_max_ttl_secs: DEFAULT_SHARE_MAX_TTL_SECS,
+            share_requests_per_minute: DEFAULT_SHARE_REQUESTS_PER_MINUTE,
+            delegation_max_ttl_secs: DEFAULT_DELEGATION_MAX_TTL_SECS,
+            verify_cache_size: DEFAULT_VERIFY_CACHE_SIZE,
+            verify_cache_ttl_secs: DEFAULT_VERIFY_CACHE_TTL_SECS,
+            verify_max_content_bytes: DEFAULT_VERIFY_MAX_CONTENT_BYTES,
+            sign_max_content_bytes: DEFAULT_SIGN_MAX_CONTENT_BYTES,
+            raw_sign_max_content_bytes: DEFAULT_RAW_SIGN_MAX_CONTENT_BYTES,
+            verify_archive_max_bytes: DEFAULT_VERIFY_ARCHIVE_MAX_BYTES,
+            verify_archive_max_entries: DEFAULT_VERIFY_ARCHIVE_MAX_ENTRIES,
+            verify_archive_max_ratio: DEFAULT_VERIFY_ARCHIVE_MAX_RATIO,
+            verify_archive_max_uncompressed_bytes: DEFAULT_VERIFY_ARCHIVE_MAX_UNCOMPRESSED_BYTES,
+            verify_requests_per_minute: DEFAULT_VERIFY_REQUESTS_PER_MINUTE,
+            redis_url: None,
+            rate_limit_backend_failure: BackendFailureModes::default(),
+            hmac_clients: BTreeMap::new(),
+            hmac_max_skew_secs: DEFAULT_HMAC_MAX_SKEW_SECS,
+            admin_clients: BTreeSet::new(),
+            read_clients: BTreeSet::new(),
+            expiry_warning_days: DEFAULT_EXPIRY_WARNING_DAYS,
+            debug_timings: false,
+            public_key_max_age_secs: DEFAULT_PUBLIC_KEY_MAX_AGE_SECS,
+            key_status_max_age_secs: DEFAULT_KEY_STATUS_MAX_AGE_SECS,
+            slo_generate_target_ms: DEFAULT_SLO_GENERATE_TARGET_MS,
+            slo_sign_target_ms: DEFAULT_SLO_SIGN_TARGET_MS,
+            slo_verify_target_ms: DEFAULT_SLO_VERIFY_TARGET_MS,
+            slo_breach_warnings: false,
+            notifications: NotificationConfig::default(),
+            field_case: FieldCase::default(),
+            legacy_envelope: false,
+            api_v2: false,
+            unversioned_sunset: DateTime::from_timestamp(DEFAULT_UNVERSIONED_SUNSET, 0).unwrap_or_default(),
+            key_templates: Vec::new(),
+            sign_policy: SignPolicyConfig::default(),
+            environments: DEFAULT_ENVIRONMENTS.iter().map(|environment| environment.to_string()).collect(),
+            environment: None,
+            environment_mismatch: MismatchPolicy::default(),
+            key_pool_size: 0,
+            key_pool_refill_below: 0,
+            key_pool_max_age_secs: DEFAULT_KEY_POOL_MAX_AGE_SECS,
+            receipt_failure: ReceiptFailurePolicy::default(),
+            federation: FederationConfig::default(),
+            non_exportable_backup: NonExportableBackup::default(),
+            storage_breaker: StorageBreakerConfig::default(),
+            profile: DeploymentProfile::compiled().unwrap_or_default(),
+            trust_manifest: None,
+            event_log_retain: DEFAULT_EVENT_LOG_RETAIN,
+            pkcs11: None,
+        }
+    }
+}
+
+impl Config {
+    /// Reads configuration from the environment
+    ///
+    /// `INKAN_KDF_ALGORITHM` (`pbkdf2-sha256` or `argon2id`), `INKAN_KDF_ITERATIONS`,
+    /// `INKAN_KDF_MEMORY_KIB`, and `INKAN_KDF_PARALLELISM` override the KDF defaults;
+    /// `INKAN_NOTARY_KEY_ID` names the key that counter-signs verification bundles;
+    /// `INKAN_MAX_KEYS` caps the number of stored keys; `INKAN_KEYSTORE_SOFT_LIMIT` and
+    /// `INKAN_KEYSTORE_HARD_LIMIT` warn about and block keystore growth, with
+    /// `INKAN_ARCHIVE_REVOKED_AFTER_DAYS` archiving long-revoked keys to stay within them;
+    /// `INKAN_CLOCK_SKEW_SECS`,
+    /// `INKAN_MIN_KEY_LIFETIME_SECS`, and `INKAN_MAX_KEY_LIFETIME_DAYS` bound key expiries;
+    /// `INKAN_DEFAULT_KEY_LIFETIME_DAYS` and `INKAN_STRICT_KEY_LIFETIME` set the lifetime policy
+    /// for generated keys; `INKAN_SWEEP_INTERVAL_SECS` sets how often the sweeper runs and
+    /// `INKAN_ENTROPY_CHECK_INTERVAL_SECS` how often the entropy source is re-checked;
+    /// `INKAN_LOCK_CONFLICT` (`refuse` or `follow`) and `INKAN_LOCK_STALE_SECS` govern the
+    /// keystore lock shared with other instances; `INKAN_WATCH_KEYSTORE` reloads the keystore
+    /// when its file is modified externally; `INKAN_DEDUPE_SIGNATURES=false` records every
+    /// repeat signature afresh instead of returning its existing receipt;
+    /// `INKAN_MAX_CONCURRENT_GENERATIONS` and `INKAN_MAX_CONCURRENT_SIGNS` cap expensive
+    /// operations, with `INKAN_OVERLOAD_POLICY` (`queue` or `reject`),
+    /// `INKAN_OVERLOAD_QUEUE_DEPTH`, `INKAN_OVERLOAD_QUEUE_TIMEOUT_SECS`, and
+    /// `INKAN_OVERLOAD_RETRY_AFTER_SECS` governing requests beyond the caps;
+    /// `INKAN_REQUEST_TIMEOUT_SECS` and `INKAN_LONG_REQUEST_TIMEOUT_SECS` bound how long a
+    /// request may run;
+    /// `INKAN_READ_ONLY`, `INKAN_READ_ONLY_RETRY_AFTER_SECS`, and
+    /// `INKAN_READ_ONLY_AFTER_WRITE_FAILURES` control read-only mode;
+    /// `INKAN_SKIP_SELF_TEST` disables the startup self-test; `INKAN_METRICS_MAX_KEY_LABELS`
+    /// caps the keys labelled individually in metrics; `INKAN_COMPRESSION_MIN_BYTES` sets the
+    /// smallest response body that is compressed; `INKAN_SHARE_TTL_SECS`,
+    /// `INKAN_SHARE_MAX_TTL_SECS`, and `INKAN_SHARE_REQUESTS_PER_MINUTE` govern verification
+    /// links; `INKAN_DELEGATION_MAX_TTL_SECS` caps the lifetime of signing delegations; `INKAN_VERIFY_CACHE_SIZE` (0 disables) and `INKAN_VERIFY_CACHE_TTL_SECS` size the
+    /// verification cache; `INKAN_VERIFY_MAX_CONTENT_BYTES` and
+    /// `INKAN_VERIFY_REQUESTS_PER_MINUTE` (0 disables) bound `/verify`; `REDIS_URL` shares rate
+    /// limits between replicas, with `INKAN_RATE_LIMIT_BACKEND_FAILURE` (`open` or `closed`)
+    /// deciding what a limit does while Redis is unreachable, overridden per limit by
+    /// `INKAN_VERIFY_RATE_LIMIT_BACKEND_FAILURE`, `INKAN_SHARE_RATE_LIMIT_BACKEND_FAILURE`, and
+    /// `INKAN_FEDERATION_RATE_LIMIT_BACKEND_FAILURE`; `INKAN_HMAC_CLIENTS` (comma-separated `client_id:secret` pairs) turns on
+    /// HMAC request signing, with `INKAN_HMAC_MAX_SKEW_SECS` bounding timestamp drift and
+    /// `INKAN_ADMIN_CLIENTS` and `INKAN_READ_CLIENTS` (comma-separated client ids) naming the
+    /// clients with admin and read scope;
+    /// `INKAN_EXPIRY_WARNING_DAYS` (0 disables) sets when responses warn of a key's expiry;
+    /// `INKAN_DEBUG_TIMINGS` lets signing requests from admin clients ask for their key unlock timings;
+    /// `INKAN_PUBLIC_KEY_MAX_AGE_SECS` sets how long caches keep public keys served by fingerprint.
+    /// `INKAN_SLO_GENERATE_TARGET_MS`, `INKAN_SLO_SIGN_TARGET_MS`, and
+    /// `INKAN_SLO_VERIFY_TARGET_MS` set the latency objectives reported at `/admin/slo`, and
+    /// `INKAN_SLO_BREACH_WARNINGS` logs when one is breached.
+    /// `INKAN_NOTIFY_THRESHOLDS_DAYS` (comma
+    /// separated), `INKAN_NOTIFY_WEBHOOK_URL`, `INKAN_NOTIFY_SMTP_HOST`,
+    /// `INKAN_NOTIFY_SMTP_USERNAME`, `INKAN_NOTIFY_SMTP_PASSWORD`, `INKAN_NOTIFY_EMAIL_FROM`,
+    /// and `INKAN_NOTIFY_EMAIL_TO` configure expiring-key notifications. `INKAN_FIELD_CASE`
+    /// (`snake` or `camel`) sets the default casing of response field names.
+    /// `INKAN_LEGACY_ENVELOPE=true` (or `LEGACY_ENVELOPE=true`) restores `200` for signing,
+    /// verification and key lookup failures.
+    /// `INKAN_API_V2` serves the `/v2` API, and `INKAN_UNVERSIONED_SUNSET` (RFC 3339) sets the
+    /// `Sunset` announced on unprefixed paths.
+    /// `INKAN_KEY_TEMPLATES_FILE` names a JSON file of key templates.
+    /// `INKAN_SIGN_POLICY_URL` names a service that must approve each signature, with
+    /// `INKAN_SIGN_POLICY_TIMEOUT_MS`, `INKAN_SIGN_POLICY_FAIL_OPEN`, and
+    /// `INKAN_SIGN_POLICY_CACHE_SECS` (0 disables) governing how it is consulted.
+    /// `INKAN_ENVIRONMENTS` (comma-separated) lists the deployment environments keys may belong
+    /// to, `INKAN_ENVIRONMENT` names the one the service runs in, and
+    /// `INKAN_ENVIRONMENT_MISMATCH` (`reject` or `warn`) decides what signing with a key of another
+    /// environment does.
+    /// `INKAN_KEY_POOL_SIZE` (0 disables) keeps key pairs drawn ahead for `fast` generation,
+    /// refilled once `INKAN_KEY_POOL_REFILL_BELOW` or fewer remain and discarded after
+    /// `INKAN_KEY_POOL_MAX_AGE_SECS`.
+    /// `INKAN_RECEIPT_FAILURE` (`reject` or `warn`) decides whether a signature whose receipt
+    /// cannot be written is withheld or released with a warning.
+    /// `INKAN_FEDERATION_PEERS` (comma-separated `name=url` pairs) names partner instances keys
+    /// unknown here are resolved from for `/verify`, with `INKAN_FEDERATION_TIMEOUT_MS`,
+    /// `INKAN_FEDERATION_CACHE_TTL_SECS` (0 disables), and `INKAN_FEDERATION_LOOKUPS_PER_MINUTE`
+    /// (0 disables) governing how they are asked.
+    /// `INKAN_BACKUP_NON_EXPORTABLE` (`exclude` or `stored`) decides whether backups leave out the
+    /// private key of non-exportable keys or keep it as stored.
+    /// `INKAN_STORAGE_BREAKER_FAILURE_PERCENT` (0 disables), `INKAN_STORAGE_BREAKER_SLOW_MS`,
+    /// `INKAN_STORAGE_BREAKER_OPEN_SECS`, and `INKAN_STORAGE_BREAKER_PROBES` decide when
+    /// storage-dependent requests fail fast because the keystore is failing or slow.
+    /// `INKAN_VERIFY_ARCHIVE_MAX_BYTES`, `INKAN_VERIFY_ARCHIVE_MAX_ENTRIES`,
+    /// `INKAN_VERIFY_ARCHIVE_MAX_RATIO`, and `INKAN_VERIFY_ARCHIVE_MAX_UNCOMPRESSED_BYTES` bound
+    /// the archives `/verify/archive` reads.
+    /// `INKAN_PROFILE` (`full` or `verifier`) sets the deployment profile; `verifier` serves only
+    /// verification and public key routes and loads no private keys.
+    /// `INKAN_TRUST_MANIFEST_PATH` starts a verifier from a signed pin set instead of the
+    /// keystore; the pin set must be signed by the key `INKAN_TRUST_ROOT_PUBLIC_KEY` (base64, PEM,
+    /// JWK or OpenSSH), and is read again only on SIGHUP.
+    /// `INKAN_EVENT_LOG_RETAIN` sets how many of the most recent operation events are kept for
+    /// `/events/replay`.
+    /// `INKAN_PKCS11_MODULE` names the PKCS#11 library HSM keys are generated and signed on, logged
+    /// in to with `INKAN_PKCS11_PIN`.
+    pub fn from_env() -> Result<Self, KeyManagementError> {
+        Self::from_lookup(|name| std::env::var(name).ok())
+    }
+
+    /// Builds configuration from an arbitrary variable source
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, KeyManagementError> {
+        let parse_u32 = |name: &str| -> Result<Option<u32>, KeyManagementError> {
+            lookup(name)
+                .map(|value| value.trim().parse::<u32>()
+                    .map_err(|_| KeyManagementError::ValidationFailed(format!("{} must be a positive integer", name))))
+                .transpose()
+        };
+
+        let parse_bool = |name: &str| -> Result<bool, KeyManagementError> {
+            match lookup(name).as_deref().map(str::trim) {
+                None | Some("false") | Some("0") => Ok(false),
+                Some("true") | Some("1") => Ok(true),
+                Some(_) => Err(KeyManagementError::ValidationFailed(format!("{} must be true or false", name))),
+            }
+        };
+
+        let algorithm = match lookup("INKAN_KDF_ALGORITHM").as_deref().map(str::trim) {
+            None | Some("pbkdf2-sha256") => KdfAlgorithm::Pbkdf2Sha256,
+            Some("argon2id") => KdfAlgorithm::Argon2id,
+            Some(other) => {
+                return Err(KeyManagementError::ValidationFailed(format!("Unknown INKAN_KDF_ALGORITHM '{}'", other)));
+            }
+        };
+
+        let kdf = match algorithm {
+            KdfAlgorithm::Pbkdf2Sha256 => KdfParams::pbkdf2(parse_u32("INKAN_KDF_ITERATIONS")?.unwrap_or(LEGACY_PBKDF2_ITERATIONS)),
+            KdfAlgorithm::Argon2id => {
+                let defaults = argon2::Params::default();
+                KdfParams::argon2id(
+                    parse_u32("INKAN_KDF_ITERATIONS")?.unwrap_or(defaults.t_cost()),
+                    parse_u32("INKAN_KDF_MEMORY_KIB")?.unwrap_or(defaults.m_cost()),
+                    parse_u32("INKAN_KDF_PARALLELISM")?.unwrap_or(defaults.p_cost()),
+                )
+            }
+        };
+        kdf.validate()?;
+
+        let notary_key_id = lookup("INKAN_NOTARY_KEY_ID")
+            .map(|value| Uuid::parse_str(value.trim())
+                .map_err(|_| KeyManagementError::ValidationFailed("INKAN_NOTARY_KEY_ID must be a UUID".to_string())))
+            .transpose()?;
+
+        let max_keys = parse_u32("INKAN_MAX_KEYS")?.map(|limit| limit as usize);
+        let keystore_soft_limit = parse_u32("INKAN_KEYSTORE_SOFT_LIMIT")?.map(|limit| limit as usize);
+        let keystore_hard_limit = parse_u32("INKAN_KEYSTORE_HARD_LIMIT")?.map(|limit| limit as usize);
+        if let (Some(soft), Some(hard)) = (keystore_soft_limit, keystore_hard_limit) {
+            if soft > hard {
+                return Err(KeyManagementError::ValidationFailed(
+                    "INKAN_KEYSTORE_SOFT_LIMIT must not exceed INKAN_KEYSTORE_HARD_LIMIT".to_string(),
+                ));
+            }
+        }
+
+        let max_key_lifetime_days = parse_u32("INKAN_MAX_KEY_LIFETIME_DAYS")?;
+        let default_key_lifetime_days = parse_u32("INKAN_DEFAULT_KEY_LIFETIME_DAYS")?;
+        if let (Some(default), Some(max)) = (default_key_lifetime_days, max_key_lifetime_days) {
+            if default > max {
+                return Err(KeyManagementError::ValidationFailed(
+                    "INKAN_DEFAULT_KEY_LIFETIME_DAYS cannot exceed INKAN_MAX_KEY_LIFETIME_DAYS".to_string(),
+                ));
+            }
+        }
+
+        let sweep_interval_secs = parse_u32("INKAN_SWEEP_INTERVAL_SECS")?.unwrap_or(DEFAULT_SWEEP_INTERVAL_SECS);
+        if sweep_interval_secs == 0 {
+            return Err(KeyManagementError::ValidationFailed("INKAN_SWEEP_INTERVAL_SECS must be at least 1".to_string()));
+        }
+
+        let entropy_check_interval_secs = parse_u32("INKAN_ENTROPY_CHECK_INTERVAL_SECS")?.unwrap_or(DEFAULT_ENTROPY_CHECK_INTERVAL_SECS);
+        if entropy_check_interval_secs == 0 {
+            return Err(KeyManagementError::ValidationFailed("INKAN_ENTROPY_CHECK_INTERVAL_SECS must be at least 1".to_string()));
+        }
+
+        let lock_conflict = match lookup("INKAN_LOCK_CONFLICT") {
+            Some(value) => LockConflict::parse(&value)
+                .ok_or_else(|| KeyManagementError::ValidationFailed("INKAN_LOCK_CONFLICT must be refuse or follow".to_string()))?,
+            None => LockConflict::default(),
+        };
+
+        // The heartbeat runs every third of the stale timeout, so it must be at least 3 seconds
+        let lock_stale_secs = parse_u32("INKAN_LOCK_STALE_SECS")?.unwrap_or(DEFAULT_LOCK_STALE_SECS);
+        if lock_stale_secs < 3 {
+            return Err(KeyManagementError::ValidationFailed("INKAN_LOCK_STALE_SECS must be at least 3".to_string()));
+        }
+
+        let max_concurrent_generations = parse_u32("INKAN_MAX_CONCURRENT_GENERATIONS")?;
+        let max_concurrent_signs = parse_u32("INKAN_MAX_CONCURRENT_SIGNS")?;
+        if max_concurrent_generations == Some(0) || max_concurrent_signs == Some(0) {
+            return Err(KeyManagementError::ValidationFailed(
+                "INKAN_MAX_CONCURRENT_GENERATIONS and INKAN_MAX_CONCURRENT_SIGNS must be at least 1".to_string(),
+            ));
+        }
+        let overload_policy = match lookup("INKAN_OVERLOAD_POLICY") {
+            Some(value) => OverloadPolicy::parse(&value)
+                .ok_or_else(|| KeyManagementError::ValidationFailed("INKAN_OVERLOAD_POLICY must be queue or reject".to_string()))?,
+            None => OverloadPolicy::default(),
+        };
+        let overload_queue_timeout_secs = parse_u32("INKAN_OVERLOAD_QUEUE_TIMEOUT_SECS")?.unwrap_or(DEFAULT_OVERLOAD_QUEUE_TIMEOUT_SECS);
+        if overload_queue_timeout_secs == 0 {
+            return Err(KeyManagementError::ValidationFailed("INKAN_OVERLOAD_QUEUE_TIMEOUT_SECS must be at least 1".to_string()));
+        }
+
+        let unversioned_sunset = match lookup("INKAN_UNVERSIONED_SUNSET") {
+            Some(value) => DateTime::parse_from_rfc3339(value.trim())
+                .map(|sunset| sunset.with_timezone(&Utc))
+                .map_err(|_| KeyManagementError::ValidationFailed("INKAN_UNVERSIONED_SUNSET must be an RFC 3339 timestamp".to_string()))?,
+            None => Config::default().unversioned_sunset,
+        };
+
+        let request_timeout_secs = parse_u32("INKAN_REQUEST_TIMEOUT_SECS")?.unwrap_or(DEFAULT_REQUEST_TIMEOUT_SECS);
+        let long_request_timeout_secs = parse_u32("INKAN_LONG_REQUEST_TIMEOUT_SECS")?.unwrap_or(DEFAULT_LONG_REQUEST_TIMEOUT_SECS);
+        if request_timeout_secs == 0 || long_request_timeout_secs == 0 {
+            return Err(KeyManagementError::ValidationFailed(
+                "INKAN_REQUEST_TIMEOUT_SECS and INKAN_LONG_REQUEST_TIMEOUT_SECS must be at least 1".to_string(),
+            ));
+        }
+
+        let thresholds_days = match lookup("INKAN_NOTIFY_THRESHOLDS_DAYS") {
+            Some(value) => value.split(',')
+                .map(str::trim)
+                .filter(|threshold| !threshold.is_empty())
+                .map(|threshold| threshold.parse::<u32>().map_err(|_| KeyManagementError::ValidationFailed(
+                    "INKAN_NOTIFY_THRESHOLDS_DAYS must be a comma-separated list of day counts".to_string(),
+                )))
+                .collect::<Result<Vec<_>, _>>()?,
+            None => DEFAULT_NOTIFY_THRESHOLDS_DAYS.to_vec(),
+        };
+        let notifications = NotificationConfig {
+            thresholds_days,
+            webhook_url: lookup("INKAN_NOTIFY_WEBHOOK_URL"),
+            smtp_host: lookup("INKAN_NOTIFY_SMTP_HOST"),
+            smtp_username: lookup("INKAN_NOTIFY_SMTP_USERNAME"),
+            smtp_password: lookup("INKAN_NOTIFY_SMTP_PASSWORD"),
+            email_from: lookup("INKAN_NOTIFY_EMAIL_FROM"),
+            email_to: lookup("INKAN_NOTIFY_EMAIL_TO"),
+        };
+
+        let compression_min_bytes = match parse_u32("INKAN_COMPRESSION_MIN_BYTES")? {
+            Some(bytes) => u16::try_from(bytes).map_err(|_| {
+                KeyManagementError::ValidationFailed(format!("INKAN_COMPRESSION_MIN_BYTES must be at most {}", u16::MAX))
+            })?,
+            None => DEFAULT_COMPRESSION_MIN_BYTES,
+        };
+
+        let share_ttl_secs = parse_u32("INKAN_SHARE_TTL_SECS")?.unwrap_or(DEFAULT_SHARE_TTL_SECS);
+        let share_max_ttl_secs = parse_u32("INKAN_SHARE_MAX_TTL_SECS")?.unwrap_or(DEFAULT_SHARE_MAX_TTL_SECS);
+        if share_ttl_secs == 0 || share_ttl_secs > share_max_ttl_secs {
+            return Err(KeyManagementError::ValidationFailed(
+                "INKAN_SHARE_TTL_SECS must be at least 1 and at most INKAN_SHARE_MAX_TTL_SECS".to_string(),
+            ));
+        }
+        let share_requests_per_minute = parse_u32("INKAN_SHARE_REQUESTS_PER_MINUTE")?.unwrap_or(DEFAULT_SHARE_REQUESTS_PER_MINUTE);
+        if share_requests_per_minute == 0 {
+            return Err(KeyManagementError::ValidationFailed("INKAN_SHARE_REQUESTS_PER_MINUTE must be at least 1".to_string()));
+        }
+        let delegation_max_ttl_secs = parse_u32("INKAN_DELEGATION_MAX_TTL_SECS")?.unwrap_or(DEFAULT_DELEGATION_MAX_TTL_SECS);
+        if delegation_max_ttl_secs == 0 {
+            return Err(KeyManagementError::ValidationFailed("INKAN_DELEGATION_MAX_TTL_SECS must be at least 1".to_string()));
+        }
+        let verify_cache_ttl_secs = parse_u32("INKAN_VERIFY_CACHE_TTL_SECS")?.unwrap_or(DEFAULT_VERIFY_CACHE_TTL_SECS);
+        if verify_cache_ttl_secs == 0 {
+            return Err(KeyManagementError::ValidationFailed("INKAN_VERIFY_CACHE_TTL_SECS must be at least 1".to_string()));
+        }
+        let hmac_clients = match lookup("INKAN_HMAC_CLIENTS") {
+            Some(value) => parse_clients(&value).ok_or_else(|| KeyManagementError::ValidationFailed(
+                "INKAN_HMAC_CLIENTS must be comma-separated client_id:secret pairs".to_string(),
+            ))?,
+            None => BTreeMap::new(),
+        };
+        let hmac_max_skew_secs = parse_u32("INKAN_HMAC_MAX_SKEW_SECS")?.unwrap_or(DEFAULT_HMAC_MAX_SKEW_SECS);
+        if hmac_max_skew_secs == 0 {
+            return Err(KeyManagementError::ValidationFailed("INKAN_HMAC_MAX_SKEW_SECS must be at least 1".to_string()));
+        }
+        let scoped_clients = |name: &str| -> Result<BTreeSet<String>, KeyManagementError> {
+            let clients: BTreeSet<String> = lookup(name)
+                .map(|value| value.split(',').map(str::trim).filter(|client| !client.is_empty()).map(str::to_string).collect())
+                .unwrap_or_default();
+            if let Some(client) = clients.iter().find(|client| !hmac_clients.contains_key(*client)) {
+                return Err(KeyManagementError::ValidationFailed(format!(
+                    "{} names {}, which is not in INKAN_HMAC_CLIENTS", name, client,
+                )));
+            }
+            Ok(clients)
+        };
+        let admin_clients = scoped_clients("INKAN_ADMIN_CLIENTS")?;
+        let read_clients = scoped_clients("INKAN_READ_CLIENTS")?;
+
+        let field_case = match lookup("INKAN_FIELD_CASE") {
+            Some(value) => FieldCase::parse(&value)
+                .ok_or_else(|| KeyManagementError::ValidationFailed("INKAN_FIELD_CASE must be snake or camel".to_string()))?,
+            None => FieldCase::default(),
+        };
+
+        let key_templates = match lookup("INKAN_KEY_TEMPLATES_FILE") {
+            Some(path) => {
+                let json = std::fs::read_to_string(path.trim()).map_err(|e| {
+                    KeyManagementError::ValidationFailed(format!("INKAN_KEY_TEMPLATES_FILE could not be read: {}", e))
+                })?;
+                parse_templates(&json, max_key_lifetime_days)?
+            }
+            None => Vec::new(),
+        };
+
+        // Each limit follows the shared setting unless it has one of its own
+        let parse_backend_failure = |name: &str, default: BackendFailure| match lookup(name) {
+            Some(value) => BackendFailure::parse(&value)
+                .ok_or_else(|| KeyManagementError::ValidationFailed(format!("{} must be open or closed", name))),
+            None => Ok(default),
+        };
+        let backend_failure = parse_backend_failure("INKAN_RATE_LIMIT_BACKEND_FAILURE", BackendFailure::default())?;
+        let rate_limit_backend_failure = BackendFailureModes {
+            verify: parse_backend_failure("INKAN_VERIFY_RATE_LIMIT_BACKEND_FAILURE", backend_failure)?,
+            shares: parse_backend_failure("INKAN_SHARE_RATE_LIMIT_BACKEND_FAILURE", backend_failure)?,
+            federation: parse_backend_failure("INKAN_FEDERATION_RATE_LIMIT_BACKEND_FAILURE", backend_failure)?,
+        };
+
+        let sign_policy = SignPolicyConfig {
+            url: lookup("INKAN_SIGN_POLICY_URL"),
+            timeout_ms: parse_u32("INKAN_SIGN_POLICY_TIMEOUT_MS")?.unwrap_or(DEFAULT_SIGN_POLICY_TIMEOUT_MS),
+            fail_open: parse_bool("INKAN_SIGN_POLICY_FAIL_OPEN")?,
+            cache_ttl_secs: parse_u32("INKAN_SIGN_POLICY_CACHE_SECS")?.unwrap_or(DEFAULT_SIGN_POLICY_CACHE_TTL_SECS),
+        };
+        if sign_policy.timeout_ms == 0 {
+            return Err(KeyManagementError::ValidationFailed("INKAN_SIGN_POLICY_TIMEOUT_MS must be at least 1".to_string()));
+        }
+
+        let federation = FederationConfig {
+            peers: lookup("INKAN_FEDERATION_PEERS").map(|value| FederationPeer::parse_list(&value)).transpose()?.unwrap_or_default(),
+            timeout_ms: parse_u32("INKAN_FEDERATION_TIMEOUT_MS")?.unwrap_or(DEFAULT_FEDERATION_TIMEOUT_MS),
+            cache_ttl_secs: parse_u32("INKAN_FEDERATION_CACHE_TTL_SECS")?.unwrap_or(DEFAULT_FEDERATION_CACHE_TTL_SECS),
+            lookups_per_minute: parse_u32("INKAN_FEDERATION_LOOKUPS_PER_MINUTE")?.unwrap_or(DEFAULT_FEDERATION_LOOKUPS_PER_MINUTE),
+        };
+        if federation.timeout_ms == 0 {
+            return Err(KeyManagementError::ValidationFailed("INKAN_FEDERATION_TIMEOUT_MS must be at least 1".to_string()));
+        }
+
+        let storage_breaker = StorageBreakerConfig {
+            failure_percent: parse_u32("INKAN_STORAGE_BREAKER_FAILURE_PERCENT")?.unwrap_or(DEFAULT_STORAGE_BREAKER_FAILURE_PERCENT),
+            slow_ms: parse_u32("INKAN_STORAGE_BREAKER_SLOW_MS")?.unwrap_or(DEFAULT_STORAGE_BREAKER_SLOW_MS),
+            open_secs: parse_u32("INKAN_STORAGE_BREAKER_OPEN_SECS")?.unwrap_or(DEFAULT_STORAGE_BREAKER_OPEN_SECS),
+            probes: parse_u32("INKAN_STORAGE_BREAKER_PROBES")?.unwrap_or(DEFAULT_STORAGE_BREAKER_PROBES),
+        };
+        if storage_breaker.failure_percent > 100 {
+            return Err(KeyManagementError::ValidationFailed("INKAN_STORAGE_BREAKER_FAILURE_PERCENT must be at most 100".to_string()));
+        }
+        if storage_breaker.slow_ms == 0 || storage_breaker.open_secs == 0 || storage_breaker.probes == 0 {
+            return Err(KeyManagementError::ValidationFailed(
+                "INKAN_STORAGE_BREAKER_SLOW_MS, INKAN_STORAGE_BREAKER_OPEN_SECS and INKAN_STORAGE_BREAKER_PROBES must be at least 1".to_string(),
+            ));
+        }
+
+        let verify_archive_max_bytes = parse_u32("INKAN_VERIFY_ARCHIVE_MAX_BYTES")?.unwrap_or(DEFAULT_VERIFY_ARCHIVE_MAX_BYTES);
+        let verify_archive_max_entries = parse_u32("INKAN_VERIFY_ARCHIVE_MAX_ENTRIES")?.unwrap_or(DEFAULT_VERIFY_ARCHIVE_MAX_ENTRIES);
+        let verify_archive_max_ratio = parse_u32("INKAN_VERIFY_ARCHIVE_MAX_RATIO")?.unwrap_or(DEFAULT_VERIFY_ARCHIVE_MAX_RATIO);
+        let verify_archive_max_uncompressed_bytes = parse_u32("INKAN_VERIFY_ARCHIVE_MAX_UNCOMPRESSED_BYTES")?
+            .unwrap_or(DEFAULT_VERIFY_ARCHIVE_MAX_UNCOMPRESSED_BYTES);
+        if verify_archive_max_entries == 0 || verify_archive_max_ratio == 0 {
+            return Err(KeyManagementError::ValidationFailed(
+                "INKAN_VERIFY_ARCHIVE_MAX_ENTRIES and INKAN_VERIFY_ARCHIVE_MAX_RATIO must be at least 1".to_string(),
+            ));
+        }
+
+        let environments = match lookup("INKAN_ENVIRONMENTS") {
+            Some(value) => {
+                let mut environments = Vec::new();
+                for environment in value.split(',').map(str::trim).filter(|environment| !environment.is_empty()) {
+                    if environment == UNKNOWN_ENVIRONMENT {
+                        return Err(KeyManagementError::ValidationFailed(format!(
+                            "INKAN_ENVIRONMENTS cannot list {}, which is reserved for keys stored before environments", UNKNOWN_ENVIRONMENT,
+                        )));
+                    }
+                    if !environments.iter().any(|listed| listed == environment) {
+                        environments.push(environment.to_string());
+                    }
+                }
+                if environments.is_empty() {
+                    return Err(KeyManagementError::ValidationFailed("INKAN_ENVIRONMENTS must list at least one environment".to_string()));
+                }
+                environments
+            }
+            None => Config::default().environments,
+        };
+        let environment = lookup("INKAN_ENVIRONMENT").map(|value| value.trim().to_string()).filter(|value| !value.is_empty());
+        if let Some(environment) = environment.as_deref().filter(|environment| !environments.iter().any(|listed| listed == environment)) {
+            return Err(KeyManagementError::ValidationFailed(format!(
+                "INKAN_ENVIRONMENT is {}, which is not in INKAN_ENVIRONMENTS", environment,
+            )));
+        }
+        let key_pool_size = parse_u32("INKAN_KEY_POOL_SIZE")?.unwrap_or(0);
+        let key_pool_refill_below = parse_u32("INKAN_KEY_POOL_REFILL_BELOW")?.unwrap_or(key_pool_size / 2);
+        if key_pool_size > 0 && key_pool_refill_below >= key_pool_size {
+            return Err(KeyManagementError::ValidationFailed("INKAN_KEY_POOL_REFILL_BELOW must be below INKAN_KEY_POOL_SIZE".to_string()));
+        }
+        let key_pool_max_age_secs = parse_u32("INKAN_KEY_POOL_MAX_AGE_SECS")?.unwrap_or(DEFAULT_KEY_POOL_MAX_AGE_SECS);
+        if key_pool_max_age_secs == 0 {
+            return Err(KeyManagementError::ValidationFailed("INKAN_KEY_POOL_MAX_AGE_SECS must be at least 1".to_string()));
+        }
+
+        let environment_mismatch = match lookup("INKAN_ENVIRONMENT_MISMATCH") {
+            Some(value) => MismatchPolicy::parse(&value)
+                .ok_or_else(|| KeyManagementError::ValidationFailed("INKAN_ENVIRONMENT_MISMATCH must be reject or warn".to_string()))?,
+            None => MismatchPolicy::default(),
+        };
+        let receipt_failure = match lookup("INKAN_RECEIPT_FAILURE") {
+            Some(value) => ReceiptFailurePolicy::parse(&value)
+                .ok_or_else(|| KeyManagementError::ValidationFailed("INKAN_RECEIPT_FAILURE must be reject or warn".to_string()))?,
+            None => ReceiptFailurePolicy::default(),
+        };
+        let non_exportable_backup = match lookup("INKAN_BACKUP_NON_EXPORTABLE") {
+            Some(value) => NonExportableBackup::parse(&value)
+                .ok_or_else(|| KeyManagementError::ValidationFailed("INKAN_BACKUP_NON_EXPORTABLE must be exclude or stored".to_string()))?,
+            None => NonExportableBackup::default(),
+        };
+        let trust_manifest = match (lookup("INKAN_TRUST_MANIFEST_PATH"), lookup("INKAN_TRUST_ROOT_PUBLIC_KEY")) {
+            (Some(path), Some(root_public_key)) => Some(TrustManifestConfig {
+                path,
+                root_public_key: normalize_public_key(&root_public_key)
+                    .map_err(|e| KeyManagementError::ValidationFailed(format!("INKAN_TRUST_ROOT_PUBLIC_KEY is not a public key: {}", e)))?
+                    .0,
+            }),
+            (Some(_), None) => return Err(KeyManagementError::ValidationFailed(
+                "INKAN_TRUST_MANIFEST_PATH needs INKAN_TRUST_ROOT_PUBLIC_KEY to verify the manifest against".to_string(),
+            )),
+            (None, Some(_)) => return Err(KeyManagementError::ValidationFailed(
+                "INKAN_TRUST_ROOT_PUBLIC_KEY is set without INKAN_TRUST_MANIFEST_PATH".to_string(),
+            )),
+            (None, None) => None,
+        };
+        // A manifest holds public keys only, so it serves the verifier profile
+        let profile = match lookup("INKAN_PROFILE") {
+            Some(value) => DeploymentProfile::parse(&value)
+                .ok_or_else(|| KeyManagementError::ValidationFailed("INKAN_PROFILE must be full or verifier".to_string()))?,
+            None if trust_manifest.is_some() => DeploymentProfile::Verifier,
+            None => DeploymentProfile::compiled().unwrap_or_default(),
+        };
+        if trust_manifest.is_some() && profile != DeploymentProfile::Verifier {
+            return Err(KeyManagementError::ValidationFailed(
+                "INKAN_TRUST_MANIFEST_PATH serves only the verifier profile".to_string(),
+            ));
+        }
+        if DeploymentProfile::compiled().is_some_and(|compiled| compiled != profile) {
+            return Err(KeyManagementError::ValidationFailed(
+                "This build was compiled with verifier-only and serves only the verifier profile".to_string(),
+            ));
+        }
+        let event_log_retain = parse_u32("INKAN_EVENT_LOG_RETAIN")?.unwrap_or(DEFAULT_EVENT_LOG_RETAIN);
+        if event_log_retain == 0 {
+            return Err(KeyManagementError::ValidationFailed("INKAN_EVENT_LOG_RETAIN must be at least 1".to_string()));
+        }
+        let pkcs11 = match (lookup("INKAN_PKCS11_MODULE"), lookup("INKAN_PKCS11_PIN")) {
+            (Some(module), Some(pin)) => Some(Pkcs11Config { module: module.trim().to_string(), pin }),
+            (Some(_), None) => return Err(KeyManagementError::ValidationFailed(
+                "INKAN_PKCS11_MODULE needs INKAN_PKCS11_PIN to log in to the token".to_string(),
+            )),
+            (None, Some(_)) => return Err(KeyManagementError::ValidationFailed(
+                "INKAN_PKCS11_PIN is set without INKAN_PKCS11_MODULE".to_string(),
+            )),
+            (None, None) => None,
+        };
+
+        Ok(Self {
+            kdf,
+            notary_key_id,
+            max_keys,
+            keystore_soft_limit,
+            keystore_hard_limit,
+            archive_revoked_after_days: parse_u32("INKAN_ARCHIVE_REVOKED_AFTER_DAYS")?,
+            clock_skew_secs: parse_u32("INKAN_CLOCK_SKEW_SECS")?.unwrap_or(DEFAULT_CLOCK_SKEW_SECS),
+            min_key_lifetime_secs: parse_u32("INKAN_MIN_KEY_LIFETIME_SECS")?.unwrap_or(0),
+            max_key_lifetime_days,
+            default_key_lifetime_days,
+            strict_key_lifetime: parse_bool("INKAN_STRICT_KEY_LIFETIME")?,
+            sweep_interval_secs,
+            entropy_check_interval_secs,
+            lock_conflict,
+            lock_stale_secs,
+            watch_keystore: parse_bool("INKAN_WATCH_KEYSTORE")?,
+            dedupe_signatures: lookup("INKAN_DEDUPE_SIGNATURES").is_none() || parse_bool("INKAN_DEDUPE_SIGNATURES")?,
+            max_concurrent_generations,
+            max_concurrent_signs,
+            overload_policy,
+            overload_queue_depth: parse_u32("INKAN_OVERLOAD_QUEUE_DEPTH")?.unwrap_or(DEFAULT_OVERLOAD_QUEUE_DEPTH),
+            overload_queue_timeout_secs,
+            overload_retry_after_secs: parse_u32("INKAN_OVERLOAD_RETRY_AFTER_SECS")?.unwrap_or(DEFAULT_OVERLOAD_RETRY_AFTER_SECS),
+            request_timeout_secs,
+            long_request_timeout_secs,
+            read_only: parse_bool("INKAN_READ_ONLY")?,
+            read_only_retry_after_secs: parse_u32("INKAN_READ_ONLY_RETRY_AFTER_SECS")?.unwrap_or(DEFAULT_READ_ONLY_RETRY_AFTER_SECS),
+            read_only_after_write_failures: parse_u32("INKAN_READ_ONLY_AFTER_WRITE_FAILURES")?,
+            startup_self_test: !parse_bool("INKAN_SKIP_SELF_TEST")?,
+            metrics_max_key_labels: parse_u32("INKAN_METRICS_MAX_KEY_LABELS")?
+                .map_or(DEFAULT_METRICS_MAX_KEY_LABELS, |limit| limit as usize),
+            compression_min_bytes,
+            share_ttl_secs,
+            share_max_ttl_secs,
+            share_requests_per_minute,
+            delegation_max_ttl_secs,
+            verify_cache_size: parse_u32("INKAN_VERIFY_CACHE_SIZE")?.unwrap_or(DEFAULT_VERIFY_CACHE_SIZE),
+            verify_cache_ttl_secs,
+            verify_max_content_bytes: parse_u32("INKAN_VERIFY_MAX_CONTENT_BYTES")?.unwrap_or(DEFAULT_VERIFY_MAX_CONTENT_BYTES),
+            sign_max_content_bytes: parse_u32("INKAN_SIGN_MAX_CONTENT_BYTES")?.unwrap_or(DEFAULT_SIGN_MAX_CONTENT_BYTES),
+            raw_sign_max_content_bytes: parse_u32("INKAN_RAW_SIGN_MAX_CONTENT_BYTES")?.unwrap_or(DEFAULT_RAW_SIGN_MAX_CONTENT_BYTES),
+            verify_archive_max_bytes,
+            verify_archive_max_entries,
+            verify_archive_max_ratio,
+            verify_archive_max_uncompressed_bytes,
+            verify_requests_per_minute: parse_u32("INKAN_VERIFY_REQUESTS_PER_MINUTE")?.unwrap_or(DEFAULT_VERIFY_REQUESTS_PER_MINUTE),
+            redis_url: lookup("REDIS_URL").map(|url| url.trim().to_string()).filter(|url| !url.is_empty()),
+            rate_limit_backend_failure,
+            hmac_clients,
+            hmac_max_skew_secs,
+            admin_clients,
+            read_clients,
+            expiry_warning_days: parse_u32("INKAN_EXPIRY_WARNING_DAYS")?.unwrap_or(DEFAULT_EXPIRY_WARNING_DAYS),
+            debug_timings: parse_bool("INKAN_DEBUG_TIMINGS")?,
+            public_key_max_age_secs: parse_u32("INKAN_PUBLIC_KEY_MAX_AGE_SECS")?.unwrap_or(DEFAULT_PUBLIC_KEY_MAX_AGE_SECS),
+            key_status_max_age_secs: parse_u32("INKAN_KEY_STATUS_MAX_AGE_SECS")?.unwrap_or(DEFAULT_KEY_STATUS_MAX_AGE_SECS),
+            slo_generate_target_ms: parse_u32("INKAN_SLO_GENERATE_TARGET_MS")?.unwrap_or(DEFAULT_SLO_GENERATE_TARGET_MS),
+            slo_sign_target_ms: parse_u32("INKAN_SLO_SIGN_TARGET_MS")?.unwrap_or(DEFAULT_SLO_SIGN_TARGET_MS),
+            slo_verify_target_ms: parse_u32("INKAN_SLO_VERIFY_TARGET_MS")?.unwrap_or(DEFAULT_SLO_VERIFY_TARGET_MS),
+            slo_breach_warnings: parse_bool("INKAN_SLO_BREACH_WARNINGS")?,
+            notifications,
+            field_case,
+            legacy_envelope: parse_bool("INKAN_LEGACY_ENVELOPE")? || parse_bool("LEGACY_ENVELOPE")?,
+            api_v2: parse_bool("INKAN_API_V2")?,
+            unversioned_sunset,
+            key_templates,
+            sign_policy,
+            environments,
+            environment,
+            environment_mismatch,
+            key_pool_size,
+            key_pool_refill_below,
+            key_pool_max_age_secs,
+            receipt_failure,
+            federation,
+            non_exportable_backup,
+            storage_breaker,
+            profile,
+            trust_manifest,
+            event_log_retain,
+            pkcs11,
+        })
+    }
+}
+
+/// Measured derivation speed of this host for one KDF configuration
+#[derive(Debug, Clone, Copy)]
+pub struct KdfBenchmark {
+    pub base: KdfParams,
+    /// Wall-clock nanoseconds spent per iteration (time cost unit)
+    pub nanos_per_iteration: f64,
+}
+
+impl KdfBenchmark {
+    /// Times a derivation with `base`'s algorithm (and memory settings for Argon2id)
+    pub fn measure(base: KdfParams) -> Result<Self, KeyManagementError> {
+        let probe = match base.algorithm {
+            KdfAlgorithm::Pbkdf2Sha256 => KdfParams::pbkdf2(MIN_PBKDF2_ITERATIONS),
+            KdfAlgorithm::Argon2id => KdfParams { iterations: 2, ..base },
+        };
+
+        let started = Instant::now();
+        probe.derive_key(b"inkan-kdf-calibration", &[0u8; 32])?;
+        let elapsed = started.elapsed().as_nanos().max(1) as f64;
+
+        Ok(Self {
+            base,
+            nanos_per_iteration: elapsed / probe.iterations as f64,
+        })
+    }
+
+    /// Suggests parameters whose derivation takes roughly `target_ms` on this host
+    pub fn suggest(&self, target_ms: u64) -> KdfParams {
+        let (minimum, maximum) = match self.base.algorithm {
+            KdfAlgorithm::Pbkdf2Sha256 => (MIN_PBKDF2_ITERATIONS, MAX_PBKDF2_ITERATIONS),
+            KdfAlgorithm::Argon2id => (MIN_ARGON2_ITERATIONS, MAX_ARGON2_ITERATIONS),
+        };
+        let iterations = (target_ms as f64 * 1_000_000.0 / self.nanos_per_iteration)
+            .round()
+            .clamp(minimum as f64, maximum as f64) as u32;
+
+        KdfParams { iterations, ..self.base }
+    }
+}
+
+/// Benchmarks the host and suggests parameters hitting `target_ms` per derivation
+pub fn calibrate_kdf(base: KdfParams, target_ms: u64) -> Result<KdfParams, KeyManagementError> {
+    Ok(KdfBenchmark::measure(base)?.suggest(target_ms))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_config_defaults_to_legacy_pbkdf2() {
+        let config = Config::from_lookup(|_| None).unwrap();
+        assert_eq!(config.kdf, KdfParams::pbkdf2(LEGACY_PBKDF2_ITERATIONS));
+    }
+
+    #[test]
+    fn test_config_reads_environment_overrides() {
+        let vars: HashMap<&str, &str> = [("INKAN_KDF_ALGORITHM", "argon2id"), ("INKAN_KDF_MEMORY_KIB", "8192")].into();
+        let config = Config::from_lookup(|name| vars.get(name).map(|v| v.to_string())).unwrap();
+        assert_eq!(config.kdf.algorithm, KdfAlgorithm::Argon2id);
+        assert_eq!(config.kdf.memory_kib, 8192);
+
+        let vars: HashMap<&str, &str> = [("INKAN_KDF_ITERATIONS", "500")].into();
+        assert!(Config::from_lookup(|name| vars.get(name).map(|v| v.to_string())).is_err());
+
+        let vars: HashMap<&str, &str> = [("INKAN_DEFAULT_KEY_LIFETIME_DAYS", "400"), ("INKAN_MAX_KEY_LIFETIME_DAYS", "365")].into();
+        assert!(Config::from_lookup(|name| vars.get(name).map(|v| v.to_string())).is_err());
+
+        let vars: HashMap<&str, &str> = [("INKAN_ENVIRONMENTS", "dev, qa"), ("INKAN_ENVIRONMENT", "qa"), ("INKAN_ENVIRONMENT_MISMATCH", "warn")].into();
+        let config = Config::from_lookup(|name| vars.get(name).map(|v| v.to_string())).unwrap();
+        assert_eq!(config.environments, ["dev", "qa"]);
+        assert_eq!((config.environment.as_deref(), config.environment_mismatch), (Some("qa"), MismatchPolicy::Warn));
+        let vars: HashMap<&str, &str> = [("INKAN_ENVIRONMENT", "qa")].into();
+        assert!(Config::from_lookup(|name| vars.get(name).map(|v| v.to_string())).is_err());
+
+        for name in ["INKAN_LEGACY_ENVELOPE", "LEGACY_ENVELOPE"] {
+            let vars: HashMap<&str, &str> = [(name, "true")].into();
+            assert!(Config::from_lookup(|name| vars.get(name).map(|v| v.to_string())).unwrap().legacy_envelope, "{}", name);
+        }
+
+        let vars: HashMap<&str, &str> = [("INKAN_RATE_LIMIT_BACKEND_FAILURE", "closed"), ("INKAN_SHARE_RATE_LIMIT_BACKEND_FAILURE", "open")].into();
+        let modes = Config::from_lookup(|name| vars.get(name).map(|v| v.to_string())).unwrap().rate_limit_backend_failure;
+        assert_eq!((modes.verify, modes.shares, modes.federation), (BackendFailure::Closed, BackendFailure::Open, BackendFailure::Closed));
+        let vars: HashMap<&str, &str> = [("INKAN_FEDERATION_RATE_LIMIT_BACKEND_FAILURE", "maybe")].into();
+        assert!(Config::from_lookup(|name| vars.get(name).map(|v| v.to_string())).is_err());
+
+        let vars: HashMap<&str, &str> = [("INKAN_RECEIPT_FAILURE", "warn")].into();
+        assert_eq!(Config::from_lookup(|name| vars.get(name).map(|v| v.to_string())).unwrap().receipt_failure, ReceiptFailurePolicy::Warn);
+        let vars: HashMap<&str, &str> = [("INKAN_RECEIPT_FAILURE", "ignore")].into();
+        assert!(Config::from_lookup(|name| vars.get(name).map(|v| v.to_string())).is_err());
+
+        let vars: HashMap<&str, &str> = [("INKAN_STORAGE_BREAKER_SLOW_MS", "500"), ("INKAN_STORAGE_BREAKER_PROBES", "1")].into();
+        let breaker = Config::from_lookup(|name| vars.get(name).map(|v| v.to_string())).unwrap().storage_breaker;
+        assert_eq!((breaker.failure_percent, breaker.slow_ms, breaker.probes), (DEFAULT_STORAGE_BREAKER_FAILURE_PERCENT, 500, 1));
+        let vars: HashMap<&str, &str> = [("INKAN_STORAGE_BREAKER_FAILURE_PERCENT", "150")].into();
+        assert!(Config::from_lookup(|name| vars.get(name).map(|v| v.to_string())).is_err());
+
+        let vars: HashMap<&str, &str> = [("INKAN_PROFILE", "verifier")].into();
+        let config = Config::from_lookup(|name| vars.get(name).map(|v| v.to_string())).unwrap();
+        assert_eq!(config.profile, DeploymentProfile::Verifier);
+        let vars: HashMap<&str, &str> = [("INKAN_PROFILE", "signer")].into();
+        assert!(Config::from_lookup(|name| vars.get(name).map(|v| v.to_string())).is_err());
+
+        let root = "11qYAYKxCrfVS/7TyWQHOg7hcvPapiMlrwIaaPcHURo=";
+        let vars: HashMap<&str, &str> = [("INKAN_TRUST_MANIFEST_PATH", "pinset.json"), ("INKAN_TRUST_ROOT_PUBLIC_KEY", root)].into();
+        let config = Config::from_lookup(|name| vars.get(name).map(|v| v.to_string())).unwrap();
+        assert_eq!(config.profile, DeploymentProfile::Verifier);
+        assert_eq!(config.trust_manifest.unwrap().root_public_key, root);
+        for vars in [
+            [("INKAN_TRUST_MANIFEST_PATH", "pinset.json"), ("INKAN_PROFILE", "full")],
+            [("INKAN_TRUST_MANIFEST_PATH", "pinset.json"), ("INKAN_TRUST_ROOT_PUBLIC_KEY", "not-a-key")],
+        ] {
+            let mut vars: HashMap<&str, &str> = vars.into();
+            vars.entry("INKAN_TRUST_ROOT_PUBLIC_KEY").or_insert(root);
+            assert!(Config::from_lookup(|name| vars.get(name).map(|v| v.to_string())).is_err(), "{:?}", vars);
+        }
+        let vars: HashMap<&str, &str> = [("INKAN_TRUST_MANIFEST_PATH", "pinset.json")].into();
+        assert!(Config::from_lookup(|name| vars.get(name).map(|v| v.to_string())).is_err());
+
+        let vars: HashMap<&str, &str> = [("INKAN_PKCS11_MODULE", "/usr/lib/softhsm/libsofthsm2.so"), ("INKAN_PKCS11_PIN", "1234")].into();
+        let pkcs11 = Config::from_lookup(|name| vars.get(name).map(|v| v.to_string())).unwrap().pkcs11.unwrap();
+        assert_eq!((pkcs11.module.as_str(), pkcs11.pin.as_str()), ("/usr/lib/softhsm/libsofthsm2.so", "1234"));
+        assert!(!serde_json::to_string(&pkcs11).unwrap().contains("1234"));
+        let vars: HashMap<&str, &str> = [("INKAN_PKCS11_MODULE", "/usr/lib/softhsm/libsofthsm2.so")].into();
+        assert!(Config::from_lookup(|name| vars.get(name).map(|v| v.to_string())).is_err());
+    }
+
+    #[test]
+    fn test_calibration_is_monotonic_in_target() {
+        let benchmark = KdfBenchmark::measure(KdfParams::default()).unwrap();
+        let suggestions: Vec<u32> = [50, 100, 250, 500, 1000]
+            .iter()
+            .map(|target| benchmark.suggest(*target).iterations)
+            .collect();
+        assert!(suggestions.windows(2).all(|pair| pair[0] <= pair[1]), "{:?}", suggestions);
+        assert!(suggestions[0] < suggestions[4]);
+        assert!(suggestions.iter().all(|iterations| *iterations >= MIN_PBKDF2_ITERATIONS));
+    }
+
+    #[test]
+    fn test_argon2id_derivation_is_deterministic() {
+        let params = KdfParams::argon2id(1, 1024, 1);
+        let first = params.derive_key(b"password", &[1u8; 16]).unwrap();
+        assert_eq!(first, params.derive_key(b"password", &[1u8; 16]).unwrap());
+        assert_ne!(first, KdfParams::pbkdf2(MIN_PBKDF2_ITERATIONS).derive_key(b"password", &[1u8; 16]).unwrap());
+    }
+}
@@ -0,0 +1,191 @@
+//! Request deadlines
+//!
+//! Every request has a time budget: `INKAN_REQUEST_TIMEOUT_SECS` for ordinary routes, and
+//! `INKAN_LONG_REQUEST_TIMEOUT_SECS` for the routes the route table marks long-running, which
+//! work through the whole keystore or read large request bodies (see
+//! [`crate::routes::long_running_routes`]). A request that outlasts its budget is answered `504`
+//! with code `DEADLINE_EXCEEDED`, and its [`Deadline`] is cancelled. A request dropped because
+//! the client went away is cancelled the same way.
+//!
+//! Cancelling a request drops its future, which stops async work at its next `.await`, but not
+//! a loop that never yields or one running in a blocking task. Such loops call
+//! [`Deadline::check`] before each item. It fails once the deadline is cancelled or past, and
+//! records how far the loop got so the `504` can report `completed` of `total`.
+
+use crate::config::Config;
+use crate::models::KeyManagementError;
+use crate::routes::long_running_routes;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// How far a cancelled loop got
+#[derive(Debug, Clone, Copy, Default, Serialize, PartialEq, Eq)]
+pub struct Progress {
+    pub completed: usize,
+    pub total: usize,
+}
+
+struct DeadlineState {
+    expires_at: Option<Instant>,
+    cancelled: AtomicBool,
+    progress: Mutex<Option<Progress>>,
+}
+
+/// Cancellation handle for one request, shared with the work it starts
+#[derive(Clone)]
+pub struct Deadline(Arc<DeadlineState>);
+
+impl Deadline {
+    /// Deadline `budget` from now
+    pub fn after(budget: Duration) -> Self {
+        Self::new(Some(Instant::now() + budget))
+    }
+
+    /// Deadline that only passes when cancelled, for work started outside a request
+    pub fn none() -> Self {
+        Self::new(None)
+    }
+
+    fn new(expires_at: Option<Instant>) -> Self {
+        Self(Arc::new(DeadlineState { expires_at, cancelled: AtomicBool::new(false), progress: Mutex::new(None) }))
+    }
+
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether the work should stop
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::SeqCst) || self.0.expires_at.is_some_and(|expires_at| Instant::now() >= expires_at)
+    }
+
+    /// Records that `completed` of `total` items are done, failing if the work should stop
+    pub fn check(&self, completed: usize, total: usize) -> Result<(), KeyManagementError> {
+        *self.0.progress.lock().unwrap_or_else(|e| e.into_inner()) = Some(Progress { completed, total });
+        if self.is_cancelled() {
+            return Err(KeyManagementError::DeadlineExceeded { completed, total });
+        }
+        Ok(())
+    }
+
+    /// Progress last recorded by [`Deadline::check`]
+    pub fn progress(&self) -> Option<Progress> {
+        *self.0.progress.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Cancels a deadline when dropped, unless disarmed once the request has been answered
+pub struct CancelOnDrop(Option<Deadline>);
+
+impl CancelOnDrop {
+    pub fn new(deadline: Deadline) -> Self {
+        Self(Some(deadline))
+    }
+
+    pub fn disarm(&mut self) {
+        self.0 = None;
+    }
+}
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        if let Some(deadline) = self.0.take() {
+            deadline.cancel();
+        }
+    }
+}
+
+/// Budgets by route, and the requests that outlasted them
+pub struct RequestDeadlines {
+    pub default: Duration,
+    pub long: Duration,
+    timeouts: Mutex<BTreeMap<(&'static str, String), u64>>,
+}
+
+impl RequestDeadlines {
+    pub fn new(default: Duration, long: Duration) -> Self {
+        Self { default, long, timeouts: Mutex::new(BTreeMap::new()) }
+    }
+
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(
+            Duration::from_secs(config.request_timeout_secs.into()),
+            Duration::from_secs(config.long_request_timeout_secs.into()),
+        )
+    }
+
+    /// Budget of a route, given as its pattern such as `/keys/:key_id`
+    pub fn budget(&self, route: &str) -> Duration {
+        if long_running_routes().iter().any(|long_running| long_running == route) {
+            self.long
+        } else {
+            self.default
+        }
+    }
+
+    /// Counts a timeout of `route` requested through the API version labelled `version`
+    pub fn record_timeout(&self, version: &'static str, route: &str) {
+        *self.timeouts.lock().unwrap_or_else(|e| e.into_inner()).entry((version, route.to_string())).or_default() += 1;
+    }
+
+    /// Timed out requests by API version label and route, for metrics
+    pub fn timeouts(&self) -> Vec<(&'static str, String, u64)> {
+        self.timeouts.lock().unwrap_or_else(|e| e.into_inner()).iter()
+            .map(|((version, route), count)| (*version, route.clone(), *count))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    #[tokio::test]
+    async fn test_slow_blocking_loop_stops_at_the_deadline() {
+        let deadline = Deadline::after(Duration::from_millis(100));
+        let processed = Arc::new(AtomicUsize::new(0));
+        let task = {
+            let (deadline, processed) = (deadline.clone(), processed.clone());
+            tokio::task::spawn_blocking(move || {
+                for item in 0..100 {
+                    deadline.check(item, 100)?;
+                    std::thread::sleep(Duration::from_millis(20));
+                    processed.fetch_add(1, Ordering::SeqCst);
+                }
+                Ok::<_, KeyManagementError>(())
+            })
+        };
+
+        let Err(KeyManagementError::DeadlineExceeded { completed, total }) = task.await.unwrap() else {
+            panic!("the loop ran to completion");
+        };
+        assert_eq!(total, 100);
+        assert!((1..20).contains(&completed), "{}", completed);
+        assert_eq!(processed.load(Ordering::SeqCst), completed);
+        assert_eq!(deadline.progress(), Some(Progress { completed, total }));
+    }
+
+    #[test]
+    fn test_dropped_requests_cancel_their_work() {
+        let deadline = Deadline::none();
+        let mut answered = CancelOnDrop::new(deadline.clone());
+        answered.disarm();
+        drop(answered);
+        assert!(deadline.check(0, 1).is_ok());
+
+        drop(CancelOnDrop::new(deadline.clone()));
+        assert!(deadline.is_cancelled());
+        assert!(matches!(deadline.check(1, 2), Err(KeyManagementError::DeadlineExceeded { completed: 1, total: 2 })));
+
+        let deadlines = RequestDeadlines::new(Duration::from_secs(1), Duration::from_secs(9));
+        for route in ["/keys/export", "/keys/status/batch", "/sign/manifest", "/verify/manifest", "/admin/reencrypt-scan"] {
+            assert_eq!(deadlines.budget(route), Duration::from_secs(9), "{}", route);
+        }
+        assert_eq!(deadlines.budget("/keys/:key_id"), Duration::from_secs(1));
+    }
+}
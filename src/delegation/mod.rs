@@ -0,0 +1,348 @@
+//! Signing delegation
+//!
+//! A key holder can hand a CI job a delegation token instead of the key's password or a client
+//! secret. The token signs with one key until its TTL runs out, at most `max_uses` times and,
+//! when contexts are listed, only with one of them. Only the SHA-256 of each token is stored. An
+//! encrypted key's password is kept sealed under a key derived from the token, so the
+//! delegations file alone can neither sign nor reveal the password.
+//!
+//! A use is spent as soon as the token is accepted, whether or not a signature is then made, so
+//! a token never signs more often than it was granted.
+
+use crate::models::KeyManagementError;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
+use hkdf::Hkdf;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::Path;
+use tokio::fs;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+/// Header a delegated `/sign` request carries its token in
+pub const DELEGATION_TOKEN_HEADER: &str = "x-delegation-token";
+
+/// Random bytes in a delegation token
+pub const DELEGATION_TOKEN_BYTES: usize = 32;
+
+/// Most signatures one delegation may grant
+pub const MAX_DELEGATION_USES: u32 = 10_000;
+
+/// Domain tag of the key sealing a delegated password
+const DELEGATION_SEAL_CONTEXT: &[u8] = b"inkan-delegation-v1";
+
+/// A grant to sign with one key under a token
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Delegation {
+    pub id: Uuid,
+    pub token_hash: String, // Hex SHA-256 of the token
+    pub key_id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issued_by: Option<String>, // Authenticated client that issued the grant
+    pub issued_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub max_uses: u32,
+    #[serde(default)]
+    pub uses: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contexts: Option<Vec<String>>, // Signing contexts the token may use; any when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sealed_password: Option<String>, // Key password sealed under the token, for encrypted keys
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_used_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+/// A delegation as reported to key holders; the token hash and sealed password stay private
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DelegationInfo {
+    pub id: Uuid,
+    pub key_id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issued_by: Option<String>,
+    pub issued_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub max_uses: u32,
+    pub uses: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contexts: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+impl From<&Delegation> for DelegationInfo {
+    fn from(delegation: &Delegation) -> Self {
+        Self {
+            id: delegation.id,
+            key_id: delegation.key_id,
+            issued_by: delegation.issued_by.clone(),
+            issued_at: delegation.issued_at,
+            expires_at: delegation.expires_at,
+            max_uses: delegation.max_uses,
+            uses: delegation.uses,
+            contexts: delegation.contexts.clone(),
+            last_used_at: delegation.last_used_at,
+        }
+    }
+}
+
+impl Delegation {
+    /// Whether the token can still sign at `now`
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.revoked_at.is_none() && now < self.expires_at && self.uses < self.max_uses
+    }
+}
+
+/// What a new delegation grants
+#[derive(Debug, Clone, Default)]
+pub struct DelegationGrant {
+    pub key_id: Uuid,
+    pub issued_by: Option<String>,
+    pub ttl: Duration,
+    pub max_uses: u32,
+    pub contexts: Option<Vec<String>>,
+    /// Password of an encrypted key, sealed under the token
+    pub password: Option<String>,
+}
+
+/// A token accepted for one signature
+#[derive(Debug, Clone)]
+pub struct Redeemed {
+    /// The delegation after this use was counted
+    pub delegation: Delegation,
+    /// The key's password, for an encrypted key
+    pub password: Option<String>,
+}
+
+/// Generates a new delegation token: 32 random bytes, base64url without padding
+pub fn generate_delegation_token() -> String {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(rand::random::<[u8; DELEGATION_TOKEN_BYTES]>())
+}
+
+/// Hex SHA-256 of a delegation token, the form it is stored and looked up in
+pub fn delegation_token_hash(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+fn seal_cipher(token: &str) -> Aes256Gcm {
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(None, token.as_bytes())
+        .expand(DELEGATION_SEAL_CONTEXT, &mut key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key))
+}
+
+/// Seals `password` under `token`, bound to the delegation `id`: base64 of nonce and ciphertext
+fn seal_password(token: &str, id: Uuid, password: &str) -> Result<String, KeyManagementError> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = seal_cipher(token)
+        .encrypt(&nonce, Payload { msg: password.as_bytes(), aad: id.as_bytes() })
+        .map_err(|e| KeyManagementError::InternalError(format!("Sealing the delegated password failed: {}", e)))?;
+    Ok(base64::engine::general_purpose::STANDARD.encode([nonce.as_slice(), &ciphertext].concat()))
+}
+
+fn open_password(token: &str, id: Uuid, sealed: &str) -> Result<String, KeyManagementError> {
+    let corrupted = || KeyManagementError::InternalError(format!("Sealed password of delegation {} cannot be opened", id));
+    let bytes = base64::engine::general_purpose::STANDARD.decode(sealed).map_err(|_| corrupted())?;
+    if bytes.len() < 12 {
+        return Err(corrupted());
+    }
+    let (nonce, ciphertext) = bytes.split_at(12);
+    let password = seal_cipher(token)
+        .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: id.as_bytes() })
+        .map_err(|_| corrupted())?;
+    String::from_utf8(password).map_err(|_| corrupted())
+}
+
+/// File-backed store of signing delegations
+pub struct DelegationStore {
+    delegations: Mutex<HashMap<Uuid, Delegation>>,
+    storage_path: String,
+}
+
+impl DelegationStore {
+    /// Creates a new delegation store persisted at `storage_path`
+    pub fn new(storage_path: &str) -> Self {
+        Self {
+            delegations: Mutex::new(HashMap::new()),
+            storage_path: storage_path.to_string(),
+        }
+    }
+
+    /// Records a delegation, returning its token
+    ///
+    /// Delegations that have expired are dropped at the same time.
+    pub async fn issue(&self, grant: DelegationGrant, now: DateTime<Utc>) -> Result<(String, Delegation), KeyManagementError> {
+        let token = generate_delegation_token();
+        let id = Uuid::new_v4();
+        let delegation = Delegation {
+            id,
+            token_hash: delegation_token_hash(&token),
+            key_id: grant.key_id,
+            issued_by: grant.issued_by,
+            issued_at: now,
+            expires_at: now + grant.ttl,
+            max_uses: grant.max_uses,
+            uses: 0,
+            contexts: grant.contexts,
+            sealed_password: grant.password.map(|password| seal_password(&token, id, &password)).transpose()?,
+            last_used_at: None,
+            revoked_at: None,
+        };
+        {
+            let mut delegations = self.delegations.lock().await;
+            delegations.retain(|_, delegation| now < delegation.expires_at);
+            delegations.insert(id, delegation.clone());
+        }
+        self.save_to_disk().await?;
+        Ok((token, delegation))
+    }
+
+    /// Spends one use of `token` on a signature with `key_id` and `context`
+    ///
+    /// Refused with [`KeyManagementError::DelegationDenied`] when the token is unknown, expired,
+    /// revoked or used up, or does not cover the key or context; a refused attempt spends nothing.
+    pub async fn redeem(&self, token: &str, key_id: Uuid, context: Option<&str>, now: DateTime<Utc>) -> Result<Redeemed, KeyManagementError> {
+        let token_hash = delegation_token_hash(token);
+        let delegation = {
+            let mut delegations = self.delegations.lock().await;
+            let delegation = delegations.values_mut()
+                .find(|delegation| delegation.token_hash == token_hash)
+                .filter(|delegation| delegation.revoked_at.is_none() && now < delegation.expires_at)
+                .ok_or_else(|| KeyManagementError::DelegationDenied("Delegation token is unknown, expired or revoked".to_string()))?;
+            if delegation.uses >= delegation.max_uses {
+                return Err(KeyManagementError::DelegationDenied(format!("Delegation {} has no uses left", delegation.id)));
+            }
+            if delegation.key_id != key_id {
+                return Err(KeyManagementError::DelegationDenied(format!("Delegation {} does not cover key {}", delegation.id, key_id)));
+            }
+            if let Some(contexts) = &delegation.contexts {
+                if !context.is_some_and(|context| contexts.iter().any(|allowed| allowed == context)) {
+                    return Err(KeyManagementError::DelegationDenied(format!(
+                        "Delegation {} may only sign with context {}", delegation.id, contexts.join(", "),
+                    )));
+                }
+            }
+            delegation.uses += 1;
+            delegation.last_used_at = Some(now);
+            delegation.clone()
+        };
+        self.save_to_disk().await?;
+        let password = delegation.sealed_password.as_deref()
+            .map(|sealed| open_password(token, delegation.id, sealed))
+            .transpose()?;
+        Ok(Redeemed { delegation, password })
+    }
+
+    /// Delegations of `key_id` that can still sign, oldest first
+    pub async fn outstanding(&self, key_id: Uuid, now: DateTime<Utc>) -> Vec<Delegation> {
+        let delegations = self.delegations.lock().await;
+        let mut outstanding: Vec<Delegation> = delegations.values()
+            .filter(|delegation| delegation.key_id == key_id && delegation.is_active(now))
+            .cloned()
+            .collect();
+        outstanding.sort_by_key(|delegation| (delegation.issued_at, delegation.id));
+        outstanding
+    }
+
+    /// Revokes a delegation; returns it, or `None` if it could no longer sign anyway
+    pub async fn revoke(&self, id: Uuid, now: DateTime<Utc>) -> Result<Option<Delegation>, KeyManagementError> {
+        let revoked = {
+            let mut delegations = self.delegations.lock().await;
+            match delegations.get_mut(&id) {
+                Some(delegation) if delegation.is_active(now) => {
+                    delegation.revoked_at = Some(now);
+                    delegation.clone()
+                }
+                _ => return Ok(None),
+            }
+        };
+        self.save_to_disk().await.map(|()| Some(revoked))
+    }
+
+    /// Loads delegations from disk on startup
+    pub async fn load_from_disk(&self) -> Result<(), KeyManagementError> {
+        let path = Path::new(&self.storage_path);
+        if !path.exists() {
+            return Ok(());
+        }
+
+        let content = fs::read_to_string(path).await
+            .map_err(|e| KeyManagementError::StorageError(format!("Failed to read delegations file: {}", e)))?;
+        if content.is_empty() {
+            return Ok(());
+        }
+
+        let loaded: Vec<Delegation> = serde_json::from_str(&content)
+            .map_err(|e| KeyManagementError::StorageError(format!("Failed to parse delegations file: {}", e)))?;
+
+        let mut delegations = self.delegations.lock().await;
+        for delegation in loaded {
+            delegations.insert(delegation.id, delegation);
+        }
+        Ok(())
+    }
+
+    async fn save_to_disk(&self) -> Result<(), KeyManagementError> {
+        let delegations = self.delegations.lock().await;
+        let delegations: Vec<&Delegation> = delegations.values().collect();
+
+        let content = serde_json::to_string_pretty(&delegations)
+            .map_err(|e| KeyManagementError::StorageError(format!("Failed to serialize delegations: {}", e)))?;
+        fs::write(&self.storage_path, content).await
+            .map_err(|e| KeyManagementError::StorageError(format!("Failed to write delegations file: {}", e)))?;
+        Ok(())
+    }
+}
+
+/// Creates a delegation store at `DELEGATIONS_PATH` (default `delegations.json`)
+pub fn create_default_delegation_store() -> DelegationStore {
+    let storage_path = std::env::var("DELEGATIONS_PATH").unwrap_or_else(|_| "delegations.json".to_string());
+    DelegationStore::new(&storage_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_tokens_are_hashed_at_rest_and_unseal_the_password() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("delegations.json");
+        let store = DelegationStore::new(path.to_str().unwrap());
+        let now = Utc::now();
+        let key_id = Uuid::new_v4();
+        let grant = DelegationGrant {
+            key_id,
+            ttl: Duration::hours(1),
+            max_uses: 1,
+            password: Some("hunter22".to_string()),
+            ..Default::default()
+        };
+        let (token, delegation) = store.issue(grant, now).await.unwrap();
+
+        let file = std::fs::read_to_string(&path).unwrap();
+        assert!(!file.contains(&token) && !file.contains("hunter22"));
+        assert!(file.contains(&delegation.token_hash));
+
+        // Loaded back, as after a restart
+        let reloaded = DelegationStore::new(path.to_str().unwrap());
+        reloaded.load_from_disk().await.unwrap();
+        assert!(matches!(reloaded.redeem(&generate_delegation_token(), key_id, None, now).await, Err(KeyManagementError::DelegationDenied(_))));
+        let redeemed = reloaded.redeem(&token, key_id, None, now).await.unwrap();
+        assert_eq!(redeemed.password.as_deref(), Some("hunter22"));
+        assert_eq!(redeemed.delegation.uses, 1);
+        assert!(reloaded.outstanding(key_id, now).await.is_empty());
+
+        // A sealed password opens only with its own token and delegation
+        let sealed = delegation.sealed_password.unwrap();
+        assert!(open_password(&generate_delegation_token(), delegation.id, &sealed).is_err());
+        assert!(open_password(&token, Uuid::new_v4(), &sealed).is_err());
+    }
+}
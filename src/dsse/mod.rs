@@ -0,0 +1,164 @@
+//! Dead Simple Signing Envelope (DSSE) framing
+//!
+//! Implements DSSE v1 as used by in-toto attestations. The signature covers the
+//! pre-authentication encoding (PAE) of the payload type and the payload, so a payload signed as
+//! one type never verifies as another. An envelope may carry several signatures; which of them
+//! must verify is the caller's choice of [`VerifyMode`], never implied.
+
+use crate::models::KeyManagementError;
+use crate::signing_backend::KeySigner;
+use base64::Engine;
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+
+/// Version tag that starts every PAE
+pub const PAE_PREFIX: &str = "DSSEv1";
+/// Payload type of in-toto statements
+pub const IN_TOTO_PAYLOAD_TYPE: &str = "application/vnd.in-toto+json";
+/// Most signatures an envelope given for verification may carry
+pub const MAX_ENVELOPE_SIGNATURES: usize = 16;
+
+/// One signature in an envelope
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DsseSignature {
+    /// Unauthenticated hint naming the signing key; this service sets its fingerprint
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub keyid: String,
+    pub sig: String,
+}
+
+/// A DSSE envelope, serialized with the spec's field names
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct DsseEnvelope {
+    pub payload: String,
+    #[serde(rename = "payloadType")]
+    pub payload_type: String,
+    pub signatures: Vec<DsseSignature>,
+}
+
+/// Which signatures of an envelope must verify
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum VerifyMode {
+    /// At least one of the trusted keys signed the envelope
+    Any,
+    /// Every trusted key signed the envelope
+    All,
+}
+
+impl VerifyMode {
+    /// Whether the keys that signed, one entry per trusted key, satisfy the mode
+    pub fn is_satisfied(self, matches: &[Option<usize>]) -> bool {
+        match self {
+            VerifyMode::Any => matches.iter().any(Option::is_some),
+            VerifyMode::All => !matches.is_empty() && matches.iter().all(Option::is_some),
+        }
+    }
+}
+
+/// Pre-authentication encoding of a payload and its type
+///
+/// `"DSSEv1" SP LEN(type) SP type SP LEN(body) SP body`, lengths in bytes as ASCII decimal.
+pub fn pae(payload_type: &str, payload: &[u8]) -> Vec<u8> {
+    let mut encoded = format!("{} {} {} {} ", PAE_PREFIX, payload_type.len(), payload_type, payload.len()).into_bytes();
+    encoded.extend_from_slice(payload);
+    encoded
+}
+
+/// Decodes base64 in either the standard or the URL-safe alphabet, as the spec asks verifiers
+/// to accept, with or without padding
+pub fn decode_base64(value: &str, field: &str) -> Result<Vec<u8>, KeyManagementError> {
+    use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD};
+    [STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD].iter()
+        .find_map(|engine| engine.decode(value).ok())
+        .ok_or_else(|| KeyManagementError::InvalidRequest(format!("{} is not valid base64", field)))
+}
+
+/// Signs `payload` as `payload_type`, returning an envelope with the one signature
+pub fn sign(signer: &dyn KeySigner, payload_type: &str, payload: &[u8], keyid: &str) -> Result<DsseEnvelope, KeyManagementError> {
+    let signature = signer.sign_message(&pae(payload_type, payload))?;
+    Ok(DsseEnvelope {
+        payload: base64::engine::general_purpose::STANDARD.encode(payload),
+        payload_type: payload_type.to_string(),
+        signatures: vec![DsseSignature {
+            keyid: keyid.to_string(),
+            sig: base64::engine::general_purpose::STANDARD.encode(signature.to_bytes()),
+        }],
+    })
+}
+
+impl DsseEnvelope {
+    /// The payload bytes
+    pub fn decode_payload(&self) -> Result<Vec<u8>, KeyManagementError> {
+        decode_base64(&self.payload, "payload")
+    }
+
+    /// For each of `keys`, the index of the first signature it verifies
+    ///
+    /// Every signature is tried against every key: `keyid` is an unauthenticated hint and does
+    /// not decide which key a signature is checked with. Signatures that do not decode count as
+    /// not verifying.
+    pub fn verify(&self, keys: &[VerifyingKey]) -> Result<Vec<Option<usize>>, KeyManagementError> {
+        if self.signatures.is_empty() {
+            return Err(KeyManagementError::InvalidRequest("Envelope has no signatures".to_string()));
+        }
+        if self.signatures.len() > MAX_ENVELOPE_SIGNATURES {
+            return Err(KeyManagementError::InvalidRequest(format!("Envelope has more than {} signatures", MAX_ENVELOPE_SIGNATURES)));
+        }
+        let message = pae(&self.payload_type, &self.decode_payload()?);
+        let signatures: Vec<Option<Signature>> = self.signatures.iter()
+            .map(|signature| {
+                let bytes = decode_base64(&signature.sig, "sig").ok()?;
+                <[u8; 64]>::try_from(bytes.as_slice()).ok().map(|bytes| Signature::from_bytes(&bytes))
+            })
+            .collect();
+        Ok(keys.iter()
+            .map(|key| signatures.iter().position(|signature| signature.is_some_and(|signature| key.verify(&message, &signature).is_ok())))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::SigningKey;
+
+    #[test]
+    fn test_pae_matches_the_spec_vectors() {
+        // From the DSSE protocol description
+        assert_eq!(
+            pae("http://example.com/HelloWorld", b"hello world"),
+            b"DSSEv1 29 http://example.com/HelloWorld 11 hello world",
+        );
+        assert_eq!(pae("", b""), b"DSSEv1 0  0 ");
+        // Lengths count bytes, not characters
+        assert_eq!(pae("t\u{e9}", "\u{2713}".as_bytes()), "DSSEv1 3 t\u{e9} 3 \u{2713}".as_bytes());
+    }
+
+    #[test]
+    fn test_multiple_signatures_verify_any_or_all() {
+        let [first, second, outsider] = [1u8, 2, 3].map(|seed| SigningKey::from_bytes(&[seed; 32]));
+        let payload = br#"{"_type":"https://in-toto.io/Statement/v1"}"#;
+        let mut envelope = sign(&first, IN_TOTO_PAYLOAD_TYPE, payload, "first").unwrap();
+        envelope.signatures.extend(sign(&second, IN_TOTO_PAYLOAD_TYPE, payload, "second").unwrap().signatures);
+        assert_eq!(envelope.decode_payload().unwrap(), payload);
+
+        let keys = [first.verifying_key(), outsider.verifying_key()];
+        let matches = envelope.verify(&keys).unwrap();
+        assert_eq!(matches, [Some(0), None]);
+        assert!(VerifyMode::Any.is_satisfied(&matches));
+        assert!(!VerifyMode::All.is_satisfied(&matches));
+        // Checked by key, not by the keyid hint
+        let matches = envelope.verify(&[second.verifying_key(), first.verifying_key()]).unwrap();
+        assert!(VerifyMode::All.is_satisfied(&matches));
+
+        // The type is bound into the signature
+        let retyped = DsseEnvelope { payload_type: "text/plain".to_string(), ..envelope.clone() };
+        assert_eq!(retyped.verify(&keys).unwrap(), [None, None]);
+
+        let json = serde_json::to_value(&envelope).unwrap();
+        assert_eq!(json["payloadType"], IN_TOTO_PAYLOAD_TYPE);
+        assert_eq!(json["signatures"][1]["keyid"], "second");
+    }
+}
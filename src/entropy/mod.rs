@@ -0,0 +1,277 @@
+//! Entropy health
+//!
+//! Key generation draws Ed25519 seeds from the operating system's RNG. A broken or starved RNG
+//! would hand out predictable or repeated keys, so the source is checked at startup and
+//! periodically: a batch of seeds is drawn and turned into keys, every key must be distinct,
+//! and the seed bytes must pass basic sanity checks (no constant seeds, roughly balanced
+//! bits). While the latest check or seed draw has failed, entropy is degraded and key
+//! generation is refused; signing and verification with existing keys are unaffected. RNG read
+//! failures are returned as errors rather than panicking.
+
+use crate::clock::Clock;
+use crate::models::KeyManagementError;
+use crate::notifications::{send_alert, Notifier, ServiceAlert};
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use ed25519_dalek::{SigningKey, SECRET_KEY_LENGTH};
+use rand_core::{OsRng, RngCore};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// Seeds drawn by each entropy check
+pub const ENTROPY_CHECK_SAMPLES: usize = 16;
+
+/// Random bytes in a generated key password, encoded as 43 URL-safe base64 characters
+pub const PASSPHRASE_BYTES: usize = 32;
+
+/// Standard deviations the share of set bits may stray from one half before a check fails
+///
+/// A healthy source fails this less than once in a million checks.
+const MAX_BIT_BIAS_SIGMAS: f64 = 5.0;
+
+/// Source of the random bytes private keys are generated from
+pub trait EntropySource: Send + Sync {
+    /// Fills `dest` with random bytes, failing if the source cannot supply them
+    fn fill(&self, dest: &mut [u8]) -> Result<(), String>;
+}
+
+/// The operating system's RNG
+pub struct OsEntropy;
+
+impl EntropySource for OsEntropy {
+    fn fill(&self, dest: &mut [u8]) -> Result<(), String> {
+        OsRng.try_fill_bytes(dest).map_err(|e| e.to_string())
+    }
+}
+
+/// Draws one Ed25519 seed from `source`
+pub fn draw_seed(source: &dyn EntropySource) -> Result<[u8; SECRET_KEY_LENGTH], KeyManagementError> {
+    let mut seed = [0u8; SECRET_KEY_LENGTH];
+    source.fill(&mut seed)
+        .map_err(|e| KeyManagementError::InternalError(format!("Random number generator failed: {}", e)))?;
+    Ok(seed)
+}
+
+/// Draws a random password for encrypting a private key
+pub fn draw_passphrase(source: &dyn EntropySource) -> Result<String, KeyManagementError> {
+    let mut bytes = [0u8; PASSPHRASE_BYTES];
+    source.fill(&mut bytes)
+        .map_err(|e| KeyManagementError::InternalError(format!("Random number generator failed: {}", e)))?;
+    Ok(base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes))
+}
+
+/// Draws `samples` seeds and checks they are usable as private keys
+pub fn check_entropy(source: &dyn EntropySource, samples: usize) -> Result<(), String> {
+    let mut seeds = Vec::with_capacity(samples);
+    for _ in 0..samples {
+        let seed = draw_seed(source).map_err(|e| e.to_string())?;
+        if seed.iter().all(|byte| *byte == seed[0]) {
+            return Err(format!("RNG returned a constant seed of 0x{:02x} bytes", seed[0]));
+        }
+        seeds.push(seed);
+    }
+
+    let public_keys: HashSet<[u8; 32]> = seeds.iter()
+        .map(|seed| SigningKey::from_bytes(seed).verifying_key().to_bytes())
+        .collect();
+    if public_keys.len() != seeds.len() {
+        return Err(format!("RNG repeated keys: {} distinct in {} draws", public_keys.len(), seeds.len()));
+    }
+
+    let bits = (seeds.len() * SECRET_KEY_LENGTH * 8) as f64;
+    let ones: u32 = seeds.iter().flatten().map(|byte| byte.count_ones()).sum();
+    if (f64::from(ones) - bits / 2.0).abs() > MAX_BIT_BIAS_SIGMAS * (bits / 4.0).sqrt() {
+        return Err(format!("RNG output is biased: {} of {} bits set", ones, bits));
+    }
+    Ok(())
+}
+
+/// Health of the entropy source behind key generation
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct EntropyStatus {
+    /// Whether the latest check or seed draw failed; key generation is refused while true
+    pub degraded: bool,
+    pub consecutive_failures: u32,
+    pub total_failures: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_checked_at: Option<DateTime<Utc>>,
+}
+
+/// Entropy source used for key generation, with the outcome of its latest check
+pub struct EntropyMonitor {
+    source: Arc<dyn EntropySource>,
+    status: std::sync::Mutex<EntropyStatus>,
+}
+
+impl EntropyMonitor {
+    pub fn new(source: Arc<dyn EntropySource>) -> Self {
+        Self {
+            source,
+            status: std::sync::Mutex::new(EntropyStatus::default()),
+        }
+    }
+
+    /// Monitor over the operating system's RNG
+    pub fn os() -> Self {
+        Self::new(Arc::new(OsEntropy))
+    }
+
+    /// Current health of the entropy source
+    pub fn status(&self) -> EntropyStatus {
+        self.status.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Runs the entropy check and records its outcome
+    pub fn check(&self, now: DateTime<Utc>) -> EntropyStatus {
+        let result = check_entropy(self.source.as_ref(), ENTROPY_CHECK_SAMPLES);
+        let mut status = self.status.lock().unwrap_or_else(|e| e.into_inner());
+        status.last_checked_at = Some(now);
+        match result {
+            Ok(()) => {
+                if status.degraded {
+                    tracing::info!("Entropy check passed after {} failures; key generation re-enabled", status.consecutive_failures);
+                }
+                status.degraded = false;
+                status.consecutive_failures = 0;
+                status.last_error = None;
+            }
+            Err(e) => Self::record_failure(&mut status, e),
+        }
+        status.clone()
+    }
+
+    /// Draws a seed for a new key, refusing while entropy is degraded
+    ///
+    /// A failed draw degrades entropy until the next passing check.
+    pub fn draw_seed(&self) -> Result<[u8; SECRET_KEY_LENGTH], KeyManagementError> {
+        self.draw(draw_seed)
+    }
+
+    /// Draws a password for a new key, under the same conditions as [`EntropyMonitor::draw_seed`]
+    pub fn draw_passphrase(&self) -> Result<String, KeyManagementError> {
+        self.draw(draw_passphrase)
+    }
+
+    fn draw<T>(&self, draw: impl FnOnce(&dyn EntropySource) -> Result<T, KeyManagementError>) -> Result<T, KeyManagementError> {
+        let status = self.status();
+        if status.degraded {
+            return Err(KeyManagementError::InternalError(format!(
+                "Key generation is disabled until the entropy check passes: {}",
+                status.last_error.unwrap_or_default(),
+            )));
+        }
+        draw(self.source.as_ref()).inspect_err(|e| {
+            let mut status = self.status.lock().unwrap_or_else(|e| e.into_inner());
+            Self::record_failure(&mut status, e.to_string());
+        })
+    }
+
+    fn record_failure(status: &mut EntropyStatus, error: String) {
+        tracing::error!("Entropy check failed; key generation disabled: {}", error);
+        status.degraded = true;
+        status.consecutive_failures += 1;
+        status.total_failures += 1;
+        status.last_error = Some(error);
+    }
+}
+
+/// Spawns a task that re-checks entropy every `interval`, alerting through `notifiers` each
+/// time entropy becomes degraded
+///
+/// The startup check is expected to have run already, so the first re-check waits a full interval.
+pub fn spawn_entropy_checks(
+    monitor: Arc<EntropyMonitor>,
+    clock: Arc<dyn Clock>,
+    notifiers: Vec<Arc<dyn Notifier>>,
+    interval: Duration,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        ticker.tick().await;
+        let mut alerted = false;
+        loop {
+            let status = monitor.status();
+            match (status.degraded, alerted) {
+                (true, false) => {
+                    let alert = ServiceAlert {
+                        alert: "entropy_degraded".to_string(),
+                        message: format!(
+                            "Key generation is disabled: {}",
+                            status.last_error.as_deref().unwrap_or("entropy check failed"),
+                        ),
+                        raised_at: clock.now(),
+                    };
+                    send_alert(&notifiers, &alert).await;
+                    alerted = true;
+                }
+                (false, _) => alerted = false,
+                (true, true) => {}
+            }
+            ticker.tick().await;
+            monitor.check(clock.now());
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU8, Ordering};
+
+    struct FailingSource;
+
+    impl EntropySource for FailingSource {
+        fn fill(&self, _dest: &mut [u8]) -> Result<(), String> {
+            Err("device not ready".to_string())
+        }
+    }
+
+    /// Fills every draw with the same counter byte, then moves on to the next one
+    struct CountingSource(AtomicU8);
+
+    impl EntropySource for CountingSource {
+        fn fill(&self, dest: &mut [u8]) -> Result<(), String> {
+            dest.fill(self.0.fetch_add(1, Ordering::SeqCst));
+            Ok(())
+        }
+    }
+
+    /// Repeats one fixed, balanced-looking seed
+    struct RepeatingSource;
+
+    impl EntropySource for RepeatingSource {
+        fn fill(&self, dest: &mut [u8]) -> Result<(), String> {
+            for (i, byte) in dest.iter_mut().enumerate() {
+                *byte = (i as u8).wrapping_mul(37) ^ 0x5a;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_entropy_check_catches_broken_sources() {
+        assert_eq!(check_entropy(&OsEntropy, ENTROPY_CHECK_SAMPLES), Ok(()));
+
+        let error = check_entropy(&FailingSource, ENTROPY_CHECK_SAMPLES).unwrap_err();
+        assert!(error.contains("device not ready"), "{}", error);
+        assert!(check_entropy(&CountingSource(AtomicU8::new(0)), ENTROPY_CHECK_SAMPLES).unwrap_err().contains("constant seed"));
+        assert!(check_entropy(&RepeatingSource, ENTROPY_CHECK_SAMPLES).unwrap_err().contains("repeated keys"));
+
+        // A failing source degrades the monitor, and seed draws are refused without panicking
+        let now = Utc::now();
+        let monitor = EntropyMonitor::new(Arc::new(FailingSource));
+        assert!(!monitor.status().degraded);
+        assert!(monitor.draw_seed().is_err());
+        let status = monitor.check(now);
+        assert!(status.degraded);
+        assert_eq!(status.consecutive_failures, 2);
+        assert_eq!(status.last_checked_at, Some(now));
+        assert!(matches!(monitor.draw_seed(), Err(KeyManagementError::InternalError(_))));
+    }
+}
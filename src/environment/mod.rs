@@ -0,0 +1,98 @@
+//! Deployment environments of keys
+//!
+//! Every key belongs to one deployment environment, such as `prod`, set when it is generated and
+//! distinct from its free-form tags. The service runs in the environment named by
+//! `INKAN_ENVIRONMENT`, one of `INKAN_ENVIRONMENTS`. Once that is set, signing with a key of
+//! another environment is refused with `ENVIRONMENT_MISMATCH`, or allowed with a
+//! `ENVIRONMENT_MISMATCH` warning when `INKAN_ENVIRONMENT_MISMATCH=warn`. Keys stored before
+//! environments existed belong to [`UNKNOWN_ENVIRONMENT`] until updated.
+
+use crate::config::Config;
+use crate::models::KeyManagementError;
+use serde::{Deserialize, Serialize};
+
+/// Environment of keys stored before environments existed
+pub const UNKNOWN_ENVIRONMENT: &str = "unknown";
+
+/// Environments a key may belong to unless `INKAN_ENVIRONMENTS` lists others
+pub const DEFAULT_ENVIRONMENTS: &[&str] = &["dev", "staging", "prod"];
+
+/// What signing with a key of another environment does
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MismatchPolicy {
+    #[default]
+    Reject,
+    Warn,
+}
+
+impl MismatchPolicy {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "reject" => Some(MismatchPolicy::Reject),
+            "warn" => Some(MismatchPolicy::Warn),
+            _ => None,
+        }
+    }
+}
+
+/// Serde default of [`crate::models::KeyPair::environment`], giving stored keys the unknown environment
+pub fn unknown_environment() -> String {
+    UNKNOWN_ENVIRONMENT.to_string()
+}
+
+/// Whether a key may be put in `environment` by a request; only migration assigns the unknown one
+pub fn is_configured(config: &Config, environment: &str) -> bool {
+    config.environments.iter().any(|configured| configured == environment)
+}
+
+/// Environment of a generated key that names none: the service's, or the unknown one
+pub fn default_for_generation(config: &Config) -> String {
+    config.environment.clone().unwrap_or_else(unknown_environment)
+}
+
+/// Checks that a key of `key_environment` may sign in this service
+///
+/// Returns the mismatch to warn about when the policy allows it, and fails when it does not.
+/// Nothing is checked while the service environment is unset.
+pub fn check_signing(config: &Config, key_environment: &str) -> Result<Option<String>, KeyManagementError> {
+    let Some(service) = config.environment.as_deref() else {
+        return Ok(None);
+    };
+    if key_environment == service {
+        return Ok(None);
+    }
+    match config.environment_mismatch {
+        MismatchPolicy::Reject => Err(KeyManagementError::EnvironmentMismatch {
+            key: key_environment.to_string(),
+            service: service.to_string(),
+        }),
+        MismatchPolicy::Warn => Ok(Some(format!(
+            "Key belongs to environment '{}' but the service runs in '{}'",
+            key_environment, service,
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signing_outside_the_service_environment() {
+        let unset = Config::default();
+        assert_eq!(check_signing(&unset, "staging").unwrap(), None);
+
+        let prod = Config { environment: Some("prod".to_string()), ..Config::default() };
+        assert_eq!(check_signing(&prod, "prod").unwrap(), None);
+        assert!(matches!(
+            check_signing(&prod, "staging"),
+            Err(KeyManagementError::EnvironmentMismatch { key, service }) if key == "staging" && service == "prod"
+        ));
+        assert!(check_signing(&prod, UNKNOWN_ENVIRONMENT).is_err());
+
+        let warn = Config { environment_mismatch: MismatchPolicy::Warn, ..prod };
+        assert!(check_signing(&warn, "staging").unwrap().unwrap().contains("'staging'"));
+        assert!(is_configured(&warn, "dev") && !is_configured(&warn, UNKNOWN_ENVIRONMENT) && !is_configured(&warn, "qa"));
+    }
+}
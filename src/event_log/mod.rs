@@ -0,0 +1,291 @@
+//! Operation event log
+//!
+//! Every change the service makes is numbered from one global sequence and appended to a log
+//! next to the keystore: each mutating request, each signature released, and each change the
+//! sweeper applies on its own. Timestamps from several clocks do not order reliably, so a
+//! consumer of webhooks that was down for a while finds what it missed as a gap in the
+//! sequence, and backfills it from `/events/replay`.
+//!
+//! An event is appended and synced before its number is handed out, and a failed append hands
+//! the number to the next event instead, so numbers are never reused or skipped. After a
+//! restart the sequence continues from the last event in the file. The file is cut down to the
+//! retained events when the keystore is flushed, which always keeps the latest event and with
+//! it the high-water mark.
+
+use crate::key_storage::write_durably;
+use crate::models::KeyManagementError;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+/// Events `/events/replay` returns when no limit is given
+pub const DEFAULT_REPLAY_LIMIT: usize = 100;
+/// Most events one `/events/replay` page may hold
+pub const MAX_REPLAY_LIMIT: usize = 1_000;
+
+/// What made an event
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Request, // A mutating API request that succeeded
+    Signature, // A signature released with its receipt
+    Sweep, // A change the background sweeper made
+    Reload, // An external change to the keystore file picked up, kept out, or failed to load
+}
+
+/// One numbered change
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OperationEvent {
+    pub seq: u64, // Assigned when the event is recorded
+    pub at: DateTime<Utc>,
+    pub kind: EventKind,
+    pub operation: String, // Method and route of a request, otherwise what changed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_id: Option<Uuid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature_id: Option<Uuid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>, // Signing context a signature was made under
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>, // Response status of a request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client: Option<String>, // Authenticated client that made a request
+}
+
+impl OperationEvent {
+    fn new(kind: EventKind, operation: String, at: DateTime<Utc>) -> Self {
+        Self { seq: 0, at, kind, operation, key_id: None, signature_id: None, context: None, status: None, client: None }
+    }
+
+    /// A mutating request, as its method and route pattern
+    pub fn request(operation: String, key_id: Option<Uuid>, status: u16, client: Option<String>, at: DateTime<Utc>) -> Self {
+        Self { key_id, status: Some(status), client, ..Self::new(EventKind::Request, operation, at) }
+    }
+
+    /// A signature released with its receipt, with the signing context it was made under
+    pub fn signature(key_id: Uuid, signature_id: Uuid, context: Option<String>, at: DateTime<Utc>) -> Self {
+        Self { key_id: Some(key_id), signature_id: Some(signature_id), context, ..Self::new(EventKind::Signature, "signature".to_string(), at) }
+    }
+
+    /// A change the sweeper made to a key, such as `scheduled_revocation`
+    pub fn sweep(operation: &str, key_id: Uuid, at: DateTime<Utc>) -> Self {
+        Self { key_id: Some(key_id), ..Self::new(EventKind::Sweep, operation.to_string(), at) }
+    }
+
+    /// An attempt to reload the keystore file, such as `keystore_reloaded`
+    pub fn reload(operation: &str, at: DateTime<Utc>) -> Self {
+        Self::new(EventKind::Reload, operation.to_string(), at)
+    }
+}
+
+/// A page of events following a sequence number
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventReplay {
+    pub events: Vec<OperationEvent>,
+    pub high_water: u64, // Sequence number of the latest event; 0 before the first
+    pub has_more: bool, // Events past this page exist; replay again after the last one
+}
+
+#[derive(Debug, Default)]
+struct LogState {
+    high_water: u64,
+    retained: VecDeque<OperationEvent>, // Consecutive, oldest first
+    lines: usize, // Lines in the file, retained or not
+    torn: bool, // The file ends in a line cut short, which the next append must not run on from
+}
+
+/// File-backed log of numbered operation events
+pub struct EventLog {
+    state: Mutex<LogState>,
+    storage_path: String,
+    retain: usize,
+    failed_writes: AtomicU64,
+}
+
+impl EventLog {
+    /// Creates an event log persisted at `storage_path`, keeping the `retain` most recent events
+    pub fn new(storage_path: &str, retain: usize) -> Self {
+        Self {
+            state: Mutex::new(LogState::default()),
+            storage_path: storage_path.to_string(),
+            retain: retain.max(1),
+            failed_writes: AtomicU64::new(0),
+        }
+    }
+
+    /// Path of the event log file
+    pub fn storage_path(&self) -> &str {
+        &self.storage_path
+    }
+
+    /// Numbers an event and appends it to the log, returning its sequence number
+    ///
+    /// The event is synced to disk before the number is returned. When the write fails the
+    /// number is not used up, so the sequence stays without gaps.
+    pub async fn record(&self, mut event: OperationEvent) -> Result<u64, KeyManagementError> {
+        let mut state = self.state.lock().await;
+        event.seq = state.high_water + 1;
+        let mut line = if state.torn { vec![b'\n'] } else { Vec::new() };
+        serde_json::to_writer(&mut line, &event)
+            .map_err(|e| KeyManagementError::StorageError(format!("Failed to serialize event: {}", e)))?;
+        line.push(b'\n');
+        if let Err(e) = append_synced(Path::new(&self.storage_path), &line).await {
+            self.failed_writes.fetch_add(1, Ordering::Relaxed);
+            return Err(KeyManagementError::StorageError(format!("Failed to write event log: {}", e)));
+        }
+
+        state.high_water = event.seq;
+        state.lines += 1;
+        state.torn = false;
+        state.retained.push_back(event);
+        if state.retained.len() > self.retain {
+            state.retained.pop_front();
+        }
+        Ok(state.high_water)
+    }
+
+    /// Sequence number of the latest event, or 0 before the first
+    pub async fn high_water(&self) -> u64 {
+        self.state.lock().await.high_water
+    }
+
+    /// Event log writes that failed since startup
+    pub fn failed_writes(&self) -> u64 {
+        self.failed_writes.load(Ordering::Relaxed)
+    }
+
+    /// Up to `limit` events numbered after `after_seq`, oldest first
+    ///
+    /// Fails if some of those events are no longer retained, since the page would silently
+    /// start past a gap.
+    pub async fn replay(&self, after_seq: u64, limit: usize) -> Result<EventReplay, KeyManagementError> {
+        let state = self.state.lock().await;
+        let oldest_seq = state.retained.front().map_or(state.high_water + 1, |event| event.seq);
+        if after_seq < state.high_water && after_seq.saturating_add(1) < oldest_seq {
+            return Err(KeyManagementError::EventsNotRetained { after_seq, oldest_seq });
+        }
+        let skip = after_seq.saturating_add(1).saturating_sub(oldest_seq) as usize;
+        let events: Vec<OperationEvent> = state.retained.iter().skip(skip).take(limit).cloned().collect();
+        let last = events.last().map_or(after_seq, |event| event.seq);
+        Ok(EventReplay { has_more: last < state.high_water, high_water: state.high_water, events })
+    }
+
+    /// Loads the log on startup, continuing the sequence after its last event
+    ///
+    /// A final line cut short by a crash is skipped, as is any line that does not read as an
+    /// event. When an append was written but reported as failed, its number was handed out
+    /// again; the later event under that number wins.
+    pub async fn load_from_disk(&self) -> Result<(), KeyManagementError> {
+        let content = match fs::read_to_string(&self.storage_path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(KeyManagementError::StorageError(format!("Failed to read event log: {}", e))),
+        };
+
+        let mut loaded = LogState { torn: !content.is_empty() && !content.ends_with('\n'), ..LogState::default() };
+        let mut skipped = 0;
+        for line in content.lines().filter(|line| !line.trim().is_empty()) {
+            loaded.lines += 1;
+            let Ok(event) = serde_json::from_str::<OperationEvent>(line) else {
+                skipped += 1;
+                continue;
+            };
+            while loaded.retained.back().is_some_and(|last| last.seq >= event.seq) {
+                loaded.retained.pop_back();
+            }
+            loaded.high_water = event.seq;
+            loaded.retained.push_back(event);
+            if loaded.retained.len() > self.retain {
+                loaded.retained.pop_front();
+            }
+        }
+        if skipped > 0 {
+            tracing::warn!("Skipped {} unreadable lines of the event log {}", skipped, self.storage_path);
+        }
+
+        *self.state.lock().await = loaded;
+        Ok(())
+    }
+
+    /// Rewrites the file with only the retained events once it holds twice as many lines,
+    /// returning whether it was rewritten
+    pub async fn compact(&self) -> Result<bool, KeyManagementError> {
+        let mut state = self.state.lock().await;
+        if state.lines <= self.retain.saturating_mul(2) {
+            return Ok(false);
+        }
+        let mut content = Vec::new();
+        for event in &state.retained {
+            serde_json::to_writer(&mut content, event)
+                .map_err(|e| KeyManagementError::StorageError(format!("Failed to serialize event: {}", e)))?;
+            content.push(b'\n');
+        }
+        write_durably(Path::new(&self.storage_path), &content).await
+            .map_err(|e| KeyManagementError::StorageError(format!("Failed to write event log: {}", e)))?;
+        state.lines = state.retained.len();
+        state.torn = false;
+        Ok(true)
+    }
+}
+
+/// Appends `line` to `path`, creating it if needed, and syncs it to disk
+async fn append_synced(path: &Path, line: &[u8]) -> std::io::Result<()> {
+    let mut file = fs::OpenOptions::new().create(true).append(true).open(path).await?;
+    file.write_all(line).await?;
+    file.sync_data().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn seqs(replay: &EventReplay) -> Vec<u64> {
+        replay.events.iter().map(|event| event.seq).collect()
+    }
+
+    #[tokio::test]
+    async fn test_sequence_survives_restarts_and_compaction() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("keys.json.events");
+        let path = path.to_str().unwrap();
+        let key_id = Uuid::new_v4();
+
+        let log = EventLog::new(path, 4);
+        log.load_from_disk().await.unwrap();
+        for _ in 0..3 {
+            log.record(OperationEvent::sweep("scheduled_revocation", key_id, Utc::now())).await.unwrap();
+        }
+        assert_eq!(seqs(&log.replay(0, 10).await.unwrap()), vec![1, 2, 3]);
+
+        // A crash mid-append leaves half a line behind
+        let mut file = std::fs::OpenOptions::new().append(true).open(path).unwrap();
+        std::io::Write::write_all(&mut file, b"{\"seq\":4,\"at\":").unwrap();
+        drop(file);
+        let log = EventLog::new(path, 4);
+        log.load_from_disk().await.unwrap();
+        assert_eq!(log.high_water().await, 3);
+        assert_eq!(log.record(OperationEvent::signature(key_id, Uuid::new_v4(), None, Utc::now())).await.unwrap(), 4);
+
+        for _ in 0..6 {
+            log.record(OperationEvent::sweep("expiry_notice", key_id, Utc::now())).await.unwrap();
+        }
+        assert!(log.compact().await.unwrap());
+        assert!(!log.compact().await.unwrap());
+        let page = log.replay(6, 2).await.unwrap();
+        assert_eq!((seqs(&page), page.high_water, page.has_more), (vec![7, 8], 10, true));
+        assert!(matches!(log.replay(2, 10).await, Err(KeyManagementError::EventsNotRetained { after_seq: 2, oldest_seq: 7 })));
+
+        let log = EventLog::new(path, 4);
+        log.load_from_disk().await.unwrap();
+        assert_eq!(seqs(&log.replay(6, 10).await.unwrap()), vec![7, 8, 9, 10]);
+        assert!(log.replay(10, 10).await.unwrap().events.is_empty());
+        assert_eq!(log.record(OperationEvent::sweep("expiry_notice", key_id, Utc::now())).await.unwrap(), 11);
+    }
+}
@@ -0,0 +1,336 @@
+//! Public key export archives
+//!
+//! Packs the keystore's public keys into a zip or gzipped tar archive for air-gapped
+//! verifiers: one file per key and requested encoding, named by fingerprint, plus a
+//! `manifest.json` describing every key. When a notary key is available, `manifest.sig.json`
+//! carries its signature over the exact manifest bytes so the snapshot can be verified offline.
+
+use crate::bundle::NotarySignature;
+use crate::canonicalize::canonicalize_value;
+use crate::deadline::Deadline;
+use crate::key_verification::decode_public_key;
+use crate::models::{KeyManagementError, KeyPair};
+use crate::utils::public_key_to_fingerprint;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufWriter, Write};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use uuid::Uuid;
+
+/// Schema identifier carried in every manifest
+pub const MANIFEST_SCHEMA: &str = "inkan-public-key-export";
+/// Current manifest schema version
+pub const MANIFEST_VERSION: u32 = 1;
+/// Domain tag prefixed to the manifest bytes before the notary signs them
+pub const MANIFEST_CONTEXT: &[u8] = b"inkan-export-manifest-v1";
+pub const MANIFEST_FILE: &str = "manifest.json";
+pub const MANIFEST_SIGNATURE_FILE: &str = "manifest.sig.json";
+
+/// DER prefix of an Ed25519 SubjectPublicKeyInfo, followed by the 32 raw key bytes
+pub const ED25519_SPKI_PREFIX: [u8; 12] = [0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00];
+
+/// Archive container for an export
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub enum ArchiveFormat {
+    #[default]
+    #[serde(rename = "zip")]
+    Zip,
+    #[serde(rename = "tar.gz")]
+    TarGz,
+}
+
+impl ArchiveFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Zip => "application/zip",
+            Self::TarGz => "application/gzip",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Zip => "zip",
+            Self::TarGz => "tar.gz",
+        }
+    }
+}
+
+/// Encoding of the per-key files in an export
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyFileEncoding {
+    /// PEM encoded SubjectPublicKeyInfo, as read by OpenSSL
+    Pem,
+    /// JSON Web Key (RFC 8037 `OKP`)
+    Jwk,
+}
+
+impl KeyFileEncoding {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Pem => "pem",
+            Self::Jwk => "jwk",
+        }
+    }
+
+    fn encode(&self, public_key: &VerifyingKey, key_id: Uuid) -> Vec<u8> {
+        match self {
+            Self::Pem => encode_pem(public_key).into_bytes(),
+            Self::Jwk => encode_jwk(public_key, key_id).to_string().into_bytes(),
+        }
+    }
+}
+
+/// Parses a comma-separated list of key file encodings such as `pem,jwk`
+pub fn parse_encodings(list: &str) -> Result<Vec<KeyFileEncoding>, KeyManagementError> {
+    let mut encodings = Vec::new();
+    for name in list.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+        let encoding = match name {
+            "pem" => KeyFileEncoding::Pem,
+            "jwk" => KeyFileEncoding::Jwk,
+            other => return Err(KeyManagementError::ValidationFailed(format!("Unknown key file encoding '{}'", other))),
+        };
+        if !encodings.contains(&encoding) {
+            encodings.push(encoding);
+        }
+    }
+    if encodings.is_empty() {
+        return Err(KeyManagementError::ValidationFailed("At least one key file encoding is required".to_string()));
+    }
+    Ok(encodings)
+}
+
+/// Encodes a public key as a PEM SubjectPublicKeyInfo
+pub fn encode_pem(public_key: &VerifyingKey) -> String {
+    let mut der = ED25519_SPKI_PREFIX.to_vec();
+    der.extend_from_slice(public_key.as_bytes());
+    format!(
+        "-----BEGIN PUBLIC KEY-----\n{}\n-----END PUBLIC KEY-----\n",
+        base64::engine::general_purpose::STANDARD.encode(der)
+    )
+}
+
+/// Encodes a public key as a JSON Web Key with the key id as `kid`
+pub fn encode_jwk(public_key: &VerifyingKey, key_id: Uuid) -> serde_json::Value {
+    serde_json::json!({
+        "kty": "OKP",
+        "crv": "Ed25519",
+        "x": base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(public_key.as_bytes()),
+        "kid": key_id,
+        "use": "sig",
+        "alg": "EdDSA",
+    })
+}
+
+/// Description of one exported key
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ManifestEntry {
+    pub key_id: Uuid,
+    pub name: String,
+    pub fingerprint: String,
+    pub public_key: String, // Base64 encoded
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub is_active: bool,
+    /// Archive paths of the key's files
+    pub files: Vec<String>,
+}
+
+/// Index of an export archive
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ExportManifest {
+    pub schema: String,
+    pub version: u32,
+    pub generated_at: DateTime<Utc>,
+    pub keys: Vec<ManifestEntry>,
+}
+
+/// File placed in an export archive
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportFile {
+    pub path: String,
+    pub content: Vec<u8>,
+}
+
+fn manifest_message(manifest: &[u8]) -> Vec<u8> {
+    let mut message = MANIFEST_CONTEXT.to_vec();
+    message.push(0);
+    message.extend_from_slice(manifest);
+    message
+}
+
+/// Builds the files of an export: the key files followed by the manifest and its signature
+///
+/// The manifest is written in RFC 8785 canonical form and signed as written, so verifiers check
+/// the file bytes directly. Stops once `deadline` passes.
+pub fn build_export(
+    keys: &[KeyPair],
+    encodings: &[KeyFileEncoding],
+    generated_at: DateTime<Utc>,
+    notary: Option<(Uuid, &SigningKey)>,
+    deadline: &Deadline,
+) -> Result<Vec<ExportFile>, KeyManagementError> {
+    let mut files = Vec::new();
+    let mut entries = Vec::new();
+
+    for (index, key_pair) in keys.iter().enumerate() {
+        deadline.check(index, keys.len())?;
+        let public_key = decode_public_key(&key_pair.public_key)?;
+        let fingerprint = public_key_to_fingerprint(&key_pair.public_key).map_err(KeyManagementError::InvalidKeyFormat)?;
+        let file_stem = format!("keys/{}", fingerprint.replace(':', ""));
+
+        let mut paths = Vec::new();
+        for encoding in encodings {
+            let path = format!("{}.{}", file_stem, encoding.extension());
+            files.push(ExportFile { path: path.clone(), content: encoding.encode(&public_key, key_pair.id) });
+            paths.push(path);
+        }
+
+        entries.push(ManifestEntry {
+            key_id: key_pair.id,
+            name: key_pair.name.clone(),
+            fingerprint,
+            public_key: key_pair.public_key.clone(),
+            created_at: key_pair.created_at,
+            expires_at: key_pair.expires_at,
+            is_active: key_pair.state(generated_at).is_usable(),
+            files: paths,
+        });
+    }
+
+    let manifest = ExportManifest {
+        schema: MANIFEST_SCHEMA.to_string(),
+        version: MANIFEST_VERSION,
+        generated_at,
+        keys: entries,
+    };
+    let value = serde_json::to_value(&manifest)
+        .map_err(|e| KeyManagementError::InternalError(format!("Failed to serialize export manifest: {}", e)))?;
+    let manifest_bytes = canonicalize_value(&value)?.into_bytes();
+
+    if let Some((notary_key_id, notary_key)) = notary {
+        let signature = NotarySignature {
+            key_id: notary_key_id,
+            public_key: base64::engine::general_purpose::STANDARD.encode(notary_key.verifying_key().to_bytes()),
+            signature: base64::engine::general_purpose::STANDARD.encode(notary_key.sign(&manifest_message(&manifest_bytes)).to_bytes()),
+        };
+        let content = serde_json::to_vec_pretty(&signature)
+            .map_err(|e| KeyManagementError::InternalError(format!("Failed to serialize manifest signature: {}", e)))?;
+        files.push(ExportFile { path: MANIFEST_FILE.to_string(), content: manifest_bytes });
+        files.push(ExportFile { path: MANIFEST_SIGNATURE_FILE.to_string(), content });
+    } else {
+        files.push(ExportFile { path: MANIFEST_FILE.to_string(), content: manifest_bytes });
+    }
+
+    Ok(files)
+}
+
+/// Verifies the notary signature over the raw bytes of `manifest.json`
+pub fn verify_manifest_signature(manifest: &[u8], signature: &NotarySignature) -> Result<(), KeyManagementError> {
+    let invalid = || KeyManagementError::SignatureVerificationFailed("Manifest signature is invalid".to_string());
+    let notary = decode_public_key(&signature.public_key)?;
+    let signature = base64::engine::general_purpose::STANDARD.decode(&signature.signature)
+        .ok()
+        .and_then(|bytes| <[u8; 64]>::try_from(bytes.as_slice()).ok())
+        .map(|bytes| Signature::from_bytes(&bytes))
+        .ok_or_else(invalid)?;
+    notary.verify(&manifest_message(manifest), &signature).map_err(|_| invalid())
+}
+
+/// Writes the files as an archive in the given format
+pub fn write_archive<W: Write>(format: ArchiveFormat, files: &[ExportFile], generated_at: DateTime<Utc>, writer: W) -> io::Result<()> {
+    match format {
+        ArchiveFormat::Zip => {
+            let mut zip = zip::ZipWriter::new_stream(writer);
+            let options = zip::write::SimpleFileOptions::default()
+                .compression_method(zip::CompressionMethod::Deflated)
+                .unix_permissions(0o644);
+            for file in files {
+                zip.start_file(file.path.as_str(), options).map_err(io::Error::other)?;
+                zip.write_all(&file.content)?;
+            }
+            zip.finish().map_err(io::Error::other)?;
+        }
+        ArchiveFormat::TarGz => {
+            let gzip = flate2::write::GzEncoder::new(writer, flate2::Compression::default());
+            let mut tar = tar::Builder::new(gzip);
+            for file in files {
+                let mut header = tar::Header::new_gnu();
+                header.set_size(file.content.len() as u64);
+                header.set_mode(0o644);
+                header.set_mtime(generated_at.timestamp().max(0) as u64);
+                header.set_cksum();
+                tar.append_data(&mut header, &file.path, file.content.as_slice())?;
+            }
+            tar.into_inner()?.finish()?;
+        }
+    }
+    Ok(())
+}
+
+/// Forwards written bytes to an async receiver, failing once the receiver is gone
+struct ChannelWriter(mpsc::Sender<io::Result<Vec<u8>>>);
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.blocking_send(Ok(buf.to_vec()))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "export stream closed"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Streams the archive as it is written, from a blocking task, in chunks of up to 64 KiB
+///
+/// A failure while writing ends the stream with the error.
+pub fn stream_archive(format: ArchiveFormat, files: Vec<ExportFile>, generated_at: DateTime<Utc>) -> ReceiverStream<io::Result<Vec<u8>>> {
+    let (sender, receiver) = mpsc::channel(4);
+    tokio::task::spawn_blocking(move || {
+        let mut writer = BufWriter::with_capacity(64 * 1024, ChannelWriter(sender.clone()));
+        let result = write_archive(format, &files, generated_at, &mut writer).and_then(|()| writer.flush());
+        if let Err(e) = result {
+            let _ = sender.blocking_send(Err(e));
+        }
+    });
+    ReceiverStream::new(receiver)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pem_is_standard_spki() {
+        let signing_key = SigningKey::from_bytes(&[7u8; 32]);
+        let pem = encode_pem(&signing_key.verifying_key());
+        let body: String = pem.lines().filter(|line| !line.starts_with("-----")).collect();
+        let der = base64::engine::general_purpose::STANDARD.decode(body).unwrap();
+        assert_eq!(der.len(), 44);
+        assert_eq!(&der[..12], &ED25519_SPKI_PREFIX);
+        assert_eq!(&der[12..], signing_key.verifying_key().as_bytes());
+
+        assert!(parse_encodings("pem, jwk,pem").unwrap() == vec![KeyFileEncoding::Pem, KeyFileEncoding::Jwk]);
+        assert!(parse_encodings("der").is_err());
+        assert!(parse_encodings("").is_err());
+    }
+
+    #[test]
+    fn test_jwk_of_fixed_key() {
+        let key_pair = crate::key_generation::generate_seeded_test_key_pair("JWK Key", 0);
+        let public_key = crate::key_verification::decode_public_key(&key_pair.public_key).unwrap();
+        assert_eq!(encode_jwk(&public_key, key_pair.id), serde_json::json!({
+            "kty": "OKP",
+            "crv": "Ed25519",
+            "x": "7dD23jQqHmpyNtYkTyPYPu387NBZo4bIUFVwFJjncDM",
+            "kid": key_pair.id,
+            "use": "sig",
+            "alg": "EdDSA",
+        }));
+    }
+}
@@ -0,0 +1,174 @@
+//! Failure injection for tests of error paths
+//!
+//! Storage writes and the encrypt, decrypt and sign primitives pass through named failpoints. A
+//! test arms a failpoint through a [`FailScenario`] to make the Nth write fail, slow a write
+//! down, or hand corrupt bytes to a primitive, and checks that the service reports and recovers
+//! from the failure the way it should.
+//!
+//! This module and every failpoint site are compiled only into test builds and builds with the
+//! `failpoints` feature; other builds carry no trace of them.
+//!
+//! Failpoints are armed per thread, so tests running in parallel never hit each other's. A
+//! `#[tokio::test]` runs its futures on its own thread, which covers everything but work handed
+//! to `spawn_blocking` or another runtime.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Writes of the keystore file
+pub const KEYSTORE_WRITE: &str = "keystore.write";
+/// Encryption of a private key under a password
+pub const KEY_ENCRYPT: &str = "crypto.encrypt";
+/// Decryption of a stored private key; corrupting it damages the stored ciphertext
+pub const KEY_DECRYPT: &str = "crypto.decrypt";
+/// Raw Ed25519 signatures made with a software key
+pub const SIGN: &str = "crypto.sign";
+
+/// What an armed failpoint does when it is hit
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FailAction {
+    /// Every hit fails
+    Fail,
+    /// Only the Nth hit after arming fails, counting from 1
+    FailNth(u32),
+    /// Every hit waits this long, then carries on
+    Delay(Duration),
+    /// Every hit corrupts the bytes passing through; sites without bytes carry on
+    Corrupt,
+}
+
+/// Failure reported by a failpoint
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("injected failure at {0}")]
+pub struct InjectedFault(pub &'static str);
+
+#[derive(Debug)]
+struct Armed {
+    action: FailAction,
+    hits: u32,
+}
+
+thread_local! {
+    static ARMED: RefCell<HashMap<&'static str, Armed>> = RefCell::new(HashMap::new());
+}
+
+/// Failpoints armed for one test, disarmed when it is dropped
+#[derive(Debug)]
+pub struct FailScenario {
+    _thread_bound: std::marker::PhantomData<*const ()>,
+}
+
+impl FailScenario {
+    /// Starts a scenario with every failpoint on this thread disarmed
+    pub fn new() -> Self {
+        ARMED.with(|armed| armed.borrow_mut().clear());
+        Self { _thread_bound: std::marker::PhantomData }
+    }
+
+    /// Arms the failpoint `name`, replacing what it did before and resetting its hits
+    pub fn arm(&self, name: &'static str, action: FailAction) {
+        ARMED.with(|armed| armed.borrow_mut().insert(name, Armed { action, hits: 0 }));
+    }
+
+    /// Disarms the failpoint `name`
+    pub fn disarm(&self, name: &'static str) {
+        ARMED.with(|armed| armed.borrow_mut().remove(name));
+    }
+
+    /// Times the failpoint `name` was hit since it was armed
+    pub fn hits(&self, name: &'static str) -> u32 {
+        ARMED.with(|armed| armed.borrow().get(name).map_or(0, |armed| armed.hits))
+    }
+}
+
+impl Default for FailScenario {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for FailScenario {
+    fn drop(&mut self) {
+        ARMED.with(|armed| armed.borrow_mut().clear());
+    }
+}
+
+/// Counts a hit of `name`, returning the action it takes this time
+fn hit(name: &'static str) -> Option<FailAction> {
+    ARMED.with(|armed| {
+        let mut armed = armed.borrow_mut();
+        let point = armed.get_mut(name)?;
+        point.hits += 1;
+        match point.action {
+            FailAction::FailNth(n) if point.hits != n => None,
+            FailAction::FailNth(_) => Some(FailAction::Fail),
+            action => Some(action),
+        }
+    })
+}
+
+/// Passes a site without bytes through the failpoint `name`, sleeping out any delay
+pub fn fault(name: &'static str) -> Result<(), InjectedFault> {
+    match hit(name) {
+        Some(FailAction::Fail) => Err(InjectedFault(name)),
+        Some(FailAction::Delay(delay)) => {
+            std::thread::sleep(delay);
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Passes an I/O site through the failpoint `name` without blocking the runtime on a delay
+pub async fn io_fault(name: &'static str) -> std::io::Result<()> {
+    match hit(name) {
+        Some(FailAction::Fail) => Err(std::io::Error::other(InjectedFault(name))),
+        Some(FailAction::Delay(delay)) => {
+            tokio::time::sleep(delay).await;
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Passes `bytes` through the failpoint `name`, flipping the bits of the last byte when it
+/// corrupts
+pub fn corrupt(name: &'static str, mut bytes: Vec<u8>) -> Result<Vec<u8>, InjectedFault> {
+    match hit(name) {
+        Some(FailAction::Fail) => Err(InjectedFault(name)),
+        Some(FailAction::Delay(delay)) => {
+            std::thread::sleep(delay);
+            Ok(bytes)
+        }
+        Some(FailAction::Corrupt) => {
+            if let Some(last) = bytes.last_mut() {
+                *last = !*last;
+            }
+            Ok(bytes)
+        }
+        Some(FailAction::FailNth(_)) | None => Ok(bytes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failpoints_fire_as_armed_and_only_on_their_thread() {
+        let scenario = FailScenario::new();
+        scenario.arm(SIGN, FailAction::FailNth(2));
+        assert_eq!((fault(SIGN), fault(SIGN), fault(SIGN)), (Ok(()), Err(InjectedFault(SIGN)), Ok(())));
+        assert_eq!(scenario.hits(SIGN), 3);
+        assert_eq!(fault(KEY_ENCRYPT), Ok(()));
+
+        scenario.arm(KEY_DECRYPT, FailAction::Corrupt);
+        assert_eq!(corrupt(KEY_DECRYPT, vec![1, 0x0f]), Ok(vec![1, 0xf0]));
+        assert!(std::thread::spawn(|| fault(SIGN).is_ok() && corrupt(KEY_DECRYPT, vec![1]) == Ok(vec![1])).join().unwrap());
+
+        scenario.arm(KEYSTORE_WRITE, FailAction::Fail);
+        drop(scenario);
+        assert_eq!(fault(KEYSTORE_WRITE), Ok(()));
+    }
+}
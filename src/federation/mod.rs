@@ -0,0 +1,285 @@
+//! Read-through resolution of keys held by partner instances
+//!
+//! A verification may name a key that another instance of this service holds. With
+//! `INKAN_FEDERATION_PEERS` set, `/verify` by a `key_id` this instance does not hold asks each
+//! peer in turn for the key's [status](crate::key_status), then for its public key from
+//! `/public/:fingerprint`. Both answer any caller, and the status reports revoked and expired
+//! keys as such instead of hiding them, so a revocation at the peer shows once the cached copy
+//! expires.
+//!
+//! Resolved keys are cached for `INKAN_FEDERATION_CACHE_TTL_SECS`. Lookups that reach the peers
+//! are limited to `INKAN_FEDERATION_LOOKUPS_PER_MINUTE` across all callers, and each is logged
+//! under the `inkan::federation` target. A peer that fails, does not answer in time, or answers
+//! with something malformed counts as not holding the key, so the caller gets the usual unknown
+//! key answer rather than an error.
+
+use crate::config::FederationConfig;
+use crate::key_status::KeyStatus;
+use crate::models::{KeyManagementError, KeyState};
+use crate::rate_limit::RequestCounter;
+use crate::utils::public_key_to_fingerprint;
+use async_trait::async_trait;
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+/// Bucket remote lookups are counted in
+const LOOKUP_BUCKET: &str = "federation:lookups";
+/// Most resolved keys cached at once; expired entries are dropped first when it is reached
+pub const MAX_CACHED_REMOTE_KEYS: usize = 10_000;
+
+/// A partner instance keys may be resolved from
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct FederationPeer {
+    pub name: String, // Reported in `key_source` as `remote(<name>)`
+    pub base_url: String, // Versioned API root, e.g. `https://keys.partner.example/v1`
+}
+
+impl FederationPeer {
+    /// Parses comma-separated `name=url` pairs
+    pub fn parse_list(value: &str) -> Result<Vec<FederationPeer>, KeyManagementError> {
+        let mut peers: Vec<FederationPeer> = Vec::new();
+        for entry in value.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let invalid = |reason: &str| KeyManagementError::ValidationFailed(format!("INKAN_FEDERATION_PEERS entry {:?} {}", entry, reason));
+            let (name, base_url) = entry.split_once('=').ok_or_else(|| invalid("is not name=url"))?;
+            let (name, base_url) = (name.trim(), base_url.trim().trim_end_matches('/'));
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')) {
+                return Err(invalid("needs a name of letters, digits, '-', '_' or '.'"));
+            }
+            if !(base_url.starts_with("https://") || base_url.starts_with("http://")) {
+                return Err(invalid("needs an http or https URL"));
+            }
+            if peers.iter().any(|peer| peer.name == name) {
+                return Err(invalid("repeats a peer name"));
+            }
+            peers.push(FederationPeer { name: name.to_string(), base_url: base_url.to_string() });
+        }
+        Ok(peers)
+    }
+}
+
+/// A key resolved from a peer
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteKey {
+    pub peer: String,
+    pub status: KeyStatus, // As the peer reported it when the key was resolved
+    pub public_key: String, // Base64, as stored keys hold it
+}
+
+impl RemoteKey {
+    /// Where the key came from, as reported in `key_source`
+    pub fn source(&self) -> String {
+        format!("remote({})", self.peer)
+    }
+}
+
+/// Fetches documents from peers
+#[async_trait]
+pub trait PeerTransport: Send + Sync {
+    /// Status code and body of a GET of `url`
+    async fn get(&self, url: &str) -> Result<(u16, Vec<u8>), KeyManagementError>;
+}
+
+/// Transport of a resolver without peers, which is never asked for anything
+struct NoTransport;
+
+#[async_trait]
+impl PeerTransport for NoTransport {
+    async fn get(&self, url: &str) -> Result<(u16, Vec<u8>), KeyManagementError> {
+        Err(KeyManagementError::InternalError(format!("No federation transport to fetch {}", url)))
+    }
+}
+
+/// Fetches over HTTP
+#[cfg(feature = "federation")]
+pub struct HttpTransport {
+    client: reqwest::Client,
+}
+
+#[cfg(feature = "federation")]
+impl HttpTransport {
+    pub fn new() -> Self {
+        Self { client: reqwest::Client::new() }
+    }
+}
+
+#[cfg(feature = "federation")]
+impl Default for HttpTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "federation")]
+#[async_trait]
+impl PeerTransport for HttpTransport {
+    async fn get(&self, url: &str) -> Result<(u16, Vec<u8>), KeyManagementError> {
+        let response = self.client.get(url)
+            .send()
+            .await
+            .map_err(|e| KeyManagementError::InternalError(format!("Request to {} failed: {}", url, e)))?;
+        let status = response.status().as_u16();
+        let body = response.bytes()
+            .await
+            .map_err(|e| KeyManagementError::InternalError(format!("Reading the answer of {} failed: {}", url, e)))?;
+        Ok((status, body.to_vec()))
+    }
+}
+
+/// Resolves keys this instance does not hold from the configured peers
+pub struct KeyResolver {
+    peers: Vec<FederationPeer>,
+    transport: Arc<dyn PeerTransport>,
+    timeout: std::time::Duration,
+    cache_ttl: Duration,
+    lookups_per_minute: u32,
+    lookups: RequestCounter,
+    cache: Mutex<HashMap<Uuid, (RemoteKey, DateTime<Utc>)>>,
+}
+
+impl KeyResolver {
+    /// Resolves from the peers of `config` through `transport`
+    pub fn new(transport: Arc<dyn PeerTransport>, config: &FederationConfig) -> Self {
+        Self {
+            peers: config.peers.clone(),
+            transport,
+            timeout: std::time::Duration::from_millis(config.timeout_ms.into()),
+            cache_ttl: Duration::seconds(config.cache_ttl_secs.into()),
+            lookups_per_minute: config.lookups_per_minute,
+            lookups: RequestCounter::in_memory(),
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Resolver without peers, which resolves nothing
+    pub fn none() -> Self {
+        Self::new(Arc::new(NoTransport), &FederationConfig::default())
+    }
+
+    /// Resolver for the peers in configuration
+    ///
+    /// Fails if peers are configured but the crate was built without the federation feature.
+    pub fn from_config(config: &FederationConfig) -> Result<Self, KeyManagementError> {
+        if config.peers.is_empty() {
+            return Ok(Self::none());
+        }
+        #[cfg(feature = "federation")]
+        return Ok(Self::new(Arc::new(HttpTransport::new()), config));
+        #[cfg(not(feature = "federation"))]
+        Err(KeyManagementError::ValidationFailed(
+            "INKAN_FEDERATION_PEERS is set but the service was built without the federation feature".to_string(),
+        ))
+    }
+
+    /// Counts remote lookups in `lookups`, so that replicas sharing it share the limit
+    pub fn with_request_counter(mut self, lookups: RequestCounter) -> Self {
+        self.lookups = lookups;
+        self
+    }
+
+    pub fn peers(&self) -> &[FederationPeer] {
+        &self.peers
+    }
+
+    /// The key `key_id` as the first peer holding it reports it, if any does
+    ///
+    /// A copy resolved within the cache TTL is returned without asking the peers. Keys deleted
+    /// at the peer count as not held.
+    pub async fn resolve(&self, key_id: Uuid, now: DateTime<Utc>) -> Option<RemoteKey> {
+        if self.peers.is_empty() {
+            return None;
+        }
+        let cached = self.cache.lock().unwrap()
+            .get(&key_id)
+            .filter(|(_, expires_at)| *expires_at > now)
+            .map(|(remote, _)| remote.clone());
+        if cached.is_some() {
+            return cached;
+        }
+        if self.lookups_per_minute > 0 && self.lookups.take(LOOKUP_BUCKET, self.lookups_per_minute, Duration::minutes(1), now).await.is_err() {
+            tracing::warn!(target: "inkan::federation", "Remote lookup of key {} refused: more than {} lookups per minute", key_id, self.lookups_per_minute);
+            return None;
+        }
+
+        for peer in &self.peers {
+            match self.lookup(peer, key_id).await {
+                Ok(Some(remote)) => {
+                    tracing::info!(target: "inkan::federation", "Resolved key {} from peer {}: {}", key_id, peer.name, remote.status.state.as_str());
+                    self.remember(&remote, now);
+                    return Some(remote);
+                }
+                Ok(None) => tracing::info!(target: "inkan::federation", "Peer {} does not hold key {}", peer.name, key_id),
+                Err(e) => tracing::warn!(target: "inkan::federation", "Lookup of key {} at peer {} failed: {}", key_id, peer.name, e),
+            }
+        }
+        None
+    }
+
+    /// Asks one peer for the key's status and then its public key
+    async fn lookup(&self, peer: &FederationPeer, key_id: Uuid) -> Result<Option<RemoteKey>, KeyManagementError> {
+        let malformed = |what: &str| KeyManagementError::InternalError(format!("Peer {} answered with a malformed {}", peer.name, what));
+        let status = match self.get(peer, &format!("/keys/{}/status", key_id)).await? {
+            (200, body) => serde_json::from_slice::<KeyStatus>(&body).map_err(|_| malformed("key status"))?,
+            (404, _) => return Ok(None),
+            (code, _) => return Err(KeyManagementError::InternalError(format!("Peer {} answered {} for the key status", peer.name, code))),
+        };
+        if status.key_id != key_id {
+            return Err(malformed("key status"));
+        }
+        if status.state == KeyState::Deleted {
+            return Ok(None);
+        }
+
+        let public_key = match self.get(peer, &format!("/public/{}.raw", status.fingerprint.replace(':', ""))).await? {
+            (200, body) if body.len() == 32 => base64::engine::general_purpose::STANDARD.encode(body),
+            (200, _) => return Err(malformed("public key")),
+            (code, _) => return Err(KeyManagementError::InternalError(format!("Peer {} answered {} for the public key", peer.name, code))),
+        };
+        if public_key_to_fingerprint(&public_key).ok().as_ref() != Some(&status.fingerprint) {
+            return Err(KeyManagementError::InternalError(format!("Public key from peer {} does not match the fingerprint in its status", peer.name)));
+        }
+        Ok(Some(RemoteKey { peer: peer.name.clone(), status, public_key }))
+    }
+
+    async fn get(&self, peer: &FederationPeer, path: &str) -> Result<(u16, Vec<u8>), KeyManagementError> {
+        let url = format!("{}{}", peer.base_url, path);
+        tokio::time::timeout(self.timeout, self.transport.get(&url))
+            .await
+            .map_err(|_| KeyManagementError::InternalError(format!("No answer from {} within {} ms", url, self.timeout.as_millis())))?
+    }
+
+    fn remember(&self, remote: &RemoteKey, now: DateTime<Utc>) {
+        if self.cache_ttl <= Duration::zero() {
+            return;
+        }
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() >= MAX_CACHED_REMOTE_KEYS {
+            cache.retain(|_, (_, expires_at)| *expires_at > now);
+            if cache.len() >= MAX_CACHED_REMOTE_KEYS {
+                cache.clear();
+            }
+        }
+        cache.insert(remote.status.key_id, (remote.clone(), now + self.cache_ttl));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_peer_list_parses_and_rejects_bad_entries() {
+        let peers = FederationPeer::parse_list(" partner-eu=https://keys.eu.example/v1/ , lab=http://10.0.0.5:3002/v1").unwrap();
+        assert_eq!(peers, [
+            FederationPeer { name: "partner-eu".to_string(), base_url: "https://keys.eu.example/v1".to_string() },
+            FederationPeer { name: "lab".to_string(), base_url: "http://10.0.0.5:3002/v1".to_string() },
+        ]);
+        assert!(FederationPeer::parse_list("").unwrap().is_empty());
+        for bad in ["https://keys.example/v1", "partner=ftp://keys.example", "a b=https://x", "p=https://a,p=https://b"] {
+            assert!(matches!(FederationPeer::parse_list(bad), Err(KeyManagementError::ValidationFailed(_))), "{}", bad);
+        }
+    }
+}
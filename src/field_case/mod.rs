@@ -0,0 +1,120 @@
+//! Response field casing
+//!
+//! Models serialize with snake_case field names. Clients that prefer camelCase ask for it with
+//! the `X-Field-Case` header (or the service default), and responses are rewritten on the way
+//! out rather than keeping a second set of models. Only object keys change; values, including
+//! enum variants such as `KeyType` and `KeyStrength`, are left as they are. Signed structures
+//! (bundles and certifications) keep their original field names so they still verify.
+//! Requests accept both casings through serde aliases on the request types.
+
+use serde::Serialize;
+use serde_json::Value;
+
+/// Header selecting the casing of response field names
+pub const FIELD_CASE_HEADER: &str = "x-field-case";
+
+/// Fields whose values are signed over their snake_case form and are never rewritten
+pub const PRESERVED_FIELDS: &[&str] = &["bundle", "certification", "certification_chain", "issued", "received"];
+
+/// Casing of JSON field names in responses
+#[derive(Debug, Clone, Copy, Default, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldCase {
+    #[default]
+    Snake,
+    Camel,
+}
+
+impl FieldCase {
+    /// Parses `snake` or `camel`, ignoring case and surrounding whitespace
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "snake" => Some(Self::Snake),
+            "camel" => Some(Self::Camel),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Snake => "snake",
+            Self::Camel => "camel",
+        }
+    }
+}
+
+/// Marker placed in response extensions by handlers whose whole body must not be rewritten
+#[derive(Debug, Clone, Copy)]
+pub struct PreserveFieldCase;
+
+/// Converts a snake_case name to camelCase
+pub fn to_camel_case(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut upper = false;
+    for c in name.chars() {
+        if c == '_' && !out.is_empty() {
+            upper = true;
+        } else if upper {
+            out.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Rewrites every object key in `value` to `case`, leaving preserved fields' values untouched
+pub fn apply_field_case(value: Value, case: FieldCase) -> Value {
+    match (case, value) {
+        (FieldCase::Snake, value) => value,
+        (FieldCase::Camel, Value::Object(object)) => Value::Object(
+            object.into_iter()
+                .map(|(key, value)| {
+                    let value = if PRESERVED_FIELDS.contains(&key.as_str()) { value } else { apply_field_case(value, case) };
+                    (to_camel_case(&key), value)
+                })
+                .collect(),
+        ),
+        (FieldCase::Camel, Value::Array(items)) => {
+            Value::Array(items.into_iter().map(|item| apply_field_case(item, case)).collect())
+        }
+        (FieldCase::Camel, value) => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{KeyInfo, KeyStrength, KeyType};
+    use crate::key_generation::generate_test_key_pair;
+    use chrono::Utc;
+
+    #[test]
+    fn test_camel_case_rewrites_keys_but_not_values() {
+        assert_eq!(to_camel_case("document_hash"), "documentHash");
+        assert_eq!(to_camel_case("old_key_info"), "oldKeyInfo");
+        assert_eq!(to_camel_case("id"), "id");
+
+        let mut key_pair = generate_test_key_pair("Casing Key").unwrap();
+        key_pair.key_type = KeyType::Ed25519Encrypted;
+        key_pair.key_strength = KeyStrength::High;
+        key_pair.tags = vec!["team_a".to_string()];
+        let info = KeyInfo::new(key_pair, Utc::now());
+
+        let camel = apply_field_case(serde_json::to_value(&info).unwrap(), FieldCase::Camel);
+        assert_eq!(camel["keyType"], "Ed25519Encrypted");
+        assert_eq!(camel["keyStrength"], "High");
+        assert_eq!(camel["publicKey"], info.public_key);
+        assert_eq!(camel["usage"]["signCount"], 0);
+        assert_eq!(camel["tags"][0], "team_a");
+        assert!(camel.get("key_type").is_none());
+
+        let snake = apply_field_case(serde_json::to_value(&info).unwrap(), FieldCase::Snake);
+        assert_eq!(snake, serde_json::to_value(&info).unwrap());
+
+        let preserved = serde_json::json!({ "signature_id": 1, "bundle": { "key_id": 2 } });
+        let rewritten = apply_field_case(preserved, FieldCase::Camel);
+        assert_eq!(rewritten, serde_json::json!({ "signatureId": 1, "bundle": { "key_id": 2 } }));
+    }
+}
@@ -0,0 +1,190 @@
+//! Signed manifests of file sets
+//!
+//! A release is a set of files, and signing each one alone does not prove which files belong
+//! together. A manifest lists every file by path and SHA-256 digest, sorted by path, and is
+//! signed as a whole: the document hash is the SHA-256 of its RFC 8785 canonical form, so the
+//! same set of files always yields the same hash however the entries were ordered or formatted.
+
+use crate::canonicalize::canonicalize_value;
+use crate::key_verification::create_document_hash;
+use crate::models::KeyManagementError;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Schema identifier carried in every file manifest
+pub const FILE_MANIFEST_SCHEMA: &str = "inkan-file-manifest";
+/// Current file manifest schema version
+pub const FILE_MANIFEST_VERSION: u32 = 1;
+/// Most files one manifest may list
+pub const MAX_MANIFEST_FILES: usize = 10_000;
+/// Longest path one manifest entry may have
+pub const MAX_MANIFEST_PATH_LENGTH: usize = 1_024;
+
+/// One file of a manifest
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct FileEntry {
+    pub path: String,
+    pub sha256: String, // Hex encoded digest of the file's contents
+}
+
+/// A set of files, as signed
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct FileManifest {
+    pub schema: String,
+    pub version: u32,
+    pub files: Vec<FileEntry>,
+}
+
+/// How one file compares with its manifest entry
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FileStatus {
+    Match,
+    Mismatch,
+}
+
+/// Outcome for one file listed in both the manifest and the recomputed hashes
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FileCheck {
+    pub path: String,
+    pub status: FileStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected_sha256: Option<String>, // Set on mismatches
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actual_sha256: Option<String>, // Set on mismatches
+}
+
+/// Recomputed file hashes checked against a manifest
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct FileComparison {
+    pub files: Vec<FileCheck>,
+    /// Listed in the manifest but not among the recomputed hashes
+    pub missing: Vec<String>,
+    /// Among the recomputed hashes but not listed in the manifest
+    pub extra: Vec<String>,
+}
+
+impl FileComparison {
+    /// Every listed file is present with its listed hash, and nothing else is
+    pub fn all_match(&self) -> bool {
+        self.missing.is_empty() && self.extra.is_empty() && self.files.iter().all(|file| file.status == FileStatus::Match)
+    }
+}
+
+/// Checks entries and returns them sorted by path with lowercase digests
+fn normalize_entries(files: Vec<FileEntry>) -> Result<Vec<FileEntry>, KeyManagementError> {
+    let invalid = |message: String| KeyManagementError::ValidationFailed(message);
+    if files.len() > MAX_MANIFEST_FILES {
+        return Err(invalid(format!("A manifest may list at most {} files", MAX_MANIFEST_FILES)));
+    }
+    let mut by_path = BTreeMap::new();
+    for file in files {
+        if file.path.is_empty() || file.path.len() > MAX_MANIFEST_PATH_LENGTH || file.path.chars().any(char::is_control) {
+            return Err(invalid(format!(
+                "File paths must be 1 to {} bytes without control characters", MAX_MANIFEST_PATH_LENGTH,
+            )));
+        }
+        let sha256 = file.sha256.to_ascii_lowercase();
+        if sha256.len() != 64 || !sha256.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(invalid(format!("sha256 of '{}' must be 64 hex characters", file.path)));
+        }
+        if by_path.insert(file.path.clone(), sha256).is_some() {
+            return Err(invalid(format!("'{}' is listed more than once", file.path)));
+        }
+    }
+    Ok(by_path.into_iter().map(|(path, sha256)| FileEntry { path, sha256 }).collect())
+}
+
+impl FileManifest {
+    /// Builds the manifest of `files`, which may be in any order
+    pub fn new(files: Vec<FileEntry>) -> Result<Self, KeyManagementError> {
+        if files.is_empty() {
+            return Err(KeyManagementError::ValidationFailed("A manifest must list at least one file".to_string()));
+        }
+        Ok(Self {
+            schema: FILE_MANIFEST_SCHEMA.to_string(),
+            version: FILE_MANIFEST_VERSION,
+            files: normalize_entries(files)?,
+        })
+    }
+
+    /// Rebuilds a manifest received from a client, refusing unknown schemas
+    pub fn normalized(self) -> Result<Self, KeyManagementError> {
+        if self.schema != FILE_MANIFEST_SCHEMA || self.version != FILE_MANIFEST_VERSION {
+            return Err(KeyManagementError::ValidationFailed(format!(
+                "Unsupported manifest {} version {}", self.schema, self.version,
+            )));
+        }
+        Self::new(self.files)
+    }
+
+    /// The RFC 8785 canonical form that is hashed and signed
+    pub fn canonical(&self) -> Result<String, KeyManagementError> {
+        let value = serde_json::to_value(self)
+            .map_err(|e| KeyManagementError::InternalError(format!("Failed to serialize file manifest: {}", e)))?;
+        canonicalize_value(&value)
+    }
+
+    /// The document hash a manifest signature covers
+    pub fn document_hash(&self) -> Result<String, KeyManagementError> {
+        Ok(create_document_hash(&self.canonical()?))
+    }
+
+    /// Compares recomputed hashes of the files against the manifest
+    pub fn compare(&self, actual: Vec<FileEntry>) -> Result<FileComparison, KeyManagementError> {
+        let mut actual: BTreeMap<String, String> = normalize_entries(actual)?
+            .into_iter()
+            .map(|file| (file.path, file.sha256))
+            .collect();
+        let mut comparison = FileComparison::default();
+        for expected in &self.files {
+            match actual.remove(&expected.path) {
+                Some(sha256) if sha256 == expected.sha256 => comparison.files.push(FileCheck {
+                    path: expected.path.clone(),
+                    status: FileStatus::Match,
+                    expected_sha256: None,
+                    actual_sha256: None,
+                }),
+                Some(sha256) => comparison.files.push(FileCheck {
+                    path: expected.path.clone(),
+                    status: FileStatus::Mismatch,
+                    expected_sha256: Some(expected.sha256.clone()),
+                    actual_sha256: Some(sha256),
+                }),
+                None => comparison.missing.push(expected.path.clone()),
+            }
+        }
+        comparison.extra = actual.into_keys().collect();
+        Ok(comparison)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: &str, content: &str) -> FileEntry {
+        FileEntry { path: path.to_string(), sha256: create_document_hash(content) }
+    }
+
+    #[test]
+    fn test_manifest_hash_ignores_entry_order() {
+        let forward = FileManifest::new(vec![entry("bin/inkan", "binary"), entry("README.md", "readme")]).unwrap();
+        let reversed = FileManifest::new(vec![entry("README.md", "readme"), entry("bin/inkan", "binary")]).unwrap();
+        assert_eq!(forward, reversed);
+        assert_eq!(forward.files[0].path, "README.md");
+        assert_eq!(
+            forward.canonical().unwrap(),
+            format!(
+                r#"{{"files":[{{"path":"README.md","sha256":"{}"}},{{"path":"bin/inkan","sha256":"{}"}}],"schema":"inkan-file-manifest","version":1}}"#,
+                create_document_hash("readme"), create_document_hash("binary"),
+            ),
+        );
+
+        assert!(FileManifest::new(vec![]).is_err());
+        assert!(FileManifest::new(vec![entry("a", "x"), entry("a", "y")]).is_err());
+        assert!(FileManifest::new(vec![FileEntry { path: "a".to_string(), sha256: "abc".to_string() }]).is_err());
+    }
+}
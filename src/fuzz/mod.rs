@@ -0,0 +1,113 @@
+//! Entry points shared by the fuzz targets and the corpus regression test
+//!
+//! Each function feeds raw fuzzer input to one family of parsers and promises only not to
+//! panic; errors are the expected outcome for almost every input. The cargo-fuzz targets in
+//! `fuzz/` call these with the `fuzzing` feature enabled, and `cargo test` replays every file
+//! under `fuzz/corpus/<target>/` through them, so an input that once crashed stays fixed.
+
+use crate::canonicalize::canonicalize_json;
+use crate::config::KdfParams;
+use crate::key_generation::{decrypt_private_key, EncryptedKeyEnvelope};
+use crate::key_verification::{decode_public_key, resolve_document_hash, validate_verify_input, verify_signature};
+use crate::minisign;
+use crate::models::VerifySignatureRequest;
+use crate::sshsig;
+use crate::utils::{compact_fingerprint, is_valid_base64, public_key_to_fingerprint, validate_key_pair_compatibility};
+use base64::Engine;
+
+/// Largest work factor the envelope target will actually derive with, to keep fuzzing fast
+const MAX_FUZZ_KDF_ITERATIONS: u32 = 16;
+
+/// Harness run on one fuzzer input
+pub type FuzzTarget = fn(&[u8]);
+
+/// Every target, by the name of its `fuzz/fuzz_targets` file and corpus directory
+pub const TARGETS: &[(&str, FuzzTarget)] = &[
+    ("verify_request", verify_request),
+    ("key_envelope", key_envelope),
+    ("encoding", encoding),
+    ("sshsig", sshsig_signature),
+    ("minisign", minisign_signature),
+];
+
+/// A `/verify` request body, through parsing, input checks and raw signature verification
+pub fn verify_request(data: &[u8]) {
+    let Ok(request) = serde_json::from_slice::<VerifySignatureRequest>(data) else { return };
+    if validate_verify_input(&request).is_err() {
+        return;
+    }
+    let _ = resolve_document_hash(request.document_hash.as_deref(), request.document_content.as_deref(), request.content_type);
+    let _ = verify_signature(&request);
+}
+
+/// A stored private key, through envelope parsing and, for cheap KDF parameters, decryption
+pub fn key_envelope(data: &[u8]) {
+    if let Ok(envelope) = EncryptedKeyEnvelope::parse(data) {
+        assert!(envelope.kdf.check_bounds().is_ok(), "parsed envelope exceeds KDF bounds: {:?}", envelope.kdf);
+        assert_eq!(EncryptedKeyEnvelope::parse(&envelope.to_bytes()).ok(), Some(envelope.clone()));
+        if envelope.kdf.iterations > MAX_FUZZ_KDF_ITERATIONS || envelope.kdf.memory_kib > 64 {
+            return;
+        }
+    }
+    // Legacy layouts carry no parameters of their own; use the cheapest
+    let encoded = base64::engine::general_purpose::STANDARD.encode(data);
+    let _ = decrypt_private_key(&encoded, "fuzz password", Some("c2FsdHNhbHQ="), &KdfParams::pbkdf2(1));
+}
+
+/// Text through the base64, hex and fingerprint helpers
+pub fn encoding(data: &[u8]) {
+    let Ok(text) = std::str::from_utf8(data) else { return };
+    let _ = is_valid_base64(text);
+    let _ = decode_public_key(text);
+    if let Ok(fingerprint) = public_key_to_fingerprint(text) {
+        assert!(compact_fingerprint(&fingerprint).is_some(), "fingerprint {} does not compact", fingerprint);
+    }
+    if let Some(compact) = compact_fingerprint(text) {
+        assert_eq!(compact_fingerprint(&compact).as_ref(), Some(&compact));
+    }
+    let (public_key, private_key) = text.split_once('\n').unwrap_or((text, text));
+    let _ = validate_key_pair_compatibility(public_key, private_key);
+    let _ = hex::decode(text);
+    let _ = canonicalize_json(text);
+}
+
+/// An armored SSH signature and OpenSSH public key line
+pub fn sshsig_signature(data: &[u8]) {
+    let Ok(text) = std::str::from_utf8(data) else { return };
+    let _ = sshsig::parse_public_key(text);
+    if let Ok(parsed) = sshsig::parse_signature(text) {
+        let _ = sshsig::verify(&parsed.public_key, &parsed.namespace, b"", text);
+    }
+}
+
+/// A minisign signature file and public key
+pub fn minisign_signature(data: &[u8]) {
+    let Ok(text) = std::str::from_utf8(data) else { return };
+    let (public_key, signature) = text.split_once("\n\n").unwrap_or((text, text));
+    let _ = minisign::is_minisign_public_key(public_key);
+    if let Ok((public_key, key_id)) = minisign::parse_public_key(public_key) {
+        let _ = minisign::verify(&public_key, Some(key_id), b"", signature);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn test_corpora_replay_without_panics() {
+        let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz").join("corpus");
+        for (target, run) in TARGETS {
+            let mut inputs = 0;
+            for entry in std::fs::read_dir(corpus.join(target)).unwrap() {
+                let path = entry.unwrap().path();
+                let data = std::fs::read(&path).unwrap();
+                let outcome = std::panic::catch_unwind(|| run(&data));
+                assert!(outcome.is_ok(), "{} panicked on {}", target, path.display());
+                inputs += 1;
+            }
+            assert!(inputs > 0, "no corpus for {}", target);
+        }
+    }
+}
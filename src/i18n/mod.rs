@@ -0,0 +1,515 @@
+//! Localized response messages
+//!
+//! Response `message` fields are written in English by the handlers. When a client prefers
+//! Arabic or French through `Accept-Language`, the message is replaced on the way out:
+//! error responses are looked up by their `code`, with parameters taken from `details`, and
+//! success messages are matched against the English templates below, which also recovers
+//! their parameters. Codes never change with the language. English, and anything without a
+//! template, is left exactly as the handler wrote it.
+
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Languages messages are translated into
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    En,
+    Ar,
+    Fr,
+}
+
+impl Locale {
+    /// BCP 47 tag, as sent in `Content-Language`
+    pub fn tag(self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::Ar => "ar",
+            Locale::Fr => "fr",
+        }
+    }
+
+    fn from_tag(tag: &str) -> Option<Self> {
+        let primary = tag.split(['-', '_']).next()?.trim().to_ascii_lowercase();
+        match primary.as_str() {
+            "en" => Some(Locale::En),
+            "ar" => Some(Locale::Ar),
+            "fr" => Some(Locale::Fr),
+            _ => None,
+        }
+    }
+
+    /// Picks the supported locale the client weights highest, falling back to English
+    pub fn negotiate(accept_language: &str) -> Self {
+        let mut ranges: Vec<(&str, f32)> = accept_language.split(',')
+            .filter_map(|range| {
+                let mut parts = range.split(';');
+                let tag = parts.next()?.trim();
+                let quality = parts
+                    .find_map(|param| param.trim().strip_prefix("q="))
+                    .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())?;
+                (!tag.is_empty() && quality > 0.0).then_some((tag, quality))
+            })
+            .collect();
+        // Stable, so equally weighted ranges keep the client's order
+        ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranges.into_iter()
+            .find_map(|(tag, _)| if tag == "*" { Some(Locale::En) } else { Self::from_tag(tag) })
+            .unwrap_or_default()
+    }
+}
+
+/// A message with its translations; `{name}` placeholders are filled from parameters
+struct Template {
+    key: &'static str,
+    en: &'static str,
+    ar: &'static str,
+    fr: &'static str,
+}
+
+impl Template {
+    fn text(&self, locale: Locale) -> &'static str {
+        match locale {
+            Locale::En => self.en,
+            Locale::Ar => self.ar,
+            Locale::Fr => self.fr,
+        }
+    }
+}
+
+/// Error templates keyed by error code; the first whose parameters are all available is used
+const ERROR_TEMPLATES: &[Template] = &[
+    Template { key: "KEY_NOT_FOUND", en: "Key {key_id} not found", ar: "المفتاح {key_id} غير موجود", fr: "Clé {key_id} introuvable" },
+    Template { key: "KEY_NOT_FOUND", en: "Key not found", ar: "المفتاح غير موجود", fr: "Clé introuvable" },
+    Template { key: "KEY_EXPIRED", en: "Key {key_id} has expired", ar: "انتهت صلاحية المفتاح {key_id}", fr: "La clé {key_id} a expiré" },
+    Template { key: "KEY_EXPIRED", en: "Key has expired", ar: "انتهت صلاحية المفتاح", fr: "La clé a expiré" },
+    Template { key: "KEY_REVOKED", en: "Key {key_id} has been revoked", ar: "تم إبطال المفتاح {key_id}", fr: "La clé {key_id} a été révoquée" },
+    Template { key: "KEY_REVOKED", en: "Key has been revoked", ar: "تم إبطال المفتاح", fr: "La clé a été révoquée" },
+    Template {
+        key: "KEY_ALREADY_REVOKED",
+        en: "Key '{key_name}' is already revoked",
+        ar: "المفتاح «{key_name}» مُبطَل بالفعل",
+        fr: "La clé « {key_name} » est déjà révoquée",
+    },
+    Template { key: "KEY_ALREADY_REVOKED", en: "Key is already revoked", ar: "المفتاح مُبطَل بالفعل", fr: "La clé est déjà révoquée" },
+    Template {
+        key: "NO_SCHEDULED_REVOCATION",
+        en: "Key has no pending scheduled revocation",
+        ar: "لا يوجد إبطال مجدول معلّق لهذا المفتاح",
+        fr: "La clé n'a aucune révocation planifiée en attente",
+    },
+    Template { key: "SIGNATURE_NOT_FOUND", en: "Signature not found", ar: "التوقيع غير موجود", fr: "Signature introuvable" },
+    Template { key: "INVALID_KEY_FORMAT", en: "Invalid key format", ar: "تنسيق المفتاح غير صالح", fr: "Format de clé invalide" },
+    Template { key: "INVALID_SIGNATURE_FORMAT", en: "Signature is invalid", ar: "التوقيع غير صالح", fr: "La signature est invalide" },
+    Template {
+        key: "SIGNATURE_VERIFICATION_FAILED",
+        en: "Signature verification failed",
+        ar: "فشل التحقق من التوقيع",
+        fr: "Échec de la vérification de la signature",
+    },
+    Template {
+        key: "PASSWORD_REQUIRED",
+        en: "A password is required for this key",
+        ar: "كلمة المرور مطلوبة لهذا المفتاح",
+        fr: "Un mot de passe est requis pour cette clé",
+    },
+    Template {
+        key: "DECRYPTION_FAILED",
+        en: "Wrong password or corrupted key",
+        ar: "كلمة المرور خاطئة أو المفتاح تالف",
+        fr: "Mot de passe incorrect ou clé corrompue",
+    },
+    Template { key: "INVALID_REQUEST", en: "Invalid request", ar: "طلب غير صالح", fr: "Requête invalide" },
+    Template { key: "INVALID_JSON", en: "Request body is not valid JSON", ar: "نص الطلب ليس JSON صالحًا", fr: "Le corps de la requête n'est pas un JSON valide" },
+    Template {
+        key: "UNKNOWN_FIELD",
+        en: "Unknown fields: {count}",
+        ar: "عدد الحقول غير المعروفة: {count}",
+        fr: "Champs inconnus : {count}",
+    },
+    Template { key: "UNKNOWN_FIELD", en: "Unknown field", ar: "حقل غير معروف", fr: "Champ inconnu" },
+    Template {
+        key: "VALIDATION_FAILED",
+        en: "Invalid fields: {count}",
+        ar: "عدد الحقول غير الصالحة: {count}",
+        fr: "Champs invalides : {count}",
+    },
+    Template { key: "VALIDATION_FAILED", en: "Validation failed", ar: "فشل التحقق من صحة الطلب", fr: "La validation a échoué" },
+    Template {
+        key: "READ_ONLY",
+        en: "Service is in read-only mode",
+        ar: "الخدمة في وضع القراءة فقط",
+        fr: "Le service est en mode lecture seule",
+    },
+    Template { key: "STORAGE_ERROR", en: "Storage error", ar: "خطأ في التخزين", fr: "Erreur de stockage" },
+    Template { key: "INTERNAL_ERROR", en: "Internal error", ar: "خطأ داخلي", fr: "Erreur interne" },
+    Template {
+        key: "INSUFFICIENT_PERMISSIONS",
+        en: "Insufficient permissions",
+        ar: "صلاحيات غير كافية",
+        fr: "Autorisations insuffisantes",
+    },
+    Template {
+        key: "RATE_LIMITED",
+        en: "Too many requests; retry later",
+        ar: "طلبات كثيرة جدًا؛ أعد المحاولة لاحقًا",
+        fr: "Trop de requêtes ; réessayez plus tard",
+    },
+    Template {
+        key: "OVERLOADED",
+        en: "Too many expensive operations in progress; retry later",
+        ar: "العمليات المكلفة الجارية كثيرة جدًا؛ أعد المحاولة لاحقًا",
+        fr: "Trop d'opérations coûteuses en cours ; réessayez plus tard",
+    },
+    Template {
+        key: "SHARE_NOT_FOUND",
+        en: "Verification link not found or no longer open",
+        ar: "رابط التحقق غير موجود أو لم يعد مفتوحًا",
+        fr: "Lien de vérification introuvable ou expiré",
+    },
+    Template {
+        key: "INVALID_REQUEST_SIGNATURE",
+        en: "Request signature is missing or invalid",
+        ar: "توقيع الطلب مفقود أو غير صالح",
+        fr: "Signature de la requête absente ou invalide",
+    },
+    Template {
+        key: "REQUEST_TIMESTAMP_EXPIRED",
+        en: "Request timestamp is outside the allowed clock skew",
+        ar: "طابع وقت الطلب خارج هامش فرق الساعة المسموح به",
+        fr: "L'horodatage de la requête dépasse le décalage d'horloge autorisé",
+    },
+    Template {
+        key: "REQUEST_REPLAYED",
+        en: "Request was already received",
+        ar: "تم استلام هذا الطلب من قبل",
+        fr: "Cette requête a déjà été reçue",
+    },
+    Template {
+        key: "KEYSTORE_FULL",
+        en: "Keystore is full",
+        ar: "مخزن المفاتيح ممتلئ",
+        fr: "Le magasin de clés est plein",
+    },
+    Template {
+        key: "MALFORMED_INPUT",
+        en: "Public key or signature is malformed",
+        ar: "المفتاح العام أو التوقيع غير صالح البنية",
+        fr: "La clé publique ou la signature est mal formée",
+    },
+    Template {
+        key: "RESTORE_CONFLICT",
+        en: "Deleted key cannot be restored",
+        ar: "تعذّرت استعادة المفتاح المحذوف",
+        fr: "La clé supprimée ne peut pas être restaurée",
+    },
+    Template {
+        key: "POLICY_DENIED",
+        en: "Signing policy refused the signature",
+        ar: "رفضت سياسة التوقيع هذا التوقيع",
+        fr: "La politique de signature a refusé la signature",
+    },
+    Template {
+        key: "KEY_CONFLICT",
+        en: "Key is already stored",
+        ar: "المفتاح مخزّن مسبقًا",
+        fr: "La clé est déjà enregistrée",
+    },
+    Template {
+        key: "DEADLINE_EXCEEDED",
+        en: "Request did not finish in time",
+        ar: "لم يكتمل الطلب في الوقت المحدد",
+        fr: "La requête ne s'est pas terminée à temps",
+    },
+    Template {
+        key: "ENVIRONMENT_MISMATCH",
+        en: "Key belongs to another deployment environment",
+        ar: "ينتمي المفتاح إلى بيئة نشر أخرى",
+        fr: "La clé appartient à un autre environnement de déploiement",
+    },
+    Template {
+        key: "RECEIPT_NOT_RECORDED",
+        en: "Signature withheld because its receipt could not be recorded",
+        ar: "تم حجب التوقيع لتعذّر تسجيل إيصاله",
+        fr: "La signature est retenue car son reçu n'a pas pu être enregistré",
+    },
+    Template { key: "KEY_SUSPENDED", en: "Key is suspended", ar: "المفتاح معلّق", fr: "La clé est suspendue" },
+    Template {
+        key: "INVALID_TRANSITION",
+        en: "The key cannot move to the requested state",
+        ar: "لا يمكن نقل المفتاح إلى الحالة المطلوبة",
+        fr: "La clé ne peut pas passer à l'état demandé",
+    },
+    Template {
+        key: "CONTENT_TOO_LARGE",
+        en: "Document too large for this endpoint",
+        ar: "المستند أكبر مما تقبله نقطة النهاية هذه",
+        fr: "Document trop volumineux pour ce point d'accès",
+    },
+    Template {
+        key: "DELEGATION_NOT_FOUND",
+        en: "Delegation not found or no longer outstanding",
+        ar: "التفويض غير موجود أو لم يعد قائمًا",
+        fr: "Délégation introuvable ou plus en cours",
+    },
+    Template {
+        key: "DELEGATION_DENIED",
+        en: "Delegation token does not allow this signature",
+        ar: "رمز التفويض لا يسمح بهذا التوقيع",
+        fr: "Le jeton de délégation n'autorise pas cette signature",
+    },
+    Template {
+        key: "STORAGE_UNAVAILABLE",
+        en: "Keystore unavailable; retry in {retry_after_secs} seconds",
+        ar: "مخزن المفاتيح غير متاح؛ أعد المحاولة بعد {retry_after_secs} ثانية",
+        fr: "Magasin de clés indisponible ; réessayez dans {retry_after_secs} secondes",
+    },
+    Template { key: "STORAGE_UNAVAILABLE", en: "Keystore unavailable", ar: "مخزن المفاتيح غير متاح", fr: "Magasin de clés indisponible" },
+    Template {
+        key: "AMBIGUOUS_PUBLIC_KEY",
+        en: "Public key input holds more than one key",
+        ar: "مدخل المفتاح العام يحتوي على أكثر من مفتاح",
+        fr: "L'entrée de clé publique contient plus d'une clé",
+    },
+    Template {
+        key: "EVENTS_NOT_RETAINED",
+        en: "Events after {after_seq} are no longer retained; the oldest retained is {oldest_seq}",
+        ar: "لم تعد الأحداث بعد {after_seq} محفوظة؛ أقدم حدث محفوظ هو {oldest_seq}",
+        fr: "Les événements après {after_seq} ne sont plus conservés ; le plus ancien conservé est {oldest_seq}",
+    },
+    Template {
+        key: "EVENTS_NOT_RETAINED",
+        en: "Events are no longer retained",
+        ar: "لم تعد الأحداث محفوظة",
+        fr: "Les événements ne sont plus conservés",
+    },
+];
+
+/// Success templates; the English text must match what the handlers write
+const STATUS_TEMPLATES: &[Template] = &[
+    Template { key: "KEY_GENERATED", en: "Key pair generated successfully", ar: "تم إنشاء زوج المفاتيح بنجاح", fr: "Paire de clés générée avec succès" },
+    Template {
+        key: "GENERATION_VALIDATED",
+        en: "Request is valid; no key was generated",
+        ar: "الطلب صالح؛ لم يتم إنشاء أي مفتاح",
+        fr: "La requête est valide ; aucune clé n'a été générée",
+    },
+    Template { key: "KEYS_FOUND", en: "Found {count} keys", ar: "عدد المفاتيح الموجودة: {count}", fr: "Clés trouvées : {count}" },
+    Template {
+        key: "MATCHING_KEYS_FOUND",
+        en: "Found {count} matching keys",
+        ar: "عدد المفاتيح المطابقة: {count}",
+        fr: "Clés correspondantes : {count}",
+    },
+    Template {
+        key: "PUBLIC_KEY_RETRIEVED",
+        en: "Public key retrieved successfully",
+        ar: "تم استرجاع المفتاح العام بنجاح",
+        fr: "Clé publique récupérée avec succès",
+    },
+    Template { key: "DOCUMENT_SIGNED", en: "Document signed successfully", ar: "تم توقيع المستند بنجاح", fr: "Document signé avec succès" },
+    Template {
+        key: "DOCUMENT_ALREADY_SIGNED",
+        en: "Document was already signed with this key; returning the existing signature",
+        ar: "سبق توقيع المستند بهذا المفتاح؛ تمت إعادة التوقيع الموجود",
+        fr: "Le document a déjà été signé avec cette clé ; la signature existante est renvoyée",
+    },
+    Template { key: "SIGNATURE_FOUND", en: "Signature found", ar: "تم العثور على التوقيع", fr: "Signature trouvée" },
+    Template {
+        key: "SHARE_CREATED",
+        en: "Verification link created",
+        ar: "تم إنشاء رابط التحقق",
+        fr: "Lien de vérification créé",
+    },
+    Template {
+        key: "SHARE_FOUND",
+        en: "Verification link found",
+        ar: "تم العثور على رابط التحقق",
+        fr: "Lien de vérification trouvé",
+    },
+    Template {
+        key: "SHARE_REVOKED",
+        en: "Verification link revoked",
+        ar: "تم إبطال رابط التحقق",
+        fr: "Lien de vérification révoqué",
+    },
+    Template {
+        key: "SHARED_DOCUMENT_MATCHES",
+        en: "Document matches the shared signature",
+        ar: "المستند يطابق التوقيع المشارك",
+        fr: "Le document correspond à la signature partagée",
+    },
+    Template {
+        key: "SHARED_DOCUMENT_MISMATCH",
+        en: "Document does not match the shared signature",
+        ar: "المستند لا يطابق التوقيع المشارك",
+        fr: "Le document ne correspond pas à la signature partagée",
+    },
+    Template { key: "SIGNATURE_VALID", en: "Signature is valid", ar: "التوقيع صالح", fr: "La signature est valide" },
+    Template { key: "SIGNATURE_INVALID", en: "Signature is invalid", ar: "التوقيع غير صالح", fr: "La signature est invalide" },
+    Template {
+        key: "SIGNATURE_WINDOW_EXPIRED",
+        en: "Signature is authentic but its validity window has expired",
+        ar: "التوقيع أصلي لكن فترة صلاحيته انتهت",
+        fr: "La signature est authentique mais sa période de validité a expiré",
+    },
+    Template {
+        key: "SIGNATURE_MATCHES_NO_CANDIDATE",
+        en: "Signature does not match any of the {count} candidate keys",
+        ar: "التوقيع لا يطابق أيًا من المفاتيح المرشحة وعددها {count}",
+        fr: "La signature ne correspond à aucune des {count} clés candidates",
+    },
+    Template { key: "KEY_UPDATED", en: "Key updated successfully", ar: "تم تحديث المفتاح بنجاح", fr: "Clé mise à jour avec succès" },
+    Template { key: "NOTHING_TO_UPDATE", en: "Nothing to update", ar: "لا يوجد ما يجب تحديثه", fr: "Rien à mettre à jour" },
+    Template { key: "KEY_REVOKED", en: "Key revoked successfully", ar: "تم إبطال المفتاح بنجاح", fr: "Clé révoquée avec succès" },
+    Template { key: "KEY_DELETED", en: "Key deleted successfully", ar: "تم حذف المفتاح بنجاح", fr: "Clé supprimée avec succès" },
+    Template { key: "KEY_RESTORED", en: "Key restored successfully", ar: "تمت استعادة المفتاح بنجاح", fr: "Clé restaurée avec succès" },
+    Template { key: "KEY_SUSPENDED", en: "Key suspended successfully", ar: "تم تعليق المفتاح بنجاح", fr: "Clé suspendue avec succès" },
+    Template { key: "KEY_RESUMED", en: "Key resumed successfully", ar: "تم استئناف المفتاح بنجاح", fr: "Clé réactivée avec succès" },
+    Template { key: "REVOCATION_SCHEDULED", en: "Key revocation scheduled", ar: "تمت جدولة إبطال المفتاح", fr: "Révocation de la clé planifiée" },
+    Template {
+        key: "REVOCATION_CANCELLED",
+        en: "Scheduled revocation cancelled",
+        ar: "تم إلغاء الإبطال المجدول",
+        fr: "Révocation planifiée annulée",
+    },
+    Template { key: "KEY_CERTIFIED", en: "Key certified successfully", ar: "تم اعتماد المفتاح بنجاح", fr: "Clé certifiée avec succès" },
+    Template {
+        key: "CERTIFICATIONS_FOUND",
+        en: "Found {issued} issued and {received} received certifications",
+        ar: "الشهادات الصادرة: {issued}، الشهادات المستلمة: {received}",
+        fr: "Certifications émises : {issued}, reçues : {received}",
+    },
+    Template {
+        key: "STATS_RETRIEVED",
+        en: "Retrieved statistics for {count} keys",
+        ar: "تم استرجاع إحصاءات {count} من المفاتيح",
+        fr: "Statistiques récupérées pour {count} clés",
+    },
+    Template { key: "CALIBRATION_COMPLETED", en: "Calibration completed", ar: "اكتملت المعايرة", fr: "Calibrage terminé" },
+    Template { key: "READ_ONLY_ENABLED", en: "Read-only mode enabled", ar: "تم تفعيل وضع القراءة فقط", fr: "Mode lecture seule activé" },
+    Template { key: "READ_ONLY_DISABLED", en: "Read-only mode disabled", ar: "تم تعطيل وضع القراءة فقط", fr: "Mode lecture seule désactivé" },
+    Template {
+        key: "READ_ONLY_FOLLOWER",
+        en: "Read-only mode cannot be disabled while another instance owns the keystore",
+        ar: "لا يمكن تعطيل وضع القراءة فقط بينما تملك نسخة أخرى مخزن المفاتيح",
+        fr: "Le mode lecture seule ne peut pas être désactivé tant qu'une autre instance détient le magasin de clés",
+    },
+    Template {
+        key: "KEYSTORE_CHECKED",
+        en: "Checked {checked} keys: {errors} errors, {warnings} warnings",
+        ar: "تم فحص {checked} من المفاتيح: {errors} أخطاء، {warnings} تحذيرات",
+        fr: "{checked} clés vérifiées : {errors} erreurs, {warnings} avertissements",
+    },
+];
+
+/// Fills `{name}` placeholders, or returns `None` if a parameter is missing
+pub fn render(template: &str, params: &HashMap<String, String>) -> Option<String> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = start + rest[start..].find('}')?;
+        out.push_str(&rest[..start]);
+        out.push_str(params.get(&rest[start + 1..end])?);
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+    Some(out)
+}
+
+/// Matches `message` against an English template, returning the placeholder values
+///
+/// Values are single words (counts), which keeps similar templates from matching each other.
+fn match_template(template: &str, message: &str) -> Option<HashMap<String, String>> {
+    let mut params = HashMap::new();
+    let mut template = template;
+    let mut message = message;
+    while let Some(start) = template.find('{') {
+        let end = start + template[start..].find('}')?;
+        message = message.strip_prefix(&template[..start])?;
+        let name = &template[start + 1..end];
+        template = &template[end + 1..];
+        // A placeholder runs up to the next literal text, or to the end
+        let next_literal = template.find('{').map_or(template, |next| &template[..next]);
+        let value_end = if next_literal.is_empty() { message.len() } else { message.find(next_literal)? };
+        let value = &message[..value_end];
+        if value.is_empty() || value.contains(char::is_whitespace) {
+            return None;
+        }
+        params.insert(name.to_string(), value.to_string());
+        message = &message[value_end..];
+    }
+    (message == template).then_some(params)
+}
+
+/// Translates an error message by its code, with parameters from the `details` object
+pub fn localize_error(code: &str, details: Option<&Value>, locale: Locale) -> Option<String> {
+    let params: HashMap<String, String> = details
+        .and_then(Value::as_object)
+        .map(|details| details.iter()
+            .map(|(name, value)| (name.clone(), value.as_str().map_or_else(|| value.to_string(), str::to_string)))
+            .collect())
+        .unwrap_or_default();
+    ERROR_TEMPLATES.iter()
+        .filter(|template| template.key == code)
+        .find_map(|template| render(template.text(locale), &params))
+}
+
+/// Translates a success message written from one of the English status templates
+pub fn localize_status(message: &str, locale: Locale) -> Option<String> {
+    STATUS_TEMPLATES.iter().find_map(|template| {
+        let params = match_template(template.en, message)?;
+        render(template.text(locale), &params)
+    })
+}
+
+/// Rewrites the top-level `message` of a response body into `locale`
+///
+/// English bodies are returned untouched, so the handlers' more specific wording is kept.
+pub fn localize_body(mut body: Value, locale: Locale) -> Value {
+    if locale == Locale::En {
+        return body;
+    }
+    let Some(object) = body.as_object_mut() else { return body };
+    let localized = match (object.get("code").and_then(Value::as_str), object.get("message").and_then(Value::as_str)) {
+        (Some(code), _) => localize_error(code, object.get("details"), locale),
+        (None, Some(message)) => localize_status(message, locale),
+        (None, None) => None,
+    };
+    if let Some(localized) = localized {
+        object.insert("message".to_string(), Value::String(localized));
+    }
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiation_and_templates() {
+        assert_eq!(Locale::negotiate("fr-CA,fr;q=0.9,en;q=0.8"), Locale::Fr);
+        assert_eq!(Locale::negotiate("de, ar;q=0.5, fr;q=0.4"), Locale::Ar);
+        assert_eq!(Locale::negotiate("ar;q=0, fr;q=0.1"), Locale::Fr);
+        assert_eq!(Locale::negotiate("de-DE"), Locale::En);
+        assert_eq!(Locale::negotiate(""), Locale::En);
+
+        let params = match_template("Checked {checked} keys: {errors} errors, {warnings} warnings", "Checked 12 keys: 1 errors, 0 warnings").unwrap();
+        assert_eq!((params["checked"].as_str(), params["errors"].as_str(), params["warnings"].as_str()), ("12", "1", "0"));
+        assert!(match_template("Found {count} keys", "Found 3 matching keys").is_none());
+        assert_eq!(localize_status("Found 3 matching keys", Locale::Fr).as_deref(), Some("Clés correspondantes : 3"));
+
+        // Templates fall back to a variant without the parameter that is missing
+        let details = serde_json::json!({ "key_id": "k1", "key_name": "Billing" });
+        assert_eq!(localize_error("KEY_ALREADY_REVOKED", Some(&details), Locale::Fr).as_deref(), Some("La clé « Billing » est déjà révoquée"));
+        assert_eq!(localize_error("KEY_ALREADY_REVOKED", None, Locale::Ar).as_deref(), Some("المفتاح مُبطَل بالفعل"));
+        assert_eq!(localize_error("VALIDATION_FAILED", Some(&serde_json::json!({ "count": 2 })), Locale::Ar).as_deref(), Some("عدد الحقول غير الصالحة: 2"));
+
+        // Every error code has a translation
+        for code in crate::models::ErrorCode::ALL {
+            for locale in [Locale::En, Locale::Ar, Locale::Fr] {
+                assert!(localize_error(code.as_str(), None, locale).is_some(), "{} {:?}", code, locale);
+            }
+        }
+    }
+}
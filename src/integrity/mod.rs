@@ -0,0 +1,593 @@
+//! Keystore integrity checks and repair
+//!
+//! Every stored entry is checked for structural validity, key pair consistency, envelope
+//! health, fingerprint correctness, index consistency, a lifecycle state its history agrees
+//! with, and dates in order. In repair mode the safe fixes are applied in place and entries that
+//! cannot be trusted are moved to the quarantine file. The owning instance runs the checks in
+//! repair mode as it loads the keystore.
+
+use crate::deadline::Deadline;
+use crate::key_generation::{is_key_envelope, validate_key_pair, EncryptedKeyEnvelope};
+use crate::key_storage::KeyStorage;
+use crate::lifecycle::Lifecycle;
+use crate::models::{
+    IssueSeverity, KeyIssue, KeyManagementError, KeyPair, KeyStrength, KeyType, KeyValidationReport, ValidateKeystoreResponse,
+};
+use crate::utils::{public_key_to_fingerprint, validate_key_pair_compatibility};
+use base64::Engine;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+/// What repair mode should do about an issue
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Remedy {
+    /// Needs a human (or a password) to resolve
+    None,
+    /// Fixed by rewriting the entry from the repaired copy
+    Rewrite,
+    /// The entry cannot be trusted and is moved out of the store
+    Quarantine,
+}
+
+/// Result of checking one entry
+#[derive(Debug, Clone)]
+pub struct KeyCheck {
+    pub issues: Vec<KeyIssue>,
+    /// The entry with every safe fix applied
+    pub repaired: KeyPair,
+    remedies: Vec<Remedy>,
+}
+
+impl KeyCheck {
+    fn push(&mut self, code: &str, severity: IssueSeverity, message: impl Into<String>, remedy: Remedy) {
+        self.issues.push(KeyIssue {
+            code: code.to_string(),
+            severity,
+            message: message.into(),
+            repaired: false,
+        });
+        self.remedies.push(remedy);
+    }
+
+    /// True when repair mode would move the entry to quarantine
+    pub fn needs_quarantine(&self) -> bool {
+        self.remedies.contains(&Remedy::Quarantine)
+    }
+
+    /// True when repair mode would rewrite the entry
+    pub fn needs_rewrite(&self) -> bool {
+        self.remedies.contains(&Remedy::Rewrite)
+    }
+}
+
+/// Checks a single entry
+///
+/// `indexed_id` is the id the entry is stored under and `taken_ids` the set of all such ids,
+/// used to tell whether re-indexing a mismatched entry would collide with another one.
+pub fn check_key(indexed_id: Uuid, key_pair: &KeyPair, taken_ids: &HashSet<Uuid>) -> KeyCheck {
+    let mut check = KeyCheck {
+        issues: Vec::new(),
+        repaired: key_pair.clone(),
+        remedies: Vec::new(),
+    };
+
+    // Index consistency
+    if indexed_id != key_pair.id {
+        if taken_ids.contains(&key_pair.id) {
+            check.push(
+                "index_mismatch",
+                IssueSeverity::Error,
+                format!("Entry is indexed under {} but records id {}, which is already in use", indexed_id, key_pair.id),
+                Remedy::None,
+            );
+        } else {
+            check.push(
+                "index_mismatch",
+                IssueSeverity::Error,
+                format!("Entry is indexed under {} but records id {}", indexed_id, key_pair.id),
+                Remedy::Rewrite,
+            );
+        }
+    }
+
+    // A type or strength from a newer release; its key material cannot be checked, and nothing is
+    // repaired or quarantined on the strength of rules written for other types
+    if key_pair.key_strength == KeyStrength::Unknown {
+        check.push(
+            "unknown_key_strength",
+            IssueSeverity::Warning,
+            "Key strength is not known to this version",
+            Remedy::None,
+        );
+    }
+    if key_pair.key_type == KeyType::Unknown {
+        check.push(
+            "unknown_key_type",
+            IssueSeverity::Warning,
+            "Key type is not known to this version; the key cannot sign until the record is migrated to a supported key type",
+            Remedy::None,
+        );
+        return check;
+    }
+
+    // Structural validity; nothing else can be trusted if this fails
+    if let Err(e) = validate_key_pair(key_pair) {
+        check.push("invalid_key", IssueSeverity::Error, e.to_string(), Remedy::Quarantine);
+        return check;
+    }
+
+    // Fingerprint
+    if let Ok(expected) = public_key_to_fingerprint(&key_pair.public_key) {
+        match &key_pair.fingerprint {
+            None => check.push("missing_fingerprint", IssueSeverity::Info, "No fingerprint recorded", Remedy::Rewrite),
+            Some(stored) if *stored != expected => check.push(
+                "fingerprint_mismatch",
+                IssueSeverity::Error,
+                format!("Recorded fingerprint {} does not match public key ({})", stored, expected),
+                Remedy::Rewrite,
+            ),
+            Some(_) => {}
+        }
+        check.repaired.fingerprint = Some(expected);
+    }
+
+    // Private key layout
+    let private_key = key_pair.private_key.expose_for_persistence();
+    let private_key_bytes = base64::engine::general_purpose::STANDARD
+        .decode(private_key)
+        .unwrap_or_default();
+    let expected_type = if key_pair.hsm.is_some() {
+        // The private key lives on the HSM, so there is nothing to check locally
+        if !key_pair.private_key.is_empty() {
+            check.push(
+                "hsm_private_key_present",
+                IssueSeverity::Error,
+                "HSM key also has private key material in the keystore",
+                Remedy::Quarantine,
+            );
+        }
+        KeyType::Ed25519Hsm
+    } else if key_pair.key_type == KeyType::Ed25519Ephemeral {
+        // Tombstone of a single-use key: only the public key is meant to remain
+        if !key_pair.private_key.is_empty() {
+            check.push(
+                "ephemeral_private_key_present",
+                IssueSeverity::Error,
+                "Ephemeral key tombstone still holds private key material",
+                Remedy::Quarantine,
+            );
+        }
+        KeyType::Ed25519Ephemeral
+    } else if private_key_bytes.len() == 64 {
+        match validate_key_pair_compatibility(&key_pair.public_key, private_key) {
+            Ok(true) => {}
+            Ok(false) | Err(_) => check.push(
+                "key_pair_mismatch",
+                IssueSeverity::Error,
+                "Private key does not correspond to the public key",
+                Remedy::Quarantine,
+            ),
+        }
+        KeyType::Ed25519
+    } else if is_key_envelope(&private_key_bytes) {
+        match EncryptedKeyEnvelope::parse(&private_key_bytes) {
+            Ok(envelope) => {
+                if key_pair.kdf != Some(envelope.kdf) {
+                    check.push(
+                        "kdf_metadata_mismatch",
+                        IssueSeverity::Warning,
+                        "Recorded KDF parameters differ from the envelope",
+                        Remedy::Rewrite,
+                    );
+                    check.repaired.kdf = Some(envelope.kdf);
+                }
+            }
+            Err(e) => check.push("corrupted_envelope", IssueSeverity::Error, e.to_string(), Remedy::Quarantine),
+        }
+        KeyType::Ed25519Encrypted
+    } else {
+        let salt = key_pair.salt.as_ref()
+            .map(|salt| base64::engine::general_purpose::STANDARD.decode(salt.expose_for_persistence()));
+        if salt.is_none() {
+            check.push(
+                "missing_salt",
+                IssueSeverity::Error,
+                "Legacy encrypted key has no salt and can never be decrypted",
+                Remedy::Quarantine,
+            );
+        } else if salt.is_some_and(|salt| salt.is_err()) {
+            check.push(
+                "invalid_salt",
+                IssueSeverity::Error,
+                "Legacy encrypted key has a salt that is not valid base64 and can never be decrypted",
+                Remedy::Quarantine,
+            );
+        } else {
+            check.push(
+                "legacy_envelope",
+                IssueSeverity::Info,
+                "Key uses the legacy encrypted layout; it is upgraded on its next successful use",
+                Remedy::None,
+            );
+        }
+        KeyType::Ed25519Encrypted
+    };
+
+    if key_pair.key_type != expected_type {
+        check.push(
+            "key_type_mismatch",
+            IssueSeverity::Warning,
+            format!("Recorded key type {:?} does not match the stored private key", key_pair.key_type),
+            Remedy::Rewrite,
+        );
+        check.repaired.key_type = expected_type;
+    }
+
+    // Lifecycle state; a state its own history contradicts may have been re-enabled by hand
+    if key_pair.lifecycle == Lifecycle::Deleted {
+        check.push(
+            "state_mismatch",
+            IssueSeverity::Error,
+            "Key is marked deleted but is still in the keystore",
+            Remedy::Quarantine,
+        );
+    } else if let Some(event) = key_pair.lifecycle_history.last().filter(|event| Lifecycle::for_target(event.to) != Some(key_pair.lifecycle)) {
+        check.push(
+            "state_mismatch",
+            IssueSeverity::Error,
+            format!("Key is stored as {:?} but its history last moved it to {}", key_pair.lifecycle, event.to.as_str()),
+            Remedy::Quarantine,
+        );
+    }
+    if key_pair.lifecycle == Lifecycle::Revoked && key_pair.revocation_scheduled_at.is_some() {
+        check.push(
+            "revoked_with_schedule",
+            IssueSeverity::Warning,
+            "Revoked key still has a revocation scheduled",
+            Remedy::Rewrite,
+        );
+        check.repaired.revocation_scheduled_at = None;
+    }
+
+    // Dates; a tombstone is dated by its one signature, which may be stamped, to the
+    // millisecond, before the key was generated
+    let dated = key_pair.key_type != KeyType::Ed25519Ephemeral;
+    let before_creation = [("Expiry", key_pair.expires_at), ("Scheduled revocation", key_pair.revocation_scheduled_at)]
+        .into_iter()
+        .filter_map(|(what, at)| at.filter(|at| *at < key_pair.created_at).map(|at| format!("{} {} is before creation {}", what, at, key_pair.created_at)))
+        .collect::<Vec<_>>();
+    if dated && !before_creation.is_empty() {
+        check.push("date_order", IssueSeverity::Error, before_creation.join("; "), Remedy::Quarantine);
+    }
+
+    check
+}
+
+/// Validates every entry in the store, optionally repairing it
+///
+/// `progress` is called after each entry with the number checked so far, the total, and the
+/// entry's report. Validation stops with [`KeyManagementError::DeadlineExceeded`] once
+/// `deadline` passes; entries already repaired stay repaired.
+pub async fn validate_keystore(
+    storage: &KeyStorage,
+    repair: bool,
+    deadline: &Deadline,
+    mut progress: impl FnMut(usize, usize, &KeyValidationReport),
+) -> Result<ValidateKeystoreResponse, KeyManagementError> {
+    let mut entries = storage.entries().await;
+    entries.sort_by_key(|(indexed_id, _)| *indexed_id);
+    let taken_ids: HashSet<Uuid> = entries.iter().map(|(indexed_id, _)| *indexed_id).collect();
+
+    // Public keys shared by more than one entry
+    let mut public_key_owners: HashMap<&str, usize> = HashMap::new();
+    for (_, key_pair) in &entries {
+        *public_key_owners.entry(key_pair.public_key.as_str()).or_default() += 1;
+    }
+
+    let total = entries.len();
+    let mut reports = Vec::with_capacity(total);
+    for (index, (indexed_id, key_pair)) in entries.iter().enumerate() {
+        deadline.check(index, total)?;
+        let mut check = check_key(*indexed_id, key_pair, &taken_ids);
+        if public_key_owners.get(key_pair.public_key.as_str()).copied().unwrap_or(0) > 1 {
+            check.push(
+                "duplicate_public_key",
+                IssueSeverity::Warning,
+                "Another entry holds the same public key",
+                Remedy::None,
+            );
+        }
+
+        let mut quarantined = false;
+        if repair {
+            let applied = if check.needs_quarantine() {
+                let reason = check.issues.iter()
+                    .zip(&check.remedies)
+                    .filter(|(_, remedy)| **remedy == Remedy::Quarantine)
+                    .map(|(issue, _)| issue.code.as_str())
+                    .collect::<Vec<_>>()
+                    .join(",");
+                storage.quarantine_key(*indexed_id, &reason).await?;
+                quarantined = true;
+                Remedy::Quarantine
+            } else if check.needs_rewrite() {
+                storage.replace_entry(*indexed_id, check.repaired.clone()).await?;
+                Remedy::Rewrite
+            } else {
+                Remedy::None
+            };
+
+            // Quarantine resolves everything about the entry; a rewrite resolves its own fixes
+            for (issue, remedy) in check.issues.iter_mut().zip(&check.remedies) {
+                issue.repaired = match applied {
+                    Remedy::Quarantine => *remedy != Remedy::None,
+                    Remedy::Rewrite => *remedy == Remedy::Rewrite,
+                    Remedy::None => false,
+                };
+            }
+        }
+
+        let report = KeyValidationReport {
+            key_id: *indexed_id,
+            name: key_pair.name.clone(),
+            issues: check.issues,
+            quarantined,
+        };
+        progress(index + 1, total, &report);
+        reports.push(report);
+    }
+
+    let count = |severity: IssueSeverity| {
+        reports.iter().flat_map(|report| &report.issues).filter(|issue| issue.severity == severity).count()
+    };
+    let errors = count(IssueSeverity::Error);
+    let warnings = count(IssueSeverity::Warning);
+    let repaired = reports.iter().flat_map(|report| &report.issues).filter(|issue| issue.repaired).count();
+    let quarantined = reports.iter().filter(|report| report.quarantined).count();
+
+    Ok(ValidateKeystoreResponse {
+        success: true,
+        checked: total,
+        errors,
+        warnings,
+        repaired,
+        quarantined,
+        message: format!("Checked {} keys: {} errors, {} warnings", total, errors, warnings),
+        code: None,
+        details: None,
+        reports,
+    })
+}
+
+/// Outcome of validating the keystore as it was loaded
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct KeystoreLoadSummary {
+    pub checked: usize, // Entries that read as keys
+    pub unreadable: usize, // Entries quarantined because they do not read as a key
+    pub quarantined: usize, // Keys quarantined for an issue that cannot be repaired
+    pub repaired: usize, // Issues corrected in place
+    pub unresolved: usize, // Errors and warnings left for an operator
+}
+
+impl KeystoreLoadSummary {
+    /// Whether the keystore loaded without anything quarantined, repaired or left to resolve
+    pub fn is_clean(&self) -> bool {
+        self.unreadable + self.quarantined + self.repaired + self.unresolved == 0
+    }
+
+    /// One-line description for logs
+    pub fn summary(&self) -> String {
+        format!(
+            "{} keys checked, {} unreadable entries and {} keys quarantined, {} issues repaired, {} left unresolved",
+            self.checked, self.unreadable, self.quarantined, self.repaired, self.unresolved,
+        )
+    }
+}
+
+/// Loads the keystore and validates every entry, quarantining what cannot be trusted and
+/// correcting what can
+///
+/// Run on startup by the instance that owns the keystore, with the checks of
+/// [`validate_keystore`] in repair mode. Each key with issues is logged.
+pub async fn validate_on_load(storage: &KeyStorage) -> Result<KeystoreLoadSummary, KeyManagementError> {
+    let unreadable = storage.load_quarantining_unreadable().await?;
+    for reason in &unreadable {
+        tracing::error!("Quarantined a keystore entry on load: {}", reason);
+    }
+
+    let report = validate_keystore(storage, true, &Deadline::none(), |_, _, report| {
+        let codes = |repaired: bool| report.issues.iter()
+            .filter(|issue| issue.repaired == repaired && (repaired || issue.severity != IssueSeverity::Info))
+            .map(|issue| issue.code.as_str())
+            .collect::<Vec<_>>();
+        if report.quarantined {
+            tracing::error!("Quarantined key {} ({}) on load: {}", report.key_id, report.name, codes(true).join(", "));
+            return;
+        }
+        if !codes(true).is_empty() {
+            tracing::warn!("Corrected key {} ({}) on load: {}", report.key_id, report.name, codes(true).join(", "));
+        }
+        if !codes(false).is_empty() {
+            tracing::warn!("Key {} ({}) needs attention: {}", report.key_id, report.name, codes(false).join(", "));
+        }
+    }).await?;
+
+    let kept_issues = || report.reports.iter().filter(|report| !report.quarantined).flat_map(|report| &report.issues);
+    Ok(KeystoreLoadSummary {
+        checked: report.checked,
+        unreadable: unreadable.len(),
+        quarantined: report.quarantined,
+        repaired: kept_issues().filter(|issue| issue.repaired).count(),
+        unresolved: kept_issues().filter(|issue| !issue.repaired && issue.severity != IssueSeverity::Info).count(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_generation::{generate_key_pair, generate_legacy_test_key_pair, generate_test_key_pair};
+    use crate::models::GenerateKeyRequest;
+    use tempfile::tempdir;
+
+    fn codes(report: &KeyValidationReport) -> Vec<&str> {
+        report.issues.iter().map(|issue| issue.code.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_detects_and_selectively_repairs_defects() {
+        let dir = tempdir().unwrap();
+        let storage = KeyStorage::new(dir.path().join("keys.json").to_str().unwrap());
+
+        let encrypted_key = |name: &str| generate_key_pair(GenerateKeyRequest {
+            name: name.to_string(),
+            description: None,
+            password: Some("pw".to_string()),
+            expires_at: None,
+            tags: None,
+            key_strength: None,
+            hsm: None,
+            generate_password: false,
+            template: None,
+            environment: None,
+            fast: false,
+            exportable: None,
+            default_output_format: None,
+            default_hash_algorithm: None,
+            default_encoding: None,
+        }).unwrap();
+        let healthy = generate_test_key_pair("Healthy").unwrap();
+        let encrypted = encrypted_key("Encrypted");
+
+        let mut wrong_fingerprint = generate_test_key_pair("Wrong Fingerprint").unwrap();
+        wrong_fingerprint.fingerprint = Some("00000000:00000000:00000000:00000000".to_string());
+
+        let mut mismatched_pair = generate_test_key_pair("Mismatched Pair").unwrap();
+        mismatched_pair.public_key = generate_test_key_pair("Other").unwrap().public_key;
+        mismatched_pair.fingerprint = public_key_to_fingerprint(&mismatched_pair.public_key).ok();
+
+        let mut lost_salt = generate_legacy_test_key_pair("pw");
+        lost_salt.name = "Lost Salt".to_string();
+        lost_salt.salt = None;
+
+        let mut truncated = encrypted_key("Truncated Envelope");
+        let bytes = base64::engine::general_purpose::STANDARD.decode(truncated.private_key.expose_for_persistence()).unwrap();
+        truncated.private_key = base64::engine::general_purpose::STANDARD.encode(&bytes[..70]).into();
+
+        let mut wrong_type = generate_test_key_pair("Wrong Type").unwrap();
+        wrong_type.key_type = KeyType::Ed25519Encrypted;
+
+        for key_pair in [&healthy, &encrypted, &wrong_fingerprint, &mismatched_pair, &lost_salt, &truncated, &wrong_type] {
+            storage.store_key(key_pair.clone()).await.unwrap();
+        }
+        // Dry run reports everything and changes nothing
+        let mut seen = 0;
+        let report = validate_keystore(&storage, false, &Deadline::none(), |checked, total, _| {
+            seen = checked;
+            assert_eq!(total, 7);
+        }).await.unwrap();
+        assert_eq!(seen, 7);
+        let by_name = |report: &ValidateKeystoreResponse, name: &str| {
+            report.reports.iter().find(|r| r.name == name).cloned().unwrap()
+        };
+        assert!(by_name(&report, "Healthy").issues.is_empty());
+        assert!(by_name(&report, "Encrypted").issues.is_empty());
+        assert_eq!(codes(&by_name(&report, "Wrong Fingerprint")), ["fingerprint_mismatch"]);
+        assert_eq!(codes(&by_name(&report, "Mismatched Pair")), ["key_pair_mismatch"]);
+        assert!(codes(&by_name(&report, "Lost Salt")).contains(&"missing_salt"));
+        assert!(codes(&by_name(&report, "Truncated Envelope")).contains(&"corrupted_envelope"));
+        assert_eq!(codes(&by_name(&report, "Wrong Type")), ["key_type_mismatch"]);
+        assert!(report.reports.iter().all(|r| !r.quarantined && r.issues.iter().all(|i| !i.repaired)));
+        assert_eq!(storage.key_count().await, 7);
+
+        // Repair fixes metadata in place and quarantines what cannot be trusted
+        let report = validate_keystore(&storage, true, &Deadline::none(), |_, _, _| {}).await.unwrap();
+        assert!(by_name(&report, "Wrong Fingerprint").issues[0].repaired);
+        assert!(by_name(&report, "Wrong Type").issues[0].repaired);
+        for name in ["Mismatched Pair", "Lost Salt", "Truncated Envelope"] {
+            assert!(by_name(&report, name).quarantined, "{}", name);
+        }
+        assert_eq!(report.quarantined, 3);
+        assert_eq!(storage.key_count().await, 4);
+
+        let quarantine = std::fs::read_to_string(storage.quarantine_path()).unwrap();
+        assert!(quarantine.contains("key_pair_mismatch") && quarantine.contains("missing_salt"));
+
+        // A second pass is clean
+        let report = validate_keystore(&storage, false, &Deadline::none(), |_, _, _| {}).await.unwrap();
+        assert_eq!((report.errors, report.warnings), (0, 0), "{:?}", report.reports);
+    }
+
+    #[tokio::test]
+    async fn test_load_quarantines_broken_entries_and_corrects_the_rest() {
+        use crate::key_storage::serialize_keys;
+        use crate::models::KeyState;
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("keys.json");
+
+        let healthy = generate_test_key_pair("Healthy").unwrap();
+        let mut wrong_fingerprint = generate_test_key_pair("Wrong Fingerprint").unwrap();
+        wrong_fingerprint.fingerprint = Some("00000000:00000000:00000000:00000000".to_string());
+        let mut still_scheduled = generate_test_key_pair("Still Scheduled").unwrap();
+        still_scheduled.transition(KeyState::Revoked, None, None, still_scheduled.created_at).unwrap();
+        still_scheduled.revocation_scheduled_at = Some(still_scheduled.created_at + chrono::Duration::days(1));
+        // Revoked, then switched back on by editing the file
+        let mut reenabled = generate_test_key_pair("Re-enabled").unwrap();
+        reenabled.transition(KeyState::Revoked, None, None, reenabled.created_at).unwrap();
+        reenabled.lifecycle = Lifecycle::Active;
+        let mut expired_before_created = generate_test_key_pair("Expired Before Created").unwrap();
+        expired_before_created.expires_at = Some(expired_before_created.created_at - chrono::Duration::days(1));
+        let mut bad_salt = generate_legacy_test_key_pair("pw");
+        bad_salt.name = "Bad Salt".to_string();
+        bad_salt.salt = Some("not base64!".to_string().into());
+        let mut short_public_key = generate_test_key_pair("Short Public Key").unwrap();
+        short_public_key.public_key = base64::engine::general_purpose::STANDARD.encode([1u8; 16]);
+        let negative_count = generate_test_key_pair("Negative Count").unwrap();
+
+        let keys = [&healthy, &wrong_fingerprint, &still_scheduled, &reenabled, &expired_before_created, &bad_salt, &short_public_key, &negative_count];
+        let mut entries: Vec<serde_json::Value> = serde_json::from_str(&serialize_keys(keys.into_iter()).unwrap()).unwrap();
+        entries.last_mut().unwrap()["usage"]["sign_count"] = serde_json::json!(-3);
+        std::fs::write(&path, serde_json::to_string(&entries).unwrap()).unwrap();
+
+        // The strict load refuses the file outright
+        assert!(KeyStorage::new(path.to_str().unwrap()).load_from_disk().await.is_err());
+
+        let storage = KeyStorage::new(path.to_str().unwrap());
+        let summary = validate_on_load(&storage).await.unwrap();
+        assert_eq!(summary, KeystoreLoadSummary { checked: 7, unreadable: 1, quarantined: 4, repaired: 2, unresolved: 0 });
+        let mut remaining: Vec<Uuid> = storage.entries().await.into_iter().map(|(id, _)| id).collect();
+        remaining.sort();
+        let mut expected = vec![healthy.id, wrong_fingerprint.id, still_scheduled.id];
+        expected.sort();
+        assert_eq!(remaining, expected);
+
+        let quarantine = std::fs::read_to_string(storage.quarantine_path()).unwrap();
+        for reason in ["state_mismatch", "date_order", "invalid_salt", "invalid_key", "unreadable: invalid value"] {
+            assert!(quarantine.contains(reason), "{}", reason);
+        }
+        assert!(quarantine.contains(&negative_count.id.to_string()));
+
+        // The corrections reach disk, and the file now loads strictly and cleanly
+        let reloaded = KeyStorage::new(path.to_str().unwrap());
+        reloaded.load_from_disk().await.unwrap();
+        let fixed = reloaded.get_key_record(wrong_fingerprint.id).await.unwrap();
+        assert_eq!(fixed.fingerprint, public_key_to_fingerprint(&fixed.public_key).ok());
+        assert_eq!(reloaded.get_key_record(still_scheduled.id).await.unwrap().revocation_scheduled_at, None);
+        assert!(validate_on_load(&KeyStorage::new(path.to_str().unwrap())).await.unwrap().is_clean());
+    }
+
+    #[test]
+    fn test_index_mismatch_is_repairable_unless_it_collides() {
+        let key_pair = generate_test_key_pair("Misindexed").unwrap();
+        let indexed_id = Uuid::new_v4();
+
+        let check = check_key(indexed_id, &key_pair, &HashSet::from([indexed_id]));
+        assert_eq!(check.issues[0].code, "index_mismatch");
+        assert!(check.needs_rewrite());
+
+        let check = check_key(indexed_id, &key_pair, &HashSet::from([indexed_id, key_pair.id]));
+        assert_eq!(check.issues[0].code, "index_mismatch");
+        assert!(!check.needs_rewrite());
+    }
+}
@@ -0,0 +1,190 @@
+//! KDF latency histograms and the per-key KDF report
+//!
+//! Raising the KDF parameters makes every unlock of a newly encrypted key slower, while keys
+//! encrypted earlier keep the parameters they were written with until they are re-encrypted.
+//! Unlocks are timed into Prometheus histograms by KDF, and [`kdf_report`] groups stored keys by
+//! the parameters they actually carry so operators can see which keys still use cheaper ones.
+
+use crate::config::KdfParams;
+use crate::key_generation::{is_key_envelope, EncryptedKeyEnvelope, KdfTiming};
+use crate::models::KeyPair;
+use base64::Engine;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use uuid::Uuid;
+
+/// Upper bounds, in seconds, of the latency histogram buckets
+pub const KDF_BUCKETS_SECS: [f64; 10] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+/// Step of an unlock a histogram times
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum KdfStage {
+    /// Deriving the encryption key from the password
+    Derivation,
+    /// Decrypting the private key, derivation included
+    Decryption,
+}
+
+impl KdfStage {
+    /// Name of the Prometheus metric for this stage
+    pub fn metric_name(self) -> &'static str {
+        match self {
+            KdfStage::Derivation => "inkan_kdf_derivation_seconds",
+            KdfStage::Decryption => "inkan_private_key_decryption_seconds",
+        }
+    }
+}
+
+/// Latencies of one stage under one KDF
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KdfHistogram {
+    /// Observations at or below each bound of [`KDF_BUCKETS_SECS`], cumulative
+    pub buckets: [u64; KDF_BUCKETS_SECS.len()],
+    pub count: u64,
+    pub sum_secs: f64,
+}
+
+impl KdfHistogram {
+    fn observe(&mut self, secs: f64) {
+        for (bucket, bound) in self.buckets.iter_mut().zip(KDF_BUCKETS_SECS) {
+            if secs <= bound {
+                *bucket += 1;
+            }
+        }
+        self.count += 1;
+        self.sum_secs += secs;
+    }
+}
+
+/// Histograms of key unlock latencies, by stage and KDF
+#[derive(Default)]
+pub struct KdfTimings {
+    histograms: Mutex<BTreeMap<(KdfStage, &'static str), KdfHistogram>>,
+}
+
+impl KdfTimings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records one unlock of an encrypted key
+    pub fn record(&self, timing: &KdfTiming) {
+        let kdf = timing.kdf.algorithm.as_str();
+        let mut histograms = self.histograms.lock().unwrap_or_else(|e| e.into_inner());
+        for (stage, elapsed) in [(KdfStage::Derivation, timing.derivation), (KdfStage::Decryption, timing.decryption)] {
+            histograms.entry((stage, kdf)).or_default().observe(elapsed.as_secs_f64());
+        }
+    }
+
+    /// Current histograms, ordered by stage and KDF
+    pub fn snapshot(&self) -> Vec<(KdfStage, &'static str, KdfHistogram)> {
+        self.histograms.lock().unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|((stage, kdf), histogram)| (*stage, *kdf, histogram.clone()))
+            .collect()
+    }
+}
+
+/// How a stored private key is protected
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyProtection {
+    /// Self-describing envelope carrying its KDF parameters
+    Envelope,
+    /// Legacy nonce and ciphertext layout with a separately stored salt
+    Legacy,
+    /// Stored without a password
+    Unencrypted,
+    /// Held on an HSM; the keystore has no private key
+    Hsm,
+}
+
+/// Stored keys sharing one protection and set of KDF parameters
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct KdfReportGroup {
+    pub protection: KeyProtection,
+    pub kdf: Option<KdfParams>, // Absent for unencrypted and HSM keys
+    pub current: bool, // Encrypted with the parameters applied to new keys
+    pub key_count: usize,
+    pub key_ids: Vec<Uuid>,
+}
+
+/// KDF protection of a stored key, with the parameters it was encrypted under
+pub fn key_kdf(key_pair: &KeyPair) -> (KeyProtection, Option<KdfParams>) {
+    if key_pair.hsm.is_some() {
+        return (KeyProtection::Hsm, None);
+    }
+    let bytes = base64::engine::general_purpose::STANDARD.decode(key_pair.private_key.expose_for_persistence()).unwrap_or_default();
+    if is_key_envelope(&bytes) {
+        // The envelope is authoritative; an unreadable one falls back to the stored parameters
+        let kdf = EncryptedKeyEnvelope::parse(&bytes).map_or(key_pair.kdf.unwrap_or_default(), |envelope| envelope.kdf);
+        (KeyProtection::Envelope, Some(kdf))
+    } else if bytes.len() > 64 {
+        (KeyProtection::Legacy, Some(key_pair.kdf.unwrap_or_default()))
+    } else {
+        (KeyProtection::Unencrypted, None)
+    }
+}
+
+/// Groups `keys` by protection and KDF parameters, most keys first
+///
+/// Keys within a group, and groups of equal size, are ordered by creation time.
+pub fn kdf_report(keys: &[KeyPair], current: &KdfParams) -> Vec<KdfReportGroup> {
+    let mut keys: Vec<&KeyPair> = keys.iter().collect();
+    keys.sort_by_key(|key_pair| (key_pair.created_at, key_pair.id));
+
+    let mut groups: Vec<KdfReportGroup> = Vec::new();
+    for key_pair in keys {
+        let (protection, kdf) = key_kdf(key_pair);
+        match groups.iter_mut().find(|group| group.protection == protection && group.kdf == kdf) {
+            Some(group) => group.key_ids.push(key_pair.id),
+            None => groups.push(KdfReportGroup {
+                protection,
+                kdf,
+                current: protection == KeyProtection::Envelope && kdf.as_ref() == Some(current),
+                key_count: 0,
+                key_ids: vec![key_pair.id],
+            }),
+        }
+    }
+    for group in &mut groups {
+        group.key_count = group.key_ids.len();
+    }
+    groups.sort_by_key(|group| std::cmp::Reverse(group.key_count));
+    groups
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::KdfAlgorithm;
+    use std::time::Duration;
+
+    #[test]
+    fn test_histograms_are_cumulative_per_kdf() {
+        let timings = KdfTimings::new();
+        let timing = |algorithm, millis| KdfTiming {
+            kdf: KdfParams { algorithm, ..KdfParams::default() },
+            derivation: Duration::from_millis(millis),
+            decryption: Duration::from_millis(millis + 1),
+        };
+        timings.record(&timing(KdfAlgorithm::Pbkdf2Sha256, 20));
+        timings.record(&timing(KdfAlgorithm::Pbkdf2Sha256, 300));
+        timings.record(&timing(KdfAlgorithm::Argon2id, 7_000));
+
+        let snapshot = timings.snapshot();
+        assert_eq!(snapshot.iter().map(|(stage, kdf, _)| (*stage, *kdf)).collect::<Vec<_>>(), vec![
+            (KdfStage::Derivation, "argon2id"),
+            (KdfStage::Derivation, "pbkdf2-sha256"),
+            (KdfStage::Decryption, "argon2id"),
+            (KdfStage::Decryption, "pbkdf2-sha256"),
+        ]);
+        let pbkdf2 = &snapshot[1].2;
+        assert_eq!(pbkdf2.buckets, [0, 0, 1, 1, 1, 1, 2, 2, 2, 2]);
+        assert_eq!(pbkdf2.count, 2);
+        assert!((pbkdf2.sum_secs - 0.32).abs() < 1e-9);
+        assert_eq!(snapshot[0].2.buckets, [0; 10]);
+    }
+}
@@ -0,0 +1,216 @@
+//! Key manifests and drift detection between instances
+//!
+//! `GET /keys/manifest` describes every stored key by fingerprint, name, state and expiry —
+//! nothing that reveals key material — counter-signed by the notary key when one is configured.
+//! `POST /keys/compare` takes such a manifest from another instance, the `manifest.json` of a
+//! public key export, or a bare fingerprint list, and reports how the local keystore differs.
+
+use crate::bundle::NotarySignature;
+use crate::canonicalize::canonicalize_value;
+use crate::export::{verify_manifest_signature, ExportManifest};
+use crate::key_verification::decode_public_key;
+use crate::models::{KeyManagementError, KeyPair, KeyState};
+use crate::utils::{compact_fingerprint, public_key_to_fingerprint};
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Schema identifier carried in every key manifest
+pub const KEY_MANIFEST_SCHEMA: &str = "inkan-key-manifest";
+/// Current key manifest schema version
+pub const KEY_MANIFEST_VERSION: u32 = 1;
+/// Domain tag prefixed to the canonical manifest before the notary signs it
+pub const KEY_MANIFEST_CONTEXT: &[u8] = b"inkan-key-manifest-v1";
+
+/// One key as listed in a manifest
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct KeyManifestEntry {
+    pub fingerprint: String,
+    pub name: String,
+    pub state: KeyState,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Every key of one instance, in creation order
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct KeyManifest {
+    pub schema: String,
+    pub version: u32,
+    pub generated_at: DateTime<Utc>,
+    pub keys: Vec<KeyManifestEntry>,
+}
+
+/// A manifest with the notary's signature over its canonical form
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SignedKeyManifest {
+    pub manifest: KeyManifest,
+    pub notary: Option<NotarySignature>,
+}
+
+impl KeyManifest {
+    /// Lists `keys` with their state at `now`; keys whose public key cannot be read are skipped
+    pub fn new(keys: &[KeyPair], now: DateTime<Utc>) -> Self {
+        let mut keys: Vec<&KeyPair> = keys.iter().collect();
+        keys.sort_by_key(|key_pair| (key_pair.created_at, key_pair.id));
+        Self {
+            schema: KEY_MANIFEST_SCHEMA.to_string(),
+            version: KEY_MANIFEST_VERSION,
+            generated_at: now,
+            keys: keys.into_iter()
+                .filter_map(|key_pair| Some(KeyManifestEntry {
+                    fingerprint: public_key_to_fingerprint(&key_pair.public_key).ok()?,
+                    name: key_pair.name.clone(),
+                    state: key_pair.state(now),
+                    expires_at: key_pair.expires_at,
+                }))
+                .collect(),
+        }
+    }
+
+    fn message(&self) -> Result<Vec<u8>, KeyManagementError> {
+        let value = serde_json::to_value(self)
+            .map_err(|e| KeyManagementError::InternalError(format!("Failed to serialize key manifest: {}", e)))?;
+        let mut message = KEY_MANIFEST_CONTEXT.to_vec();
+        message.push(0);
+        message.extend_from_slice(canonicalize_value(&value)?.as_bytes());
+        Ok(message)
+    }
+
+    /// Signs the manifest with the notary key, if one is available
+    pub fn sign(self, notary: Option<(uuid::Uuid, &SigningKey)>) -> Result<SignedKeyManifest, KeyManagementError> {
+        let notary = match notary {
+            Some((key_id, key)) => Some(NotarySignature {
+                key_id,
+                public_key: base64::engine::general_purpose::STANDARD.encode(key.verifying_key().to_bytes()),
+                signature: base64::engine::general_purpose::STANDARD.encode(key.sign(&self.message()?).to_bytes()),
+            }),
+            None => None,
+        };
+        Ok(SignedKeyManifest { manifest: self, notary })
+    }
+}
+
+impl SignedKeyManifest {
+    /// Checks the notary signature; `Ok(false)` when the manifest is unsigned
+    pub fn verify(&self) -> Result<bool, KeyManagementError> {
+        let Some(notary) = &self.notary else { return Ok(false) };
+        let invalid = || KeyManagementError::SignatureVerificationFailed("Key manifest signature is invalid".to_string());
+        let public_key = decode_public_key(&notary.public_key)?;
+        let signature = base64::engine::general_purpose::STANDARD.decode(&notary.signature)
+            .ok()
+            .and_then(|bytes| <[u8; 64]>::try_from(bytes.as_slice()).ok())
+            .map(|bytes| Signature::from_bytes(&bytes))
+            .ok_or_else(invalid)?;
+        public_key.verify(&self.manifest.message()?, &signature).map_err(|_| invalid())?;
+        Ok(true)
+    }
+}
+
+/// Checks the notary signature of an export's `manifest.json`; `Ok(false)` when unsigned
+///
+/// The export writes its manifest in canonical form, so canonicalizing the parsed manifest
+/// reproduces the bytes that were signed.
+pub fn verify_export_manifest(manifest: &ExportManifest, signature: Option<&NotarySignature>) -> Result<bool, KeyManagementError> {
+    let Some(signature) = signature else { return Ok(false) };
+    let value = serde_json::to_value(manifest)
+        .map_err(|e| KeyManagementError::InternalError(format!("Failed to serialize export manifest: {}", e)))?;
+    verify_manifest_signature(canonicalize_value(&value)?.as_bytes(), signature)?;
+    Ok(true)
+}
+
+/// A key the other side lists, described from the local keystore
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ComparedKey {
+    pub fingerprint: String,
+    pub name: String,
+    pub state: KeyState,
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Fields the other side records differently: `name`, `state` or `expires_at`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub differences: Vec<String>,
+}
+
+/// How the local keystore differs from a reference list of keys
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct KeyComparison {
+    /// Listed keys stored here and not revoked
+    pub present: Vec<ComparedKey>,
+    /// Listed keys stored here but revoked
+    pub revoked: Vec<ComparedKey>,
+    /// Listed fingerprints with no key here
+    pub absent: Vec<String>,
+    /// Keys stored here that the list does not mention
+    pub local_only: Vec<KeyManifestEntry>,
+    /// Nothing is absent, revoked, local-only or recorded differently
+    pub in_sync: bool,
+}
+
+/// Compares the local manifest against `expected`, keyed by fingerprint
+///
+/// Entries known only by fingerprint are compared by presence alone. Fingerprints are matched
+/// with or without their colons and in any case.
+pub fn compare_keys(local: &KeyManifest, expected: &[(String, Option<KeyManifestEntry>)]) -> KeyComparison {
+    let mut by_fingerprint: BTreeMap<String, &KeyManifestEntry> = local.keys.iter()
+        .filter_map(|entry| Some((compact_fingerprint(&entry.fingerprint)?, entry)))
+        .collect();
+    let mut comparison = KeyComparison::default();
+
+    for (fingerprint, remote) in expected {
+        let Some(entry) = compact_fingerprint(fingerprint).and_then(|compact| by_fingerprint.remove(&compact)) else {
+            comparison.absent.push(fingerprint.clone());
+            continue;
+        };
+        let differences = match remote {
+            Some(remote) => [
+                ("name", remote.name != entry.name),
+                ("state", remote.state != entry.state),
+                ("expires_at", remote.expires_at != entry.expires_at),
+            ].into_iter().filter(|(_, differs)| *differs).map(|(field, _)| field.to_string()).collect(),
+            None => Vec::new(),
+        };
+        let compared = ComparedKey {
+            fingerprint: entry.fingerprint.clone(),
+            name: entry.name.clone(),
+            state: entry.state,
+            expires_at: entry.expires_at,
+            differences,
+        };
+        if entry.state == KeyState::Revoked {
+            comparison.revoked.push(compared);
+        } else {
+            comparison.present.push(compared);
+        }
+    }
+
+    comparison.local_only = local.keys.iter()
+        .filter(|entry| compact_fingerprint(&entry.fingerprint).is_some_and(|compact| by_fingerprint.contains_key(&compact)))
+        .cloned()
+        .collect();
+    comparison.in_sync = comparison.absent.is_empty()
+        && comparison.revoked.is_empty()
+        && comparison.local_only.is_empty()
+        && comparison.present.iter().all(|key| key.differences.is_empty());
+    comparison
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_generation::generate_seeded_test_key_pair;
+
+    #[test]
+    fn test_signed_manifest_detects_tampering() {
+        let notary = SigningKey::from_bytes(&[7u8; 32]);
+        let keys = [generate_seeded_test_key_pair("Staging Root", 0)];
+        let signed = KeyManifest::new(&keys, Utc::now()).sign(Some((uuid::Uuid::nil(), &notary))).unwrap();
+        assert_eq!(signed.manifest.keys[0].fingerprint, "8d6470fa:2d00d290:d7eb1df2:c16964ae");
+        assert!(signed.verify().unwrap());
+
+        let mut tampered = signed.clone();
+        tampered.manifest.keys[0].state = KeyState::Revoked;
+        assert!(tampered.verify().is_err());
+        assert!(!SignedKeyManifest { notary: None, ..signed }.verify().unwrap());
+    }
+}
@@ -0,0 +1,106 @@
+//! Disclosure of key state to callers without read scope
+//!
+//! A key id is a UUID, so telling a caller that a key is revoked or expired, rather than not
+//! found, tells them it exists. On the routes in [`CONCEALING_ROUTES`], a caller without read
+//! scope gets one `404 KEY_NOT_FOUND` body whether the key is missing, revoked or expired, and
+//! changes to such a key are refused before they are made. The listings in [`FILTERING_ROUTES`]
+//! show such a caller only usable keys, with counts of those alone. Read scope belongs to the
+//! signing clients in `INKAN_READ_CLIENTS` and `INKAN_ADMIN_CLIENTS`; with request signing off,
+//! no caller has it.
+//!
+//! Every answer on these routes to a caller without read scope is held back until
+//! [`CONCEALED_FAILURE_FLOOR`] after the request arrived, so the time a lookup, a listing or a
+//! failed signature takes does not tell the cases apart.
+
+use crate::config::Config;
+use crate::models::ErrorCode;
+use axum::http::Method;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Routes whose key failures are concealed, as method and route pattern
+pub const CONCEALING_ROUTES: &[(Method, &str)] = &[
+    (Method::GET, "/keys/:key_id"),
+    (Method::GET, "/keys/:key_id/public"),
+    (Method::PUT, "/keys/:key_id"),
+    (Method::PATCH, "/keys/:key_id"),
+    (Method::DELETE, "/keys/:key_id"),
+    (Method::POST, "/keys/:key_id/revoke"),
+    (Method::POST, "/keys/:key_id/suspend"),
+    (Method::POST, "/keys/:key_id/resume"),
+    (Method::POST, "/sign"),
+    (Method::POST, "/sign/raw"),
+];
+
+/// Listings that show only usable keys to callers without read scope
+pub const FILTERING_ROUTES: &[(Method, &str)] = &[
+    (Method::GET, "/keys"),
+    (Method::GET, "/keys/search"),
+];
+
+/// Marks a request whose caller may see only usable keys, for the handlers of [`FILTERING_ROUTES`]
+#[derive(Debug, Clone, Copy)]
+pub struct UsableKeysOnly;
+
+/// Codes that would tell a caller a key exists
+pub const CONCEALED_CODES: &[ErrorCode] = &[
+    ErrorCode::KeyNotFound,
+    ErrorCode::KeyRevoked,
+    ErrorCode::KeyExpired,
+    ErrorCode::KeyAlreadyRevoked,
+    ErrorCode::KeySuspended,
+    ErrorCode::InvalidTransition,
+];
+
+/// Earliest an answer to a caller without read scope is sent, counted from the request's arrival
+pub const CONCEALED_FAILURE_FLOOR: Duration = Duration::from_millis(25);
+
+/// Message of every concealed failure
+pub const CONCEALED_MESSAGE: &str = "Key not found";
+
+/// Whether a route conceals key state from callers without read scope
+pub fn conceals(method: &Method, route: &str) -> bool {
+    CONCEALING_ROUTES.iter().any(|(concealing_method, concealing_route)| concealing_method == method && *concealing_route == route)
+}
+
+/// Whether a listing shows only usable keys to callers without read scope
+pub fn filters(method: &Method, route: &str) -> bool {
+    FILTERING_ROUTES.iter().any(|(filtering_method, filtering_route)| filtering_method == method && *filtering_route == route)
+}
+
+/// Whether the signing client `client` may learn why a key cannot be used
+pub fn has_read_scope(config: &Config, client: Option<&str>) -> bool {
+    client.is_some_and(|client| config.read_clients.contains(client)) || crate::request_auth::has_admin_scope(config, client)
+}
+
+/// Key named by a `/keys/:key_id` path
+pub fn path_key_id(path: &str) -> Option<Uuid> {
+    path.strip_prefix("/keys/")?.split('/').next()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_read_and_admin_clients_see_key_state() {
+        let config = Config {
+            admin_clients: ["ops".to_string()].into_iter().collect(),
+            read_clients: ["audit".to_string()].into_iter().collect(),
+            ..Config::default()
+        };
+        assert!(has_read_scope(&config, Some("ops")));
+        assert!(has_read_scope(&config, Some("audit")));
+        assert!(!has_read_scope(&config, Some("billing")));
+        assert!(!has_read_scope(&config, None));
+
+        assert!(conceals(&Method::PATCH, "/keys/:key_id"));
+        assert!(conceals(&Method::DELETE, "/keys/:key_id"));
+        assert!(!conceals(&Method::GET, "/keys"));
+        assert!(filters(&Method::GET, "/keys/search"));
+        assert!(!filters(&Method::GET, "/keys/:key_id"));
+        let key_id = Uuid::new_v4();
+        assert_eq!(path_key_id(&format!("/keys/{}/revoke", key_id)), Some(key_id));
+        assert_eq!(path_key_id("/keys/stats"), None);
+    }
+}
@@ -0,0 +1,160 @@
+//! Public keys as callers have them
+//!
+//! Verifiers often hold a key as a PEM file, an OpenSSH `ssh-ed25519` line or a JWK rather than
+//! the raw base64 the service stores. The format is told apart by its first characters, and
+//! every format is reduced to the same 32-byte Ed25519 key. Input holding several keys is
+//! refused rather than guessed at.
+
+use crate::export::ED25519_SPKI_PREFIX;
+use crate::key_verification::decode_public_key;
+use crate::models::KeyManagementError;
+use crate::sshsig;
+use base64::Engine;
+use ed25519_dalek::VerifyingKey;
+use serde::{Deserialize, Serialize};
+
+const PEM_BEGIN: &str = "-----BEGIN PUBLIC KEY-----";
+const PEM_END: &str = "-----END PUBLIC KEY-----";
+
+/// The format a supplied public key was recognized in
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PublicKeyInputFormat {
+    /// The 32 key bytes in base64
+    Raw,
+    /// PEM encoded SubjectPublicKeyInfo
+    Pem,
+    /// OpenSSH `ssh-ed25519 AAAA... [comment]` line
+    Ssh,
+    /// JSON Web Key, or a JWK set holding exactly one key
+    Jwk,
+}
+
+/// Parses an Ed25519 public key in any supported format, returning the format it was in
+pub fn parse_public_key(input: &str) -> Result<(VerifyingKey, PublicKeyInputFormat), KeyManagementError> {
+    let input = input.trim();
+    if input.starts_with("-----BEGIN") {
+        parse_pem(input).map(|key| (key, PublicKeyInputFormat::Pem))
+    } else if input.starts_with("ssh-") {
+        parse_ssh(input).map(|key| (key, PublicKeyInputFormat::Ssh))
+    } else if input.starts_with('{') {
+        parse_jwk(input).map(|key| (key, PublicKeyInputFormat::Jwk))
+    } else {
+        decode_public_key(input)
+            .map(|key| (key, PublicKeyInputFormat::Raw))
+            .map_err(|e| encoded_other_format(input).unwrap_or(e))
+    }
+}
+
+/// Parses a public key in any supported format into the raw base64 the service stores
+pub fn normalize_public_key(input: &str) -> Result<(String, PublicKeyInputFormat), KeyManagementError> {
+    let (key, format) = parse_public_key(input)?;
+    Ok((base64::engine::general_purpose::STANDARD.encode(key.as_bytes()), format))
+}
+
+fn invalid(message: &str) -> KeyManagementError {
+    KeyManagementError::InvalidKeyFormat(message.to_string())
+}
+
+fn verifying_key(bytes: &[u8]) -> Result<VerifyingKey, KeyManagementError> {
+    let bytes: [u8; 32] = bytes.try_into().map_err(|_| invalid("Invalid public key length"))?;
+    VerifyingKey::from_bytes(&bytes).map_err(|_| invalid("Invalid public key format"))
+}
+
+fn parse_pem(input: &str) -> Result<VerifyingKey, KeyManagementError> {
+    let blocks = input.matches("-----BEGIN ").count();
+    if blocks > 1 {
+        return Err(KeyManagementError::AmbiguousPublicKey(format!("PEM input holds {} blocks; supply exactly one public key", blocks)));
+    }
+    let body = input.strip_prefix(PEM_BEGIN)
+        .and_then(|rest| rest.strip_suffix(PEM_END))
+        .ok_or_else(|| invalid("PEM public key must be a single BEGIN PUBLIC KEY block"))?;
+    let der = base64::engine::general_purpose::STANDARD.decode(body.split_whitespace().collect::<String>())
+        .map_err(|_| invalid("Invalid PEM public key encoding"))?;
+    let raw = der.strip_prefix(&ED25519_SPKI_PREFIX[..])
+        .ok_or_else(|| invalid("PEM public key is not an Ed25519 SubjectPublicKeyInfo"))?;
+    verifying_key(raw)
+}
+
+fn parse_ssh(input: &str) -> Result<VerifyingKey, KeyManagementError> {
+    let lines = input.lines().filter(|line| !line.trim().is_empty()).count();
+    if lines > 1 {
+        return Err(KeyManagementError::AmbiguousPublicKey(format!("OpenSSH input holds {} lines; supply exactly one public key", lines)));
+    }
+    sshsig::parse_public_key(input)
+}
+
+fn parse_jwk(input: &str) -> Result<VerifyingKey, KeyManagementError> {
+    let value: serde_json::Value = serde_json::from_str(input).map_err(|_| invalid("Invalid JWK JSON"))?;
+    let jwk = match value.get("keys") {
+        Some(serde_json::Value::Array(keys)) if keys.len() == 1 => &keys[0],
+        Some(serde_json::Value::Array(keys)) if keys.is_empty() => return Err(invalid("JWK set holds no keys")),
+        Some(serde_json::Value::Array(keys)) => {
+            return Err(KeyManagementError::AmbiguousPublicKey(format!("JWK set holds {} keys; supply exactly one", keys.len())));
+        }
+        Some(_) => return Err(invalid("JWK set keys must be an array")),
+        None => &value,
+    };
+    if jwk["kty"] != "OKP" || jwk["crv"] != "Ed25519" {
+        return Err(invalid("JWK must be an OKP key on the Ed25519 curve"));
+    }
+    if jwk.get("d").is_some() {
+        return Err(invalid("JWK holds a private key; supply the public key only"));
+    }
+    let x = jwk["x"].as_str().ok_or_else(|| invalid("JWK is missing x"))?;
+    let bytes = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(x.trim_end_matches('='))
+        .map_err(|_| invalid("Invalid JWK x encoding"))?;
+    verifying_key(&bytes)
+}
+
+/// Explains base64 input that decodes to a key in another format, a common mistake
+fn encoded_other_format(input: &str) -> Option<KeyManagementError> {
+    let decoded = base64::engine::general_purpose::STANDARD.decode(input).ok()?;
+    let text = String::from_utf8(decoded).ok()?;
+    let text = text.trim_start();
+    let format = if text.starts_with("-----BEGIN") {
+        "a PEM file"
+    } else if text.starts_with("ssh-") {
+        "an OpenSSH key line"
+    } else if text.starts_with('{') {
+        "a JWK"
+    } else {
+        return None;
+    };
+    Some(KeyManagementError::InvalidKeyFormat(format!(
+        "public_key is the base64 encoding of {}; send it as it is, without encoding it again", format,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::{encode_jwk, encode_pem};
+    use ed25519_dalek::SigningKey;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_every_format_parses_to_the_same_key() {
+        let key = SigningKey::from_bytes(&[7u8; 32]).verifying_key();
+        let raw = base64::engine::general_purpose::STANDARD.encode(key.as_bytes());
+        let jwk = encode_jwk(&key, Uuid::nil());
+        let inputs = [
+            (raw.clone(), PublicKeyInputFormat::Raw),
+            (encode_pem(&key), PublicKeyInputFormat::Pem),
+            (sshsig::encode_public_key(&key, "ci@example"), PublicKeyInputFormat::Ssh),
+            (jwk.to_string(), PublicKeyInputFormat::Jwk),
+            (serde_json::json!({ "keys": [jwk.clone()] }).to_string(), PublicKeyInputFormat::Jwk),
+        ];
+        for (input, format) in inputs {
+            assert_eq!(normalize_public_key(&input).unwrap(), (raw.clone(), format), "{}", input);
+        }
+
+        let two = serde_json::json!({ "keys": [jwk.clone(), jwk] }).to_string();
+        assert!(matches!(parse_public_key(&two), Err(KeyManagementError::AmbiguousPublicKey(_))));
+        let pems = format!("{}{}", encode_pem(&key), encode_pem(&key));
+        assert!(matches!(parse_public_key(&pems), Err(KeyManagementError::AmbiguousPublicKey(_))));
+        let encoded_pem = base64::engine::general_purpose::STANDARD.encode(encode_pem(&key));
+        assert!(matches!(parse_public_key(&encoded_pem), Err(KeyManagementError::InvalidKeyFormat(m)) if m.contains("PEM file")));
+        assert!(parse_public_key("ssh-rsa AAAAB3NzaC1yc2E=").is_err());
+    }
+}
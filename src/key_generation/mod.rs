@@ -1,25 +1,454 @@
-use crate::models::{GenerateKeyRequest, KeyPair, KeyManagementError, KeyType, KeyStrength};
+use crate::config::{Config, KdfAlgorithm, KdfParams};
+use crate::models::{ExpirySource, FieldError, KeyPair, KeyManagementError, KeyType, KeyStrength};
+use crate::timestamps;
 use base64::Engine;
-use chrono::Utc;
-use ed25519_dalek::{SigningKey, VerifyingKey};
-use rand_core::OsRng;
-use rand::Rng;
-use uuid::Uuid;
+use chrono::{DateTime, Duration, Utc};
+use ed25519_dalek::VerifyingKey;
+use std::time::Instant;
 use aes_gcm::{
-    aead::{Aead, KeyInit, AeadCore},
+    aead::{Aead, KeyInit},
     Aes256Gcm, Key, Nonce,
 };
-use sha2::Sha256;
+// Generating and encrypting keys, which verifier-only builds compile out apart from the test
+// fixtures
+#[cfg(any(test, feature = "test-util", not(feature = "verifier-only")))]
+use crate::{
+    environment,
+    models::GenerateKeyRequest,
+    secret::SecretString,
+    text_normalization::{clean_multiline, clean_name, clean_tags},
+};
+#[cfg(not(feature = "verifier-only"))]
+use crate::{
+    capabilities::{HashAlgorithm, KeyCapabilities},
+    models::{HsmKeyRef, KeyInfo, KeyState, SignatureEncoding, SignatureOutputFormat, UpdateKeyRequest},
+    signing_backend::SigningBackend,
+    text_normalization::{grapheme_len, normalize_line, normalize_multiline, same_folded},
+};
+#[cfg(any(test, feature = "test-util", not(feature = "verifier-only")))]
+use aes_gcm::aead::AeadCore;
+#[cfg(any(test, feature = "test-util", not(feature = "verifier-only")))]
+use ed25519_dalek::{SigningKey, SECRET_KEY_LENGTH};
+#[cfg(any(test, feature = "test-util", not(feature = "verifier-only")))]
+use rand_core::{CryptoRngCore, OsRng};
+#[cfg(any(test, feature = "test-util", not(feature = "verifier-only")))]
+use uuid::Uuid;
+
+/// Limits enforced on key generation requests
+pub const MAX_KEY_NAME_LENGTH: usize = 100;
+pub const MAX_DESCRIPTION_LENGTH: usize = 1_000;
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+pub const MAX_TAGS: usize = 20;
+pub const MAX_TAG_LENGTH: usize = 50;
+pub const MAX_ALLOWED_CONTEXTS: usize = 20;
+
+/// Outcome of a generation request that passed validation
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenerateValidation {
+    pub key_type: KeyType,
+    pub key_strength: KeyStrength,
+    /// Expiry the key will be created with once the lifetime policy is applied
+    pub expires_at: Option<DateTime<Utc>>,
+    pub expiry_source: Option<ExpirySource>,
+    pub warnings: Vec<String>,
+}
+
+/// Normalizes a generation request and validates it against the existing keys without
+/// creating anything
+///
+/// Both real and dry-run generation go through this, so a request that validates here is
+/// exactly one the service would accept. The name, description and tags are normalized in
+/// place, see [`crate::text_normalization`]. Every failing field is reported, not just the first.
+#[cfg(not(feature = "verifier-only"))]
+pub fn validate_generate_request(
+    request: &mut GenerateKeyRequest,
+    existing: &[KeyInfo],
+    config: &Config,
+    now: DateTime<Utc>,
+) -> Result<GenerateValidation, Vec<FieldError>> {
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+
+    match normalize_line(&request.name) {
+        Ok(name) => {
+            request.name = name;
+            if let Some(error) = check_name(&request.name) {
+                errors.push(error);
+            } else if existing.iter().any(|key| (key.is_active || key.state == KeyState::Suspended) && same_folded(key.name.trim(), &request.name)) {
+                errors.push(FieldError::new("name", format!("An active key named '{}' already exists", request.name)));
+            }
+        }
+        Err(e) => errors.push(FieldError::new("name", format!("Key name {}", e))),
+    }
+
+    if let Some(limit) = config.max_keys {
+        if existing.len() >= limit {
+            errors.push(FieldError::new("name", format!("Key quota of {} keys reached", limit)));
+        }
+    }
+
+    if let Some(description) = &mut request.description {
+        errors.extend(normalize_description(description));
+    }
+
+    match &mut request.environment {
+        Some(environment) => errors.extend(normalize_environment(environment, config)),
+        None => request.environment = Some(environment::default_for_generation(config)),
+    }
+
+    if request.generate_password {
+        if request.password.is_some() {
+            errors.push(FieldError::new("generate_password", "Cannot be combined with password"));
+        }
+        if request.hsm.is_some() {
+            errors.push(FieldError::new("generate_password", "HSM keys are protected by the HSM PIN and take no password"));
+        }
+    }
+
+    match &request.password {
+        // HSM keys are protected by the device PIN, never by a request password
+        Some(_) if request.hsm.is_some() => {
+            errors.push(FieldError::new("password", "HSM keys are protected by the HSM PIN and take no password"));
+        }
+        Some(password) if password.chars().count() < MIN_PASSWORD_LENGTH => {
+            errors.push(FieldError::new("password", format!("Password must be at least {} characters", MIN_PASSWORD_LENGTH)));
+        }
+        Some(password) if password.trim().is_empty() => {
+            errors.push(FieldError::new("password", "Password cannot be only whitespace"));
+        }
+        Some(_) => {}
+        None if request.hsm.is_some() || request.generate_password => {}
+        None => warnings.push("Private key is not encrypted - not recommended for production".to_string()),
+    }
+
+    if request.fast && request.hsm.is_some() {
+        errors.push(FieldError::new("fast", "HSM keys are generated on the device and cannot come from the key pool"));
+    }
+
+    if let Some(hsm) = &request.hsm {
+        if hsm.label.trim().is_empty() {
+            errors.push(FieldError::new("hsm", "HSM key label cannot be empty"));
+        } else if existing.iter().any(|key| key.hsm.as_ref() == Some(hsm)) {
+            errors.push(FieldError::new("hsm", format!("HSM slot {} label '{}' is already registered", hsm.slot, hsm.label)));
+        }
+    }
+
+    let (expires_at, expiry_source) = apply_lifetime_policy(request.expires_at, config, now);
+    if expiry_source == Some(ExpirySource::Clamped) {
+        warnings.push(format!(
+            "Requested expiry exceeds the maximum lifetime of {} days and was shortened",
+            config.max_key_lifetime_days.unwrap_or_default(),
+        ));
+    }
+    if let Some(expires_at) = expires_at {
+        if let Some(error) = validate_expiry(expires_at, config, now) {
+            errors.push(error);
+        } else if expires_at - now < Duration::days(1) {
+            warnings.push("Key expires in less than a day".to_string());
+        }
+    }
+
+    if let Some(tags) = &mut request.tags {
+        errors.extend(normalize_tags(tags));
+    }
+
+    let key_strength = request.key_strength.clone().unwrap_or_default();
+    if key_strength == KeyStrength::Unknown {
+        errors.push(FieldError::new("key_strength", "Unknown key strength"));
+    }
+
+    errors.extend(check_signature_defaults(
+        request.default_output_format,
+        request.default_hash_algorithm,
+        request.default_encoding,
+        request.hsm.is_some(),
+    ));
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    Ok(GenerateValidation {
+        key_type: match (&request.hsm, request.password.is_some() || request.generate_password) {
+            (Some(_), _) => KeyType::Ed25519Hsm,
+            (None, true) => KeyType::Ed25519Encrypted,
+            (None, false) => KeyType::Ed25519,
+        },
+        key_strength,
+        expires_at,
+        expiry_source,
+        warnings,
+    })
+}
+
+/// Checks the length of a normalized key name
+#[cfg(not(feature = "verifier-only"))]
+fn check_name(name: &str) -> Option<FieldError> {
+    if name.is_empty() {
+        Some(FieldError::new("name", "Key name cannot be empty"))
+    } else if grapheme_len(name) > MAX_KEY_NAME_LENGTH {
+        Some(FieldError::new("name", format!("Key name must be at most {} characters", MAX_KEY_NAME_LENGTH)))
+    } else {
+        None
+    }
+}
+
+/// Normalizes a description in place and checks its length
+#[cfg(not(feature = "verifier-only"))]
+fn normalize_description(description: &mut String) -> Option<FieldError> {
+    match normalize_multiline(description) {
+        Ok(normalized) if grapheme_len(&normalized) > MAX_DESCRIPTION_LENGTH => {
+            Some(FieldError::new("description", format!("Description must be at most {} characters", MAX_DESCRIPTION_LENGTH)))
+        }
+        Ok(normalized) => {
+            *description = normalized;
+            None
+        }
+        Err(e) => Some(FieldError::new("description", format!("Description {}", e))),
+    }
+}
+
+/// Trims an environment in place and checks that it is a configured one
+#[cfg(not(feature = "verifier-only"))]
+fn normalize_environment(environment: &mut String, config: &Config) -> Option<FieldError> {
+    *environment = environment.trim().to_string();
+    (!environment::is_configured(config, environment)).then(|| {
+        FieldError::new("environment", format!("Environment must be one of: {}", config.environments.join(", ")))
+    })
+}
+
+/// Normalizes tags in place and checks their count, lengths and case-insensitive uniqueness
+#[cfg(not(feature = "verifier-only"))]
+fn normalize_tags(tags: &mut [String]) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    if tags.len() > MAX_TAGS {
+        errors.push(FieldError::new("tags", format!("At most {} tags are allowed", MAX_TAGS)));
+    }
+    for tag in tags.iter_mut() {
+        match normalize_line(tag) {
+            Ok(normalized) => *tag = normalized,
+            Err(e) => {
+                errors.push(FieldError::new("tags", format!("Tag '{}' {}", tag.escape_default(), e)));
+                return errors;
+            }
+        }
+    }
+    if tags.iter().any(|tag| tag.is_empty()) {
+        errors.push(FieldError::new("tags", "Tags cannot be empty"));
+    }
+    if let Some(tag) = tags.iter().find(|tag| grapheme_len(tag) > MAX_TAG_LENGTH) {
+        errors.push(FieldError::new("tags", format!("Tag '{}' exceeds {} characters", tag, MAX_TAG_LENGTH)));
+    }
+    let duplicate = tags.iter().enumerate().find(|(i, tag)| tags[..*i].iter().any(|seen| same_folded(seen, tag)));
+    if let Some((_, tag)) = duplicate {
+        errors.push(FieldError::new("tags", format!("Duplicate tag '{}'", tag)));
+    }
+    errors
+}
+
+/// Derives the expiry a generated key receives under the lifetime policy
+///
+/// A missing expiry gets the default lifetime, or the maximum when only that is configured.
+/// An expiry beyond the maximum is shortened to it unless the policy is strict, in which case it
+/// is left for [`validate_expiry`] to reject.
+pub fn apply_lifetime_policy(
+    requested: Option<DateTime<Utc>>,
+    config: &Config,
+    now: DateTime<Utc>,
+) -> (Option<DateTime<Utc>>, Option<ExpirySource>) {
+    let lifetime = |days: u32| timestamps::normalize(now + Duration::days(days.into()));
+    let max_expiry = config.max_key_lifetime_days.map(lifetime);
 
-/// Generates a new Ed25519 key pair for document signing
+    match requested {
+        None => match config.default_key_lifetime_days.or(config.max_key_lifetime_days) {
+            Some(days) => (Some(lifetime(days)), Some(ExpirySource::Defaulted)),
+            None => (None, None),
+        },
+        Some(expires_at) => match max_expiry {
+            Some(max_expiry) if !config.strict_key_lifetime
+                && expires_at - Duration::seconds(config.clock_skew_secs.into()) > max_expiry =>
+            {
+                (Some(max_expiry), Some(ExpirySource::Clamped))
+            }
+            _ => (Some(expires_at), Some(ExpirySource::Requested)),
+        },
+    }
+}
+
+/// Checks a requested expiry against the configured lifetime bounds
+///
+/// The expiry counts as future while it is within `clock_skew_secs` of now, and the same
+/// tolerance is granted on the minimum and maximum lifetime boundaries.
+pub fn validate_expiry(expires_at: DateTime<Utc>, config: &Config, now: DateTime<Utc>) -> Option<FieldError> {
+    let skew = Duration::seconds(config.clock_skew_secs.into());
+
+    if expires_at <= now - skew {
+        return Some(FieldError::new("expires_at", "Expiry date must be in the future"));
+    }
+    if expires_at + skew < now + Duration::seconds(config.min_key_lifetime_secs.into()) {
+        return Some(FieldError::new(
+            "expires_at",
+            format!("Key lifetime must be at least {} seconds", config.min_key_lifetime_secs),
+        ));
+    }
+    if let Some(days) = config.max_key_lifetime_days {
+        if expires_at - skew > now + Duration::days(days.into()) {
+            return Some(FieldError::new("expires_at", format!("Key lifetime must not exceed {} days", days)));
+        }
+    }
+    None
+}
+
+/// Normalizes a metadata update and validates it against the key it applies to
+///
+/// A new name, description or tags are normalized in place and held to the limits of
+/// generation. A new expiry must pass [`validate_expiry`], except on a revoked key, whose
+/// expiry may only be shortened so listings never show it as valid for longer than it was.
+#[cfg(not(feature = "verifier-only"))]
+pub fn validate_update_request(
+    request: &mut UpdateKeyRequest,
+    current: &KeyPair,
+    config: &Config,
+    now: DateTime<Utc>,
+) -> Result<(), Vec<FieldError>> {
+    let mut errors = Vec::new();
+
+    if let Some(name) = &mut request.name {
+        match normalize_line(name) {
+            Ok(normalized) => {
+                errors.extend(check_name(&normalized));
+                *name = normalized;
+            }
+            Err(e) => errors.push(FieldError::new("name", format!("Key name {}", e))),
+        }
+    }
+    if let Some(description) = &mut request.description {
+        errors.extend(normalize_description(description));
+    }
+    if let Some(tags) = &mut request.tags {
+        errors.extend(normalize_tags(tags));
+    }
+    if let Some(environment) = &mut request.environment {
+        errors.extend(normalize_environment(environment, config));
+    }
+
+    if let Some(expires_at) = request.expires_at {
+        if current.state(now) == KeyState::Revoked {
+            if current.expires_at.is_none_or(|current_expiry| expires_at > current_expiry) {
+                errors.push(FieldError::new("expires_at", "The expiry of a revoked key cannot be extended"));
+            }
+        } else if let Some(error) = validate_expiry(expires_at, config, now) {
+            errors.push(error);
+        }
+    }
+
+    if let Some(allowed_contexts) = &request.allowed_contexts {
+        if allowed_contexts.len() > MAX_ALLOWED_CONTEXTS {
+            errors.push(FieldError::new("allowed_contexts", format!("At most {} contexts are allowed", MAX_ALLOWED_CONTEXTS)));
+        }
+        if allowed_contexts.iter().any(|context| context.is_empty() || crate::key_verification::validate_context(Some(context)).is_err()) {
+            errors.push(FieldError::new(
+                "allowed_contexts",
+                format!("Contexts must be between 1 and {} bytes", crate::key_verification::MAX_CONTEXT_LENGTH),
+            ));
+        }
+    }
+
+    // The defaults are checked as they will stand, so changing one cannot strand another
+    if request.default_output_format.is_some() || request.default_hash_algorithm.is_some() || request.default_encoding.is_some() {
+        errors.extend(check_signature_defaults(
+            request.default_output_format.or(current.default_output_format),
+            request.default_hash_algorithm.or(current.default_hash_algorithm),
+            request.default_encoding.or(current.default_encoding),
+            current.hsm.is_some() || current.key_type == KeyType::Ed25519Hsm,
+        ));
+    }
+
+    if errors.is_empty() { Ok(()) } else { Err(errors) }
+}
+
+/// Checks a key's signature defaults against what the key can sign
+///
+/// A default digest has to be one the default output format signs with, or with no default
+/// format one the key signs with at all; `/sign` only applies it to formats that take it. A
+/// default encoding other than base64 is only accepted alongside raw output.
+#[cfg(not(feature = "verifier-only"))]
+fn check_signature_defaults(
+    output_format: Option<SignatureOutputFormat>,
+    hash_algorithm: Option<HashAlgorithm>,
+    encoding: Option<SignatureEncoding>,
+    on_hsm: bool,
+) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    let (hash_algorithms, output_formats) = KeyCapabilities::signing_formats(on_hsm);
+    if let Some(format) = output_format.filter(|format| !output_formats.contains(format)) {
+        errors.push(FieldError::new("default_output_format", format!("This key cannot sign {} output", format.as_str())));
+    }
+    if let Some(hash_algorithm) = hash_algorithm {
+        if !hash_algorithms.contains(&hash_algorithm) {
+            errors.push(FieldError::new("default_hash_algorithm", format!("This key cannot sign with {}", hash_algorithm.as_str())));
+        } else if let Some(format) = output_format.filter(|format| !HashAlgorithm::for_format(*format).contains(&hash_algorithm)) {
+            errors.push(FieldError::new(
+                "default_hash_algorithm",
+                format!("{} output does not sign with {}", format.as_str(), hash_algorithm.as_str()),
+            ));
+        }
+    }
+    if let Some(format) = output_format.filter(|format| *format != SignatureOutputFormat::Raw) {
+        if encoding.is_some_and(|encoding| encoding != SignatureEncoding::Base64) {
+            errors.push(FieldError::new("default_encoding", format!("{} output is always base64; encoding applies to raw signatures only", format.as_str())));
+        }
+    }
+    errors
+}
+
+/// Generates a new Ed25519 key pair for document signing, created at the system time
+#[cfg(any(test, feature = "test-util", not(feature = "verifier-only")))]
 pub fn generate_key_pair(
     request: GenerateKeyRequest,
 ) -> Result<KeyPair, KeyManagementError> {
-    // Generate a cryptographically secure Ed25519 key pair
-    let mut rng = OsRng;
+    generate_key_pair_with_kdf(request, &KdfParams::default())
+}
+
+/// Generates a new Ed25519 key pair, encrypting it with the given KDF parameters, created at the
+/// system time
+#[cfg(any(test, feature = "test-util", not(feature = "verifier-only")))]
+pub fn generate_key_pair_with_kdf(
+    request: GenerateKeyRequest,
+    kdf: &KdfParams,
+) -> Result<KeyPair, KeyManagementError> {
+    generate_key_pair_with_rng(request, kdf, &mut OsRng, Utc::now())
+}
+
+/// Generates a new Ed25519 key pair with its seed drawn from `rng`
+///
+/// Only the key material comes from `rng`; salts and nonces for encryption always come from
+/// the OS, so a deterministic `rng` never makes an encrypted key predictable to decrypt.
+#[cfg(any(test, feature = "test-util", not(feature = "verifier-only")))]
+pub(crate) fn generate_key_pair_with_rng<R: CryptoRngCore + ?Sized>(
+    request: GenerateKeyRequest,
+    kdf: &KdfParams,
+    rng: &mut R,
+    now: DateTime<Utc>,
+) -> Result<KeyPair, KeyManagementError> {
+    // Draw the seed fallibly so an RNG failure is an error rather than a panic
+    let mut seed = [0u8; SECRET_KEY_LENGTH];
+    rng.try_fill_bytes(&mut seed)
+        .map_err(|e| KeyManagementError::InternalError(format!("Random number generator failed: {}", e)))?;
+    generate_key_pair_from_seed(request, kdf, &seed, now)
+}
+
+/// Generates a new Ed25519 key pair from a seed drawn by the caller, created at `now`
+#[cfg(any(test, feature = "test-util", not(feature = "verifier-only")))]
+pub fn generate_key_pair_from_seed(
+    request: GenerateKeyRequest,
+    kdf: &KdfParams,
+    seed: &[u8; SECRET_KEY_LENGTH],
+    now: DateTime<Utc>,
+) -> Result<KeyPair, KeyManagementError> {
     tracing::info!("DEBUG: About to generate signing key");
     
-    let signing_key = SigningKey::generate(&mut rng);
+    let signing_key = SigningKey::from_bytes(seed);
     
     tracing::info!("DEBUG: Signing key generated successfully");
     let verifying_key = signing_key.verifying_key();
@@ -34,10 +463,11 @@ pub fn generate_key_pair(
     tracing::info!("DEBUG: About to handle private key encryption");
     let (encrypted_private_key, salt) = if let Some(password) = &request.password {
         tracing::info!("DEBUG: Encrypting private key with password");
-        match encrypt_private_key(&private_key_bytes, password) {
-            Ok(result) => {
+        // The envelope carries its own salt, so the separate salt column stays empty
+        match encrypt_private_key(&private_key_bytes, password, kdf) {
+            Ok(envelope) => {
                 tracing::info!("DEBUG: Private key encrypted successfully");
-                result
+                (envelope, None)
             },
             Err(e) => {
                 tracing::error!("DEBUG: Failed to encrypt private key: {:?}", e);
@@ -47,7 +477,7 @@ pub fn generate_key_pair(
     } else {
         tracing::info!("DEBUG: Storing private key unencrypted");
         // For development, store unencrypted (not recommended for production)
-        (base64::engine::general_purpose::STANDARD.encode(&private_key_bytes), None)
+        (base64::engine::general_purpose::STANDARD.encode(private_key_bytes), None)
     };
     
     // Convert to base64 for storage
@@ -62,42 +492,217 @@ pub fn generate_key_pair(
     
     let key_strength = request.key_strength.unwrap_or(KeyStrength::Standard);
     
+    let fingerprint = crate::utils::public_key_to_fingerprint(&public_key_b64).ok();
+    
     // Create key pair record
     let key_pair = KeyPair {
         id: Uuid::new_v4(),
-        name: request.name,
-        description: request.description,
+        name: clean_name(&request.name),
+        description: request.description.as_deref().map(clean_multiline),
         public_key: public_key_b64,
-        private_key: encrypted_private_key,
+        private_key: SecretString::from(encrypted_private_key),
         salt,
-        created_at: Utc::now(),
+        created_at: timestamps::normalize(now),
         last_used: None,
-        expires_at: request.expires_at,
-        is_active: true,
-        tags: request.tags.unwrap_or_default(),
+        expires_at: request.expires_at.map(timestamps::normalize),
+        lifecycle: Default::default(),
+        tags: clean_tags(&request.tags.unwrap_or_default()),
         key_type,
         key_strength,
+        kdf: request.password.as_ref().map(|_| *kdf),
+        fingerprint,
+        revocation_scheduled_at: None,
+        usage: Default::default(),
+        notified_thresholds: Default::default(),
+        hsm: None,
+        allowed_contexts: None,
+        metadata_history: Vec::new(),
+        lifecycle_history: Vec::new(),
+        envelope_history: Vec::new(),
+        exportable: request.exportable.unwrap_or(true),
+        default_output_format: request.default_output_format,
+        default_hash_algorithm: request.default_hash_algorithm,
+        default_encoding: request.default_encoding,
+        environment: request.environment.unwrap_or_else(environment::unknown_environment),
     };
     
     Ok(key_pair)
 }
 
-/// Encrypts a private key using AES-256-GCM with a password-derived key
-fn encrypt_private_key(
+/// Generates a new Ed25519 key on an HSM, recording only its public key and device reference,
+/// created at `now`
+#[cfg(not(feature = "verifier-only"))]
+pub fn generate_hsm_key_pair(
+    request: GenerateKeyRequest,
+    hsm: HsmKeyRef,
+    backend: &dyn SigningBackend,
+    now: DateTime<Utc>,
+) -> Result<KeyPair, KeyManagementError> {
+    let public_key = backend.generate(&hsm)?;
+    let public_key_b64 = base64::engine::general_purpose::STANDARD.encode(public_key.to_bytes());
+
+    Ok(KeyPair {
+        id: Uuid::new_v4(),
+        name: clean_name(&request.name),
+        description: request.description.as_deref().map(clean_multiline),
+        fingerprint: crate::utils::public_key_to_fingerprint(&public_key_b64).ok(),
+        public_key: public_key_b64,
+        private_key: SecretString::default(),
+        salt: None,
+        created_at: timestamps::normalize(now),
+        last_used: None,
+        expires_at: request.expires_at.map(timestamps::normalize),
+        lifecycle: Default::default(),
+        tags: clean_tags(&request.tags.unwrap_or_default()),
+        key_type: KeyType::Ed25519Hsm,
+        key_strength: request.key_strength.unwrap_or(KeyStrength::Standard),
+        kdf: None,
+        revocation_scheduled_at: None,
+        usage: Default::default(),
+        notified_thresholds: Default::default(),
+        hsm: Some(hsm),
+        allowed_contexts: None,
+        metadata_history: Vec::new(),
+        lifecycle_history: Vec::new(),
+        envelope_history: Vec::new(),
+        exportable: request.exportable.unwrap_or(true),
+        default_output_format: request.default_output_format,
+        default_hash_algorithm: request.default_hash_algorithm,
+        default_encoding: request.default_encoding,
+        environment: request.environment.unwrap_or_else(environment::unknown_environment),
+    })
+}
+
+/// Magic prefix identifying a self-describing encrypted key envelope
+pub const ENVELOPE_MAGIC: &[u8; 3] = b"IKE";
+/// Current encrypted key envelope version
+pub const ENVELOPE_VERSION: u8 = 1;
+
+const NONCE_LEN: usize = 12;
+
+/// Self-describing encrypted private key
+///
+/// Serialized as `magic || version || kdf id || iterations || memory_kib || parallelism ||
+/// salt length || salt || nonce || ciphertext`, with integers as big-endian u32, so everything
+/// needed to decrypt travels in the single `private_key` field.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncryptedKeyEnvelope {
+    pub kdf: KdfParams,
+    pub salt: Vec<u8>,
+    pub nonce: [u8; NONCE_LEN],
+    pub ciphertext: Vec<u8>,
+}
+
+impl EncryptedKeyEnvelope {
+    fn kdf_id(algorithm: KdfAlgorithm) -> u8 {
+        match algorithm {
+            KdfAlgorithm::Pbkdf2Sha256 => 1,
+            KdfAlgorithm::Argon2id => 2,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(18 + self.salt.len() + NONCE_LEN + self.ciphertext.len());
+        bytes.extend_from_slice(ENVELOPE_MAGIC);
+        bytes.push(ENVELOPE_VERSION);
+        bytes.push(Self::kdf_id(self.kdf.algorithm));
+        bytes.extend_from_slice(&self.kdf.iterations.to_be_bytes());
+        bytes.extend_from_slice(&self.kdf.memory_kib.to_be_bytes());
+        bytes.extend_from_slice(&self.kdf.parallelism.to_be_bytes());
+        bytes.push(self.salt.len() as u8);
+        bytes.extend_from_slice(&self.salt);
+        bytes.extend_from_slice(&self.nonce);
+        bytes.extend_from_slice(&self.ciphertext);
+        bytes
+    }
+
+    /// Parses an envelope, rejecting unknown versions and truncated data
+    pub fn parse(bytes: &[u8]) -> Result<Self, KeyManagementError> {
+        let corrupted = |what: &str| KeyManagementError::InvalidKeyFormat(format!("Corrupted key envelope: {}", what));
+
+        let rest = bytes.strip_prefix(ENVELOPE_MAGIC.as_slice()).ok_or_else(|| corrupted("missing magic"))?;
+        let (&version, rest) = rest.split_first().ok_or_else(|| corrupted("missing version"))?;
+        if version != ENVELOPE_VERSION {
+            return Err(KeyManagementError::InvalidKeyFormat(format!("Unsupported key envelope version {}", version)));
+        }
+        let (&kdf_id, rest) = rest.split_first().ok_or_else(|| corrupted("missing KDF id"))?;
+        let algorithm = match kdf_id {
+            1 => KdfAlgorithm::Pbkdf2Sha256,
+            2 => KdfAlgorithm::Argon2id,
+            other => return Err(KeyManagementError::InvalidKeyFormat(format!("Unknown key envelope KDF id {}", other))),
+        };
+        if rest.len() < 13 {
+            return Err(corrupted("truncated KDF parameters"));
+        }
+        let read_u32 = |at: usize| u32::from_be_bytes([rest[at], rest[at + 1], rest[at + 2], rest[at + 3]]);
+        let kdf = KdfParams {
+            algorithm,
+            iterations: read_u32(0),
+            memory_kib: read_u32(4),
+            parallelism: read_u32(8),
+        };
+        // Parameters are attacker-controlled if the keystore is, so bound the work they demand
+        kdf.check_bounds().map_err(|e| corrupted(&e.to_string()))?;
+        let salt_len = rest[12] as usize;
+        let rest = &rest[13..];
+        if rest.len() < salt_len + NONCE_LEN + 16 {
+            return Err(corrupted("truncated salt, nonce or ciphertext"));
+        }
+        let (salt, rest) = rest.split_at(salt_len);
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+        Ok(Self {
+            kdf,
+            salt: salt.to_vec(),
+            nonce: nonce.try_into().map_err(|_| corrupted("invalid nonce"))?,
+            ciphertext: ciphertext.to_vec(),
+        })
+    }
+}
+
+/// Returns true when the stored private key bytes are an encrypted key envelope
+pub fn is_key_envelope(private_key_bytes: &[u8]) -> bool {
+    private_key_bytes.starts_with(ENVELOPE_MAGIC)
+}
+
+/// Returns true when a key is encrypted in the legacy nonce+ciphertext layout with a separate salt
+pub fn is_legacy_encrypted_key(key_pair: &KeyPair) -> bool {
+    match base64::engine::general_purpose::STANDARD.decode(key_pair.private_key.expose_for_persistence()) {
+        Ok(bytes) => bytes.len() > 64 && !is_key_envelope(&bytes),
+        Err(_) => false,
+    }
+}
+
+/// Re-encrypts a legacy key as an envelope, keeping its KDF parameters
+///
+/// Returns `Ok(None)` when the key does not use the legacy layout.
+#[cfg(not(feature = "verifier-only"))]
+pub fn upgrade_legacy_private_key(key_pair: &KeyPair, password: &str) -> Result<Option<String>, KeyManagementError> {
+    if !is_legacy_encrypted_key(key_pair) {
+        return Ok(None);
+    }
+    let kdf = key_pair.kdf.unwrap_or_default();
+    let (private_key, salt) = key_pair.signing_secrets();
+    let private_key_bytes = decrypt_private_key(private_key, password, salt, &kdf)?;
+    encrypt_private_key(&private_key_bytes, password, &kdf).map(Some)
+}
+
+/// Encrypts a private key using AES-256-GCM with a password-derived key, returning a base64 envelope
+#[cfg(any(test, feature = "test-util", not(feature = "verifier-only")))]
+pub(crate) fn encrypt_private_key(
     private_key: &[u8],
     password: &str,
-) -> Result<(String, Option<String>), KeyManagementError> {
+    kdf: &KdfParams,
+) -> Result<String, KeyManagementError> {
+    #[cfg(any(test, feature = "failpoints"))]
+    crate::failpoints::fault(crate::failpoints::KEY_ENCRYPT)
+        .map_err(|e| KeyManagementError::InternalError(format!("Encryption failed: {}", e)))?;
+
     // Generate a random salt
     let salt = rand::random::<[u8; 32]>();
     
-    // Derive key from password using PBKDF2
-    let mut key = [0u8; 32];
-    pbkdf2::pbkdf2::<hmac::Hmac<sha2::Sha256>>(
-        password.as_bytes(),
-        &salt,
-        100_000, // 100k iterations
-        &mut key,
-    ).map_err(|_| KeyManagementError::InternalError("PBKDF2 key derivation failed".to_string()))?;
+    // Derive key from password using the configured KDF
+    let key = kdf.derive_key(password.as_bytes(), &salt)?;
     
     // Create AES-256-GCM cipher
     let cipher_key = Key::<Aes256Gcm>::from_slice(&key);
@@ -111,27 +716,70 @@ fn encrypt_private_key(
         .encrypt(&nonce, private_key)
         .map_err(|e| KeyManagementError::InternalError(format!("Encryption failed: {}", e)))?;
     
-    // Combine nonce and encrypted data
-    let mut combined = Vec::new();
-    combined.extend_from_slice(nonce.as_slice());
-    combined.extend_from_slice(&encrypted_data);
+    // Package salt, KDF parameters, nonce and ciphertext together
+    let envelope = EncryptedKeyEnvelope {
+        kdf: *kdf,
+        salt: salt.to_vec(),
+        nonce: nonce.into(),
+        ciphertext: encrypted_data,
+    };
     
     // Encode as base64
-    let encrypted_b64 = base64::engine::general_purpose::STANDARD.encode(&combined);
-    let salt_b64 = base64::engine::general_purpose::STANDARD.encode(&salt);
-    
-    Ok((encrypted_b64, Some(salt_b64)))
+    Ok(base64::engine::general_purpose::STANDARD.encode(envelope.to_bytes()))
+}
+
+/// Time spent unlocking an encrypted private key
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KdfTiming {
+    /// Parameters the key was encrypted under
+    pub kdf: KdfParams,
+    /// Deriving the encryption key from the password
+    pub derivation: std::time::Duration,
+    /// The whole decryption, derivation included
+    pub decryption: std::time::Duration,
 }
 
 /// Decrypts a private key using the provided password
+///
+/// Envelopes carry their own salt and KDF parameters. For keys in the legacy layout the
+/// separately stored `salt` and `kdf` are used instead.
 pub fn decrypt_private_key(
     encrypted_private_key: &str,
     password: &str,
     salt: Option<&str>,
+    kdf: &KdfParams,
 ) -> Result<Vec<u8>, KeyManagementError> {
+    decrypt_private_key_timed(encrypted_private_key, password, salt, kdf).map(|(private_key, _)| private_key)
+}
+
+/// Decrypts a private key like [`decrypt_private_key`], also reporting how long it took
+pub fn decrypt_private_key_timed(
+    encrypted_private_key: &str,
+    password: &str,
+    salt: Option<&str>,
+    kdf: &KdfParams,
+) -> Result<(Vec<u8>, KdfTiming), KeyManagementError> {
+    let started = Instant::now();
+    let timing = |kdf: KdfParams, derivation| KdfTiming { kdf, derivation, decryption: started.elapsed() };
+
     // Decode the encrypted data
     let encrypted_data = base64::engine::general_purpose::STANDARD.decode(encrypted_private_key)
         .map_err(|_| KeyManagementError::InvalidKeyFormat("Invalid encrypted key encoding".to_string()))?;
+    #[cfg(any(test, feature = "failpoints"))]
+    let encrypted_data = crate::failpoints::corrupt(crate::failpoints::KEY_DECRYPT, encrypted_data)
+        .map_err(|e| KeyManagementError::PrivateKeyDecryptionFailed(e.to_string()))?;
+    
+    if is_key_envelope(&encrypted_data) {
+        let envelope = EncryptedKeyEnvelope::parse(&encrypted_data)?;
+        let deriving = Instant::now();
+        let key = envelope.kdf.derive_key(password.as_bytes(), &envelope.salt)?;
+        let derivation = deriving.elapsed();
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+        return cipher
+            .decrypt(Nonce::from_slice(&envelope.nonce), envelope.ciphertext.as_slice())
+            .map(|private_key| (private_key, timing(envelope.kdf, derivation)))
+            .map_err(|_| KeyManagementError::PrivateKeyDecryptionFailed("Invalid password or corrupted data".to_string()));
+    }
     
     if encrypted_data.len() < 12 {
         return Err(KeyManagementError::InvalidKeyFormat("Encrypted data too short".to_string()));
@@ -149,14 +797,10 @@ pub fn decrypt_private_key(
         return Err(KeyManagementError::InvalidRequest("Salt required for encrypted keys".to_string()));
     };
     
-    // Derive key from password
-    let mut key = [0u8; 32];
-    pbkdf2::pbkdf2::<hmac::Hmac<sha2::Sha256>>(
-        password.as_bytes(),
-        &salt_bytes,
-        100_000, // 100k iterations
-        &mut key,
-    ).map_err(|_| KeyManagementError::InternalError("PBKDF2 key derivation failed".to_string()))?;
+    // Derive key from password with the parameters the key was encrypted under
+    let deriving = Instant::now();
+    let key = kdf.derive_key(password.as_bytes(), &salt_bytes)?;
+    let derivation = deriving.elapsed();
     
     // Create AES-256-GCM cipher
     let cipher_key = Key::<Aes256Gcm>::from_slice(&key);
@@ -170,7 +814,7 @@ pub fn decrypt_private_key(
         .decrypt(nonce, encrypted_content)
         .map_err(|_| KeyManagementError::PrivateKeyDecryptionFailed("Invalid password or corrupted data".to_string()))?;
     
-    Ok(decrypted_data)
+    Ok((decrypted_data, timing(*kdf, derivation)))
 }
 
 /// Validates a key pair to ensure it's properly formatted
@@ -192,8 +836,13 @@ pub fn validate_key_pair(key_pair: &KeyPair) -> Result<(), KeyManagementError> {
     VerifyingKey::from_bytes(&public_key_array)
         .map_err(|_| KeyManagementError::InvalidKeyFormat("Invalid Ed25519 public key".to_string()))?;
     
+    // An ephemeral key's tombstone keeps only the public key
+    if key_pair.key_type == KeyType::Ed25519Ephemeral {
+        return Ok(());
+    }
+
     // Validate private key format (encrypted or unencrypted)
-    let private_key_bytes = base64::engine::general_purpose::STANDARD.decode(&key_pair.private_key)
+    let private_key_bytes = base64::engine::general_purpose::STANDARD.decode(key_pair.private_key.expose_for_persistence())
         .map_err(|_| KeyManagementError::InvalidKeyFormat("Invalid private key encoding".to_string()))?;
     
     // Check if it's encrypted (should be longer than 64 bytes due to nonce + encrypted data)
@@ -207,6 +856,7 @@ pub fn validate_key_pair(key_pair: &KeyPair) -> Result<(), KeyManagementError> {
 }
 
 /// Generates a key pair with additional metadata
+#[cfg(not(feature = "verifier-only"))]
 pub fn generate_key_pair_with_metadata(
     name: String,
     description: Option<String>,
@@ -220,6 +870,15 @@ pub fn generate_key_pair_with_metadata(
         expires_at: None,
         tags,
         key_strength: None,
+        hsm: None,
+        generate_password: false,
+        template: None,
+        environment: None,
+        fast: false,
+        exportable: None,
+        default_output_format: None,
+        default_hash_algorithm: None,
+        default_encoding: None,
     };
     
     let key_pair = generate_key_pair(request)?;
@@ -240,11 +899,97 @@ pub fn generate_test_key_pair(name: &str) -> Result<KeyPair, KeyManagementError>
         expires_at: None,
         tags: None,
         key_strength: None,
+        hsm: None,
+        generate_password: false,
+        template: None,
+        environment: None,
+        fast: false,
+        exportable: None,
+        default_output_format: None,
+        default_hash_algorithm: None,
+        default_encoding: None,
     };
     
     generate_key_pair(request)
 }
 
+/// Generates an unencrypted key pair whose key material is fixed by `seed`
+///
+/// The seed drives a ChaCha20 RNG, so the same seed yields the same keys on every platform and
+/// run; the id and timestamps are still fresh. Only test builds and the `test-util` feature can
+/// reach this. Public keys of the first seeds, checked by a snapshot test:
+///
+/// | Seed | Public key | Fingerprint |
+/// |------|------------|-------------|
+/// | 0 | `7dD23jQqHmpyNtYkTyPYPu387NBZo4bIUFVwFJjncDM=` | `8d6470fa:2d00d290:d7eb1df2:c16964ae` |
+/// | 1 | `R4uOUH4LsrGMD54IJHaehWLRDfmr4ud0iW+CtLRAUmY=` | `f07815cb:38d0a5cd:0519604d:319662b5` |
+/// | 2 | `WSW6huIYlESmw7Q3sl0u812uzRq/gsX7NgYPn8CvQow=` | `7dfcf246:f51248ce:1c5f70c8:2ae6c448` |
+#[cfg(any(test, feature = "test-util"))]
+pub fn generate_seeded_test_key_pair(name: &str, seed: u64) -> KeyPair {
+    use rand_chacha::rand_core::SeedableRng;
+    let request = GenerateKeyRequest {
+        name: name.to_string(),
+        description: None,
+        password: None,
+        expires_at: None,
+        tags: None,
+        key_strength: None,
+        hsm: None,
+        generate_password: false,
+        template: None,
+        environment: None,
+        fast: false,
+        exportable: None,
+        default_output_format: None,
+        default_hash_algorithm: None,
+        default_encoding: None,
+    };
+    let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(seed);
+    generate_key_pair_with_rng(request, &KdfParams::default(), &mut rng, Utc::now()).expect("ChaCha20 never fails")
+}
+
+/// Builds a key in the pre-envelope layout: base64(nonce || ciphertext) plus a separate salt
+#[cfg(test)]
+pub fn generate_legacy_test_key_pair(password: &str) -> KeyPair {
+    let mut key_pair = generate_test_key_pair("Legacy Key").unwrap();
+    let private_key = base64::engine::general_purpose::STANDARD.decode(key_pair.private_key.expose_for_persistence()).unwrap();
+
+    let salt = rand::random::<[u8; 32]>();
+    let key = KdfParams::default().derive_key(password.as_bytes(), &salt).unwrap();
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let mut combined = nonce.to_vec();
+    combined.extend_from_slice(&cipher.encrypt(&nonce, private_key.as_slice()).unwrap());
+
+    key_pair.private_key = base64::engine::general_purpose::STANDARD.encode(combined).into();
+    key_pair.salt = Some(base64::engine::general_purpose::STANDARD.encode(salt).into());
+    key_pair.key_type = KeyType::Ed25519Encrypted;
+    key_pair
+}
+
+/// Builds a key encrypted into an envelope with the given salt, as a script reusing or
+/// truncating salts would
+#[cfg(test)]
+pub fn generate_salted_test_key_pair(name: &str, password: &str, salt: &[u8], kdf: &KdfParams) -> KeyPair {
+    let mut key_pair = generate_test_key_pair(name).unwrap();
+    let private_key = base64::engine::general_purpose::STANDARD.decode(key_pair.private_key.expose_for_persistence()).unwrap();
+
+    let key = kdf.derive_key(password.as_bytes(), salt).unwrap();
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let envelope = EncryptedKeyEnvelope {
+        kdf: *kdf,
+        salt: salt.to_vec(),
+        nonce: nonce.into(),
+        ciphertext: cipher.encrypt(&nonce, private_key.as_slice()).unwrap(),
+    };
+
+    key_pair.private_key = base64::engine::general_purpose::STANDARD.encode(envelope.to_bytes()).into();
+    key_pair.kdf = Some(*kdf);
+    key_pair.key_type = KeyType::Ed25519Encrypted;
+    key_pair
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -258,13 +1003,22 @@ mod tests {
             expires_at: None,
             tags: None,
             key_strength: None,
+            hsm: None,
+            generate_password: false,
+            template: None,
+            environment: None,
+            fast: false,
+            exportable: None,
+            default_output_format: None,
+            default_hash_algorithm: None,
+            default_encoding: None,
         };
         
         let key_pair = generate_key_pair(request).unwrap();
         
         assert_eq!(key_pair.name, "Test Key");
         assert_eq!(key_pair.description, Some("Test key for unit testing".to_string()));
-        assert!(key_pair.is_active);
+        assert_eq!(key_pair.lifecycle, crate::lifecycle::Lifecycle::Active);
         assert!(key_pair.last_used.is_none());
         
         // Validate the generated key pair
@@ -280,6 +1034,15 @@ mod tests {
             expires_at: None,
             tags: None,
             key_strength: None,
+            hsm: None,
+            generate_password: false,
+            template: None,
+            environment: None,
+            fast: false,
+            exportable: None,
+            default_output_format: None,
+            default_hash_algorithm: None,
+            default_encoding: None,
         };
         
         let key_pair = generate_key_pair(request).unwrap();
@@ -288,7 +1051,7 @@ mod tests {
         validate_key_pair(&key_pair).unwrap();
         
         // The private key should be encrypted (longer than 64 bytes due to nonce + encrypted data)
-        let private_key_bytes = base64::engine::general_purpose::STANDARD.decode(&key_pair.private_key).unwrap();
+        let private_key_bytes = base64::engine::general_purpose::STANDARD.decode(key_pair.private_key.expose_for_persistence()).unwrap();
         assert!(private_key_bytes.len() > 64);
     }
     
@@ -301,6 +1064,15 @@ mod tests {
             expires_at: None,
             tags: None,
             key_strength: None,
+            hsm: None,
+            generate_password: false,
+            template: None,
+            environment: None,
+            fast: false,
+            exportable: None,
+            default_output_format: None,
+            default_hash_algorithm: None,
+            default_encoding: None,
         };
         
         let key_pair = generate_key_pair(request).unwrap();
@@ -312,9 +1084,331 @@ mod tests {
         let test_data = b"test private key data";
         let password = "test_password";
         
-        let (encrypted, salt) = encrypt_private_key(test_data, password).unwrap();
-        let decrypted = decrypt_private_key(&encrypted, password, salt.as_deref()).unwrap();
+        let encrypted = encrypt_private_key(test_data, password, &KdfParams::default()).unwrap();
+        let decrypted = decrypt_private_key(&encrypted, password, None, &KdfParams::default()).unwrap();
         
         assert_eq!(test_data, decrypted.as_slice());
     }
+
+    #[test]
+    fn test_configured_kdf_applies_only_to_new_keys() {
+        let request = |name: &str| GenerateKeyRequest {
+            name: name.to_string(),
+            description: None,
+            password: Some("test_password_123".to_string()),
+            expires_at: None,
+            tags: None,
+            key_strength: None,
+            hsm: None,
+            generate_password: false,
+            template: None,
+            environment: None,
+            fast: false,
+            exportable: None,
+            default_output_format: None,
+            default_hash_algorithm: None,
+            default_encoding: None,
+        };
+
+        let old_key = generate_key_pair(request("Old Key")).unwrap();
+        let tuned = KdfParams::pbkdf2(20_000);
+        let new_key = generate_key_pair_with_kdf(request("New Key"), &tuned).unwrap();
+
+        assert_eq!(old_key.kdf, Some(KdfParams::default()));
+        assert_eq!(new_key.kdf, Some(tuned));
+
+        // Each envelope records the parameters it was encrypted with
+        let envelope = |key: &KeyPair| {
+            let bytes = base64::engine::general_purpose::STANDARD.decode(key.private_key.expose_for_persistence()).unwrap();
+            EncryptedKeyEnvelope::parse(&bytes).unwrap()
+        };
+        assert_eq!(envelope(&old_key).kdf, KdfParams::default());
+        assert_eq!(envelope(&new_key).kdf, tuned);
+        for key in [&old_key, &new_key] {
+            assert!(decrypt_private_key(key.private_key.expose_for_signing(), "test_password_123", None, &KdfParams::default()).is_ok());
+        }
+    }
+
+    #[cfg(not(feature = "verifier-only"))]
+    #[test]
+    fn test_legacy_key_decrypts_and_upgrades_to_envelope() {
+        let legacy = generate_legacy_test_key_pair("pw");
+        assert!(is_legacy_encrypted_key(&legacy));
+        let (private_key, salt) = legacy.signing_secrets();
+        let original = decrypt_private_key(private_key, "pw", salt, &KdfParams::default()).unwrap();
+
+        let upgraded = upgrade_legacy_private_key(&legacy, "pw").unwrap().unwrap();
+        let decrypted = decrypt_private_key(&upgraded, "pw", None, &KdfParams::default()).unwrap();
+        assert_eq!(original, decrypted);
+
+        // Upgrading again is a no-op
+        let upgraded_key = KeyPair { private_key: upgraded.into(), salt: None, ..legacy.clone() };
+        assert!(upgrade_legacy_private_key(&upgraded_key, "pw").unwrap().is_none());
+        assert!(upgrade_legacy_private_key(&legacy, "wrong").is_err());
+    }
+
+    #[test]
+    fn test_corrupted_keys_fail_cleanly() {
+        let decrypt = |private_key: &str, salt: Option<&str>| decrypt_private_key(private_key, "pw", salt, &KdfParams::default());
+        let encode = |bytes: &[u8]| base64::engine::general_purpose::STANDARD.encode(bytes);
+
+        // Legacy ciphertext whose salt column was lost
+        let legacy = generate_legacy_test_key_pair("pw");
+        assert!(matches!(decrypt(legacy.private_key.expose_for_signing(), None), Err(KeyManagementError::InvalidRequest(_))));
+
+        let envelope = base64::engine::general_purpose::STANDARD
+            .decode(encrypt_private_key(&[7u8; 64], "pw", &KdfParams::pbkdf2(10_000)).unwrap())
+            .unwrap();
+
+        // Every truncation is rejected without panicking
+        for len in 0..envelope.len() {
+            assert!(decrypt(&encode(&envelope[..len]), None).is_err(), "truncated to {}", len);
+        }
+
+        // Unknown version and KDF id
+        let mut wrong_version = envelope.clone();
+        wrong_version[3] = 2;
+        match decrypt(&encode(&wrong_version), None) {
+            Err(KeyManagementError::InvalidKeyFormat(message)) => assert!(message.contains("version 2"), "{}", message),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        let mut wrong_kdf = envelope.clone();
+        wrong_kdf[4] = 9;
+        assert!(matches!(decrypt(&encode(&wrong_kdf), None), Err(KeyManagementError::InvalidKeyFormat(_))));
+
+        // Flipped ciphertext byte fails authentication
+        let mut tampered = envelope.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(matches!(decrypt(&encode(&tampered), None), Err(KeyManagementError::PrivateKeyDecryptionFailed(_))));
+    }
+
+    #[cfg(not(feature = "verifier-only"))]
+    #[test]
+    fn test_validate_generate_request_reports_every_field() {
+        let now = Utc::now();
+        let mut request = GenerateKeyRequest {
+            name: "   ".to_string(),
+            description: Some("d".repeat(MAX_DESCRIPTION_LENGTH + 1)),
+            password: Some("short".to_string()),
+            expires_at: Some(now - Duration::days(1)),
+            tags: Some(vec!["a".to_string(), "".to_string(), "a".to_string(), "t".repeat(MAX_TAG_LENGTH + 1)]),
+            key_strength: Some(KeyStrength::Unknown),
+            hsm: None,
+            generate_password: false,
+            template: None,
+            environment: None,
+            fast: false,
+            exportable: None,
+            default_output_format: None,
+            default_hash_algorithm: None,
+            default_encoding: None,
+        };
+
+        let errors = validate_generate_request(&mut request, &[], &Config::default(), now).unwrap_err();
+        let fields: Vec<&str> = errors.iter().map(|error| error.field.as_str()).collect();
+        for field in ["name", "description", "password", "expires_at", "tags", "key_strength"] {
+            assert!(fields.contains(&field), "missing {} in {:?}", field, errors);
+        }
+        assert_eq!(fields.iter().filter(|field| **field == "tags").count(), 3);
+    }
+
+    #[cfg(not(feature = "verifier-only"))]
+    #[test]
+    fn test_validate_generate_request_derives_key_type_and_warnings() {
+        let now = Utc::now();
+        let mut request = GenerateKeyRequest {
+            name: "Soon".to_string(),
+            description: None,
+            password: None,
+            expires_at: Some(now + Duration::hours(1)),
+            tags: None,
+            key_strength: Some(KeyStrength::High),
+            hsm: None,
+            generate_password: false,
+            template: None,
+            environment: None,
+            fast: false,
+            exportable: None,
+            default_output_format: None,
+            default_hash_algorithm: None,
+            default_encoding: None,
+        };
+
+        let validation = validate_generate_request(&mut request, &[], &Config::default(), now).unwrap();
+        assert_eq!(validation.key_type, KeyType::Ed25519);
+        assert_eq!(validation.key_strength, KeyStrength::High);
+        assert_eq!(validation.warnings.len(), 2);
+    }
+
+    #[cfg(not(feature = "verifier-only"))]
+    #[test]
+    fn test_validate_generate_request_normalizes_confusable_metadata() {
+        let now = Utc::now();
+        let existing = [KeyInfo::new(generate_test_key_pair("Caf\u{e9} Root").unwrap(), now)];
+        let request = |name: &str, tags: &[&str]| GenerateKeyRequest {
+            name: name.to_string(),
+            description: Some("  Signs\u{200B} releases\n".to_string()),
+            tags: Some(tags.iter().map(|tag| tag.to_string()).collect()),
+            ..Default::default()
+        };
+
+        // A decomposed accent and a zero-width space do not make a second "Café Root"
+        let mut lookalike = request("Cafe\u{301}\u{200B} Root", &[]);
+        let errors = validate_generate_request(&mut lookalike, &existing, &Config::default(), now).unwrap_err();
+        assert_eq!(errors[0].message, "An active key named 'Caf\u{e9} Root' already exists");
+
+        let mut mixed = request("Staging", &["\u{420}\u{435}\u{43b}\u{438}\u{437}", "release", "Release\u{2060}"]);
+        let errors = validate_generate_request(&mut mixed, &existing, &Config::default(), now).unwrap_err();
+        assert_eq!(errors[0].message, "Duplicate tag 'Release'");
+
+        let mut valid = request("Staging", &["\u{420}\u{435}\u{43b}\u{438}\u{437}", "release", "\u{1F680}".repeat(MAX_TAG_LENGTH).as_str()]);
+        validate_generate_request(&mut valid, &existing, &Config::default(), now).unwrap();
+        assert_eq!(valid.description.as_deref(), Some("Signs releases"));
+
+        let mut spoofed = request("invoice\u{202E}fdp.exe", &["ok\u{0007}"]);
+        let errors = validate_generate_request(&mut spoofed, &existing, &Config::default(), now).unwrap_err();
+        assert_eq!(errors[0].message, "Key name must not contain bidirectional control characters");
+        assert_eq!(errors[1].message, "Tag 'ok\\u{7}' must not contain control characters");
+    }
+
+    #[test]
+    fn test_validate_expiry_boundaries() {
+        let now = Utc::now();
+        let config = Config {
+            clock_skew_secs: 60,
+            min_key_lifetime_secs: 3_600,
+            max_key_lifetime_days: Some(30),
+            ..Default::default()
+        };
+        let check = |expires_at| validate_expiry(expires_at, &config, now).map(|error| error.message);
+
+        // Past expiries are tolerated only within the skew window
+        assert!(check(now - Duration::seconds(60)).unwrap().contains("future"));
+        assert!(check(now - Duration::seconds(59)).unwrap().contains("at least"));
+
+        // The minimum lifetime is measured with the same tolerance
+        assert!(check(now + Duration::seconds(3_539)).unwrap().contains("at least"));
+        assert_eq!(check(now + Duration::seconds(3_540)), None);
+
+        assert_eq!(check(now + Duration::days(30) + Duration::seconds(60)), None);
+        assert!(check(now + Duration::days(30) + Duration::seconds(61)).unwrap().contains("30 days"));
+    }
+
+    #[cfg(not(feature = "verifier-only"))]
+    #[test]
+    fn test_lifetime_policy_in_strict_and_lenient_modes() {
+        let now = timestamps::normalize(Utc::now());
+        let within = now + Duration::days(100);
+        let over = now + Duration::days(400);
+        let request = |expires_at| GenerateKeyRequest {
+            name: "Policy".to_string(),
+            description: None,
+            password: None,
+            expires_at,
+            tags: None,
+            key_strength: None,
+            hsm: None,
+            generate_password: false,
+            template: None,
+            environment: None,
+            fast: false,
+            exportable: None,
+            default_output_format: None,
+            default_hash_algorithm: None,
+            default_encoding: None,
+        };
+
+        for strict in [false, true] {
+            let config = Config {
+                default_key_lifetime_days: Some(90),
+                max_key_lifetime_days: Some(365),
+                strict_key_lifetime: strict,
+                ..Default::default()
+            };
+            let validate = |expires_at| validate_generate_request(&mut request(expires_at), &[], &config, now);
+
+            let omitted = validate(None).unwrap();
+            assert_eq!(omitted.expires_at, Some(now + Duration::days(90)));
+            assert_eq!(omitted.expiry_source, Some(ExpirySource::Defaulted));
+
+            let requested = validate(Some(within)).unwrap();
+            assert_eq!(requested.expires_at, Some(within));
+            assert_eq!(requested.expiry_source, Some(ExpirySource::Requested));
+
+            if strict {
+                let errors = validate(Some(over)).unwrap_err();
+                assert_eq!(errors[0].field, "expires_at");
+            } else {
+                let clamped = validate(Some(over)).unwrap();
+                assert_eq!(clamped.expires_at, Some(now + Duration::days(365)));
+                assert_eq!(clamped.expiry_source, Some(ExpirySource::Clamped));
+                assert!(clamped.warnings.iter().any(|warning| warning.contains("365 days")));
+            }
+        }
+
+        // Without a default, the maximum still bounds keys that request no expiry
+        let config = Config { max_key_lifetime_days: Some(365), ..Default::default() };
+        let (expires_at, source) = apply_lifetime_policy(None, &config, now);
+        assert_eq!(expires_at, Some(now + Duration::days(365)));
+        assert_eq!(source, Some(ExpirySource::Defaulted));
+        assert_eq!(apply_lifetime_policy(None, &Config::default(), now), (None, None));
+    }
+
+    proptest::proptest! {
+        #![proptest_config(proptest::prelude::ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn test_envelope_round_trips_any_key_and_password(private_key in proptest::collection::vec(proptest::prelude::any::<u8>(), 0..128), password in ".{0,64}") {
+            let kdf = KdfParams::pbkdf2(1);
+            let encrypted = encrypt_private_key(&private_key, &password, &kdf).unwrap();
+            proptest::prop_assert_eq!(decrypt_private_key(&encrypted, &password, None, &kdf).unwrap(), private_key);
+            let wrong = format!("{}x", password);
+            proptest::prop_assert!(decrypt_private_key(&encrypted, &wrong, None, &kdf).is_err());
+        }
+
+        #[test]
+        fn test_envelope_parse_never_exceeds_kdf_bounds(bytes in proptest::collection::vec(proptest::prelude::any::<u8>(), 0..96)) {
+            let mut bytes = bytes;
+            bytes.splice(0..0, ENVELOPE_MAGIC.iter().copied().chain([ENVELOPE_VERSION]));
+            if let Ok(envelope) = EncryptedKeyEnvelope::parse(&bytes) {
+                proptest::prop_assert!(envelope.kdf.check_bounds().is_ok());
+                proptest::prop_assert_eq!(EncryptedKeyEnvelope::parse(&envelope.to_bytes()).unwrap(), envelope);
+            }
+        }
+    }
+
+    #[test]
+    fn test_envelope_rejects_unbounded_kdf_parameters() {
+        let envelope = |kdf: KdfParams| EncryptedKeyEnvelope { kdf, salt: vec![7; 16], nonce: [1; NONCE_LEN], ciphertext: vec![0; 48] };
+        for kdf in [
+            KdfParams { algorithm: KdfAlgorithm::Argon2id, iterations: 1, memory_kib: u32::MAX, parallelism: 1 },
+            KdfParams { algorithm: KdfAlgorithm::Argon2id, iterations: u32::MAX, memory_kib: 8, parallelism: 1 },
+            KdfParams { algorithm: KdfAlgorithm::Argon2id, iterations: 1, memory_kib: 65_536, parallelism: u32::MAX },
+            KdfParams { algorithm: KdfAlgorithm::Pbkdf2Sha256, iterations: u32::MAX, memory_kib: 0, parallelism: 0 },
+        ] {
+            let error = EncryptedKeyEnvelope::parse(&envelope(kdf).to_bytes()).unwrap_err();
+            assert!(error.to_string().contains("must be at most"), "{}", error);
+        }
+        assert!(EncryptedKeyEnvelope::parse(&envelope(KdfParams::pbkdf2(600_000)).to_bytes()).is_ok());
+    }
+
+    #[test]
+    fn test_seeded_keys_are_fixed() {
+        // Changing how the RNG reaches key generation changes these; the doc table must follow
+        let snapshot: Vec<(String, String)> = (0..3)
+            .map(|seed| generate_seeded_test_key_pair("Seeded Key", seed))
+            .map(|key_pair| (key_pair.public_key, key_pair.fingerprint.unwrap()))
+            .collect();
+        assert_eq!(snapshot, [
+            ("7dD23jQqHmpyNtYkTyPYPu387NBZo4bIUFVwFJjncDM=", "8d6470fa:2d00d290:d7eb1df2:c16964ae"),
+            ("R4uOUH4LsrGMD54IJHaehWLRDfmr4ud0iW+CtLRAUmY=", "f07815cb:38d0a5cd:0519604d:319662b5"),
+            ("WSW6huIYlESmw7Q3sl0u812uzRq/gsX7NgYPn8CvQow=", "7dfcf246:f51248ce:1c5f70c8:2ae6c448"),
+        ].map(|(public_key, fingerprint)| (public_key.to_string(), fingerprint.to_string())));
+
+        // Only the key material is fixed
+        let (first, second) = (generate_seeded_test_key_pair("A", 0), generate_seeded_test_key_pair("B", 0));
+        assert_eq!(first.private_key.expose_for_persistence(), second.private_key.expose_for_persistence());
+        assert_ne!(first.id, second.id);
+        validate_key_pair(&first).unwrap();
+    }
 }
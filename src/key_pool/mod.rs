@@ -0,0 +1,219 @@
+//! Pre-generated key pool
+//!
+//! Onboarding flows that need a key synchronously can ask `/keys/generate` for `fast: true`,
+//! which takes a key pair drawn ahead of time instead of drawing one while the caller waits.
+//! Pooled key pairs come from the same [`EntropyMonitor`] as every other key. They live only in
+//! memory, are never persisted and are zeroized when dropped: on claim, once older than
+//! `INKAN_KEY_POOL_MAX_AGE_SECS`, when entropy degrades, and on shutdown. A claimed key is
+//! named, tagged and encrypted at claim time exactly as an on-demand key would be, so the
+//! stored key does not show where it came from.
+//!
+//! An empty or disabled pool is not an error: the request falls back to drawing a seed
+//! synchronously. A claim that leaves `INKAN_KEY_POOL_REFILL_BELOW` or fewer keys wakes the
+//! refill task started by [`spawn_key_pool`].
+
+use crate::clock::Clock;
+use crate::config::Config;
+use crate::entropy::EntropyMonitor;
+use crate::models::KeyManagementError;
+use chrono::{DateTime, Duration, Utc};
+use ed25519_dalek::SigningKey;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+
+/// Longest the refill task sleeps between sweeps for stale keys
+const MAX_SWEEP_INTERVAL_SECS: i64 = 60;
+
+struct PooledKey {
+    signing_key: SigningKey,
+    drawn_at: DateTime<Utc>,
+}
+
+/// Pool size, hits and misses, for metrics
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    pub enabled: bool,
+    pub available: usize,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// Key pairs drawn ahead of the requests that claim them
+pub struct KeyPool {
+    size: usize,
+    refill_below: usize,
+    max_age: Duration,
+    keys: Mutex<VecDeque<PooledKey>>,
+    refill: Notify,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl KeyPool {
+    /// Pool holding up to `size` keys, refilled once `refill_below` or fewer remain; a size of
+    /// 0 disables it
+    pub fn new(size: usize, refill_below: usize, max_age: Duration) -> Self {
+        Self {
+            size,
+            refill_below,
+            max_age,
+            keys: Mutex::new(VecDeque::new()),
+            refill: Notify::new(),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(
+            config.key_pool_size as usize,
+            config.key_pool_refill_below as usize,
+            Duration::seconds(config.key_pool_max_age_secs.into()),
+        )
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.size > 0
+    }
+
+    fn keys(&self) -> std::sync::MutexGuard<'_, VecDeque<PooledKey>> {
+        self.keys.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Keys ready to be claimed
+    pub fn available(&self) -> usize {
+        self.keys().len()
+    }
+
+    /// Drops stale keys and draws new ones until the pool is full, returning how many were drawn
+    ///
+    /// Draws stop at the first failure, which degrades entropy and empties the pool.
+    pub fn fill(&self, entropy: &EntropyMonitor, now: DateTime<Utc>) -> Result<usize, KeyManagementError> {
+        self.discard_stale(now);
+        let mut drawn = 0;
+        while self.available() < self.size {
+            let signing_key = match entropy.draw_seed() {
+                Ok(seed) => SigningKey::from_bytes(&seed),
+                Err(e) => {
+                    self.clear();
+                    return Err(e);
+                }
+            };
+            self.keys().push_back(PooledKey { signing_key, drawn_at: now });
+            drawn += 1;
+        }
+        Ok(drawn)
+    }
+
+    /// Takes the oldest usable key, if any, waking the refill task when the pool runs low
+    ///
+    /// Nothing is handed out while entropy is degraded, since keys drawn just before the
+    /// failure are as suspect as those after it.
+    pub fn claim(&self, entropy: &EntropyMonitor, now: DateTime<Utc>) -> Option<SigningKey> {
+        if entropy.status().degraded {
+            self.clear();
+        }
+        self.discard_stale(now);
+        let (claimed, remaining) = {
+            let mut keys = self.keys();
+            (keys.pop_front(), keys.len())
+        };
+        match &claimed {
+            Some(_) => self.hits.fetch_add(1, Ordering::Relaxed),
+            None => self.misses.fetch_add(1, Ordering::Relaxed),
+        };
+        if self.is_enabled() && remaining <= self.refill_below {
+            self.refill.notify_one();
+        }
+        claimed.map(|pooled| pooled.signing_key)
+    }
+
+    /// Drops every pooled key
+    pub fn clear(&self) {
+        self.keys().clear();
+    }
+
+    fn discard_stale(&self, now: DateTime<Utc>) {
+        self.keys().retain(|pooled| now - pooled.drawn_at < self.max_age);
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        PoolStats {
+            enabled: self.is_enabled(),
+            available: self.available(),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Spawns the task that fills `pool` at once, again whenever a claim leaves it low, and drops
+/// stale keys in between; does nothing for a disabled pool
+pub fn spawn_key_pool(pool: Arc<KeyPool>, entropy: Arc<EntropyMonitor>, clock: Arc<dyn Clock>) -> Option<JoinHandle<()>> {
+    if !pool.is_enabled() {
+        return None;
+    }
+    let sweep_interval = pool.max_age.min(Duration::seconds(MAX_SWEEP_INTERVAL_SECS)).to_std().unwrap_or_default();
+    Some(tokio::spawn(async move {
+        loop {
+            let filling = (pool.clone(), entropy.clone(), clock.now());
+            let filled = tokio::task::spawn_blocking(move || {
+                let (pool, entropy, now) = filling;
+                pool.fill(&entropy, now)
+            }).await;
+            match filled {
+                Ok(Ok(drawn)) if drawn > 0 => tracing::debug!("Drew {} keys into the key pool", drawn),
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => tracing::warn!("Key pool refill failed: {}", e),
+                Err(e) => tracing::error!("Key pool refill task failed: {}", e),
+            }
+            let _ = tokio::time::timeout(sweep_interval, pool.refill.notified()).await;
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entropy::EntropySource;
+    use std::sync::atomic::AtomicBool;
+
+    struct SwitchableSource(AtomicBool);
+
+    impl EntropySource for SwitchableSource {
+        fn fill(&self, dest: &mut [u8]) -> Result<(), String> {
+            if self.0.load(Ordering::SeqCst) {
+                return Err("source unavailable".to_string());
+            }
+            crate::entropy::OsEntropy.fill(dest)
+        }
+    }
+
+    #[test]
+    fn test_pool_hands_out_fresh_distinct_keys_only() {
+        let source = Arc::new(SwitchableSource(AtomicBool::new(false)));
+        let entropy = EntropyMonitor::new(source.clone());
+        let pool = KeyPool::new(3, 1, Duration::minutes(10));
+        let now = Utc::now();
+        assert_eq!(pool.fill(&entropy, now).unwrap(), 3);
+
+        let first = pool.claim(&entropy, now).unwrap();
+        let second = pool.claim(&entropy, now).unwrap();
+        assert_ne!(first.to_bytes(), second.to_bytes());
+        assert_eq!(pool.fill(&entropy, now).unwrap(), 2);
+
+        // Stale keys are dropped rather than handed out
+        assert!(pool.claim(&entropy, now + Duration::minutes(10)).is_none());
+        assert_eq!(pool.stats(), PoolStats { enabled: true, available: 0, hits: 2, misses: 1 });
+
+        // A failed draw empties the pool, and nothing is claimed until entropy recovers
+        pool.fill(&entropy, now).unwrap();
+        source.0.store(true, Ordering::SeqCst);
+        assert!(entropy.draw_seed().is_err());
+        assert!(pool.claim(&entropy, now).is_none());
+        assert_eq!(pool.available(), 0);
+    }
+}
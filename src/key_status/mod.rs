@@ -0,0 +1,140 @@
+//! Key status checks for external caches
+//!
+//! `GET /keys/:id/status` tells a partner caching public keys whether one key is still good right
+//! now, with a minimal document — id, fingerprint, state and time of the check — meant to be
+//! cached for a short time. Deleted and archived keys are still answered, with the state they
+//! were left in, so only ids no key ever had are unknown. The notary key can counter-sign the
+//! document, which can then be kept with an audit trail and checked offline.
+
+use crate::bundle::NotarySignature;
+use crate::canonicalize::canonicalize_value;
+use crate::key_storage::KeyStorage;
+use crate::key_verification::decode_public_key;
+use crate::models::{KeyManagementError, KeyPair, KeyState};
+use crate::utils::public_key_to_fingerprint;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Domain tag prefixed to the canonical status before the notary signs it
+pub const KEY_STATUS_CONTEXT: &[u8] = b"inkan-key-status-v1";
+/// Most keys one batch request may ask about
+pub const MAX_STATUS_BATCH: usize = 100;
+
+/// A key's state at the time it was checked
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct KeyStatus {
+    pub key_id: Uuid,
+    pub fingerprint: String,
+    pub state: KeyState,
+    pub checked_at: DateTime<Utc>,
+}
+
+/// A key status, with the notary's signature over its canonical form when one was requested
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct KeyStatusDocument {
+    #[serde(flatten)]
+    pub status: KeyStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notary: Option<NotarySignature>,
+}
+
+impl KeyStatus {
+    fn new(key_pair: &KeyPair, state: KeyState, now: DateTime<Utc>) -> Self {
+        Self {
+            key_id: key_pair.id,
+            fingerprint: public_key_to_fingerprint(&key_pair.public_key).unwrap_or_default(),
+            state,
+            checked_at: now,
+        }
+    }
+
+    fn message(&self) -> Result<Vec<u8>, KeyManagementError> {
+        let value = serde_json::to_value(self)
+            .map_err(|e| KeyManagementError::InternalError(format!("Failed to serialize key status: {}", e)))?;
+        let mut message = KEY_STATUS_CONTEXT.to_vec();
+        message.push(0);
+        message.extend_from_slice(canonicalize_value(&value)?.as_bytes());
+        Ok(message)
+    }
+
+    /// Signs the status with the notary key, if one is given
+    pub fn sign(self, notary: Option<(Uuid, &SigningKey)>) -> Result<KeyStatusDocument, KeyManagementError> {
+        let notary = match notary {
+            Some((key_id, key)) => Some(NotarySignature {
+                key_id,
+                public_key: base64::engine::general_purpose::STANDARD.encode(key.verifying_key().to_bytes()),
+                signature: base64::engine::general_purpose::STANDARD.encode(key.sign(&self.message()?).to_bytes()),
+            }),
+            None => None,
+        };
+        Ok(KeyStatusDocument { status: self, notary })
+    }
+}
+
+impl KeyStatusDocument {
+    /// Checks the notary signature; `Ok(false)` when the status is unsigned
+    pub fn verify(&self) -> Result<bool, KeyManagementError> {
+        let Some(notary) = &self.notary else { return Ok(false) };
+        let invalid = || KeyManagementError::SignatureVerificationFailed("Key status signature is invalid".to_string());
+        let public_key = decode_public_key(&notary.public_key)?;
+        let signature = base64::engine::general_purpose::STANDARD.decode(&notary.signature)
+            .ok()
+            .and_then(|bytes| <[u8; 64]>::try_from(bytes.as_slice()).ok())
+            .map(|bytes| Signature::from_bytes(&bytes))
+            .ok_or_else(invalid)?;
+        public_key.verify(&self.status.message()?, &signature).map_err(|_| invalid())?;
+        Ok(true)
+    }
+}
+
+/// Status of each of `key_ids` at `now`, `None` for ids no key ever had
+///
+/// Stored keys are answered from the keystore. The deleted-keys and archive files are read only
+/// when some id is not stored; a key deleted more than once is answered from its last deletion.
+pub async fn key_statuses(storage: &KeyStorage, key_ids: &[Uuid], now: DateTime<Utc>) -> Result<Vec<Option<KeyStatus>>, KeyManagementError> {
+    let mut statuses = Vec::with_capacity(key_ids.len());
+    for key_id in key_ids {
+        statuses.push(storage.get_key_record(*key_id).await.ok().map(|key_pair| KeyStatus::new(&key_pair, key_pair.state(now), now)));
+    }
+    if statuses.iter().all(Option::is_some) {
+        return Ok(statuses);
+    }
+
+    let deleted = storage.deleted_keys().await?;
+    let archived = storage.archived_keys().await?;
+    for (status, key_id) in statuses.iter_mut().zip(key_ids).filter(|(status, _)| status.is_none()) {
+        *status = deleted.iter().rev()
+            .find(|record| record.key_pair.id == *key_id)
+            .map(|record| KeyStatus::new(&record.key_pair, KeyState::Deleted, now))
+            .or_else(|| archived.iter().rev()
+                .find(|record| record.key_pair.id == *key_id)
+                .map(|record| KeyStatus::new(&record.key_pair, record.key_pair.state(now), now)));
+    }
+    Ok(statuses)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_generation::generate_seeded_test_key_pair;
+
+    #[test]
+    fn test_signed_status_verifies_offline_and_detects_tampering() {
+        let notary = SigningKey::from_bytes(&[7u8; 32]);
+        let key_pair = generate_seeded_test_key_pair("Partner Key", 0);
+        let document = KeyStatus::new(&key_pair, KeyState::Revoked, Utc::now()).sign(Some((Uuid::nil(), &notary))).unwrap();
+        assert_eq!(document.status.fingerprint, "8d6470fa:2d00d290:d7eb1df2:c16964ae");
+
+        // Parsed back from its JSON, as a partner holding it would
+        let parsed: KeyStatusDocument = serde_json::from_str(&serde_json::to_string(&document).unwrap()).unwrap();
+        assert!(parsed.verify().unwrap());
+
+        let mut tampered = parsed.clone();
+        tampered.status.state = KeyState::Active;
+        assert!(tampered.verify().is_err());
+        assert!(!KeyStatusDocument { notary: None, ..parsed }.verify().unwrap());
+    }
+}
@@ -1,26 +1,582 @@
-use crate::models::{KeyPair, KeyInfo, KeyManagementError, UpdateKeyRequest, KeyType, KeyStrength};
-use chrono::{Utc, Duration};
+use crate::capabilities::HashAlgorithm;
+use crate::clock::{Clock, SystemClock};
+use crate::config::{KdfParams, DEFAULT_EVENT_LOG_RETAIN};
+use crate::event_log::EventLog;
+use crate::lifecycle::{Lifecycle, LifecycleEvent};
+use crate::models::{HsmKeyRef, KeyPair, KeyInfo, KeyManagementError, KeyState, KeyStrength, KeyUsage, MetadataRevision, SignatureEncoding, SignatureOutputFormat, UpdateKeyRequest, KeyType};
+use crate::reencryption::EnvelopeRevision;
+use crate::secret::SecretString;
+use crate::storage_breaker::StorageBreaker;
+use crate::text_normalization::{clean_line, clean_tags, normalize_stored_key, same_folded};
+use crate::usage_shadow::{UsageEvent, UsageShadow};
+use crate::utils::{compact_fingerprint, public_key_to_fingerprint};
+use base64::Engine;
+use chrono::{DateTime, Utc, Duration};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json;
-use std::collections::HashMap;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashMap};
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{MappedMutexGuard, Mutex, MutexGuard};
 use tokio::fs;
 use uuid::Uuid;
 
+/// Delay before the first retry of a failed keystore write; doubles with each further failure
+pub const WRITE_RETRY_BASE_SECS: i64 = 5;
+/// Longest delay between retries of a failed keystore write
+pub const WRITE_RETRY_MAX_SECS: i64 = 900;
+
+/// Health of keystore persistence
+///
+/// Persistence is degraded while the latest write failed: changes are held in memory and the
+/// background flusher retries the write with exponential backoff.
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct PersistenceStatus {
+    pub degraded: bool,
+    pub consecutive_failures: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_failure_at: Option<DateTime<Utc>>,
+}
+
+impl PersistenceStatus {
+    /// Earliest time the flusher should retry after the latest failure
+    pub fn next_retry_at(&self) -> Option<DateTime<Utc>> {
+        let last_failure_at = self.last_failure_at.filter(|_| self.degraded)?;
+        let exponent = self.consecutive_failures.saturating_sub(1).min(16);
+        let delay = (WRITE_RETRY_BASE_SECS << exponent).min(WRITE_RETRY_MAX_SECS);
+        Some(last_failure_at + Duration::seconds(delay))
+    }
+
+    /// Whether a background write should be attempted at `now`
+    pub fn write_due(&self, now: DateTime<Utc>) -> bool {
+        self.next_retry_at().is_none_or(|retry_at| now >= retry_at)
+    }
+}
+
+/// How a key pair is written to the keystore, its backups, and the archive, deleted-keys and
+/// quarantine files
+///
+/// This is the only serialized form of a key's secrets. The fields keep the names and defaults
+/// [`KeyPair`] was once serialized with, so existing files read unchanged.
+#[derive(Serialize, Deserialize)]
+struct PersistedKeyPair {
+    id: Uuid,
+    name: String,
+    description: Option<String>,
+    public_key: String,
+    private_key: String,
+    salt: Option<String>,
+    created_at: DateTime<Utc>,
+    last_used: Option<DateTime<Utc>>,
+    #[serde(default, with = "crate::timestamps::option")]
+    expires_at: Option<DateTime<Utc>>,
+    is_active: bool, // Written for older readers; `lifecycle` decides when present
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    lifecycle: Option<Lifecycle>,
+    tags: Vec<String>,
+    key_type: KeyType,
+    key_strength: KeyStrength,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    kdf: Option<KdfParams>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fingerprint: Option<String>,
+    #[serde(default, with = "crate::timestamps::option", skip_serializing_if = "Option::is_none")]
+    revocation_scheduled_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    usage: KeyUsage,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    notified_thresholds: BTreeSet<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hsm: Option<HsmKeyRef>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    allowed_contexts: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    metadata_history: Vec<MetadataRevision>,
+    #[serde(default = "crate::environment::unknown_environment")]
+    environment: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    lifecycle_history: Vec<LifecycleEvent>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    envelope_history: Vec<EnvelopeRevision>,
+    #[serde(default = "crate::models::exportable_by_default")]
+    exportable: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    default_output_format: Option<SignatureOutputFormat>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    default_hash_algorithm: Option<HashAlgorithm>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    default_encoding: Option<SignatureEncoding>,
+}
+
+impl From<&KeyPair> for PersistedKeyPair {
+    fn from(key_pair: &KeyPair) -> Self {
+        Self {
+            id: key_pair.id,
+            name: key_pair.name.clone(),
+            description: key_pair.description.clone(),
+            public_key: key_pair.public_key.clone(),
+            private_key: key_pair.private_key.expose_for_persistence().to_string(),
+            salt: key_pair.salt.as_ref().map(|salt| salt.expose_for_persistence().to_string()),
+            created_at: key_pair.created_at,
+            last_used: key_pair.last_used,
+            expires_at: key_pair.expires_at,
+            is_active: key_pair.lifecycle == Lifecycle::Active,
+            lifecycle: Some(key_pair.lifecycle),
+            tags: key_pair.tags.clone(),
+            key_type: key_pair.key_type.clone(),
+            key_strength: key_pair.key_strength.clone(),
+            kdf: key_pair.kdf,
+            fingerprint: key_pair.fingerprint.clone(),
+            revocation_scheduled_at: key_pair.revocation_scheduled_at,
+            usage: key_pair.usage.clone(),
+            notified_thresholds: key_pair.notified_thresholds.clone(),
+            hsm: key_pair.hsm.clone(),
+            allowed_contexts: key_pair.allowed_contexts.clone(),
+            metadata_history: key_pair.metadata_history.clone(),
+            environment: key_pair.environment.clone(),
+            lifecycle_history: key_pair.lifecycle_history.clone(),
+            envelope_history: key_pair.envelope_history.clone(),
+            exportable: key_pair.exportable,
+            default_output_format: key_pair.default_output_format,
+            default_hash_algorithm: key_pair.default_hash_algorithm,
+            default_encoding: key_pair.default_encoding,
+        }
+    }
+}
+
+impl From<PersistedKeyPair> for KeyPair {
+    fn from(persisted: PersistedKeyPair) -> Self {
+        Self {
+            id: persisted.id,
+            name: persisted.name,
+            description: persisted.description,
+            public_key: persisted.public_key,
+            private_key: SecretString::from(persisted.private_key),
+            salt: persisted.salt.map(SecretString::from),
+            created_at: persisted.created_at,
+            last_used: persisted.last_used,
+            expires_at: persisted.expires_at,
+            // Keys written before lifecycles existed were revoked by clearing `is_active`
+            lifecycle: persisted.lifecycle.unwrap_or(if persisted.is_active { Lifecycle::Active } else { Lifecycle::Revoked }),
+            tags: persisted.tags,
+            key_type: persisted.key_type,
+            key_strength: persisted.key_strength,
+            kdf: persisted.kdf,
+            fingerprint: persisted.fingerprint,
+            revocation_scheduled_at: persisted.revocation_scheduled_at,
+            usage: persisted.usage,
+            notified_thresholds: persisted.notified_thresholds,
+            hsm: persisted.hsm,
+            allowed_contexts: persisted.allowed_contexts,
+            metadata_history: persisted.metadata_history,
+            environment: persisted.environment,
+            lifecycle_history: persisted.lifecycle_history,
+            envelope_history: persisted.envelope_history,
+            exportable: persisted.exportable,
+            default_output_format: persisted.default_output_format,
+            default_hash_algorithm: persisted.default_hash_algorithm,
+            default_encoding: persisted.default_encoding,
+        }
+    }
+}
+
+/// Reads the key pair of an archive or deleted-keys record
+fn deserialize_persisted<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<KeyPair, D::Error> {
+    PersistedKeyPair::deserialize(deserializer).map(KeyPair::from)
+}
+
+/// A deleted-keys record as written to the file
+fn deleted_record(deleted: &DeletedKey) -> serde_json::Value {
+    let mut record = serde_json::json!({
+        "deleted_at": deleted.deleted_at,
+        "key_pair": PersistedKeyPair::from(&deleted.key_pair),
+    });
+    if let Some(deleted_by) = &deleted.deleted_by {
+        record["deleted_by"] = serde_json::json!(deleted_by);
+    }
+    record
+}
+
+/// What a backup holds of keys that may not be exported
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NonExportableBackup {
+    /// The public key and metadata, without the private key
+    #[default]
+    Exclude,
+    /// The private key as the keystore holds it, under the key's own password if it has one
+    Stored,
+}
+
+impl NonExportableBackup {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "exclude" => Some(NonExportableBackup::Exclude),
+            "stored" => Some(NonExportableBackup::Stored),
+            _ => None,
+        }
+    }
+}
+
+/// Serializes every key in its persisted form, as written to the keystore file and backups
+pub(crate) fn serialize_keys<'a>(keys: impl Iterator<Item = &'a KeyPair>) -> serde_json::Result<String> {
+    serde_json::to_string_pretty(&keys.map(PersistedKeyPair::from).collect::<Vec<_>>())
+}
+
+/// Keys that differ between the in-memory keystore and the keystore file
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct KeystoreChange {
+    pub added: Vec<Uuid>,
+    pub removed: Vec<Uuid>,
+    pub changed: Vec<Uuid>,
+}
+
+impl KeystoreChange {
+    fn between(current: &HashMap<Uuid, KeyPair>, updated: &HashMap<Uuid, KeyPair>) -> Self {
+        let mut change = Self::default();
+        for (id, key_pair) in updated {
+            match current.get(id) {
+                None => change.added.push(*id),
+                Some(existing) if serde_json::to_value(PersistedKeyPair::from(existing)).ok() != serde_json::to_value(PersistedKeyPair::from(key_pair)).ok() => {
+                    change.changed.push(*id);
+                }
+                Some(_) => {}
+            }
+        }
+        change.removed = current.keys().filter(|id| !updated.contains_key(id)).copied().collect();
+        change.added.sort();
+        change.removed.sort();
+        change.changed.sort();
+        change
+    }
+
+    /// One-line description for logs
+    pub fn summary(&self) -> String {
+        format!("{} added, {} removed, {} changed", self.added.len(), self.removed.len(), self.changed.len())
+    }
+}
+
+/// Key moved to the archive file to keep the keystore within its limits
+#[derive(Debug, Clone, Deserialize)]
+pub struct ArchivedKey {
+    pub reason: String,
+    pub archived_at: DateTime<Utc>,
+    #[serde(deserialize_with = "deserialize_persisted")]
+    pub key_pair: KeyPair,
+}
+
+/// Soft-deleted key, held in the deleted-keys file until it is restored or purged
+#[derive(Debug, Clone, Deserialize)]
+pub struct DeletedKey {
+    pub deleted_at: DateTime<Utc>,
+    #[serde(default)]
+    pub deleted_by: Option<String>, // Authenticated client that deleted the key, if requests are signed
+    #[serde(deserialize_with = "deserialize_persisted")]
+    pub key_pair: KeyPair,
+}
+
+/// Outcome of checking the keystore file for changes made outside the service
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeystoreReload {
+    /// The file holds what the service last read or wrote
+    Unchanged,
+    /// The file was modified externally and its keys replaced the in-memory ones
+    Reloaded(KeystoreChange),
+    /// The file was modified externally but unsaved in-memory changes were kept instead; the
+    /// next write overwrites the external change
+    Conflict(KeystoreChange),
+}
+
+fn content_hash(content: &[u8]) -> [u8; 32] {
+    Sha256::digest(content).into()
+}
+
+fn parse_keystore(content: &[u8]) -> Result<Vec<KeyPair>, KeyManagementError> {
+    if content.is_empty() {
+        return Ok(Vec::new());
+    }
+    serde_json::from_slice::<Vec<PersistedKeyPair>>(content)
+        .map(|keys| keys.into_iter().map(KeyPair::from).collect())
+        .map_err(|e| KeyManagementError::StorageError(format!("Failed to parse storage file: {}", e)))
+}
+
+/// Which keys a listing returns; an unset field matches every key
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KeyFilter {
+    /// Keys that may sign (`true`) or may not (`false`)
+    pub active_only: Option<bool>,
+    pub key_type: Option<KeyType>,
+    /// Keys carrying every one of these tags, compared ignoring case
+    pub tags: Option<Vec<String>>,
+    /// Case-insensitive text found in the name, description or a tag
+    pub search: Option<String>,
+    /// Keys of this deployment environment
+    pub environment: Option<String>,
+    /// Usable keys expiring at or before this time
+    pub expiring_before: Option<DateTime<Utc>>,
+}
+
+impl KeyFilter {
+    /// The filter with its tags and search text normalized the way stored metadata is
+    fn normalized(&self) -> Self {
+        Self {
+            tags: self.tags.as_deref().map(clean_tags),
+            search: self.search.as_deref().map(clean_line),
+            ..self.clone()
+        }
+    }
+
+    /// Whether `key_pair`, currently in `state`, is listed; reads only public fields
+    fn matches(&self, key_pair: &KeyPair, state: KeyState) -> bool {
+        if self.active_only.is_some_and(|active| state.is_usable() != active) {
+            return false;
+        }
+        if self.key_type.as_ref().is_some_and(|key_type| key_pair.key_type != *key_type) {
+            return false;
+        }
+        if self.tags.as_ref().is_some_and(|tags| !tags.iter().all(|tag| key_pair.tags.iter().any(|held| same_folded(held, tag)))) {
+            return false;
+        }
+        if self.environment.as_ref().is_some_and(|environment| key_pair.environment != *environment) {
+            return false;
+        }
+        if let Some(before) = self.expiring_before {
+            if !state.is_usable() || key_pair.expires_at.is_none_or(|expires_at| expires_at > before) {
+                return false;
+            }
+        }
+        if let Some(search) = &self.search {
+            let found = contains_ignoring_case(&key_pair.name, search)
+                || key_pair.description.as_deref().is_some_and(|description| contains_ignoring_case(description, search))
+                || key_pair.tags.iter().any(|tag| contains_ignoring_case(tag, search));
+            if !found {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Case-insensitive substring test that allocates only for non-ASCII text
+fn contains_ignoring_case(haystack: &str, needle: &str) -> bool {
+    if !haystack.is_ascii() || !needle.is_ascii() {
+        return haystack.to_lowercase().contains(&needle.to_lowercase());
+    }
+    needle.is_empty() || haystack.as_bytes().windows(needle.len()).any(|window| window.eq_ignore_ascii_case(needle.as_bytes()))
+}
+
+/// One page of a filtered listing, in creation order
+#[derive(Debug, Clone)]
+pub struct KeyPage {
+    pub keys: Vec<KeyInfo>,
+    /// Keys matching the filter, across all pages
+    pub matched: usize,
+    /// Where the next page starts, when keys remain after this one
+    pub next_cursor: Option<KeyCursor>,
+}
+
+/// Position in a key listing: the sort key of the last key returned
+///
+/// Listings sort keys by creation time, then id. Neither ever changes, so a cursor keeps its
+/// place however many keys are created or deleted after it is handed out, including the key it
+/// names. Clients see it only as an opaque token from [`KeyCursor::encode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct KeyCursor {
+    pub created_at: DateTime<Utc>,
+    pub id: Uuid,
+}
+
+impl KeyCursor {
+    /// Cursor just past `key_pair`
+    pub fn after(key_pair: &KeyPair) -> Self {
+        Self { created_at: key_pair.created_at, id: key_pair.id }
+    }
+
+    /// The opaque token handed to clients
+    pub fn encode(&self) -> String {
+        let position = format!("{}/{}", self.created_at.to_rfc3339_opts(chrono::SecondsFormat::Nanos, true), self.id);
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(position)
+    }
+
+    /// Reads a token from [`KeyCursor::encode`]
+    pub fn decode(token: &str) -> Result<Self, KeyManagementError> {
+        let invalid = || KeyManagementError::InvalidRequest("Invalid cursor; pass back a next_cursor unchanged".to_string());
+        let position = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(token).map_err(|_| invalid())?;
+        let position = String::from_utf8(position).map_err(|_| invalid())?;
+        let (created_at, id) = position.split_once('/').ok_or_else(invalid)?;
+        Ok(Self {
+            created_at: DateTime::parse_from_rfc3339(created_at).map_err(|_| invalid())?.with_timezone(&Utc),
+            id: id.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// Reads a JSON array file next to the keystore; a missing or empty file holds no records
+async fn read_records<T: DeserializeOwned>(path: &str, kind: &str) -> Result<Vec<T>, KeyManagementError> {
+    match fs::read_to_string(path).await {
+        Ok(content) if !content.is_empty() => serde_json::from_str(&content)
+            .map_err(|e| KeyManagementError::StorageError(format!("Failed to parse {} file: {}", kind, e))),
+        _ => Ok(Vec::new()),
+    }
+}
+
+/// Replaces the records of a JSON array file next to the keystore
+async fn write_records(path: &str, kind: &str, records: &[serde_json::Value]) -> Result<(), KeyManagementError> {
+    let content = serde_json::to_string_pretty(records)
+        .map_err(|e| KeyManagementError::StorageError(format!("Failed to serialize {}: {}", kind, e)))?;
+    fs::write(path, content).await
+        .map_err(|e| KeyManagementError::StorageError(format!("Failed to write {} file: {}", kind, e)))
+}
+
+/// Appends records to a JSON array file next to the keystore, creating it if needed
+async fn append_records(path: &str, kind: &str, records: Vec<serde_json::Value>) -> Result<(), KeyManagementError> {
+    let mut existing: Vec<serde_json::Value> = read_records(path, kind).await?;
+    existing.extend(records);
+    write_records(path, kind, &existing).await
+}
+
 /// In-memory storage for key pairs (in production, use a proper database)
 pub struct KeyStorage {
-    keys: Arc<Mutex<HashMap<Uuid, KeyPair>>>,
+    /// Shared with any snapshots still held, and copied on the first change after one is taken
+    keys: Arc<Mutex<Arc<HashMap<Uuid, KeyPair>>>>,
     storage_path: String,
+    /// Changes not yet written to disk, besides pending usage
+    dirty: AtomicBool,
+    /// Usage recorded since it was last folded into the keys, kept off the keys lock
+    usage: UsageShadow,
+    /// Times the keys lock was taken, for measuring contention
+    lock_acquisitions: AtomicU64,
+    /// Usage updates lost because their key was gone by the time they were recorded
+    dropped_usage: AtomicU64,
+    persistence: std::sync::Mutex<PersistenceStatus>,
+    /// Hash of the keystore file as last read or written by this instance
+    synced_hash: std::sync::Mutex<Option<[u8; 32]>>,
+    /// Time against which key expiry and revocation are evaluated
+    clock: Arc<dyn Clock>,
+    /// What backups hold of non-exportable keys
+    non_exportable_backup: NonExportableBackup,
+    /// Told the outcome and latency of every keystore write
+    breaker: Arc<StorageBreaker>,
+    /// Private keys are dropped as keys are loaded, and the keystore is never written
+    public_only: bool,
+    /// Numbered record of every change, kept next to the keystore
+    events: EventLog,
 }
 
 impl KeyStorage {
     /// Creates a new key storage instance
     pub fn new(storage_path: &str) -> Self {
         Self {
-            keys: Arc::new(Mutex::new(HashMap::new())),
+            keys: Arc::new(Mutex::new(Arc::new(HashMap::new()))),
             storage_path: storage_path.to_string(),
+            dirty: AtomicBool::new(false),
+            usage: UsageShadow::default(),
+            lock_acquisitions: AtomicU64::new(0),
+            dropped_usage: AtomicU64::new(0),
+            persistence: std::sync::Mutex::new(PersistenceStatus::default()),
+            synced_hash: std::sync::Mutex::new(None),
+            clock: Arc::new(SystemClock),
+            non_exportable_backup: NonExportableBackup::default(),
+            breaker: Arc::new(StorageBreaker::default()),
+            public_only: false,
+            events: EventLog::new(&format!("{}.events", storage_path), DEFAULT_EVENT_LOG_RETAIN as usize),
+        }
+    }
+
+    /// Evaluates key expiry and revocation against `clock` instead of the system time
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Writes non-exportable keys to backups as `policy` says
+    pub fn with_non_exportable_backup(mut self, policy: NonExportableBackup) -> Self {
+        self.non_exportable_backup = policy;
+        self
+    }
+
+    /// Reports keystore writes to `breaker`
+    pub fn with_breaker(mut self, breaker: StorageBreaker) -> Self {
+        self.breaker = Arc::new(breaker);
+        self
+    }
+
+    /// Loads keys without their private keys and salts, and refuses to write the keystore, so
+    /// a copy of another instance's keystore stays as it is
+    pub fn with_public_only(mut self, public_only: bool) -> Self {
+        self.public_only = public_only;
+        self
+    }
+
+    /// Keeps the `retain` most recent operation events for replay
+    pub fn with_event_retention(mut self, retain: usize) -> Self {
+        self.events = EventLog::new(self.events.storage_path(), retain);
+        self
+    }
+
+    /// Circuit breaker guarding requests that depend on this store
+    pub fn breaker(&self) -> &StorageBreaker {
+        &self.breaker
+    }
+
+    /// Clock that key expiry, revocation and record timestamps are evaluated against
+    pub fn clock(&self) -> &dyn Clock {
+        self.clock.as_ref()
+    }
+
+    /// Numbered record of the changes made to this store and through the service
+    pub fn events(&self) -> &EventLog {
+        &self.events
+    }
+
+    async fn lock_keys(&self) -> MutexGuard<'_, Arc<HashMap<Uuid, KeyPair>>> {
+        self.lock_acquisitions.fetch_add(1, Ordering::Relaxed);
+        self.keys.lock().await
+    }
+
+    /// Times the keys lock has been taken since startup
+    pub fn keys_lock_acquisitions(&self) -> u64 {
+        self.lock_acquisitions.load(Ordering::Relaxed)
+    }
+
+    /// The live keys, for changing, with pending usage folded in; copied first if a snapshot
+    /// still shares them
+    async fn keys_mut(&self) -> MappedMutexGuard<'_, HashMap<Uuid, KeyPair>> {
+        let mut keys = self.lock_keys().await;
+        self.fold_usage(&mut keys);
+        MutexGuard::map(keys, Arc::make_mut)
+    }
+
+    /// Folds pending usage into the keys, which must be locked
+    fn fold_usage(&self, keys: &mut Arc<HashMap<Uuid, KeyPair>>) {
+        if self.usage.is_empty() {
+            return;
         }
+        let dropped = self.usage.drain().fold_into(Arc::make_mut(keys));
+        self.dropped_usage.fetch_add(dropped, Ordering::Relaxed);
+        self.dirty.store(true, Ordering::Release);
+    }
+
+    /// A copy of a stored key with its pending usage added
+    fn with_pending_usage(&self, mut key_pair: KeyPair) -> KeyPair {
+        self.usage.merge(key_pair.id, &mut key_pair.last_used, &mut key_pair.usage);
+        key_pair
+    }
+    
+    /// Takes a consistent point-in-time view of every stored entry
+    ///
+    /// Taking it costs one reference count under the lock, so it holds each change made through
+    /// this store either whole or not at all. Changes made while it is held go to a copy of the
+    /// keys, leaving the snapshot as it was. Pending usage is folded in first.
+    pub async fn begin_snapshot(&self) -> KeystoreSnapshot {
+        let mut keys = self.lock_keys().await;
+        self.fold_usage(&mut keys);
+        KeystoreSnapshot { keys: Arc::clone(&keys), taken_at: self.clock.now() }
     }
     
     /// Stores a key pair
@@ -29,63 +585,124 @@ impl KeyStorage {
         
         // Store in memory
         {
-            let mut keys = self.keys.lock().await;
+            let mut keys = self.keys_mut().await;
             keys.insert(key_id, key_pair.clone());
         }
         
         // Store on disk
-        self.save_to_disk().await?;
+        self.persist().await;
         
         Ok(())
     }
     
+    /// Stores a newly generated key pair, unless an active or suspended key already has its name
+    ///
+    /// Names are compared the way generation validates them, see
+    /// [`crate::text_normalization::same_folded`]. The check holds the same lock as the insert, so
+    /// of two generations racing for one name only the first is stored; the other gets
+    /// [`KeyManagementError::KeyConflict`].
+    pub async fn store_new_key(&self, key_pair: KeyPair) -> Result<(), KeyManagementError> {
+        {
+            let now = self.clock.now();
+            let mut keys = self.keys_mut().await;
+            let taken = keys.values().any(|key| {
+                let state = key.state(now);
+                (state.is_usable() || state == KeyState::Suspended) && same_folded(key.name.trim(), key_pair.name.trim())
+            });
+            if taken {
+                return Err(KeyManagementError::KeyConflict(format!("an active key named '{}' already exists", key_pair.name)));
+            }
+            keys.insert(key_pair.id, key_pair);
+        }
+
+        self.persist().await;
+        Ok(())
+    }
+
     /// Retrieves a key pair by ID
     pub async fn get_key(&self, key_id: Uuid) -> Result<KeyPair, KeyManagementError> {
-        let keys = self.keys.lock().await;
+        let keys = self.lock_keys().await;
         let key_pair = keys.get(&key_id)
             .cloned()
             .ok_or(KeyManagementError::KeyNotFound(key_id))?;
         
-        // Check if key is expired
-        if let Some(expires_at) = key_pair.expires_at {
-            if Utc::now() > expires_at {
-                return Err(KeyManagementError::KeyExpired(key_id));
-            }
-        }
-        
-        // Check if key is active
-        if !key_pair.is_active {
-            return Err(KeyManagementError::KeyRevoked(key_id));
-        }
-        
-        Ok(key_pair)
+        key_pair.state(self.clock.now()).check_usable(key_id)?;
+        Ok(self.with_pending_usage(key_pair))
     }
     
-    /// Lists all keys (returns only public information)
-    pub async fn list_keys(&self) -> Vec<KeyInfo> {
-        let keys = self.keys.lock().await;
-        let now = Utc::now();
-        
+    /// Retrieves a key pair by ID regardless of whether it is expired or revoked
+    pub async fn get_key_record(&self, key_id: Uuid) -> Result<KeyPair, KeyManagementError> {
+        let keys = self.lock_keys().await;
+        keys.get(&key_id)
+            .cloned()
+            .map(|key_pair| self.with_pending_usage(key_pair))
+            .ok_or(KeyManagementError::KeyNotFound(key_id))
+    }
+    
+    /// Retrieves the key pair whose public key has the given fingerprint, regardless of key state
+    ///
+    /// The fingerprint is computed from each public key rather than read from the stored field,
+    /// which older records lack. Either fingerprint form is accepted, see [`compact_fingerprint`].
+    pub async fn find_by_fingerprint(&self, fingerprint: &str) -> Option<KeyPair> {
+        let wanted = compact_fingerprint(fingerprint)?;
+        let keys = self.lock_keys().await;
         keys.values()
-            .map(|key_pair| {
-                let is_expired = key_pair.expires_at.map_or(false, |exp| now > exp);
-                let is_active = key_pair.is_active && !is_expired;
-                
-                KeyInfo {
-                    id: key_pair.id,
-                    name: key_pair.name.clone(),
-                    description: key_pair.description.clone(),
-                    public_key: key_pair.public_key.clone(),
-                    created_at: key_pair.created_at,
-                    last_used: key_pair.last_used,
-                    expires_at: key_pair.expires_at,
-                    is_active,
-                    tags: key_pair.tags.clone(),
-                    key_type: key_pair.key_type.clone(),
-                    key_strength: key_pair.key_strength.clone(),
-                }
+            .find(|key_pair| {
+                public_key_to_fingerprint(&key_pair.public_key).ok().and_then(|fingerprint| compact_fingerprint(&fingerprint)).as_ref() == Some(&wanted)
             })
-            .collect()
+            .cloned()
+            .map(|key_pair| self.with_pending_usage(key_pair))
+    }
+
+    /// Lists the keys matching `filter` in creation order, skipping `offset` and returning at most `limit`
+    ///
+    /// Keys are matched in place under the lock; only those on the page are converted to
+    /// [`KeyInfo`], and no private key is ever copied.
+    pub async fn list_keys_page(&self, filter: &KeyFilter, offset: usize, limit: Option<usize>) -> KeyPage {
+        self.page_keys(filter, None, offset, limit).await
+    }
+
+    /// Lists the keys matching `filter` that sort after `cursor`, returning at most `limit`
+    ///
+    /// Paging with each page's `next_cursor` neither skips nor repeats a key while keys are
+    /// created or deleted in between: new keys sort after every cursor already handed out, as
+    /// long as the system clock does not step back. The exception is a key imported with an
+    /// earlier creation time, which sorts before cursors past that time and is not returned to
+    /// them.
+    pub async fn list_keys_after(&self, cursor: Option<&KeyCursor>, limit: Option<usize>, filter: &KeyFilter) -> KeyPage {
+        self.page_keys(filter, cursor, 0, limit).await
+    }
+
+    async fn page_keys(&self, filter: &KeyFilter, cursor: Option<&KeyCursor>, offset: usize, limit: Option<usize>) -> KeyPage {
+        let filter = filter.normalized();
+        let keys = self.lock_keys().await;
+        let now = self.clock.now();
+
+        let mut matched = 0;
+        let mut remaining: Vec<&KeyPair> = keys.values()
+            .filter(|key_pair| filter.matches(key_pair, key_pair.state(now)))
+            .inspect(|_| matched += 1)
+            .filter(|key_pair| cursor.is_none_or(|cursor| KeyCursor::after(key_pair) > *cursor))
+            .collect();
+        remaining.sort_unstable_by_key(|key_pair| KeyCursor::after(key_pair));
+        let end = offset.saturating_add(limit.unwrap_or(usize::MAX)).min(remaining.len());
+        let page = remaining.get(offset..end).unwrap_or_default();
+        KeyPage {
+            matched,
+            next_cursor: page.last().filter(|_| end < remaining.len()).map(|key_pair| KeyCursor::after(key_pair)),
+            keys: page.iter()
+                .map(|key_pair| {
+                    let mut info = KeyInfo::from_key_pair(key_pair, now);
+                    self.usage.merge(info.id, &mut info.last_used, &mut info.usage);
+                    info
+                })
+                .collect(),
+        }
+    }
+
+    /// Lists all keys (returns only public information)
+    pub async fn list_keys(&self) -> Vec<KeyInfo> {
+        self.list_keys_page(&KeyFilter::default(), 0, None).await.keys
     }
     
     /// Lists keys with filtering options
@@ -95,51 +712,111 @@ impl KeyStorage {
         key_type: Option<KeyType>,
         tags: Option<Vec<String>>,
     ) -> Vec<KeyInfo> {
-        let keys = self.list_keys().await;
-        
-        keys.into_iter()
-            .filter(|key| {
-                // Filter by active status
-                if let Some(active) = active_only {
-                    if key.is_active != active {
-                        return false;
-                    }
-                }
-                
-                // Filter by key type
-                if let Some(ref kt) = key_type {
-                    if key.key_type != *kt {
-                        return false;
-                    }
-                }
-                
-                // Filter by tags
-                if let Some(ref required_tags) = tags {
-                    if !required_tags.iter().all(|tag| key.tags.contains(tag)) {
-                        return false;
-                    }
-                }
-                
-                true
-            })
-            .collect()
+        let filter = KeyFilter { active_only, key_type, tags, ..KeyFilter::default() };
+        self.list_keys_page(&filter, 0, None).await.keys
     }
     
-    /// Updates the last used timestamp for a key
+    /// Updates the last used timestamp for a key, refusing keys that are expired or revoked
+    ///
+    /// The key is only read under the lock; the timestamp goes to the pending usage, like the
+    /// counters of [`KeyStorage::record_sign`].
     pub async fn update_last_used(&self, key_id: Uuid) -> Result<(), KeyManagementError> {
-        let mut keys = self.keys.lock().await;
-        if let Some(key_pair) = keys.get_mut(&key_id) {
-            key_pair.last_used = Some(Utc::now());
-            Ok(())
-        } else {
-            Err(KeyManagementError::KeyNotFound(key_id))
+        let now = self.clock.now();
+        self.lock_keys().await
+            .get(&key_id)
+            .ok_or(KeyManagementError::KeyNotFound(key_id))?
+            .state(now)
+            .check_usable(key_id)?;
+        self.usage.record(key_id, UsageEvent::Use, now);
+        Ok(())
+    }
+    
+    /// Records a successful signature by a key
+    ///
+    /// The signature is counted without the keys lock, and folded into the key by
+    /// [`KeyStorage::flush`] or the next save, so parallel signatures neither wait on each other
+    /// nor rewrite the keystore. Only a key's first use of a day since the last fold takes the
+    /// lock, to check the key is stored; an update for a key that is not is dropped and counted.
+    pub async fn record_sign(&self, key_id: Uuid, signed_at: DateTime<Utc>) -> Result<(), KeyManagementError> {
+        self.record_usage(key_id, UsageEvent::Sign, signed_at).await
+    }
+    
+    /// Records a completed verification against a stored key, like [`KeyStorage::record_sign`]
+    pub async fn record_verify(&self, key_id: Uuid) -> Result<(), KeyManagementError> {
+        self.record_usage(key_id, UsageEvent::Verify, self.clock.now()).await
+    }
+
+    async fn record_usage(&self, key_id: Uuid, event: UsageEvent, at: DateTime<Utc>) -> Result<(), KeyManagementError> {
+        if self.usage.try_record(key_id, event, at) {
+            return Ok(());
         }
+        if !self.lock_keys().await.contains_key(&key_id) {
+            return Err(self.drop_usage(key_id));
+        }
+        self.usage.record(key_id, event, at);
+        Ok(())
+    }
+    
+    fn drop_usage(&self, key_id: Uuid) -> KeyManagementError {
+        self.dropped_usage.fetch_add(1, Ordering::Relaxed);
+        KeyManagementError::KeyNotFound(key_id)
+    }
+
+    /// Usage updates dropped since startup
+    pub fn dropped_usage_updates(&self) -> u64 {
+        self.dropped_usage.load(Ordering::Relaxed)
+    }
+    
+    /// Writes pending changes to disk, returning whether anything was written
+    ///
+    /// Unlike the writes made by mutating methods, a failure is returned to the caller. The event
+    /// log is cut down to its retained events along the way.
+    pub async fn flush(&self) -> Result<bool, KeyManagementError> {
+        if !self.public_only {
+            if let Err(e) = self.events.compact().await {
+                tracing::warn!("Event log not compacted: {}", e);
+            }
+        }
+        if !self.dirty.load(Ordering::Acquire) && self.usage.is_empty() {
+            return Ok(false);
+        }
+        let result = self.write_to_disk().await;
+        self.record_write(&result);
+        result.map(|_| true)
+    }
+    
+    /// Current health of keystore persistence
+    pub fn persistence_status(&self) -> PersistenceStatus {
+        self.persistence.lock().unwrap_or_else(|e| e.into_inner()).clone()
     }
     
     /// Updates key information
-    pub async fn update_key(&self, key_id: Uuid, update: UpdateKeyRequest) -> Result<KeyPair, KeyManagementError> {
-        let mut keys = self.keys.lock().await;
+    ///
+    /// `is_active: false` revokes the key and `is_active: true` resumes a suspended one, through
+    /// the key's lifecycle; a move it does not allow fails before anything is changed, as does
+    /// making a non-exportable key exportable again. Only a caller with `admin_scope` may change
+    /// the signing contexts the key is limited to.
+    pub async fn update_key(&self, key_id: Uuid, update: UpdateKeyRequest, admin_scope: bool) -> Result<KeyPair, KeyManagementError> {
+        let now = self.clock.now();
+        let mut keys = self.keys_mut().await;
         if let Some(key_pair) = keys.get_mut(&key_id) {
+            if update.exportable == Some(true) && !key_pair.exportable {
+                return Err(KeyManagementError::InsufficientPermissions(format!("Key {} is not exportable and cannot be made so", key_id)));
+            }
+            if update.allowed_contexts.is_some() && !admin_scope {
+                return Err(KeyManagementError::InsufficientPermissions(format!("Only admin clients may change the signing contexts of key {}", key_id)));
+            }
+            if let Some(is_active) = update.is_active {
+                let target = if is_active { KeyState::Active } else { KeyState::Revoked };
+                // Expired keys and keys pending revocation are already as active as they can be
+                let reached = match key_pair.state(now) {
+                    KeyState::Expired | KeyState::ScheduledRevocation => KeyState::Active,
+                    current => current,
+                };
+                if target != reached {
+                    key_pair.transition(target, None, Some("key update".to_string()), now)?;
+                }
+            }
             if let Some(name) = update.name {
                 key_pair.name = name;
             }
@@ -149,18 +826,37 @@ impl KeyStorage {
             if let Some(tags) = update.tags {
                 key_pair.tags = tags;
             }
-            if let Some(expires_at) = update.expires_at {
+            if let Some(expires_at) = update.expires_at.map(crate::timestamps::normalize) {
+                // A new expiry re-arms the expiry notifications
+                if key_pair.expires_at != Some(expires_at) {
+                    key_pair.notified_thresholds.clear();
+                }
                 key_pair.expires_at = Some(expires_at);
             }
-            if let Some(is_active) = update.is_active {
-                key_pair.is_active = is_active;
+            if let Some(allowed_contexts) = update.allowed_contexts {
+                key_pair.allowed_contexts = (!allowed_contexts.is_empty()).then_some(allowed_contexts);
+            }
+            if let Some(environment) = update.environment {
+                key_pair.environment = environment;
+            }
+            if let Some(exportable) = update.exportable {
+                key_pair.exportable = exportable;
+            }
+            if let Some(format) = update.default_output_format {
+                key_pair.default_output_format = Some(format);
+            }
+            if let Some(hash_algorithm) = update.default_hash_algorithm {
+                key_pair.default_hash_algorithm = Some(hash_algorithm);
+            }
+            if let Some(encoding) = update.default_encoding {
+                key_pair.default_encoding = Some(encoding);
             }
             
             let updated_key_pair = key_pair.clone();
             drop(keys);
             
             // Save to disk
-            self.save_to_disk().await?;
+            self.persist().await;
             
             Ok(updated_key_pair)
         } else {
@@ -168,28 +864,265 @@ impl KeyStorage {
         }
     }
     
-    /// Deactivates a key
-    pub async fn deactivate_key(&self, key_id: Uuid) -> Result<(), KeyManagementError> {
-        let mut keys = self.keys.lock().await;
+    /// Replaces a key's encrypted private key with a self-describing envelope
+    pub async fn replace_private_key(&self, key_id: Uuid, private_key: String) -> Result<(), KeyManagementError> {
+        let mut keys = self.keys_mut().await;
         if let Some(key_pair) = keys.get_mut(&key_id) {
-            key_pair.is_active = false;
+            key_pair.private_key = SecretString::from(private_key);
+            // The envelope carries its own salt
+            key_pair.salt = None;
+            drop(keys);
+            
+            self.persist().await;
             Ok(())
         } else {
             Err(KeyManagementError::KeyNotFound(key_id))
         }
     }
     
-    /// Revokes a key (marks as inactive and sets expiration to now)
-    pub async fn revoke_key(&self, key_id: Uuid, _reason: Option<String>) -> Result<(), KeyManagementError> {
-        let mut keys = self.keys.lock().await;
-        if let Some(key_pair) = keys.get_mut(&key_id) {
-            key_pair.is_active = false;
-            key_pair.expires_at = Some(Utc::now());
-            // TODO: Store revocation reason
-            Ok(())
-        } else {
-            Err(KeyManagementError::KeyNotFound(key_id))
+    /// Stores a key's private key re-encrypted into a fresh envelope, with the revision recording it
+    pub async fn rewrap_private_key(&self, key_id: Uuid, private_key: String, revision: EnvelopeRevision) -> Result<KeyPair, KeyManagementError> {
+        let mut keys = self.keys_mut().await;
+        let key_pair = keys.get_mut(&key_id).ok_or(KeyManagementError::KeyNotFound(key_id))?;
+        key_pair.private_key = SecretString::from(private_key);
+        key_pair.salt = None;
+        key_pair.kdf = Some(revision.kdf);
+        key_pair.envelope_history.push(revision);
+        let rewrapped = key_pair.clone();
+        drop(keys);
+
+        self.persist().await;
+        Ok(rewrapped)
+    }
+
+    /// Returns every stored entry with the id it is indexed under, regardless of key state
+    pub async fn entries(&self) -> Vec<(Uuid, KeyPair)> {
+        let keys = self.lock_keys().await;
+        keys.iter().map(|(key_id, key_pair)| (*key_id, self.with_pending_usage(key_pair.clone()))).collect()
+    }
+    
+    /// Replaces the entry indexed under `indexed_id`, re-indexing it under the record's own id
+    pub async fn replace_entry(&self, indexed_id: Uuid, key_pair: KeyPair) -> Result<(), KeyManagementError> {
+        let mut keys = self.keys_mut().await;
+        if keys.remove(&indexed_id).is_none() {
+            return Err(KeyManagementError::KeyNotFound(indexed_id));
+        }
+        keys.insert(key_pair.id, key_pair);
+        drop(keys);
+        
+        self.persist().await;
+        Ok(())
+    }
+    
+    /// Path of the keystore file
+    pub fn storage_path(&self) -> &str {
+        &self.storage_path
+    }
+    
+    /// Path of the file quarantined entries are moved to
+    pub fn quarantine_path(&self) -> String {
+        format!("{}.quarantine", self.storage_path)
+    }
+    
+    /// Removes an entry from the store and appends it, with the reason, to the quarantine file
+    pub async fn quarantine_key(&self, indexed_id: Uuid, reason: &str) -> Result<(), KeyManagementError> {
+        let key_pair = {
+            let mut keys = self.keys_mut().await;
+            keys.remove(&indexed_id).ok_or(KeyManagementError::KeyNotFound(indexed_id))?
+        };
+        
+        let record = serde_json::json!({
+            "indexed_id": indexed_id,
+            "reason": reason,
+            "quarantined_at": crate::timestamps::normalize(self.clock.now()),
+            "key_pair": PersistedKeyPair::from(&key_pair),
+        });
+        append_records(&self.quarantine_path(), "quarantine", vec![record]).await?;
+        
+        self.persist().await;
+        Ok(())
+    }
+    
+    /// Path of the file archived keys are moved to
+    pub fn archive_path(&self) -> String {
+        format!("{}.archive", self.storage_path)
+    }
+    
+    /// Moves keys out of the store and appends them, with the reason, to the archive file
+    ///
+    /// Ids that are not stored are skipped; returns how many keys were archived. The archive is
+    /// written before the keystore, so a failed write never loses a key.
+    pub async fn archive_keys(&self, key_ids: &[Uuid], reason: &str) -> Result<usize, KeyManagementError> {
+        let mut keys = self.keys_mut().await;
+        let now = crate::timestamps::normalize(self.clock.now());
+        let records: Vec<serde_json::Value> = key_ids.iter()
+            .filter_map(|key_id| keys.get(key_id))
+            .map(|key_pair| serde_json::json!({
+                "reason": reason,
+                "archived_at": now,
+                "key_pair": PersistedKeyPair::from(key_pair),
+            }))
+            .collect();
+        if records.is_empty() {
+            return Ok(0);
+        }
+        append_records(&self.archive_path(), "archive", records).await?;
+        let archived = key_ids.iter().filter(|key_id| keys.remove(key_id).is_some()).count();
+        drop(keys);
+        
+        self.persist().await;
+        Ok(archived)
+    }
+    
+    /// Keys in the archive file, oldest first
+    pub async fn archived_keys(&self) -> Result<Vec<ArchivedKey>, KeyManagementError> {
+        read_records(&self.archive_path(), "archive").await
+    }
+    
+    /// Path of the file soft-deleted keys are moved to
+    pub fn deleted_path(&self) -> String {
+        format!("{}.deleted", self.storage_path)
+    }
+    
+    /// Moves a key out of the store into the deleted-keys file, from which it can be restored
+    ///
+    /// Like archival, the record is written before the keystore so a failed write never loses
+    /// the key.
+    pub async fn soft_delete_key(&self, key_id: Uuid, deleted_by: Option<String>) -> Result<DeletedKey, KeyManagementError> {
+        let now = self.clock.now();
+        let mut keys = self.keys_mut().await;
+        let mut key_pair = keys.get(&key_id).cloned().ok_or(KeyManagementError::KeyNotFound(key_id))?;
+        key_pair.transition(KeyState::Deleted, deleted_by.clone(), None, now)?;
+        let deleted = DeletedKey { deleted_at: now, deleted_by, key_pair };
+        append_records(&self.deleted_path(), "deleted keys", vec![deleted_record(&deleted)]).await?;
+        keys.remove(&key_id);
+        drop(keys);
+        
+        self.persist().await;
+        Ok(deleted)
+    }
+    
+    /// Soft-deleted keys, oldest deletion first
+    pub async fn deleted_keys(&self) -> Result<Vec<DeletedKey>, KeyManagementError> {
+        read_records(&self.deleted_path(), "deleted keys").await
+    }
+    
+    /// Moves a soft-deleted key back into the store in the state it was deleted in
+    ///
+    /// Fails with [`KeyManagementError::KeyNotFound`] if the key was never deleted or has been
+    /// purged, and with [`KeyManagementError::RestoreConflict`] if a key with its id is stored.
+    /// The returned record holds the key as restored.
+    pub async fn restore_deleted_key(&self, key_id: Uuid) -> Result<DeletedKey, KeyManagementError> {
+        let now = self.clock.now();
+        let mut keys = self.keys_mut().await;
+        if keys.contains_key(&key_id) {
+            return Err(KeyManagementError::RestoreConflict(format!("a key with id {} is already stored", key_id)));
+        }
+        let mut records: Vec<DeletedKey> = self.deleted_keys().await?;
+        let position = records.iter().rposition(|record| record.key_pair.id == key_id)
+            .ok_or(KeyManagementError::KeyNotFound(key_id))?;
+        let mut restored = records.remove(position);
+        // Keys deleted before lifecycles existed were stored unchanged, and are restored so
+        if let Some(target) = restored.key_pair.state_before_deletion().filter(|_| restored.key_pair.lifecycle == Lifecycle::Deleted) {
+            restored.key_pair.transition(target, None, Some("restored".to_string()), now)?;
+        }
+        let remaining: Vec<serde_json::Value> = records.iter().map(deleted_record).collect();
+        write_records(&self.deleted_path(), "deleted keys", &remaining).await?;
+        keys.insert(key_id, restored.key_pair.clone());
+        drop(keys);
+        
+        self.persist().await;
+        Ok(restored)
+    }
+    
+    /// Moves a stored key to `to` through its lifecycle, returning the key and the recorded move
+    pub async fn transition_key(
+        &self,
+        key_id: Uuid,
+        to: KeyState,
+        actor: Option<String>,
+        reason: Option<String>,
+    ) -> Result<(KeyPair, LifecycleEvent), KeyManagementError> {
+        let now = self.clock.now();
+        let mut keys = self.keys_mut().await;
+        let key_pair = keys.get_mut(&key_id).ok_or(KeyManagementError::KeyNotFound(key_id))?;
+        let event = key_pair.transition(to, actor, reason, now)?;
+        let updated_key_pair = key_pair.clone();
+        drop(keys);
+
+        self.persist().await;
+        Ok((updated_key_pair, event))
+    }
+
+    /// Deactivates a key by revoking it
+    pub async fn deactivate_key(&self, key_id: Uuid) -> Result<(), KeyManagementError> {
+        self.transition_key(key_id, KeyState::Revoked, None, Some("deactivated".to_string())).await.map(|_| ())
+    }
+    
+    /// Revokes a key, cancelling any scheduled revocation and bringing its expiry forward to now
+    pub async fn revoke_key(&self, key_id: Uuid, reason: Option<String>) -> Result<(), KeyManagementError> {
+        self.transition_key(key_id, KeyState::Revoked, None, reason).await.map(|_| ())
+    }
+    
+    /// Schedules a key's revocation; it stays usable until `effective_at`
+    pub async fn schedule_revocation(&self, key_id: Uuid, effective_at: DateTime<Utc>) -> Result<KeyPair, KeyManagementError> {
+        let mut keys = self.keys_mut().await;
+        let key_pair = keys.get_mut(&key_id).ok_or(KeyManagementError::KeyNotFound(key_id))?;
+        key_pair.revocation_scheduled_at = Some(crate::timestamps::normalize(effective_at));
+        let updated_key_pair = key_pair.clone();
+        drop(keys);
+
+        self.persist().await;
+        Ok(updated_key_pair)
+    }
+    
+    /// Cancels a pending scheduled revocation, returning whether one was pending
+    pub async fn cancel_scheduled_revocation(&self, key_id: Uuid) -> Result<bool, KeyManagementError> {
+        let mut keys = self.keys_mut().await;
+        let key_pair = keys.get_mut(&key_id).ok_or(KeyManagementError::KeyNotFound(key_id))?;
+        let was_pending = key_pair.revocation_scheduled_at.take().is_some();
+        drop(keys);
+
+        if was_pending {
+            self.persist().await;
         }
+        Ok(was_pending)
+    }
+    
+    /// Executes every scheduled revocation due at `now`, returning the revoked key IDs
+    ///
+    /// Revoked keys expire at their scheduled time, not at the moment the sweep ran.
+    pub async fn execute_due_revocations(&self, now: DateTime<Utc>) -> Result<Vec<Uuid>, KeyManagementError> {
+        let mut keys = self.keys_mut().await;
+        let mut revoked = Vec::new();
+        for key_pair in keys.values_mut() {
+            let Some(effective_at) = key_pair.revocation_scheduled_at else { continue };
+            if effective_at > now {
+                continue;
+            }
+            let from = if key_pair.lifecycle == Lifecycle::Suspended { KeyState::Suspended } else { KeyState::ScheduledRevocation };
+            let reason = Some("scheduled revocation".to_string());
+            if key_pair.transition_from(from, KeyState::Revoked, Some("sweeper".to_string()), reason, effective_at).is_ok() {
+                revoked.push(key_pair.id);
+            }
+        }
+        drop(keys);
+
+        if !revoked.is_empty() {
+            self.persist().await;
+        }
+        Ok(revoked)
+    }
+    
+    /// Records that expiry notifications for the given thresholds were sent for a key
+    pub async fn mark_expiry_notified(&self, key_id: Uuid, thresholds_days: &[u32]) -> Result<(), KeyManagementError> {
+        let mut keys = self.keys_mut().await;
+        let key_pair = keys.get_mut(&key_id).ok_or(KeyManagementError::KeyNotFound(key_id))?;
+        key_pair.notified_thresholds.extend(thresholds_days);
+        drop(keys);
+
+        self.persist().await;
+        Ok(())
     }
     
     /// Rotates a key by creating a new one and deactivating the old one
@@ -209,120 +1142,423 @@ impl KeyStorage {
     
     /// Gets keys that are expiring soon (within specified days)
     pub async fn get_keys_expiring_soon(&self, days: u32) -> Vec<KeyInfo> {
-        let keys = self.list_keys().await;
-        let threshold = Utc::now() + Duration::days(days as i64);
-        
-        keys.into_iter()
-            .filter(|key| {
-                if let Some(expires_at) = key.expires_at {
-                    expires_at <= threshold && expires_at > Utc::now()
-                } else {
-                    false
-                }
-            })
-            .collect()
+        let filter = KeyFilter {
+            expiring_before: Some(self.clock.now() + Duration::days(days as i64)),
+            ..KeyFilter::default()
+        };
+        self.list_keys_page(&filter, 0, None).await.keys
     }
     
-    /// Gets key statistics
-    pub async fn get_key_stats(&self) -> (usize, usize, usize, usize) {
-        let keys = self.list_keys().await;
-        let now = Utc::now();
+    /// Gets key statistics: total, usable, expired, revoked and suspended keys
+    ///
+    /// Each key is counted under exactly one of its [`KeyState`]s, so the last four add up to
+    /// the total.
+    pub async fn get_key_stats(&self) -> (usize, usize, usize, usize, usize) {
+        let keys = self.lock_keys().await;
+        let now = self.clock.now();
         
-        let total = keys.len();
-        let active = keys.iter().filter(|k| k.is_active).count();
-        let expired = keys.iter().filter(|k| {
-            k.expires_at.map_or(false, |exp| now > exp)
-        }).count();
-        let revoked = keys.iter().filter(|k| !k.is_active).count();
+        let (mut active, mut expired, mut revoked, mut suspended) = (0, 0, 0, 0);
+        for state in keys.values().map(|key_pair| key_pair.state(now)) {
+            match state {
+                KeyState::Active | KeyState::ScheduledRevocation => active += 1,
+                KeyState::Expired => expired += 1,
+                KeyState::Revoked | KeyState::Deleted => revoked += 1,
+                KeyState::Suspended => suspended += 1,
+            }
+        }
         
-        (total, active, expired, revoked)
+        (keys.len(), active, expired, revoked, suspended)
     }
     
     /// Loads keys from disk on startup
+    ///
+    /// Fails if any entry does not read as a key, leaving the file as it is.
     pub async fn load_from_disk(&self) -> Result<(), KeyManagementError> {
+        let Some(content) = self.read_for_load().await? else {
+            return Ok(());
+        };
+        let keys = self.parse_keys(&content)?;
+        
+        let mut key_map = self.keys_mut().await;
+        for key_pair in keys {
+            key_map.insert(key_pair.id, key_pair);
+        }
+        self.set_synced_hash(content_hash(&content));
+        
+        Ok(())
+    }
+    
+    /// Loads keys from disk on startup, moving entries that do not read as a key to the
+    /// quarantine file instead of failing
+    ///
+    /// For the instance that owns the keystore. An entry may be unreadable for a missing field or
+    /// a value out of range, such as a negative usage count. Returns the reason each entry was
+    /// quarantined for; like [`KeyStorage::quarantine_key`], the record is written before the
+    /// keystore.
+    pub async fn load_quarantining_unreadable(&self) -> Result<Vec<String>, KeyManagementError> {
+        let Some(content) = self.read_for_load().await? else {
+            return Ok(Vec::new());
+        };
+        if content.is_empty() {
+            return Ok(Vec::new());
+        }
+        let entries: Vec<serde_json::Value> = serde_json::from_slice(&content)
+            .map_err(|e| KeyManagementError::StorageError(format!("Failed to parse storage file: {}", e)))?;
+        
+        let now = self.clock.now();
+        let mut keys = Vec::with_capacity(entries.len());
+        let mut reasons = Vec::new();
+        let mut records = Vec::new();
+        for entry in entries {
+            match PersistedKeyPair::deserialize(&entry) {
+                Ok(persisted) => keys.push(KeyPair::from(persisted)),
+                Err(e) => {
+                    let reason = format!("unreadable: {}", e);
+                    records.push(serde_json::json!({
+                        "indexed_id": entry.get("id"),
+                        "reason": reason,
+                        "quarantined_at": now,
+                        "entry": entry,
+                    }));
+                    reasons.push(reason);
+                }
+            }
+        }
+        if !records.is_empty() {
+            append_records(&self.quarantine_path(), "quarantine", records).await?;
+        }
+        
+        let mut key_map = self.keys_mut().await;
+        for key_pair in keys {
+            key_map.insert(key_pair.id, key_pair);
+        }
+        drop(key_map);
+        if reasons.is_empty() {
+            self.set_synced_hash(content_hash(&content));
+        } else {
+            self.persist().await;
+        }
+        
+        Ok(reasons)
+    }
+    
+    /// The keystore file's contents, or `None` when there is no file yet, in which case its
+    /// directory is created; the event log beside it is loaded first
+    async fn read_for_load(&self) -> Result<Option<Vec<u8>>, KeyManagementError> {
+        self.events.load_from_disk().await?;
         let path = Path::new(&self.storage_path);
         if !path.exists() {
-            // Create directory if it doesn't exist
             if let Some(parent) = path.parent() {
                 fs::create_dir_all(parent).await
                     .map_err(|e| KeyManagementError::StorageError(format!("Failed to create directory: {}", e)))?;
             }
-            return Ok(());
+            return Ok(None);
         }
         
-        let content = fs::read_to_string(path).await
-            .map_err(|e| KeyManagementError::StorageError(format!("Failed to read storage file: {}", e)))?;
-        
-        if content.is_empty() {
-            return Ok(());
+        fs::read(path).await
+            .map(Some)
+            .map_err(|e| KeyManagementError::StorageError(format!("Failed to read storage file: {}", e)))
+    }
+    
+    /// Normalizes the name, description and tags of every stored key, keeping the originals in
+    /// the metadata history of each key that changed
+    ///
+    /// Run after loading by the instance that owns the keystore. Returns how many keys changed.
+    pub async fn normalize_stored_metadata(&self) -> usize {
+        let now = self.clock.now();
+        let mut changed = 0;
+        for key_pair in self.keys_mut().await.values_mut() {
+            if normalize_stored_key(key_pair, now) {
+                changed += 1;
+            }
+        }
+        if changed > 0 {
+            self.persist().await;
+        }
+        changed
+    }
+    
+    /// Rewrites the keystore file if it holds expiries or scheduled revocations stored before
+    /// timestamps were normalized, with an offset other than `Z` or finer than milliseconds
+    ///
+    /// The keys in memory were normalized as they were loaded, so only the file changes. Run
+    /// after loading by the instance that owns the keystore. Returns how many keys were stored
+    /// with such a timestamp.
+    pub async fn normalize_stored_timestamps(&self) -> usize {
+        let stored = match fs::read(&self.storage_path).await {
+            Ok(content) => serde_json::from_slice::<Vec<serde_json::Value>>(&content).unwrap_or_default(),
+            Err(_) => Vec::new(),
+        };
+        let outdated = stored.iter()
+            .filter(|key| ["expires_at", "revocation_scheduled_at"].iter().any(|field| {
+                key[field].as_str().is_some_and(|value| crate::timestamps::parse(value).map(crate::timestamps::format).as_deref() != Ok(value))
+            }))
+            .count();
+        if outdated > 0 {
+            self.persist().await;
         }
+        outdated
+    }
+    
+    /// Replaces the in-memory keys with the keystore file's contents
+    ///
+    /// Used by read-only followers to pick up another instance's writes, including deletions and
+    /// the events it recorded.
+    pub async fn reload_from_disk(&self) -> Result<(), KeyManagementError> {
+        let content = match fs::read(&self.storage_path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(KeyManagementError::StorageError(format!("Failed to read storage file: {}", e))),
+        };
+        let keys = self.parse_keys(&content)?;
+        self.events.load_from_disk().await?;
         
-        let keys: Vec<KeyPair> = serde_json::from_str(&content)
-            .map_err(|e| KeyManagementError::StorageError(format!("Failed to parse storage file: {}", e)))?;
+        *self.lock_keys().await = Arc::new(keys.into_iter().map(|key_pair| (key_pair.id, key_pair)).collect());
+        self.usage.drain();
+        self.set_synced_hash(content_hash(&content));
+        Ok(())
+    }
+
+    /// Replaces the in-memory keys with a verified set that never touches the keystore file
+    ///
+    /// Used when a verifier takes its keys from a trust manifest; private key material is
+    /// dropped as when loading a public-only store.
+    pub async fn replace_with_trusted(&self, keys: Vec<KeyPair>) {
+        let keys: HashMap<Uuid, KeyPair> = keys.into_iter()
+            .map(|mut key_pair| {
+                key_pair.private_key = SecretString::default();
+                key_pair.salt = None;
+                (key_pair.id, key_pair)
+            })
+            .collect();
+        *self.lock_keys().await = Arc::new(keys);
+        self.usage.drain();
+    }
+
+    /// Picks up changes made to the keystore file outside the service, such as restores or
+    /// manual fixes
+    ///
+    /// The file is compared with the hash of what this instance last read or wrote, so the
+    /// service's own writes never trigger a reload. A changed file replaces the in-memory keys
+    /// in one swap, unless there are in-memory changes not yet written to disk: those win, and
+    /// the conflict is logged.
+    pub async fn reload_if_changed(&self) -> Result<KeystoreReload, KeyManagementError> {
+        // Holding the map lock keeps our own writes out while the file is compared
+        let mut keys = self.lock_keys().await;
+        let content = match fs::read(&self.storage_path).await {
+            Ok(content) => content,
+            // Editors and restores may briefly remove the file; wait for it to reappear
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(KeystoreReload::Unchanged),
+            Err(e) => return Err(KeyManagementError::StorageError(format!("Failed to read storage file: {}", e))),
+        };
+        let hash = content_hash(&content);
+        if *self.synced_hash.lock().unwrap_or_else(|e| e.into_inner()) == Some(hash) {
+            return Ok(KeystoreReload::Unchanged);
+        }
         
-        let mut key_map = self.keys.lock().await;
-        for key_pair in keys {
-            key_map.insert(key_pair.id, key_pair);
+        let updated: HashMap<Uuid, KeyPair> = self.parse_keys(&content)?.into_iter()
+            .map(|key_pair| (key_pair.id, key_pair))
+            .collect();
+        let change = KeystoreChange::between(&keys, &updated);
+        if self.dirty.load(Ordering::Acquire) || !self.usage.is_empty() {
+            tracing::error!(
+                "Keystore file {} was modified externally ({}) while in-memory changes are unsaved; \
+                 keeping the in-memory keys, which will overwrite the external change",
+                self.storage_path,
+                change.summary(),
+            );
+            return Ok(KeystoreReload::Conflict(change));
         }
         
-        Ok(())
+        *keys = Arc::new(updated);
+        self.set_synced_hash(hash);
+        tracing::warn!("Reloaded externally modified keystore {}: {}", self.storage_path, change.summary());
+        Ok(KeystoreReload::Reloaded(change))
     }
     
-    /// Saves keys to disk
-    async fn save_to_disk(&self) -> Result<(), KeyManagementError> {
-        let keys = self.keys.lock().await;
-        let keys_vec: Vec<&KeyPair> = keys.values().collect();
-        
-        let content = serde_json::to_string_pretty(&keys_vec)
+    /// Parses the keystore file, dropping private key material when this store is public-only
+    fn parse_keys(&self, content: &[u8]) -> Result<Vec<KeyPair>, KeyManagementError> {
+        let mut keys = parse_keystore(content)?;
+        if self.public_only {
+            for key_pair in &mut keys {
+                key_pair.private_key = SecretString::default();
+                key_pair.salt = None;
+            }
+        }
+        Ok(keys)
+    }
+
+    fn set_synced_hash(&self, hash: [u8; 32]) {
+        *self.synced_hash.lock().unwrap_or_else(|e| e.into_inner()) = Some(hash);
+    }
+    
+    /// Saves keys to disk, recording a failure instead of returning it
+    ///
+    /// The in-memory change stands either way; a failed write marks persistence as degraded and
+    /// leaves the changes pending for [`KeyStorage::flush`].
+    async fn persist(&self) {
+        let result = self.write_to_disk().await;
+        self.record_write(&result);
+    }
+    
+    fn record_write(&self, result: &Result<(), KeyManagementError>) {
+        let mut status = self.persistence.lock().unwrap_or_else(|e| e.into_inner());
+        match result {
+            Ok(()) => {
+                if status.degraded {
+                    tracing::info!("Keystore persistence recovered after {} failed writes", status.consecutive_failures);
+                }
+                *status = PersistenceStatus::default();
+            }
+            Err(e) => {
+                tracing::error!("Keystore write failed, keeping changes in memory: {}", e);
+                status.degraded = true;
+                status.consecutive_failures += 1;
+                status.last_error = Some(e.to_string());
+                status.last_failure_at = Some(crate::timestamps::normalize(self.clock.now()));
+            }
+        }
+    }
+    
+    /// Writes every key to disk durably, reporting the outcome and time taken to the breaker
+    ///
+    /// The time includes waiting for the keys, which every request touching them also waits for.
+    async fn write_to_disk(&self) -> Result<(), KeyManagementError> {
+        let started = std::time::Instant::now();
+        let result = self.write_keys().await;
+        self.breaker.record(result.is_ok(), started.elapsed(), self.clock.now());
+        result
+    }
+
+    async fn write_keys(&self) -> Result<(), KeyManagementError> {
+        if self.public_only {
+            return Err(KeyManagementError::StorageError("A keystore loaded without private keys is never written".to_string()));
+        }
+        let mut keys = self.lock_keys().await;
+        self.fold_usage(&mut keys);
+        let content = serialize_keys(keys.values())
             .map_err(|e| KeyManagementError::StorageError(format!("Failed to serialize keys: {}", e)))?;
         
-        fs::write(&self.storage_path, content).await
-            .map_err(|e| KeyManagementError::StorageError(format!("Failed to write storage file: {}", e)))?;
+        // Changes recorded while the lock is held are part of this snapshot
+        self.dirty.store(false, Ordering::Release);
+        if let Err(e) = write_keystore_file(Path::new(&self.storage_path), content.as_bytes()).await {
+            self.dirty.store(true, Ordering::Release);
+            return Err(KeyManagementError::StorageError(format!("Failed to write storage file: {}", e)));
+        }
+        self.set_synced_hash(content_hash(content.as_bytes()));
         
         Ok(())
     }
     
+    /// Permanently removes a key pair
+    pub async fn remove_key(&self, key_id: Uuid) -> Result<KeyPair, KeyManagementError> {
+        let removed = self.keys_mut().await
+            .remove(&key_id)
+            .ok_or(KeyManagementError::KeyNotFound(key_id))?;
+        self.persist().await;
+        Ok(removed)
+    }
+    
     /// Gets the count of stored keys
     pub async fn key_count(&self) -> usize {
-        let keys = self.keys.lock().await;
+        let keys = self.lock_keys().await;
         keys.len()
     }
     
     /// Checks if a key exists
     pub async fn key_exists(&self, key_id: Uuid) -> bool {
-        let keys = self.keys.lock().await;
+        let keys = self.lock_keys().await;
         keys.contains_key(&key_id)
     }
     
     /// Searches keys by name or tags
     pub async fn search_keys(&self, query: &str) -> Vec<KeyInfo> {
-        let keys = self.list_keys().await;
-        let query_lower = query.to_lowercase();
-        
-        keys.into_iter()
-            .filter(|key| {
-                key.name.to_lowercase().contains(&query_lower) ||
-                key.description.as_ref().map_or(false, |desc| desc.to_lowercase().contains(&query_lower)) ||
-                key.tags.iter().any(|tag| tag.to_lowercase().contains(&query_lower))
-            })
-            .collect()
+        let filter = KeyFilter { search: Some(query.to_string()), ..KeyFilter::default() };
+        self.list_keys_page(&filter, 0, None).await.keys
     }
     
-    /// Creates a backup of the current keys
-    pub async fn create_backup(&self, backup_path: &str) -> Result<(), KeyManagementError> {
-        let keys = self.keys.lock().await;
-        let keys_vec: Vec<&KeyPair> = keys.values().collect();
-        
-        let content = serde_json::to_string_pretty(&keys_vec)
+    /// Creates a backup of the keys as they are now, returning the time of the snapshot it was
+    /// written from
+    ///
+    /// Writes carry on against the live keys while the backup file is written. Keys that are not
+    /// exportable lose their private key unless the backup policy keeps it as stored.
+    pub async fn create_backup(&self, backup_path: &str) -> Result<DateTime<Utc>, KeyManagementError> {
+        let snapshot = self.begin_snapshot().await;
+        let keys: Vec<PersistedKeyPair> = snapshot.keys.values()
+            .map(|key_pair| {
+                let mut persisted = PersistedKeyPair::from(key_pair);
+                if !key_pair.exportable && self.non_exportable_backup == NonExportableBackup::Exclude {
+                    persisted.private_key.clear();
+                    persisted.salt = None;
+                    persisted.kdf = None;
+                }
+                persisted
+            })
+            .collect();
+        let content = serde_json::to_string_pretty(&keys)
             .map_err(|e| KeyManagementError::StorageError(format!("Failed to serialize keys for backup: {}", e)))?;
         
         fs::write(backup_path, content).await
             .map_err(|e| KeyManagementError::StorageError(format!("Failed to write backup file: {}", e)))?;
         
-        Ok(())
+        Ok(snapshot.taken_at)
     }
 }
 
+/// A point-in-time view of the keystore, from [`KeyStorage::begin_snapshot`]
+///
+/// Clones share the view, which is released when the last of them is dropped.
+#[derive(Debug, Clone)]
+pub struct KeystoreSnapshot {
+    keys: Arc<HashMap<Uuid, KeyPair>>,
+    taken_at: DateTime<Utc>,
+}
+
+impl KeystoreSnapshot {
+    /// When the snapshot was taken
+    pub fn taken_at(&self) -> DateTime<Utc> {
+        self.taken_at
+    }
+    
+    /// Every entry in the snapshot, regardless of key state, oldest first
+    pub fn key_pairs(&self) -> Vec<KeyPair> {
+        let mut keys: Vec<KeyPair> = self.keys.values().cloned().collect();
+        keys.sort_by_key(|key_pair| (key_pair.created_at, key_pair.id));
+        keys
+    }
+    
+    /// Number of entries in the snapshot
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+    
+    /// Whether the snapshot holds no entries
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+/// Replaces `path` with `content` through a synced temporary file, then syncs the directory so
+/// the rename itself survives a crash
+pub(crate) async fn write_durably(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    let temp_path = format!("{}.tmp", path.display());
+    let mut file = fs::File::create(&temp_path).await?;
+    file.write_all(content).await?;
+    file.sync_all().await?;
+    drop(file);
+    fs::rename(&temp_path, path).await?;
+    
+    let directory = path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    fs::File::open(directory).await?.sync_all().await
+}
+
+/// Writes the keystore file, through the `keystore.write` failpoint in test builds
+async fn write_keystore_file(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    #[cfg(any(test, feature = "failpoints"))]
+    crate::failpoints::io_fault(crate::failpoints::KEYSTORE_WRITE).await?;
+    write_durably(path, content).await
+}
+
 /// Creates a default key storage instance
 pub fn create_default_storage() -> KeyStorage {
     let storage_path = std::env::var("STORAGE_PATH").unwrap_or_else(|_| "keys.json".to_string());
@@ -332,8 +1568,9 @@ pub fn create_default_storage() -> KeyStorage {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::key_generation::generate_test_key_pair;
+    use crate::key_generation::{generate_key_pair, generate_test_key_pair};
     use crate::models::{GenerateKeyRequest, UpdateKeyRequest};
+    use chrono::TimeZone;
     use tempfile::tempdir;
     
     #[tokio::test]
@@ -367,6 +1604,24 @@ mod tests {
         assert_eq!(keys.len(), 1);
         assert_eq!(keys[0].name, "Test Key");
     }
+
+    #[tokio::test]
+    async fn test_store_new_key_refuses_a_name_in_use() {
+        let temp_dir = tempdir().unwrap();
+        let storage = KeyStorage::new(temp_dir.path().join("test_keys.json").to_str().unwrap());
+        let first = generate_test_key_pair("Release").unwrap();
+        storage.store_new_key(first.clone()).await.unwrap();
+
+        // Names match case-insensitively, as generation validates them
+        let second = generate_test_key_pair("release").unwrap();
+        assert!(matches!(storage.store_new_key(second.clone()).await, Err(KeyManagementError::KeyConflict(_))));
+        assert_eq!(storage.key_count().await, 1);
+
+        // A revoked key no longer holds its name
+        storage.transition_key(first.id, KeyState::Revoked, None, None).await.unwrap();
+        storage.store_new_key(second).await.unwrap();
+        assert_eq!(storage.key_count().await, 2);
+    }
     
     #[tokio::test]
     async fn test_update_key() {
@@ -384,11 +1639,422 @@ mod tests {
             tags: Some(vec!["updated".to_string()]),
             expires_at: None,
             is_active: None,
+            allowed_contexts: None,
+            environment: None,
+            exportable: None,
+            default_output_format: None,
+            default_hash_algorithm: None,
+            default_encoding: None,
         };
         
-        let updated = storage.update_key(key_id, update).await.unwrap();
+        let updated = storage.update_key(key_id, update, false).await.unwrap();
         assert_eq!(updated.name, "Updated Key");
         assert_eq!(updated.description, Some("Updated description".to_string()));
         assert_eq!(updated.tags, vec!["updated"]);
     }
+    
+    #[tokio::test]
+    async fn test_stored_metadata_is_normalized_and_searchable() {
+        let temp_dir = tempdir().unwrap();
+        let storage_path = temp_dir.path().join("test_keys.json");
+        // Written by an older version that stored names and tags as given
+        let mut key_pair = generate_test_key_pair("Root").unwrap();
+        key_pair.name = "Cafe\u{301}\u{200B} Root".to_string();
+        key_pair.tags = vec!["Release\u{202E}".to_string(), "\u{410}\u{420}\u{406}".to_string()];
+        std::fs::write(&storage_path, serialize_keys([&key_pair].into_iter()).unwrap()).unwrap();
+
+        let storage = KeyStorage::new(storage_path.to_str().unwrap());
+        storage.load_from_disk().await.unwrap();
+        assert_eq!(storage.normalize_stored_metadata().await, 1);
+        assert_eq!(storage.normalize_stored_metadata().await, 0);
+
+        // The rewrite reaches disk with the original metadata in the key's history
+        let reloaded = KeyStorage::new(storage_path.to_str().unwrap());
+        reloaded.load_from_disk().await.unwrap();
+        let stored = reloaded.get_key_record(key_pair.id).await.unwrap();
+        assert_eq!(stored.name, "Caf\u{e9} Root");
+        assert_eq!(stored.tags, ["Release", "\u{410}\u{420}\u{406}"]);
+        assert_eq!(stored.metadata_history[0].name, key_pair.name);
+        assert_eq!(stored.metadata_history[0].tags, key_pair.tags);
+
+        // Searches in either composition, with stray invisible characters, find the key
+        for query in ["café", "Cafe\u{301} root", "caf\u{200D}é"] {
+            assert_eq!(ids(&reloaded.search_keys(query).await), [key_pair.id], "{:?}", query);
+        }
+        for tags in [vec!["RELEASE"], vec!["\u{430}\u{440}\u{456}", "release\u{FEFF}"]] {
+            let filter = KeyFilter { tags: Some(tags.into_iter().map(str::to_string).collect()), ..KeyFilter::default() };
+            assert_eq!(reloaded.list_keys_page(&filter, 0, None).await.matched, 1);
+        }
+        // The Cyrillic tag is not its Latin lookalike "API"
+        let latin = KeyFilter { tags: Some(vec!["api".to_string()]), ..KeyFilter::default() };
+        assert_eq!(reloaded.list_keys_page(&latin, 0, None).await.matched, 0);
+    }
+
+    #[tokio::test]
+    async fn test_failed_writes_degrade_and_recover() {
+        let temp_dir = tempdir().unwrap();
+        // Writes into a missing directory fail even for root, unlike a read-only permission bit
+        let storage_dir = temp_dir.path().join("unavailable");
+        let storage_path = storage_dir.join("test_keys.json");
+        let storage = KeyStorage::new(storage_path.to_str().unwrap());
+        
+        let key_pair = generate_test_key_pair("Test Key").unwrap();
+        storage.store_key(key_pair.clone()).await.unwrap();
+        assert!(storage.get_key(key_pair.id).await.is_ok());
+        
+        let status = storage.persistence_status();
+        assert!(status.degraded);
+        assert_eq!(status.consecutive_failures, 1);
+        let retry_at = status.next_retry_at().unwrap();
+        assert!(!status.write_due(retry_at - Duration::seconds(1)));
+        assert!(status.write_due(retry_at));
+        
+        assert!(storage.flush().await.is_err());
+        assert_eq!(storage.persistence_status().consecutive_failures, 2);
+        
+        // Once the storage is writable again the pending changes are flushed
+        fs::create_dir_all(&storage_dir).await.unwrap();
+        assert!(storage.flush().await.unwrap());
+        assert_eq!(storage.persistence_status(), PersistenceStatus::default());
+        assert!(!Path::new(&format!("{}.tmp", storage_path.display())).exists());
+        
+        let restarted = KeyStorage::new(storage_path.to_str().unwrap());
+        restarted.load_from_disk().await.unwrap();
+        assert!(restarted.key_exists(key_pair.id).await);
+    }
+    
+    #[tokio::test]
+    async fn test_external_keystore_changes_are_reloaded() {
+        let temp_dir = tempdir().unwrap();
+        let storage_path = temp_dir.path().join("test_keys.json");
+        let storage = KeyStorage::new(storage_path.to_str().unwrap());
+        let original = generate_test_key_pair("Original Key").unwrap();
+        storage.store_key(original.clone()).await.unwrap();
+        
+        // The service's own writes are not mistaken for external changes
+        assert_eq!(storage.reload_if_changed().await.unwrap(), KeystoreReload::Unchanged);
+        
+        // An operator restores a keystore with a different key in place of the original
+        let restored = generate_test_key_pair("Restored Key").unwrap();
+        fs::write(&storage_path, serialize_keys([&restored].into_iter()).unwrap()).await.unwrap();
+        let KeystoreReload::Reloaded(change) = storage.reload_if_changed().await.unwrap() else {
+            panic!("external change should be reloaded");
+        };
+        assert_eq!(change, KeystoreChange { added: vec![restored.id], removed: vec![original.id], changed: vec![] });
+        assert!(storage.key_exists(restored.id).await);
+        assert!(!storage.key_exists(original.id).await);
+        assert_eq!(storage.reload_if_changed().await.unwrap(), KeystoreReload::Unchanged);
+        
+        // Unsaved in-memory changes win over a conflicting external edit
+        storage.record_verify(restored.id).await.unwrap();
+        fs::write(&storage_path, "[]").await.unwrap();
+        assert!(matches!(storage.reload_if_changed().await.unwrap(), KeystoreReload::Conflict(_)));
+        assert!(storage.key_exists(restored.id).await);
+        assert!(storage.flush().await.unwrap());
+        assert_eq!(storage.reload_if_changed().await.unwrap(), KeystoreReload::Unchanged);
+    }
+
+    thread_local! {
+        static ALLOCATED_BYTES: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+    }
+
+    /// Counts the bytes each thread allocates, so concurrently running tests do not interfere
+    struct CountingAllocator;
+
+    unsafe impl std::alloc::GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
+            let _ = ALLOCATED_BYTES.try_with(|bytes| bytes.set(bytes.get() + layout.size()));
+            std::alloc::System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: std::alloc::Layout) {
+            std::alloc::System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    fn allocated_bytes() -> usize {
+        ALLOCATED_BYTES.with(|bytes| bytes.get())
+    }
+
+    /// The listing before filtering moved under the lock: every key cloned and converted, then filtered
+    async fn list_by_cloning(storage: &KeyStorage, keep: impl Fn(&KeyInfo) -> bool) -> Vec<KeyInfo> {
+        let keys = storage.keys.lock().await;
+        let now = storage.clock.now();
+        keys.values().map(|key_pair| KeyInfo::new(key_pair.clone(), now)).filter(keep).collect()
+    }
+
+    fn ids(keys: &[KeyInfo]) -> Vec<Uuid> {
+        let mut ids: Vec<Uuid> = keys.iter().map(|key| key.id).collect();
+        ids.sort();
+        ids
+    }
+
+    #[tokio::test]
+    async fn test_export_from_a_snapshot_ignores_writes_made_during_it() {
+        use crate::deadline::Deadline;
+        use crate::export::{build_export, ExportManifest, KeyFileEncoding, MANIFEST_FILE};
+        let temp_dir = tempdir().unwrap();
+        let storage = Arc::new(KeyStorage::new(temp_dir.path().join("test_keys.json").to_str().unwrap()));
+        for i in 0..40 {
+            storage.store_key(generate_test_key_pair(&format!("Key {}", i)).unwrap()).await.unwrap();
+        }
+        let snapshot = storage.begin_snapshot().await;
+        let before = serialize_keys(snapshot.key_pairs().iter()).unwrap();
+        let ids: Vec<Uuid> = snapshot.key_pairs().iter().map(|key_pair| key_pair.id).collect();
+
+        // Every kind of write races the export, including a bulk archive
+        let writer = tokio::spawn({
+            let storage = storage.clone();
+            let ids = ids.clone();
+            async move {
+                for (i, key_id) in ids.iter().enumerate().skip(10) {
+                    match i % 4 {
+                        0 => storage.revoke_key(*key_id, Some("rotated".to_string())).await.unwrap(),
+                        1 => drop(storage.remove_key(*key_id).await.unwrap()),
+                        2 => storage.record_sign(*key_id, Utc::now()).await.unwrap(),
+                        _ => storage.store_key(generate_test_key_pair("Added").unwrap()).await.unwrap(),
+                    }
+                    tokio::task::yield_now().await;
+                }
+                storage.archive_keys(&ids[..10], "cleanup").await.unwrap();
+            }
+        });
+        let mut exports = Vec::new();
+        while !writer.is_finished() {
+            let files = build_export(&snapshot.key_pairs(), &[KeyFileEncoding::Pem], snapshot.taken_at(), None, &Deadline::none()).unwrap();
+            exports.push(files);
+            tokio::task::yield_now().await;
+        }
+        writer.await.unwrap();
+        exports.push(build_export(&snapshot.key_pairs(), &[KeyFileEncoding::Pem], snapshot.taken_at(), None, &Deadline::none()).unwrap());
+
+        for files in exports {
+            let manifest = files.iter().find(|file| file.path == MANIFEST_FILE).unwrap();
+            let manifest: ExportManifest = serde_json::from_slice(&manifest.content).unwrap();
+            assert_eq!(manifest.keys.iter().map(|entry| entry.key_id).collect::<Vec<_>>(), ids);
+            assert!(manifest.keys.iter().all(|entry| entry.is_active));
+            assert_eq!(manifest.generated_at, snapshot.taken_at());
+        }
+        assert_eq!(serialize_keys(snapshot.key_pairs().iter()).unwrap(), before);
+
+        // The live keys moved on, and a backup now takes them as they are
+        assert_eq!(storage.key_count().await, 40 - 10 - 7 + 8); // Archived, removed, added
+        let backup_path = temp_dir.path().join("backup.json");
+        let taken_at = storage.create_backup(backup_path.to_str().unwrap()).await.unwrap();
+        assert!(taken_at >= snapshot.taken_at());
+        let backed_up = parse_keystore(&std::fs::read(&backup_path).unwrap()).unwrap();
+        assert_eq!(backed_up.len(), storage.key_count().await);
+        assert!(backed_up.iter().all(|key_pair| !ids[..10].contains(&key_pair.id)));
+    }
+
+    #[tokio::test]
+    async fn test_filtered_listing_converts_only_matching_keys() {
+        let temp_dir = tempdir().unwrap();
+        let storage = KeyStorage::new(temp_dir.path().join("test_keys.json").to_str().unwrap());
+        let template = generate_test_key_pair("Template").unwrap();
+        {
+            let mut keys = storage.keys_mut().await;
+            for i in 0..10_000 {
+                let key_pair = KeyPair {
+                    id: Uuid::new_v4(),
+                    name: format!("Key {}", i),
+                    created_at: template.created_at + Duration::seconds(i),
+                    tags: if i % 100 == 0 { vec!["release".to_string()] } else { vec!["build".to_string(), format!("shard-{}", i % 7)] },
+                    ..template.clone()
+                };
+                keys.insert(key_pair.id, key_pair);
+            }
+        }
+
+        let before = allocated_bytes();
+        let cloned = list_by_cloning(&storage, |key| key.tags.contains(&"release".to_string())).await;
+        let cloning_bytes = allocated_bytes() - before;
+
+        let filter = KeyFilter { tags: Some(vec!["release".to_string()]), ..KeyFilter::default() };
+        let before = allocated_bytes();
+        let page = storage.list_keys_page(&filter, 0, None).await;
+        let filtering_bytes = allocated_bytes() - before;
+
+        assert_eq!(page.matched, 100);
+        assert_eq!(ids(&page.keys), ids(&cloned));
+        let by_id = |keys: &[KeyInfo]| keys.iter().map(|key| (key.id, serde_json::to_value(key).unwrap())).collect::<HashMap<_, _>>();
+        assert_eq!(by_id(&page.keys), by_id(&cloned));
+        assert!(filtering_bytes * 10 < cloning_bytes, "{} bytes filtering vs {} cloning", filtering_bytes, cloning_bytes);
+
+        // A page converts only its own keys, in creation order
+        let before = allocated_bytes();
+        let second = storage.list_keys_page(&filter, 10, Some(10)).await;
+        let page_bytes = allocated_bytes() - before;
+        assert_eq!(second.matched, 100);
+        assert_eq!(second.keys.iter().map(|key| key.name.as_str()).collect::<Vec<_>>(), (10..20).map(|i| format!("Key {}", i * 100)).collect::<Vec<_>>());
+        assert!(page_bytes < filtering_bytes);
+
+        // The cursor of a page picks up where the offset would
+        let after = storage.list_keys_after(second.next_cursor.as_ref(), Some(10), &filter).await;
+        assert_eq!(ids(&after.keys), ids(&storage.list_keys_page(&filter, 20, Some(10)).await.keys));
+        assert_eq!(after.matched, 100);
+
+        // Search and expiry listings match the old results
+        let searched = storage.search_keys("KEY 99").await;
+        assert_eq!(ids(&searched), ids(&list_by_cloning(&storage, |key| key.name.to_lowercase().contains("key 99")).await));
+        assert_eq!(searched.len(), 111);
+        assert!(storage.get_keys_expiring_soon(30).await.is_empty());
+        assert_eq!(storage.get_key_stats().await, (10_000, 10_000, 0, 0, 0));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    async fn test_parallel_signatures_are_counted_off_the_keys_lock() {
+        const SIGNS: usize = 400;
+        let temp_dir = tempdir().unwrap();
+        let storage_path = temp_dir.path().join("test_keys.json");
+        let storage = Arc::new(KeyStorage::new(storage_path.to_str().unwrap()));
+        let key_pair = generate_test_key_pair("Busy Key").unwrap();
+        storage.store_key(key_pair.clone()).await.unwrap();
+        let start = Utc::now();
+        let signed_at = move |i: usize| start + Duration::milliseconds(i as i64);
+
+        let sign_in_parallel = |storage: Arc<KeyStorage>, key_id: Uuid| async move {
+            let before = storage.keys_lock_acquisitions();
+            let tasks: Vec<_> = (0..SIGNS).map(|i| {
+                let storage = storage.clone();
+                tokio::spawn(async move { storage.record_sign(key_id, signed_at(SIGNS - i)).await })
+            }).collect();
+            for task in tasks {
+                task.await.unwrap().unwrap();
+            }
+            storage.keys_lock_acquisitions() - before
+        };
+        // Counting under the lock took it once per signature; now only the first signatures of
+        // the day, racing to check the key, take it, and once the day has an entry none do
+        let first = sign_in_parallel(storage.clone(), key_pair.id).await;
+        assert!(first < SIGNS as u64 / 10, "{} acquisitions for {} signatures", first, SIGNS);
+        assert_eq!(sign_in_parallel(storage.clone(), key_pair.id).await, 0);
+
+        // Reads see the pending usage, and a flush writes it once
+        let expected_last = Some(signed_at(SIGNS));
+        let served = storage.get_key_record(key_pair.id).await.unwrap();
+        assert_eq!((served.usage.sign_count, served.last_used, served.usage.last_sign_at), (2 * SIGNS as u64, expected_last, expected_last));
+        assert_eq!(storage.list_keys().await[0].usage, served.usage);
+        assert!(storage.flush().await.unwrap());
+        assert!(!storage.flush().await.unwrap());
+
+        let restarted = KeyStorage::new(storage_path.to_str().unwrap());
+        restarted.load_from_disk().await.unwrap();
+        let persisted = restarted.get_key_record(key_pair.id).await.unwrap();
+        assert_eq!((persisted.usage, persisted.last_used), (served.usage, expected_last));
+        assert_eq!(storage.dropped_usage_updates(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_injected_write_failures_and_latency_reach_degraded_mode_and_the_breaker() {
+        use crate::config::StorageBreakerConfig;
+        use crate::failpoints::{FailAction, FailScenario, KEYSTORE_WRITE};
+        use crate::storage_breaker::{BreakerState, StorageBreaker};
+
+        let temp_dir = tempdir().unwrap();
+        let storage_path = temp_dir.path().join("test_keys.json");
+        let breaker = StorageBreaker::new(StorageBreakerConfig { failure_percent: 50, slow_ms: 20, open_secs: 30, probes: 1 });
+        let storage = KeyStorage::new(storage_path.to_str().unwrap()).with_breaker(breaker);
+        let scenario = FailScenario::new();
+
+        // Only the second write fails; its key stays in memory and reaches disk on the next flush
+        scenario.arm(KEYSTORE_WRITE, FailAction::FailNth(2));
+        let (first, second) = (generate_test_key_pair("First").unwrap(), generate_test_key_pair("Second").unwrap());
+        storage.store_key(first.clone()).await.unwrap();
+        assert!(!storage.persistence_status().degraded);
+        storage.store_key(second.clone()).await.unwrap();
+        let status = storage.persistence_status();
+        assert!(status.degraded);
+        assert!(status.last_error.unwrap().contains("injected failure at keystore.write"));
+        assert!(storage.key_exists(second.id).await);
+        let restarted = KeyStorage::new(storage_path.to_str().unwrap());
+        restarted.load_from_disk().await.unwrap();
+        assert!(!restarted.key_exists(second.id).await);
+
+        assert!(storage.flush().await.unwrap());
+        assert_eq!(scenario.hits(KEYSTORE_WRITE), 3);
+        assert_eq!(storage.persistence_status(), PersistenceStatus::default());
+        restarted.load_from_disk().await.unwrap();
+        assert!(restarted.key_exists(second.id).await);
+
+        // Writes that succeed but crawl open the breaker all the same
+        scenario.arm(KEYSTORE_WRITE, FailAction::Delay(std::time::Duration::from_millis(30)));
+        for name in ["Slow 1", "Slow 2", "Slow 3", "Slow 4", "Slow 5"] {
+            storage.store_key(generate_test_key_pair(name).unwrap()).await.unwrap();
+        }
+        assert!(!storage.persistence_status().degraded);
+        assert_eq!(storage.breaker().status(Utc::now()).state, BreakerState::Open);
+    }
+
+    #[tokio::test]
+    async fn test_key_cursors_round_trip_and_break_creation_time_ties_by_id() {
+        let temp_dir = tempdir().unwrap();
+        let storage = KeyStorage::new(temp_dir.path().join("test_keys.json").to_str().unwrap());
+        let template = generate_test_key_pair("Template").unwrap();
+        let mut ids = Vec::new();
+        for i in 0..5 {
+            let key_pair = KeyPair { id: Uuid::new_v4(), name: format!("Key {}", i), ..template.clone() };
+            ids.push(key_pair.id);
+            storage.store_key(key_pair).await.unwrap();
+        }
+        ids.sort();
+
+        let mut cursor = None;
+        let mut listed = Vec::new();
+        loop {
+            let page = storage.list_keys_after(cursor.as_ref(), Some(2), &KeyFilter::default()).await;
+            listed.extend(page.keys.iter().map(|key| key.id));
+            let Some(next) = page.next_cursor else { break };
+            let token = next.encode();
+            assert!(token.bytes().all(|byte| byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_'));
+            cursor = Some(KeyCursor::decode(&token).unwrap());
+            assert_eq!(cursor, Some(next));
+        }
+        assert_eq!(listed, ids);
+
+        for token in ["", "not a cursor", "bm90LWEtZGF0ZS94"] {
+            assert!(matches!(KeyCursor::decode(token), Err(KeyManagementError::InvalidRequest(_))), "{}", token);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stored_expiries_round_trip_normalized_and_older_files_are_rewritten() {
+        let temp_dir = tempdir().unwrap();
+        let storage_path = temp_dir.path().join("test_keys.json");
+        let storage = KeyStorage::new(storage_path.to_str().unwrap());
+
+        // An expiry set with nanoseconds is the same instant before and after a save and load
+        let mut key_pair = generate_key_pair(GenerateKeyRequest {
+            name: "Expiring".to_string(),
+            expires_at: Some(Utc::now() + Duration::days(30)),
+            ..Default::default()
+        }).unwrap();
+        assert_eq!(key_pair.expires_at, key_pair.expires_at.map(crate::timestamps::normalize));
+        storage.store_key(key_pair.clone()).await.unwrap();
+        let scheduled = storage.schedule_revocation(key_pair.id, Utc::now() + Duration::days(7)).await.unwrap();
+        let reloaded = KeyStorage::new(storage_path.to_str().unwrap());
+        reloaded.load_from_disk().await.unwrap();
+        let stored = reloaded.get_key_record(key_pair.id).await.unwrap();
+        assert_eq!((stored.expires_at, stored.revocation_scheduled_at), (key_pair.expires_at, scheduled.revocation_scheduled_at));
+        let written: Vec<serde_json::Value> = serde_json::from_slice(&std::fs::read(&storage_path).unwrap()).unwrap();
+        assert!(written[0]["expires_at"].as_str().unwrap().ends_with('Z'));
+
+        // Written by an older version that kept the offset and precision it was sent
+        key_pair.id = Uuid::new_v4();
+        let mut older: Vec<serde_json::Value> = serde_json::from_str(&serialize_keys([&key_pair].into_iter()).unwrap()).unwrap();
+        older[0]["expires_at"] = serde_json::json!("2030-06-01T10:30:00.123456789+01:00");
+        std::fs::write(&storage_path, serde_json::to_vec(&older).unwrap()).unwrap();
+
+        let storage = KeyStorage::new(storage_path.to_str().unwrap());
+        storage.load_from_disk().await.unwrap();
+        let expected = Utc.with_ymd_and_hms(2030, 6, 1, 9, 30, 0).unwrap() + Duration::milliseconds(123);
+        assert_eq!(storage.get_key_record(key_pair.id).await.unwrap().expires_at, Some(expected));
+        assert_eq!(storage.normalize_stored_timestamps().await, 1);
+        assert_eq!(storage.normalize_stored_timestamps().await, 0);
+        let written: Vec<serde_json::Value> = serde_json::from_slice(&std::fs::read(&storage_path).unwrap()).unwrap();
+        assert_eq!(written[0]["expires_at"], "2030-06-01T09:30:00.123Z");
+    }
 }
@@ -0,0 +1,352 @@
+//! Moving keys between instances
+//!
+//! Every instance has an X25519 transport key pair, created on first start and kept at
+//! `TRANSPORT_KEY_PATH` (default `transport_key.json`). To move a key, the destination publishes
+//! its transport public key (`GET /admin/transport-key`), the source seals the key's private half
+//! to it (`POST /keys/:id/export`), and the destination opens the envelope and stores the key
+//! under its original id (`POST /keys/import-wrapped`).
+//!
+//! The seal is an ephemeral-static X25519 exchange whose shared secret is expanded with
+//! HKDF-SHA256, salted with both public keys, into an AES-256-GCM key. The envelope's metadata
+//! is the associated data, so it cannot be altered without the import failing.
+
+use crate::capabilities::HashAlgorithm;
+use crate::canonicalize::canonicalize_value;
+use crate::config::KdfParams;
+use crate::key_generation::generate_key_pair_from_seed;
+use crate::lifecycle::Lifecycle;
+use crate::models::{GenerateKeyRequest, KeyManagementError, KeyPair, KeyStrength, KeyType, SignatureEncoding, SignatureOutputFormat};
+use crate::utils::public_key_to_fingerprint;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use ed25519_dalek::SigningKey;
+use hkdf::Hkdf;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::path::Path;
+use uuid::Uuid;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+/// Schema identifier carried in every wrapped key envelope
+pub const WRAPPED_KEY_SCHEMA: &str = "inkan-wrapped-key";
+/// Current wrapped key envelope version
+pub const WRAPPED_KEY_VERSION: u32 = 1;
+/// HKDF info and associated data prefix binding the seal to this scheme
+pub const KEY_TRANSPORT_CONTEXT: &[u8] = b"inkan-key-transport-v1";
+
+fn encode(bytes: &[u8]) -> String {
+    base64::engine::general_purpose::STANDARD.encode(bytes)
+}
+
+fn decode_32(value: &str, what: &str) -> Result<[u8; 32], KeyManagementError> {
+    base64::engine::general_purpose::STANDARD.decode(value)
+        .ok()
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes.as_slice()).ok())
+        .ok_or_else(|| KeyManagementError::InvalidKeyFormat(format!("{} must be 32 base64 encoded bytes", what)))
+}
+
+/// Transport key pair as persisted
+#[derive(Serialize, Deserialize)]
+struct TransportKeyFile {
+    public_key: String,
+    private_key: String,
+    created_at: DateTime<Utc>,
+}
+
+/// This instance's X25519 key pair that keys are sealed to
+pub struct TransportKey {
+    secret: StaticSecret,
+    public: PublicKey,
+    created_at: DateTime<Utc>,
+}
+
+impl TransportKey {
+    /// Creates a fresh transport key pair
+    pub fn generate(now: DateTime<Utc>) -> Self {
+        let secret = StaticSecret::random_from_rng(OsRng);
+        Self { public: PublicKey::from(&secret), secret, created_at: now }
+    }
+
+    /// Loads the transport key pair at `path`, creating it on first use
+    ///
+    /// A new file is written with owner-only permissions, since it holds the private key.
+    pub fn load_or_create(path: &str, now: DateTime<Utc>) -> Result<Self, KeyManagementError> {
+        if Path::new(path).exists() {
+            let content = std::fs::read_to_string(path)
+                .map_err(|e| KeyManagementError::StorageError(format!("Failed to read transport key file: {}", e)))?;
+            let file: TransportKeyFile = serde_json::from_str(&content)
+                .map_err(|e| KeyManagementError::StorageError(format!("Failed to parse transport key file: {}", e)))?;
+            let secret = StaticSecret::from(decode_32(&file.private_key, "Transport private key")?);
+            let public = PublicKey::from(&secret);
+            if encode(public.as_bytes()) != file.public_key {
+                return Err(KeyManagementError::StorageError("Transport key file holds mismatched keys".to_string()));
+            }
+            return Ok(Self { secret, public, created_at: file.created_at });
+        }
+
+        let key = Self::generate(now);
+        let file = TransportKeyFile {
+            public_key: key.public_key(),
+            private_key: encode(key.secret.as_bytes()),
+            created_at: now,
+        };
+        let content = serde_json::to_string_pretty(&file)
+            .map_err(|e| KeyManagementError::StorageError(format!("Failed to serialize transport key: {}", e)))?;
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut handle = options.open(path)
+            .map_err(|e| KeyManagementError::StorageError(format!("Failed to create transport key file: {}", e)))?;
+        std::io::Write::write_all(&mut handle, content.as_bytes())
+            .map_err(|e| KeyManagementError::StorageError(format!("Failed to write transport key file: {}", e)))?;
+        Ok(key)
+    }
+
+    /// Base64 encoded public key, as given to exporting instances
+    pub fn public_key(&self) -> String {
+        encode(self.public.as_bytes())
+    }
+
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    /// Opens an envelope sealed to this key, returning the signing key it carries
+    ///
+    /// Fails if the envelope was sealed to another instance, was altered, or carries a key
+    /// whose public half differs from its metadata.
+    pub fn open(&self, envelope: &WrappedKey) -> Result<SigningKey, KeyManagementError> {
+        if envelope.schema != WRAPPED_KEY_SCHEMA || envelope.version != WRAPPED_KEY_VERSION {
+            return Err(KeyManagementError::ValidationFailed(format!(
+                "Unsupported wrapped key {} version {}", envelope.schema, envelope.version,
+            )));
+        }
+        if envelope.recipient != self.public_key() {
+            return Err(KeyManagementError::ValidationFailed(
+                "The key was wrapped for another instance's transport key".to_string(),
+            ));
+        }
+        let ephemeral = PublicKey::from(decode_32(&envelope.ephemeral_public_key, "ephemeral_public_key")?);
+        let nonce: [u8; 12] = base64::engine::general_purpose::STANDARD.decode(&envelope.nonce)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| KeyManagementError::InvalidKeyFormat("nonce must be 12 base64 encoded bytes".to_string()))?;
+        let ciphertext = base64::engine::general_purpose::STANDARD.decode(&envelope.ciphertext)
+            .map_err(|_| KeyManagementError::InvalidKeyFormat("ciphertext is not valid base64".to_string()))?;
+
+        let unreadable = || KeyManagementError::InvalidKeyFormat("The wrapped key could not be opened".to_string());
+        let shared = self.secret.diffie_hellman(&ephemeral);
+        if !shared.was_contributory() {
+            return Err(unreadable());
+        }
+        let cipher = transport_cipher(shared.as_bytes(), &ephemeral, &self.public);
+        let seed = cipher.decrypt(Nonce::from_slice(&nonce), Payload { msg: &ciphertext, aad: &associated_data(&envelope.metadata)? })
+            .map_err(|_| unreadable())?;
+        let seed: [u8; 32] = seed.as_slice().try_into().map_err(|_| unreadable())?;
+
+        let signing_key = SigningKey::from_bytes(&seed);
+        if encode(signing_key.verifying_key().as_bytes()) != envelope.metadata.public_key {
+            return Err(KeyManagementError::InvalidKeyFormat("The wrapped key does not match its public key".to_string()));
+        }
+        Ok(signing_key)
+    }
+}
+
+/// Everything about a wrapped key except its private half
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct WrappedKeyMetadata {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub public_key: String,
+    pub fingerprint: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub is_active: bool,
+    /// Set only for suspended keys, so that other wrapped keys keep the metadata older instances
+    /// accept
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suspended: Option<bool>,
+    pub revocation_scheduled_at: Option<DateTime<Utc>>,
+    pub tags: Vec<String>,
+    pub key_strength: KeyStrength,
+    pub allowed_contexts: Option<Vec<String>>,
+    /// Deployment environment at the source; absent from keys wrapped before environments, whose
+    /// authenticated metadata must serialize as it did
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub environment: Option<String>,
+    /// Signature defaults at the source, serialized only when set for the same reason
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_output_format: Option<SignatureOutputFormat>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_hash_algorithm: Option<HashAlgorithm>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_encoding: Option<SignatureEncoding>,
+    /// The key was password-protected at the source, so importing it requires a password
+    pub password_protected: bool,
+    pub exported_at: DateTime<Utc>,
+}
+
+/// A private key sealed to one instance's transport key, with its metadata in the clear
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct WrappedKey {
+    pub schema: String,
+    pub version: u32,
+    pub metadata: WrappedKeyMetadata,
+    /// Transport public key the envelope is sealed to
+    pub recipient: String,
+    pub ephemeral_public_key: String,
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+fn transport_cipher(shared: &[u8; 32], ephemeral: &PublicKey, recipient: &PublicKey) -> Aes256Gcm {
+    let salt = [ephemeral.as_bytes().as_slice(), recipient.as_bytes().as_slice()].concat();
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(&salt), shared)
+        .expand(KEY_TRANSPORT_CONTEXT, &mut key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key))
+}
+
+fn associated_data(metadata: &WrappedKeyMetadata) -> Result<Vec<u8>, KeyManagementError> {
+    let value = serde_json::to_value(metadata)
+        .map_err(|e| KeyManagementError::InternalError(format!("Failed to serialize wrapped key metadata: {}", e)))?;
+    let mut aad = KEY_TRANSPORT_CONTEXT.to_vec();
+    aad.push(0);
+    aad.extend_from_slice(canonicalize_value(&value)?.as_bytes());
+    Ok(aad)
+}
+
+/// Seals `signing_key`, the unlocked private half of `key_pair`, to the transport key `recipient`
+pub fn wrap_key(
+    key_pair: &KeyPair,
+    signing_key: &SigningKey,
+    recipient: &str,
+    now: DateTime<Utc>,
+) -> Result<WrappedKey, KeyManagementError> {
+    let recipient_key = PublicKey::from(decode_32(recipient, "transport_public_key")?);
+    let metadata = WrappedKeyMetadata {
+        id: key_pair.id,
+        name: key_pair.name.clone(),
+        description: key_pair.description.clone(),
+        public_key: key_pair.public_key.clone(),
+        fingerprint: public_key_to_fingerprint(&key_pair.public_key).map_err(KeyManagementError::InvalidKeyFormat)?,
+        created_at: key_pair.created_at,
+        expires_at: key_pair.expires_at,
+        is_active: key_pair.lifecycle == Lifecycle::Active,
+        suspended: (key_pair.lifecycle == Lifecycle::Suspended).then_some(true),
+        revocation_scheduled_at: key_pair.revocation_scheduled_at,
+        tags: key_pair.tags.clone(),
+        key_strength: key_pair.key_strength.clone(),
+        allowed_contexts: key_pair.allowed_contexts.clone(),
+        environment: Some(key_pair.environment.clone()),
+        default_output_format: key_pair.default_output_format,
+        default_hash_algorithm: key_pair.default_hash_algorithm,
+        default_encoding: key_pair.default_encoding,
+        password_protected: key_pair.key_type == KeyType::Ed25519Encrypted,
+        exported_at: now,
+    };
+
+    let ephemeral = StaticSecret::random_from_rng(OsRng);
+    let ephemeral_public = PublicKey::from(&ephemeral);
+    let shared = ephemeral.diffie_hellman(&recipient_key);
+    if !shared.was_contributory() {
+        return Err(KeyManagementError::InvalidKeyFormat("transport_public_key is not a usable X25519 key".to_string()));
+    }
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = transport_cipher(shared.as_bytes(), &ephemeral_public, &recipient_key)
+        .encrypt(&nonce, Payload { msg: signing_key.as_bytes(), aad: &associated_data(&metadata)? })
+        .map_err(|e| KeyManagementError::InternalError(format!("Key wrapping failed: {}", e)))?;
+
+    Ok(WrappedKey {
+        schema: WRAPPED_KEY_SCHEMA.to_string(),
+        version: WRAPPED_KEY_VERSION,
+        metadata,
+        recipient: recipient.to_string(),
+        ephemeral_public_key: encode(ephemeral_public.as_bytes()),
+        nonce: encode(&nonce),
+        ciphertext: encode(&ciphertext),
+    })
+}
+
+impl WrappedKeyMetadata {
+    /// Rebuilds the stored key from its metadata and unwrapped private half
+    ///
+    /// The private key is encrypted under `password` with `kdf` when one is given. Usage
+    /// counters start afresh on the new instance.
+    pub fn into_key_pair(self, signing_key: &SigningKey, password: Option<String>, kdf: &KdfParams) -> Result<KeyPair, KeyManagementError> {
+        let request = GenerateKeyRequest {
+            name: self.name,
+            description: self.description,
+            password,
+            expires_at: self.expires_at,
+            tags: Some(self.tags),
+            key_strength: Some(self.key_strength),
+            default_output_format: self.default_output_format,
+            default_hash_algorithm: self.default_hash_algorithm,
+            default_encoding: self.default_encoding,
+            ..Default::default()
+        };
+        let key_pair = generate_key_pair_from_seed(request, kdf, signing_key.as_bytes(), self.created_at)?;
+        Ok(KeyPair {
+            id: self.id,
+            created_at: self.created_at,
+            lifecycle: match (self.is_active, self.suspended) {
+                (_, Some(true)) => Lifecycle::Suspended,
+                (true, _) => Lifecycle::Active,
+                (false, _) => Lifecycle::Revoked,
+            },
+            revocation_scheduled_at: self.revocation_scheduled_at,
+            allowed_contexts: self.allowed_contexts,
+            environment: self.environment.unwrap_or_else(crate::environment::unknown_environment),
+            fingerprint: Some(self.fingerprint),
+            ..key_pair
+        })
+    }
+}
+
+/// Loads the transport key at `TRANSPORT_KEY_PATH` (default `transport_key.json`), creating it if absent
+pub fn load_default_transport_key() -> Result<TransportKey, KeyManagementError> {
+    let path = std::env::var("TRANSPORT_KEY_PATH").unwrap_or_else(|_| "transport_key.json".to_string());
+    TransportKey::load_or_create(&path, Utc::now())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_generation::generate_seeded_test_key_pair;
+    use crate::key_verification::load_signing_key;
+
+    #[test]
+    fn test_wrapped_key_opens_only_for_its_recipient_and_unaltered() {
+        let key_pair = generate_seeded_test_key_pair("Staging Root", 3);
+        let signing_key = load_signing_key(key_pair.private_key.expose_for_signing(), None, &KdfParams::default(), None).unwrap();
+        let destination = TransportKey::generate(Utc::now());
+        let envelope = wrap_key(&key_pair, &signing_key, &destination.public_key(), Utc::now()).unwrap();
+        assert_eq!(destination.open(&envelope).unwrap().to_bytes(), signing_key.to_bytes());
+
+        let mut renamed = envelope.clone();
+        renamed.metadata.name = "Production Root".to_string();
+        assert!(destination.open(&renamed).is_err());
+        let elsewhere = TransportKey::generate(Utc::now());
+        assert!(elsewhere.open(&WrappedKey { recipient: elsewhere.public_key(), ..envelope.clone() }).is_err());
+        assert!(elsewhere.open(&envelope).is_err());
+    }
+
+    #[test]
+    fn test_transport_key_persists_across_restarts() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("transport_key.json");
+        let created = TransportKey::load_or_create(path.to_str().unwrap(), Utc::now()).unwrap();
+        let reloaded = TransportKey::load_or_create(path.to_str().unwrap(), Utc::now()).unwrap();
+        assert_eq!(created.public_key(), reloaded.public_key());
+        #[cfg(unix)]
+        assert_eq!(std::os::unix::fs::PermissionsExt::mode(&std::fs::metadata(&path).unwrap().permissions()) & 0o777, 0o600);
+    }
+}
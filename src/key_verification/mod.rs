@@ -1,139 +1,473 @@
-use crate::models::{KeyManagementError, SignDocumentRequest, VerifySignatureRequest};
-use crate::key_generation::decrypt_private_key;
+use crate::config::KdfParams;
+use crate::deadline::Deadline;
+use crate::models::{DocumentContentType, DocumentHash, KeyManagementError, MessageEncoding, SignDocumentRequest, SignatureBytes, SignatureEncoding, VerifySignatureRequest};
+use crate::canonicalize::canonicalize_json;
+use crate::key_generation::{decrypt_private_key_timed, KdfTiming};
+use crate::signing_backend::KeySigner;
 use base64::Engine;
-use ed25519_dalek::{Signer, SigningKey, Verifier, VerifyingKey};
+use chrono::{DateTime, Utc};
+use ed25519_dalek::{SigningKey, Verifier, VerifyingKey};
 use sha2::{Digest, Sha256};
 use std::collections::HashMap;
+use uuid::Uuid;
 
-/// Signs a document hash with a private key
-pub fn sign_document(
-    request: &SignDocumentRequest,
+/// Domain tag for version 1 of the validity-window binding format
+pub const VALIDITY_BINDING_V1: &[u8] = b"inkan-validity-v1";
+
+/// Domain tag for version 1 of the signing-context binding format
+pub const CONTEXT_BINDING_V1: &[u8] = b"inkan-context-v1";
+
+/// Domain tag for version 1 of the signing-time binding format
+pub const TIMESTAMP_BINDING_V1: &[u8] = b"inkan-timestamp-v1";
+
+/// Domain tag for version 1 of derived signature ids
+pub const SIGNATURE_ID_V1: &[u8] = b"inkan-signature-id-v1";
+
+/// Longest signing context accepted, in bytes
+pub const MAX_CONTEXT_LENGTH: usize = 255;
+
+/// Longest public key or signature, in characters, verification will try to decode
+///
+/// Comfortably above the longest accepted form, an armored SSH signature.
+pub const MAX_VERIFY_ENCODED_LENGTH: usize = 8 * 1024;
+
+/// Longest document hash verification accepts, in characters
+pub const MAX_DOCUMENT_HASH_LENGTH: usize = 128;
+
+/// Largest document, in bytes, signed or verified as a `raw-message`
+pub const MAX_RAW_MESSAGE_BYTES: usize = 64 * 1024;
+
+/// Treats an empty signing context the same as none
+pub fn normalize_context(context: Option<&str>) -> Option<&str> {
+    context.filter(|context| !context.is_empty())
+}
+
+/// Checks a signing context is short enough to bind
+pub fn validate_context(context: Option<&str>) -> Result<(), KeyManagementError> {
+    match normalize_context(context) {
+        Some(context) if context.len() > MAX_CONTEXT_LENGTH => Err(KeyManagementError::ValidationFailed(
+            format!("context must be at most {} bytes", MAX_CONTEXT_LENGTH),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Checks the size and shape of caller-supplied verification input before any of it is decoded
+///
+/// Oversized public keys and signatures are refused with one message, so the check itself does
+/// not tell a caller which of the two it got wrong.
+pub fn validate_verify_input(request: &VerifySignatureRequest) -> Result<(), KeyManagementError> {
+    let oversized = std::iter::once(&request.public_key)
+        .chain(&request.public_keys)
+        .chain(std::iter::once(&request.signature))
+        .any(|encoded| encoded.len() > MAX_VERIFY_ENCODED_LENGTH);
+    if oversized {
+        return Err(KeyManagementError::ValidationFailed(format!(
+            "public keys and signatures must be at most {} characters", MAX_VERIFY_ENCODED_LENGTH,
+        )));
+    }
+    if let Some(hash) = &request.document_hash {
+        if hash.len() > MAX_DOCUMENT_HASH_LENGTH || hash.chars().any(char::is_control) {
+            return Err(KeyManagementError::ValidationFailed(format!(
+                "document_hash must be at most {} characters, without control characters", MAX_DOCUMENT_HASH_LENGTH,
+            )));
+        }
+    }
+    validate_context(request.context.as_deref())?;
+    check_message_encoding(
+        request.message_encoding,
+        request.document_content.as_deref().map(str::len),
+        request.valid_until,
+        request.context.as_deref(),
+        request.signing_time,
+    )
+}
+
+/// Checks that a signature under `encoding` can be made or checked with the given bindings
+///
+/// Only `hash-raw-bytes` binds a context, signing time or validity window. `raw-message` needs
+/// the document content, at most [`MAX_RAW_MESSAGE_BYTES`] of it.
+pub fn check_message_encoding(
+    encoding: MessageEncoding,
+    content_len: Option<usize>,
+    valid_until: Option<DateTime<Utc>>,
+    context: Option<&str>,
+    signing_time: Option<DateTime<Utc>>,
+) -> Result<(), KeyManagementError> {
+    if encoding == MessageEncoding::HashRawBytes {
+        return Ok(());
+    }
+    if valid_until.is_some() || normalize_context(context).is_some() || signing_time.is_some() {
+        return Err(KeyManagementError::ValidationFailed(format!(
+            "message_encoding {} does not support valid_until, context or a bound signing time", encoding.as_str(),
+        )));
+    }
+    match (encoding, content_len) {
+        (MessageEncoding::RawMessage, None) => Err(KeyManagementError::ValidationFailed(
+            "message_encoding raw-message requires document_content".to_string(),
+        )),
+        (MessageEncoding::RawMessage, Some(len)) if len > MAX_RAW_MESSAGE_BYTES => Err(KeyManagementError::ContentTooLarge {
+            field: "document_content",
+            limit: MAX_RAW_MESSAGE_BYTES as u64,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Builds the message that is actually signed for a document hash
+///
+/// Without a context, signing time, or validity window the message is the raw hash bytes,
+/// exactly as before. A non-empty context is bound first, so a signature made for one kind of
+/// document cannot be replayed as another:
+/// `SHA-256("inkan-context-v1" || 0x00 || context_len_u32_be || context_utf8 || hash_bytes)`.
+/// A bound signing time comes next, as
+/// `SHA-256("inkan-timestamp-v1" || 0x00 || hash_bytes || signing_time_unix_millis_i64_be)`.
+/// With a window, v1 then binds it in as
+/// `SHA-256("inkan-validity-v1" || 0x00 || hash_bytes || valid_until_unix_millis_i64_be)`.
+/// Each step's `hash_bytes` is the digest produced by the steps before it.
+pub fn build_signing_message(
+    hash_bytes: &[u8],
+    valid_until: Option<DateTime<Utc>>,
+    context: Option<&str>,
+    signing_time: Option<DateTime<Utc>>,
+) -> Vec<u8> {
+    let hash_bytes = match normalize_context(context) {
+        None => hash_bytes.to_vec(),
+        Some(context) => {
+            let mut hasher = Sha256::new();
+            hasher.update(CONTEXT_BINDING_V1);
+            hasher.update([0u8]);
+            hasher.update((context.len() as u32).to_be_bytes());
+            hasher.update(context.as_bytes());
+            hasher.update(hash_bytes);
+            hasher.finalize().to_vec()
+        }
+    };
+    let hash_bytes = match signing_time {
+        None => hash_bytes,
+        Some(signing_time) => {
+            let mut hasher = Sha256::new();
+            hasher.update(TIMESTAMP_BINDING_V1);
+            hasher.update([0u8]);
+            hasher.update(&hash_bytes);
+            hasher.update(signing_time.timestamp_millis().to_be_bytes());
+            hasher.finalize().to_vec()
+        }
+    };
+    match valid_until {
+        None => hash_bytes,
+        Some(valid_until) => {
+            let mut hasher = Sha256::new();
+            hasher.update(VALIDITY_BINDING_V1);
+            hasher.update([0u8]);
+            hasher.update(&hash_bytes);
+            hasher.update(valid_until.timestamp_millis().to_be_bytes());
+            hasher.finalize().to_vec()
+        }
+    }
+}
+
+/// Builds the message a raw signature covers for a document under `encoding`
+///
+/// `hash-raw-bytes` is [`build_signing_message`] over the digest. The other encodings are plain
+/// Ed25519 over the digest's hex form or over `content`, the document as it was hashed
+/// (canonical for json-jcs), so other tools make and check the same signatures; they are
+/// checked with [`check_message_encoding`] first.
+pub fn encode_signing_message(
+    encoding: MessageEncoding,
+    document_hash: &DocumentHash,
+    content: Option<&[u8]>,
+    valid_until: Option<DateTime<Utc>>,
+    context: Option<&str>,
+    signing_time: Option<DateTime<Utc>>,
+) -> Result<Vec<u8>, KeyManagementError> {
+    check_message_encoding(encoding, content.map(<[u8]>::len), valid_until, context, signing_time)?;
+    match (encoding, content) {
+        (MessageEncoding::HashRawBytes, _) => Ok(build_signing_message(document_hash.as_bytes(), valid_until, context, signing_time)),
+        (MessageEncoding::HashHexBytes, _) => Ok(document_hash.to_string().into_bytes()),
+        (MessageEncoding::RawMessage, content) => Ok(content.unwrap_or_default().to_vec()),
+    }
+}
+
+/// Bytes of document content as it is hashed and signed: the content itself, or its RFC 8785
+/// canonical form for json-jcs
+pub fn content_bytes(content: &str, content_type: DocumentContentType) -> Result<Vec<u8>, KeyManagementError> {
+    match content_type {
+        DocumentContentType::Text => Ok(content.as_bytes().to_vec()),
+        DocumentContentType::JsonJcs => Ok(canonicalize_json(content)?.into_bytes()),
+    }
+}
+
+/// Drops the sub-millisecond part of a signing time, which the binding does not carry
+///
+/// A bound signing time must be returned exactly as it was signed, or it cannot be verified.
+pub fn bindable_signing_time(time: DateTime<Utc>) -> DateTime<Utc> {
+    crate::timestamps::normalize(time)
+}
+
+/// Derives the id of a raw signature from everything that determines its bytes
+///
+/// Ed25519 signatures are deterministic, so one key signing one document hash under the same
+/// scheme (message encoding, validity window, context, and bound signing time) always produces
+/// the same signature, and the same id:
+/// the first 16 bytes of
+/// `SHA-256("inkan-signature-id-v1" || 0x00 || key_fingerprint || 0x00 || "ed25519" || 0x00 || signing_message)`
+/// as a version 8 UUID, where `signing_message` is what [`build_signing_message`] returns. Any
+/// encoding but `hash-raw-bytes` adds its name and a 0x00 before `signing_message`.
+pub fn derive_signature_id(
+    key_fingerprint: &str,
+    message_encoding: MessageEncoding,
+    document_hash: &DocumentHash,
+    valid_until: Option<DateTime<Utc>>,
+    context: Option<&str>,
+    signing_time: Option<DateTime<Utc>>,
+) -> Uuid {
+    let mut hasher = Sha256::new();
+    hasher.update(SIGNATURE_ID_V1);
+    hasher.update([0u8]);
+    hasher.update(key_fingerprint.as_bytes());
+    hasher.update([0u8]);
+    hasher.update(b"ed25519");
+    hasher.update([0u8]);
+    if !message_encoding.is_default() {
+        hasher.update(message_encoding.as_str());
+        hasher.update([0u8]);
+    }
+    hasher.update(build_signing_message(document_hash.as_bytes(), valid_until, context, signing_time));
+    let digest = hasher.finalize();
+
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    uuid::Builder::from_custom_bytes(bytes).into_uuid()
+}
+
+/// Checks whether a signature's validity window has passed at the given instant
+///
+/// The window is inclusive: a signature is still in its window at exactly `valid_until`.
+pub fn is_signature_window_expired(valid_until: Option<DateTime<Utc>>, now: DateTime<Utc>) -> bool {
+    valid_until.is_some_and(|until| now > until)
+}
+
+/// Loads a stored private key into a signing key, decrypting it with the password if needed
+pub fn load_signing_key(
     private_key_b64: &str,
     salt_b64: Option<&str>,
-) -> Result<String, KeyManagementError> {
+    kdf: &KdfParams,
+    password: Option<&str>,
+) -> Result<SigningKey, KeyManagementError> {
+    load_signing_key_timed(private_key_b64, salt_b64, kdf, password).map(|(signing_key, _)| signing_key)
+}
+
+/// Loads a signing key like [`load_signing_key`], also reporting the time spent decrypting it
+///
+/// The timing is `None` for unencrypted keys.
+pub fn load_signing_key_timed(
+    private_key_b64: &str,
+    salt_b64: Option<&str>,
+    kdf: &KdfParams,
+    password: Option<&str>,
+) -> Result<(SigningKey, Option<KdfTiming>), KeyManagementError> {
     // Decode the private key
     let private_key_bytes = base64::engine::general_purpose::STANDARD.decode(private_key_b64)
         .map_err(|_| KeyManagementError::InvalidKeyFormat("Invalid private key encoding".to_string()))?;
     
     // Check if the private key is encrypted (longer than 64 bytes due to nonce + encrypted data)
-    let signing_key = if private_key_bytes.len() > 64 {
+    let loaded = if private_key_bytes.len() > 64 {
         // Key is encrypted, need password to decrypt
-        if let Some(password) = &request.password {
-            let decrypted_bytes = decrypt_private_key(private_key_b64, password, salt_b64)?;
-            SigningKey::from_keypair_bytes(&decrypted_bytes.try_into().unwrap())
-                .map_err(|_| KeyManagementError::InvalidKeyFormat("Invalid decrypted private key format".to_string()))?
+        if let Some(password) = password {
+            let (decrypted_bytes, timing) = decrypt_private_key_timed(private_key_b64, password, salt_b64, kdf)?;
+            let decrypted_bytes: [u8; 64] = decrypted_bytes
+                .try_into()
+                .map_err(|_| KeyManagementError::InvalidKeyFormat("Invalid decrypted private key length".to_string()))?;
+            let signing_key = SigningKey::from_keypair_bytes(&decrypted_bytes)
+                .map_err(|_| KeyManagementError::InvalidKeyFormat("Invalid decrypted private key format".to_string()))?;
+            (signing_key, Some(timing))
         } else {
-            return Err(KeyManagementError::InvalidRequest(
+            return Err(KeyManagementError::PasswordRequired(
                 "Password required for encrypted private key".to_string()
             ));
         }
     } else {
         // Key is unencrypted (development mode)
-        SigningKey::from_keypair_bytes(&private_key_bytes.try_into().unwrap())
-            .map_err(|_| KeyManagementError::InvalidKeyFormat("Invalid private key format".to_string()))?
+        let private_key_bytes: [u8; 64] = private_key_bytes.try_into()
+            .map_err(|_| KeyManagementError::InvalidKeyFormat("Invalid private key length".to_string()))?;
+        let signing_key = SigningKey::from_keypair_bytes(&private_key_bytes)
+            .map_err(|_| KeyManagementError::InvalidKeyFormat("Invalid private key format".to_string()))?;
+        (signing_key, None)
     };
     
-    // Get the document hash to sign
-    let document_hash = if let Some(hash) = &request.document_hash {
-        if hash.len() == 64 {
-            // Already a SHA256 hash
-            hash.clone()
-        } else {
-            // Hash the document content
-            let mut hasher = Sha256::new();
-            hasher.update(hash.as_bytes());
-            hex::encode(hasher.finalize())
+    Ok(loaded)
+}
+
+/// Signs a document hash with a private key
+pub fn sign_document(
+    request: &SignDocumentRequest,
+    private_key_b64: &str,
+    salt_b64: Option<&str>,
+    kdf: &KdfParams,
+) -> Result<String, KeyManagementError> {
+    let signing_key = load_signing_key(private_key_b64, salt_b64, kdf, request.password.as_deref())?;
+    let (document_hash, content) = request_document(request.message_encoding, request.document_hash.as_deref(), request.document_content.as_deref(), request.content_type)?;
+    let message = encode_signing_message(request.message_encoding, &document_hash, content.as_deref(), request.valid_until, request.context.as_deref(), None)?;
+    signing_key.sign_message(&message).map(|signature| SignatureBytes::from(signature).to_string())
+}
+
+/// The hash, and for `raw-message` the content bytes, a request-level signature covers
+///
+/// Only a raw-message signature is made over the content itself; otherwise the hash field is
+/// read as [`request_document_hash`] reads it.
+fn request_document(
+    encoding: MessageEncoding,
+    document_hash: Option<&str>,
+    document_content: Option<&str>,
+    content_type: DocumentContentType,
+) -> Result<(DocumentHash, Option<Vec<u8>>), KeyManagementError> {
+    match (encoding, document_content) {
+        (MessageEncoding::RawMessage, Some(content)) => {
+            let content = content_bytes(content, content_type)?;
+            Ok((DocumentHash::digest(&content), Some(content)))
         }
-    } else {
-        return Err(KeyManagementError::InvalidRequest(
+        _ => Ok((request_document_hash(document_hash)?, None)),
+    }
+}
+
+/// Reads the hash field of a request that has no separate content field
+///
+/// A 64 character hash is taken as hex SHA-256; anything else is hashed as document content.
+fn request_document_hash(document_hash: Option<&str>) -> Result<DocumentHash, KeyManagementError> {
+    match document_hash {
+        Some(hash) if hash.len() == 64 => DocumentHash::from_hex(hash),
+        Some(content) => Ok(DocumentHash::digest(content.as_bytes())),
+        None => Err(KeyManagementError::InvalidRequest(
             "Document hash or content must be provided".to_string()
-        ));
-    };
-    
-    // Convert hash to bytes
-    let hash_bytes = hex::decode(&document_hash)
-        .map_err(|_| KeyManagementError::InvalidRequest("Invalid document hash format".to_string()))?;
-    
-    // Sign the hash
-    let signature = signing_key.sign(&hash_bytes);
-    
-    // Encode signature as base64
-    let signature_b64 = base64::engine::general_purpose::STANDARD.encode(signature.to_bytes());
-    
-    Ok(signature_b64)
+        )),
+    }
 }
 
-/// Verifies a document signature using a public key
-pub fn verify_signature(
-    request: &VerifySignatureRequest,
-) -> Result<bool, KeyManagementError> {
-    // Decode the public key
-    let public_key_bytes = base64::engine::general_purpose::STANDARD.decode(&request.public_key)
+/// Signs a document hash with an already loaded key
+pub fn sign_document_hash(
+    signer: &dyn KeySigner,
+    document_hash: &DocumentHash,
+    valid_until: Option<DateTime<Utc>>,
+    context: Option<&str>,
+    signing_time: Option<DateTime<Utc>>,
+) -> Result<SignatureBytes, KeyManagementError> {
+    // Sign the hash, binding the context, signing time, and validity window if they were requested
+    let message = build_signing_message(document_hash.as_bytes(), valid_until, context, signing_time);
+    signer.sign_message(&message).map(SignatureBytes::from)
+}
+
+/// Decodes a base64 encoded Ed25519 public key
+pub fn decode_public_key(public_key_b64: &str) -> Result<VerifyingKey, KeyManagementError> {
+    let public_key_bytes = base64::engine::general_purpose::STANDARD.decode(public_key_b64)
         .map_err(|_| KeyManagementError::InvalidKeyFormat("Invalid public key encoding".to_string()))?;
     
     // Create public key from bytes
     let public_key_array: [u8; 32] = public_key_bytes.try_into()
         .map_err(|_| KeyManagementError::InvalidKeyFormat("Invalid public key length".to_string()))?;
     
-    let public_key = VerifyingKey::from_bytes(&public_key_array)
-        .map_err(|_| KeyManagementError::InvalidKeyFormat("Invalid public key format".to_string()))?;
-    
-    // Decode the signature
-    let signature_bytes = base64::engine::general_purpose::STANDARD.decode(&request.signature)
-        .map_err(|_| KeyManagementError::InvalidKeyFormat("Invalid signature encoding".to_string()))?;
-    
-    // Create signature from bytes
-    let signature_array: [u8; 64] = signature_bytes.try_into()
-        .map_err(|_| KeyManagementError::InvalidKeyFormat("Invalid signature length".to_string()))?;
-    
-    let signature = ed25519_dalek::Signature::from_bytes(&signature_array);
-    
-    // Get the document hash to verify
-    let document_hash = if let Some(hash) = &request.document_hash {
-        if hash.len() == 64 {
-            // Already a SHA256 hash
-            hash.clone()
-        } else {
-            // Hash the document content
-            let mut hasher = Sha256::new();
-            hasher.update(hash.as_bytes());
-            hex::encode(hasher.finalize())
-        }
-    } else {
-        return Err(KeyManagementError::InvalidRequest(
-            "Document hash or content must be provided".to_string()
-        ));
+    VerifyingKey::from_bytes(&public_key_array)
+        .map_err(|_| KeyManagementError::InvalidKeyFormat("Invalid public key format".to_string()))
+}
+
+/// Encodes raw signature bytes in `encoding`
+pub fn encode_signature(bytes: &[u8], encoding: SignatureEncoding) -> String {
+    match encoding {
+        SignatureEncoding::Base64 => base64::engine::general_purpose::STANDARD.encode(bytes),
+        SignatureEncoding::Base64Url => base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes),
+        SignatureEncoding::Hex => hex::encode(bytes),
+    }
+}
+
+/// Decodes an Ed25519 signature in `encoding`, or in whichever encoding it is in when `None`
+///
+/// The encodings of a 64-byte signature cannot be confused: hex takes 128 characters, standard
+/// base64 88 ending in `==`, and unpadded base64url 86.
+pub fn decode_signature(signature: &str, encoding: Option<SignatureEncoding>) -> Result<[u8; 64], KeyManagementError> {
+    let decode = |encoding: SignatureEncoding| match encoding {
+        SignatureEncoding::Base64 => base64::engine::general_purpose::STANDARD.decode(signature).ok(),
+        SignatureEncoding::Base64Url => base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(signature).ok(),
+        SignatureEncoding::Hex => hex::decode(signature).ok(),
     };
-    
-    // Convert hash to bytes
-    let hash_bytes = hex::decode(&document_hash)
-        .map_err(|_| KeyManagementError::InvalidRequest("Invalid document hash format".to_string()))?;
-    
-    // Verify the signature
-    let is_valid = public_key.verify(&hash_bytes, &signature).is_ok();
-    
-    Ok(is_valid)
+    let bytes = match encoding {
+        Some(encoding) => decode(encoding),
+        None if signature.len() == 128 => decode(SignatureEncoding::Hex),
+        None => decode(SignatureEncoding::Base64).or_else(|| decode(SignatureEncoding::Base64Url)),
+    };
+    let bytes = bytes.ok_or_else(|| KeyManagementError::InvalidSignatureFormat(match encoding {
+        Some(encoding) => format!("Invalid signature encoding, expected {}", encoding.as_str()),
+        None => "Invalid signature encoding".to_string(),
+    }))?;
+    bytes.try_into()
+        .map_err(|_| KeyManagementError::InvalidSignatureFormat("Invalid signature length".to_string()))
+}
+
+/// Verifies a document signature using a public key
+pub fn verify_signature(
+    request: &VerifySignatureRequest,
+) -> Result<bool, KeyManagementError> {
+    // Parse every field once, in the order their errors have always been reported
+    let public_key = decode_public_key(&request.public_key)?;
+    let signature = SignatureBytes::decode(&request.signature, request.signature_encoding)?;
+    let (document_hash, content) = request_document(request.message_encoding, request.document_hash.as_deref(), request.document_content.as_deref(), request.content_type)?;
+
+    let message = encode_signing_message(
+        request.message_encoding,
+        &document_hash,
+        content.as_deref(),
+        request.valid_until,
+        request.context.as_deref(),
+        request.signing_time,
+    )?;
+    Ok(public_key.verify(&message, &signature.to_signature()).is_ok())
+}
+
+/// Verifies an already parsed signature over a document hash
+///
+/// The message is built exactly as [`sign_document_hash`] builds it.
+pub fn verify_document_hash(
+    public_key: &VerifyingKey,
+    signature: &SignatureBytes,
+    document_hash: &DocumentHash,
+    valid_until: Option<DateTime<Utc>>,
+    context: Option<&str>,
+    signing_time: Option<DateTime<Utc>>,
+) -> bool {
+    let message = build_signing_message(document_hash.as_bytes(), valid_until, context, signing_time);
+    public_key.verify(&message, &signature.to_signature()).is_ok()
 }
 
 /// Creates a document hash from content
 pub fn create_document_hash(content: &str) -> String {
-    let mut hasher = Sha256::new();
-    hasher.update(content.as_bytes());
-    hex::encode(hasher.finalize())
+    DocumentHash::digest(content.as_bytes()).to_string()
 }
 
-/// Validates a signature format without verifying
-pub fn validate_signature_format(signature: &str) -> Result<(), KeyManagementError> {
-    let signature_bytes = base64::engine::general_purpose::STANDARD.decode(signature)
-        .map_err(|_| KeyManagementError::InvalidKeyFormat("Invalid signature encoding".to_string()))?;
-    
-    if signature_bytes.len() != 64 {
-        return Err(KeyManagementError::InvalidKeyFormat(
-            "Signature must be 64 bytes".to_string()
-        ));
+/// Resolves the hash to sign or verify from a request's hash and content fields
+///
+/// Content takes precedence over an explicit hash. For `json-jcs` content the hash is
+/// computed over the RFC 8785 canonical form, so formatting differences don't matter.
+pub fn resolve_document_hash(
+    document_hash: Option<&str>,
+    document_content: Option<&str>,
+    content_type: DocumentContentType,
+) -> Result<String, KeyManagementError> {
+    match (document_content, content_type) {
+        (Some(content), DocumentContentType::Text) => Ok(create_document_hash(content)),
+        (Some(content), DocumentContentType::JsonJcs) => Ok(create_document_hash(&canonicalize_json(content)?)),
+        (None, DocumentContentType::JsonJcs) => Err(KeyManagementError::ValidationFailed(
+            "content_type json-jcs requires document_content".to_string()
+        )),
+        (None, DocumentContentType::Text) => document_hash
+            .map(str::to_string)
+            .ok_or_else(|| KeyManagementError::InvalidRequest(
+                "Document hash or content must be provided".to_string()
+            )),
     }
-    
-    Ok(())
+}
+
+/// Validates a signature format without verifying, detecting the encoding when `encoding` is `None`
+pub fn validate_signature_format(signature: &str, encoding: Option<SignatureEncoding>) -> Result<(), KeyManagementError> {
+    decode_signature(signature, encoding).map(|_| ())
 }
 
 /// Validates a public key format without using it
@@ -158,12 +492,18 @@ pub fn validate_public_key_format(public_key: &str) -> Result<(), KeyManagementE
 }
 
 /// Batch verifies multiple signatures
+///
+/// Stops with [`KeyManagementError::DeadlineExceeded`], reporting how many were verified, once
+/// `deadline` passes.
 pub fn batch_verify_signatures(
     verifications: Vec<VerifySignatureRequest>,
+    deadline: &Deadline,
 ) -> Result<HashMap<usize, bool>, KeyManagementError> {
     let mut results = HashMap::new();
+    let total = verifications.len();
     
     for (index, verification) in verifications.into_iter().enumerate() {
+        deadline.check(index, total)?;
         let is_valid = verify_signature(&verification)?;
         results.insert(index, is_valid);
     }
@@ -176,6 +516,7 @@ pub fn sign_document_content(
     request: &SignDocumentRequest,
     private_key_b64: &str,
     salt_b64: Option<&str>,
+    kdf: &KdfParams,
     document_content: &str,
 ) -> Result<String, KeyManagementError> {
     // Create hash from content
@@ -186,31 +527,34 @@ pub fn sign_document_content(
         document_hash: Some(document_hash),
         key_id: request.key_id,
         password: request.password.clone(),
-        document_content: None,
+        document_content: (request.message_encoding == MessageEncoding::RawMessage).then(|| document_content.to_string()),
+        valid_until: request.valid_until,
+        content_type: request.content_type,
+        output_format: request.output_format,
+        hash_algorithm: request.hash_algorithm,
+        namespace: request.namespace.clone(),
+        bundle: request.bundle,
+        context: request.context.clone(),
+        bind_timestamp: request.bind_timestamp,
+        encoding: request.encoding,
+        message_encoding: request.message_encoding,
     };
     
     // Sign the document
-    sign_document(&modified_request, private_key_b64, salt_b64)
+    sign_document(&modified_request, private_key_b64, salt_b64, kdf)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::key_generation::generate_test_key_pair;
+    use crate::clock::{Clock, MockClock};
+    use crate::key_generation::{generate_key_pair, generate_test_key_pair};
     use crate::models::{GenerateKeyRequest, SignDocumentRequest, VerifySignatureRequest};
+    use chrono::Duration;
     
     #[test]
     fn test_sign_and_verify_document() {
         // Generate a key pair
-        let request = GenerateKeyRequest {
-            name: "Test Key".to_string(),
-            description: None,
-            password: None,
-            expires_at: None,
-            tags: None,
-            key_strength: None,
-        };
-        
         let key_pair = generate_test_key_pair("Test Key").unwrap();
         
         // Create a test document
@@ -220,19 +564,24 @@ mod tests {
         // Sign the document
         let sign_request = SignDocumentRequest {
             key_id: key_pair.id,
-            document_hash: document_hash.clone(),
+            document_hash: Some(document_hash.clone()),
             password: None,
             document_content: None,
+            valid_until: None,
+            ..Default::default()
         };
         
-        let signature = sign_document(&sign_request, &key_pair.private_key).unwrap();
+        let (private_key, salt) = key_pair.signing_secrets();
+        let signature = sign_document(&sign_request, private_key, salt, &key_pair.kdf.unwrap_or_default()).unwrap();
         
         // Verify the signature
         let verify_request = VerifySignatureRequest {
             public_key: key_pair.public_key,
-            document_hash,
+            document_hash: Some(document_hash),
             signature,
             document_content: None,
+            valid_until: None,
+            ..Default::default()
         };
         
         let is_valid = verify_signature(&verify_request).unwrap();
@@ -249,9 +598,18 @@ mod tests {
             expires_at: None,
             tags: None,
             key_strength: None,
+            hsm: None,
+            generate_password: false,
+            template: None,
+            environment: None,
+            fast: false,
+            exportable: None,
+            default_output_format: None,
+            default_hash_algorithm: None,
+            default_encoding: None,
         };
         
-        let key_pair = generate_test_key_pair("Encrypted Test Key").unwrap();
+        let key_pair = generate_key_pair(request).unwrap();
         
         // Create a test document
         let document_content = "Hello, Encrypted World!";
@@ -260,19 +618,24 @@ mod tests {
         // Sign the document with password
         let sign_request = SignDocumentRequest {
             key_id: key_pair.id,
-            document_hash: document_hash.clone(),
+            document_hash: Some(document_hash.clone()),
             password: Some("test_password_123".to_string()),
             document_content: None,
+            valid_until: None,
+            ..Default::default()
         };
         
-        let signature = sign_document(&sign_request, &key_pair.private_key).unwrap();
+        let (private_key, salt) = key_pair.signing_secrets();
+        let signature = sign_document(&sign_request, private_key, salt, &key_pair.kdf.unwrap_or_default()).unwrap();
         
         // Verify the signature
         let verify_request = VerifySignatureRequest {
             public_key: key_pair.public_key,
-            document_hash,
+            document_hash: Some(document_hash),
             signature,
             document_content: None,
+            valid_until: None,
+            ..Default::default()
         };
         
         let is_valid = verify_signature(&verify_request).unwrap();
@@ -294,9 +657,11 @@ mod tests {
         // Verify the fake signature
         let verify_request = VerifySignatureRequest {
             public_key: key_pair.public_key,
-            document_hash,
+            document_hash: Some(document_hash),
             signature: fake_signature,
             document_content: None,
+            valid_until: None,
+            ..Default::default()
         };
         
         let is_valid = verify_signature(&verify_request).unwrap();
@@ -315,12 +680,30 @@ mod tests {
     #[test]
     fn test_validate_signature_format() {
         let valid_signature = base64::engine::general_purpose::STANDARD.encode(vec![0u8; 64]);
-        assert!(validate_signature_format(&valid_signature).is_ok());
+        assert!(validate_signature_format(&valid_signature, None).is_ok());
         
         let invalid_signature = "invalid";
-        assert!(validate_signature_format(invalid_signature).is_err());
+        assert!(validate_signature_format(invalid_signature, None).is_err());
     }
     
+    #[test]
+    fn test_signature_encodings_round_trip() {
+        let bytes: Vec<u8> = (0..64).map(|i| i * 4 + 2).collect();
+        for encoding in [SignatureEncoding::Base64, SignatureEncoding::Base64Url, SignatureEncoding::Hex] {
+            let encoded = encode_signature(&bytes, encoding);
+            assert_eq!(decode_signature(&encoded, Some(encoding)).unwrap().to_vec(), bytes);
+            assert_eq!(decode_signature(&encoded, None).unwrap().to_vec(), bytes);
+            assert!(validate_signature_format(&encoded, None).is_ok());
+        }
+        assert!(encode_signature(&bytes, SignatureEncoding::Base64Url).contains(['-', '_']));
+
+        // An explicit encoding is not second-guessed
+        let url_safe = encode_signature(&bytes, SignatureEncoding::Base64Url);
+        assert!(decode_signature(&url_safe, Some(SignatureEncoding::Hex)).is_err());
+        assert!(decode_signature(&url_safe, Some(SignatureEncoding::Base64)).is_err());
+        assert!(decode_signature(&hex::encode(&bytes[..63]), None).is_err());
+    }
+
     #[test]
     fn test_validate_public_key_format() {
         let valid_public_key = base64::engine::general_purpose::STANDARD.encode(vec![0u8; 32]);
@@ -337,23 +720,212 @@ mod tests {
         
         let sign_request = SignDocumentRequest {
             key_id: key_pair.id,
-            document_hash: "".to_string(), // Will be ignored
+            document_hash: None, // Will be ignored
             password: None,
             document_content: None,
+            valid_until: None,
+            ..Default::default()
         };
         
-        let signature = sign_document_content(&sign_request, &key_pair.private_key, document_content).unwrap();
+        let (private_key, salt) = key_pair.signing_secrets();
+        let signature = sign_document_content(&sign_request, private_key, salt, &key_pair.kdf.unwrap_or_default(), document_content).unwrap();
         
         // Verify the signature
         let document_hash = create_document_hash(document_content);
         let verify_request = VerifySignatureRequest {
             public_key: key_pair.public_key,
-            document_hash,
+            document_hash: Some(document_hash),
             signature,
             document_content: None,
+            valid_until: None,
+            ..Default::default()
         };
         
         let is_valid = verify_signature(&verify_request).unwrap();
         assert!(is_valid);
     }
+    
+    #[test]
+    fn test_validity_window_round_trip_and_tampering() {
+        let key_pair = generate_test_key_pair("Window Key").unwrap();
+        let document_hash = create_document_hash("release manifest");
+        let valid_until = Utc::now() + Duration::days(7);
+        
+        let sign_request = SignDocumentRequest {
+            key_id: key_pair.id,
+            document_hash: Some(document_hash.clone()),
+            password: None,
+            document_content: None,
+            valid_until: Some(valid_until),
+            ..Default::default()
+        };
+        let signature = sign_document(&sign_request, key_pair.private_key.expose_for_signing(), None, &KdfParams::default()).unwrap();
+        
+        let mut verify_request = VerifySignatureRequest {
+            public_key: key_pair.public_key.clone(),
+            document_hash: Some(document_hash),
+            signature,
+            document_content: None,
+            valid_until: Some(valid_until),
+            ..Default::default()
+        };
+        assert!(verify_signature(&verify_request).unwrap());
+        
+        // Extending the window must break the signature
+        verify_request.valid_until = Some(valid_until + Duration::milliseconds(1));
+        assert!(!verify_signature(&verify_request).unwrap());
+        
+        // Dropping the window must break it too
+        verify_request.valid_until = None;
+        assert!(!verify_signature(&verify_request).unwrap());
+    }
+    
+    #[test]
+    fn test_plain_signature_message_unchanged() {
+        let hash_bytes = [7u8; 32];
+        assert_eq!(build_signing_message(&hash_bytes, None, None, None), hash_bytes.to_vec());
+        assert_eq!(build_signing_message(&hash_bytes, None, Some(""), None), hash_bytes.to_vec());
+        assert_ne!(build_signing_message(&hash_bytes, Some(Utc::now()), None, None), hash_bytes.to_vec());
+        assert_ne!(build_signing_message(&hash_bytes, None, Some("invoice"), None), hash_bytes.to_vec());
+    }
+    
+    #[test]
+    fn test_validity_window_boundary_instant() {
+        let valid_until = Utc::now() + Duration::hours(1);
+        let clock = MockClock::new(valid_until - Duration::milliseconds(1));
+        assert!(!is_signature_window_expired(Some(valid_until), clock.now()));
+        
+        clock.set(valid_until);
+        assert!(!is_signature_window_expired(Some(valid_until), clock.now()));
+        
+        clock.advance(Duration::milliseconds(1));
+        assert!(is_signature_window_expired(Some(valid_until), clock.now()));
+        
+        // No window never expires
+        assert!(!is_signature_window_expired(None, clock.now()));
+    }
+
+    /// Verification as it was before hashes and signatures were parsed into typed values: every
+    /// call decodes the key, the signature and the hex hash into fresh vectors
+    fn verify_from_strings(request: &VerifySignatureRequest) -> Result<bool, KeyManagementError> {
+        let public_key = decode_public_key(&request.public_key)?;
+        let signature = base64::engine::general_purpose::STANDARD.decode(&request.signature)
+            .map_err(|_| KeyManagementError::InvalidSignatureFormat("Invalid signature encoding".to_string()))?;
+        let signature: [u8; 64] = signature.try_into()
+            .map_err(|_| KeyManagementError::InvalidSignatureFormat("Invalid signature length".to_string()))?;
+        let document_hash = request.document_hash.clone().unwrap_or_default();
+        let hash_bytes = hex::decode(&document_hash)
+            .map_err(|_| KeyManagementError::InvalidRequest("Invalid document hash format".to_string()))?;
+        let message = build_signing_message(&hash_bytes, request.valid_until, request.context.as_deref(), request.signing_time);
+        Ok(public_key.verify(&message, &ed25519_dalek::Signature::from_bytes(&signature)).is_ok())
+    }
+
+    /// Benchmark; run with `cargo test --release -- --ignored --nocapture typed_verification`
+    #[test]
+    #[ignore = "benchmark"]
+    fn test_typed_verification_matches_string_verification_on_a_batch() {
+        const BATCH: usize = 10_000;
+        let signing_key = SigningKey::from_bytes(&[5u8; 32]);
+        let public_key = base64::engine::general_purpose::STANDARD.encode(signing_key.verifying_key().to_bytes());
+        let requests: Vec<VerifySignatureRequest> = (0..BATCH)
+            .map(|index| {
+                let document_hash = DocumentHash::digest(format!("artifact {}", index).as_bytes());
+                let context = (index % 3 == 0).then(|| "ci-artifact".to_string());
+                let signature = sign_document_hash(&signing_key, &document_hash, None, context.as_deref(), None).unwrap();
+                // Every tenth signature is checked against the wrong document
+                let checked = if index % 10 == 0 { DocumentHash::digest(b"other") } else { document_hash };
+                VerifySignatureRequest {
+                    public_key: public_key.clone(),
+                    document_hash: Some(checked.to_string()),
+                    signature: signature.to_string(),
+                    context,
+                    ..Default::default()
+                }
+            })
+            .collect();
+
+        let started = std::time::Instant::now();
+        let from_strings: Vec<bool> = requests.iter().map(|request| verify_from_strings(request).unwrap()).collect();
+        let strings_elapsed = started.elapsed();
+
+        // Parsed once at the boundary, then verified from bytes
+        let started = std::time::Instant::now();
+        let verifying_key = decode_public_key(&public_key).unwrap();
+        let typed: Vec<bool> = requests.iter()
+            .map(|request| {
+                let signature = SignatureBytes::decode(&request.signature, Some(SignatureEncoding::Base64)).unwrap();
+                let document_hash = DocumentHash::from_hex(request.document_hash.as_deref().unwrap()).unwrap();
+                verify_document_hash(&verifying_key, &signature, &document_hash, None, request.context.as_deref(), None)
+            })
+            .collect();
+        let typed_elapsed = started.elapsed();
+
+        assert_eq!(typed, from_strings);
+        assert_eq!(typed.iter().filter(|valid| !**valid).count(), BATCH / 10);
+        eprintln!("{} verifications: strings {:?}, typed {:?}", BATCH, strings_elapsed, typed_elapsed);
+    }
+
+    #[test]
+    fn test_message_encodings_match_openssl_and_libsodium_fixtures() {
+        // Made with `openssl pkeyutl -sign -rawin` over the digest bytes, the digest's hex form
+        // and the document, from the PKCS#8 key of seed 0x01..=0x20; libsodium's
+        // `crypto_sign_detached` over the same messages gives the same bytes
+        const PUBLIC_KEY: &str = "ebVWLo/mVPlAeLES6KmLp5AfhTrmlb7X4OORC60ElmQ=";
+        const DOCUMENT: &str = "Partner invoice 2024-117: EUR 1,250.00\n";
+        let fixtures = [
+            (MessageEncoding::HashRawBytes, "IKhXf7iEkEO6ItE5H/fOssbWjJibbgYS4AeX3/CaXv5lTozADEdKouK7bH18S1GceDzm+wQgpFtn+uRmXvVBCg=="),
+            (MessageEncoding::HashHexBytes, "t4/qzXQ+3XpfTejiJg0ccs0tkFTuBOYdU22zkocY+suFvPYIipW5ntz2jFRxDpn38P43GedvO0EdMK+rJOxnDg=="),
+            (MessageEncoding::RawMessage, "Oir1NRJ577ekm7t3gwqaO4U7Sod2696a6W9VCFNjtAiw5+VXb5/UuOD0DEs+6hjTRA2pHLHr4piLm0rLk6h9Bw=="),
+        ];
+        let signing_key = SigningKey::from_bytes(&std::array::from_fn(|i| i as u8 + 1));
+        let document_hash = DocumentHash::digest(DOCUMENT.as_bytes());
+        assert_eq!(document_hash.to_string(), "004b6bd54cd878fe9f535cbba449820364c363680de2403a65f470786c920c8b");
+
+        for (encoding, expected) in fixtures {
+            // This service makes the same signature the other tools made
+            let message = encode_signing_message(encoding, &document_hash, Some(DOCUMENT.as_bytes()), None, None, None).unwrap();
+            assert_eq!(SignatureBytes::from(signing_key.sign_message(&message).unwrap()).to_string(), expected, "{}", encoding.as_str());
+
+            // Each verifies under its own encoding only; no other encoding is tried
+            for (other, _) in fixtures {
+                let request = VerifySignatureRequest {
+                    public_key: PUBLIC_KEY.to_string(),
+                    document_hash: Some(document_hash.to_string()),
+                    document_content: Some(DOCUMENT.to_string()),
+                    signature: expected.to_string(),
+                    message_encoding: other,
+                    ..Default::default()
+                };
+                assert_eq!(verify_signature(&request).unwrap(), other == encoding, "{} as {}", encoding.as_str(), other.as_str());
+            }
+        }
+
+        // Only the default encoding binds anything beyond the document
+        let bound = VerifySignatureRequest {
+            public_key: PUBLIC_KEY.to_string(),
+            document_content: Some(DOCUMENT.to_string()),
+            signature: fixtures[2].1.to_string(),
+            message_encoding: MessageEncoding::RawMessage,
+            context: Some("invoice".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(validate_verify_input(&bound), Err(KeyManagementError::ValidationFailed(_))));
+        let oversized = VerifySignatureRequest { context: None, document_content: Some("x".repeat(MAX_RAW_MESSAGE_BYTES + 1)), ..bound };
+        assert!(matches!(validate_verify_input(&oversized), Err(KeyManagementError::ContentTooLarge { .. })));
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn test_verify_rejects_arbitrary_input_without_panicking(public_key in ".{0,96}", signature in "[A-Za-z0-9+/=]{0,100}", document_hash in ".{0,80}") {
+            let request = VerifySignatureRequest {
+                public_key,
+                document_hash: Some(document_hash),
+                signature,
+                ..Default::default()
+            };
+            if validate_verify_input(&request).is_ok() {
+                let _ = verify_signature(&request);
+            }
+        }
+    }
 }
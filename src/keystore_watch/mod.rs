@@ -0,0 +1,135 @@
+//! Keystore file watching
+//!
+//! Operators sometimes restore or hand-edit the keystore file while the service runs. With
+//! `INKAN_WATCH_KEYSTORE` set (and the `watch` feature built in), the keystore's directory is
+//! watched and every event touching the keystore file triggers
+//! [`KeyStorage::reload_if_changed`], which ignores the service's own writes and keeps unsaved
+//! in-memory changes over conflicting external ones. Each reload that finds the file changed,
+//! including one that keeps the in-memory keys or fails, is numbered in the event log.
+
+use crate::key_storage::KeyStorage;
+use crate::models::KeyManagementError;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+
+/// Pause after a file event before reloading, so a burst of events from one save is read once
+pub const WATCH_DEBOUNCE: std::time::Duration = std::time::Duration::from_millis(250);
+
+/// Starts watching the keystore file, reloading it whenever it is changed externally
+///
+/// Fails if the watcher cannot be set up or the crate was built without the `watch` feature.
+#[cfg(feature = "watch")]
+pub fn spawn_keystore_watcher(storage: Arc<KeyStorage>) -> Result<JoinHandle<()>, KeyManagementError> {
+    use notify::{RecursiveMode, Watcher};
+    use std::path::Path;
+
+    let path = Path::new(storage.storage_path()).to_path_buf();
+    let file_name = path.file_name().map(|name| name.to_os_string())
+        .ok_or_else(|| KeyManagementError::ValidationFailed(format!("Cannot watch keystore path {}", path.display())))?;
+    // Watch the directory: keystore writes replace the file by renaming over it
+    let directory = path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new(".")).to_path_buf();
+
+    let (events, mut received) = tokio::sync::mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+        Ok(event) => {
+            if event.paths.iter().any(|changed| changed.file_name() == Some(file_name.as_os_str())) {
+                let _ = events.send(());
+            }
+        }
+        Err(e) => tracing::warn!("Keystore watcher error: {}", e),
+    }).map_err(|e| KeyManagementError::StorageError(format!("Failed to start keystore watcher: {}", e)))?;
+    watcher.watch(&directory, RecursiveMode::NonRecursive)
+        .map_err(|e| KeyManagementError::StorageError(format!("Failed to watch {}: {}", directory.display(), e)))?;
+
+    Ok(tokio::spawn(async move {
+        // Dropping the watcher stops it, so it lives as long as the task
+        let _watcher = watcher;
+        while received.recv().await.is_some() {
+            tokio::time::sleep(WATCH_DEBOUNCE).await;
+            while received.try_recv().is_ok() {}
+            reload_and_record(&storage).await;
+        }
+    }))
+}
+
+/// Reloads the keystore if its file changed, numbering the outcome in the event log
+#[cfg(feature = "watch")]
+async fn reload_and_record(storage: &KeyStorage) {
+    use crate::event_log::OperationEvent;
+    use crate::key_storage::KeystoreReload;
+
+    let operation = match storage.reload_if_changed().await {
+        Ok(KeystoreReload::Unchanged) => return,
+        Ok(KeystoreReload::Reloaded(_)) => "keystore_reloaded",
+        Ok(KeystoreReload::Conflict(_)) => "keystore_reload_conflict",
+        Err(e) => {
+            tracing::warn!("Failed to reload externally modified keystore, keeping the loaded keys: {}", e);
+            "keystore_reload_failed"
+        }
+    };
+    if let Err(e) = storage.events().record(OperationEvent::reload(operation, storage.clock().now())).await {
+        tracing::warn!("Keystore reload outcome {} not numbered: {}", operation, e);
+    }
+}
+
+/// Starts watching the keystore file, reloading it whenever it is changed externally
+///
+/// Fails if the watcher cannot be set up or the crate was built without the `watch` feature.
+#[cfg(not(feature = "watch"))]
+pub fn spawn_keystore_watcher(storage: Arc<KeyStorage>) -> Result<JoinHandle<()>, KeyManagementError> {
+    Err(KeyManagementError::ValidationFailed(format!(
+        "INKAN_WATCH_KEYSTORE is set for {} but the service was built without the watch feature",
+        storage.storage_path(),
+    )))
+}
+
+#[cfg(all(test, feature = "watch"))]
+mod tests {
+    use super::*;
+    use crate::key_generation::generate_test_key_pair;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_watcher_picks_up_external_edits() {
+        let dir = tempdir().unwrap();
+        let storage_path = dir.path().join("keys.json");
+        let storage = Arc::new(KeyStorage::new(storage_path.to_str().unwrap()));
+        storage.store_key(generate_test_key_pair("Original Key").unwrap()).await.unwrap();
+        let _watcher = spawn_keystore_watcher(storage.clone()).unwrap();
+
+        let restored = generate_test_key_pair("Restored Key").unwrap();
+        tokio::fs::write(&storage_path, crate::key_storage::serialize_keys([&restored].into_iter()).unwrap()).await.unwrap();
+
+        let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(5);
+        while !storage.key_exists(restored.id).await {
+            assert!(tokio::time::Instant::now() < deadline, "external edit was not picked up");
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+        assert_eq!(storage.key_count().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_reloads_are_numbered_whether_or_not_they_succeed() {
+        use crate::event_log::EventKind;
+
+        let dir = tempdir().unwrap();
+        let storage_path = dir.path().join("keys.json");
+        let storage = KeyStorage::new(storage_path.to_str().unwrap());
+        storage.store_key(generate_test_key_pair("Original Key").unwrap()).await.unwrap();
+
+        // The service's own write is not a reload
+        reload_and_record(&storage).await;
+        assert_eq!(storage.events().high_water().await, 0);
+
+        let restored = generate_test_key_pair("Restored Key").unwrap();
+        tokio::fs::write(&storage_path, crate::key_storage::serialize_keys([&restored].into_iter()).unwrap()).await.unwrap();
+        reload_and_record(&storage).await;
+        tokio::fs::write(&storage_path, b"{ not a keystore").await.unwrap();
+        reload_and_record(&storage).await;
+
+        let replay = storage.events().replay(0, 10).await.unwrap();
+        let outcomes: Vec<_> = replay.events.iter().map(|event| (event.kind, event.operation.as_str())).collect();
+        assert_eq!(outcomes, [(EventKind::Reload, "keystore_reloaded"), (EventKind::Reload, "keystore_reload_failed")]);
+        assert!(storage.key_exists(restored.id).await);
+    }
+}
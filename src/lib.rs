@@ -0,0 +1,78 @@
+pub mod api;
+pub mod api_version;
+pub mod archive_verification;
+pub mod build_info;
+pub mod bundle;
+pub mod canonicalize;
+pub mod capabilities;
+pub mod capacity;
+pub mod certification;
+#[cfg(feature = "client")]
+pub mod client;
+pub mod clock;
+pub mod config;
+pub mod deadline;
+pub mod delegation;
+pub mod dsse;
+pub mod entropy;
+pub mod environment;
+pub mod event_log;
+pub mod export;
+#[cfg(any(test, feature = "failpoints"))]
+pub mod failpoints;
+pub mod federation;
+pub mod field_case;
+pub mod file_manifest;
+#[cfg(any(test, feature = "fuzzing"))]
+pub mod fuzz;
+pub mod i18n;
+pub mod integrity;
+pub mod kdf_stats;
+pub mod key_comparison;
+pub mod key_disclosure;
+pub mod key_formats;
+pub mod key_generation;
+#[cfg(not(feature = "verifier-only"))]
+pub mod key_pool;
+pub mod key_status;
+pub mod key_storage;
+#[cfg(not(feature = "verifier-only"))]
+pub mod key_transport;
+pub mod keystore_watch;
+pub mod key_verification;
+pub mod lifecycle;
+pub mod limits;
+pub mod metrics;
+#[cfg(not(feature = "verifier-only"))]
+pub mod migration;
+pub mod minisign;
+pub mod models;
+pub mod notifications;
+pub mod overview;
+pub mod pinset;
+#[cfg(feature = "pkcs11")]
+pub mod pkcs11;
+pub mod profile;
+pub mod rate_limit;
+pub mod receipts;
+pub mod reencryption;
+pub mod request_auth;
+pub mod routes;
+pub mod secret;
+pub mod self_test;
+pub mod shares;
+pub mod sign_policy;
+pub mod signing_backend;
+pub mod slo;
+pub mod sshsig;
+pub mod storage_breaker;
+pub mod storage_lock;
+pub mod sweeper;
+pub mod templates;
+pub mod text_normalization;
+pub mod timestamps;
+pub mod trust_manifest;
+pub mod usage_report;
+pub mod usage_shadow;
+pub mod utils;
+pub mod verification_cache;
@@ -0,0 +1,254 @@
+//! Key lifecycle
+//!
+//! A stored key is active, suspended, revoked or deleted; [`Lifecycle`] records which. Expiry
+//! and pending scheduled revocations are not stored states but are derived from the key's
+//! dates, giving the [`KeyState`] a key is in at a given moment.
+//!
+//! Every move between states goes through [`KeyPair::transition`], which refuses moves the
+//! lifecycle does not allow and appends each accepted one to the key's history:
+//!
+//! ```text
+//! Active ⇄ Suspended
+//!   │          │
+//!   └─► Revoked ◄┘      (expired keys may be revoked too)
+//!          │
+//!  any ──► Deleted ──► restored to the state it was deleted in
+//! ```
+//!
+//! Suspension disables a key temporarily: it cannot sign until resumed, but unlike revocation
+//! it can be undone.
+
+use crate::models::{KeyManagementError, KeyPair, KeyState};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Stored lifecycle state of a key
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Lifecycle {
+    #[default]
+    Active,
+    Suspended,
+    Revoked,
+    Deleted,
+}
+
+impl Lifecycle {
+    /// The stored state a key must hold to be in `state`, if `state` can be moved to
+    pub fn for_target(state: KeyState) -> Option<Self> {
+        match state {
+            KeyState::Active => Some(Lifecycle::Active),
+            KeyState::Suspended => Some(Lifecycle::Suspended),
+            KeyState::Revoked => Some(Lifecycle::Revoked),
+            KeyState::Deleted => Some(Lifecycle::Deleted),
+            KeyState::Expired | KeyState::ScheduledRevocation => None,
+        }
+    }
+}
+
+/// One accepted move of a key between lifecycle states
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LifecycleEvent {
+    pub at: DateTime<Utc>,
+    pub from: KeyState,
+    pub to: KeyState,
+    /// Authenticated client that made the move, or the background task that did
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actor: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// Whether the lifecycle allows moving a key from `from` to `to`
+///
+/// Expired and scheduled-revocation states follow from a key's dates and cannot be moved to. A
+/// deleted key may be restored to any stored state here; [`KeyPair::transition`] further
+/// requires it to be the one the key was deleted in.
+pub fn is_legal(from: KeyState, to: KeyState) -> bool {
+    use KeyState::*;
+    matches!(
+        (from, to),
+        (Active | ScheduledRevocation, Suspended)
+            | (Suspended, Active)
+            | (Active | ScheduledRevocation | Expired | Suspended, Revoked)
+            | (Active | ScheduledRevocation | Expired | Suspended | Revoked, Deleted)
+            | (Deleted, Active | Suspended | Revoked)
+    )
+}
+
+impl KeyPair {
+    /// Moves the key to `to` at `now`, recording who did it and why
+    ///
+    /// Revoking also cancels a pending scheduled revocation and brings the key's expiry forward
+    /// to `now`.
+    pub fn transition(
+        &mut self,
+        to: KeyState,
+        actor: Option<String>,
+        reason: Option<String>,
+        now: DateTime<Utc>,
+    ) -> Result<LifecycleEvent, KeyManagementError> {
+        self.transition_from(self.state(now), to, actor, reason, now)
+    }
+
+    /// Moves the key from `from` rather than the state it is in at `now`
+    ///
+    /// A scheduled revocation reads as revoked from the moment it is due, so the sweeper
+    /// executing it records the move from the scheduled state it was made in.
+    pub(crate) fn transition_from(
+        &mut self,
+        from: KeyState,
+        to: KeyState,
+        actor: Option<String>,
+        reason: Option<String>,
+        now: DateTime<Utc>,
+    ) -> Result<LifecycleEvent, KeyManagementError> {
+        let invalid = || KeyManagementError::InvalidTransition { key_id: self.id, from, to };
+        let lifecycle = Lifecycle::for_target(to).filter(|_| is_legal(from, to)).ok_or_else(invalid)?;
+        if from == KeyState::Deleted && self.state_before_deletion().is_some_and(|before| Lifecycle::for_target(before) != Some(lifecycle)) {
+            return Err(invalid());
+        }
+
+        self.lifecycle = lifecycle;
+        if to == KeyState::Revoked {
+            self.revocation_scheduled_at = None;
+            let now = crate::timestamps::normalize(now);
+            self.expires_at = Some(self.expires_at.map_or(now, |expires_at| expires_at.min(now)));
+        }
+        let event = LifecycleEvent { at: now, from, to, actor, reason };
+        tracing::info!(
+            "Key {} moved from {} to {} by {}{}",
+            self.id,
+            from.as_str(),
+            to.as_str(),
+            event.actor.as_deref().unwrap_or("an unauthenticated caller"),
+            event.reason.as_deref().map(|reason| format!(": {}", reason)).unwrap_or_default(),
+        );
+        self.lifecycle_history.push(event.clone());
+        Ok(event)
+    }
+
+    /// The stored state a deleted key returns to when restored
+    ///
+    /// Expired keys and keys pending revocation were active when deleted, and are restored so.
+    pub fn state_before_deletion(&self) -> Option<KeyState> {
+        let deletion = self.lifecycle_history.iter().rev().find(|event| event.to == KeyState::Deleted)?;
+        Some(match deletion.from {
+            KeyState::Suspended => KeyState::Suspended,
+            KeyState::Revoked => KeyState::Revoked,
+            _ => KeyState::Active,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_generation::generate_test_key_pair;
+    use chrono::Duration;
+
+    const STATES: [KeyState; 6] = [
+        KeyState::Active,
+        KeyState::ScheduledRevocation,
+        KeyState::Expired,
+        KeyState::Suspended,
+        KeyState::Revoked,
+        KeyState::Deleted,
+    ];
+
+    /// A key in `state` at `now`, reached through legal moves
+    fn key_in(state: KeyState, now: DateTime<Utc>) -> KeyPair {
+        let mut key_pair = generate_test_key_pair("Lifecycle").unwrap();
+        let earlier = now - Duration::hours(1);
+        match state {
+            KeyState::Active => {}
+            KeyState::ScheduledRevocation => key_pair.revocation_scheduled_at = Some(now + Duration::days(1)),
+            KeyState::Expired => key_pair.expires_at = Some(earlier),
+            _ => {
+                key_pair.transition(state, None, None, earlier).unwrap();
+            }
+        }
+        assert_eq!(key_pair.state(now), state);
+        key_pair
+    }
+
+    #[test]
+    fn test_every_transition_is_legal_or_refused() {
+        let now = Utc::now();
+        let legal = [
+            (KeyState::Active, KeyState::Suspended),
+            (KeyState::Active, KeyState::Revoked),
+            (KeyState::Active, KeyState::Deleted),
+            (KeyState::ScheduledRevocation, KeyState::Suspended),
+            (KeyState::ScheduledRevocation, KeyState::Revoked),
+            (KeyState::ScheduledRevocation, KeyState::Deleted),
+            (KeyState::Expired, KeyState::Revoked),
+            (KeyState::Expired, KeyState::Deleted),
+            (KeyState::Suspended, KeyState::Active),
+            (KeyState::Suspended, KeyState::Revoked),
+            (KeyState::Suspended, KeyState::Deleted),
+            (KeyState::Revoked, KeyState::Deleted),
+            (KeyState::Deleted, KeyState::Active),
+        ];
+        for from in STATES {
+            for to in STATES {
+                let mut key_pair = key_in(from, now);
+                let before = key_pair.clone();
+                let result = key_pair.transition(to, Some("ops".to_string()), Some("test".to_string()), now);
+                if legal.contains(&(from, to)) {
+                    let event = result.unwrap_or_else(|e| panic!("{:?} -> {:?} refused: {}", from, to, e));
+                    assert_eq!((event.from, event.to, event.actor.as_deref()), (from, to, Some("ops")));
+                    assert_eq!(key_pair.lifecycle_history.last(), Some(&event));
+                } else {
+                    assert!(matches!(result, Err(KeyManagementError::InvalidTransition { .. })), "{:?} -> {:?}: {:?}", from, to, result);
+                    assert_eq!(key_pair.lifecycle_history, before.lifecycle_history);
+                    assert_eq!(key_pair.lifecycle, before.lifecycle);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_deleted_keys_are_restored_to_the_state_they_were_deleted_in() {
+        let now = Utc::now();
+        for (deleted_in, restored_to) in [
+            (KeyState::Active, KeyState::Active),
+            (KeyState::Expired, KeyState::Expired),
+            (KeyState::Suspended, KeyState::Suspended),
+            (KeyState::Revoked, KeyState::Revoked),
+        ] {
+            let mut key_pair = key_in(deleted_in, now);
+            key_pair.transition(KeyState::Deleted, None, None, now).unwrap();
+            let target = key_pair.state_before_deletion().unwrap();
+            for other in [KeyState::Active, KeyState::Suspended, KeyState::Revoked].into_iter().filter(|other| *other != target) {
+                assert!(key_pair.clone().transition(other, None, None, now).is_err(), "{:?} restored as {:?}", deleted_in, other);
+            }
+            key_pair.transition(target, None, None, now).unwrap();
+            assert_eq!(key_pair.state(now), restored_to);
+        }
+    }
+
+    #[test]
+    fn test_revoking_cancels_the_schedule_and_stamps_the_expiry() {
+        let now = crate::timestamps::normalize(Utc::now());
+        let mut key_pair = key_in(KeyState::ScheduledRevocation, now);
+        key_pair.expires_at = Some(now + Duration::days(30));
+        key_pair.transition(KeyState::Revoked, None, Some("compromised".to_string()), now).unwrap();
+        assert_eq!((key_pair.revocation_scheduled_at, key_pair.expires_at), (None, Some(now)));
+
+        // An earlier expiry is kept
+        let mut expired = key_in(KeyState::Expired, now);
+        let expired_at = expired.expires_at;
+        expired.transition(KeyState::Revoked, None, None, now).unwrap();
+        assert_eq!(expired.expires_at, expired_at);
+    }
+
+    #[test]
+    fn test_suspended_keys_cannot_sign_until_resumed() {
+        let now = Utc::now();
+        let mut key_pair = key_in(KeyState::Suspended, now);
+        assert!(matches!(key_pair.state(now).check_usable(key_pair.id), Err(KeyManagementError::KeySuspended(_))));
+        key_pair.transition(KeyState::Active, None, None, now).unwrap();
+        assert!(key_pair.state(now).check_usable(key_pair.id).is_ok());
+    }
+}
@@ -0,0 +1,205 @@
+//! Limits on concurrent expensive operations
+//!
+//! Generating a key and signing with an encrypted key both run the password KDF, which is
+//! deliberately slow. A burst of either can occupy every core and starve cheap requests such as
+//! verification, so each is capped by its own semaphore. When every permit is taken, a request
+//! either waits in a bounded queue for up to the queue timeout or is refused at once, depending
+//! on the overload policy. Refused requests get `503` with `Retry-After`.
+
+use crate::config::Config;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+/// What happens to a request when an operation is at its concurrency limit
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OverloadPolicy {
+    /// Wait for a permit, up to the queue depth and timeout
+    #[default]
+    Queue,
+    /// Refuse immediately
+    Reject,
+}
+
+impl OverloadPolicy {
+    /// Parses `queue` or `reject`, ignoring case and surrounding whitespace
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "queue" => Some(Self::Queue),
+            "reject" => Some(Self::Reject),
+            _ => None,
+        }
+    }
+}
+
+/// A request refused because its operation is at capacity
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("Too many {operation} operations in progress; retry later")]
+pub struct Overloaded {
+    pub operation: &'static str,
+}
+
+/// Load on one limited operation, for metrics
+#[derive(Debug, Clone, PartialEq)]
+pub struct LimiterStats {
+    pub operation: &'static str,
+    pub in_flight: usize,
+    pub queued: usize,
+    pub rejected: u64,
+}
+
+/// Caps how many instances of one operation run at once
+pub struct OperationLimiter {
+    operation: &'static str,
+    limit: Option<usize>,
+    permits: Semaphore,
+    policy: OverloadPolicy,
+    max_queue: usize,
+    queue_timeout: Duration,
+    queued: AtomicUsize,
+    rejected: AtomicU64,
+}
+
+impl OperationLimiter {
+    /// Limiter allowing `limit` concurrent operations, or any number when `None`
+    pub fn new(operation: &'static str, limit: Option<usize>, policy: OverloadPolicy, max_queue: usize, queue_timeout: Duration) -> Self {
+        Self {
+            operation,
+            limit,
+            permits: Semaphore::new(limit.unwrap_or(0)),
+            policy,
+            max_queue,
+            queue_timeout,
+            queued: AtomicUsize::new(0),
+            rejected: AtomicU64::new(0),
+        }
+    }
+
+    /// Limiter that never holds a request back
+    pub fn unlimited(operation: &'static str) -> Self {
+        Self::new(operation, None, OverloadPolicy::default(), 0, Duration::ZERO)
+    }
+
+    /// Waits for a slot to run the operation in, per the overload policy
+    ///
+    /// The slot is held until the returned permit is dropped; unlimited operations get no permit.
+    pub async fn acquire(&self) -> Result<Option<SemaphorePermit<'_>>, Overloaded> {
+        if self.limit.is_none() {
+            return Ok(None);
+        }
+        if let Ok(permit) = self.permits.try_acquire() {
+            return Ok(Some(permit));
+        }
+        if self.policy == OverloadPolicy::Reject {
+            return Err(self.reject());
+        }
+
+        if self.queued.fetch_add(1, Ordering::SeqCst) >= self.max_queue {
+            self.queued.fetch_sub(1, Ordering::SeqCst);
+            return Err(self.reject());
+        }
+        let waited = tokio::time::timeout(self.queue_timeout, self.permits.acquire()).await;
+        self.queued.fetch_sub(1, Ordering::SeqCst);
+        match waited {
+            Ok(Ok(permit)) => Ok(Some(permit)),
+            // Timed out, or the semaphore was closed
+            _ => Err(self.reject()),
+        }
+    }
+
+    fn reject(&self) -> Overloaded {
+        self.rejected.fetch_add(1, Ordering::Relaxed);
+        Overloaded { operation: self.operation }
+    }
+
+    /// Current load
+    pub fn stats(&self) -> LimiterStats {
+        LimiterStats {
+            operation: self.operation,
+            in_flight: self.limit.map_or(0, |limit| limit - self.permits.available_permits()),
+            queued: self.queued.load(Ordering::SeqCst),
+            rejected: self.rejected.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Concurrency limits for every expensive operation
+pub struct OperationLimits {
+    /// Key generation
+    pub generation: OperationLimiter,
+    /// Signing with a password-encrypted key
+    pub signing: OperationLimiter,
+}
+
+impl Default for OperationLimits {
+    fn default() -> Self {
+        Self {
+            generation: OperationLimiter::unlimited("generate"),
+            signing: OperationLimiter::unlimited("sign"),
+        }
+    }
+}
+
+impl OperationLimits {
+    /// Limits set in configuration
+    pub fn from_config(config: &Config) -> Self {
+        let limiter = |operation, limit: Option<u32>| OperationLimiter::new(
+            operation,
+            limit.map(|limit| limit as usize),
+            config.overload_policy,
+            config.overload_queue_depth as usize,
+            Duration::from_secs(config.overload_queue_timeout_secs.into()),
+        );
+        Self {
+            generation: limiter("generate", config.max_concurrent_generations),
+            signing: limiter("sign", config.max_concurrent_signs),
+        }
+    }
+
+    /// Load on each limited operation
+    pub fn stats(&self) -> Vec<LimiterStats> {
+        vec![self.generation.stats(), self.signing.stats()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_queue_admits_in_turn_and_rejects_when_full() {
+        let limiter = Arc::new(OperationLimiter::new("generate", Some(1), OverloadPolicy::Queue, 1, Duration::from_secs(5)));
+        let running = limiter.acquire().await.unwrap();
+        assert_eq!(limiter.stats().in_flight, 1);
+
+        // The next request waits for the running one; the one after finds the queue full
+        let waiting = tokio::spawn({
+            let limiter = limiter.clone();
+            async move { limiter.acquire().await.map(|permit| permit.is_some()) }
+        });
+        while limiter.stats().queued == 0 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(limiter.acquire().await.unwrap_err(), Overloaded { operation: "generate" });
+
+        drop(running);
+        assert_eq!(waiting.await.unwrap(), Ok(true));
+        assert_eq!(limiter.stats(), LimiterStats { operation: "generate", in_flight: 0, queued: 0, rejected: 1 });
+
+        // A queued request gives up after the timeout
+        let impatient = OperationLimiter::new("sign", Some(1), OverloadPolicy::Queue, 4, Duration::from_millis(20));
+        let _running = impatient.acquire().await.unwrap();
+        assert!(impatient.acquire().await.is_err());
+        assert_eq!(impatient.stats().queued, 0);
+
+        let strict = OperationLimiter::new("sign", Some(1), OverloadPolicy::Reject, 4, Duration::from_secs(5));
+        let _running = strict.acquire().await.unwrap();
+        assert!(strict.acquire().await.is_err());
+        assert_eq!(strict.stats().rejected, 1);
+
+        assert!(OperationLimiter::unlimited("sign").acquire().await.unwrap().is_none());
+    }
+}
@@ -1,133 +1,357 @@
-mod api;
-mod key_generation;
-mod key_storage;
-mod key_verification;
-mod models;
-mod utils;
-
-use axum::{
-    extract::{Json, Path, State},
-    routing::{get, post, put},
-    Router,
-    http::StatusCode,
-    response::IntoResponse,
-};
+use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
-use tower_http::cors::{Any, CorsLayer};
-use tracing::{info, Level};
-use tracing_subscriber;
-use serde_json;
+use tokio::sync::RwLock;
+use tracing::info;
+use tracing_subscriber::EnvFilter;
 
-use crate::api::AppState;
-use crate::key_storage::create_default_storage;
-use crate::models::{
-    GenerateKeyRequest, SignDocumentRequest, VerifySignatureRequest, UpdateKeyRequest, RevokeKeyRequest,
-};
+use inkan_key_management_module::api::AppState;
+use inkan_key_management_module::api_version::ApiUsage;
+use inkan_key_management_module::capabilities::About;
+use inkan_key_management_module::capacity::KeystoreCapacity;
+use inkan_key_management_module::certification::create_default_certification_store;
+use inkan_key_management_module::clock::SystemClock;
+use inkan_key_management_module::config::Config;
+use inkan_key_management_module::deadline::RequestDeadlines;
+use inkan_key_management_module::storage_breaker::StorageBreaker;
+use inkan_key_management_module::delegation::create_default_delegation_store;
+use inkan_key_management_module::entropy::{spawn_entropy_checks, EntropyMonitor};
+use inkan_key_management_module::integrity::validate_on_load;
+use inkan_key_management_module::kdf_stats::KdfTimings;
+#[cfg(not(feature = "verifier-only"))]
+use inkan_key_management_module::key_pool::{spawn_key_pool, KeyPool};
+use inkan_key_management_module::key_storage::{create_default_storage, KeyStorage};
+use inkan_key_management_module::keystore_watch::spawn_keystore_watcher;
+use inkan_key_management_module::limits::OperationLimits;
+#[cfg(not(feature = "verifier-only"))]
+use inkan_key_management_module::migration::migrate_directory;
+use inkan_key_management_module::notifications::ExpiryNotifications;
+use inkan_key_management_module::federation::KeyResolver;
+use inkan_key_management_module::rate_limit::{rate_buckets, BackendFailure, ClientRateLimiter, RequestCounter};
+use inkan_key_management_module::receipts::create_default_receipt_store;
+use inkan_key_management_module::request_auth::RequestAuthenticator;
+use inkan_key_management_module::routes;
+#[cfg(not(feature = "verifier-only"))]
+use inkan_key_management_module::self_test::startup_self_test;
+use inkan_key_management_module::shares::create_default_share_store;
+use inkan_key_management_module::sign_policy::SignPolicy;
+use inkan_key_management_module::signing_backend::open_backend;
+use inkan_key_management_module::slo::SloTracker;
+#[cfg(not(feature = "verifier-only"))]
+use inkan_key_management_module::key_transport::{load_default_transport_key, TransportKey};
+use inkan_key_management_module::storage_lock::{claim_storage, spawn_follower_reload, spawn_lock_heartbeat, StorageRole};
+use inkan_key_management_module::sweeper::{spawn_sweeper, TaskStatus};
+use inkan_key_management_module::trust_manifest;
+use inkan_key_management_module::usage_report::UsageReportCache;
+use inkan_key_management_module::verification_cache::VerificationCache;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    // Initialize logging
+    // Initialize logging; RUST_LOG overrides the level, e.g. to trace private key reads
     tracing_subscriber::fmt()
-        .with_max_level(Level::INFO)
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")))
         .init();
 
     info!("🚀 Starting Inkan Key Management Module...");
 
-    // Create and initialize storage
-    let storage = create_default_storage();
-    storage.load_from_disk().await?;
+    // Load configuration
+    let config = Config::from_env()?;
+    info!("🔐 New keys use {:?} with {} iterations", config.kdf.algorithm, config.kdf.iterations);
+
+    // Take ownership of the keystore before reading it, or follow the instance that owns it
+    let storage = create_default_storage()
+        .with_public_only(config.profile.public_only())
+        .with_non_exportable_backup(config.non_exportable_backup)
+        .with_breaker(StorageBreaker::new(config.storage_breaker.clone()))
+        .with_event_retention(config.event_log_retain as usize);
+    let lock_stale_after = chrono::Duration::seconds(config.lock_stale_secs.into());
+
+    // `migrate <dir>` imports keys from another key store and exits instead of serving
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(command) = args.first() {
+        let [_, dir] = args.as_slice() else {
+            anyhow::bail!("Usage: inkan-key-management-module migrate <dir>");
+        };
+        if command != "migrate" {
+            anyhow::bail!("Unknown command {:?}; the only command is `migrate <dir>`", command);
+        }
+        if config.profile.public_only() {
+            anyhow::bail!("The verifier profile holds no private keys and cannot migrate a key store");
+        }
+        return migrate(&storage, &config, dir, lock_stale_after).await;
+    }
+
+    let heartbeat_interval = std::time::Duration::from_secs((config.lock_stale_secs / 3).into());
+    // A verifier never writes the keystore, so it follows it without claiming it; one started
+    // from a trust manifest does not read the keystore at all
+    let (lock, follower) = if let Some(manifest) = &config.trust_manifest {
+        info!("🛡️  Verifier profile: serving the keys of trust manifest {} only", manifest.path);
+        (None, true)
+    } else if config.profile.public_only() {
+        info!("🔎 Verifier profile: following the keystore read-only, without private keys");
+        (None, true)
+    } else {
+        match claim_storage(storage.storage_path(), config.lock_conflict, chrono::Utc::now(), lock_stale_after)? {
+            StorageRole::Owner(lock) => (Some(Arc::new(lock)), false),
+            StorageRole::Follower(holder) => {
+                info!("👥 Keystore is owned by pid {}; following it read-only", holder.pid);
+                (None, true)
+            }
+        }
+    };
+    // The owner validates the keystore as it loads it; a follower picks up the owner's repairs
+    let mut manifest_generated_at = None;
+    let keystore_load = if let Some(manifest) = &config.trust_manifest {
+        // An unsigned or tampered manifest stops startup here
+        manifest_generated_at = Some(trust_manifest::install(&storage, manifest, None).await?.generated_at);
+        info!("🛡️  Trust manifest verified against the configured root key");
+        None
+    } else if follower {
+        storage.load_from_disk().await?;
+        None
+    } else {
+        let summary = validate_on_load(&storage).await?;
+        if summary.is_clean() {
+            info!("🩺 Keystore validated on load: {}", summary.summary());
+        } else {
+            tracing::warn!("🩺 Keystore validated on load: {}", summary.summary());
+        }
+        Some(summary)
+    };
     info!("📁 Storage initialized with {} keys", storage.key_count().await);
+    info!("🔢 Operation events continue after sequence number {}", storage.events().high_water().await);
+    // Names, descriptions and tags stored before normalization are brought in line once; a
+    // follower picks up the owner's rewrite
+    if !follower {
+        let normalized = storage.normalize_stored_metadata().await;
+        if normalized > 0 {
+            info!("🔤 Normalized the metadata of {} keys, originals kept in their history", normalized);
+        }
+        let retimed = storage.normalize_stored_timestamps().await;
+        if retimed > 0 {
+            info!("🕒 Rewrote the expiry timestamps of {} keys in UTC at millisecond precision", retimed);
+        }
+    }
+
+    let receipts = create_default_receipt_store();
+    receipts.load_from_disk().await?;
+    info!("🧾 Loaded {} signature receipts", receipts.count().await);
+
+    let certifications = create_default_certification_store();
+    certifications.load_from_disk().await?;
+
+    // Rate limits are counted in Redis when replicas share one, otherwise in memory
+    let request_counter = RequestCounter::new(rate_buckets(config.redis_url.as_deref())?, BackendFailure::default());
+    let backend_failure = config.rate_limit_backend_failure;
+    if config.redis_url.is_some() {
+        info!("🚦 Rate limits shared through Redis");
+    }
+
+    let shares = create_default_share_store().with_request_counter(request_counter.with_failure_mode(backend_failure.shares));
+    shares.load_from_disk().await?;
+    let delegations = create_default_delegation_store();
+    delegations.load_from_disk().await?;
+
+    let notifications = ExpiryNotifications::from_config(&config.notifications)?;
+    if let Some(notifications) = &notifications {
+        info!("📣 Expiry notifications at {:?} days before expiry", notifications.thresholds_days);
+    }
+
+    let sign_policy = SignPolicy::from_config(&config.sign_policy)?;
+    if let Some(url) = &config.sign_policy.url {
+        info!("⚖️  Signatures need approval from {} ({})", url, if config.sign_policy.fail_open { "fail-open" } else { "fail-closed" });
+    }
+
+    let key_resolver = KeyResolver::from_config(&config.federation)?.with_request_counter(request_counter.with_failure_mode(backend_failure.federation));
+    for peer in key_resolver.peers() {
+        info!("🤝 Unknown keys resolved from peer {} at {}", peer.name, peer.base_url);
+    }
+
+    // The routes that use the transport key are not served by the verifier profile, which gets
+    // a throwaway one rather than reading or creating the key file
+    #[cfg(not(feature = "verifier-only"))]
+    let transport_key = if config.profile.public_only() {
+        TransportKey::generate(chrono::Utc::now())
+    } else {
+        load_default_transport_key()?
+    };
+
+    if config.read_only && !follower {
+        info!("🔒 Starting in read-only mode");
+    }
+
+    // Prove the crypto path and storage backend work before serving traffic; the storage check
+    // writes the keystore, so a follower leaves it to the owner
+    #[cfg(not(feature = "verifier-only"))]
+    let self_test = if config.startup_self_test && !follower {
+        let report = startup_self_test(&storage, &config.kdf).await?;
+        info!("✅ Self-test passed ({} checks)", report.checks.len());
+        Some(report)
+    } else {
+        info!("⚠️  Startup self-test skipped");
+        None
+    };
+    // Verifier-only builds always follow the keystore and have no signing path to prove
+    #[cfg(feature = "verifier-only")]
+    let self_test = None;
+
+    // HSM keys sign on the token; without one they stay listed and verifiable but cannot sign
+    let hsm = open_backend(&config)?;
+    if let Some(backend) = &hsm {
+        info!("🔏 HSM keys sign through the {} backend", backend.name());
+    }
+
+    // A failed entropy check disables key generation but the service still starts, since
+    // existing keys stay usable for signing and verification
+    let entropy = Arc::new(EntropyMonitor::os());
+    let entropy_status = entropy.check(chrono::Utc::now());
+    if entropy_status.degraded {
+        tracing::error!("🎲 Entropy check failed, key generation disabled: {}", entropy_status.last_error.unwrap_or_default());
+    } else {
+        info!("🎲 Entropy check passed");
+    }
 
     // Create application state
     let state = Arc::new(AppState {
         storage: Arc::new(storage),
+        clock: Arc::new(SystemClock),
+        read_only: AtomicBool::new(config.read_only || follower),
+        limits: OperationLimits::from_config(&config),
+        shares: Arc::new(shares),
+        delegations: Arc::new(delegations),
+        verification_cache: VerificationCache::new(
+            config.verify_cache_size as usize,
+            chrono::Duration::seconds(config.verify_cache_ttl_secs.into()),
+        ),
+        request_auth: RequestAuthenticator::from_config(&config),
+        capacity: KeystoreCapacity::from_config(&config),
+        kdf_timings: KdfTimings::new(),
+        verify_rate_limit: ClientRateLimiter::with_counter(config.verify_requests_per_minute, request_counter.with_failure_mode(backend_failure.verify)),
+        sign_policy: Arc::new(sign_policy),
+        key_resolver: Arc::new(key_resolver),
+        #[cfg(not(feature = "verifier-only"))]
+        transport_key: Arc::new(transport_key),
+        sweeper: Arc::new(TaskStatus::default()),
+        deadlines: RequestDeadlines::from_config(&config),
+        api_usage: ApiUsage::default(),
+        #[cfg(not(feature = "verifier-only"))]
+        key_pool: Arc::new(KeyPool::from_config(&config)),
+        usage_reports: UsageReportCache::default(),
+        slo: SloTracker::from_config(&config),
+        config: Arc::new(config),
+        receipts: Arc::new(receipts),
+        certifications: Arc::new(certifications),
+        self_test: RwLock::new(self_test),
+        keystore_load,
+        entropy,
+        hsm,
+        follower,
     });
 
-    // Create CORS layer
-    let cors = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods(Any)
-        .allow_headers(Any);
-
-    // Create router with all endpoints
-    let app = Router::new()
-        .route("/health", get(|| async { "OK" }))
-
-        .route("/keys/generate", post(|state: State<Arc<AppState>>, json: Json<GenerateKeyRequest>| async move {
-            tracing::info!("DEBUG: Route handler called with request: {:?}", json.0);
-            
-            // Simple test response to see if the route works
-            let test_response = serde_json::json!({
-                "success": true,
-                "message": "Test response - route working",
-                "request": json.0
-            });
-            
-            tracing::info!("DEBUG: Returning test response");
-            Json(test_response)
-        }))
-        .route("/keys", get(|state: State<Arc<AppState>>, query: axum::extract::Query<crate::api::ListKeysQuery>| async move {
-            crate::api::list_keys(state, query).await
-        }))
-        .route("/keys/search", get(|state: State<Arc<AppState>>, query: axum::extract::Query<crate::api::ListKeysQuery>| async move {
-            crate::api::search_keys(state, query).await
-        }))
-        .route("/keys/stats", get(|state: State<Arc<AppState>>| async move {
-            crate::api::get_key_stats(state).await
-        }))
-        .route("/keys/:key_id", get(|state: State<Arc<AppState>>, Path(key_id): Path<uuid::Uuid>| async move {
-            match crate::api::get_public_key(state, Path(key_id)).await {
-                Ok(response) => response.into_response(),
-                Err(status) => status.into_response(),
-            }
-        }))
-        .route("/keys/:key_id", put(|state: State<Arc<AppState>>, Path(key_id): Path<uuid::Uuid>, json: Json<UpdateKeyRequest>| async move {
-            match crate::api::update_key(state, Path(key_id), json).await {
-                Ok(response) => response.into_response(),
-                Err(status) => status.into_response(),
+    // Keep the keystore lock fresh, pick up the owner's changes when following, or wait for
+    // SIGHUP to re-read a trust manifest
+    match &lock {
+        Some(lock) => {
+            spawn_lock_heartbeat(lock.clone(), state.clock.clone(), heartbeat_interval);
+        }
+        None => match &state.config.trust_manifest {
+            Some(manifest) => {
+                trust_manifest::spawn_reload_on_hangup(state.storage.clone(), manifest.clone(), manifest_generated_at)?;
+                info!("🛡️  Send SIGHUP to reload the trust manifest");
             }
-        }))
-        .route("/keys/:key_id/revoke", post(|state: State<Arc<AppState>>, Path(key_id): Path<uuid::Uuid>, json: Json<RevokeKeyRequest>| async move {
-            match crate::api::revoke_key(state, Path(key_id), json).await {
-                Ok(response) => response.into_response(),
-                Err(status) => status.into_response(),
+            None => {
+                spawn_follower_reload(state.storage.clone(), heartbeat_interval);
             }
-        }))
-        .route("/keys/:key_id/public", get(|state: State<Arc<AppState>>, Path(key_id): Path<uuid::Uuid>| async move {
-            match crate::api::get_public_key(state, Path(key_id)).await {
-                Ok(response) => response.into_response(),
-                Err(status) => status.into_response(),
-            }
-        }))
-        .route("/sign", post(|state: State<Arc<AppState>>, json: Json<SignDocumentRequest>| async move {
-            match crate::api::sign_document(state, json).await {
-                Ok(response) => response.into_response(),
-                Err(status) => status.into_response(),
-            }
-        }))
-        .route("/verify", post(|_state: State<Arc<AppState>>, json: Json<VerifySignatureRequest>| async move {
-            crate::api::verify_signature(json).await
-        }))
-        .with_state(state)
-        .layer(cors);
+        },
+    }
+
+    // Pick up restores and manual fixes to the keystore file without a restart; a follower
+    // already reloads it
+    if state.config.watch_keystore && !follower {
+        spawn_keystore_watcher(state.storage.clone())?;
+        info!("👀 Watching the keystore file for external changes");
+    }
+
+    // Re-check entropy periodically, alerting through the notification channels when it degrades
+    spawn_entropy_checks(
+        state.entropy.clone(),
+        state.clock.clone(),
+        notifications.as_ref().map(|notifications| notifications.notifiers.clone()).unwrap_or_default(),
+        std::time::Duration::from_secs(state.config.entropy_check_interval_secs.into()),
+    );
+
+    // Execute scheduled revocations, send expiry notices, and flush usage counters in the
+    // background; a follower leaves this to the owner
+    if !follower {
+        spawn_sweeper(
+            state.storage.clone(),
+            state.clock.clone(),
+            notifications,
+            std::time::Duration::from_secs(state.config.sweep_interval_secs.into()),
+            state.sweeper.clone(),
+        );
+    }
+
+    // Draw keys ahead for `fast` generation; a follower cannot store keys, so it keeps none
+    #[cfg(not(feature = "verifier-only"))]
+    if !follower && spawn_key_pool(state.key_pool.clone(), state.entropy.clone(), state.clock.clone()).is_some() {
+        info!("🏊 Keeping {} keys ready for fast generation", state.config.key_pool_size);
+    }
+
+    let app = routes::router(state.clone());
 
     // Bind and serve
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3002").await?;
     info!("🌐 Key management server listening on http://localhost:3002");
-    info!("📚 Available endpoints:");
-    info!("   POST /keys/generate - Generate new key pair");
-    info!("   GET  /keys - List all keys");
-    info!("   GET  /keys/search - Search keys");
-    info!("   GET  /keys/stats - Get key statistics");
-    info!("   GET  /keys/:id - Get key information");
-    info!("   PUT  /keys/:id - Update key information");
-    info!("   POST /keys/:id/revoke - Revoke a key");
-    info!("   GET  /keys/:id/public - Get public key");
-    info!("   POST /sign - Sign document with private key");
-    info!("   POST /verify - Verify document signature");
-    info!("   GET  /health - Health check");
-
-    axum::serve(listener, app).await?;
 
+    // One machine-readable description of the build and its routes, the same document `/about`
+    // serves, rather than a hand-kept list
+    let about = About::new(
+        &routes::served_versions(&state.config),
+        state.hsm.is_some(),
+        state.request_auth.is_enabled(),
+        state.config.profile,
+        routes::served_endpoints(state.config.profile),
+    );
+    info!(target: "inkan::about", about = %serde_json::to_string(&about)?, "📚 Serving {} endpoints", about.endpoints.len());
+
+    axum::serve(listener, app.into_make_service_with_connect_info::<std::net::SocketAddr>())
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+            info!("🛑 Shutting down");
+        })
+        .await?;
+
+    // Pooled keys were never stored; drop them before anything else
+    #[cfg(not(feature = "verifier-only"))]
+    state.key_pool.clear();
+
+    // Persist changes recorded since the last sweep, such as usage counters, then release the lock
+    if !follower {
+        state.storage.flush().await?;
+    }
+    drop(lock);
+
+    Ok(())
+}
+
+/// Imports the keys of another key store in `dir` into `storage`, owning it meanwhile
+#[cfg(not(feature = "verifier-only"))]
+async fn migrate(storage: &KeyStorage, config: &Config, dir: &str, lock_stale_after: chrono::Duration) -> anyhow::Result<()> {
+    let StorageRole::Owner(_lock) = claim_storage(storage.storage_path(), config.lock_conflict, chrono::Utc::now(), lock_stale_after)? else {
+        anyhow::bail!("Keystore is in use by another instance; stop it before migrating");
+    };
+    storage.load_from_disk().await?;
+    let password = std::env::var("INKAN_MIGRATE_PASSWORD").ok();
+    let report = migrate_directory(storage, std::path::Path::new(dir), password.as_deref(), &config.kdf).await?;
+    info!("📦 Migration finished: {}", report.summary());
+    println!("{}", serde_json::to_string_pretty(&report)?);
     Ok(())
 }
+
+/// Imports the keys of another key store in `dir` into `storage`, owning it meanwhile
+///
+/// Builds with the `verifier-only` feature hold no private keys and compile migration out.
+#[cfg(feature = "verifier-only")]
+async fn migrate(storage: &KeyStorage, _config: &Config, dir: &str, _lock_stale_after: chrono::Duration) -> anyhow::Result<()> {
+    anyhow::bail!("Cannot migrate {} into {}: this build was compiled with verifier-only", dir, storage.storage_path())
+}
@@ -0,0 +1,232 @@
+//! Prometheus metrics
+//!
+//! Renders keystore gauges and per-key usage counters in the Prometheus text exposition
+//! format. Per-key series are labelled by key id; to bound cardinality only the busiest keys
+//! get their own label and the remainder are summed under `key_id="other"`.
+
+use crate::capacity::{CapacityLevel, CapacityStatus};
+use crate::entropy::EntropyStatus;
+use crate::kdf_stats::{KdfHistogram, KdfStage, KDF_BUCKETS_SECS};
+#[cfg(not(feature = "verifier-only"))]
+use crate::key_pool::PoolStats;
+use crate::key_storage::PersistenceStatus;
+use crate::limits::LimiterStats;
+use crate::models::KeyInfo;
+use crate::storage_breaker::BreakerState;
+use crate::verification_cache::CacheStats;
+use std::fmt::Write;
+
+/// Content type of the Prometheus text exposition format
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// Label used for keys beyond the per-key label limit
+pub const OTHER_KEYS_LABEL: &str = "other";
+
+fn write_header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn write_key_counter(
+    out: &mut String,
+    name: &str,
+    help: &str,
+    labelled: &[&KeyInfo],
+    rest: &[&KeyInfo],
+    value: impl Fn(&KeyInfo) -> u64,
+) {
+    write_header(out, name, "counter", help);
+    for key in labelled {
+        let _ = writeln!(out, "{}{{key_id=\"{}\"}} {}", name, key.id, value(key));
+    }
+    if !rest.is_empty() {
+        let total: u64 = rest.iter().map(|key| value(key)).sum();
+        let _ = writeln!(out, "{}{{key_id=\"{}\"}} {}", name, OTHER_KEYS_LABEL, total);
+    }
+}
+
+fn write_histograms<'a>(
+    out: &mut String,
+    name: &str,
+    help: &str,
+    histograms: impl Iterator<Item = &'a (KdfStage, &'a str, KdfHistogram)>,
+) {
+    write_header(out, name, "histogram", help);
+    for (_, kdf, histogram) in histograms {
+        for (bound, count) in KDF_BUCKETS_SECS.iter().zip(histogram.buckets) {
+            let _ = writeln!(out, "{}_bucket{{kdf=\"{}\",le=\"{}\"}} {}", name, kdf, bound, count);
+        }
+        let _ = writeln!(out, "{}_bucket{{kdf=\"{}\",le=\"+Inf\"}} {}", name, kdf, histogram.count);
+        let _ = writeln!(out, "{}_sum{{kdf=\"{}\"}} {}", name, kdf, histogram.sum_secs);
+        let _ = writeln!(out, "{}_count{{kdf=\"{}\"}} {}", name, kdf, histogram.count);
+    }
+}
+
+/// Service-wide state reported alongside the per-key metrics
+#[derive(Debug, Default)]
+pub struct ServiceMetrics {
+    pub persistence: PersistenceStatus,
+    pub entropy: EntropyStatus,
+    pub limits: Vec<LimiterStats>,
+    pub verification_cache: CacheStats,
+    pub capacity: CapacityStatus,
+    pub kdf_timings: Vec<(KdfStage, &'static str, KdfHistogram)>,
+    /// Requests answered `504` for outlasting their deadline, by API version label and route
+    pub request_timeouts: Vec<(&'static str, String, u64)>,
+    /// Requests to the versioned API, by the label of the version they addressed
+    pub api_requests: Vec<(&'static str, u64)>,
+    #[cfg(not(feature = "verifier-only"))]
+    pub key_pool: PoolStats,
+    /// Receipt writes that failed, withholding or degrading the signatures they recorded
+    pub receipt_write_failures: u64,
+    /// Key usage updates lost because their key was gone
+    pub usage_updates_dropped: u64,
+    pub storage_breaker: BreakerState,
+    /// Transitions of the storage breaker into each state
+    pub storage_breaker_transitions: Vec<(&'static str, u64)>,
+    /// Requests refused by the storage breaker, by route
+    pub storage_breaker_rejections: Vec<(String, u64)>,
+}
+
+/// Renders metrics for the given keys, labelling at most `max_key_labels` keys individually
+pub fn render_metrics(keys: &[KeyInfo], service: &ServiceMetrics, max_key_labels: usize) -> String {
+    let ServiceMetrics { persistence, entropy, limits, verification_cache, capacity, kdf_timings, request_timeouts, api_requests, #[cfg(not(feature = "verifier-only"))] key_pool, receipt_write_failures, usage_updates_dropped,
+        storage_breaker, storage_breaker_transitions, storage_breaker_rejections } = service;
+    let mut out = String::new();
+
+    write_header(&mut out, "inkan_persistence_degraded", "gauge", "Whether the latest keystore write failed");
+    let _ = writeln!(out, "inkan_persistence_degraded {}", u8::from(persistence.degraded));
+    write_header(&mut out, "inkan_persistence_consecutive_failures", "gauge", "Keystore writes failed in a row");
+    let _ = writeln!(out, "inkan_persistence_consecutive_failures {}", persistence.consecutive_failures);
+
+    write_header(&mut out, "inkan_receipt_write_failures_total", "counter", "Signature receipts that could not be written");
+    let _ = writeln!(out, "inkan_receipt_write_failures_total {}", receipt_write_failures);
+    write_header(&mut out, "inkan_usage_updates_dropped_total", "counter", "Key usage updates dropped because the key was no longer stored");
+    let _ = writeln!(out, "inkan_usage_updates_dropped_total {}", usage_updates_dropped);
+
+    write_header(&mut out, "inkan_storage_breaker_state", "gauge", "Storage breaker position: 0 closed, 1 half-open, 2 open");
+    let position = match storage_breaker {
+        BreakerState::Closed => 0,
+        BreakerState::HalfOpen => 1,
+        BreakerState::Open => 2,
+    };
+    let _ = writeln!(out, "inkan_storage_breaker_state {}", position);
+    write_header(&mut out, "inkan_storage_breaker_transitions_total", "counter", "Storage breaker transitions, by the state entered");
+    for (state, count) in storage_breaker_transitions {
+        let _ = writeln!(out, "inkan_storage_breaker_transitions_total{{state=\"{}\"}} {}", state, count);
+    }
+    write_header(&mut out, "inkan_storage_breaker_rejections_total", "counter", "Requests refused while the storage breaker was open, by route");
+    for (route, count) in storage_breaker_rejections {
+        let _ = writeln!(out, "inkan_storage_breaker_rejections_total{{route=\"{}\"}} {}", route, count);
+    }
+
+    write_header(&mut out, "inkan_entropy_degraded", "gauge", "Whether the latest entropy check failed; key generation is refused while set");
+    let _ = writeln!(out, "inkan_entropy_degraded {}", u8::from(entropy.degraded));
+    write_header(&mut out, "inkan_entropy_failures_total", "counter", "Failed entropy checks and seed draws");
+    let _ = writeln!(out, "inkan_entropy_failures_total {}", entropy.total_failures);
+
+    write_header(&mut out, "inkan_operations_in_flight", "gauge", "Expensive operations running, by operation");
+    for stats in limits {
+        let _ = writeln!(out, "inkan_operations_in_flight{{operation=\"{}\"}} {}", stats.operation, stats.in_flight);
+    }
+    write_header(&mut out, "inkan_operation_queue_depth", "gauge", "Requests waiting for an expensive operation slot, by operation");
+    for stats in limits {
+        let _ = writeln!(out, "inkan_operation_queue_depth{{operation=\"{}\"}} {}", stats.operation, stats.queued);
+    }
+    write_header(&mut out, "inkan_operation_rejections_total", "counter", "Requests refused because an operation was at capacity, by operation");
+    for stats in limits {
+        let _ = writeln!(out, "inkan_operation_rejections_total{{operation=\"{}\"}} {}", stats.operation, stats.rejected);
+    }
+    write_header(&mut out, "inkan_request_timeouts_total", "counter", "Requests answered 504 for outlasting their deadline, by API version and route");
+    for (version, route, count) in request_timeouts {
+        let _ = writeln!(out, "inkan_request_timeouts_total{{version=\"{}\",route=\"{}\"}} {}", version, route, count);
+    }
+    write_header(&mut out, "inkan_api_requests_total", "counter", "Requests to the versioned API, by version; unversioned counts deprecated unprefixed paths");
+    for (version, count) in api_requests {
+        let _ = writeln!(out, "inkan_api_requests_total{{version=\"{}\"}} {}", version, count);
+    }
+
+    #[cfg(not(feature = "verifier-only"))]
+    if key_pool.enabled {
+        write_header(&mut out, "inkan_key_pool_available", "gauge", "Pre-generated keys ready for fast generation");
+        let _ = writeln!(out, "inkan_key_pool_available {}", key_pool.available);
+        write_header(&mut out, "inkan_key_pool_claims_total", "counter", "Fast generation requests, by whether the pool had a key");
+        let _ = writeln!(out, "inkan_key_pool_claims_total{{result=\"hit\"}} {}", key_pool.hits);
+        let _ = writeln!(out, "inkan_key_pool_claims_total{{result=\"miss\"}} {}", key_pool.misses);
+    }
+
+    if verification_cache.enabled {
+        write_header(&mut out, "inkan_verify_cache_hits_total", "counter", "Verifications answered from the verification cache");
+        let _ = writeln!(out, "inkan_verify_cache_hits_total {}", verification_cache.hits);
+        write_header(&mut out, "inkan_verify_cache_misses_total", "counter", "Verifications that ran the signature check");
+        let _ = writeln!(out, "inkan_verify_cache_misses_total {}", verification_cache.misses);
+        write_header(&mut out, "inkan_verify_cache_entries", "gauge", "Results held in the verification cache");
+        let _ = writeln!(out, "inkan_verify_cache_entries {}", verification_cache.entries);
+    }
+
+    write_header(&mut out, "inkan_keystore_limit", "gauge", "Configured keystore size limits, by kind");
+    for (kind, limit) in [("soft", capacity.soft_limit), ("hard", capacity.hard_limit)] {
+        if let Some(limit) = limit {
+            let _ = writeln!(out, "inkan_keystore_limit{{kind=\"{}\"}} {}", kind, limit);
+        }
+    }
+    write_header(&mut out, "inkan_keystore_over_soft_limit", "gauge", "Whether the keystore holds at least its soft limit of keys");
+    let _ = writeln!(out, "inkan_keystore_over_soft_limit {}", u8::from(capacity.level != CapacityLevel::Ok));
+    if let Some(headroom) = capacity.headroom {
+        write_header(&mut out, "inkan_keystore_headroom", "gauge", "Keys that can still be created before the hard limit");
+        let _ = writeln!(out, "inkan_keystore_headroom {}", headroom);
+    }
+
+    for stage in [KdfStage::Derivation, KdfStage::Decryption] {
+        let help = match stage {
+            KdfStage::Derivation => "Time to derive a private key's encryption key from its password, by KDF",
+            KdfStage::Decryption => "Time to decrypt a private key, derivation included, by KDF",
+        };
+        write_histograms(&mut out, stage.metric_name(), help, kdf_timings.iter().filter(|(s, _, _)| *s == stage));
+    }
+
+    let active = keys.iter().filter(|key| key.is_active).count();
+    write_header(&mut out, "inkan_keys", "gauge", "Stored keys by state");
+    let _ = writeln!(out, "inkan_keys{{state=\"active\"}} {}", active);
+    let _ = writeln!(out, "inkan_keys{{state=\"inactive\"}} {}", keys.len() - active);
+
+    let mut ranked: Vec<&KeyInfo> = keys.iter().collect();
+    ranked.sort_by(|a, b| {
+        let activity = |key: &KeyInfo| key.usage.sign_count + key.usage.verify_count;
+        activity(b).cmp(&activity(a)).then(a.id.cmp(&b.id))
+    });
+    let (labelled, rest) = ranked.split_at(max_key_labels.min(ranked.len()));
+
+    write_key_counter(&mut out, "inkan_key_signatures_total", "Signatures made, by key", labelled, rest, |key| key.usage.sign_count);
+    write_key_counter(&mut out, "inkan_key_verifications_total", "Verifications against stored keys, by key", labelled, rest, |key| key.usage.verify_count);
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_generation::generate_test_key_pair;
+    use chrono::Utc;
+
+    #[test]
+    fn test_key_labels_are_capped() {
+        let keys: Vec<KeyInfo> = (0..3u64)
+            .map(|i| {
+                let mut key_pair = generate_test_key_pair("Metrics Key").unwrap();
+                key_pair.usage.sign_count = i;
+                key_pair.usage.verify_count = 1;
+                KeyInfo::new(key_pair, Utc::now())
+            })
+            .collect();
+
+        let rendered = render_metrics(&keys, &ServiceMetrics::default(), 1);
+        assert!(rendered.contains(&format!("inkan_key_signatures_total{{key_id=\"{}\"}} 2", keys[2].id)));
+        assert!(rendered.contains("inkan_key_signatures_total{key_id=\"other\"} 1"));
+        assert!(rendered.contains("inkan_key_verifications_total{key_id=\"other\"} 2"));
+        assert!(!rendered.contains(&keys[0].id.to_string()));
+
+        let uncapped = render_metrics(&keys, &ServiceMetrics::default(), 10);
+        assert!(!uncapped.contains("other"));
+    }
+}
@@ -0,0 +1,251 @@
+//! minisign / signify compatible signature and public key framing
+//!
+//! Signatures use minisign's prehashed `ED` algorithm (Ed25519 over the BLAKE2b-512 digest of
+//! the file) with a global signature over the trusted comment. The 8-byte key id is derived
+//! from the stored public key so the same key always presents the same id.
+
+use crate::models::KeyManagementError;
+use base64::Engine;
+use blake2::Blake2b512;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use sha2::{Digest, Sha256};
+
+/// Algorithm tag for public keys
+pub const PUBLIC_KEY_ALGORITHM: &[u8; 2] = b"Ed";
+/// Algorithm tag for legacy signatures over the raw file
+pub const LEGACY_SIGNATURE_ALGORITHM: &[u8; 2] = b"Ed";
+/// Algorithm tag for signatures over the BLAKE2b-512 digest of the file
+pub const PREHASHED_SIGNATURE_ALGORITHM: &[u8; 2] = b"ED";
+
+const UNTRUSTED_COMMENT_PREFIX: &str = "untrusted comment: ";
+const TRUSTED_COMMENT_PREFIX: &str = "trusted comment: ";
+
+/// Derives the minisign key id for a public key (first 8 bytes of its SHA-256)
+pub fn key_id(public_key: &VerifyingKey) -> [u8; 8] {
+    let digest = Sha256::digest(public_key.as_bytes());
+    let mut id = [0u8; 8];
+    id.copy_from_slice(&digest[..8]);
+    id
+}
+
+/// Formats a key id the way minisign displays it (little-endian u64, uppercase hex)
+pub fn format_key_id(key_id: &[u8; 8]) -> String {
+    format!("{:016X}", u64::from_le_bytes(*key_id))
+}
+
+/// Renders a minisign public key file for the given key
+pub fn encode_public_key(public_key: &VerifyingKey) -> String {
+    let id = key_id(public_key);
+    let mut blob = Vec::with_capacity(42);
+    blob.extend_from_slice(PUBLIC_KEY_ALGORITHM);
+    blob.extend_from_slice(&id);
+    blob.extend_from_slice(public_key.as_bytes());
+
+    format!(
+        "{}minisign public key {}\n{}\n",
+        UNTRUSTED_COMMENT_PREFIX,
+        format_key_id(&id),
+        base64::engine::general_purpose::STANDARD.encode(blob)
+    )
+}
+
+/// Parses a minisign public key, given either as a full file or as its base64 line
+pub fn parse_public_key(input: &str) -> Result<(VerifyingKey, [u8; 8]), KeyManagementError> {
+    let line = input
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with("untrusted comment:"))
+        .ok_or_else(|| KeyManagementError::InvalidKeyFormat("Empty minisign public key".to_string()))?;
+
+    let blob = base64::engine::general_purpose::STANDARD.decode(line)
+        .map_err(|_| KeyManagementError::InvalidKeyFormat("Invalid minisign public key encoding".to_string()))?;
+    if blob.len() != 42 || &blob[..2] != PUBLIC_KEY_ALGORITHM {
+        return Err(KeyManagementError::InvalidKeyFormat("Unsupported minisign public key".to_string()));
+    }
+
+    let mut id = [0u8; 8];
+    id.copy_from_slice(&blob[2..10]);
+    let key_bytes: [u8; 32] = blob[10..].try_into()
+        .map_err(|_| KeyManagementError::InvalidKeyFormat("Invalid minisign public key length".to_string()))?;
+    let public_key = VerifyingKey::from_bytes(&key_bytes)
+        .map_err(|_| KeyManagementError::InvalidKeyFormat("Invalid Ed25519 public key".to_string()))?;
+
+    Ok((public_key, id))
+}
+
+/// Returns true when the input is framed like a minisign public key
+pub fn is_minisign_public_key(input: &str) -> bool {
+    let trimmed = input.trim_start();
+    if trimmed.starts_with("untrusted comment:") {
+        return true;
+    }
+    base64::engine::general_purpose::STANDARD.decode(trimmed.trim())
+        .map(|blob| blob.len() == 42 && &blob[..2] == PUBLIC_KEY_ALGORITHM)
+        .unwrap_or(false)
+}
+
+/// Returns true when the input is framed like a minisign signature file
+pub fn is_minisign_signature(input: &str) -> bool {
+    input.trim_start().starts_with("untrusted comment:")
+}
+
+/// Produces a minisign signature file over `content`
+pub fn sign(signing_key: &SigningKey, content: &[u8], trusted_comment: &str) -> String {
+    let id = key_id(&signing_key.verifying_key());
+    let signature = signing_key.sign(&Blake2b512::digest(content));
+
+    let mut sig_blob = Vec::with_capacity(74);
+    sig_blob.extend_from_slice(PREHASHED_SIGNATURE_ALGORITHM);
+    sig_blob.extend_from_slice(&id);
+    sig_blob.extend_from_slice(&signature.to_bytes());
+
+    // The global signature authenticates the trusted comment together with the signature
+    let mut global_message = signature.to_bytes().to_vec();
+    global_message.extend_from_slice(trusted_comment.as_bytes());
+    let global_signature = signing_key.sign(&global_message);
+
+    let engine = &base64::engine::general_purpose::STANDARD;
+    format!(
+        "{}signature from inkan secret key {}\n{}\n{}{}\n{}\n",
+        UNTRUSTED_COMMENT_PREFIX,
+        format_key_id(&id),
+        engine.encode(sig_blob),
+        TRUSTED_COMMENT_PREFIX,
+        trusted_comment,
+        engine.encode(global_signature.to_bytes())
+    )
+}
+
+/// Verifies a minisign signature file over `content`
+///
+/// When `expected_key_id` is given (the public key came in minisign framing) the signature's
+/// key id must match it. Both the legacy `Ed` and prehashed `ED` algorithms are accepted, and
+/// the trusted comment must be covered by the global signature.
+pub fn verify(
+    public_key: &VerifyingKey,
+    expected_key_id: Option<[u8; 8]>,
+    content: &[u8],
+    signature_file: &str,
+) -> Result<bool, KeyManagementError> {
+    let malformed = || KeyManagementError::InvalidKeyFormat("Malformed minisign signature".to_string());
+
+    let mut lines = signature_file.lines().map(|line| line.trim_end_matches('\r'));
+    let untrusted = lines.next().ok_or_else(malformed)?;
+    if !untrusted.starts_with("untrusted comment:") {
+        return Err(malformed());
+    }
+    let sig_line = lines.next().ok_or_else(malformed)?;
+    let trusted_comment = lines.next()
+        .and_then(|line| line.strip_prefix(TRUSTED_COMMENT_PREFIX))
+        .ok_or_else(malformed)?;
+    let global_line = lines.next().ok_or_else(malformed)?;
+
+    let engine = &base64::engine::general_purpose::STANDARD;
+    let sig_blob = engine.decode(sig_line.trim()).map_err(|_| malformed())?;
+    if sig_blob.len() != 74 {
+        return Err(malformed());
+    }
+    let global_bytes: [u8; 64] = engine.decode(global_line.trim())
+        .map_err(|_| malformed())?
+        .try_into()
+        .map_err(|_| malformed())?;
+
+    let algorithm = &sig_blob[..2];
+    let signature_bytes: [u8; 64] = sig_blob[10..].try_into().map_err(|_| malformed())?;
+    if let Some(expected) = expected_key_id {
+        if sig_blob[2..10] != expected {
+            return Ok(false);
+        }
+    }
+
+    let signature = Signature::from_bytes(&signature_bytes);
+    let file_valid = if algorithm == PREHASHED_SIGNATURE_ALGORITHM {
+        public_key.verify(&Blake2b512::digest(content), &signature).is_ok()
+    } else if algorithm == LEGACY_SIGNATURE_ALGORITHM {
+        public_key.verify(content, &signature).is_ok()
+    } else {
+        return Err(KeyManagementError::InvalidKeyFormat("Unsupported minisign signature algorithm".to_string()));
+    };
+
+    let mut global_message = signature_bytes.to_vec();
+    global_message.extend_from_slice(trusted_comment.as_bytes());
+    let global_valid = public_key
+        .verify(&global_message, &Signature::from_bytes(&global_bytes))
+        .is_ok();
+
+    Ok(file_valid && global_valid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand_core::OsRng;
+
+    #[test]
+    fn test_output_verifies_with_reference_implementation() {
+        let signing_key = SigningKey::generate(&mut OsRng);
+        let content = b"release-1.2.3.tar.gz contents";
+
+        let public_key_file = encode_public_key(&signing_key.verifying_key());
+        let signature_file = sign(&signing_key, content, "timestamp:1700000000\tfile:release-1.2.3.tar.gz");
+
+        let public_key = minisign_verify::PublicKey::decode(&public_key_file).unwrap();
+        let signature = minisign_verify::Signature::decode(&signature_file).unwrap();
+        assert_eq!(signature.trusted_comment(), "timestamp:1700000000\tfile:release-1.2.3.tar.gz");
+        public_key.verify(content, &signature, false).unwrap();
+        assert!(public_key.verify(b"tampered contents", &signature, false).is_err());
+    }
+
+    #[test]
+    fn test_round_trip_and_tampering() {
+        let signing_key = SigningKey::generate(&mut OsRng);
+        let content = b"artifact";
+        let (public_key, id) = parse_public_key(&encode_public_key(&signing_key.verifying_key())).unwrap();
+        assert_eq!(public_key, signing_key.verifying_key());
+        assert_eq!(id, key_id(&public_key));
+
+        let signature_file = sign(&signing_key, content, "timestamp:1");
+        assert!(verify(&public_key, Some(id), content, &signature_file).unwrap());
+        assert!(!verify(&public_key, Some(id), b"other", &signature_file).unwrap());
+
+        // Altering the trusted comment breaks the global signature
+        let tampered = signature_file.replace("timestamp:1", "timestamp:2");
+        assert!(!verify(&public_key, Some(id), content, &tampered).unwrap());
+
+        // A different key id is rejected when the public key carries one
+        assert!(!verify(&public_key, Some([0u8; 8]), content, &signature_file).unwrap());
+    }
+
+    #[test]
+    fn test_verifies_legacy_signatures() {
+        let signing_key = SigningKey::generate(&mut OsRng);
+        let content = b"legacy file";
+        let id = key_id(&signing_key.verifying_key());
+
+        let signature = signing_key.sign(content);
+        let mut sig_blob = LEGACY_SIGNATURE_ALGORITHM.to_vec();
+        sig_blob.extend_from_slice(&id);
+        sig_blob.extend_from_slice(&signature.to_bytes());
+        let mut global_message = signature.to_bytes().to_vec();
+        global_message.extend_from_slice(b"legacy");
+        let global = signing_key.sign(&global_message);
+
+        let engine = &base64::engine::general_purpose::STANDARD;
+        let signature_file = format!(
+            "untrusted comment: legacy\n{}\ntrusted comment: legacy\n{}\n",
+            engine.encode(sig_blob),
+            engine.encode(global.to_bytes())
+        );
+        assert!(verify(&signing_key.verifying_key(), None, content, &signature_file).unwrap());
+    }
+
+    #[test]
+    fn test_malformed_signatures_error_cleanly() {
+        let signing_key = SigningKey::generate(&mut OsRng);
+        let public_key = signing_key.verifying_key();
+        for input in ["", "untrusted comment: x", "untrusted comment: x\nAAAA\ntrusted comment: y\nAAAA"] {
+            assert!(verify(&public_key, None, b"x", input).is_err());
+        }
+        assert!(parse_public_key("untrusted comment: x\nnot base64!").is_err());
+    }
+}
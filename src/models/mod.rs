@@ -1,102 +1,922 @@
-use chrono::{DateTime, Utc};
+use crate::build_info::SignerInfo;
+use crate::bundle::{Bundle, BundleBody};
+use crate::capabilities::{About, HashAlgorithm, KeyCapabilities, ServiceCapabilities};
+use crate::capacity::CapacityStatus;
+use crate::certification::Certification;
+use crate::config::{KdfAlgorithm, KdfParams};
+use crate::delegation::DelegationInfo;
+use crate::integrity::KeystoreLoadSummary;
+use crate::kdf_stats::KdfReportGroup;
+use crate::lifecycle::{Lifecycle, LifecycleEvent};
+use crate::reencryption::{EnvelopeFinding, EnvelopeRevision, EnvelopeWeakness};
+use crate::entropy::EntropyStatus;
+use crate::key_formats::PublicKeyInputFormat;
+use crate::key_status::KeyStatus;
+use crate::key_storage::PersistenceStatus;
+use crate::secret::SecretString;
+use crate::self_test::SelfTestReport;
+use crate::storage_breaker::BreakerStatus;
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
 use uuid::Uuid;
 
 /// Key pair information
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// Neither serializable nor printable with its secrets; the keystore writes it through its own
+/// representation in `crate::key_storage`, and the API returns [`KeyInfo`].
+#[derive(Clone)]
 pub struct KeyPair {
     pub id: Uuid,
     pub name: String,
     pub description: Option<String>,
     pub public_key: String, // Base64 encoded
-    pub private_key: String, // Base64 encoded (encrypted in production)
-    pub salt: Option<String>, // Salt for encrypted private keys
+    pub private_key: SecretString, // Base64 encoded (encrypted in production)
+    pub salt: Option<SecretString>, // Salt for encrypted private keys
     pub created_at: DateTime<Utc>,
     pub last_used: Option<DateTime<Utc>>,
     pub expires_at: Option<DateTime<Utc>>,
-    pub is_active: bool,
+    pub lifecycle: Lifecycle, // Stored lifecycle state; change it through KeyPair::transition
     pub tags: Vec<String>,
     pub key_type: KeyType,
     pub key_strength: KeyStrength,
+    pub kdf: Option<KdfParams>, // Parameters the private key was encrypted with; absent means legacy PBKDF2
+    pub fingerprint: Option<String>, // Fingerprint of the public key, see utils::public_key_to_fingerprint
+    pub revocation_scheduled_at: Option<DateTime<Utc>>, // Pending revocation executed by the sweeper
+    pub usage: KeyUsage, // Signing and verification counters
+    pub notified_thresholds: BTreeSet<u32>, // Expiry notification thresholds, in days, already sent
+    pub hsm: Option<HsmKeyRef>, // Set for keys held in an HSM; private_key is then empty
+    pub allowed_contexts: Option<Vec<String>>, // Signing contexts the key may sign for; absent allows any
+    pub metadata_history: Vec<MetadataRevision>, // Earlier name, description and tags, oldest first
+    pub environment: String, // Deployment environment the key signs in, see crate::environment
+    pub lifecycle_history: Vec<LifecycleEvent>, // Lifecycle moves, oldest first
+    pub envelope_history: Vec<EnvelopeRevision>, // Rewraps of the private key, oldest first
+    pub exportable: bool, // Whether the private key may leave the service; once cleared it stays cleared
+    pub default_output_format: Option<SignatureOutputFormat>, // Used by `/sign` when the request names no format
+    pub default_hash_algorithm: Option<HashAlgorithm>, // Used by `/sign` when the request names no digest and the format takes this one
+    pub default_encoding: Option<SignatureEncoding>, // Used for raw signatures when the request names no encoding
 }
 
-/// Type of cryptographic key
+/// A key's name, description and tags as they were before a change made by the service
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MetadataRevision {
+    pub changed_at: DateTime<Utc>,
+    pub reason: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+}
+
+impl std::fmt::Debug for KeyPair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KeyPair")
+            .field("id", &self.id)
+            .field("name", &self.name)
+            .field("key_type", &self.key_type)
+            .field("lifecycle", &self.lifecycle)
+            .finish_non_exhaustive()
+    }
+}
+
+impl KeyPair {
+    /// The private key and its salt, for loading the signing key
+    ///
+    /// Both reads are traced with the caller's location, which a closure would lose.
+    #[track_caller]
+    pub fn signing_secrets(&self) -> (&str, Option<&str>) {
+        match &self.salt {
+            Some(salt) => (self.private_key.expose_for_signing(), Some(salt.expose_for_signing())),
+            None => (self.private_key.expose_for_signing(), None),
+        }
+    }
+
+    /// The key's lifecycle state at `now`
+    pub fn state(&self, now: DateTime<Utc>) -> KeyState {
+        match self.lifecycle {
+            Lifecycle::Deleted => KeyState::Deleted,
+            Lifecycle::Revoked => KeyState::Revoked,
+            _ if self.revocation_scheduled_at.is_some_and(|at| now >= at) => KeyState::Revoked,
+            Lifecycle::Suspended => KeyState::Suspended,
+            Lifecycle::Active if self.expires_at.is_some_and(|expires_at| now > expires_at) => KeyState::Expired,
+            Lifecycle::Active if self.revocation_scheduled_at.is_some() => KeyState::ScheduledRevocation,
+            Lifecycle::Active => KeyState::Active,
+        }
+    }
+}
+
+/// Lifecycle state of a stored key at a given moment
+///
+/// Derived from the key's stored [`Lifecycle`] and its dates, so every key is in exactly one
+/// state: deletion takes precedence, then revocation, then suspension, then expiry. A scheduled
+/// revocation counts as revoked from the moment it is due, even before the sweeper executes it.
+/// See [`crate::lifecycle`] for the moves between states.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyState {
+    #[default]
+    Active,
+    /// Usable until its scheduled revocation takes effect
+    ScheduledRevocation,
+    Expired,
+    /// Disabled until resumed
+    Suspended,
+    Revoked,
+    /// Soft-deleted, and restorable
+    Deleted,
+}
+
+impl KeyState {
+    /// Name of the state as serialized, e.g. `scheduled_revocation`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            KeyState::Active => "active",
+            KeyState::ScheduledRevocation => "scheduled_revocation",
+            KeyState::Expired => "expired",
+            KeyState::Suspended => "suspended",
+            KeyState::Revoked => "revoked",
+            KeyState::Deleted => "deleted",
+        }
+    }
+
+    /// Whether a key in this state may sign
+    pub fn is_usable(self) -> bool {
+        matches!(self, KeyState::Active | KeyState::ScheduledRevocation)
+    }
+
+    /// Refuses use of a key that is expired, suspended, revoked or deleted
+    pub fn check_usable(self, key_id: Uuid) -> Result<(), KeyManagementError> {
+        match self {
+            KeyState::Expired => Err(KeyManagementError::KeyExpired(key_id)),
+            KeyState::Suspended => Err(KeyManagementError::KeySuspended(key_id)),
+            KeyState::Revoked => Err(KeyManagementError::KeyRevoked(key_id)),
+            KeyState::Deleted => Err(KeyManagementError::KeyNotFound(key_id)),
+            KeyState::Active | KeyState::ScheduledRevocation => Ok(()),
+        }
+    }
+}
+
+/// Location of a non-exportable key on an HSM
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(deny_unknown_fields)]
+pub struct HsmKeyRef {
+    pub slot: u64,
+    pub label: String,
+}
+
+/// Signing and verification activity of a key
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct KeyUsage {
+    pub sign_count: u64, // Successful signatures
+    pub verify_count: u64, // Completed key_id-based verifications
+    pub last_sign_at: Option<DateTime<Utc>>,
+    /// Signatures and verifications per UTC day, kept for [`USAGE_HISTORY_DAYS`] for usage reports
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub days: BTreeMap<NaiveDate, DayUsage>,
+}
+
+/// Days of per-day usage a key keeps
+pub const USAGE_HISTORY_DAYS: i64 = 400;
+
+/// A key's activity on one UTC day
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct DayUsage {
+    pub signs: u64,
+    pub verifies: u64,
+}
+
+impl KeyUsage {
+    /// Counts a signature made at `signed_at`
+    pub fn record_sign(&mut self, signed_at: DateTime<Utc>) {
+        self.sign_count += 1;
+        self.last_sign_at = Some(signed_at);
+        self.day(signed_at).signs += 1;
+    }
+
+    /// Counts a verification made at `verified_at`
+    pub fn record_verify(&mut self, verified_at: DateTime<Utc>) {
+        self.verify_count += 1;
+        self.day(verified_at).verifies += 1;
+    }
+
+    /// Adds counts made on `day`, the latest signature among them made at `last_sign_at`
+    pub fn add_day(&mut self, day: NaiveDate, counts: DayUsage, last_sign_at: Option<DateTime<Utc>>) {
+        self.sign_count += counts.signs;
+        self.verify_count += counts.verifies;
+        self.last_sign_at = self.last_sign_at.max(last_sign_at);
+        let entry = self.day_of(day);
+        entry.signs += counts.signs;
+        entry.verifies += counts.verifies;
+    }
+
+    /// The counts of the day of `at`, dropping days older than [`USAGE_HISTORY_DAYS`]
+    fn day(&mut self, at: DateTime<Utc>) -> &mut DayUsage {
+        self.day_of(at.date_naive())
+    }
+
+    fn day_of(&mut self, day: NaiveDate) -> &mut DayUsage {
+        self.days = self.days.split_off(&(day - chrono::Duration::days(USAGE_HISTORY_DAYS)));
+        self.days.entry(day).or_default()
+    }
+}
+
+/// Type of cryptographic key
+///
+/// Names are matched case-insensitively when read; a name this version does not know, such as a
+/// type written by a newer release, reads as `Unknown` rather than failing the whole record.
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
 pub enum KeyType {
+    #[default]
     Ed25519,
     Ed25519Encrypted,
-    #[serde(other)]
-    Unknown,
+    Ed25519Hsm, // Private key held in an HSM
+    Ed25519Ephemeral, // Single-use key kept as a revoked tombstone; its private key was discarded
+    Unknown, // Written by a newer release; can be listed but not used until the record is migrated
+}
+
+impl KeyType {
+    pub const ALL: [KeyType; 5] = [KeyType::Ed25519, KeyType::Ed25519Encrypted, KeyType::Ed25519Hsm, KeyType::Ed25519Ephemeral, KeyType::Unknown];
+
+    /// The name the type is serialized as
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeyType::Ed25519 => "Ed25519",
+            KeyType::Ed25519Encrypted => "Ed25519Encrypted",
+            KeyType::Ed25519Hsm => "Ed25519Hsm",
+            KeyType::Ed25519Ephemeral => "Ed25519Ephemeral",
+            KeyType::Unknown => "Unknown",
+        }
+    }
+
+    /// The type named `name`, ignoring case; `None` for a name this version does not know
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|key_type| key_type.as_str().eq_ignore_ascii_case(name.trim()))
+    }
+}
+
+impl<'de> Deserialize<'de> for KeyType {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Ok(Self::from_name(&name).unwrap_or(KeyType::Unknown))
+    }
 }
 
 /// Cryptographic strength of the key
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+///
+/// Read like [`KeyType`]: case-insensitively, with unrecognized names as `Unknown`.
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
 pub enum KeyStrength {
+    #[default]
     Standard,    // 256-bit
     High,        // 384-bit
     Ultra,       // 512-bit
-    #[serde(other)]
     Unknown,
 }
 
+impl KeyStrength {
+    pub const ALL: [KeyStrength; 4] = [KeyStrength::Standard, KeyStrength::High, KeyStrength::Ultra, KeyStrength::Unknown];
+
+    /// The name the strength is serialized as
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeyStrength::Standard => "Standard",
+            KeyStrength::High => "High",
+            KeyStrength::Ultra => "Ultra",
+            KeyStrength::Unknown => "Unknown",
+        }
+    }
+
+    /// The strength named `name`, ignoring case; `None` for a name this version does not know
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|strength| strength.as_str().eq_ignore_ascii_case(name.trim()))
+    }
+}
+
+impl<'de> Deserialize<'de> for KeyStrength {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Ok(Self::from_name(&name).unwrap_or(KeyStrength::Unknown))
+    }
+}
+
 /// Request to generate a new key pair
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct GenerateKeyRequest {
     pub name: String,
     pub description: Option<String>,
     pub password: Option<String>, // For encrypting private key
+    #[serde(default, with = "crate::timestamps::option", alias = "expiresAt")]
     pub expires_at: Option<DateTime<Utc>>, // Key expiration date
     pub tags: Option<Vec<String>>, // Key tags for organization
+    #[serde(alias = "keyStrength")]
     pub key_strength: Option<KeyStrength>, // Desired key strength
+    #[serde(default)]
+    pub hsm: Option<HsmKeyRef>, // Generate on the configured HSM instead of in software
+    #[serde(default, alias = "generatePassword")]
+    pub generate_password: bool, // Encrypt with a password the service generates and returns once
+    #[serde(default)]
+    pub template: Option<String>, // Key template whose defaults the request is merged over
+    #[serde(default)]
+    pub environment: Option<String>, // Deployment environment; defaults to the service's
+    #[serde(default)]
+    pub fast: bool, // Take a pre-generated key from the key pool when one is ready
+    #[serde(default)]
+    pub exportable: Option<bool>, // false keeps the private key from ever leaving the service; default true
+    #[serde(default, alias = "defaultOutputFormat")]
+    pub default_output_format: Option<SignatureOutputFormat>, // Output format `/sign` uses when the request names none
+    #[serde(default, alias = "defaultHashAlgorithm")]
+    pub default_hash_algorithm: Option<HashAlgorithm>, // Digest `/sign` uses when the request names none
+    #[serde(default, alias = "defaultEncoding")]
+    pub default_encoding: Option<SignatureEncoding>, // Raw signature encoding `/sign` uses when the request names none
 }
 
 /// Response for key generation
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct GenerateKeyResponse {
     pub success: bool,
-    pub key_pair: Option<KeyPair>,
+    pub key_pair: Option<KeyInfo>, // Public information of the new key; its private key is never returned
     pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<ErrorCode>, // Set on failures
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>, // Structured context for the failure
     pub warnings: Vec<String>, // Any warnings about the generated key
+    pub dry_run: bool, // True when the request was only validated and no key was created
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_type: Option<KeyType>, // Key type the request produces
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_strength: Option<KeyStrength>, // Key strength the request produces
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>, // Effective expiry after the lifetime policy was applied
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expiry_source: Option<ExpirySource>, // How the effective expiry was derived
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>, // Validation failures, one per offending field
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generated_password: Option<String>, // Set for generate_password; returned only in this response
+}
+
+/// How a generated key's expiry was derived from the request and lifetime policy
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExpirySource {
+    /// The requested expiry was used as-is
+    Requested,
+    /// No expiry was requested, so the default lifetime was applied
+    Defaulted,
+    /// The requested expiry exceeded the maximum lifetime and was shortened
+    Clamped,
+}
+
+/// Validation failure tied to a single request field
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &str, message: impl Into<String>) -> Self {
+        Self {
+            field: field.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+/// Advisory attached to a successful response, such as a key that expires soon
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ApiWarning {
+    pub code: WarningCode,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>, // Request field the warning concerns, if any
+}
+
+impl ApiWarning {
+    pub fn new(code: WarningCode, message: impl Into<String>) -> Self {
+        Self { code, message: message.into(), field: None }
+    }
+
+    /// The warning, tied to a request field
+    pub fn for_field(mut self, field: &str) -> Self {
+        self.field = Some(field.to_string());
+        self
+    }
+}
+
+/// How `document_content` is interpreted before hashing
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub enum DocumentContentType {
+    /// Content is hashed byte-for-byte as UTF-8 text
+    #[default]
+    #[serde(rename = "text")]
+    Text,
+    /// Content is parsed as JSON and hashed over its RFC 8785 canonical form
+    #[serde(rename = "json-jcs")]
+    JsonJcs,
+}
+
+/// Framing of the signature returned from `/sign`
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SignatureOutputFormat {
+    /// Base64 encoded raw Ed25519 signature
+    #[default]
+    Raw,
+    /// minisign signature file (untrusted comment, signature, trusted comment, global signature)
+    Minisign,
+    /// OpenSSH `-----BEGIN SSH SIGNATURE-----` block, as produced by `ssh-keygen -Y sign`
+    Sshsig,
+}
+
+impl SignatureOutputFormat {
+    /// Name of the format as serialized, e.g. `sshsig`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SignatureOutputFormat::Raw => "raw",
+            SignatureOutputFormat::Minisign => "minisign",
+            SignatureOutputFormat::Sshsig => "sshsig",
+        }
+    }
+}
+
+/// Text encoding of a raw Ed25519 signature
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SignatureEncoding {
+    /// Standard base64 with padding
+    #[default]
+    Base64,
+    /// URL-safe base64 without padding, as used in JWTs
+    Base64Url,
+    /// Lowercase hex
+    Hex,
+}
+
+impl SignatureEncoding {
+    /// Name of the encoding as serialized, e.g. `base64url`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SignatureEncoding::Base64 => "base64",
+            SignatureEncoding::Base64Url => "base64url",
+            SignatureEncoding::Hex => "hex",
+        }
+    }
+}
+
+/// What a raw Ed25519 signature over a document covers
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum MessageEncoding {
+    /// The 32 raw bytes of the SHA-256 digest, with any context, signing time or validity window
+    /// bound in
+    #[default]
+    HashRawBytes,
+    /// The 64 lowercase hex characters of the SHA-256 digest, as `sha256sum` prints it
+    HashHexBytes,
+    /// The document bytes themselves, as `openssl pkeyutl -rawin` and libsodium's
+    /// `crypto_sign_detached` sign them
+    RawMessage,
+}
+
+impl MessageEncoding {
+    /// Name of the encoding as serialized, e.g. `raw-message`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MessageEncoding::HashRawBytes => "hash-raw-bytes",
+            MessageEncoding::HashHexBytes => "hash-hex-bytes",
+            MessageEncoding::RawMessage => "raw-message",
+        }
+    }
+
+    pub fn is_default(&self) -> bool {
+        *self == MessageEncoding::default()
+    }
+}
+
+/// A SHA-256 document hash
+///
+/// Parsed once where a hash enters the service and passed around as bytes; serialized as
+/// lowercase hex and read from hex in either case.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DocumentHash(pub [u8; 32]);
+
+impl DocumentHash {
+    /// SHA-256 of `content`
+    pub fn digest(content: &[u8]) -> Self {
+        use sha2::Digest;
+        Self(sha2::Sha256::digest(content).into())
+    }
+
+    /// Parses 64 hex characters
+    pub fn from_hex(hash: &str) -> Result<Self, KeyManagementError> {
+        let mut bytes = [0u8; 32];
+        hex::decode_to_slice(hash, &mut bytes)
+            .map_err(|_| KeyManagementError::InvalidRequest("Invalid document hash format".to_string()))?;
+        Ok(Self(bytes))
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for DocumentHash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut hex = [0u8; 64];
+        hex::encode_to_slice(self.0, &mut hex).map_err(|_| std::fmt::Error)?;
+        f.write_str(std::str::from_utf8(&hex).map_err(|_| std::fmt::Error)?)
+    }
+}
+
+impl std::str::FromStr for DocumentHash {
+    type Err = KeyManagementError;
+
+    fn from_str(hash: &str) -> Result<Self, Self::Err> {
+        Self::from_hex(hash)
+    }
+}
+
+impl Serialize for DocumentHash {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for DocumentHash {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hash = String::deserialize(deserializer)?;
+        Self::from_hex(&hash).map_err(serde::de::Error::custom)
+    }
+}
+
+/// A raw Ed25519 signature
+///
+/// Serialized as standard base64, the form receipts and bundles keep; other encodings are
+/// produced with [`SignatureBytes::encode`] only where a response asks for them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SignatureBytes(pub [u8; 64]);
+
+impl SignatureBytes {
+    /// Decodes a signature in `encoding`, or in whichever encoding it is in when `None`
+    pub fn decode(signature: &str, encoding: Option<SignatureEncoding>) -> Result<Self, KeyManagementError> {
+        crate::key_verification::decode_signature(signature, encoding).map(Self)
+    }
+
+    /// The signature in `encoding`
+    pub fn encode(&self, encoding: SignatureEncoding) -> String {
+        crate::key_verification::encode_signature(&self.0, encoding)
+    }
+
+    pub fn to_signature(&self) -> ed25519_dalek::Signature {
+        ed25519_dalek::Signature::from_bytes(&self.0)
+    }
+}
+
+impl From<ed25519_dalek::Signature> for SignatureBytes {
+    fn from(signature: ed25519_dalek::Signature) -> Self {
+        Self(signature.to_bytes())
+    }
+}
+
+impl std::fmt::Display for SignatureBytes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.encode(SignatureEncoding::Base64))
+    }
+}
+
+impl Serialize for SignatureBytes {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for SignatureBytes {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let signature = String::deserialize(deserializer)?;
+        Self::decode(&signature, Some(SignatureEncoding::Base64)).map_err(serde::de::Error::custom)
+    }
 }
 
 /// Request to sign a document
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct SignDocumentRequest {
+    #[serde(alias = "keyId")]
     pub key_id: Uuid,
+    #[serde(alias = "documentHash")]
     pub document_hash: Option<String>, // SHA256 hash of the document (optional if document_content provided)
     pub password: Option<String>, // If private key is encrypted
+    #[serde(alias = "documentContent")]
     pub document_content: Option<String>, // Alternative: provide content directly
+    #[serde(default, with = "crate::timestamps::option", alias = "validUntil")]
+    pub valid_until: Option<DateTime<Utc>>, // Bound into the signature when present
+    #[serde(default, alias = "contentType")]
+    pub content_type: DocumentContentType,
+    #[serde(default, alias = "outputFormat", skip_serializing_if = "Option::is_none")]
+    pub output_format: Option<SignatureOutputFormat>, // Defaults to the key's default_output_format, then raw
+    #[serde(default, alias = "hashAlgorithm", skip_serializing_if = "Option::is_none")]
+    pub hash_algorithm: Option<HashAlgorithm>, // Digest of sshsig output; defaults to the key's, then the format's own
+    #[serde(default)]
+    pub namespace: Option<String>, // sshsig namespace, defaults to "file"
+    #[serde(default)]
+    pub bundle: bool, // Return a portable verification bundle with the signature
+    #[serde(default)]
+    pub context: Option<String>, // Domain-separation context, e.g. "invoice"; bound into the signature
+    #[serde(default, alias = "bindTimestamp")]
+    pub bind_timestamp: bool, // Bind signing_time into the signature; it is then needed to verify
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<SignatureEncoding>, // Encoding of the returned raw signature; defaults to the key's, then base64
+    #[serde(default, alias = "messageEncoding")]
+    pub message_encoding: MessageEncoding, // What the raw signature covers
 }
 
-/// Response for document signing
+/// Request to sign with a freshly generated single-use key
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EphemeralSignRequest {
+    #[serde(alias = "documentHash")]
+    pub document_hash: Option<String>,
+    #[serde(alias = "documentContent")]
+    pub document_content: Option<String>,
+    #[serde(default, alias = "contentType")]
+    pub content_type: DocumentContentType,
+    #[serde(default, with = "crate::timestamps::option", alias = "validUntil")]
+    pub valid_until: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub context: Option<String>,
+    #[serde(default, alias = "bindTimestamp")]
+    pub bind_timestamp: bool,
+    #[serde(default)]
+    pub bundle: bool, // Return the verification bundle with the signature
+    #[serde(default)]
+    pub tombstone: bool, // Keep the public key as a revoked keystore entry; otherwise nothing is stored
+    #[serde(default)]
+    pub name: Option<String>, // Name of the tombstone entry
+}
+
+/// Response for signing with a single-use key
 #[derive(Debug, Serialize)]
+pub struct EphemeralSignResponse {
+    pub success: bool,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<ErrorCode>, // Set on failures
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>, // Structured context for the failure
+    pub signature: Option<String>,
+    pub key_id: Option<Uuid>, // Names the tombstone entry when one was kept
+    pub public_key: Option<String>,
+    pub key_fingerprint: Option<String>,
+    pub document_hash: Option<String>,
+    pub signing_time: Option<DateTime<Utc>>,
+    pub valid_until: Option<DateTime<Utc>>,
+    pub context: Option<String>,
+    pub timestamp_bound: bool,
+    pub signature_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bundle: Option<Bundle>,
+    pub tombstone: bool, // A revoked entry without private key was stored
+}
+
+/// Response for document signing
+#[derive(Debug, Serialize, Deserialize)]
 pub struct SignDocumentResponse {
     pub success: bool,
     pub signature: Option<String>,
     pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<ErrorCode>, // Set on failures
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>, // Structured context for the failure
     pub key_id: Option<Uuid>,
     pub document_hash: Option<String>, // The hash that was signed
     pub signing_time: Option<DateTime<Utc>>,
+    pub valid_until: Option<DateTime<Utc>>, // End of the signature validity window, if any
+    pub canonical_hash: Option<String>, // Hash of the canonical form for json-jcs content
+    pub output_format: SignatureOutputFormat,
+    pub signature_encoding: SignatureEncoding,
+    #[serde(default)]
+    pub message_encoding: MessageEncoding, // What the raw signature covers
+    pub signature_id: Option<Uuid>, // Derived from key, document hash, and scheme; also the receipt id
+    pub bundle: Option<Bundle>,
+    pub context: Option<String>, // Signing context bound into the signature, if any
+    pub duplicate: bool, // The signature was already recorded; its existing receipt is returned
+    pub timestamp_bound: bool, // signing_time is bound into the signature
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<ApiWarning>, // Advisories about the key or the request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timings: Option<SignTimings>, // Set when debug timings were requested and are enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signer_info: Option<SignerInfo>, // Build of the service that made the signature
+}
+
+/// Time spent serving a signing request, in milliseconds
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SignTimings {
+    pub kdf: Option<KdfAlgorithm>, // KDF that unlocked the key; absent for unencrypted and HSM keys
+    pub kdf_derivation_ms: Option<f64>,
+    pub key_decryption_ms: Option<f64>, // Includes the derivation
+    pub total_ms: f64,
+}
+
+/// Response for the admin overview
+#[derive(Debug, Serialize)]
+pub struct AdminOverviewResponse {
+    pub success: bool,
+    #[serde(flatten)]
+    pub overview: crate::overview::AdminOverview,
+}
+
+/// Response for a usage report
+#[derive(Debug, Serialize)]
+pub struct UsageReportResponse {
+    pub success: bool,
+    #[serde(flatten)]
+    pub report: crate::usage_report::UsageReport,
+}
+
+/// Response for a page of replayed operation events
+#[derive(Debug, Serialize)]
+pub struct EventReplayResponse {
+    pub success: bool,
+    #[serde(flatten)]
+    pub replay: crate::event_log::EventReplay,
+}
+
+/// Recorded raw signature, looked up by its signature id
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignatureRecordResponse {
+    pub success: bool,
+    pub message: String,
+    #[serde(flatten)]
+    pub record: BundleBody,
+}
+
+/// Request to publish a signature as a verification link
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CreateShareRequest {
+    #[serde(default, alias = "signatureId")]
+    pub signature_id: Option<Uuid>, // Recorded signature to publish
+    #[serde(default)]
+    pub bundle: Option<Bundle>, // Alternative: a complete verification bundle
+    #[serde(default, alias = "ttlSecs")]
+    pub ttl_secs: Option<u32>, // Lifetime of the link; defaults to INKAN_SHARE_TTL_SECS
+}
+
+/// Response for a new verification link
+#[derive(Debug, Serialize)]
+pub struct CreateShareResponse {
+    pub success: bool,
+    pub message: String,
+    pub token: String, // Only returned here; the service keeps just its hash
+    pub signature_id: Uuid,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Request to delegate signing with a key to a token
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DelegateKeyRequest {
+    #[serde(alias = "ttlSecs")]
+    pub ttl_secs: u32, // Lifetime of the token; at most INKAN_DELEGATION_MAX_TTL_SECS
+    #[serde(default, alias = "maxUses")]
+    pub max_uses: Option<u32>, // Signatures the token may make; defaults to 1
+    #[serde(default)]
+    pub contexts: Option<Vec<String>>, // Signing contexts the token is restricted to
+    #[serde(default)]
+    pub password: Option<String>, // The key's password, required for an encrypted key
+}
+
+/// Response for a new delegation
+#[derive(Debug, Serialize)]
+pub struct DelegateKeyResponse {
+    pub success: bool,
+    pub message: String,
+    pub token: String, // Only returned here; the service keeps just its hash
+    pub delegation: DelegationInfo,
+}
+
+/// Outstanding delegations of a key
+#[derive(Debug, Serialize)]
+pub struct ListDelegationsResponse {
+    pub success: bool,
+    pub message: String,
+    pub key_id: Uuid,
+    pub delegations: Vec<DelegationInfo>,
+}
+
+/// Public data behind a verification link
+#[derive(Debug, Serialize)]
+pub struct SharedSignatureResponse {
+    pub success: bool,
+    pub message: String,
+    #[serde(flatten)]
+    pub record: BundleBody,
+    pub key_name: Option<String>, // Name of the signing key, if this service holds it
+    pub link_expires_at: DateTime<Utc>,
+}
+
+/// Document to check against a verification link
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CheckShareRequest {
+    #[serde(alias = "documentHash")]
+    pub document_hash: Option<String>, // Hex SHA-256 of the document
+    #[serde(alias = "documentContent")]
+    pub document_content: Option<String>, // Alternative: the document itself
+}
+
+/// Outcome of checking a document against a verification link
+#[derive(Debug, Serialize)]
+pub struct CheckShareResponse {
+    pub success: bool,
+    pub message: String,
+    pub matches: bool, // The document is the one signed and every signature in the receipt verifies
+    pub document_matches: bool,
+    pub signature_valid: bool,
+    pub expected_hash: String,
 }
 
 /// Request to verify a signature
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct VerifySignatureRequest {
-    pub public_key: String, // Base64 encoded public key
+    #[serde(default, alias = "publicKey")]
+    pub public_key: String, // Raw base64, PEM, OpenSSH or JWK public key (may be omitted when key_id is given)
+    #[serde(alias = "documentHash")]
     pub document_hash: Option<String>, // SHA256 hash of the document (optional if document_content provided)
-    pub signature: String, // Base64 encoded signature
+    pub signature: String, // Base64, base64url or hex encoded signature
+    #[serde(alias = "documentContent")]
     pub document_content: Option<String>, // Alternative: provide content directly
+    #[serde(default, with = "crate::timestamps::option", alias = "validUntil")]
+    pub valid_until: Option<DateTime<Utc>>, // Must match the window the signature was created with
+    #[serde(default, alias = "contentType")]
+    pub content_type: DocumentContentType,
+    #[serde(default)]
+    pub namespace: Option<String>, // sshsig namespace, defaults to "file"
+    #[serde(default, alias = "keyId")]
+    pub key_id: Option<Uuid>, // Verify against a stored key instead of a supplied public key
+    #[serde(default, alias = "includeChain")]
+    pub include_chain: bool, // Return the certification chain of the key_id key
+    #[serde(default)]
+    pub context: Option<String>, // Must match the context the signature was created with
+    #[serde(default, alias = "keyIds")]
+    pub key_ids: Vec<Uuid>, // Stored candidate keys, tried in order before public_keys
+    #[serde(default, alias = "publicKeys")]
+    pub public_keys: Vec<String>, // Candidate public keys, for signers that may have used any of them
+    #[serde(default, with = "crate::timestamps::option", alias = "signingTime")]
+    pub signing_time: Option<DateTime<Utc>>, // Bound signing time, for timestamp-bound signatures
+    #[serde(default, alias = "signatureEncoding")]
+    pub signature_encoding: Option<SignatureEncoding>, // Detected from the signature when absent
+    #[serde(default, alias = "messageEncoding")]
+    pub message_encoding: MessageEncoding, // Must match what the signature was made over; never detected
+}
+
+/// Candidate key that validated a multi-key verification
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MatchedCandidate {
+    pub index: usize, // Position among the candidates, counting key_ids first
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_id: Option<Uuid>, // Set when the candidate came from key_ids
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
 }
 
 /// Response for signature verification
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct VerifySignatureResponse {
     pub success: bool,
-    pub is_valid: bool,
+    pub is_valid: bool, // Cryptographically valid and within its validity window
     pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<ErrorCode>, // Set on failures
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>, // Structured context for the failure
     pub key_info: Option<KeyInfo>,
     pub verification_time: Option<DateTime<Utc>>,
     pub document_hash: Option<String>, // The hash that was verified
+    pub cryptographically_valid: bool, // Signature checks out regardless of the validity window
+    pub expired_signature: bool, // The signature's validity window has passed
+    pub valid_until: Option<DateTime<Utc>>,
+    pub canonical_hash: Option<String>, // Hash of the canonical form for json-jcs content
+    pub context: Option<String>, // Signing context the signature was checked under, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub certification_chain: Option<Vec<Certification>>, // Chain from a root certifier to the key, when requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub matched_candidate: Option<MatchedCandidate>, // Which candidate validated, for key_ids or public_keys requests
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signing_time: Option<DateTime<Utc>>, // Bound signing time the signature was checked under
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<ApiWarning>, // Advisories about the stored key the signature was checked against
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_source: Option<String>, // `remote(<peer>)` when key_id was resolved from a federation peer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote_key: Option<KeyStatus>, // The remote key's status as its peer reported it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key_format: Option<PublicKeyInputFormat>, // Format the supplied public key was recognized in
 }
 
 /// Public key information (safe to share)
@@ -109,57 +929,241 @@ pub struct KeyInfo {
     pub created_at: DateTime<Utc>,
     pub last_used: Option<DateTime<Utc>>,
     pub expires_at: Option<DateTime<Utc>>,
-    pub is_active: bool,
+    pub is_active: bool, // Whether the key may sign, i.e. its state is usable; kept for compatibility
+    #[serde(default)]
+    pub state: KeyState,
     pub tags: Vec<String>,
+    #[serde(default = "crate::environment::unknown_environment")]
+    pub environment: String,
     pub key_type: KeyType,
     pub key_strength: KeyStrength,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revocation_scheduled_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub usage: KeyUsage,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hsm: Option<HsmKeyRef>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_contexts: Option<Vec<String>>,
+    #[serde(default)]
+    pub capabilities: KeyCapabilities, // What `/sign` will accept for this key
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lifecycle_history: Vec<LifecycleEvent>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub envelope_history: Vec<EnvelopeRevision>,
+    #[serde(default = "crate::models::exportable_by_default")]
+    pub exportable: bool, // Whether the private key may leave the service
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_output_format: Option<SignatureOutputFormat>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_hash_algorithm: Option<HashAlgorithm>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_encoding: Option<SignatureEncoding>,
+}
+
+/// Keys are exportable unless generated otherwise
+pub fn exportable_by_default() -> bool {
+    true
+}
+
+impl KeyInfo {
+    /// Public information of a key, with its state evaluated at `now`
+    pub fn new(key_pair: KeyPair, now: DateTime<Utc>) -> Self {
+        Self::from_key_pair(&key_pair, now)
+    }
+
+    /// Public information of a borrowed key; only the public fields are copied
+    ///
+    /// Takes `now` rather than implementing `From<&KeyPair>`, since the state depends on the
+    /// caller's clock.
+    pub fn from_key_pair(key_pair: &KeyPair, now: DateTime<Utc>) -> Self {
+        let state = key_pair.state(now);
+        Self {
+            id: key_pair.id,
+            name: key_pair.name.clone(),
+            description: key_pair.description.clone(),
+            public_key: key_pair.public_key.clone(),
+            created_at: key_pair.created_at,
+            last_used: key_pair.last_used,
+            expires_at: key_pair.expires_at,
+            is_active: state.is_usable(),
+            state,
+            tags: key_pair.tags.clone(),
+            environment: key_pair.environment.clone(),
+            key_type: key_pair.key_type.clone(),
+            key_strength: key_pair.key_strength.clone(),
+            revocation_scheduled_at: key_pair.revocation_scheduled_at,
+            usage: key_pair.usage.clone(),
+            hsm: key_pair.hsm.clone(),
+            allowed_contexts: key_pair.allowed_contexts.clone(),
+            capabilities: KeyCapabilities::new(key_pair, state),
+            lifecycle_history: key_pair.lifecycle_history.clone(),
+            envelope_history: key_pair.envelope_history.clone(),
+            exportable: key_pair.exportable,
+            default_output_format: key_pair.default_output_format,
+            default_hash_algorithm: key_pair.default_hash_algorithm,
+            default_encoding: key_pair.default_encoding,
+        }
+    }
 }
 
 /// List of keys response
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ListKeysResponse {
     pub success: bool,
     pub keys: Vec<KeyInfo>,
     pub message: String,
+    pub matched_count: usize, // Keys matching the filters, across all pages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>, // Pass as `cursor` for the next page; absent on the last
     pub total_count: usize,
     pub active_count: usize,
     pub expired_count: usize,
 }
 
+/// Representation requested for a public key
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PublicKeyFormat {
+    /// JSON key information
+    #[default]
+    Json,
+    /// minisign public key file
+    Minisign,
+    /// OpenSSH `ssh-ed25519` public key line
+    Ssh,
+}
+
+/// Encoding of a public key served at its content-addressed URL
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PublicKeyEncoding {
+    /// The 32 raw key bytes
+    #[default]
+    Raw,
+    /// PEM encoded SubjectPublicKeyInfo
+    Pem,
+    /// JSON Web Key (RFC 8037 `OKP`)
+    Jwk,
+}
+
+impl PublicKeyEncoding {
+    /// File extension selecting this encoding in a content-addressed URL
+    pub fn extension(self) -> &'static str {
+        match self {
+            PublicKeyEncoding::Raw => "raw",
+            PublicKeyEncoding::Pem => "pem",
+            PublicKeyEncoding::Jwk => "jwk",
+        }
+    }
+
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        [PublicKeyEncoding::Raw, PublicKeyEncoding::Pem, PublicKeyEncoding::Jwk]
+            .into_iter()
+            .find(|encoding| encoding.extension() == extension)
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            PublicKeyEncoding::Raw => "application/octet-stream",
+            PublicKeyEncoding::Pem => "application/x-pem-file",
+            PublicKeyEncoding::Jwk => "application/jwk+json",
+        }
+    }
+}
+
+/// Encoding requested for a verification bundle
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BundleFormat {
+    #[default]
+    Json,
+    Cbor,
+}
+
 /// Public key response
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct PublicKeyResponse {
     pub success: bool,
     pub key_info: Option<KeyInfo>,
     pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<ErrorCode>, // Set on failures
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>, // Structured context for the failure
 }
 
 /// Request to update key information
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct UpdateKeyRequest {
     pub name: Option<String>,
     pub description: Option<String>,
     pub tags: Option<Vec<String>>,
+    #[serde(default, with = "crate::timestamps::option", alias = "expiresAt")]
     pub expires_at: Option<DateTime<Utc>>,
+    #[serde(alias = "isActive")]
     pub is_active: Option<bool>,
+    #[serde(alias = "allowedContexts")]
+    pub allowed_contexts: Option<Vec<String>>, // Replaces the key's allow-list; an empty list lifts it
+    pub environment: Option<String>, // Moves the key to another configured environment
+    pub exportable: Option<bool>, // Only false is accepted; an exportable key can be locked in, never the reverse
+    #[serde(alias = "defaultOutputFormat")]
+    pub default_output_format: Option<SignatureOutputFormat>,
+    #[serde(alias = "defaultHashAlgorithm")]
+    pub default_hash_algorithm: Option<HashAlgorithm>,
+    #[serde(alias = "defaultEncoding")]
+    pub default_encoding: Option<SignatureEncoding>,
+}
+
+impl UpdateKeyRequest {
+    /// Whether the request sets no field at all
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none()
+            && self.description.is_none()
+            && self.tags.is_none()
+            && self.expires_at.is_none()
+            && self.is_active.is_none()
+            && self.allowed_contexts.is_none()
+            && self.environment.is_none()
+            && self.exportable.is_none()
+            && self.default_output_format.is_none()
+            && self.default_hash_algorithm.is_none()
+            && self.default_encoding.is_none()
+    }
 }
 
 /// Response for key update
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct UpdateKeyResponse {
     pub success: bool,
     pub key_info: Option<KeyInfo>,
     pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<ErrorCode>, // Set on failures
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>, // Structured context for the failure
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>, // Validation failures, one per offending field
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<ApiWarning>, // Advisories about the updated key
 }
 
 /// Request to rotate a key
 #[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct RotateKeyRequest {
+    #[serde(alias = "oldKeyId")]
     pub old_key_id: Uuid,
+    #[serde(alias = "newKeyName")]
     pub new_key_name: String,
+    #[serde(alias = "newKeyDescription")]
     pub new_key_description: Option<String>,
+    #[serde(alias = "newKeyPassword")]
     pub new_key_password: Option<String>,
+    #[serde(alias = "newKeyTags")]
     pub new_key_tags: Option<Vec<String>>,
+    #[serde(default, with = "crate::timestamps::option", alias = "newKeyExpiresAt")]
     pub new_key_expires_at: Option<DateTime<Utc>>,
 }
 
@@ -173,34 +1177,575 @@ pub struct RotateKeyResponse {
 }
 
 /// Request to revoke a key
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct RevokeKeyRequest {
+    #[serde(alias = "keyId")]
     pub key_id: Uuid,
     pub reason: Option<String>,
-    pub immediate: bool, // If true, revoke immediately; if false, mark for expiration
+    pub immediate: bool, // If true, revoke immediately; if false, revoke at effective_at
+    #[serde(default, with = "crate::timestamps::option", alias = "effectiveAt")]
+    pub effective_at: Option<DateTime<Utc>>, // When a non-immediate revocation takes effect
 }
 
 /// Response for key revocation
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct RevokeKeyResponse {
     pub success: bool,
     pub key_info: Option<KeyInfo>,
     pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<ErrorCode>, // Set on failures
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>, // Structured context for the failure
     pub revocation_time: Option<DateTime<Utc>>,
+    pub scheduled: bool, // True when the revocation is pending rather than already in effect
 }
 
-/// Key statistics response
+/// Response for soft-deleting or restoring a key
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KeyRemovalResponse {
+    pub success: bool,
+    pub key_info: Option<KeyInfo>,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<ErrorCode>, // Set on failures
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>, // Structured context for the failure
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted_by: Option<String>,
+}
+
+/// Request to suspend or resume a key
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct KeyTransitionRequest {
+    #[serde(default)]
+    pub reason: Option<String>, // Recorded in the key's lifecycle history
+}
+
+/// Response for suspending or resuming a key
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KeyTransitionResponse {
+    pub success: bool,
+    pub key_info: Option<KeyInfo>,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<ErrorCode>, // Set on failures
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>, // Structured context for the failure
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transition: Option<LifecycleEvent>, // The move recorded in the key's history
+}
+
+/// A soft-deleted key, restorable until purged
+#[derive(Debug, Serialize)]
+pub struct DeletedKeyInfo {
+    pub key_info: KeyInfo,
+    pub deleted_at: DateTime<Utc>,
+    pub deleted_by: Option<String>, // Authenticated client that deleted it; unset without request signing
+}
+
+/// A key moved to the archive to keep the keystore within its limits
+#[derive(Debug, Serialize)]
+pub struct ArchivedKeyInfo {
+    pub key_info: KeyInfo,
+    pub archived_at: DateTime<Utc>,
+    pub reason: String, // Eviction policy that archived it
+}
+
+/// Soft-deleted keys
+#[derive(Debug, Serialize)]
+pub struct DeletedKeysResponse {
+    pub success: bool,
+    pub keys: Vec<DeletedKeyInfo>,
+    pub total_count: usize,
+}
+
+/// Archived keys
+#[derive(Debug, Serialize)]
+pub struct ArchivedKeysResponse {
+    pub success: bool,
+    pub keys: Vec<ArchivedKeyInfo>,
+    pub total_count: usize,
+}
+
+/// Key templates available to `/keys/generate`
+#[derive(Debug, Serialize)]
+pub struct TemplatesResponse {
+    pub success: bool,
+    pub templates: Vec<crate::templates::KeyTemplate>,
+    pub total_count: usize,
+}
+
+/// Keys another instance holds, to compare the local keystore against
+///
+/// The sources are combined; at least one key must be given.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CompareKeysRequest {
+    #[serde(default)]
+    pub fingerprints: Vec<String>, // Compared by presence only
+    #[serde(default)]
+    pub manifest: Option<crate::key_comparison::SignedKeyManifest>, // As served by `GET /keys/manifest`
+    #[serde(default, alias = "exportManifest")]
+    pub export_manifest: Option<crate::export::ExportManifest>, // `manifest.json` of a public key export
+    #[serde(default, alias = "exportSignature")]
+    pub export_signature: Option<crate::bundle::NotarySignature>, // `manifest.sig.json` of the same export
+}
+
+/// Differences between the local keystore and another instance's keys
 #[derive(Debug, Serialize)]
+pub struct CompareKeysResponse {
+    pub success: bool,
+    #[serde(flatten)]
+    pub comparison: crate::key_comparison::KeyComparison,
+    pub signed: bool, // Every manifest given carried a valid notary signature
+}
+
+/// Request for the status of several keys at once
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KeyStatusBatchRequest {
+    #[serde(alias = "keyIds")]
+    pub key_ids: Vec<Uuid>, // At most `MAX_STATUS_BATCH`
+    #[serde(default)]
+    pub signed: bool, // Have the notary key sign each status
+}
+
+/// Status of each known key asked about, in request order
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KeyStatusBatchResponse {
+    pub success: bool,
+    pub statuses: Vec<crate::key_status::KeyStatusDocument>,
+    pub unknown: Vec<Uuid>, // Ids no key ever had
+}
+
+/// Request to seal a key to another instance's transport key
+#[cfg(not(feature = "verifier-only"))]
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExportWrappedKeyRequest {
+    #[serde(alias = "transportPublicKey")]
+    pub transport_public_key: String, // As served by the destination's `GET /admin/transport-key`
+    pub password: Option<String>, // Unlocks a password-protected key
+}
+
+/// Response for exporting a wrapped key
+#[cfg(not(feature = "verifier-only"))]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WrappedKeyResponse {
+    pub success: bool,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<ErrorCode>, // Set on failures
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>, // Structured context for the failure
+    pub envelope: Option<crate::key_transport::WrappedKey>,
+}
+
+/// Request to store a key wrapped for this instance
+#[cfg(not(feature = "verifier-only"))]
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ImportWrappedKeyRequest {
+    pub envelope: crate::key_transport::WrappedKey,
+    pub password: Option<String>, // Encrypts the imported key; required if it was protected at the source
+}
+
+/// Response for importing a wrapped key
+#[cfg(not(feature = "verifier-only"))]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportWrappedKeyResponse {
+    pub success: bool,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<ErrorCode>, // Set on failures
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>, // Structured context for the failure
+    pub key_info: Option<KeyInfo>,
+}
+
+/// Request to import keys from a legacy JSON export
+#[cfg(not(feature = "verifier-only"))]
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ImportLegacyKeysRequest {
+    pub records: Vec<serde_json::Value>, // Key records as the export holds them, see migration::LegacyKeyRecord
+    #[serde(default, with = "crate::timestamps::option")]
+    pub created_at: Option<DateTime<Utc>>, // Creation time of records that carry none; defaults to now
+}
+
+/// Response for importing legacy keys, with what happened to each record
+#[cfg(not(feature = "verifier-only"))]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportLegacyKeysResponse {
+    pub success: bool,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<ErrorCode>, // Set on failures
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub report: Option<crate::migration::MigrationReport>,
+}
+
+/// This instance's transport public key, which other instances wrap exported keys for
+#[cfg(not(feature = "verifier-only"))]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TransportKeyResponse {
+    pub success: bool,
+    pub algorithm: String,
+    pub public_key: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Request to sign a manifest of files
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SignManifestRequest {
+    #[serde(alias = "keyId")]
+    pub key_id: Uuid,
+    pub files: Vec<crate::file_manifest::FileEntry>, // In any order; signed sorted by path
+    pub password: Option<String>,
+    #[serde(default, with = "crate::timestamps::option", alias = "validUntil")]
+    pub valid_until: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub bundle: bool,
+    #[serde(default)]
+    pub context: Option<String>,
+    #[serde(default, alias = "bindTimestamp")]
+    pub bind_timestamp: bool,
+}
+
+/// Response for manifest signing: the signature result and the manifest it covers
+#[derive(Debug, Serialize)]
+pub struct SignManifestResponse {
+    #[serde(flatten)]
+    pub signed: SignDocumentResponse,
+    pub manifest: Option<crate::file_manifest::FileManifest>, // Exactly as signed; keep it with the signature
+}
+
+/// Request to verify a manifest signature
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VerifyManifestRequest {
+    pub manifest: crate::file_manifest::FileManifest,
+    pub signature: String,
+    #[serde(default, alias = "publicKey")]
+    pub public_key: String,
+    #[serde(default, alias = "keyId")]
+    pub key_id: Option<Uuid>,
+    #[serde(default, with = "crate::timestamps::option", alias = "validUntil")]
+    pub valid_until: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub context: Option<String>,
+    #[serde(default, with = "crate::timestamps::option", alias = "signingTime")]
+    pub signing_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub files: Option<Vec<crate::file_manifest::FileEntry>>, // Recomputed hashes of the files at hand
+}
+
+/// Response for manifest verification
+#[derive(Debug, Serialize)]
+pub struct VerifyManifestResponse {
+    #[serde(flatten)]
+    pub verification: VerifySignatureResponse,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub comparison: Option<crate::file_manifest::FileComparison>, // Per-file results, when files were given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub files_match: Option<bool>, // Every listed file matched and none are missing or extra
+}
+
+/// Response for archive verification
+#[derive(Debug, Serialize)]
+pub struct VerifyArchiveResponse {
+    pub success: bool,
+    pub all_valid: bool, // Every entry verified and the archive holds nothing the manifest does not list
+    #[serde(flatten)]
+    pub report: crate::archive_verification::ArchiveReport,
+    pub verification_time: DateTime<Utc>,
+}
+
+/// Request to sign a payload into a DSSE envelope
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SignDsseRequest {
+    #[serde(alias = "keyId")]
+    pub key_id: Uuid,
+    #[serde(alias = "payloadType")]
+    pub payload_type: String, // Bound into the signature, e.g. `application/vnd.in-toto+json`
+    #[serde(alias = "payloadB64")]
+    pub payload_b64: String,
+    pub password: Option<String>,
+}
+
+/// Response for DSSE signing: the signature result and the envelope carrying it
+#[derive(Debug, Serialize)]
+pub struct SignDsseResponse {
+    #[serde(flatten)]
+    pub signed: SignDocumentResponse, // `document_hash` is the SHA-256 of the PAE that was signed
+    pub envelope: Option<crate::dsse::DsseEnvelope>,
+}
+
+/// Request to verify a DSSE envelope against stored or supplied keys
+///
+/// The keys given are combined; at least one is required. `mode` must be given whenever the
+/// envelope carries several signatures or several keys are given.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VerifyDsseRequest {
+    pub envelope: crate::dsse::DsseEnvelope,
+    #[serde(default, alias = "keyId")]
+    pub key_id: Option<Uuid>,
+    #[serde(default, alias = "publicKey")]
+    pub public_key: Option<String>,
+    #[serde(default, alias = "keyIds")]
+    pub key_ids: Vec<Uuid>,
+    #[serde(default, alias = "publicKeys")]
+    pub public_keys: Vec<String>,
+    #[serde(default)]
+    pub mode: Option<crate::dsse::VerifyMode>,
+}
+
+/// Whether one trusted key signed a DSSE envelope
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DsseKeyResult {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_id: Option<Uuid>, // Set for stored keys
+    pub fingerprint: String,
+    pub signature_index: Option<usize>, // Envelope signature the key verified, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<KeyState>, // State of a stored key now
+}
+
+/// Response for DSSE envelope verification
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VerifyDsseResponse {
+    pub success: bool,
+    pub is_valid: bool, // The signatures required by `mode` verified
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<ErrorCode>,
+    pub mode: Option<crate::dsse::VerifyMode>,
+    pub payload_type: Option<String>,
+    pub keys: Vec<DsseKeyResult>,
+    pub verification_time: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<ApiWarning>, // Advisories about the stored keys checked
+}
+
+/// Request to certify another key's public key
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CertifyKeyRequest {
+    #[serde(alias = "targetKeyId")]
+    pub target_key_id: Option<Uuid>, // Key held by this service to certify
+    #[serde(alias = "targetPublicKey")]
+    pub target_public_key: Option<String>, // Alternative: base64 encoded public key of an external key
+    pub password: Option<String>, // Password for the certifying key's private key
+    #[serde(default, with = "crate::timestamps::option", alias = "validUntil")]
+    pub valid_until: Option<DateTime<Utc>>, // End of the certification's validity window
+}
+
+/// Response for key certification
+#[derive(Debug, Serialize)]
+pub struct CertifyKeyResponse {
+    pub success: bool,
+    pub certification: Option<Certification>,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<ErrorCode>, // Set on failures
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>, // Structured context for the failure
+}
+
+/// Certifications issued and received by a key
+#[derive(Debug, Serialize)]
+pub struct KeyCertificationsResponse {
+    pub success: bool,
+    pub key_id: Uuid,
+    pub issued: Vec<Certification>,
+    pub received: Vec<Certification>,
+    pub message: String,
+}
+
+/// Key statistics response
+#[derive(Debug, Serialize, Deserialize)]
 pub struct KeyStatsResponse {
     pub success: bool,
     pub total_keys: usize,
     pub active_keys: usize,
     pub expired_keys: usize,
     pub revoked_keys: usize,
+    #[serde(default)]
+    pub suspended_keys: usize, // Suspended until resumed; counted apart from active keys
     pub keys_expiring_soon: usize, // Within 30 days
+    pub total_sign_count: u64, // Signatures made by all keys
+    pub total_verify_count: u64, // key_id-based verifications against all keys
+    pub capacity: CapacityStatus, // Keystore size against its soft and hard limits
+    #[serde(default)]
+    pub keys_by_environment: BTreeMap<String, usize>, // Stored keys per deployment environment
+    #[serde(default)]
+    pub keys_by_type: BTreeMap<String, usize>, // Stored keys per key type, with types this version does not know under "Unknown"
+    pub message: String,
+}
+
+/// Stored keys grouped by the KDF parameters protecting them
+#[derive(Debug, Serialize)]
+pub struct KdfReportResponse {
+    pub success: bool,
+    pub current: KdfParams, // Parameters applied to newly encrypted keys
+    pub outdated_keys: usize, // Encrypted keys not using the current parameters
+    pub groups: Vec<KdfReportGroup>,
+    pub message: String,
+}
+
+/// Encrypted keys whose envelopes should be re-encrypted
+#[derive(Debug, Serialize)]
+pub struct ReencryptScanResponse {
+    pub success: bool,
+    pub scanned: usize, // Encrypted keys examined
+    pub current: KdfParams, // Parameters keys are re-encrypted under
+    pub affected: Vec<EnvelopeFinding>,
     pub message: String,
 }
 
+/// Request to re-encrypt the keys a scan finds, with each key's password
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReencryptRequest {
+    pub passwords: BTreeMap<Uuid, String>,
+}
+
+/// A key re-encrypted into a fresh envelope
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RewrappedKey {
+    pub key_id: Uuid,
+    pub version: u32, // Envelope version now stored
+    pub weaknesses: Vec<EnvelopeWeakness>, // What the previous envelope was re-encrypted for
+}
+
+/// A key that could not be re-encrypted, left as it was
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReencryptFailure {
+    pub key_id: Uuid,
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+/// Outcome of re-encrypting weak envelopes
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReencryptResponse {
+    pub success: bool, // False when any key given a password failed
+    pub rewrapped: Vec<RewrappedKey>,
+    pub failed: Vec<ReencryptFailure>,
+    pub missing_password: Vec<Uuid>, // Affected keys no password was given for
+    pub not_affected: Vec<Uuid>, // Keys given a password whose envelopes need no re-encryption
+    pub message: String,
+}
+
+/// KDF calibration response
+#[derive(Debug, Serialize)]
+pub struct KdfCalibrationResponse {
+    pub success: bool,
+    pub target_ms: u64,
+    pub current: KdfParams, // Parameters applied to newly encrypted keys
+    pub suggested: Option<KdfParams>, // Parameters expected to take about target_ms on this host
+    pub measured_ms: Option<u64>, // Time one derivation with the suggested parameters took
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<ErrorCode>, // Set on failures
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>, // Structured context for the failure
+}
+
+/// Request to validate (and optionally repair) the keystore
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ValidateKeystoreRequest {
+    #[serde(default)]
+    pub repair: bool, // Fix what can be fixed safely
+    #[serde(default)]
+    pub stream: bool, // Stream per-key progress as newline-delimited JSON
+}
+
+/// Severity of a keystore integrity finding
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum IssueSeverity {
+    Info,
+    Warning,
+    Error,
+}
+
+/// A single integrity finding for a stored key
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct KeyIssue {
+    pub code: String, // Stable identifier such as "fingerprint_mismatch"
+    pub severity: IssueSeverity,
+    pub message: String,
+    pub repaired: bool,
+}
+
+/// Integrity findings for one stored key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyValidationReport {
+    pub key_id: Uuid, // Id the entry is indexed under
+    pub name: String,
+    pub issues: Vec<KeyIssue>,
+    pub quarantined: bool,
+}
+
+/// Keystore validation response
+#[derive(Debug, Serialize)]
+pub struct ValidateKeystoreResponse {
+    pub success: bool,
+    pub checked: usize,
+    pub errors: usize,
+    pub warnings: usize,
+    pub repaired: usize,
+    pub quarantined: usize,
+    pub reports: Vec<KeyValidationReport>,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<ErrorCode>, // Set on failures
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>, // Structured context for the failure
+}
+
+/// Request to switch read-only mode
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReadOnlyRequest {
+    pub enabled: bool,
+}
+
+/// Current read-only mode
+#[derive(Debug, Serialize)]
+pub struct ReadOnlyResponse {
+    pub success: bool,
+    pub read_only: bool,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<ErrorCode>, // Set on failures
+}
+
+/// Readiness report for load balancers and operators
+#[derive(Debug, Serialize)]
+pub struct ReadinessResponse {
+    pub ready: bool,
+    pub read_only: bool, // Mutations are refused with 503 while true
+    pub follower: bool, // Another instance owns the keystore; this one reloads it read-only
+    pub key_count: usize,
+    pub self_test: Option<SelfTestReport>, // Most recent self-test, if one has run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keystore_load: Option<KeystoreLoadSummary>, // Validation of the keystore on load, owners only
+    pub persistence: PersistenceStatus,
+    pub entropy: EntropyStatus, // Key generation is refused while degraded
+    pub capacity: CapacityStatus, // Keystore size against its soft and hard limits
+    pub storage_breaker: BreakerStatus, // Storage-dependent requests are refused while open
+}
+
 /// Error types for the key management system
 #[derive(Debug, thiserror::Error)]
 pub enum KeyManagementError {
@@ -216,12 +1761,21 @@ pub enum KeyManagementError {
     #[error("Private key decryption failed: {0}")]
     PrivateKeyDecryptionFailed(String),
     
+    #[error("Password required: {0}")]
+    PasswordRequired(String),
+    
+    #[error("Invalid signature format: {0}")]
+    InvalidSignatureFormat(String),
+    
     #[error("Storage error: {0}")]
     StorageError(String),
     
     #[error("Invalid request: {0}")]
     InvalidRequest(String),
     
+    #[error("Validation failed: {0}")]
+    ValidationFailed(String),
+    
     #[error("Internal error: {0}")]
     InternalError(String),
     
@@ -236,6 +1790,48 @@ pub enum KeyManagementError {
     
     #[error("Rate limit exceeded: {0}")]
     RateLimitExceeded(String),
+    
+    #[error("Keystore full: {0}")]
+    KeystoreFull(String),
+    
+    #[error("Restore conflict: {0}")]
+    RestoreConflict(String),
+    
+    #[error("Signing policy denied: {0}")]
+    PolicyDenied(String),
+    
+    #[error("Key conflict: {0}")]
+    KeyConflict(String),
+
+    #[error("Deadline exceeded after {completed} of {total} items")]
+    DeadlineExceeded { completed: usize, total: usize },
+
+    #[error("Key belongs to environment '{key}' but the service runs in '{service}'")]
+    EnvironmentMismatch { key: String, service: String },
+
+    #[error("Signature receipt not recorded: {0}")]
+    ReceiptNotRecorded(String),
+
+    #[error("Key suspended: {0}")]
+    KeySuspended(Uuid),
+
+    #[error("Key {key_id} cannot move from {} to {}", from.as_str(), to.as_str())]
+    InvalidTransition { key_id: Uuid, from: KeyState, to: KeyState },
+
+    #[error("{field} exceeds {limit} bytes")]
+    ContentTooLarge { field: &'static str, limit: u64 },
+
+    #[error("Delegation denied: {0}")]
+    DelegationDenied(String),
+
+    #[error("Keystore unavailable; retry in {retry_after_secs} seconds")]
+    StorageUnavailable { retry_after_secs: u64 },
+
+    #[error("Ambiguous public key: {0}")]
+    AmbiguousPublicKey(String),
+
+    #[error("Events after {after_seq} are no longer retained; the oldest retained is {oldest_seq}")]
+    EventsNotRetained { after_seq: u64, oldest_seq: u64 },
 }
 
 impl From<KeyManagementError> for axum::http::StatusCode {
@@ -245,25 +1841,555 @@ impl From<KeyManagementError> for axum::http::StatusCode {
             KeyManagementError::InvalidKeyFormat(_) => axum::http::StatusCode::BAD_REQUEST,
             KeyManagementError::SignatureVerificationFailed(_) => axum::http::StatusCode::BAD_REQUEST,
             KeyManagementError::PrivateKeyDecryptionFailed(_) => axum::http::StatusCode::UNAUTHORIZED,
+            KeyManagementError::PasswordRequired(_) => axum::http::StatusCode::UNAUTHORIZED,
+            KeyManagementError::InvalidSignatureFormat(_) => axum::http::StatusCode::BAD_REQUEST,
             KeyManagementError::StorageError(_) => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
             KeyManagementError::InvalidRequest(_) => axum::http::StatusCode::BAD_REQUEST,
+            KeyManagementError::ValidationFailed(_) => axum::http::StatusCode::UNPROCESSABLE_ENTITY,
             KeyManagementError::InternalError(_) => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
             KeyManagementError::KeyExpired(_) => axum::http::StatusCode::GONE,
             KeyManagementError::KeyRevoked(_) => axum::http::StatusCode::GONE,
             KeyManagementError::InsufficientPermissions(_) => axum::http::StatusCode::FORBIDDEN,
             KeyManagementError::RateLimitExceeded(_) => axum::http::StatusCode::TOO_MANY_REQUESTS,
+            KeyManagementError::KeystoreFull(_) => axum::http::StatusCode::INSUFFICIENT_STORAGE,
+            KeyManagementError::RestoreConflict(_) => axum::http::StatusCode::CONFLICT,
+            KeyManagementError::PolicyDenied(_) => axum::http::StatusCode::FORBIDDEN,
+            KeyManagementError::KeyConflict(_) => axum::http::StatusCode::CONFLICT,
+            KeyManagementError::DeadlineExceeded { .. } => axum::http::StatusCode::GATEWAY_TIMEOUT,
+            KeyManagementError::EnvironmentMismatch { .. } => axum::http::StatusCode::FORBIDDEN,
+            KeyManagementError::ReceiptNotRecorded(_) => axum::http::StatusCode::SERVICE_UNAVAILABLE,
+            KeyManagementError::ContentTooLarge { .. } => axum::http::StatusCode::PAYLOAD_TOO_LARGE,
+            KeyManagementError::KeySuspended(_) => axum::http::StatusCode::LOCKED,
+            KeyManagementError::InvalidTransition { .. } => axum::http::StatusCode::CONFLICT,
+            KeyManagementError::DelegationDenied(_) => axum::http::StatusCode::FORBIDDEN,
+            KeyManagementError::StorageUnavailable { .. } => axum::http::StatusCode::SERVICE_UNAVAILABLE,
+            KeyManagementError::AmbiguousPublicKey(_) => axum::http::StatusCode::BAD_REQUEST,
+            KeyManagementError::EventsNotRetained { .. } => axum::http::StatusCode::GONE,
         }
     }
 }
 
-impl Default for KeyType {
-    fn default() -> Self {
-        KeyType::Ed25519
+impl KeyManagementError {
+    /// Stable code identifying this error to clients
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            KeyManagementError::KeyNotFound(_) => ErrorCode::KeyNotFound,
+            KeyManagementError::InvalidKeyFormat(_) => ErrorCode::InvalidKeyFormat,
+            KeyManagementError::SignatureVerificationFailed(_) => ErrorCode::SignatureVerificationFailed,
+            KeyManagementError::PrivateKeyDecryptionFailed(_) => ErrorCode::DecryptionFailed,
+            KeyManagementError::PasswordRequired(_) => ErrorCode::PasswordRequired,
+            KeyManagementError::InvalidSignatureFormat(_) => ErrorCode::InvalidSignatureFormat,
+            KeyManagementError::StorageError(_) => ErrorCode::StorageError,
+            KeyManagementError::InvalidRequest(_) => ErrorCode::InvalidRequest,
+            KeyManagementError::ValidationFailed(_) => ErrorCode::ValidationFailed,
+            KeyManagementError::InternalError(_) => ErrorCode::InternalError,
+            KeyManagementError::KeyExpired(_) => ErrorCode::KeyExpired,
+            KeyManagementError::KeyRevoked(_) => ErrorCode::KeyRevoked,
+            KeyManagementError::InsufficientPermissions(_) => ErrorCode::InsufficientPermissions,
+            KeyManagementError::RateLimitExceeded(_) => ErrorCode::RateLimited,
+            KeyManagementError::KeystoreFull(_) => ErrorCode::KeystoreFull,
+            KeyManagementError::RestoreConflict(_) => ErrorCode::RestoreConflict,
+            KeyManagementError::PolicyDenied(_) => ErrorCode::PolicyDenied,
+            KeyManagementError::KeyConflict(_) => ErrorCode::KeyConflict,
+            KeyManagementError::DeadlineExceeded { .. } => ErrorCode::DeadlineExceeded,
+            KeyManagementError::EnvironmentMismatch { .. } => ErrorCode::EnvironmentMismatch,
+            KeyManagementError::ReceiptNotRecorded(_) => ErrorCode::ReceiptNotRecorded,
+            KeyManagementError::KeySuspended(_) => ErrorCode::KeySuspended,
+            KeyManagementError::InvalidTransition { .. } => ErrorCode::InvalidTransition,
+            KeyManagementError::ContentTooLarge { .. } => ErrorCode::ContentTooLarge,
+            KeyManagementError::DelegationDenied(_) => ErrorCode::DelegationDenied,
+            KeyManagementError::StorageUnavailable { .. } => ErrorCode::StorageUnavailable,
+            KeyManagementError::AmbiguousPublicKey(_) => ErrorCode::AmbiguousPublicKey,
+            KeyManagementError::EventsNotRetained { .. } => ErrorCode::EventsNotRetained,
+        }
     }
+
+    /// Structured context for the error, such as the key it concerns
+    pub fn details(&self) -> Option<serde_json::Value> {
+        match self {
+            KeyManagementError::KeyNotFound(key_id)
+            | KeyManagementError::KeyExpired(key_id)
+            | KeyManagementError::KeyRevoked(key_id)
+            | KeyManagementError::KeySuspended(key_id) => Some(serde_json::json!({ "key_id": key_id })),
+            KeyManagementError::InvalidTransition { key_id, from, to } => {
+                Some(serde_json::json!({ "key_id": key_id, "from": from, "to": to }))
+            }
+            KeyManagementError::DeadlineExceeded { completed, total } => {
+                Some(serde_json::json!({ "completed": completed, "total": total }))
+            }
+            KeyManagementError::EnvironmentMismatch { key, service } => {
+                Some(serde_json::json!({ "key_environment": key, "service_environment": service }))
+            }
+            KeyManagementError::ContentTooLarge { field, limit } => {
+                Some(serde_json::json!({ "field": field, "limit_bytes": limit }))
+            }
+            KeyManagementError::StorageUnavailable { retry_after_secs } => {
+                Some(serde_json::json!({ "retry_after_secs": retry_after_secs }))
+            }
+            KeyManagementError::EventsNotRetained { after_seq, oldest_seq } => {
+                Some(serde_json::json!({ "after_seq": after_seq, "oldest_seq": oldest_seq }))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Stable, machine-readable identifier carried by every error response
+///
+/// Codes are part of the API contract: existing codes are never renamed or reused, and
+/// messages may be reworded freely. New codes must also be added to [`ErrorCode::ALL`].
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    KeyNotFound,
+    KeyExpired,
+    KeyRevoked,
+    KeyAlreadyRevoked,
+    NoScheduledRevocation,
+    SignatureNotFound,
+    InvalidKeyFormat,
+    InvalidSignatureFormat,
+    SignatureVerificationFailed,
+    PasswordRequired,
+    DecryptionFailed,
+    InvalidRequest,
+    InvalidJson,
+    UnknownField,
+    ValidationFailed,
+    ReadOnly,
+    StorageError,
+    InternalError,
+    InsufficientPermissions,
+    RateLimited,
+    Overloaded,
+    ShareNotFound,
+    InvalidRequestSignature,
+    RequestTimestampExpired,
+    RequestReplayed,
+    KeystoreFull,
+    MalformedInput,
+    RestoreConflict,
+    PolicyDenied,
+    KeyConflict,
+    DeadlineExceeded,
+    EnvironmentMismatch,
+    ReceiptNotRecorded,
+    KeySuspended,
+    InvalidTransition,
+    ContentTooLarge,
+    DelegationNotFound,
+    DelegationDenied,
+    StorageUnavailable,
+    AmbiguousPublicKey,
+    EventsNotRetained,
+}
+
+impl ErrorCode {
+    /// Every code, in catalog order
+    pub const ALL: &'static [ErrorCode] = &[
+        ErrorCode::KeyNotFound,
+        ErrorCode::KeyExpired,
+        ErrorCode::KeyRevoked,
+        ErrorCode::KeyAlreadyRevoked,
+        ErrorCode::NoScheduledRevocation,
+        ErrorCode::SignatureNotFound,
+        ErrorCode::InvalidKeyFormat,
+        ErrorCode::InvalidSignatureFormat,
+        ErrorCode::SignatureVerificationFailed,
+        ErrorCode::PasswordRequired,
+        ErrorCode::DecryptionFailed,
+        ErrorCode::InvalidRequest,
+        ErrorCode::InvalidJson,
+        ErrorCode::UnknownField,
+        ErrorCode::ValidationFailed,
+        ErrorCode::ReadOnly,
+        ErrorCode::StorageError,
+        ErrorCode::InternalError,
+        ErrorCode::InsufficientPermissions,
+        ErrorCode::RateLimited,
+        ErrorCode::Overloaded,
+        ErrorCode::ShareNotFound,
+        ErrorCode::InvalidRequestSignature,
+        ErrorCode::RequestTimestampExpired,
+        ErrorCode::RequestReplayed,
+        ErrorCode::KeystoreFull,
+        ErrorCode::MalformedInput,
+        ErrorCode::RestoreConflict,
+        ErrorCode::PolicyDenied,
+        ErrorCode::KeyConflict,
+        ErrorCode::DeadlineExceeded,
+        ErrorCode::EnvironmentMismatch,
+        ErrorCode::ReceiptNotRecorded,
+        ErrorCode::KeySuspended,
+        ErrorCode::InvalidTransition,
+        ErrorCode::ContentTooLarge,
+        ErrorCode::DelegationNotFound,
+        ErrorCode::DelegationDenied,
+        ErrorCode::StorageUnavailable,
+        ErrorCode::AmbiguousPublicKey,
+        ErrorCode::EventsNotRetained,
+    ];
+
+    /// The code as it appears on the wire
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::KeyNotFound => "KEY_NOT_FOUND",
+            ErrorCode::KeyExpired => "KEY_EXPIRED",
+            ErrorCode::KeyRevoked => "KEY_REVOKED",
+            ErrorCode::KeyAlreadyRevoked => "KEY_ALREADY_REVOKED",
+            ErrorCode::NoScheduledRevocation => "NO_SCHEDULED_REVOCATION",
+            ErrorCode::SignatureNotFound => "SIGNATURE_NOT_FOUND",
+            ErrorCode::InvalidKeyFormat => "INVALID_KEY_FORMAT",
+            ErrorCode::InvalidSignatureFormat => "INVALID_SIGNATURE_FORMAT",
+            ErrorCode::SignatureVerificationFailed => "SIGNATURE_VERIFICATION_FAILED",
+            ErrorCode::PasswordRequired => "PASSWORD_REQUIRED",
+            ErrorCode::DecryptionFailed => "DECRYPTION_FAILED",
+            ErrorCode::InvalidRequest => "INVALID_REQUEST",
+            ErrorCode::InvalidJson => "INVALID_JSON",
+            ErrorCode::UnknownField => "UNKNOWN_FIELD",
+            ErrorCode::ValidationFailed => "VALIDATION_FAILED",
+            ErrorCode::ReadOnly => "READ_ONLY",
+            ErrorCode::StorageError => "STORAGE_ERROR",
+            ErrorCode::InternalError => "INTERNAL_ERROR",
+            ErrorCode::InsufficientPermissions => "INSUFFICIENT_PERMISSIONS",
+            ErrorCode::RateLimited => "RATE_LIMITED",
+            ErrorCode::Overloaded => "OVERLOADED",
+            ErrorCode::ShareNotFound => "SHARE_NOT_FOUND",
+            ErrorCode::InvalidRequestSignature => "INVALID_REQUEST_SIGNATURE",
+            ErrorCode::RequestTimestampExpired => "REQUEST_TIMESTAMP_EXPIRED",
+            ErrorCode::RequestReplayed => "REQUEST_REPLAYED",
+            ErrorCode::KeystoreFull => "KEYSTORE_FULL",
+            ErrorCode::MalformedInput => "MALFORMED_INPUT",
+            ErrorCode::RestoreConflict => "RESTORE_CONFLICT",
+            ErrorCode::PolicyDenied => "POLICY_DENIED",
+            ErrorCode::KeyConflict => "KEY_CONFLICT",
+            ErrorCode::DeadlineExceeded => "DEADLINE_EXCEEDED",
+            ErrorCode::EnvironmentMismatch => "ENVIRONMENT_MISMATCH",
+            ErrorCode::ReceiptNotRecorded => "RECEIPT_NOT_RECORDED",
+            ErrorCode::KeySuspended => "KEY_SUSPENDED",
+            ErrorCode::InvalidTransition => "INVALID_TRANSITION",
+            ErrorCode::ContentTooLarge => "CONTENT_TOO_LARGE",
+            ErrorCode::DelegationNotFound => "DELEGATION_NOT_FOUND",
+            ErrorCode::DelegationDenied => "DELEGATION_DENIED",
+            ErrorCode::StorageUnavailable => "STORAGE_UNAVAILABLE",
+            ErrorCode::AmbiguousPublicKey => "AMBIGUOUS_PUBLIC_KEY",
+            ErrorCode::EventsNotRetained => "EVENTS_NOT_RETAINED",
+        }
+    }
+
+    /// What the code means, for the error catalog
+    pub fn description(self) -> &'static str {
+        match self {
+            ErrorCode::KeyNotFound => "No key with the given id exists",
+            ErrorCode::KeyExpired => "The key has passed its expiry date",
+            ErrorCode::KeyRevoked => "The key has been revoked",
+            ErrorCode::KeyAlreadyRevoked => "The key was already revoked, so it cannot be revoked again",
+            ErrorCode::NoScheduledRevocation => "The key has no pending scheduled revocation",
+            ErrorCode::SignatureNotFound => "No signature receipt with the given id exists",
+            ErrorCode::InvalidKeyFormat => "A key is malformed or cannot be decoded",
+            ErrorCode::InvalidSignatureFormat => "The signature is not valid base64 or has the wrong length",
+            ErrorCode::SignatureVerificationFailed => "The signature could not be verified",
+            ErrorCode::PasswordRequired => "The key is encrypted and no password was supplied",
+            ErrorCode::DecryptionFailed => "The password is wrong or the encrypted key is corrupted",
+            ErrorCode::InvalidRequest => "The request is missing required input",
+            ErrorCode::InvalidJson => "The request body is not valid JSON",
+            ErrorCode::UnknownField => "The request body contains fields the endpoint does not define",
+            ErrorCode::ValidationFailed => "One or more request fields failed validation",
+            ErrorCode::ReadOnly => "The service is in read-only mode and refuses changes",
+            ErrorCode::StorageError => "The keystore could not be read or written",
+            ErrorCode::InternalError => "An unexpected server error occurred",
+            ErrorCode::InsufficientPermissions => "The caller may not perform this operation",
+            ErrorCode::RateLimited => "Too many requests; retry later",
+            ErrorCode::Overloaded => "Too many expensive operations are in progress; retry after the Retry-After delay",
+            ErrorCode::ShareNotFound => "No open verification link with the given token exists; it may have expired or been revoked",
+            ErrorCode::InvalidRequestSignature => "The request's HMAC signature headers are missing, or the signature does not match",
+            ErrorCode::RequestTimestampExpired => "The request's signature timestamp is outside the allowed clock skew",
+            ErrorCode::RequestReplayed => "A request with the same signature was already received",
+            ErrorCode::KeystoreFull => "The keystore is at its hard limit; remove or archive keys before creating more",
+            ErrorCode::MalformedInput => "The public key or signature could not be decoded; unauthenticated callers are not told which",
+            ErrorCode::RestoreConflict => "The deleted key cannot be restored because it would clash with a stored key",
+            ErrorCode::PolicyDenied => "The signing policy service refused the signature, or could not be reached in time",
+            ErrorCode::KeyConflict => "A key with the same id, public key or active name is already stored",
+            ErrorCode::DeadlineExceeded => "The request did not finish within its time budget; details report how much of a batch completed",
+            ErrorCode::EnvironmentMismatch => "The key belongs to another deployment environment than the service",
+            ErrorCode::ReceiptNotRecorded => "The signature was withheld because its receipt could not be recorded; retry later",
+            ErrorCode::KeySuspended => "The key is suspended and cannot be used until it is resumed",
+            ErrorCode::InvalidTransition => "The key's lifecycle does not allow moving it to the requested state",
+            ErrorCode::ContentTooLarge => "The document is larger than the endpoint accepts; sign large documents with POST /sign/raw",
+            ErrorCode::DelegationNotFound => "No outstanding delegation with the given id exists; it may have expired, been used up or been revoked",
+            ErrorCode::DelegationDenied => "The delegation token is unknown, expired, revoked or used up, or does not cover the key or context",
+            ErrorCode::StorageUnavailable => "The keystore is failing or slow, so requests that need it are refused until it recovers; retry after the Retry-After delay",
+            ErrorCode::AmbiguousPublicKey => "The public key input holds several keys, such as a JWK set or several PEM blocks; supply exactly one",
+            ErrorCode::EventsNotRetained => "Events after the requested sequence number have been dropped from the event log, so replay cannot fill the gap",
+        }
+    }
+
+    /// HTTP status the code is usually returned with
+    pub fn status(self) -> u16 {
+        match self {
+            ErrorCode::KeyNotFound | ErrorCode::SignatureNotFound | ErrorCode::ShareNotFound | ErrorCode::DelegationNotFound => 404,
+            ErrorCode::KeyExpired | ErrorCode::KeyRevoked | ErrorCode::EventsNotRetained => 410,
+            ErrorCode::KeyAlreadyRevoked
+            | ErrorCode::NoScheduledRevocation
+            | ErrorCode::RestoreConflict
+            | ErrorCode::KeyConflict
+            | ErrorCode::InvalidTransition => 409,
+            ErrorCode::KeySuspended => 423,
+            ErrorCode::InvalidKeyFormat
+            | ErrorCode::AmbiguousPublicKey
+            | ErrorCode::InvalidSignatureFormat
+            | ErrorCode::SignatureVerificationFailed
+            | ErrorCode::InvalidRequest
+            | ErrorCode::InvalidJson
+            | ErrorCode::MalformedInput => 400,
+            ErrorCode::PasswordRequired
+            | ErrorCode::DecryptionFailed
+            | ErrorCode::InvalidRequestSignature
+            | ErrorCode::RequestTimestampExpired
+            | ErrorCode::RequestReplayed => 401,
+            ErrorCode::UnknownField | ErrorCode::ValidationFailed => 422,
+            ErrorCode::ReadOnly | ErrorCode::Overloaded | ErrorCode::ReceiptNotRecorded | ErrorCode::StorageUnavailable => 503,
+            ErrorCode::StorageError | ErrorCode::InternalError => 500,
+            ErrorCode::InsufficientPermissions
+            | ErrorCode::PolicyDenied
+            | ErrorCode::EnvironmentMismatch
+            | ErrorCode::DelegationDenied => 403,
+            ErrorCode::RateLimited => 429,
+            ErrorCode::KeystoreFull => 507,
+            ErrorCode::DeadlineExceeded => 504,
+            ErrorCode::ContentTooLarge => 413,
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Stable, machine-readable identifier of a response warning
+///
+/// Like error codes, warning codes are never renamed or reused. New codes must also be added to
+/// [`WarningCode::ALL`].
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum WarningCode {
+    KeyExpiringSoon,
+    KeyInactive,
+    UnencryptedPrivateKey,
+    ValidityOutlastsKey,
+    PersistenceDegraded,
+    DeprecatedPath,
+    EnvironmentMismatch,
+    ReceiptNotRecorded,
+    UsageNotRecorded,
+    PasswordInBody,
+}
+
+impl WarningCode {
+    /// Every code, in catalog order
+    pub const ALL: &'static [WarningCode] = &[
+        WarningCode::KeyExpiringSoon,
+        WarningCode::KeyInactive,
+        WarningCode::UnencryptedPrivateKey,
+        WarningCode::ValidityOutlastsKey,
+        WarningCode::PersistenceDegraded,
+        WarningCode::DeprecatedPath,
+        WarningCode::EnvironmentMismatch,
+        WarningCode::ReceiptNotRecorded,
+        WarningCode::UsageNotRecorded,
+        WarningCode::PasswordInBody,
+    ];
+
+    /// The code as it appears on the wire
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WarningCode::KeyExpiringSoon => "KEY_EXPIRING_SOON",
+            WarningCode::KeyInactive => "KEY_INACTIVE",
+            WarningCode::UnencryptedPrivateKey => "UNENCRYPTED_PRIVATE_KEY",
+            WarningCode::ValidityOutlastsKey => "VALIDITY_OUTLASTS_KEY",
+            WarningCode::PersistenceDegraded => "PERSISTENCE_DEGRADED",
+            WarningCode::DeprecatedPath => "DEPRECATED_PATH",
+            WarningCode::EnvironmentMismatch => "ENVIRONMENT_MISMATCH",
+            WarningCode::ReceiptNotRecorded => "RECEIPT_NOT_RECORDED",
+            WarningCode::UsageNotRecorded => "USAGE_NOT_RECORDED",
+            WarningCode::PasswordInBody => "PASSWORD_IN_BODY",
+        }
+    }
+
+    /// What the code means, for the catalog
+    pub fn description(self) -> &'static str {
+        match self {
+            WarningCode::KeyExpiringSoon => "The key expires within INKAN_EXPIRY_WARNING_DAYS",
+            WarningCode::KeyInactive => "The stored key is revoked or expired; signatures it made earlier still verify",
+            WarningCode::UnencryptedPrivateKey => "The private key is stored without password encryption",
+            WarningCode::ValidityOutlastsKey => "The signature's validity window ends after the key expires",
+            WarningCode::PersistenceDegraded => "The change is held in memory because keystore writes are failing",
+            WarningCode::DeprecatedPath => "The path has no version prefix; it aliases /v1 until its sunset",
+            WarningCode::EnvironmentMismatch => "The key belongs to another deployment environment than the service; allowed by INKAN_ENVIRONMENT_MISMATCH=warn",
+            WarningCode::ReceiptNotRecorded => "The signature's receipt could not be recorded; released anyway under INKAN_RECEIPT_FAILURE=warn",
+            WarningCode::UsageNotRecorded => "The key's usage counters and last_used were not updated for this signature",
+            WarningCode::PasswordInBody => "The key password was sent in the request body, which is deprecated; send it in the X-Key-Password header",
+        }
+    }
+}
+
+/// One entry of the published warning catalog
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct WarningCatalogEntry {
+    pub code: WarningCode,
+    pub description: &'static str,
+}
+
+/// Every warning code responses may carry
+pub fn warning_catalog() -> Vec<WarningCatalogEntry> {
+    WarningCode::ALL.iter()
+        .map(|&code| WarningCatalogEntry { code, description: code.description() })
+        .collect()
 }
 
-impl Default for KeyStrength {
-    fn default() -> Self {
-        KeyStrength::Standard
+/// One entry of the published error catalog
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ErrorCatalogEntry {
+    pub code: ErrorCode,
+    pub status: u16,
+    pub description: &'static str,
+}
+
+/// Every error code clients may receive, for documentation and API specs
+pub fn error_catalog() -> Vec<ErrorCatalogEntry> {
+    ErrorCode::ALL.iter()
+        .map(|&code| ErrorCatalogEntry { code, status: code.status(), description: code.description() })
+        .collect()
+}
+
+/// Response describing what the service supports
+#[derive(Debug, Serialize)]
+pub struct CapabilitiesResponse {
+    pub success: bool,
+    pub capabilities: ServiceCapabilities,
+}
+
+/// Response describing the service for operators
+#[derive(Debug, Serialize)]
+pub struct AboutResponse {
+    pub success: bool,
+    #[serde(flatten)]
+    pub about: About,
+}
+
+/// Response describing the running build
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VersionResponse {
+    pub success: bool,
+    #[serde(flatten)]
+    pub build: SignerInfo,
+}
+
+/// Response listing every error code
+#[derive(Debug, Serialize)]
+pub struct ErrorCatalogResponse {
+    pub success: bool,
+    pub errors: Vec<ErrorCatalogEntry>,
+    pub warnings: Vec<WarningCatalogEntry>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Codes are a published contract; changing this list breaks clients
+    #[test]
+    fn test_error_codes_never_change() {
+        let codes: Vec<String> = ErrorCode::ALL.iter()
+            .map(|code| serde_json::to_value(code).unwrap().as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(codes, [
+            "KEY_NOT_FOUND", "KEY_EXPIRED", "KEY_REVOKED", "KEY_ALREADY_REVOKED", "NO_SCHEDULED_REVOCATION",
+            "SIGNATURE_NOT_FOUND", "INVALID_KEY_FORMAT", "INVALID_SIGNATURE_FORMAT", "SIGNATURE_VERIFICATION_FAILED",
+            "PASSWORD_REQUIRED", "DECRYPTION_FAILED", "INVALID_REQUEST", "INVALID_JSON", "UNKNOWN_FIELD",
+            "VALIDATION_FAILED", "READ_ONLY", "STORAGE_ERROR", "INTERNAL_ERROR", "INSUFFICIENT_PERMISSIONS",
+            "RATE_LIMITED", "OVERLOADED", "SHARE_NOT_FOUND", "INVALID_REQUEST_SIGNATURE", "REQUEST_TIMESTAMP_EXPIRED",
+            "REQUEST_REPLAYED", "KEYSTORE_FULL", "MALFORMED_INPUT", "RESTORE_CONFLICT",
+            "POLICY_DENIED", "KEY_CONFLICT", "DEADLINE_EXCEEDED",
+            "ENVIRONMENT_MISMATCH", "RECEIPT_NOT_RECORDED", "KEY_SUSPENDED", "INVALID_TRANSITION",
+            "CONTENT_TOO_LARGE", "DELEGATION_NOT_FOUND", "DELEGATION_DENIED", "STORAGE_UNAVAILABLE",
+            "AMBIGUOUS_PUBLIC_KEY", "EVENTS_NOT_RETAINED",
+        ]);
+        for code in ErrorCode::ALL {
+            assert_eq!(serde_json::to_value(code).unwrap(), code.as_str());
+        }
+
+        let id = Uuid::nil();
+        let text = || "x".to_string();
+        let variants = [
+            (KeyManagementError::KeyNotFound(id), "KEY_NOT_FOUND"),
+            (KeyManagementError::InvalidKeyFormat(text()), "INVALID_KEY_FORMAT"),
+            (KeyManagementError::SignatureVerificationFailed(text()), "SIGNATURE_VERIFICATION_FAILED"),
+            (KeyManagementError::PrivateKeyDecryptionFailed(text()), "DECRYPTION_FAILED"),
+            (KeyManagementError::PasswordRequired(text()), "PASSWORD_REQUIRED"),
+            (KeyManagementError::InvalidSignatureFormat(text()), "INVALID_SIGNATURE_FORMAT"),
+            (KeyManagementError::StorageError(text()), "STORAGE_ERROR"),
+            (KeyManagementError::InvalidRequest(text()), "INVALID_REQUEST"),
+            (KeyManagementError::ValidationFailed(text()), "VALIDATION_FAILED"),
+            (KeyManagementError::InternalError(text()), "INTERNAL_ERROR"),
+            (KeyManagementError::KeyExpired(id), "KEY_EXPIRED"),
+            (KeyManagementError::KeyRevoked(id), "KEY_REVOKED"),
+            (KeyManagementError::InsufficientPermissions(text()), "INSUFFICIENT_PERMISSIONS"),
+            (KeyManagementError::RateLimitExceeded(text()), "RATE_LIMITED"),
+            (KeyManagementError::KeystoreFull(text()), "KEYSTORE_FULL"),
+            (KeyManagementError::DeadlineExceeded { completed: 1, total: 2 }, "DEADLINE_EXCEEDED"),
+            (KeyManagementError::EnvironmentMismatch { key: text(), service: text() }, "ENVIRONMENT_MISMATCH"),
+            (KeyManagementError::ReceiptNotRecorded(text()), "RECEIPT_NOT_RECORDED"),
+            (KeyManagementError::KeySuspended(id), "KEY_SUSPENDED"),
+            (KeyManagementError::InvalidTransition { key_id: id, from: KeyState::Revoked, to: KeyState::Active }, "INVALID_TRANSITION"),
+            (KeyManagementError::ContentTooLarge { field: "document_content", limit: 1 }, "CONTENT_TOO_LARGE"),
+            (KeyManagementError::StorageUnavailable { retry_after_secs: 1 }, "STORAGE_UNAVAILABLE"),
+            (KeyManagementError::AmbiguousPublicKey("two keys".to_string()), "AMBIGUOUS_PUBLIC_KEY"),
+            (KeyManagementError::EventsNotRetained { after_seq: 1, oldest_seq: 5 }, "EVENTS_NOT_RETAINED"),
+        ];
+        for (error, code) in variants {
+            assert_eq!(error.code().as_str(), code, "{}", error);
+            assert_eq!(axum::http::StatusCode::from(error).as_u16(), ErrorCode::ALL.iter().find(|c| c.as_str() == code).unwrap().status());
+        }
+
+        assert_eq!(error_catalog().len(), ErrorCode::ALL.len());
+    }
+
+    /// Warning codes are published like error codes
+    #[test]
+    fn test_warning_codes_never_change() {
+        let codes: Vec<String> = WarningCode::ALL.iter()
+            .map(|code| serde_json::to_value(code).unwrap().as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(codes, [
+            "KEY_EXPIRING_SOON", "KEY_INACTIVE", "UNENCRYPTED_PRIVATE_KEY", "VALIDITY_OUTLASTS_KEY", "PERSISTENCE_DEGRADED",
+            "DEPRECATED_PATH", "ENVIRONMENT_MISMATCH", "RECEIPT_NOT_RECORDED", "USAGE_NOT_RECORDED", "PASSWORD_IN_BODY",
+        ]);
+        for code in WarningCode::ALL {
+            assert_eq!(serde_json::to_value(code).unwrap(), code.as_str());
+        }
+        assert_eq!(warning_catalog().len(), WarningCode::ALL.len());
+    }
+
+    #[test]
+    fn test_document_hash_and_signature_serialize_as_their_text_forms() {
+        let hash = DocumentHash::digest(b"contract");
+        let hex = crate::key_verification::create_document_hash("contract");
+        assert_eq!(serde_json::to_value(hash).unwrap(), hex.as_str());
+        assert_eq!(serde_json::from_value::<DocumentHash>(hex.to_uppercase().into()).unwrap(), hash);
+        for malformed in ["", "abcd", &"g".repeat(64), &format!("{}00", hex)] {
+            assert!(serde_json::from_value::<DocumentHash>(malformed.into()).is_err(), "{}", malformed);
+        }
+
+        let signature = SignatureBytes([7u8; 64]);
+        let base64 = signature.encode(SignatureEncoding::Base64);
+        assert_eq!(serde_json::to_value(signature).unwrap(), base64.as_str());
+        assert_eq!(serde_json::from_value::<SignatureBytes>(base64.into()).unwrap(), signature);
+        // Stored signatures are standard base64; other encodings are only for responses
+        assert!(serde_json::from_value::<SignatureBytes>(signature.encode(SignatureEncoding::Hex).into()).is_err());
+        assert!(serde_json::from_value::<SignatureBytes>(serde_json::Value::from("AAAA")).is_err());
+        assert_eq!(SignatureBytes::decode(&signature.encode(SignatureEncoding::Base64Url), None).unwrap(), signature);
     }
 }
@@ -0,0 +1,406 @@
+//! Expiring-key notifications
+//!
+//! The sweeper checks every active key against a schedule of thresholds (days before expiry)
+//! and sends one notice per key and threshold through the configured [`Notifier`]s. Sent
+//! thresholds are persisted on the key, so restarts do not repeat notices; changing a key's
+//! expiry re-arms them. The same channels carry operational alerts, such as degraded entropy.
+
+use crate::config::NotificationConfig;
+use crate::event_log::OperationEvent;
+use crate::key_storage::KeyStorage;
+use crate::models::{KeyManagementError, KeyPair};
+use crate::utils::public_key_to_fingerprint;
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Tag prefix naming the owner a key's notices are addressed to, e.g. `owner:release-team`
+pub const OWNER_TAG_PREFIX: &str = "owner:";
+
+/// Notice that a key crossed an expiry threshold
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ExpiryNotice {
+    pub key_id: Uuid,
+    pub key_name: String,
+    pub owner: Option<String>,
+    pub fingerprint: Option<String>,
+    pub expires_at: DateTime<Utc>,
+    /// Threshold, in days before expiry, that triggered the notice
+    pub threshold_days: u32,
+    /// Number of the notice in the operation event log, to find missed notices by
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seq: Option<u64>,
+}
+
+impl ExpiryNotice {
+    fn for_key(key_pair: &KeyPair, expires_at: DateTime<Utc>, threshold_days: u32) -> Self {
+        Self {
+            key_id: key_pair.id,
+            key_name: key_pair.name.clone(),
+            owner: key_pair.tags.iter()
+                .find_map(|tag| tag.strip_prefix(OWNER_TAG_PREFIX))
+                .map(str::to_string),
+            fingerprint: key_pair.fingerprint.clone()
+                .or_else(|| public_key_to_fingerprint(&key_pair.public_key).ok()),
+            expires_at,
+            threshold_days,
+            seq: None,
+        }
+    }
+
+    /// One-line human readable summary, used as the email subject
+    pub fn summary(&self) -> String {
+        format!("Key '{}' expires within {} days", self.key_name, self.threshold_days)
+    }
+
+    /// Plain text listing of the notice's details, used as the email body
+    pub fn body(&self) -> String {
+        format!(
+            "Key: {}\nKey ID: {}\nOwner: {}\nFingerprint: {}\nExpires at: {}\n",
+            self.key_name,
+            self.key_id,
+            self.owner.as_deref().unwrap_or("unknown"),
+            self.fingerprint.as_deref().unwrap_or("unknown"),
+            self.expires_at.to_rfc3339(),
+        )
+    }
+}
+
+/// Operational alert raised when the service degrades in a way operators must act on
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ServiceAlert {
+    /// Machine readable alert name, e.g. `entropy_degraded`
+    pub alert: String,
+    pub message: String,
+    pub raised_at: DateTime<Utc>,
+}
+
+impl ServiceAlert {
+    /// One-line human readable summary, used as the email subject
+    pub fn summary(&self) -> String {
+        format!("Inkan alert: {}", self.alert)
+    }
+}
+
+/// Channel expiry notices are delivered through
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    /// Short channel name used in logs
+    fn name(&self) -> &str;
+
+    /// Delivers a notice, failing if the channel did not accept it
+    async fn notify(&self, notice: &ExpiryNotice) -> Result<(), KeyManagementError>;
+
+    /// Delivers an operational alert; channels that carry only expiry notices ignore it
+    async fn alert(&self, _alert: &ServiceAlert) -> Result<(), KeyManagementError> {
+        Ok(())
+    }
+}
+
+/// Sends an alert through every channel, logging the channels that did not accept it
+pub async fn send_alert(notifiers: &[Arc<dyn Notifier>], alert: &ServiceAlert) {
+    for notifier in notifiers {
+        if let Err(e) = notifier.alert(alert).await {
+            tracing::warn!("{} alert '{}' failed: {}", notifier.name(), alert.alert, e);
+        }
+    }
+}
+
+/// Posts notices as JSON to a webhook URL
+#[cfg(feature = "webhook")]
+pub struct WebhookNotifier {
+    url: String,
+    client: reqwest::Client,
+}
+
+#[cfg(feature = "webhook")]
+impl WebhookNotifier {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[cfg(feature = "webhook")]
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    async fn notify(&self, notice: &ExpiryNotice) -> Result<(), KeyManagementError> {
+        self.client.post(&self.url)
+            .json(notice)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| KeyManagementError::InternalError(format!("Webhook notification failed: {}", e)))?;
+        Ok(())
+    }
+
+    async fn alert(&self, alert: &ServiceAlert) -> Result<(), KeyManagementError> {
+        self.client.post(&self.url)
+            .json(alert)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| KeyManagementError::InternalError(format!("Webhook alert failed: {}", e)))?;
+        Ok(())
+    }
+}
+
+/// Emails notices through an SMTP relay
+#[cfg(feature = "email")]
+pub struct EmailNotifier {
+    transport: lettre::AsyncSmtpTransport<lettre::Tokio1Executor>,
+    from: lettre::message::Mailbox,
+    to: lettre::message::Mailbox,
+}
+
+#[cfg(feature = "email")]
+impl EmailNotifier {
+    pub fn new(host: &str, credentials: Option<(String, String)>, from: &str, to: &str) -> Result<Self, KeyManagementError> {
+        let invalid = |e: &dyn std::fmt::Display| KeyManagementError::ValidationFailed(format!("Invalid email notification settings: {}", e));
+        let mut builder = lettre::AsyncSmtpTransport::<lettre::Tokio1Executor>::relay(host).map_err(|e| invalid(&e))?;
+        if let Some((username, password)) = credentials {
+            builder = builder.credentials(lettre::transport::smtp::authentication::Credentials::new(username, password));
+        }
+        Ok(Self {
+            transport: builder.build(),
+            from: from.parse().map_err(|e| invalid(&e))?,
+            to: to.parse().map_err(|e| invalid(&e))?,
+        })
+    }
+}
+
+#[cfg(feature = "email")]
+#[async_trait]
+impl Notifier for EmailNotifier {
+    fn name(&self) -> &str {
+        "email"
+    }
+
+    async fn notify(&self, notice: &ExpiryNotice) -> Result<(), KeyManagementError> {
+        use lettre::AsyncTransport;
+
+        let message = lettre::Message::builder()
+            .from(self.from.clone())
+            .to(self.to.clone())
+            .subject(notice.summary())
+            .body(notice.body())
+            .map_err(|e| KeyManagementError::InternalError(format!("Failed to build notification email: {}", e)))?;
+        self.transport.send(message).await
+            .map_err(|e| KeyManagementError::InternalError(format!("Email notification failed: {}", e)))?;
+        Ok(())
+    }
+
+    async fn alert(&self, alert: &ServiceAlert) -> Result<(), KeyManagementError> {
+        use lettre::AsyncTransport;
+
+        let message = lettre::Message::builder()
+            .from(self.from.clone())
+            .to(self.to.clone())
+            .subject(alert.summary())
+            .body(format!("{}\nRaised at: {}\n", alert.message, alert.raised_at.to_rfc3339()))
+            .map_err(|e| KeyManagementError::InternalError(format!("Failed to build alert email: {}", e)))?;
+        self.transport.send(message).await
+            .map_err(|e| KeyManagementError::InternalError(format!("Email alert failed: {}", e)))?;
+        Ok(())
+    }
+}
+
+/// Notification channels and the schedule they are driven on
+#[derive(Clone)]
+pub struct ExpiryNotifications {
+    pub notifiers: Vec<Arc<dyn Notifier>>,
+    /// Days before expiry at which a notice is sent
+    pub thresholds_days: Vec<u32>,
+}
+
+impl ExpiryNotifications {
+    /// Builds the channels enabled in configuration, or `None` if there are none
+    ///
+    /// Fails if a channel is configured but the crate was built without its feature.
+    pub fn from_config(config: &NotificationConfig) -> Result<Option<Self>, KeyManagementError> {
+        #[allow(unused_mut)]
+        let mut notifiers: Vec<Arc<dyn Notifier>> = Vec::new();
+
+        if let Some(url) = &config.webhook_url {
+            #[cfg(feature = "webhook")]
+            notifiers.push(Arc::new(WebhookNotifier::new(url)));
+            #[cfg(not(feature = "webhook"))]
+            return Err(KeyManagementError::ValidationFailed(format!(
+                "INKAN_NOTIFY_WEBHOOK_URL is set to {} but the service was built without the webhook feature",
+                url
+            )));
+        }
+
+        if let Some(host) = &config.smtp_host {
+            let (Some(from), Some(to)) = (&config.email_from, &config.email_to) else {
+                return Err(KeyManagementError::ValidationFailed(
+                    "INKAN_NOTIFY_SMTP_HOST requires INKAN_NOTIFY_EMAIL_FROM and INKAN_NOTIFY_EMAIL_TO".to_string(),
+                ));
+            };
+            #[cfg(feature = "email")]
+            {
+                let credentials = config.smtp_username.clone().zip(config.smtp_password.clone());
+                notifiers.push(Arc::new(EmailNotifier::new(host, credentials, from, to)?));
+            }
+            #[cfg(not(feature = "email"))]
+            {
+                let _ = (from, to);
+                return Err(KeyManagementError::ValidationFailed(format!(
+                    "INKAN_NOTIFY_SMTP_HOST is set to {} but the service was built without the email feature",
+                    host
+                )));
+            }
+        }
+
+        if notifiers.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            notifiers,
+            thresholds_days: config.thresholds_days.clone(),
+        }))
+    }
+}
+
+/// Sends notices for every threshold crossed since the last check, returning the key and
+/// threshold of each notice sent
+///
+/// A key that crosses several thresholds at once, such as one created a few days before its
+/// expiry, gets a single notice for the tightest threshold. Thresholds are only marked as sent
+/// once at least one channel accepted the notice, so failed deliveries are retried on the next
+/// check. Every notice is numbered in the operation event log before it is sent, a retried one
+/// afresh.
+pub async fn notify_expiring_keys(
+    storage: &KeyStorage,
+    notifications: &ExpiryNotifications,
+    now: DateTime<Utc>,
+) -> Result<Vec<(Uuid, u32)>, KeyManagementError> {
+    let mut sent = Vec::new();
+
+    for (_, key_pair) in storage.entries().await {
+        let Some(expires_at) = key_pair.expires_at else { continue };
+        if !key_pair.state(now).is_usable() {
+            continue;
+        }
+
+        let crossed: Vec<u32> = notifications.thresholds_days.iter()
+            .copied()
+            .filter(|days| expires_at - now <= Duration::days(i64::from(*days)))
+            .filter(|days| !key_pair.notified_thresholds.contains(days))
+            .collect();
+        let Some(threshold_days) = crossed.iter().copied().min() else { continue };
+
+        let mut notice = ExpiryNotice::for_key(&key_pair, expires_at, threshold_days);
+        notice.seq = match storage.events().record(OperationEvent::sweep("expiry_notice", key_pair.id, now)).await {
+            Ok(seq) => Some(seq),
+            Err(e) => {
+                tracing::warn!("Expiry notice for key {} not numbered: {}", key_pair.id, e);
+                None
+            }
+        };
+        let mut delivered = false;
+        for notifier in &notifications.notifiers {
+            match notifier.notify(&notice).await {
+                Ok(()) => delivered = true,
+                Err(e) => tracing::warn!("{} notification for key {} failed: {}", notifier.name(), key_pair.id, e),
+            }
+        }
+
+        if delivered {
+            storage.mark_expiry_notified(key_pair.id, &crossed).await?;
+            sent.push((key_pair.id, threshold_days));
+        }
+    }
+
+    Ok(sent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{Clock, MockClock};
+    use crate::key_generation::generate_test_key_pair;
+    use crate::sweeper::sweep;
+    use tempfile::tempdir;
+    use tokio::sync::Mutex;
+
+    #[derive(Default)]
+    struct MockNotifier {
+        notices: Mutex<Vec<ExpiryNotice>>,
+    }
+
+    #[async_trait]
+    impl Notifier for MockNotifier {
+        fn name(&self) -> &str {
+            "mock"
+        }
+
+        async fn notify(&self, notice: &ExpiryNotice) -> Result<(), KeyManagementError> {
+            self.notices.lock().await.push(notice.clone());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_each_threshold_fires_once() {
+        let dir = tempdir().unwrap();
+        let storage_path = dir.path().join("keys.json");
+        let storage = KeyStorage::new(storage_path.to_str().unwrap());
+        let clock = MockClock::new(Utc::now());
+
+        let mut key_pair = generate_test_key_pair("Expiring Key").unwrap();
+        key_pair.expires_at = Some(clock.now() + Duration::days(40));
+        key_pair.tags = vec!["owner:release-team".to_string()];
+        storage.store_key(key_pair.clone()).await.unwrap();
+
+        let notifier = Arc::new(MockNotifier::default());
+        let notifications = ExpiryNotifications {
+            notifiers: vec![notifier.clone()],
+            thresholds_days: vec![30, 7, 1],
+        };
+
+        let mut fired = Vec::new();
+        for _ in 0..40 * 4 {
+            let report = sweep(&storage, &clock, Some(&notifications)).await.unwrap();
+            fired.extend(report.notified.into_iter().map(|(_, days)| days));
+            clock.advance(Duration::hours(6));
+        }
+        assert_eq!(fired, vec![30, 7, 1]);
+
+        let notices = notifier.notices.lock().await;
+        assert_eq!(notices.len(), 3);
+        assert_eq!(notices[0].owner.as_deref(), Some("release-team"));
+        assert_eq!(notices[0].fingerprint, public_key_to_fingerprint(&key_pair.public_key).ok());
+
+        // Sent thresholds are persisted, so a restarted service does not repeat them
+        let restarted = KeyStorage::new(storage_path.to_str().unwrap());
+        restarted.load_from_disk().await.unwrap();
+        assert_eq!(restarted.get_key_record(key_pair.id).await.unwrap().notified_thresholds.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_late_key_gets_single_notice() {
+        let dir = tempdir().unwrap();
+        let storage = KeyStorage::new(dir.path().join("keys.json").to_str().unwrap());
+        let now = Utc::now();
+
+        let mut key_pair = generate_test_key_pair("Short Key").unwrap();
+        key_pair.expires_at = Some(now + Duration::days(5));
+        storage.store_key(key_pair.clone()).await.unwrap();
+
+        let notifications = ExpiryNotifications {
+            notifiers: vec![Arc::new(MockNotifier::default())],
+            thresholds_days: vec![30, 7, 1],
+        };
+        assert_eq!(notify_expiring_keys(&storage, &notifications, now).await.unwrap(), vec![(key_pair.id, 7)]);
+        assert!(notify_expiring_keys(&storage, &notifications, now).await.unwrap().is_empty());
+    }
+}
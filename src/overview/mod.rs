@@ -0,0 +1,213 @@
+//! Admin overview
+//!
+//! `GET /admin/overview` gathers what an operations page shows — key statistics, keys expiring
+//! soon, recent signatures, storage and mode flags, background task runs and build information —
+//! in one response. Sections are assembled concurrently, and one that fails or does not finish
+//! in time is reported as an error object in place of its data rather than failing the response.
+
+use crate::capacity::CapacityStatus;
+use crate::entropy::EntropyStatus;
+use crate::key_storage::PersistenceStatus;
+use crate::models::{ErrorCode, KeyManagementError};
+use crate::sweeper::TaskRun;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::future::Future;
+use uuid::Uuid;
+
+/// Days ahead within which a key counts as expiring soon, as in `/keys/stats`
+pub const EXPIRING_SOON_DAYS: u32 = 30;
+/// Most expiring keys listed, soonest first
+pub const MAX_EXPIRING_KEYS: usize = 10;
+/// Most recent signatures listed, newest first
+pub const MAX_RECENT_SIGNATURES: usize = 20;
+/// Longest one section may take before it is reported as failed
+pub const SECTION_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// A section's data, or why it could not be gathered
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(untagged)]
+pub enum Section<T> {
+    Ready(T),
+    Failed { error: SectionError },
+}
+
+/// Error reported in place of a section
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct SectionError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl<T> Section<T> {
+    /// Gathers a section, failing it if `gather` errors or outlasts `timeout`
+    pub async fn gather(
+        gather: impl Future<Output = Result<T, KeyManagementError>>,
+        timeout: std::time::Duration,
+    ) -> Self {
+        match tokio::time::timeout(timeout, gather).await {
+            Ok(Ok(data)) => Section::Ready(data),
+            Ok(Err(e)) => Section::Failed { error: SectionError { code: e.code(), message: e.to_string() } },
+            Err(_) => Section::Failed {
+                error: SectionError {
+                    code: ErrorCode::InternalError,
+                    message: format!("Section did not finish within {} ms", timeout.as_millis()),
+                },
+            },
+        }
+    }
+}
+
+/// Keystore counts by state and usage totals
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct KeyStats {
+    pub total_keys: usize,
+    pub active_keys: usize,
+    pub expired_keys: usize,
+    pub revoked_keys: usize,
+    pub suspended_keys: usize,
+    pub keys_expiring_soon: usize,
+    pub total_sign_count: u64,
+    pub total_verify_count: u64,
+    pub capacity: CapacityStatus,
+}
+
+/// A usable key that expires within [`EXPIRING_SOON_DAYS`]
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ExpiringKey {
+    pub key_id: Uuid,
+    pub name: String,
+    pub fingerprint: Option<String>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// A recorded signature, from its receipt
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct RecentSignature {
+    pub signature_id: Uuid,
+    pub key_id: Uuid,
+    pub signing_time: DateTime<Utc>,
+    pub document_hash: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+}
+
+/// Operating mode and storage health
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ServiceFlags {
+    pub read_only: bool,
+    pub follower: bool,
+    pub persistence: PersistenceStatus,
+    pub entropy: EntropyStatus,
+}
+
+/// Latest runs of the background tasks; `None` until a task has run, or on a follower
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct BackgroundTasks {
+    pub sweeper: Option<TaskRun>,
+}
+
+/// The running build
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct BuildInfo {
+    pub name: &'static str,
+    pub version: &'static str,
+    pub features: Vec<&'static str>,
+}
+
+impl BuildInfo {
+    pub fn current() -> Self {
+        let features = [
+            ("webhook", cfg!(feature = "webhook")),
+            ("email", cfg!(feature = "email")),
+            ("watch", cfg!(feature = "watch")),
+            ("client", cfg!(feature = "client")),
+        ];
+        Self {
+            name: env!("CARGO_PKG_NAME"),
+            version: env!("CARGO_PKG_VERSION"),
+            features: features.into_iter().filter(|(_, enabled)| *enabled).map(|(name, _)| name).collect(),
+        }
+    }
+}
+
+/// The sections of an overview that are gathered concurrently
+pub struct OverviewSources<S, E, A, B> {
+    pub stats: S,
+    pub expiring_soon: E,
+    pub recent_signatures: A,
+    pub background: B,
+}
+
+/// Everything `GET /admin/overview` reports
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct AdminOverview {
+    pub generated_at: DateTime<Utc>,
+    pub build: BuildInfo,
+    pub service: ServiceFlags,
+    pub stats: Section<KeyStats>,
+    pub expiring_soon: Section<Vec<ExpiringKey>>,
+    pub recent_signatures: Section<Vec<RecentSignature>>,
+    pub background: Section<BackgroundTasks>,
+}
+
+impl AdminOverview {
+    /// Gathers every section at once, each bounded by `timeout`
+    pub async fn assemble<S, E, A, B>(
+        generated_at: DateTime<Utc>,
+        service: ServiceFlags,
+        sources: OverviewSources<S, E, A, B>,
+        timeout: std::time::Duration,
+    ) -> Self
+    where
+        S: Future<Output = Result<KeyStats, KeyManagementError>>,
+        E: Future<Output = Result<Vec<ExpiringKey>, KeyManagementError>>,
+        A: Future<Output = Result<Vec<RecentSignature>, KeyManagementError>>,
+        B: Future<Output = Result<BackgroundTasks, KeyManagementError>>,
+    {
+        let (stats, expiring_soon, recent_signatures, background) = tokio::join!(
+            Section::gather(sources.stats, timeout),
+            Section::gather(sources.expiring_soon, timeout),
+            Section::gather(sources.recent_signatures, timeout),
+            Section::gather(sources.background, timeout),
+        );
+        Self {
+            generated_at,
+            build: BuildInfo::current(),
+            service,
+            stats,
+            expiring_soon,
+            recent_signatures,
+            background,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_failed_sections_do_not_fail_the_overview() {
+        let service = ServiceFlags {
+            read_only: false,
+            follower: false,
+            persistence: PersistenceStatus::default(),
+            entropy: EntropyStatus::default(),
+        };
+        let overview = AdminOverview::assemble(Utc::now(), service, OverviewSources {
+            stats: std::future::pending(),
+            expiring_soon: async { Ok(Vec::new()) },
+            recent_signatures: async { Err(KeyManagementError::StorageError("receipts unavailable".to_string())) },
+            background: async { Ok(BackgroundTasks { sweeper: None }) },
+        }, std::time::Duration::from_millis(20)).await;
+
+        assert_eq!(overview.expiring_soon, Section::Ready(Vec::new()));
+        assert_eq!(overview.background, Section::Ready(BackgroundTasks { sweeper: None }));
+        let body = serde_json::to_value(&overview).unwrap();
+        assert_eq!(body["recent_signatures"]["error"]["code"], "STORAGE_ERROR");
+        assert_eq!(body["recent_signatures"]["error"]["message"], "Storage error: receipts unavailable");
+        assert_eq!(body["stats"]["error"]["message"], "Section did not finish within 20 ms");
+        assert_eq!(body["build"]["version"], env!("CARGO_PKG_VERSION"));
+    }
+}
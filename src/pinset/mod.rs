@@ -0,0 +1,504 @@
+//! Pin sets of trusted public keys
+//!
+//! `GET /keys/pinset` lists the usable keys carrying the requested tags as a minimal artifact a
+//! client can ship to pin the keys it trusts: JSON for generic use, counter-signed by the notary
+//! key when one is configured, or source code declaring the same keys as constant arrays for
+//! Rust, Swift or Kotlin. Generated source carries the notary signature in its header, so it can
+//! be traced back to the signed JSON it was generated from.
+
+use crate::bundle::NotarySignature;
+use crate::canonicalize::canonicalize_value;
+use crate::key_verification::decode_public_key;
+use crate::models::{KeyManagementError, KeyPair};
+use crate::text_normalization::{clean_tags, same_folded};
+use crate::utils::public_key_to_fingerprint;
+use base64::Engine;
+use chrono::{DateTime, SecondsFormat, Utc};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use uuid::Uuid;
+
+/// Schema identifier carried in every pin set
+pub const PINSET_SCHEMA: &str = "inkan-pinset";
+/// Current pin set schema version
+pub const PINSET_VERSION: u32 = 1;
+/// Domain tag prefixed to the canonical pin set before the notary signs it
+pub const PINSET_CONTEXT: &[u8] = b"inkan-pinset-v1";
+
+/// Form a pin set is served in
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PinFormat {
+    #[default]
+    Json,
+    Rust,
+    Swift,
+    Kotlin,
+}
+
+impl PinFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Rust => "text/x-rust; charset=utf-8",
+            Self::Swift => "text/x-swift; charset=utf-8",
+            Self::Kotlin => "text/x-kotlin; charset=utf-8",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Rust => "rs",
+            Self::Swift => "swift",
+            Self::Kotlin => "kt",
+        }
+    }
+}
+
+/// One trusted key as listed in a pin set
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PinnedKey {
+    pub key_id: Uuid,
+    pub name: String,
+    pub fingerprint: String,
+    pub public_key: String, // Base64 raw Ed25519 public key
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// The usable keys carrying every requested tag, in creation order
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Pinset {
+    pub schema: String,
+    pub version: u32,
+    pub generated_at: DateTime<Utc>,
+    pub tags: Vec<String>,
+    pub keys: Vec<PinnedKey>,
+}
+
+/// A pin set with the notary's signature over its canonical form
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SignedPinset {
+    pub pinset: Pinset,
+    pub notary: Option<NotarySignature>,
+}
+
+impl Pinset {
+    /// Pins the keys usable at `now` that carry all of `tags`; keys whose public key cannot be
+    /// read are skipped
+    ///
+    /// Tags are cleaned first, since they are written into the header of generated source.
+    pub fn new(keys: &[KeyPair], tags: &[String], now: DateTime<Utc>) -> Self {
+        let tags = clean_tags(tags);
+        let mut keys: Vec<&KeyPair> = keys.iter()
+            .filter(|key_pair| key_pair.state(now).is_usable())
+            .filter(|key_pair| tags.iter().all(|tag| key_pair.tags.iter().any(|held| same_folded(held, tag))))
+            .collect();
+        keys.sort_by_key(|key_pair| (key_pair.created_at, key_pair.id));
+        Self {
+            schema: PINSET_SCHEMA.to_string(),
+            version: PINSET_VERSION,
+            generated_at: now,
+            keys: keys.into_iter()
+                .filter_map(|key_pair| Some(PinnedKey {
+                    key_id: key_pair.id,
+                    name: key_pair.name.clone(),
+                    fingerprint: public_key_to_fingerprint(&key_pair.public_key).ok()?,
+                    public_key: key_pair.public_key.clone(),
+                    expires_at: key_pair.expires_at,
+                }))
+                .collect(),
+            tags,
+        }
+    }
+
+    fn message(&self) -> Result<Vec<u8>, KeyManagementError> {
+        let value = serde_json::to_value(self)
+            .map_err(|e| KeyManagementError::InternalError(format!("Failed to serialize pin set: {}", e)))?;
+        let mut message = PINSET_CONTEXT.to_vec();
+        message.push(0);
+        message.extend_from_slice(canonicalize_value(&value)?.as_bytes());
+        Ok(message)
+    }
+
+    /// Signs the pin set with the notary key, if one is available
+    pub fn sign(self, notary: Option<(Uuid, &SigningKey)>) -> Result<SignedPinset, KeyManagementError> {
+        let notary = match notary {
+            Some((key_id, key)) => Some(NotarySignature {
+                key_id,
+                public_key: base64::engine::general_purpose::STANDARD.encode(key.verifying_key().to_bytes()),
+                signature: base64::engine::general_purpose::STANDARD.encode(key.sign(&self.message()?).to_bytes()),
+            }),
+            None => None,
+        };
+        Ok(SignedPinset { pinset: self, notary })
+    }
+}
+
+impl SignedPinset {
+    /// Checks the notary signature; `Ok(false)` when the pin set is unsigned
+    pub fn verify(&self) -> Result<bool, KeyManagementError> {
+        let Some(notary) = &self.notary else { return Ok(false) };
+        let invalid = || KeyManagementError::SignatureVerificationFailed("Pin set signature is invalid".to_string());
+        let public_key = decode_public_key(&notary.public_key)?;
+        let signature = base64::engine::general_purpose::STANDARD.decode(&notary.signature)
+            .ok()
+            .and_then(|bytes| <[u8; 64]>::try_from(bytes.as_slice()).ok())
+            .map(|bytes| Signature::from_bytes(&bytes))
+            .ok_or_else(invalid)?;
+        public_key.verify(&self.pinset.message()?, &signature).map_err(|_| invalid())?;
+        Ok(true)
+    }
+
+    /// Source code declaring the pinned keys in `format`; `None` for JSON
+    pub fn render(&self, format: PinFormat) -> Option<String> {
+        match format {
+            PinFormat::Json => None,
+            PinFormat::Rust => Some(render_rust(self)),
+            PinFormat::Swift => Some(render_swift(self)),
+            PinFormat::Kotlin => Some(render_kotlin(self)),
+        }
+    }
+}
+
+/// Raw public key bytes of a pinned key
+fn key_bytes(key: &PinnedKey) -> Vec<u8> {
+    base64::engine::general_purpose::STANDARD.decode(&key.public_key).unwrap_or_default()
+}
+
+fn timestamp(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Comment lines opening every generated file, each prefixed with `//`
+fn header(signed: &SignedPinset) -> String {
+    let pinset = &signed.pinset;
+    let mut header = format!(
+        "// Generated by inkan at {} from {} key(s){}. Do not edit.\n",
+        timestamp(pinset.generated_at),
+        pinset.keys.len(),
+        if pinset.tags.is_empty() { String::new() } else { format!(" tagged {}", pinset.tags.join(", ")) },
+    );
+    if let Some(notary) = &signed.notary {
+        let _ = writeln!(header, "// Notary key {} ({})", notary.key_id, notary.public_key);
+        let _ = writeln!(header, "// Notary signature over the JSON pin set: {}", notary.signature);
+    }
+    header
+}
+
+/// `bytes` as hexadecimal literals, sixteen to a line
+fn byte_lines(bytes: &[u8], indent: &str, literal: impl Fn(u8) -> String) -> String {
+    bytes.chunks(16)
+        .map(|chunk| format!("{}{},\n", indent, chunk.iter().map(|byte| literal(*byte)).collect::<Vec<_>>().join(", ")))
+        .collect()
+}
+
+/// A string literal escaped for Swift or Kotlin, which share C-like escapes
+fn quoted(value: &str, unicode: impl Fn(char) -> String, escape_dollar: bool) -> String {
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            '$' if escape_dollar => quoted.push_str("\\$"),
+            c if c.is_control() => quoted.push_str(&unicode(c)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn swift_string(value: &str) -> String {
+    quoted(value, |c| format!("\\u{{{:x}}}", c as u32), false)
+}
+
+fn kotlin_string(value: &str) -> String {
+    quoted(value, |c| format!("\\u{:04x}", c as u32), true)
+}
+
+fn render_rust(signed: &SignedPinset) -> String {
+    let pinset = &signed.pinset;
+    let mut source = header(signed);
+    source.push_str(concat!(
+        "\n",
+        "/// A trusted Ed25519 public key\n",
+        "pub struct PinnedKey {\n",
+        "    pub key_id: &'static str,\n",
+        "    pub name: &'static str,\n",
+        "    pub fingerprint: &'static str,\n",
+        "    pub expires_at: Option<&'static str>,\n",
+        "    pub public_key: [u8; 32],\n",
+        "}\n",
+        "\n",
+    ));
+    let _ = writeln!(source, "pub const PINSET_GENERATED_AT: &str = {:?};\n", timestamp(pinset.generated_at));
+    let _ = writeln!(source, "pub const PINNED_KEYS: [PinnedKey; {}] = [", pinset.keys.len());
+    for key in &pinset.keys {
+        source.push_str("    PinnedKey {\n");
+        let _ = writeln!(source, "        key_id: {:?},", key.key_id.to_string());
+        let _ = writeln!(source, "        name: {:?},", key.name);
+        let _ = writeln!(source, "        fingerprint: {:?},", key.fingerprint);
+        match key.expires_at {
+            Some(expires_at) => { let _ = writeln!(source, "        expires_at: Some({:?}),", timestamp(expires_at)); }
+            None => source.push_str("        expires_at: None,\n"),
+        }
+        source.push_str("        public_key: [\n");
+        source.push_str(&byte_lines(&key_bytes(key), "            ", |byte| format!("0x{:02x}", byte)));
+        source.push_str("        ],\n    },\n");
+    }
+    source.push_str("];\n");
+    source
+}
+
+fn render_swift(signed: &SignedPinset) -> String {
+    let pinset = &signed.pinset;
+    let mut source = header(signed);
+    source.push_str(concat!(
+        "\n",
+        "/// A trusted Ed25519 public key\n",
+        "struct PinnedKey {\n",
+        "    let keyId: String\n",
+        "    let name: String\n",
+        "    let fingerprint: String\n",
+        "    let expiresAt: String?\n",
+        "    let publicKey: [UInt8]\n",
+        "}\n",
+        "\n",
+    ));
+    let _ = writeln!(source, "let pinsetGeneratedAt = {}\n", swift_string(&timestamp(pinset.generated_at)));
+    source.push_str("let pinnedKeys: [PinnedKey] = [\n");
+    for key in &pinset.keys {
+        source.push_str("    PinnedKey(\n");
+        let _ = writeln!(source, "        keyId: {},", swift_string(&key.key_id.to_string()));
+        let _ = writeln!(source, "        name: {},", swift_string(&key.name));
+        let _ = writeln!(source, "        fingerprint: {},", swift_string(&key.fingerprint));
+        let _ = writeln!(source, "        expiresAt: {},", key.expires_at.map_or("nil".to_string(), |expires_at| swift_string(&timestamp(expires_at))));
+        source.push_str("        publicKey: [\n");
+        source.push_str(&byte_lines(&key_bytes(key), "            ", |byte| format!("0x{:02x}", byte)));
+        source.push_str("        ]\n    ),\n");
+    }
+    source.push_str("]\n");
+    source
+}
+
+/// Kotlin bytes are signed, so bytes from 0x80 are written as their negative value
+fn kotlin_byte(byte: u8) -> String {
+    let signed = byte as i8;
+    if signed < 0 {
+        format!("-0x{:02x}", signed.unsigned_abs())
+    } else {
+        format!("0x{:02x}", signed)
+    }
+}
+
+fn render_kotlin(signed: &SignedPinset) -> String {
+    let pinset = &signed.pinset;
+    let mut source = header(signed);
+    source.push_str(concat!(
+        "\n",
+        "/** A trusted Ed25519 public key */\n",
+        "class PinnedKey(\n",
+        "    val keyId: String,\n",
+        "    val name: String,\n",
+        "    val fingerprint: String,\n",
+        "    val expiresAt: String?,\n",
+        "    val publicKey: ByteArray,\n",
+        ")\n",
+        "\n",
+    ));
+    let _ = writeln!(source, "const val PINSET_GENERATED_AT = {}\n", kotlin_string(&timestamp(pinset.generated_at)));
+    source.push_str("val PINNED_KEYS: List<PinnedKey> = listOf(\n");
+    for key in &pinset.keys {
+        source.push_str("    PinnedKey(\n");
+        let _ = writeln!(source, "        keyId = {},", kotlin_string(&key.key_id.to_string()));
+        let _ = writeln!(source, "        name = {},", kotlin_string(&key.name));
+        let _ = writeln!(source, "        fingerprint = {},", kotlin_string(&key.fingerprint));
+        let _ = writeln!(source, "        expiresAt = {},", key.expires_at.map_or("null".to_string(), |expires_at| kotlin_string(&timestamp(expires_at))));
+        source.push_str("        publicKey = byteArrayOf(\n");
+        source.push_str(&byte_lines(&key_bytes(key), "            ", kotlin_byte));
+        source.push_str("        ),\n    ),\n");
+    }
+    source.push_str(")\n");
+    source
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_generation::generate_seeded_test_key_pair;
+    use crate::models::KeyState;
+    use chrono::TimeZone;
+
+    fn fixed_pinset() -> SignedPinset {
+        let now = Utc.with_ymd_and_hms(2024, 8, 17, 14, 0, 0).unwrap();
+        let mut root = generate_seeded_test_key_pair("Release \"Root\" $1", 0);
+        root.id = Uuid::from_u128(1);
+        root.created_at = now - chrono::Duration::days(2);
+        root.tags = vec!["Release".to_string()];
+        let mut mobile = generate_seeded_test_key_pair("Mobile", 1);
+        mobile.id = Uuid::from_u128(2);
+        mobile.created_at = now - chrono::Duration::days(1);
+        mobile.expires_at = Some(Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap());
+        mobile.tags = vec!["release".to_string(), "mobile".to_string()];
+        let mut untagged = generate_seeded_test_key_pair("Internal", 2);
+        untagged.created_at = now - chrono::Duration::days(3);
+        let mut suspended = generate_seeded_test_key_pair("Suspended", 3);
+        suspended.tags = vec!["release".to_string()];
+        suspended.transition(KeyState::Suspended, None, None, now).unwrap();
+
+        Pinset::new(&[mobile, untagged, suspended, root], &["release".to_string(), "Release\n".to_string()], now).sign(None).unwrap()
+    }
+
+    #[test]
+    fn test_pinset_keeps_usable_keys_with_every_tag() {
+        let signed = fixed_pinset();
+        let names: Vec<&str> = signed.pinset.keys.iter().map(|key| key.name.as_str()).collect();
+        assert_eq!(names, ["Release \"Root\" $1", "Mobile"]);
+        assert_eq!(signed.pinset.tags, ["release"]);
+        assert_eq!(signed.pinset.keys[0].fingerprint, "8d6470fa:2d00d290:d7eb1df2:c16964ae");
+        assert_eq!(signed.render(PinFormat::Json), None);
+    }
+
+    #[test]
+    fn test_signed_pinset_detects_tampering() {
+        let notary = SigningKey::from_bytes(&[7u8; 32]);
+        let signed = fixed_pinset().pinset.sign(Some((Uuid::nil(), &notary))).unwrap();
+        assert!(signed.verify().unwrap());
+        let header = signed.render(PinFormat::Rust).unwrap();
+        assert!(header.contains(&format!("// Notary signature over the JSON pin set: {}\n", signed.notary.as_ref().unwrap().signature)));
+
+        let mut tampered = signed.clone();
+        tampered.pinset.keys.pop();
+        assert!(tampered.verify().is_err());
+        assert!(!SignedPinset { notary: None, ..signed }.verify().unwrap());
+    }
+
+    #[test]
+    fn test_rust_snapshot() {
+        assert_eq!(fixed_pinset().render(PinFormat::Rust).unwrap(), RUST_SNAPSHOT);
+    }
+
+    #[test]
+    fn test_swift_snapshot() {
+        assert_eq!(fixed_pinset().render(PinFormat::Swift).unwrap(), SWIFT_SNAPSHOT);
+    }
+
+    #[test]
+    fn test_kotlin_snapshot() {
+        assert_eq!(fixed_pinset().render(PinFormat::Kotlin).unwrap(), KOTLIN_SNAPSHOT);
+    }
+
+    const RUST_SNAPSHOT: &str = r#"// Generated by inkan at 2024-08-17T14:00:00Z from 2 key(s) tagged release. Do not edit.
+
+/// A trusted Ed25519 public key
+pub struct PinnedKey {
+    pub key_id: &'static str,
+    pub name: &'static str,
+    pub fingerprint: &'static str,
+    pub expires_at: Option<&'static str>,
+    pub public_key: [u8; 32],
+}
+
+pub const PINSET_GENERATED_AT: &str = "2024-08-17T14:00:00Z";
+
+pub const PINNED_KEYS: [PinnedKey; 2] = [
+    PinnedKey {
+        key_id: "00000000-0000-0000-0000-000000000001",
+        name: "Release \"Root\" $1",
+        fingerprint: "8d6470fa:2d00d290:d7eb1df2:c16964ae",
+        expires_at: None,
+        public_key: [
+            0xed, 0xd0, 0xf6, 0xde, 0x34, 0x2a, 0x1e, 0x6a, 0x72, 0x36, 0xd6, 0x24, 0x4f, 0x23, 0xd8, 0x3e,
+            0xed, 0xfc, 0xec, 0xd0, 0x59, 0xa3, 0x86, 0xc8, 0x50, 0x55, 0x70, 0x14, 0x98, 0xe7, 0x70, 0x33,
+        ],
+    },
+    PinnedKey {
+        key_id: "00000000-0000-0000-0000-000000000002",
+        name: "Mobile",
+        fingerprint: "f07815cb:38d0a5cd:0519604d:319662b5",
+        expires_at: Some("2025-01-01T00:00:00Z"),
+        public_key: [
+            0x47, 0x8b, 0x8e, 0x50, 0x7e, 0x0b, 0xb2, 0xb1, 0x8c, 0x0f, 0x9e, 0x08, 0x24, 0x76, 0x9e, 0x85,
+            0x62, 0xd1, 0x0d, 0xf9, 0xab, 0xe2, 0xe7, 0x74, 0x89, 0x6f, 0x82, 0xb4, 0xb4, 0x40, 0x52, 0x66,
+        ],
+    },
+];
+"#;
+    const SWIFT_SNAPSHOT: &str = r#"// Generated by inkan at 2024-08-17T14:00:00Z from 2 key(s) tagged release. Do not edit.
+
+/// A trusted Ed25519 public key
+struct PinnedKey {
+    let keyId: String
+    let name: String
+    let fingerprint: String
+    let expiresAt: String?
+    let publicKey: [UInt8]
+}
+
+let pinsetGeneratedAt = "2024-08-17T14:00:00Z"
+
+let pinnedKeys: [PinnedKey] = [
+    PinnedKey(
+        keyId: "00000000-0000-0000-0000-000000000001",
+        name: "Release \"Root\" $1",
+        fingerprint: "8d6470fa:2d00d290:d7eb1df2:c16964ae",
+        expiresAt: nil,
+        publicKey: [
+            0xed, 0xd0, 0xf6, 0xde, 0x34, 0x2a, 0x1e, 0x6a, 0x72, 0x36, 0xd6, 0x24, 0x4f, 0x23, 0xd8, 0x3e,
+            0xed, 0xfc, 0xec, 0xd0, 0x59, 0xa3, 0x86, 0xc8, 0x50, 0x55, 0x70, 0x14, 0x98, 0xe7, 0x70, 0x33,
+        ]
+    ),
+    PinnedKey(
+        keyId: "00000000-0000-0000-0000-000000000002",
+        name: "Mobile",
+        fingerprint: "f07815cb:38d0a5cd:0519604d:319662b5",
+        expiresAt: "2025-01-01T00:00:00Z",
+        publicKey: [
+            0x47, 0x8b, 0x8e, 0x50, 0x7e, 0x0b, 0xb2, 0xb1, 0x8c, 0x0f, 0x9e, 0x08, 0x24, 0x76, 0x9e, 0x85,
+            0x62, 0xd1, 0x0d, 0xf9, 0xab, 0xe2, 0xe7, 0x74, 0x89, 0x6f, 0x82, 0xb4, 0xb4, 0x40, 0x52, 0x66,
+        ]
+    ),
+]
+"#;
+    const KOTLIN_SNAPSHOT: &str = r#"// Generated by inkan at 2024-08-17T14:00:00Z from 2 key(s) tagged release. Do not edit.
+
+/** A trusted Ed25519 public key */
+class PinnedKey(
+    val keyId: String,
+    val name: String,
+    val fingerprint: String,
+    val expiresAt: String?,
+    val publicKey: ByteArray,
+)
+
+const val PINSET_GENERATED_AT = "2024-08-17T14:00:00Z"
+
+val PINNED_KEYS: List<PinnedKey> = listOf(
+    PinnedKey(
+        keyId = "00000000-0000-0000-0000-000000000001",
+        name = "Release \"Root\" \$1",
+        fingerprint = "8d6470fa:2d00d290:d7eb1df2:c16964ae",
+        expiresAt = null,
+        publicKey = byteArrayOf(
+            -0x13, -0x30, -0x0a, -0x22, 0x34, 0x2a, 0x1e, 0x6a, 0x72, 0x36, -0x2a, 0x24, 0x4f, 0x23, -0x28, 0x3e,
+            -0x13, -0x04, -0x14, -0x30, 0x59, -0x5d, -0x7a, -0x38, 0x50, 0x55, 0x70, 0x14, -0x68, -0x19, 0x70, 0x33,
+        ),
+    ),
+    PinnedKey(
+        keyId = "00000000-0000-0000-0000-000000000002",
+        name = "Mobile",
+        fingerprint = "f07815cb:38d0a5cd:0519604d:319662b5",
+        expiresAt = "2025-01-01T00:00:00Z",
+        publicKey = byteArrayOf(
+            0x47, -0x75, -0x72, 0x50, 0x7e, 0x0b, -0x4e, -0x4f, -0x74, 0x0f, -0x62, 0x08, 0x24, 0x76, -0x62, -0x7b,
+            0x62, -0x2f, 0x0d, -0x07, -0x55, -0x1e, -0x19, 0x74, -0x77, 0x6f, -0x7e, -0x4c, -0x4c, 0x40, 0x52, 0x66,
+        ),
+    ),
+)
+"#;
+}
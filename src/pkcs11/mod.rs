@@ -0,0 +1,196 @@
+//! PKCS#11 signing backend
+//!
+//! Holds Ed25519 keys on a token reached through the module at `INKAN_PKCS11_MODULE`, such as
+//! an HSM vendor's library, or SoftHSM2 in tests. A key is found by the slot and label of its
+//! [`HsmKeyRef`]. Keys are generated on the token as sensitive and never extractable, so the
+//! service only ever reads back the public point. Sessions log in with `INKAN_PKCS11_PIN`; each
+//! generation and each signer opens its own, so one backend serves concurrent requests.
+
+use crate::config::Pkcs11Config;
+use crate::models::{HsmKeyRef, KeyManagementError};
+use crate::signing_backend::{KeySigner, SigningBackend};
+use cryptoki::context::{CInitializeArgs, CInitializeFlags, Function, Pkcs11};
+use cryptoki::error::{Error, RvError};
+use cryptoki::mechanism::eddsa::{EddsaParams, EddsaSignatureScheme};
+use cryptoki::mechanism::Mechanism;
+use cryptoki::object::{Attribute, AttributeType, ObjectClass, ObjectHandle};
+use cryptoki::session::{Session, UserType};
+use cryptoki::slot::Slot;
+use cryptoki::types::AuthPin;
+use ed25519_dalek::{Signature, VerifyingKey};
+use std::sync::{Arc, Mutex};
+
+/// `CKA_EC_PARAMS` naming the Ed25519 curve, as the printable string `edwards25519`
+const ED25519_PARAMS: [u8; 14] = [0x13, 0x0c, b'e', b'd', b'w', b'a', b'r', b'd', b's', b'2', b'5', b'5', b'1', b'9'];
+
+/// Ed25519 keys on a PKCS#11 token
+pub struct Pkcs11Backend {
+    token: Arc<Token>,
+}
+
+/// Loaded module and the PIN its sessions log in with
+struct Token {
+    context: Pkcs11,
+    pin: AuthPin,
+}
+
+impl Pkcs11Backend {
+    /// Loads and initializes the module named by `config`
+    pub fn open(config: &Pkcs11Config) -> Result<Self, KeyManagementError> {
+        let context = Pkcs11::new(&config.module)
+            .map_err(|e| KeyManagementError::InternalError(format!("Cannot load PKCS#11 module {}: {}", config.module, e)))?;
+        match context.initialize(CInitializeArgs::new(CInitializeFlags::OS_LOCKING_OK)) {
+            Ok(()) | Err(Error::Pkcs11(RvError::CryptokiAlreadyInitialized, Function::Initialize)) => {}
+            Err(e) => {
+                return Err(KeyManagementError::InternalError(format!("Cannot initialize PKCS#11 module {}: {}", config.module, e)));
+            }
+        }
+
+        Ok(Self {
+            token: Arc::new(Token { context, pin: AuthPin::from(config.pin.clone()) }),
+        })
+    }
+}
+
+impl Token {
+    /// Opens a session on the key's slot and logs in as the user
+    fn session(&self, key: &HsmKeyRef, read_write: bool) -> Result<Session, KeyManagementError> {
+        let slot = Slot::try_from(key.slot).map_err(|e| device_error(key, "slot lookup", e))?;
+        let session = if read_write {
+            self.context.open_rw_session(slot)
+        } else {
+            self.context.open_ro_session(slot)
+        }
+        .map_err(|e| device_error(key, "session", e))?;
+
+        // Login state is shared by every session of the token, so a second one finds it done
+        match session.login(UserType::User, Some(&self.pin)) {
+            Ok(()) | Err(Error::Pkcs11(RvError::UserAlreadyLoggedIn, _)) => Ok(session),
+            Err(e) => Err(device_error(key, "login", e)),
+        }
+    }
+}
+
+/// Finds the object of `class` labelled `key.label`
+fn find_key(session: &Session, key: &HsmKeyRef, class: ObjectClass) -> Result<Option<ObjectHandle>, KeyManagementError> {
+    let template = [Attribute::Class(class), Attribute::Label(key.label.as_bytes().to_vec())];
+    let handles = session.find_objects(&template).map_err(|e| device_error(key, "key lookup", e))?;
+    match handles.as_slice() {
+        [] => Ok(None),
+        [handle] => Ok(Some(*handle)),
+        _ => Err(KeyManagementError::KeyConflict(format!(
+            "HSM slot {} holds {} keys labelled '{}'",
+            key.slot,
+            handles.len(),
+            key.label,
+        ))),
+    }
+}
+
+/// Reads an Ed25519 public key from `CKA_EC_POINT`, which tokens return either DER-wrapped in an
+/// OCTET STRING or as the bare 32 bytes
+fn public_key_from_ec_point(key: &HsmKeyRef, point: &[u8]) -> Result<VerifyingKey, KeyManagementError> {
+    let bytes = match point {
+        [0x04, 0x20, rest @ ..] if rest.len() == 32 => rest,
+        bare if bare.len() == 32 => bare,
+        _ => {
+            return Err(KeyManagementError::InvalidKeyFormat(format!(
+                "HSM key '{}' in slot {} has a {}-byte EC point, not an Ed25519 public key",
+                key.label,
+                key.slot,
+                point.len(),
+            )));
+        }
+    };
+    let bytes: [u8; 32] = bytes.try_into().expect("matched 32 bytes");
+    VerifyingKey::from_bytes(&bytes)
+        .map_err(|e| KeyManagementError::InvalidKeyFormat(format!("HSM key '{}' has an invalid public key: {}", key.label, e)))
+}
+
+fn device_error(key: &HsmKeyRef, action: &str, error: Error) -> KeyManagementError {
+    KeyManagementError::InternalError(format!("PKCS#11 {} for '{}' in slot {} failed: {}", action, key.label, key.slot, error))
+}
+
+impl SigningBackend for Pkcs11Backend {
+    fn name(&self) -> &str {
+        "pkcs11"
+    }
+
+    fn generate(&self, key: &HsmKeyRef) -> Result<VerifyingKey, KeyManagementError> {
+        let session = self.token.session(key, true)?;
+        if find_key(&session, key, ObjectClass::PRIVATE_KEY)?.is_some() {
+            return Err(KeyManagementError::KeyConflict(format!(
+                "HSM slot {} already holds a key labelled '{}'",
+                key.slot, key.label,
+            )));
+        }
+
+        let label = Attribute::Label(key.label.as_bytes().to_vec());
+        let public_template = [
+            Attribute::Token(true),
+            Attribute::Private(false),
+            Attribute::Verify(true),
+            Attribute::EcParams(ED25519_PARAMS.to_vec()),
+            label.clone(),
+        ];
+        let private_template = [
+            Attribute::Token(true),
+            Attribute::Private(true),
+            Attribute::Sensitive(true),
+            Attribute::Extractable(false),
+            Attribute::Sign(true),
+            label,
+        ];
+        let (public, _) = session
+            .generate_key_pair(&Mechanism::EccEdwardsKeyPairGen, &public_template, &private_template)
+            .map_err(|e| device_error(key, "key generation", e))?;
+
+        let attributes = session.get_attributes(public, &[AttributeType::EcPoint]).map_err(|e| device_error(key, "public key read", e))?;
+        match attributes.as_slice() {
+            [Attribute::EcPoint(point)] => public_key_from_ec_point(key, point),
+            _ => Err(KeyManagementError::InternalError(format!("HSM key '{}' in slot {} has no EC point", key.label, key.slot))),
+        }
+    }
+
+    fn signer(&self, key: &HsmKeyRef) -> Result<Box<dyn KeySigner>, KeyManagementError> {
+        let session = self.token.session(key, false)?;
+        let private = find_key(&session, key, ObjectClass::PRIVATE_KEY)?.ok_or_else(|| {
+            KeyManagementError::InternalError(format!("HSM slot {} holds no key labelled '{}'", key.slot, key.label))
+        })?;
+        Ok(Box::new(Pkcs11Signer { session: Mutex::new(session), private, key: key.clone() }))
+    }
+}
+
+/// Signs with one key over its own logged-in session
+struct Pkcs11Signer {
+    session: Mutex<Session>,
+    private: ObjectHandle,
+    key: HsmKeyRef,
+}
+
+impl KeySigner for Pkcs11Signer {
+    fn sign_message(&self, message: &[u8]) -> Result<Signature, KeyManagementError> {
+        let session = self.session.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mechanism = Mechanism::Eddsa(EddsaParams::new(EddsaSignatureScheme::Pure));
+        let signature = session.sign(&mechanism, self.private, message).map_err(|e| device_error(&self.key, "signing", e))?;
+        Signature::from_slice(&signature)
+            .map_err(|e| KeyManagementError::InternalError(format!("HSM key '{}' returned an invalid signature: {}", self.key.label, e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_public_key_from_ec_point_accepts_wrapped_and_bare_points() {
+        let key = HsmKeyRef { slot: 0, label: "root".to_string() };
+        let public_key = ed25519_dalek::SigningKey::from_bytes(&[7; 32]).verifying_key();
+        let mut wrapped = vec![0x04, 0x20];
+        wrapped.extend_from_slice(public_key.as_bytes());
+
+        assert_eq!(public_key_from_ec_point(&key, &wrapped).unwrap(), public_key);
+        assert_eq!(public_key_from_ec_point(&key, public_key.as_bytes()).unwrap(), public_key);
+        assert!(public_key_from_ec_point(&key, &wrapped[..20]).is_err());
+    }
+}
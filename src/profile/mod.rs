@@ -0,0 +1,88 @@
+//! Deployment profiles
+//!
+//! The `full` profile serves every route. The `verifier` profile is for public, internet-facing
+//! instances that verify signatures against keys managed elsewhere: it serves only the routes
+//! that read public data or verify, loads the keystore without private key material, and never
+//! writes it. Routes it leaves out are answered `404` as if they did not exist, before any other
+//! middleware sees the request.
+//!
+//! Builds with the `verifier-only` feature always run the verifier profile.
+
+use axum::http::Method;
+use serde::{Deserialize, Serialize};
+
+/// Routes the verifier profile serves, by method and route pattern
+pub const VERIFIER_ROUTES: &[(Method, &str)] = &[
+    (Method::GET, "/health"),
+    (Method::GET, "/metrics"),
+    (Method::GET, "/health/ready"),
+    (Method::GET, "/errors"),
+    (Method::GET, "/capabilities"),
+    (Method::GET, "/version"),
+    (Method::GET, "/about"),
+    (Method::GET, "/keys"),
+    (Method::GET, "/keys/search"),
+    (Method::GET, "/keys/stats"),
+    (Method::GET, "/keys/pinset"),
+    (Method::POST, "/keys/status/batch"),
+    (Method::GET, "/keys/:key_id"),
+    (Method::GET, "/keys/:key_id/certifications"),
+    (Method::GET, "/keys/:key_id/status"),
+    (Method::GET, "/keys/:key_id/public"),
+    (Method::GET, "/keys/:key_id/public/permalink"),
+    (Method::GET, "/public/:fingerprint"),
+    (Method::GET, "/signatures/by-id/:signature_id"),
+    (Method::GET, "/signatures/:signature_id/bundle"),
+    (Method::POST, "/verify"),
+    (Method::POST, "/verify/dsse"),
+    (Method::POST, "/verify/manifest"),
+    (Method::POST, "/verify/archive"),
+    (Method::GET, "/verifications/:token"),
+    (Method::POST, "/verifications/:token/check"),
+];
+
+/// Which routes an instance serves and whether it may hold private keys
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DeploymentProfile {
+    /// Every route, with private keys
+    #[default]
+    Full,
+    /// Verification and public key routes only, without private keys
+    Verifier,
+}
+
+impl DeploymentProfile {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "full" => Some(DeploymentProfile::Full),
+            "verifier" => Some(DeploymentProfile::Verifier),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeploymentProfile::Full => "full",
+            DeploymentProfile::Verifier => "verifier",
+        }
+    }
+
+    /// The profile builds with the `verifier-only` feature are held to
+    pub fn compiled() -> Option<Self> {
+        cfg!(feature = "verifier-only").then_some(DeploymentProfile::Verifier)
+    }
+
+    /// Whether the keystore is loaded and kept without private key material
+    pub fn public_only(&self) -> bool {
+        *self == DeploymentProfile::Verifier
+    }
+
+    /// Whether this profile serves `method` requests to the route pattern `route`
+    pub fn serves(&self, method: &Method, route: &str) -> bool {
+        match self {
+            DeploymentProfile::Full => true,
+            DeploymentProfile::Verifier => VERIFIER_ROUTES.iter().any(|(allowed, pattern)| allowed == method && *pattern == route),
+        }
+    }
+}
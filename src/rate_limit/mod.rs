@@ -0,0 +1,328 @@
+//! Per-client request rate limits
+//!
+//! `/verify` needs no stored key and, without HMAC request signing, no credentials, so anyone
+//! who can reach the service can make it decode and check signatures. Each client address gets
+//! a fixed one-minute window of requests. IPv6 clients are grouped by their /64 prefix, which is
+//! what a single host is usually given, so rotating addresses within it does not reset the limit.
+//!
+//! Requests are counted in [`RateBuckets`], in memory by default. Replicas behind a load balancer
+//! each count only the requests they see, so with the `redis` feature and `REDIS_URL` set the
+//! buckets are kept in Redis and shared between them. When Redis cannot be reached, requests are
+//! allowed or refused as [`BackendFailure`] says, set for each limit in [`BackendFailureModes`].
+//! Rate limits are the only state kept in Redis.
+
+use crate::models::KeyManagementError;
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv6Addr};
+use std::sync::{Arc, Mutex};
+
+/// Length of a rate limit window, in seconds
+pub const RATE_WINDOW_SECS: i64 = 60;
+
+/// Address a client's requests are counted under
+fn client_key(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(_) => ip,
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => IpAddr::V4(v4),
+            None => {
+                let segments = v6.segments();
+                IpAddr::V6(Ipv6Addr::new(segments[0], segments[1], segments[2], segments[3], 0, 0, 0, 0))
+            }
+        },
+    }
+}
+
+/// Request counts by bucket in fixed windows
+#[async_trait]
+pub trait RateBuckets: Send + Sync {
+    /// Counts a request in `bucket` at `now`
+    ///
+    /// Returns the requests counted in the bucket's current window, this one included, and when
+    /// that window ends.
+    async fn hit(&self, bucket: &str, now: DateTime<Utc>, window: Duration) -> Result<(u32, DateTime<Utc>), KeyManagementError>;
+}
+
+/// Buckets kept in this process, each window starting at the bucket's first request
+#[derive(Default)]
+pub struct MemoryRateBuckets {
+    /// End of the current window and requests in it, by bucket
+    windows: Mutex<HashMap<String, (DateTime<Utc>, u32)>>,
+    /// When finished windows were last dropped
+    pruned_at: Mutex<Option<DateTime<Utc>>>,
+}
+
+#[async_trait]
+impl RateBuckets for MemoryRateBuckets {
+    async fn hit(&self, bucket: &str, now: DateTime<Utc>, window: Duration) -> Result<(u32, DateTime<Utc>), KeyManagementError> {
+        let mut windows = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+
+        // Dropping finished windows walks every bucket, so do it at most once per window
+        let mut pruned_at = self.pruned_at.lock().unwrap_or_else(|e| e.into_inner());
+        if pruned_at.is_none_or(|pruned_at| now >= pruned_at + window) {
+            windows.retain(|_, (ends_at, _)| now < *ends_at);
+            *pruned_at = Some(now);
+        }
+
+        let (ends_at, count) = windows.entry(bucket.to_string()).or_insert((now + window, 0));
+        if now >= *ends_at {
+            *ends_at = now + window;
+            *count = 0;
+        }
+        *count = count.saturating_add(1);
+        Ok((*count, *ends_at))
+    }
+}
+
+/// What a rate limit does with a request when its buckets cannot be reached
+#[derive(Debug, Clone, Copy, Default, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendFailure {
+    /// Allow the request, so an outage does not take the endpoint down with it
+    #[default]
+    Open,
+    /// Refuse the request, so an outage does not lift the limit
+    Closed,
+}
+
+impl BackendFailure {
+    /// Parses `open` or `closed`, ignoring case and surrounding whitespace
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "open" => Some(Self::Open),
+            "closed" => Some(Self::Closed),
+            _ => None,
+        }
+    }
+}
+
+/// What each limit counted in shared buckets does while they cannot be reached
+#[derive(Debug, Clone, Copy, Default, Serialize, PartialEq, Eq)]
+pub struct BackendFailureModes {
+    /// Unsigned `/verify` requests per client address
+    pub verify: BackendFailure,
+    /// Lookups of one verification link
+    pub shares: BackendFailure,
+    /// Key lookups sent to federation peers
+    pub federation: BackendFailure,
+}
+
+/// Counts requests against limits in shared or in-memory buckets
+#[derive(Clone)]
+pub struct RequestCounter {
+    buckets: Arc<dyn RateBuckets>,
+    on_failure: BackendFailure,
+}
+
+impl RequestCounter {
+    /// Counter over `buckets`, treating requests as `on_failure` says when they cannot be counted
+    pub fn new(buckets: Arc<dyn RateBuckets>, on_failure: BackendFailure) -> Self {
+        Self { buckets, on_failure }
+    }
+
+    /// Counter over buckets of its own, in memory
+    pub fn in_memory() -> Self {
+        Self::new(Arc::new(MemoryRateBuckets::default()), BackendFailure::Open)
+    }
+
+    /// Counter over the same buckets, treating requests as `on_failure` says when they cannot be
+    /// counted
+    pub fn with_failure_mode(&self, on_failure: BackendFailure) -> Self {
+        Self { buckets: self.buckets.clone(), on_failure }
+    }
+
+    /// Counts a request in `bucket`
+    ///
+    /// Returns the seconds until the bucket's window ends if it has already had `limit` requests
+    /// in it. A request refused because the buckets could not be reached is told to retry after
+    /// one window.
+    pub async fn take(&self, bucket: &str, limit: u32, window: Duration, now: DateTime<Utc>) -> Result<(), i64> {
+        match self.buckets.hit(bucket, now, window).await {
+            Ok((count, _)) if count <= limit => Ok(()),
+            Ok((_, ends_at)) => Err((ends_at - now).num_seconds().max(1)),
+            Err(e) => match self.on_failure {
+                BackendFailure::Open => {
+                    tracing::warn!("Allowing request without a rate limit: {}", e);
+                    Ok(())
+                }
+                BackendFailure::Closed => {
+                    tracing::warn!("Refusing request that could not be rate limited: {}", e);
+                    Err(window.num_seconds().max(1))
+                }
+            },
+        }
+    }
+}
+
+impl Default for RequestCounter {
+    fn default() -> Self {
+        Self::in_memory()
+    }
+}
+
+/// Counts requests per client address in fixed windows
+pub struct ClientRateLimiter {
+    /// Requests allowed per client per window; 0 disables the limit
+    limit: u32,
+    counter: RequestCounter,
+}
+
+impl ClientRateLimiter {
+    /// Limiter allowing `limit` requests per client per minute, or any number when 0
+    pub fn new(limit: u32) -> Self {
+        Self::with_counter(limit, RequestCounter::in_memory())
+    }
+
+    /// Limiter counting requests with `counter`, which other limiters may share
+    pub fn with_counter(limit: u32, counter: RequestCounter) -> Self {
+        Self { limit, counter }
+    }
+
+    /// Limiter that never refuses a request
+    pub fn unlimited() -> Self {
+        Self::new(0)
+    }
+
+    /// Counts a request from `ip`
+    ///
+    /// Returns the seconds until the client's window resets if it has already had `limit`
+    /// requests in it.
+    pub async fn take(&self, ip: IpAddr, now: DateTime<Utc>) -> Result<(), i64> {
+        if self.limit == 0 {
+            return Ok(());
+        }
+        self.counter.take(&format!("verify:{}", client_key(ip)), self.limit, Duration::seconds(RATE_WINDOW_SECS), now).await
+    }
+}
+
+impl Default for ClientRateLimiter {
+    fn default() -> Self {
+        Self::unlimited()
+    }
+}
+
+/// Buckets kept in Redis and shared by every instance using the same server
+///
+/// Each bucket's window is aligned to multiples of its length since the epoch, so instances agree
+/// on where windows start, and its key expires with the window. The connection is opened on first
+/// use and re-established after failures.
+#[cfg(feature = "redis")]
+pub struct RedisRateBuckets {
+    client: redis::Client,
+    connection: tokio::sync::OnceCell<redis::aio::ConnectionManager>,
+}
+
+/// Longest a Redis round trip may take before the buckets count as unavailable
+#[cfg(feature = "redis")]
+const REDIS_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(500);
+
+#[cfg(feature = "redis")]
+impl RedisRateBuckets {
+    /// Buckets on the server at `url`; only the URL is checked here
+    pub fn open(url: &str) -> Result<Self, KeyManagementError> {
+        let client = redis::Client::open(url)
+            .map_err(|e| KeyManagementError::ValidationFailed(format!("REDIS_URL is invalid: {}", e)))?;
+        Ok(Self { client, connection: tokio::sync::OnceCell::new() })
+    }
+}
+
+#[cfg(feature = "redis")]
+#[async_trait]
+impl RateBuckets for RedisRateBuckets {
+    async fn hit(&self, bucket: &str, now: DateTime<Utc>, window: Duration) -> Result<(u32, DateTime<Utc>), KeyManagementError> {
+        let unavailable = |e: String| KeyManagementError::InternalError(format!("Redis rate limit buckets unavailable: {}", e));
+        let length = window.num_seconds().max(1);
+        let index = now.timestamp().div_euclid(length);
+        let key = format!("inkan:rate:{}:{}", bucket, index);
+        let counted = async {
+            let mut connection = self.connection
+                .get_or_try_init(|| self.client.get_connection_manager())
+                .await?
+                .clone();
+            redis::pipe().atomic()
+                .incr(&key, 1).expire(&key, length).ignore()
+                .query_async::<(u32,)>(&mut connection)
+                .await
+        };
+        let (count,) = tokio::time::timeout(REDIS_TIMEOUT, counted).await
+            .map_err(|_| unavailable("timed out".to_string()))?
+            .map_err(|e| unavailable(e.to_string()))?;
+        let ends_at = DateTime::from_timestamp((index + 1) * length, 0).unwrap_or(now + window);
+        Ok((count, ends_at))
+    }
+}
+
+/// Buckets at `redis_url` when one is configured, otherwise in memory
+pub fn rate_buckets(redis_url: Option<&str>) -> Result<Arc<dyn RateBuckets>, KeyManagementError> {
+    match redis_url {
+        None => Ok(Arc::new(MemoryRateBuckets::default())),
+        #[cfg(feature = "redis")]
+        Some(url) => Ok(Arc::new(RedisRateBuckets::open(url)?)),
+        #[cfg(not(feature = "redis"))]
+        Some(_) => Err(KeyManagementError::ValidationFailed("REDIS_URL requires the redis feature".to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_window_per_client_and_ipv6_prefix() {
+        let limiter = ClientRateLimiter::new(2);
+        let now = Utc::now();
+        let a: IpAddr = "203.0.113.7".parse().unwrap();
+        let b: IpAddr = "203.0.113.8".parse().unwrap();
+        assert!(limiter.take(a, now).await.is_ok());
+        assert!(limiter.take(a, now).await.is_ok());
+        assert_eq!(limiter.take(a, now + Duration::seconds(15)).await, Err(45));
+        assert!(limiter.take(b, now).await.is_ok());
+
+        // Addresses within one /64 share a window; IPv4-mapped addresses count as IPv4
+        let v6 = |address: &str| address.parse::<IpAddr>().unwrap();
+        assert!(limiter.take(v6("2001:db8:1:2::1"), now).await.is_ok());
+        assert!(limiter.take(v6("2001:db8:1:2:ffff::9"), now).await.is_ok());
+        assert!(limiter.take(v6("2001:db8:1:2:abcd::1"), now).await.is_err());
+        assert!(limiter.take(v6("2001:db8:1:3::1"), now).await.is_ok());
+        assert!(limiter.take(v6("::ffff:203.0.113.8"), now).await.is_ok());
+        assert!(limiter.take(b, now).await.is_err());
+        assert!(limiter.take(a, now + Duration::seconds(60)).await.is_ok());
+
+        let unlimited = ClientRateLimiter::unlimited();
+        for _ in 0..1_000 {
+            assert!(unlimited.take(a, now).await.is_ok());
+        }
+    }
+
+    struct UnreachableBuckets;
+
+    #[async_trait]
+    impl RateBuckets for UnreachableBuckets {
+        async fn hit(&self, _: &str, _: DateTime<Utc>, _: Duration) -> Result<(u32, DateTime<Utc>), KeyManagementError> {
+            Err(KeyManagementError::InternalError("connection refused".to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_buckets_fail_open_or_closed() {
+        let now = Utc::now();
+        let ip: IpAddr = "198.51.100.1".parse().unwrap();
+        let open = ClientRateLimiter::with_counter(1, RequestCounter::new(Arc::new(UnreachableBuckets), BackendFailure::Open));
+        assert!(open.take(ip, now).await.is_ok());
+        assert!(open.take(ip, now).await.is_ok());
+        let closed = ClientRateLimiter::with_counter(1, RequestCounter::new(Arc::new(UnreachableBuckets), BackendFailure::Closed));
+        assert_eq!(closed.take(ip, now).await, Err(RATE_WINDOW_SECS));
+
+        // Limits over the same buckets each keep their own failure mode
+        let shared = RequestCounter::new(Arc::new(UnreachableBuckets), BackendFailure::Open);
+        let verify = ClientRateLimiter::with_counter(1, shared.with_failure_mode(BackendFailure::Closed));
+        assert!(verify.take(ip, now).await.is_err());
+        assert!(shared.take("share:link", 1, Duration::seconds(RATE_WINDOW_SECS), now).await.is_ok());
+
+        assert_eq!(BackendFailure::parse(" Closed "), Some(BackendFailure::Closed));
+        assert_eq!(BackendFailure::parse("maybe"), None);
+    }
+}
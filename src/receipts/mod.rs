@@ -0,0 +1,163 @@
+//! Signature receipts
+//!
+//! Every raw signature produced by `/sign` is recorded as a verification bundle under its
+//! signature id, so the bundle can be fetched again later without access to the private key.
+//! Signature ids are derived from the key, document hash, and signing scheme, so the id also
+//! indexes repeat signatures of the same document.
+//!
+//! A receipt is written before its signature is released. When the write fails, `/sign`
+//! answers `503 RECEIPT_NOT_RECORDED` without the signature, or releases it with a
+//! `RECEIPT_NOT_RECORDED` warning when `INKAN_RECEIPT_FAILURE=warn`.
+
+use crate::bundle::Bundle;
+use crate::key_storage::write_durably;
+use crate::models::KeyManagementError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::fs;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+/// What releasing a signature whose receipt cannot be written does
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReceiptFailurePolicy {
+    #[default]
+    Reject,
+    Warn,
+}
+
+impl ReceiptFailurePolicy {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "reject" => Some(ReceiptFailurePolicy::Reject),
+            "warn" => Some(ReceiptFailurePolicy::Warn),
+            _ => None,
+        }
+    }
+}
+
+/// File-backed store of signature receipts
+pub struct ReceiptStore {
+    receipts: Mutex<HashMap<Uuid, Bundle>>,
+    storage_path: String,
+    failed_writes: AtomicU64,
+}
+
+impl ReceiptStore {
+    /// Creates a new receipt store persisted at `storage_path`
+    pub fn new(storage_path: &str) -> Self {
+        Self {
+            receipts: Mutex::new(HashMap::new()),
+            storage_path: storage_path.to_string(),
+            failed_writes: AtomicU64::new(0),
+        }
+    }
+
+    /// Records the bundle for a new signature
+    ///
+    /// A receipt whose write fails is not kept, so it is neither served nor written later.
+    pub async fn record(&self, bundle: Bundle) -> Result<(), KeyManagementError> {
+        let signature_id = bundle.body.signature_id;
+        let previous = {
+            let mut receipts = self.receipts.lock().await;
+            receipts.insert(signature_id, bundle)
+        };
+        self.save_or_restore(signature_id, previous).await
+    }
+
+    /// Records the bundle for a signature unless one is already recorded under its id, in which
+    /// case the existing bundle is returned and nothing is written
+    pub async fn record_if_absent(&self, bundle: Bundle) -> Result<Option<Bundle>, KeyManagementError> {
+        let signature_id = bundle.body.signature_id;
+        {
+            let mut receipts = self.receipts.lock().await;
+            if let Some(existing) = receipts.get(&signature_id) {
+                return Ok(Some(existing.clone()));
+            }
+            receipts.insert(signature_id, bundle);
+        }
+        self.save_or_restore(signature_id, None).await.map(|()| None)
+    }
+
+    /// Receipt writes that failed since startup
+    pub fn failed_writes(&self) -> u64 {
+        self.failed_writes.load(Ordering::Relaxed)
+    }
+
+    /// Writes the receipts, putting back what `signature_id` held before when the write fails
+    async fn save_or_restore(&self, signature_id: Uuid, previous: Option<Bundle>) -> Result<(), KeyManagementError> {
+        let result = self.save_to_disk().await;
+        if result.is_err() {
+            self.failed_writes.fetch_add(1, Ordering::Relaxed);
+            let mut receipts = self.receipts.lock().await;
+            match previous {
+                Some(previous) => receipts.insert(signature_id, previous),
+                None => receipts.remove(&signature_id),
+            };
+        }
+        result
+    }
+
+    /// Looks up the bundle for a signature id
+    pub async fn get(&self, signature_id: Uuid) -> Option<Bundle> {
+        let receipts = self.receipts.lock().await;
+        receipts.get(&signature_id).cloned()
+    }
+
+    /// The `limit` most recent receipts, newest first
+    pub async fn recent(&self, limit: usize) -> Vec<Bundle> {
+        let receipts = self.receipts.lock().await;
+        let mut recent: Vec<&Bundle> = receipts.values().collect();
+        recent.sort_by_key(|bundle| std::cmp::Reverse((bundle.body.signing_time, bundle.body.signature_id)));
+        recent.into_iter().take(limit).cloned().collect()
+    }
+
+    /// Number of stored receipts
+    pub async fn count(&self) -> usize {
+        self.receipts.lock().await.len()
+    }
+
+    /// Loads receipts from disk on startup
+    pub async fn load_from_disk(&self) -> Result<(), KeyManagementError> {
+        let path = Path::new(&self.storage_path);
+        if !path.exists() {
+            return Ok(());
+        }
+
+        let content = fs::read_to_string(path).await
+            .map_err(|e| KeyManagementError::StorageError(format!("Failed to read receipts file: {}", e)))?;
+        if content.is_empty() {
+            return Ok(());
+        }
+
+        let bundles: Vec<Bundle> = serde_json::from_str(&content)
+            .map_err(|e| KeyManagementError::StorageError(format!("Failed to parse receipts file: {}", e)))?;
+
+        let mut receipts = self.receipts.lock().await;
+        for bundle in bundles {
+            receipts.insert(bundle.body.signature_id, bundle);
+        }
+        Ok(())
+    }
+
+    /// Replaces the receipts file as the keystore is, so a crash mid-write leaves the old file whole
+    async fn save_to_disk(&self) -> Result<(), KeyManagementError> {
+        let receipts = self.receipts.lock().await;
+        let bundles: Vec<&Bundle> = receipts.values().collect();
+
+        let content = serde_json::to_string_pretty(&bundles)
+            .map_err(|e| KeyManagementError::StorageError(format!("Failed to serialize receipts: {}", e)))?;
+        write_durably(Path::new(&self.storage_path), content.as_bytes()).await
+            .map_err(|e| KeyManagementError::StorageError(format!("Failed to write receipts file: {}", e)))?;
+        Ok(())
+    }
+}
+
+/// Creates a receipt store at `RECEIPTS_PATH` (default `receipts.json`)
+pub fn create_default_receipt_store() -> ReceiptStore {
+    let storage_path = std::env::var("RECEIPTS_PATH").unwrap_or_else(|_| "receipts.json".to_string());
+    ReceiptStore::new(&storage_path)
+}
@@ -0,0 +1,206 @@
+//! Detection and repair of weak private key envelopes
+//!
+//! An encrypted key is weak when its salt is shared with another key or shorter than
+//! [`MIN_SALT_LEN`], when it still uses the legacy layout with a separately stored salt, or when
+//! it was encrypted under KDF parameters other than the current ones. `POST /admin/reencrypt-scan`
+//! reports such keys; `POST /admin/reencrypt`, given their passwords, re-encrypts them with a
+//! fresh salt and nonce under the current parameters.
+//!
+//! Rewrapping keeps the key material: the private key is decrypted, checked against the stored
+//! public key, and encrypted again. Each rewrap appends an [`EnvelopeRevision`] to the key's
+//! history, numbering the envelope versions; the key's first envelope is version 1.
+//!
+//! Unencrypted and HSM keys have no envelope and are not scanned.
+
+use crate::config::KdfParams;
+use crate::kdf_stats::{key_kdf, KeyProtection};
+use crate::key_generation::EncryptedKeyEnvelope;
+use crate::models::KeyPair;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+#[cfg(not(feature = "verifier-only"))]
+use crate::{key_generation::encrypt_private_key, key_verification::load_signing_key, models::KeyManagementError};
+
+/// Shortest salt an envelope may carry
+pub const MIN_SALT_LEN: usize = 16;
+
+/// Why an encrypted key should be re-encrypted
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum EnvelopeWeakness {
+    /// Another stored key was encrypted with the same salt
+    SharedSalt,
+    /// The salt is shorter than [`MIN_SALT_LEN`]
+    ShortSalt,
+    /// Nonce and ciphertext with a separately stored salt, from before envelopes
+    LegacyLayout,
+    /// Encrypted under KDF parameters other than the current ones
+    OutdatedKdf,
+}
+
+/// A weakly encrypted key; the salt itself is never reported
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct EnvelopeFinding {
+    pub key_id: Uuid,
+    pub name: String,
+    pub protection: KeyProtection,
+    pub kdf: Option<KdfParams>,
+    pub salt_bytes: usize,
+    pub weaknesses: Vec<EnvelopeWeakness>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub shares_salt_with: Vec<Uuid>,
+}
+
+/// A rewrap of a key's private key into a fresh envelope
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EnvelopeRevision {
+    pub version: u32, // Version of the envelope written; the key's first envelope is 1
+    pub rewrapped_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actor: Option<String>, // Authenticated client that requested the rewrap
+    pub weaknesses: Vec<EnvelopeWeakness>,
+    pub previous_kdf: KdfParams,
+    pub kdf: KdfParams,
+}
+
+impl KeyPair {
+    /// Version of the envelope the private key is stored in
+    pub fn envelope_version(&self) -> u32 {
+        self.envelope_history.last().map_or(1, |revision| revision.version)
+    }
+}
+
+/// Salt an encrypted key was encrypted with, whichever layout it is stored in
+fn key_salt(key_pair: &KeyPair, protection: KeyProtection) -> Option<Vec<u8>> {
+    match protection {
+        KeyProtection::Envelope => {
+            let bytes = base64::engine::general_purpose::STANDARD.decode(key_pair.private_key.expose_for_persistence()).ok()?;
+            EncryptedKeyEnvelope::parse(&bytes).ok().map(|envelope| envelope.salt)
+        }
+        KeyProtection::Legacy => key_pair.salt.as_ref()
+            .and_then(|salt| base64::engine::general_purpose::STANDARD.decode(salt.expose_for_persistence()).ok()),
+        KeyProtection::Unencrypted | KeyProtection::Hsm => None,
+    }
+}
+
+/// Finds the weakly encrypted keys among `keys`, in creation order
+///
+/// Returns the findings with the number of encrypted keys scanned. Envelopes that cannot be
+/// parsed are left to the keystore validation.
+pub fn scan_envelopes(keys: &[KeyPair], current: &KdfParams) -> (usize, Vec<EnvelopeFinding>) {
+    let mut keys: Vec<&KeyPair> = keys.iter().collect();
+    keys.sort_by_key(|key_pair| (key_pair.created_at, key_pair.id));
+
+    let encrypted: Vec<(&KeyPair, KeyProtection, Option<KdfParams>, Vec<u8>)> = keys.into_iter()
+        .filter_map(|key_pair| {
+            let (protection, kdf) = key_kdf(key_pair);
+            Some((key_pair, protection, kdf, key_salt(key_pair, protection)?))
+        })
+        .collect();
+    let mut by_salt: HashMap<&[u8], Vec<Uuid>> = HashMap::new();
+    for (key_pair, _, _, salt) in &encrypted {
+        by_salt.entry(salt.as_slice()).or_default().push(key_pair.id);
+    }
+
+    let findings = encrypted.iter()
+        .filter_map(|(key_pair, protection, kdf, salt)| {
+            let shares_salt_with: Vec<Uuid> = by_salt[salt.as_slice()].iter().copied().filter(|id| *id != key_pair.id).collect();
+            let weaknesses: Vec<EnvelopeWeakness> = [
+                (!shares_salt_with.is_empty(), EnvelopeWeakness::SharedSalt),
+                (salt.len() < MIN_SALT_LEN, EnvelopeWeakness::ShortSalt),
+                (*protection == KeyProtection::Legacy, EnvelopeWeakness::LegacyLayout),
+                (kdf.as_ref() != Some(current), EnvelopeWeakness::OutdatedKdf),
+            ]
+                .into_iter()
+                .filter_map(|(weak, weakness)| weak.then_some(weakness))
+                .collect();
+            (!weaknesses.is_empty()).then(|| EnvelopeFinding {
+                key_id: key_pair.id,
+                name: key_pair.name.clone(),
+                protection: *protection,
+                kdf: *kdf,
+                salt_bytes: salt.len(),
+                weaknesses,
+                shares_salt_with,
+            })
+        })
+        .collect();
+    (encrypted.len(), findings)
+}
+
+/// Decrypts a key with `password` and encrypts the same key material again under `kdf`, with a
+/// fresh salt and nonce
+///
+/// Fails without writing anything if the password is wrong or the decrypted key does not match
+/// the stored public key.
+#[cfg(not(feature = "verifier-only"))]
+pub fn rewrap_private_key(key_pair: &KeyPair, password: &str, kdf: &KdfParams) -> Result<String, KeyManagementError> {
+    if !matches!(key_kdf(key_pair).0, KeyProtection::Envelope | KeyProtection::Legacy) {
+        return Err(KeyManagementError::InvalidRequest(format!("Key {} is not encrypted", key_pair.id)));
+    }
+    let (private_key, salt) = key_pair.signing_secrets();
+    let signing_key = load_signing_key(private_key, salt, &key_pair.kdf.unwrap_or_default(), Some(password))?;
+    let public_key = base64::engine::general_purpose::STANDARD.encode(signing_key.verifying_key().to_bytes());
+    if public_key != key_pair.public_key {
+        return Err(KeyManagementError::InvalidKeyFormat(format!("Private key of {} does not match its public key", key_pair.id)));
+    }
+    encrypt_private_key(&signing_key.to_keypair_bytes(), password, kdf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::KdfAlgorithm;
+    use crate::key_generation::{generate_legacy_test_key_pair, generate_salted_test_key_pair, generate_test_key_pair};
+
+    const CHEAP: KdfParams = KdfParams { algorithm: KdfAlgorithm::Pbkdf2Sha256, iterations: 1_000, memory_kib: 0, parallelism: 0 };
+
+    #[test]
+    fn test_scan_flags_shared_short_legacy_and_outdated_envelopes() {
+        let shared = [3u8; 32];
+        let first = generate_salted_test_key_pair("First", "first-pass", &shared, &CHEAP);
+        let second = generate_salted_test_key_pair("Second", "second-pass", &shared, &CHEAP);
+        let short = generate_salted_test_key_pair("Short", "short-pass", &[9u8; 8], &CHEAP);
+        let healthy = generate_salted_test_key_pair("Healthy", "healthy-pass", &rand::random::<[u8; 32]>(), &CHEAP);
+        let outdated = generate_salted_test_key_pair("Outdated", "outdated-pass", &rand::random::<[u8; 32]>(), &KdfParams { iterations: 500, ..CHEAP });
+        let legacy = generate_legacy_test_key_pair("hunter22");
+        let unencrypted = generate_test_key_pair("Plain").unwrap();
+
+        let keys = [first.clone(), second.clone(), short.clone(), healthy, outdated.clone(), legacy.clone(), unencrypted];
+        let (scanned, findings) = scan_envelopes(&keys, &CHEAP);
+        assert_eq!(scanned, 6);
+        let found: HashMap<Uuid, &EnvelopeFinding> = findings.iter().map(|finding| (finding.key_id, finding)).collect();
+        assert_eq!(found.len(), 5);
+        assert_eq!(found[&first.id].weaknesses, [EnvelopeWeakness::SharedSalt]);
+        assert_eq!(found[&first.id].shares_salt_with, [second.id]);
+        assert_eq!(found[&short.id].weaknesses, [EnvelopeWeakness::ShortSalt]);
+        assert_eq!(found[&short.id].salt_bytes, 8);
+        assert_eq!(found[&outdated.id].weaknesses, [EnvelopeWeakness::OutdatedKdf]);
+        assert!(found[&legacy.id].weaknesses.contains(&EnvelopeWeakness::LegacyLayout));
+    }
+
+    #[cfg(not(feature = "verifier-only"))]
+    #[test]
+    fn test_rewrap_keeps_the_key_and_clears_its_weaknesses() {
+        let weak = generate_salted_test_key_pair("Weak", "weak-pass", &[1u8; 4], &CHEAP);
+        let stronger = KdfParams { iterations: 2_000, ..CHEAP };
+        assert!(matches!(rewrap_private_key(&weak, "wrong-pass", &stronger), Err(KeyManagementError::PrivateKeyDecryptionFailed(_))));
+
+        let rewrapped = KeyPair {
+            private_key: rewrap_private_key(&weak, "weak-pass", &stronger).unwrap().into(),
+            kdf: Some(stronger),
+            ..weak.clone()
+        };
+        let (_, findings) = scan_envelopes(std::slice::from_ref(&rewrapped), &stronger);
+        assert!(findings.is_empty());
+        let (private_key, salt) = rewrapped.signing_secrets();
+        let signing_key = load_signing_key(private_key, salt, &stronger, Some("weak-pass")).unwrap();
+        assert_eq!(base64::engine::general_purpose::STANDARD.encode(signing_key.verifying_key().to_bytes()), weak.public_key);
+
+        let plain = generate_test_key_pair("Plain").unwrap();
+        assert!(matches!(rewrap_private_key(&plain, "any-pass", &stronger), Err(KeyManagementError::InvalidRequest(_))));
+    }
+}
@@ -0,0 +1,429 @@
+//! Route table of the HTTP API
+//!
+//! Built in the library rather than the binary so the end-to-end tests serve exactly the
+//! routes, middleware and layers that production serves.
+//!
+//! There is one route table. Each served API version mounts it under its prefix, and the
+//! unprefixed paths alias `/v1`; see [`crate::api_version`].
+//!
+//! Every route is registered with its method and a one-line summary, which [`endpoint_list`]
+//! returns for `/about` and the startup log. A [`DeploymentProfile`] that leaves a route out
+//! skips registering it, and [`served_endpoints`] lists what the profile serves. Builds with
+//! the `verifier-only` feature compile out the handlers of every route the verifier profile
+//! leaves out, so no code that signs, generates, exports or re-encrypts keys is reachable.
+
+use axum::{
+    extract::{DefaultBodyLimit, Json, Path, State},
+    handler::Handler,
+    http::{Method, StatusCode},
+    routing::{on, MethodFilter},
+    Router,
+    response::IntoResponse,
+};
+use std::sync::{Arc, OnceLock};
+use tower::Layer;
+use tower_http::cors::{Any, CorsLayer};
+
+use crate::api::{self, AppState, StrictJson};
+use crate::api_version::ApiVersion;
+use crate::capabilities::EndpointInfo;
+use crate::config::Config;
+#[cfg(not(feature = "verifier-only"))]
+use crate::deadline::Deadline;
+use crate::key_disclosure::UsableKeysOnly;
+use crate::profile::DeploymentProfile;
+use crate::models::{CheckShareRequest, KeyStatusBatchRequest, VerifyDsseRequest, VerifyManifestRequest, VerifySignatureRequest};
+#[cfg(not(feature = "verifier-only"))]
+use crate::models::{
+    GenerateKeyRequest, SignDocumentRequest, UpdateKeyRequest, RevokeKeyRequest, KeyTransitionRequest,
+    ValidateKeystoreRequest, CertifyKeyRequest, ReadOnlyRequest, CreateShareRequest,
+    EphemeralSignRequest, CompareKeysRequest, SignManifestRequest,
+    ExportWrappedKeyRequest, ImportWrappedKeyRequest, ImportLegacyKeysRequest, ReencryptRequest, SignDsseRequest,
+    DelegateKeyRequest,
+};
+
+/// The handler of a route only the full profile serves
+///
+/// Builds with the `verifier-only` feature compile the handler out and answer the route not
+/// found, as the verifier profile does for any route it leaves out.
+macro_rules! full_only {
+    ($handler:expr) => {{
+        #[cfg(not(feature = "verifier-only"))]
+        let handler = $handler;
+        #[cfg(feature = "verifier-only")]
+        let handler = || async { StatusCode::NOT_FOUND };
+        handler
+    }};
+}
+
+/// Every endpoint with its middleware, serving `state` under the API versions it configures
+pub fn router(state: Arc<AppState>) -> Router {
+    let versions = served_versions(&state.config);
+    router_with_versions(state, &versions)
+}
+
+/// API versions served: `v1`, and `v2` when `INKAN_API_V2` is set
+pub fn served_versions(config: &Config) -> Vec<ApiVersion> {
+    ApiVersion::ALL.iter().copied().filter(|version| *version != ApiVersion::V2 || config.api_v2).collect()
+}
+
+/// Every endpoint with its middleware, serving `state` under the prefix of each of `versions`
+pub fn router_with_versions(state: Arc<AppState>, versions: &[ApiVersion]) -> Router {
+    // Create CORS layer
+    let cors = CorsLayer::new()
+        .allow_origin(Any)
+        .allow_methods(Any)
+        .allow_headers(Any);
+
+    // The version prefix is stripped before the route table sees the path
+    let endpoints = axum::middleware::from_fn_with_state(Arc::<[ApiVersion]>::from(versions), api::route_api_version)
+        .layer(endpoints(state));
+    Router::new()
+        .fallback_service(endpoints)
+        .layer(cors)
+}
+
+/// Route table being built, with a description of every endpoint registered on it
+struct RouteTable {
+    router: Router<Arc<AppState>>,
+    endpoints: Vec<EndpointInfo>,
+    long_running: Vec<String>,
+    profile: DeploymentProfile,
+}
+
+impl RouteTable {
+    fn new(profile: DeploymentProfile) -> Self {
+        Self { router: Router::new(), endpoints: Vec::new(), long_running: Vec::new(), profile }
+    }
+
+    /// Serves `handler` for `method` requests to `path`, described by `summary`, if the profile
+    /// serves it
+    fn route<H, T>(mut self, method: Method, path: &str, summary: &str, handler: H) -> Self
+    where
+        H: Handler<T, Arc<AppState>>,
+        T: 'static,
+    {
+        let filter = MethodFilter::try_from(method.clone()).expect("routes use standard methods");
+        // Routes the profile leaves out are still registered, answering not found, so that a
+        // literal path such as /keys/export is never taken for a served /keys/:key_id
+        self.router = if self.profile.serves(&method, path) {
+            self.router.route(path, on(filter, handler))
+        } else {
+            self.router.route(path, on(filter, || async { StatusCode::NOT_FOUND }))
+        };
+        self.endpoints.push(EndpointInfo { method: method.to_string(), path: path.to_string(), summary: summary.to_string() });
+        self
+    }
+
+    /// Serves a route like [`RouteTable::route`], giving it the long request budget because it
+    /// works through the whole keystore or reads a large request body
+    fn long_route<H, T>(mut self, method: Method, path: &str, summary: &str, handler: H) -> Self
+    where
+        H: Handler<T, Arc<AppState>>,
+        T: 'static,
+    {
+        self.long_running.push(path.to_string());
+        self.route(method, path, summary, handler)
+    }
+}
+
+/// Every endpoint the route table registers under the full profile, in registration order
+pub fn endpoint_list() -> &'static [EndpointInfo] {
+    static ENDPOINTS: OnceLock<Vec<EndpointInfo>> = OnceLock::new();
+    ENDPOINTS.get_or_init(|| route_table(DeploymentProfile::Full).endpoints)
+}
+
+/// Paths of the routes the route table gives the long request budget
+pub fn long_running_routes() -> &'static [String] {
+    static LONG_RUNNING: OnceLock<Vec<String>> = OnceLock::new();
+    LONG_RUNNING.get_or_init(|| route_table(DeploymentProfile::Full).long_running)
+}
+
+/// The endpoints `profile` serves, in registration order
+pub fn served_endpoints(profile: DeploymentProfile) -> Vec<EndpointInfo> {
+    endpoint_list().iter()
+        .filter(|endpoint| endpoint.method.parse().is_ok_and(|method| profile.serves(&method, &endpoint.path)))
+        .cloned()
+        .collect()
+}
+
+/// The route table with its middleware, addressed by unprefixed paths
+fn endpoints(state: Arc<AppState>) -> Router {
+    route_table(state.config.profile).router
+        .layer(axum::middleware::from_fn_with_state(state.clone(), api::event_log_layer))
+        .layer(axum::middleware::from_fn_with_state(state.clone(), api::slo_layer))
+        .layer(axum::middleware::from_fn_with_state(state.clone(), api::key_disclosure_layer))
+        .layer(axum::middleware::from_fn_with_state(state.clone(), api::storage_breaker_layer))
+        .layer(axum::middleware::from_fn_with_state(state.clone(), api::deadline_layer))
+        .layer(axum::middleware::from_fn_with_state(state.clone(), api::read_only_guard))
+        .layer(axum::middleware::from_fn_with_state(state.clone(), api::admin_scope_guard))
+        .layer(axum::middleware::from_fn_with_state(state.clone(), api::request_auth_guard))
+        .layer(axum::middleware::from_fn(api::localize_layer))
+        .layer(axum::middleware::from_fn_with_state(state.clone(), api::field_case_layer))
+        .layer(axum::middleware::from_fn_with_state(state.clone(), api::api_version_layer))
+        .layer(axum::middleware::from_fn(api::sensitive_headers_layer))
+        .layer(api::compression_layer(&state.config))
+        .layer(axum::middleware::from_fn_with_state(state.clone(), api::profile_layer))
+        .with_state(state)
+}
+
+/// Every endpoint and its handler, without middleware
+fn route_table(profile: DeploymentProfile) -> RouteTable {
+    RouteTable::new(profile)
+        .route(Method::GET, "/health", "Health check", || async { "OK" })
+        .route(Method::GET, "/metrics", "Prometheus metrics", |state: State<Arc<AppState>>| async move {
+            api::metrics(state).await
+        })
+        .route(Method::GET, "/health/ready", "Readiness, operating mode, and self-test results", |state: State<Arc<AppState>>| async move {
+            api::readiness(state).await
+        })
+        .route(Method::GET, "/errors", "Error code catalog", api::error_codes)
+        .route(Method::GET, "/capabilities", "Supported algorithms, formats and limits", api::capabilities)
+        .route(Method::GET, "/version", "Service version and build provenance", api::version)
+        .route(Method::GET, "/about", "Build, storage, authentication and every endpoint, for operators", |state: State<Arc<AppState>>| async move {
+            api::about(state).await
+        })
+        .route(Method::GET, "/templates", "List key templates for generation", full_only!(api::list_templates))
+
+        .route(Method::POST, "/keys/generate", "Generate a new key pair (?dry_run=true to validate only)", full_only!(|state: State<Arc<AppState>>, query: axum::extract::Query<api::GenerateKeyQuery>, headers: axum::http::HeaderMap, StrictJson(json): StrictJson<GenerateKeyRequest>| async move {
+            match api::generate_keys_with_headers(state, query, headers, Json(json)).await {
+                Ok(response) => response.into_response(),
+                Err(error) => error.into_response(),
+            }
+        }))
+        .route(Method::GET, "/keys", "List all keys", |state: State<Arc<AppState>>, query: axum::extract::Query<api::ListKeysQuery>, usable_only: Option<axum::Extension<UsableKeysOnly>>| async move {
+            api::list_keys(state, query, usable_only).await
+        })
+        .route(Method::GET, "/keys/search", "Search keys", |state: State<Arc<AppState>>, query: axum::extract::Query<api::ListKeysQuery>, usable_only: Option<axum::Extension<UsableKeysOnly>>| async move {
+            api::search_keys(state, query, usable_only).await
+        })
+        .long_route(Method::GET, "/keys/export", "Download an archive of all public keys", full_only!(|state: State<Arc<AppState>>, query: axum::extract::Query<api::ExportKeysQuery>, deadline: Option<axum::Extension<Deadline>>| async move {
+            api::export_keys(state, query, deadline).await
+        }))
+        .route(Method::GET, "/keys/stats", "Get key statistics", |state: State<Arc<AppState>>| async move {
+            api::get_key_stats(state).await
+        })
+        .route(Method::GET, "/keys/:key_id", "Get key information", |state: State<Arc<AppState>>, Path(key_id): Path<uuid::Uuid>| async move {
+            match api::get_public_key(state, Path(key_id)).await {
+                Ok(response) => response.into_response(),
+                Err(error) => error.into_response(),
+            }
+        })
+        .route(Method::PUT, "/keys/:key_id", "Update key information (alias of PATCH)", full_only!(|state: State<Arc<AppState>>, Path(key_id): Path<uuid::Uuid>, client: Option<axum::Extension<api::AuthenticatedClient>>, StrictJson(json): StrictJson<UpdateKeyRequest>| async move {
+            match api::update_key(state, Path(key_id), Json(json), client.map(|axum::Extension(client)| client)).await {
+                Ok(response) => response.into_response(),
+                Err(status) => status.into_response(),
+            }
+        }))
+        .route(Method::PATCH, "/keys/:key_id", "Update key information", full_only!(|state: State<Arc<AppState>>, Path(key_id): Path<uuid::Uuid>, client: Option<axum::Extension<api::AuthenticatedClient>>, StrictJson(json): StrictJson<UpdateKeyRequest>| async move {
+            match api::update_key(state, Path(key_id), Json(json), client.map(|axum::Extension(client)| client)).await {
+                Ok(response) => response.into_response(),
+                Err(status) => status.into_response(),
+            }
+        }))
+        .route(Method::POST, "/keys/:key_id/revoke", "Revoke a key (now or scheduled)", full_only!(|state: State<Arc<AppState>>, Path(key_id): Path<uuid::Uuid>, client: Option<axum::Extension<api::AuthenticatedClient>>, StrictJson(json): StrictJson<RevokeKeyRequest>| async move {
+            match api::revoke_key(state, Path(key_id), Json(json), client.map(|axum::Extension(client)| client)).await {
+                Ok(response) => response.into_response(),
+                Err(status) => status.into_response(),
+            }
+        }))
+        .route(Method::POST, "/keys/:key_id/suspend", "Suspend a key until it is resumed", full_only!(|state: State<Arc<AppState>>, Path(key_id): Path<uuid::Uuid>, client: Option<axum::Extension<api::AuthenticatedClient>>, StrictJson(json): StrictJson<KeyTransitionRequest>| async move {
+            match api::suspend_key(state, Path(key_id), Json(json), client.map(|axum::Extension(client)| client)).await {
+                Ok(response) => response.into_response(),
+                Err(error) => error.into_response(),
+            }
+        }))
+        .route(Method::POST, "/keys/:key_id/resume", "Resume a suspended key", full_only!(|state: State<Arc<AppState>>, Path(key_id): Path<uuid::Uuid>, client: Option<axum::Extension<api::AuthenticatedClient>>, StrictJson(json): StrictJson<KeyTransitionRequest>| async move {
+            match api::resume_key(state, Path(key_id), Json(json), client.map(|axum::Extension(client)| client)).await {
+                Ok(response) => response.into_response(),
+                Err(error) => error.into_response(),
+            }
+        }))
+        .route(Method::GET, "/keys/deleted", "List soft-deleted keys", full_only!(|state: State<Arc<AppState>>| async move {
+            api::list_deleted_keys(state).await
+        }))
+        .route(Method::GET, "/keys/archived", "List archived keys", full_only!(|state: State<Arc<AppState>>| async move {
+            api::list_archived_keys(state).await
+        }))
+        .route(Method::GET, "/keys/manifest", "Signed manifest of key fingerprints, names, states and expiries", full_only!(|state: State<Arc<AppState>>| async move {
+            api::get_key_manifest(state).await
+        }))
+        .route(Method::GET, "/keys/pinset", "Pin set of trusted public keys as JSON, Rust, Swift or Kotlin", |state: State<Arc<AppState>>, query: axum::extract::Query<api::PinsetQuery>| async move {
+            api::get_pinset(state, query).await
+        })
+        .long_route(Method::POST, "/keys/status/batch", "Status of up to 100 keys at once", |state: State<Arc<AppState>>, StrictJson(json): StrictJson<KeyStatusBatchRequest>| async move {
+            api::key_status_batch(state, Json(json)).await
+        })
+        .route(Method::POST, "/keys/compare", "Compare keys against another instance's manifest", full_only!(|state: State<Arc<AppState>>, StrictJson(json): StrictJson<CompareKeysRequest>| async move {
+            api::compare_keys(state, Json(json)).await
+        }))
+        .route(Method::POST, "/keys/import-wrapped", "Import a key wrapped for this instance", full_only!(|state: State<Arc<AppState>>, StrictJson(json): StrictJson<ImportWrappedKeyRequest>| async move {
+            match api::import_wrapped_key(state, Json(json)).await {
+                Ok(response) => response.into_response(),
+                Err(error) => error.into_response(),
+            }
+        }))
+        .route(Method::POST, "/keys/import-legacy", "Import keys from a legacy JSON export", full_only!(|state: State<Arc<AppState>>, StrictJson(json): StrictJson<ImportLegacyKeysRequest>| async move {
+            match api::import_legacy_keys(state, Json(json)).await {
+                Ok(response) => response.into_response(),
+                Err(error) => error.into_response(),
+            }
+        }))
+        .route(Method::DELETE, "/keys/:key_id", "Soft-delete a key", full_only!(|state: State<Arc<AppState>>, Path(key_id): Path<uuid::Uuid>, client: Option<axum::Extension<api::AuthenticatedClient>>| async move {
+            match api::delete_key(state, Path(key_id), client.map(|axum::Extension(client)| client)).await {
+                Ok(response) => response.into_response(),
+                Err(error) => error.into_response(),
+            }
+        }))
+        .route(Method::POST, "/keys/:key_id/restore", "Restore a soft-deleted key", full_only!(|state: State<Arc<AppState>>, Path(key_id): Path<uuid::Uuid>| async move {
+            match api::restore_key(state, Path(key_id)).await {
+                Ok(response) => response.into_response(),
+                Err(error) => error.into_response(),
+            }
+        }))
+        .route(Method::POST, "/keys/:key_id/export", "Wrap a key for another instance's transport key", full_only!(|state: State<Arc<AppState>>, Path(key_id): Path<uuid::Uuid>, StrictJson(json): StrictJson<ExportWrappedKeyRequest>| async move {
+            match api::export_wrapped_key(state, Path(key_id), Json(json)).await {
+                Ok(response) => response.into_response(),
+                Err(error) => error.into_response(),
+            }
+        }))
+        .route(Method::DELETE, "/keys/:key_id/revoke-schedule", "Cancel a scheduled revocation", full_only!(|state: State<Arc<AppState>>, Path(key_id): Path<uuid::Uuid>| async move {
+            match api::cancel_scheduled_revocation(state, Path(key_id)).await {
+                Ok(response) => response.into_response(),
+                Err(error) => error.into_response(),
+            }
+        }))
+        .route(Method::POST, "/keys/:key_id/certify", "Certify another key with this key", full_only!(|state: State<Arc<AppState>>, Path(key_id): Path<uuid::Uuid>, StrictJson(json): StrictJson<CertifyKeyRequest>| async move {
+            match api::certify_key(state, Path(key_id), Json(json)).await {
+                Ok(response) => response.into_response(),
+                Err(error) => error.into_response(),
+            }
+        }))
+        .route(Method::GET, "/keys/:key_id/certifications", "List issued and received certifications", |state: State<Arc<AppState>>, Path(key_id): Path<uuid::Uuid>| async move {
+            match api::get_key_certifications(state, Path(key_id)).await {
+                Ok(response) => response.into_response(),
+                Err(status) => status.into_response(),
+            }
+        })
+        .route(Method::POST, "/keys/:key_id/delegate", "Issue a short-lived signing token for a CI job", full_only!(|state: State<Arc<AppState>>, Path(key_id): Path<uuid::Uuid>, client: Option<axum::Extension<api::AuthenticatedClient>>, StrictJson(json): StrictJson<DelegateKeyRequest>| async move {
+            api::delegate_key(state, Path(key_id), client.map(|axum::Extension(client)| client), Json(json)).await
+        }))
+        .route(Method::GET, "/keys/:key_id/delegations", "List a key's outstanding delegations", full_only!(|state: State<Arc<AppState>>, Path(key_id): Path<uuid::Uuid>| async move {
+            api::list_delegations(state, Path(key_id)).await
+        }))
+        .route(Method::DELETE, "/delegations/:delegation_id", "Revoke a delegation", full_only!(|state: State<Arc<AppState>>, Path(delegation_id): Path<uuid::Uuid>, client: Option<axum::Extension<api::AuthenticatedClient>>| async move {
+            api::revoke_delegation(state, Path(delegation_id), client.map(|axum::Extension(client)| client)).await
+        }))
+        .route(Method::GET, "/keys/:key_id/status", "Whether a key is still good right now, optionally notary-signed", |state: State<Arc<AppState>>, Path(key_id): Path<uuid::Uuid>, query: axum::extract::Query<api::KeyStatusQuery>| async move {
+            api::get_key_status(state, Path(key_id), query).await
+        })
+        .route(Method::GET, "/keys/:key_id/public", "Get public key", |state: State<Arc<AppState>>, Path(key_id): Path<uuid::Uuid>, query: axum::extract::Query<api::PublicKeyQuery>| async move {
+            api::get_public_key_formatted(state, Path(key_id), query).await
+        })
+        .route(Method::GET, "/keys/:key_id/public/permalink", "Redirect to the public key's content-addressed URL", |state: State<Arc<AppState>>, Path(key_id): Path<uuid::Uuid>, query: axum::extract::Query<api::PublicKeyPermalinkQuery>| async move {
+            api::get_public_key_permalink(state, Path(key_id), query).await
+        })
+        .route(Method::GET, "/public/:fingerprint", "Public key by fingerprint (.raw, .pem or .jwk), cacheable forever", |state: State<Arc<AppState>>, Path(fingerprint): Path<String>, query: axum::extract::Query<api::PublicKeyPermalinkQuery>| async move {
+            api::get_public_key_by_fingerprint(state, Path(fingerprint), query).await
+        })
+        .route(Method::POST, "/sign", "Sign a document with a private key (or a delegation token)", full_only!(|state: State<Arc<AppState>>, query: axum::extract::Query<api::SignQuery>, headers: axum::http::HeaderMap, client: Option<axum::Extension<api::AuthenticatedClient>>, StrictJson(json): StrictJson<SignDocumentRequest>| async move {
+            match api::sign_document_with_query(state, query, headers, client, Json(json)).await {
+                Ok(response) => response.into_response(),
+                Err(status) => status.into_response(),
+            }
+        }))
+        .long_route(Method::POST, "/sign/raw", "Sign a document streamed as the request body", full_only!(|state: State<Arc<AppState>>, query: axum::extract::Query<api::RawSignQuery>, headers: axum::http::HeaderMap, client: Option<axum::Extension<api::AuthenticatedClient>>, body: axum::body::Body| async move {
+            match api::sign_raw(state, query, headers, client, body).await {
+                Ok(response) => response.into_response(),
+                Err(status) => status.into_response(),
+            }
+        }))
+        .route(Method::POST, "/sign/ephemeral", "Sign with a single-use key generated for the request", full_only!(|state: State<Arc<AppState>>, StrictJson(json): StrictJson<EphemeralSignRequest>| async move {
+            match api::sign_ephemeral(state, Json(json)).await {
+                Ok(response) => response.into_response(),
+                Err(error) => error.into_response(),
+            }
+        }))
+        .route(Method::POST, "/sign/dsse", "Sign a payload into a DSSE envelope", full_only!(|state: State<Arc<AppState>>, headers: axum::http::HeaderMap, client: Option<axum::Extension<api::AuthenticatedClient>>, StrictJson(json): StrictJson<SignDsseRequest>| async move {
+            match api::sign_dsse(state, headers, client, Json(json)).await {
+                Ok(response) => response.into_response(),
+                Err(error) => error.into_response(),
+            }
+        }))
+        .long_route(Method::POST, "/sign/manifest", "Sign a manifest of file paths and SHA-256 hashes", full_only!(|state: State<Arc<AppState>>, headers: axum::http::HeaderMap, client: Option<axum::Extension<api::AuthenticatedClient>>, StrictJson(json): StrictJson<SignManifestRequest>| async move {
+            match api::sign_manifest(state, headers, client, Json(json)).await {
+                Ok(response) => response.into_response(),
+                Err(error) => error.into_response(),
+            }
+        }))
+        .route(Method::GET, "/signatures/by-id/:signature_id", "Look up a recorded signature", |state: State<Arc<AppState>>, Path(signature_id): Path<uuid::Uuid>| async move {
+            api::get_signature_record(state, Path(signature_id)).await
+        })
+        .route(Method::GET, "/signatures/:signature_id/bundle", "Get a signature's verification bundle", |state: State<Arc<AppState>>, Path(signature_id): Path<uuid::Uuid>, query: axum::extract::Query<api::BundleQuery>| async move {
+            api::get_signature_bundle(state, Path(signature_id), query).await
+        })
+        .route(Method::POST, "/verify", "Verify a document signature", |state: State<Arc<AppState>>, caller: api::VerifyCaller, StrictJson(json): StrictJson<VerifySignatureRequest>| async move {
+            api::verify_signature_from(state, caller, Json(json)).await
+        })
+        .route(Method::POST, "/verify/dsse", "Verify a DSSE envelope against stored or supplied keys", |state: State<Arc<AppState>>, caller: api::VerifyCaller, StrictJson(json): StrictJson<VerifyDsseRequest>| async move {
+            api::verify_dsse(state, caller, Json(json)).await
+        })
+        .long_route(Method::POST, "/verify/manifest", "Verify a manifest signature and check files against it", |state: State<Arc<AppState>>, caller: api::VerifyCaller, StrictJson(json): StrictJson<VerifyManifestRequest>| async move {
+            api::verify_manifest(state, caller, Json(json)).await
+        })
+        // The upload is bounded by INKAN_VERIFY_ARCHIVE_MAX_BYTES in the handler instead
+        .long_route(Method::POST, "/verify/archive", "Verify every entry of a zip archive against a manifest of signatures", (|state: State<Arc<AppState>>, caller: api::VerifyCaller, headers: axum::http::HeaderMap, multipart| async move {
+            api::verify_archive(state, caller, headers, multipart).await
+        }).layer(DefaultBodyLimit::disable()))
+        .route(Method::POST, "/verifications/share", "Publish a signature behind a verification link", full_only!(|state: State<Arc<AppState>>, StrictJson(json): StrictJson<CreateShareRequest>| async move {
+            api::create_share(state, Json(json)).await
+        }))
+        .route(Method::GET, "/verifications/:token", "Public data behind a verification link", |state: State<Arc<AppState>>, Path(token): Path<String>| async move {
+            api::get_share(state, Path(token)).await
+        })
+        .route(Method::DELETE, "/verifications/:token", "Revoke a verification link", full_only!(|state: State<Arc<AppState>>, Path(token): Path<String>| async move {
+            api::revoke_share(state, Path(token)).await
+        }))
+        .route(Method::POST, "/verifications/:token/check", "Check a document against a verification link", |state: State<Arc<AppState>>, Path(token): Path<String>, StrictJson(json): StrictJson<CheckShareRequest>| async move {
+            api::check_share(state, Path(token), Json(json)).await
+        })
+        .long_route(Method::POST, "/admin/validate", "Check keystore integrity (optionally repair)", full_only!(|state: State<Arc<AppState>>, deadline: Option<axum::Extension<Deadline>>, StrictJson(json): StrictJson<ValidateKeystoreRequest>| async move {
+            api::validate_keystore(state, deadline, Json(json)).await
+        }))
+        .long_route(Method::GET, "/admin/kdf-calibration", "Suggest KDF parameters for this host", full_only!(|state: State<Arc<AppState>>, query: axum::extract::Query<api::KdfCalibrationQuery>| async move {
+            api::kdf_calibration(state, query).await
+        }))
+        .route(Method::GET, "/admin/overview", "Stats, expiring keys, recent signatures and task status in one call", full_only!(|state: State<Arc<AppState>>| async move {
+            api::admin_overview(state).await
+        }))
+        .route(Method::GET, "/reports/usage", "Keys generated, signatures, verifications and revocations per group between two dates", full_only!(|state: State<Arc<AppState>>, query: axum::extract::Query<api::UsageReportQuery>, headers: axum::http::HeaderMap| async move {
+            api::usage_report(state, query, headers).await
+        }))
+        .route(Method::GET, "/events/replay", "Operation events after a sequence number, to backfill missed webhooks", full_only!(|state: State<Arc<AppState>>, query: axum::extract::Query<api::EventReplayQuery>| async move {
+            api::replay_events(state, query).await
+        }))
+        .route(Method::GET, "/admin/transport-key", "This instance's transport public key", full_only!(|state: State<Arc<AppState>>| async move {
+            api::get_transport_key(state).await
+        }))
+        .route(Method::GET, "/admin/slo", "Generate, sign and verify latency percentiles against their objectives", full_only!(|state: State<Arc<AppState>>| async move {
+            api::slo_report(state).await
+        }))
+        .route(Method::GET, "/admin/kdf-report", "Group keys by their stored KDF parameters", full_only!(|state: State<Arc<AppState>>| async move {
+            api::kdf_report(state).await
+        }))
+        .long_route(Method::POST, "/admin/reencrypt-scan", "Find keys with shared or short salts or outdated envelopes", full_only!(|state: State<Arc<AppState>>| async move {
+            api::reencrypt_scan(state).await
+        }))
+        .long_route(Method::POST, "/admin/reencrypt", "Re-encrypt weak envelopes with fresh salts and current parameters", full_only!(|state: State<Arc<AppState>>, client: Option<axum::Extension<api::AuthenticatedClient>>, StrictJson(json): StrictJson<ReencryptRequest>| async move {
+            api::reencrypt_keys(state, client.map(|axum::Extension(client)| client), Json(json)).await
+        }))
+        .long_route(Method::POST, "/admin/self-test", "Run the self-test on demand", full_only!(|state: State<Arc<AppState>>| async move {
+            match api::self_test(state).await {
+                Ok(response) => response.into_response(),
+                Err(error) => error.into_response(),
+            }
+        }))
+        .route(Method::POST, "/admin/read-only", "Switch read-only mode", full_only!(|state: State<Arc<AppState>>, StrictJson(json): StrictJson<ReadOnlyRequest>| async move {
+            api::set_read_only(state, Json(json)).await
+        }))
+}
@@ -0,0 +1,98 @@
+//! Secret key material held in memory
+//!
+//! The private key and salt of a [`crate::models::KeyPair`] are [`SecretString`]s, which
+//! implement neither `Serialize` nor `Debug`: code that would put them in a response or a log
+//! line does not compile. Reading one takes an explicit [`SecretString::expose_for_signing`] or
+//! [`SecretString::expose_for_persistence`], each traced on the `inkan::secret_access` target with
+//! the calling code's location, so every read path shows up when that target is enabled. The
+//! only serialized form is the keystore's own, private to `crate::key_storage`.
+
+use std::panic::Location;
+use zeroize::Zeroize;
+
+/// A secret string, zeroized when dropped
+#[derive(Clone, Default)]
+pub struct SecretString(String);
+
+impl SecretString {
+    pub fn new(value: String) -> Self {
+        Self(value)
+    }
+
+    /// Whether there is no secret, as for keys held in an HSM or discarded ephemeral keys
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The secret, for loading a signing key from it
+    #[track_caller]
+    pub fn expose_for_signing(&self) -> &str {
+        trace_access("signing", Location::caller());
+        &self.0
+    }
+
+    /// The secret in its stored encoding, for writing, moving or checking the keystore
+    #[track_caller]
+    pub fn expose_for_persistence(&self) -> &str {
+        trace_access("persistence", Location::caller());
+        &self.0
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+fn trace_access(purpose: &str, caller: &Location<'_>) {
+    tracing::trace!(target: "inkan::secret_access", purpose, caller = %caller, "Secret key material read");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_reads_are_traced_with_their_caller() {
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::TRACE)
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        let secret = SecretString::from("c2VjcmV0".to_string());
+        tracing::subscriber::with_default(subscriber, || {
+            assert_eq!(secret.expose_for_signing(), "c2VjcmV0");
+            assert!(!secret.is_empty());
+        });
+
+        let log = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(log.lines().count(), 1);
+        assert!(log.contains("purpose=\"signing\"") && log.contains("src/secret/mod.rs:"), "{}", log);
+        assert!(!log.contains("c2VjcmV0"));
+    }
+}
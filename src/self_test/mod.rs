@@ -0,0 +1,221 @@
+//! Startup self-test
+//!
+//! Exercises the generate → encrypt → decrypt → sign → verify path and the storage backend
+//! with throwaway material before the service accepts traffic, so a broken build or backend
+//! fails loudly at startup instead of through client errors.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+// Running the self-test generates and signs, which verifier-only builds compile out; they
+// keep the report types for readiness
+#[cfg(not(feature = "verifier-only"))]
+use crate::{
+    config::KdfParams,
+    key_generation::generate_key_pair_with_kdf,
+    key_storage::KeyStorage,
+    key_verification::{create_document_hash, load_signing_key, sign_document_hash, verify_signature},
+    models::{DocumentHash, GenerateKeyRequest, KeyManagementError, KeyPair, VerifySignatureRequest},
+};
+#[cfg(not(feature = "verifier-only"))]
+use std::time::Instant;
+
+/// Name given to the sentinel key written to the keystore during the storage check
+pub const SENTINEL_KEY_NAME: &str = "__inkan_self_test__";
+
+#[cfg(not(feature = "verifier-only"))]
+const PAYLOAD: &str = "inkan self-test payload";
+#[cfg(not(feature = "verifier-only"))]
+const PASSWORD: &str = "inkan-self-test-password";
+
+/// Outcome of one self-test step
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct SelfTestCheck {
+    pub name: String,
+    pub passed: bool,
+    pub duration_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Outcome of a full self-test run
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct SelfTestReport {
+    pub passed: bool,
+    pub ran_at: DateTime<Utc>,
+    pub checks: Vec<SelfTestCheck>,
+}
+
+impl SelfTestReport {
+    /// Describes every failed check, for startup errors and logs
+    pub fn failure_summary(&self) -> String {
+        self.checks.iter()
+            .filter(|check| !check.passed)
+            .map(|check| format!("{}: {}", check.name, check.error.as_deref().unwrap_or("failed")))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
+
+#[cfg(not(feature = "verifier-only"))]
+fn record(checks: &mut Vec<SelfTestCheck>, name: &str, started: Instant, result: Result<(), String>) {
+    checks.push(SelfTestCheck {
+        name: name.to_string(),
+        passed: result.is_ok(),
+        duration_ms: started.elapsed().as_secs_f64() * 1000.0,
+        error: result.err(),
+    });
+}
+
+#[cfg(not(feature = "verifier-only"))]
+fn timed<T>(checks: &mut Vec<SelfTestCheck>, name: &str, step: impl FnOnce() -> Result<T, String>) -> Option<T> {
+    let started = Instant::now();
+    match step() {
+        Ok(value) => {
+            record(checks, name, started, Ok(()));
+            Some(value)
+        }
+        Err(e) => {
+            record(checks, name, started, Err(e));
+            None
+        }
+    }
+}
+
+#[cfg(not(feature = "verifier-only"))]
+async fn check_storage(storage: &KeyStorage, sentinel: &KeyPair) -> Result<(), String> {
+    // Keystore writes fail soft, so a failed write shows up as degraded persistence
+    let written = storage.store_key(sentinel.clone()).await
+        .map_err(|e| e.to_string())
+        .and_then(|()| match storage.persistence_status() {
+            status if status.degraded => Err(status.last_error.unwrap_or_default()),
+            _ => Ok(()),
+        })
+        .map_err(|e| format!("write failed: {}", e));
+    let read = match written {
+        Ok(()) => storage.get_key_record(sentinel.id).await.map_err(|e| format!("read failed: {}", e)),
+        Err(e) => Err(e),
+    };
+    // Always try to remove the sentinel, even if it only made it into memory
+    let removed = storage.remove_key(sentinel.id).await;
+
+    let read = read?;
+    if read.public_key != sentinel.public_key || read.private_key.expose_for_persistence() != sentinel.private_key.expose_for_persistence() {
+        return Err("read back a different sentinel record".to_string());
+    }
+    removed.map(|_| ()).map_err(|e| format!("delete failed: {}", e))
+}
+
+/// Runs every self-test step against the configured KDF and storage backend
+///
+/// Steps depend on their predecessors, so a failure skips the remaining steps and the report
+/// only lists those that ran.
+#[cfg(not(feature = "verifier-only"))]
+pub async fn run_self_test(storage: &KeyStorage, kdf: &KdfParams) -> SelfTestReport {
+    let ran_at = Utc::now();
+    let mut checks = Vec::new();
+
+    let key_pair = timed(&mut checks, "generate", || {
+        generate_key_pair_with_kdf(GenerateKeyRequest {
+            name: SENTINEL_KEY_NAME.to_string(),
+            description: None,
+            password: Some(PASSWORD.to_string()),
+            expires_at: None,
+            tags: None,
+            key_strength: None,
+            hsm: None,
+            generate_password: false,
+            template: None,
+            environment: None,
+            fast: false,
+            exportable: None,
+            default_output_format: None,
+            default_hash_algorithm: None,
+            default_encoding: None,
+        }, kdf).map_err(|e| e.to_string())
+    });
+
+    let signing_key = key_pair.as_ref().and_then(|key_pair| timed(&mut checks, "encrypt_decrypt", || {
+        let kdf = key_pair.kdf.unwrap_or_default();
+        let (private_key, salt) = key_pair.signing_secrets();
+        if load_signing_key(private_key, salt, &kdf, Some("wrong password")).is_ok() {
+            return Err("private key decrypted with the wrong password".to_string());
+        }
+        load_signing_key(private_key, salt, &kdf, Some(PASSWORD)).map_err(|e| e.to_string())
+    }));
+
+    let signed = match (&key_pair, &signing_key) {
+        (Some(key_pair), Some(signing_key)) => timed(&mut checks, "sign_verify", || {
+            let document_hash = DocumentHash::digest(PAYLOAD.as_bytes());
+            let signature = sign_document_hash(signing_key, &document_hash, None, None, None).map_err(|e| e.to_string())?.to_string();
+            let request = |document_hash: String| VerifySignatureRequest {
+                public_key: key_pair.public_key.clone(),
+                document_hash: Some(document_hash),
+                signature: signature.clone(),
+                ..Default::default()
+            };
+            match (verify_signature(&request(document_hash.to_string())), verify_signature(&request(create_document_hash("tampered")))) {
+                (Ok(true), Ok(false)) => Ok(()),
+                (Ok(true), _) => Err("signature also verified for a different payload".to_string()),
+                (Ok(false), _) => Err("signature over the fixed payload did not verify".to_string()),
+                (Err(e), _) => Err(e.to_string()),
+            }
+        }),
+        _ => None,
+    };
+
+    if let (Some(sentinel), Some(())) = (&key_pair, signed) {
+        let started = Instant::now();
+        let result = check_storage(storage, sentinel).await;
+        record(&mut checks, "storage", started, result);
+    }
+
+    SelfTestReport {
+        passed: checks.len() == 4 && checks.iter().all(|check| check.passed),
+        ran_at,
+        checks,
+    }
+}
+
+/// Runs the self-test and refuses to continue unless every step passed
+#[cfg(not(feature = "verifier-only"))]
+pub async fn startup_self_test(storage: &KeyStorage, kdf: &KdfParams) -> Result<SelfTestReport, KeyManagementError> {
+    let report = run_self_test(storage, kdf).await;
+    if !report.passed {
+        return Err(KeyManagementError::InternalError(format!("Startup self-test failed: {}", report.failure_summary())));
+    }
+    Ok(report)
+}
+
+#[cfg(all(test, not(feature = "verifier-only")))]
+mod tests {
+    use super::*;
+    use crate::config::MIN_PBKDF2_ITERATIONS;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_self_test_passes_and_leaves_no_sentinel() {
+        let dir = tempdir().unwrap();
+        let storage = KeyStorage::new(dir.path().join("keys.json").to_str().unwrap());
+
+        let report = startup_self_test(&storage, &KdfParams::pbkdf2(MIN_PBKDF2_ITERATIONS)).await.unwrap();
+        assert_eq!(report.checks.len(), 4);
+        assert_eq!(storage.key_count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_startup_refuses_when_a_component_fails() {
+        let dir = tempdir().unwrap();
+        // A keystore path inside a missing directory makes the storage backend fail every write
+        let storage = KeyStorage::new(dir.path().join("missing").join("keys.json").to_str().unwrap());
+
+        let error = startup_self_test(&storage, &KdfParams::pbkdf2(MIN_PBKDF2_ITERATIONS)).await.unwrap_err();
+        assert!(error.to_string().contains("storage: write failed"), "{}", error);
+        assert_eq!(storage.key_count().await, 0);
+
+        // KDF parameters the crypto library rejects fail generation and skip the later steps
+        let report = run_self_test(&storage, &KdfParams::argon2id(1, 1, 1)).await;
+        assert!(!report.passed);
+        assert_eq!(report.checks.len(), 1);
+        assert_eq!(report.checks[0].name, "generate");
+    }
+}
@@ -0,0 +1,170 @@
+//! Shareable verification links
+//!
+//! A share publishes the public half of a signature receipt under a random opaque token, so a
+//! counterparty given the link can see who signed what and when, and check their copy of the
+//! document against it. Only the SHA-256 of each token is stored, so the shares file alone
+//! cannot be used to open a link. Shares expire after their TTL and can be revoked early; every
+//! request naming a token counts against that token's per-minute allowance.
+
+use crate::bundle::Bundle;
+use crate::models::KeyManagementError;
+use crate::rate_limit::RequestCounter;
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::Path;
+use tokio::fs;
+use tokio::sync::Mutex;
+
+/// Random bytes in a share token
+pub const SHARE_TOKEN_BYTES: usize = 32;
+
+/// Length of the window requests against a token are counted in
+pub const SHARE_RATE_WINDOW_SECS: i64 = 60;
+
+/// A published signature receipt
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Share {
+    pub token_hash: String, // Hex SHA-256 of the token
+    pub bundle: Bundle,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl Share {
+    /// Whether the link still opens at `now`
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.revoked_at.is_none() && now < self.expires_at
+    }
+}
+
+/// Generates a new share token: 32 random bytes, base64url without padding
+pub fn generate_share_token() -> String {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(rand::random::<[u8; SHARE_TOKEN_BYTES]>())
+}
+
+/// Hex SHA-256 of a share token, the form it is stored and looked up in
+pub fn share_token_hash(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// File-backed store of verification shares
+pub struct ShareStore {
+    shares: Mutex<HashMap<String, Share>>,
+    /// Requests against each token, counted under its hash
+    requests: RequestCounter,
+    storage_path: String,
+}
+
+impl ShareStore {
+    /// Creates a new share store persisted at `storage_path`
+    pub fn new(storage_path: &str) -> Self {
+        Self {
+            shares: Mutex::new(HashMap::new()),
+            requests: RequestCounter::in_memory(),
+            storage_path: storage_path.to_string(),
+        }
+    }
+
+    /// Counts requests against tokens with `requests`, which other instances may share
+    pub fn with_request_counter(mut self, requests: RequestCounter) -> Self {
+        self.requests = requests;
+        self
+    }
+
+    /// Publishes a bundle for `ttl`, returning the new token and its share
+    ///
+    /// Shares that have expired are dropped at the same time.
+    pub async fn create(&self, bundle: Bundle, now: DateTime<Utc>, ttl: Duration) -> Result<(String, Share), KeyManagementError> {
+        let token = generate_share_token();
+        let share = Share {
+            token_hash: share_token_hash(&token),
+            bundle,
+            created_at: now,
+            expires_at: now + ttl,
+            revoked_at: None,
+        };
+        {
+            let mut shares = self.shares.lock().await;
+            shares.retain(|_, share| now < share.expires_at);
+            shares.insert(share.token_hash.clone(), share.clone());
+        }
+        self.save_to_disk().await?;
+        Ok((token, share))
+    }
+
+    /// Looks up the share a token opens, if it is still active
+    pub async fn get(&self, token: &str, now: DateTime<Utc>) -> Option<Share> {
+        let shares = self.shares.lock().await;
+        shares.get(&share_token_hash(token)).filter(|share| share.is_active(now)).cloned()
+    }
+
+    /// Revokes the share a token opens; returns false if it was not active
+    pub async fn revoke(&self, token: &str, now: DateTime<Utc>) -> Result<bool, KeyManagementError> {
+        {
+            let mut shares = self.shares.lock().await;
+            match shares.get_mut(&share_token_hash(token)) {
+                Some(share) if share.is_active(now) => share.revoked_at = Some(now),
+                _ => return Ok(false),
+            }
+        }
+        self.save_to_disk().await.map(|()| true)
+    }
+
+    /// Counts a request against a token
+    ///
+    /// Returns the seconds until the token's window resets if it has already had `limit`
+    /// requests in it. Unknown tokens are counted too, so guessing is limited the same way.
+    pub async fn take_request(&self, token: &str, now: DateTime<Utc>, limit: u32) -> Result<(), i64> {
+        self.requests.take(&format!("share:{}", share_token_hash(token)), limit, Duration::seconds(SHARE_RATE_WINDOW_SECS), now).await
+    }
+
+    /// Number of stored shares, including expired and revoked ones not yet dropped
+    pub async fn count(&self) -> usize {
+        self.shares.lock().await.len()
+    }
+
+    /// Loads shares from disk on startup
+    pub async fn load_from_disk(&self) -> Result<(), KeyManagementError> {
+        let path = Path::new(&self.storage_path);
+        if !path.exists() {
+            return Ok(());
+        }
+
+        let content = fs::read_to_string(path).await
+            .map_err(|e| KeyManagementError::StorageError(format!("Failed to read shares file: {}", e)))?;
+        if content.is_empty() {
+            return Ok(());
+        }
+
+        let loaded: Vec<Share> = serde_json::from_str(&content)
+            .map_err(|e| KeyManagementError::StorageError(format!("Failed to parse shares file: {}", e)))?;
+
+        let mut shares = self.shares.lock().await;
+        for share in loaded {
+            shares.insert(share.token_hash.clone(), share);
+        }
+        Ok(())
+    }
+
+    async fn save_to_disk(&self) -> Result<(), KeyManagementError> {
+        let shares = self.shares.lock().await;
+        let shares: Vec<&Share> = shares.values().collect();
+
+        let content = serde_json::to_string_pretty(&shares)
+            .map_err(|e| KeyManagementError::StorageError(format!("Failed to serialize shares: {}", e)))?;
+        fs::write(&self.storage_path, content).await
+            .map_err(|e| KeyManagementError::StorageError(format!("Failed to write shares file: {}", e)))?;
+        Ok(())
+    }
+}
+
+/// Creates a share store at `SHARES_PATH` (default `shares.json`)
+pub fn create_default_share_store() -> ShareStore {
+    let storage_path = std::env::var("SHARES_PATH").unwrap_or_else(|_| "shares.json".to_string());
+    ShareStore::new(&storage_path)
+}
@@ -0,0 +1,262 @@
+//! Pre-sign policy hook
+//!
+//! An external approval service can veto signatures. When `INKAN_SIGN_POLICY_URL` is set, each
+//! signature is first described to the service — key id, fingerprint, document hash, context
+//! and requesting client, never the document or any key material — and made only if the service
+//! allows it. Decisions are cached for a short TTL. A service that fails or does not answer in
+//! time refuses the signature, unless `INKAN_SIGN_POLICY_FAIL_OPEN` trades that for availability.
+
+use crate::config::SignPolicyConfig;
+use crate::models::KeyManagementError;
+use crate::verification_cache::{cache_key, CacheKey};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+/// Most decisions cached at once; expired entries are dropped first when it is reached
+pub const MAX_CACHED_DECISIONS: usize = 10_000;
+
+/// What the policy service is told about a signature
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PolicyRequest {
+    pub key_id: Uuid,
+    pub key_fingerprint: Option<String>,
+    pub document_hash: String,
+    pub context: Option<String>,
+    /// Client id of an HMAC-authenticated caller
+    pub requester: Option<String>,
+}
+
+impl PolicyRequest {
+    fn cache_key(&self) -> CacheKey {
+        cache_key("sign-policy", &[serde_json::to_string(self).unwrap_or_default().as_bytes()])
+    }
+}
+
+/// The policy service's answer
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PolicyDecision {
+    pub allow: bool,
+    /// Why the signature was refused, passed on to the caller
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl PolicyDecision {
+    pub fn allow() -> Self {
+        Self { allow: true, reason: None }
+    }
+
+    pub fn deny(reason: impl Into<String>) -> Self {
+        Self { allow: false, reason: Some(reason.into()) }
+    }
+}
+
+/// Decides whether a signature may be made
+#[async_trait]
+pub trait PolicyEngine: Send + Sync {
+    /// Short engine name used in logs
+    fn name(&self) -> &str;
+
+    /// Decides on a signature, failing if no decision could be obtained
+    async fn evaluate(&self, request: &PolicyRequest) -> Result<PolicyDecision, KeyManagementError>;
+}
+
+/// Allows every signature; used when no policy service is configured
+pub struct AllowAll;
+
+#[async_trait]
+impl PolicyEngine for AllowAll {
+    fn name(&self) -> &str {
+        "allow-all"
+    }
+
+    async fn evaluate(&self, _request: &PolicyRequest) -> Result<PolicyDecision, KeyManagementError> {
+        Ok(PolicyDecision::allow())
+    }
+}
+
+/// Posts the request as JSON to a policy service, which answers with a [`PolicyDecision`]
+#[cfg(feature = "webhook")]
+pub struct HttpPolicyEngine {
+    url: String,
+    client: reqwest::Client,
+}
+
+#[cfg(feature = "webhook")]
+impl HttpPolicyEngine {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[cfg(feature = "webhook")]
+#[async_trait]
+impl PolicyEngine for HttpPolicyEngine {
+    fn name(&self) -> &str {
+        "http"
+    }
+
+    async fn evaluate(&self, request: &PolicyRequest) -> Result<PolicyDecision, KeyManagementError> {
+        self.client.post(&self.url)
+            .json(request)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| KeyManagementError::InternalError(format!("Signing policy request failed: {}", e)))?
+            .json::<PolicyDecision>()
+            .await
+            .map_err(|e| KeyManagementError::InternalError(format!("Signing policy answer is malformed: {}", e)))
+    }
+}
+
+/// A policy engine with its timeout, failure mode and decision cache
+pub struct SignPolicy {
+    engine: Arc<dyn PolicyEngine>,
+    timeout: std::time::Duration,
+    fail_open: bool,
+    cache_ttl: Duration,
+    cache: Mutex<HashMap<CacheKey, (PolicyDecision, DateTime<Utc>)>>,
+}
+
+impl SignPolicy {
+    /// Wraps `engine` with the timeout, failure mode and cache TTL of `config`
+    pub fn new(engine: Arc<dyn PolicyEngine>, config: &SignPolicyConfig) -> Self {
+        Self {
+            engine,
+            timeout: std::time::Duration::from_millis(config.timeout_ms.into()),
+            fail_open: config.fail_open,
+            cache_ttl: Duration::seconds(config.cache_ttl_secs.into()),
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Policy allowing every signature
+    pub fn allow_all() -> Self {
+        Self::new(Arc::new(AllowAll), &SignPolicyConfig::default())
+    }
+
+    /// Builds the engine named in configuration, allowing everything if none is
+    ///
+    /// Fails if a policy URL is configured but the crate was built without the webhook feature.
+    pub fn from_config(config: &SignPolicyConfig) -> Result<Self, KeyManagementError> {
+        let Some(url) = &config.url else { return Ok(Self::allow_all()) };
+        #[cfg(feature = "webhook")]
+        return Ok(Self::new(Arc::new(HttpPolicyEngine::new(url)), config));
+        #[cfg(not(feature = "webhook"))]
+        Err(KeyManagementError::ValidationFailed(format!(
+            "INKAN_SIGN_POLICY_URL is set to {} but the service was built without the webhook feature",
+            url
+        )))
+    }
+
+    pub fn engine_name(&self) -> &str {
+        self.engine.name()
+    }
+
+    /// Allows the signature, or refuses it with [`KeyManagementError::PolicyDenied`]
+    ///
+    /// Only decisions are cached; a failed or timed out evaluation is retried on the next request.
+    pub async fn check(&self, request: &PolicyRequest, now: DateTime<Utc>) -> Result<(), KeyManagementError> {
+        let key = request.cache_key();
+        let cached = self.cache.lock().unwrap()
+            .get(&key)
+            .filter(|(_, expires_at)| *expires_at > now)
+            .map(|(decision, _)| decision.clone());
+        let decision = match cached {
+            Some(decision) => decision,
+            None => match tokio::time::timeout(self.timeout, self.engine.evaluate(request)).await {
+                Ok(Ok(decision)) => {
+                    self.remember(key, &decision, now);
+                    decision
+                }
+                Ok(Err(e)) => return self.unavailable(request, e.to_string()),
+                Err(_) => return self.unavailable(request, format!("no answer within {} ms", self.timeout.as_millis())),
+            },
+        };
+
+        if decision.allow {
+            Ok(())
+        } else {
+            Err(KeyManagementError::PolicyDenied(
+                decision.reason.unwrap_or_else(|| "the signing policy refused the signature".to_string()),
+            ))
+        }
+    }
+
+    fn remember(&self, key: CacheKey, decision: &PolicyDecision, now: DateTime<Utc>) {
+        if self.cache_ttl <= Duration::zero() {
+            return;
+        }
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() >= MAX_CACHED_DECISIONS {
+            cache.retain(|_, (_, expires_at)| *expires_at > now);
+            if cache.len() >= MAX_CACHED_DECISIONS {
+                cache.clear();
+            }
+        }
+        cache.insert(key, (decision.clone(), now + self.cache_ttl));
+    }
+
+    fn unavailable(&self, request: &PolicyRequest, error: String) -> Result<(), KeyManagementError> {
+        if self.fail_open {
+            tracing::warn!("Signing policy unavailable for key {}, allowing the signature: {}", request.key_id, error);
+            return Ok(());
+        }
+        Err(KeyManagementError::PolicyDenied(format!("the signing policy service is unavailable: {}", error)))
+    }
+}
+
+#[cfg(all(test, feature = "webhook"))]
+mod tests {
+    use super::*;
+    use axum::routing::post;
+    use axum::Json;
+
+    /// Serves a policy that denies documents whose hash starts with `0` and stalls on `f`
+    async fn spawn_policy_server() -> String {
+        let app = axum::Router::new().route("/policy", post(|Json(request): Json<PolicyRequest>| async move {
+            if request.document_hash.starts_with('f') {
+                tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+            }
+            if request.document_hash.starts_with('0') {
+                Json(PolicyDecision::deny("release freeze"))
+            } else {
+                Json(PolicyDecision::allow())
+            }
+        }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}/policy", address)
+    }
+
+    #[tokio::test]
+    async fn test_http_policy_allows_denies_and_times_out() {
+        let config = SignPolicyConfig { url: Some(spawn_policy_server().await), timeout_ms: 200, ..SignPolicyConfig::default() };
+        let policy = SignPolicy::from_config(&config).unwrap();
+        let request = |document_hash: &str| PolicyRequest {
+            key_id: Uuid::new_v4(),
+            key_fingerprint: None,
+            document_hash: document_hash.to_string(),
+            context: None,
+            requester: Some("billing".to_string()),
+        };
+        let now = Utc::now();
+
+        assert!(policy.check(&request("a1"), now).await.is_ok());
+        let denied = policy.check(&request("01"), now).await.unwrap_err();
+        assert_eq!(denied.to_string(), "Signing policy denied: release freeze");
+        assert!(matches!(policy.check(&request("f1"), now).await, Err(KeyManagementError::PolicyDenied(_))));
+
+        let fail_open = SignPolicy::from_config(&SignPolicyConfig { fail_open: true, ..config }).unwrap();
+        assert!(fail_open.check(&request("f1"), now).await.is_ok());
+        assert!(fail_open.check(&request("01"), now).await.is_err());
+    }
+}
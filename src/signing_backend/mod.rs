@@ -0,0 +1,101 @@
+//! Signing backends
+//!
+//! The sign path never touches private key bytes directly; it asks a backend for a
+//! [`KeySigner`] for the key. Software keys are decrypted from the keystore with the request
+//! password. Keys whose `KeyPair::hsm` names a hardware reference are held by an HSM: the
+//! keystore keeps only the slot and label with an empty `private_key`, the configured
+//! [`SigningBackend`] signs on the device, and request passwords are ignored in favor of the
+//! backend's own PIN. Listing, metadata, revocation, and verification only use the public key,
+//! so they work the same for both kinds of keys.
+//!
+//! The production backend is the PKCS#11 token in [`crate::pkcs11`], built with the `pkcs11`
+//! feature and opened by [`open_backend`].
+
+use crate::config::Config;
+use crate::key_generation::KdfTiming;
+use crate::key_verification::load_signing_key_timed;
+use crate::models::{HsmKeyRef, KeyManagementError, KeyPair, KeyType};
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use std::sync::Arc;
+
+/// Private key operations for one key, wherever the key is held
+pub trait KeySigner: Send + Sync {
+    /// Signs `message` with Ed25519
+    fn sign_message(&self, message: &[u8]) -> Result<Signature, KeyManagementError>;
+}
+
+impl KeySigner for SigningKey {
+    fn sign_message(&self, message: &[u8]) -> Result<Signature, KeyManagementError> {
+        #[cfg(any(test, feature = "failpoints"))]
+        crate::failpoints::fault(crate::failpoints::SIGN)
+            .map_err(|e| KeyManagementError::InternalError(format!("Signing failed: {}", e)))?;
+        Ok(self.sign(message))
+    }
+}
+
+/// Store of non-exportable keys, such as a PKCS#11 token
+pub trait SigningBackend: Send + Sync {
+    /// Short backend name used in logs and errors
+    fn name(&self) -> &str;
+
+    /// Generates a key on the device under `key`, returning its public key
+    fn generate(&self, key: &HsmKeyRef) -> Result<VerifyingKey, KeyManagementError>;
+
+    /// Opens the key at `key` for signing
+    fn signer(&self, key: &HsmKeyRef) -> Result<Box<dyn KeySigner>, KeyManagementError>;
+}
+
+/// Opens the backend `config` names, if any
+#[cfg(feature = "pkcs11")]
+pub fn open_backend(config: &Config) -> Result<Option<Arc<dyn SigningBackend>>, KeyManagementError> {
+    let Some(pkcs11) = &config.pkcs11 else { return Ok(None) };
+    Ok(Some(Arc::new(crate::pkcs11::Pkcs11Backend::open(pkcs11)?)))
+}
+
+/// Opens the backend `config` names, if any
+#[cfg(not(feature = "pkcs11"))]
+pub fn open_backend(config: &Config) -> Result<Option<Arc<dyn SigningBackend>>, KeyManagementError> {
+    match &config.pkcs11 {
+        Some(pkcs11) => Err(KeyManagementError::ValidationFailed(format!(
+            "INKAN_PKCS11_MODULE is set to {} but the service was built without the pkcs11 feature",
+            pkcs11.module,
+        ))),
+        None => Ok(None),
+    }
+}
+
+/// Refuses a key whose type this version does not know, rather than treating it as Ed25519
+pub fn ensure_known_key_type(key_pair: &KeyPair) -> Result<(), KeyManagementError> {
+    if key_pair.key_type == KeyType::Unknown {
+        return Err(KeyManagementError::InvalidKeyFormat(format!(
+            "Key {} has a key type this version does not support; migrate the record to a supported key type before using its private key",
+            key_pair.id,
+        )));
+    }
+    Ok(())
+}
+
+/// Loads a signer for `key_pair`, from the HSM backend for hardware keys and from the keystore
+/// (decrypting with `password`) otherwise
+///
+/// Also returns the time spent decrypting a password-protected software key. Keys of unknown
+/// type are refused with [`ensure_known_key_type`].
+pub fn load_signer(
+    key_pair: &KeyPair,
+    password: Option<&str>,
+    hsm: Option<&dyn SigningBackend>,
+) -> Result<(Box<dyn KeySigner>, Option<KdfTiming>), KeyManagementError> {
+    ensure_known_key_type(key_pair)?;
+    match (&key_pair.hsm, hsm) {
+        (Some(key), Some(backend)) => Ok((backend.signer(key)?, None)),
+        (Some(key), None) => Err(KeyManagementError::InternalError(format!(
+            "Key {} is held in HSM slot {} as '{}' but no HSM backend is configured",
+            key_pair.id, key.slot, key.label,
+        ))),
+        (None, _) => {
+            let (private_key, salt) = key_pair.signing_secrets();
+            let (signing_key, timing) = load_signing_key_timed(private_key, salt, &key_pair.kdf.unwrap_or_default(), password)?;
+            Ok((Box::new(signing_key), timing))
+        }
+    }
+}
@@ -0,0 +1,338 @@
+//! Latency objectives for key generation, signing and verification
+//!
+//! Deployments without a metrics stack can still check their latency SLOs. Every successful
+//! generate, sign and verify request is timed into a histogram for the minute it finished in,
+//! and `GET /admin/slo` reports percentiles over the last 5 and 60 minutes with the number of
+//! requests slower than each operation's target. Recording is a few array increments under a
+//! mutex, cheap enough to stay on always; the Prometheus metrics are unaffected.
+//!
+//! Percentiles are read from the histogram as the upper bound of the bucket they fall in, or the
+//! slowest request seen for those beyond the last bucket. Violations are counted exactly, against
+//! the target in force when the request was recorded, so whether a window breaches its target
+//! does not depend on the buckets.
+
+use crate::config::Config;
+use axum::http::Method;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Upper bounds, in milliseconds, of the latency histogram buckets
+pub const SLO_BUCKETS_MS: [u32; 27] = [
+    1, 2, 5, 10, 15, 20, 25, 30, 40, 50, 75, 100, 125, 150, 200, 250, 300, 400, 500, 750, 1_000, 1_500, 2_000,
+    2_500, 5_000, 10_000, 30_000,
+];
+/// Lengths, in minutes, of the windows reported
+pub const SLO_WINDOWS_MINUTES: [u32; 2] = [5, 60];
+/// Window whose p99 is compared with the target for breach warnings
+pub const BREACH_WINDOW_MINUTES: u32 = 5;
+/// Minutes of history kept, one histogram each
+const SLOTS: usize = 60;
+
+/// An operation with a latency objective
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SloOperation {
+    Generate,
+    Sign,
+    Verify,
+}
+
+impl SloOperation {
+    pub const ALL: [SloOperation; 3] = [SloOperation::Generate, SloOperation::Sign, SloOperation::Verify];
+
+    /// The operation a request to the route pattern `route` performs, if it is tracked
+    pub fn for_route(method: &Method, route: &str) -> Option<Self> {
+        if method != Method::POST {
+            return None;
+        }
+        match route {
+            "/keys/generate" => Some(SloOperation::Generate),
+            "/sign" | "/sign/raw" | "/sign/ephemeral" | "/sign/dsse" | "/sign/manifest" => Some(SloOperation::Sign),
+            "/verify" | "/verify/dsse" | "/verify/manifest" | "/verify/archive" => Some(SloOperation::Verify),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SloOperation::Generate => "generate",
+            SloOperation::Sign => "sign",
+            SloOperation::Verify => "verify",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Latencies recorded in one minute
+#[derive(Debug, Clone, Copy, Default)]
+struct MinuteHistogram {
+    minute: i64,
+    /// Requests in each bucket of [`SLO_BUCKETS_MS`], then those beyond the last
+    buckets: [u64; SLO_BUCKETS_MS.len() + 1],
+    count: u64,
+    violations: u64,
+    max_ms: f64,
+}
+
+/// Latencies of one operation, with whether its breach window is over the target
+#[derive(Debug)]
+struct OperationHistory {
+    /// Indexed by minute modulo [`SLOTS`]
+    minutes: [MinuteHistogram; SLOTS],
+    breached: bool,
+}
+
+impl Default for OperationHistory {
+    fn default() -> Self {
+        Self { minutes: [MinuteHistogram::default(); SLOTS], breached: false }
+    }
+}
+
+impl OperationHistory {
+    /// The latencies of the last `minutes` minutes up to `minute`, merged
+    fn window(&self, minute: i64, minutes: u32) -> MinuteHistogram {
+        let mut merged = MinuteHistogram { minute, ..MinuteHistogram::default() };
+        let in_window = |histogram: &&MinuteHistogram| {
+            histogram.count > 0 && histogram.minute <= minute && minute - histogram.minute < i64::from(minutes)
+        };
+        for histogram in self.minutes.iter().filter(in_window) {
+            for (total, count) in merged.buckets.iter_mut().zip(histogram.buckets) {
+                *total += count;
+            }
+            merged.count += histogram.count;
+            merged.violations += histogram.violations;
+            merged.max_ms = merged.max_ms.max(histogram.max_ms);
+        }
+        merged
+    }
+}
+
+impl MinuteHistogram {
+    /// Latency, in milliseconds, at or below which `quantile` of the requests finished
+    fn percentile(&self, quantile: f64) -> Option<f64> {
+        let rank = rank(self.count, quantile)?;
+        let mut seen = 0;
+        for (index, count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Some(SLO_BUCKETS_MS.get(index).map_or(self.max_ms, |bound| f64::from(*bound)));
+            }
+        }
+        None
+    }
+
+    /// Whether the p99 is over the target, that is more requests were slower than it than the
+    /// p99 allows
+    fn breaches(&self) -> bool {
+        rank(self.count, 0.99).is_some_and(|rank| self.count - self.violations < rank)
+    }
+}
+
+/// Position, counting from 1, of the request at `quantile` among `count`
+fn rank(count: u64, quantile: f64) -> Option<u64> {
+    (count > 0).then(|| ((quantile * count as f64).ceil() as u64).clamp(1, count))
+}
+
+/// Latency objective of one operation over one window
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct SloWindow {
+    pub minutes: u32,
+    pub count: u64,
+    pub p50_ms: Option<f64>,
+    pub p95_ms: Option<f64>,
+    pub p99_ms: Option<f64>,
+    /// Requests slower than the target
+    pub violations: u64,
+    /// The p99 is over the target
+    pub breached: bool,
+}
+
+/// Latency objective of one operation
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct OperationSlo {
+    pub operation: SloOperation,
+    pub target_ms: u32,
+    pub windows: Vec<SloWindow>,
+}
+
+/// Response for `GET /admin/slo`
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct SloReport {
+    pub success: bool,
+    pub generated_at: DateTime<Utc>,
+    pub operations: Vec<OperationSlo>,
+}
+
+/// Sliding-window latency histograms of generate, sign and verify requests
+pub struct SloTracker {
+    /// p99 target of each operation, in milliseconds, in the order of [`SloOperation::ALL`]
+    targets_ms: [u32; 3],
+    /// Log a warning when an operation's breach window goes over its target, and when it recovers
+    warn_on_breach: bool,
+    operations: Mutex<[OperationHistory; 3]>,
+}
+
+impl SloTracker {
+    /// A tracker with the targets and breach warnings of `config`
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            targets_ms: [config.slo_generate_target_ms, config.slo_sign_target_ms, config.slo_verify_target_ms],
+            warn_on_breach: config.slo_breach_warnings,
+            operations: Mutex::new(Default::default()),
+        }
+    }
+
+    /// Target of `operation`, in milliseconds
+    pub fn target_ms(&self, operation: SloOperation) -> u32 {
+        self.targets_ms[operation.index()]
+    }
+
+    /// Records a request of `operation` that took `latency` and finished at `now`
+    pub fn record(&self, operation: SloOperation, latency: Duration, now: DateTime<Utc>) {
+        let minute = now.timestamp().div_euclid(60);
+        let latency_ms = latency.as_secs_f64() * 1_000.0;
+        let target_ms = self.target_ms(operation);
+        let mut operations = self.operations.lock().unwrap_or_else(|e| e.into_inner());
+        let history = &mut operations[operation.index()];
+
+        let histogram = &mut history.minutes[minute.rem_euclid(SLOTS as i64) as usize];
+        if histogram.minute != minute {
+            *histogram = MinuteHistogram { minute, ..MinuteHistogram::default() };
+        }
+        let bucket = SLO_BUCKETS_MS.iter().position(|bound| latency_ms <= f64::from(*bound)).unwrap_or(SLO_BUCKETS_MS.len());
+        histogram.buckets[bucket] += 1;
+        histogram.count += 1;
+        histogram.violations += u64::from(latency_ms > f64::from(target_ms));
+        histogram.max_ms = histogram.max_ms.max(latency_ms);
+
+        if !self.warn_on_breach {
+            return;
+        }
+        let window = history.window(minute, BREACH_WINDOW_MINUTES);
+        let breached = window.breaches();
+        if breached != history.breached {
+            history.breached = breached;
+            if breached {
+                tracing::warn!(
+                    "{} latency over the last {} minutes breaches its {} ms target: p99 {:.1} ms, {} of {} requests slower",
+                    operation.as_str(),
+                    BREACH_WINDOW_MINUTES,
+                    target_ms,
+                    window.percentile(0.99).unwrap_or_default(),
+                    window.violations,
+                    window.count,
+                );
+            } else {
+                tracing::info!("{} latency is back within its {} ms target", operation.as_str(), target_ms);
+            }
+        }
+    }
+
+    /// Percentiles and violations of every operation over each of [`SLO_WINDOWS_MINUTES`]
+    pub fn report(&self, now: DateTime<Utc>) -> SloReport {
+        let minute = now.timestamp().div_euclid(60);
+        let operations = self.operations.lock().unwrap_or_else(|e| e.into_inner());
+        SloReport {
+            success: true,
+            generated_at: now,
+            operations: SloOperation::ALL.into_iter()
+                .map(|operation| OperationSlo {
+                    operation,
+                    target_ms: self.target_ms(operation),
+                    windows: SLO_WINDOWS_MINUTES.into_iter()
+                        .map(|minutes| {
+                            let window = operations[operation.index()].window(minute, minutes);
+                            SloWindow {
+                                minutes,
+                                count: window.count,
+                                p50_ms: window.percentile(0.50),
+                                p95_ms: window.percentile(0.95),
+                                p99_ms: window.percentile(0.99),
+                                violations: window.violations,
+                                breached: window.breaches(),
+                            }
+                        })
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn tracker() -> SloTracker {
+        SloTracker::from_config(&Config { slo_sign_target_ms: 250, ..Config::default() })
+    }
+
+    fn window(report: &SloReport, operation: SloOperation, minutes: u32) -> SloWindow {
+        let operation = report.operations.iter().find(|slo| slo.operation == operation).unwrap();
+        operation.windows.iter().find(|window| window.minutes == minutes).unwrap().clone()
+    }
+
+    #[test]
+    fn test_percentiles_and_violations_over_sliding_windows() {
+        let tracker = tracker();
+        let start = Utc.with_ymd_and_hms(2026, 10, 17, 12, 0, 0).unwrap();
+        let millis = |ms: u64| Duration::from_millis(ms);
+
+        // An hour ago: one slow request, outside the 5-minute window but inside the hour
+        tracker.record(SloOperation::Sign, millis(900), start - chrono::Duration::minutes(50));
+        // Now: 100 requests, 97 fast, 2 just under the target and 1 far over it
+        for _ in 0..97 {
+            tracker.record(SloOperation::Sign, millis(8), start);
+        }
+        tracker.record(SloOperation::Sign, millis(240), start);
+        tracker.record(SloOperation::Sign, millis(245), start);
+        tracker.record(SloOperation::Sign, millis(42_000), start);
+        tracker.record(SloOperation::Verify, millis(3), start);
+
+        let report = tracker.report(start + chrono::Duration::seconds(30));
+        let recent = window(&report, SloOperation::Sign, 5);
+        assert_eq!((recent.count, recent.violations), (100, 1));
+        assert_eq!((recent.p50_ms, recent.p95_ms, recent.p99_ms), (Some(10.0), Some(10.0), Some(250.0)));
+        // One slow request in a hundred is within the p99
+        assert!(!recent.breached);
+
+        let hour = window(&report, SloOperation::Sign, 60);
+        assert_eq!((hour.count, hour.violations), (101, 2));
+        assert_eq!(hour.p99_ms, Some(1_000.0));
+        assert!(hour.breached);
+
+        // The slowest request beyond the last bucket is reported as it was
+        let mut slow = MinuteHistogram::default();
+        slow.buckets[SLO_BUCKETS_MS.len()] = 1;
+        slow.count = 1;
+        slow.max_ms = 42_000.0;
+        assert_eq!(slow.percentile(0.5), Some(42_000.0));
+
+        assert_eq!(window(&report, SloOperation::Verify, 5).p99_ms, Some(5.0));
+        let idle = window(&report, SloOperation::Generate, 60);
+        assert_eq!((idle.count, idle.p50_ms, idle.breached), (0, None, false));
+
+        // Minutes fall out of the window, and their slots are reused an hour on
+        let later = tracker.report(start + chrono::Duration::minutes(5));
+        assert_eq!(window(&later, SloOperation::Sign, 5).count, 0);
+        assert_eq!(window(&later, SloOperation::Sign, 60).count, 101);
+        tracker.record(SloOperation::Sign, millis(1), start + chrono::Duration::minutes(60));
+        let next_hour = tracker.report(start + chrono::Duration::minutes(60));
+        assert_eq!(window(&next_hour, SloOperation::Sign, 60).count, 1);
+    }
+
+    #[test]
+    fn test_tracked_routes() {
+        assert_eq!(SloOperation::for_route(&Method::POST, "/keys/generate"), Some(SloOperation::Generate));
+        assert_eq!(SloOperation::for_route(&Method::POST, "/sign/raw"), Some(SloOperation::Sign));
+        assert_eq!(SloOperation::for_route(&Method::POST, "/verify/dsse"), Some(SloOperation::Verify));
+        assert_eq!(SloOperation::for_route(&Method::GET, "/keys/:key_id"), None);
+        assert_eq!(SloOperation::for_route(&Method::POST, "/keys/:key_id/revoke"), None);
+    }
+}
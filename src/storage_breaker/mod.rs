@@ -0,0 +1,347 @@
+//! Circuit breaker in front of the keystore
+//!
+//! When the keystore disk gets slow, every handler that touches the keys waits behind the write
+//! holding them, and the whole service stops answering. [`KeyStorage`](crate::key_storage::KeyStorage)
+//! reports the outcome and latency of each keystore write to a [`StorageBreaker`]. Once
+//! `failure_percent` of the last [`WINDOW`] writes have failed or taken longer than `slow_ms`,
+//! the breaker opens: requests to storage-dependent routes are refused at once with
+//! `503 STORAGE_UNAVAILABLE` and a `Retry-After`, while the routes in [`STORAGE_FREE_ROUTES`],
+//! such as `/verify` against a given public key, keep serving.
+//!
+//! After `open_secs` the breaker is half-open and lets up to `probes` requests through. A failed
+//! or slow write opens it again; `probes` good writes in a row close it. Every transition is
+//! logged and kept in the breaker's recent history for readiness and metrics.
+
+use crate::config::StorageBreakerConfig;
+use crate::models::KeyManagementError;
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Mutex;
+
+/// Recent keystore operations the failure rate is taken over
+pub const WINDOW: usize = 20;
+/// Operations in the window before the failure rate can open the breaker
+pub const MIN_OPERATIONS: usize = 5;
+/// Transitions kept in the breaker's history
+pub const MAX_TRANSITIONS: usize = 20;
+
+/// Routes served whatever the state of the keystore
+///
+/// `/verify` looks keys up only when the request names them by id, and refuses those lookups
+/// itself while the breaker is open.
+pub const STORAGE_FREE_ROUTES: &[&str] = &[
+    "/health", "/health/ready", "/metrics", "/errors", "/capabilities", "/version", "/about", "/templates",
+    "/verify", "/verify/manifest", "/verify/archive",
+];
+
+/// Whether requests to the route pattern `route` need the keystore
+pub fn depends_on_storage(route: &str) -> bool {
+    !STORAGE_FREE_ROUTES.contains(&route)
+}
+
+/// Position of the breaker
+#[derive(Debug, Clone, Copy, Default, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum BreakerState {
+    /// Requests pass and keystore operations are counted
+    #[default]
+    Closed,
+    /// Storage-dependent requests are refused
+    Open,
+    /// A few probe requests pass to find out whether the keystore recovered
+    HalfOpen,
+}
+
+impl BreakerState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BreakerState::Closed => "closed",
+            BreakerState::Open => "open",
+            BreakerState::HalfOpen => "half-open",
+        }
+    }
+}
+
+/// One change of the breaker's state
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct BreakerTransition {
+    pub from: BreakerState,
+    pub to: BreakerState,
+    pub at: DateTime<Utc>,
+    pub reason: String,
+}
+
+/// State of the breaker for readiness and metrics
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct BreakerStatus {
+    pub enabled: bool,
+    pub state: BreakerState,
+    pub recent_operations: usize, // Keystore operations in the failure-rate window
+    pub recent_failures: usize, // Of those, the ones that failed or were slow
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_after_secs: Option<u64>, // While open, until probes are let through
+    pub rejected_requests: u64,
+    pub transitions: Vec<BreakerTransition>, // Most recent last
+}
+
+struct BreakerInner {
+    state: BreakerState,
+    /// Whether each recent operation failed or was slow, oldest first
+    recent: VecDeque<bool>,
+    /// When an open breaker starts probing, or a half-open one allows another round of probes
+    next_probe_at: DateTime<Utc>,
+    probes_admitted: u32,
+    probe_successes: u32,
+    /// Requests refused, by route
+    rejected: BTreeMap<String, u64>,
+    /// Transitions into each state since startup
+    entered: BTreeMap<&'static str, u64>,
+    transitions: VecDeque<BreakerTransition>,
+}
+
+/// Opens when keystore operations fail or slow down, refusing storage-dependent requests
+pub struct StorageBreaker {
+    config: StorageBreakerConfig,
+    inner: Mutex<BreakerInner>,
+}
+
+impl StorageBreaker {
+    pub fn new(config: StorageBreakerConfig) -> Self {
+        Self {
+            config,
+            inner: Mutex::new(BreakerInner {
+                state: BreakerState::Closed,
+                recent: VecDeque::with_capacity(WINDOW),
+                next_probe_at: DateTime::<Utc>::MIN_UTC,
+                probes_admitted: 0,
+                probe_successes: 0,
+                rejected: BTreeMap::new(),
+                entered: BTreeMap::new(),
+                transitions: VecDeque::with_capacity(MAX_TRANSITIONS),
+            }),
+        }
+    }
+
+    /// Breaker that never opens
+    pub fn disabled() -> Self {
+        Self::new(StorageBreakerConfig { failure_percent: 0, ..StorageBreakerConfig::default() })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.failure_percent > 0
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BreakerInner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn open_for(&self) -> Duration {
+        Duration::seconds(self.config.open_secs.into())
+    }
+
+    /// Lets a request to `route` reach the keystore, or refuses it while the breaker is open
+    ///
+    /// An open breaker turns half-open once its time is up; a half-open one admits `probes`
+    /// requests, and another round if none of them has touched the keystore within `open_secs`.
+    pub fn admit(&self, route: &str, now: DateTime<Utc>) -> Result<(), KeyManagementError> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        let mut inner = self.lock();
+        match inner.state {
+            BreakerState::Closed => return Ok(()),
+            BreakerState::Open if now >= inner.next_probe_at => {
+                self.transition(&mut inner, BreakerState::HalfOpen, now, "Probing the keystore".to_string());
+                inner.next_probe_at = now + self.open_for();
+                inner.probes_admitted = 1;
+                inner.probe_successes = 0;
+                return Ok(());
+            }
+            BreakerState::HalfOpen if inner.probes_admitted < self.config.probes => {
+                inner.probes_admitted += 1;
+                return Ok(());
+            }
+            BreakerState::HalfOpen if now >= inner.next_probe_at => {
+                inner.next_probe_at = now + self.open_for();
+                inner.probes_admitted = 1;
+                return Ok(());
+            }
+            BreakerState::Open | BreakerState::HalfOpen => {}
+        }
+        *inner.rejected.entry(route.to_string()).or_default() += 1;
+        Err(KeyManagementError::StorageUnavailable { retry_after_secs: retry_after_secs(inner.next_probe_at, now) })
+    }
+
+    /// Seconds until requests may reach the keystore again, while the breaker refuses them
+    pub fn retry_after(&self, now: DateTime<Utc>) -> Option<u64> {
+        let inner = self.lock();
+        let refusing = match inner.state {
+            BreakerState::Closed => false,
+            BreakerState::Open => true,
+            BreakerState::HalfOpen => inner.probes_admitted >= self.config.probes,
+        };
+        (self.is_enabled() && refusing && now < inner.next_probe_at).then(|| retry_after_secs(inner.next_probe_at, now))
+    }
+
+    /// Counts one keystore operation that took `elapsed`
+    pub fn record(&self, succeeded: bool, elapsed: std::time::Duration, now: DateTime<Utc>) {
+        if !self.is_enabled() {
+            return;
+        }
+        let slow = elapsed >= std::time::Duration::from_millis(self.config.slow_ms.into());
+        let failed = !succeeded || slow;
+        let outcome = if !succeeded { "failed" } else { "was slow" };
+        let mut inner = self.lock();
+        match inner.state {
+            BreakerState::Closed => {
+                if inner.recent.len() == WINDOW {
+                    inner.recent.pop_front();
+                }
+                inner.recent.push_back(failed);
+                let failures = inner.recent.iter().filter(|failed| **failed).count();
+                let operations = inner.recent.len();
+                if failed && operations >= MIN_OPERATIONS && failures * 100 >= self.config.failure_percent as usize * operations {
+                    let reason = format!("{} of the last {} keystore operations failed or took over {} ms", failures, operations, self.config.slow_ms);
+                    self.open(&mut inner, now, reason);
+                }
+            }
+            BreakerState::HalfOpen if failed => {
+                let reason = format!("A probe keystore operation {} after {} ms", outcome, elapsed.as_millis());
+                self.open(&mut inner, now, reason);
+            }
+            BreakerState::HalfOpen => {
+                inner.probe_successes += 1;
+                if inner.probe_successes >= self.config.probes {
+                    let reason = format!("{} probe keystore operations succeeded", inner.probe_successes);
+                    self.transition(&mut inner, BreakerState::Closed, now, reason);
+                    inner.recent.clear();
+                }
+            }
+            // Operations finishing while open, such as a background flush, do not move the breaker
+            BreakerState::Open => {}
+        }
+    }
+
+    fn open(&self, inner: &mut BreakerInner, now: DateTime<Utc>, reason: String) {
+        self.transition(inner, BreakerState::Open, now, reason);
+        inner.next_probe_at = now + self.open_for();
+    }
+
+    fn transition(&self, inner: &mut BreakerInner, to: BreakerState, now: DateTime<Utc>, reason: String) {
+        let from = inner.state;
+        match to {
+            BreakerState::Open => tracing::warn!("Storage breaker {} -> open: {}; refusing storage-dependent requests for {} s", from.as_str(), reason, self.config.open_secs),
+            _ => tracing::info!("Storage breaker {} -> {}: {}", from.as_str(), to.as_str(), reason),
+        }
+        inner.state = to;
+        *inner.entered.entry(to.as_str()).or_default() += 1;
+        if inner.transitions.len() == MAX_TRANSITIONS {
+            inner.transitions.pop_front();
+        }
+        inner.transitions.push_back(BreakerTransition { from, to, at: now, reason });
+    }
+
+    pub fn status(&self, now: DateTime<Utc>) -> BreakerStatus {
+        let retry_after_secs = self.retry_after(now);
+        let inner = self.lock();
+        BreakerStatus {
+            enabled: self.is_enabled(),
+            state: inner.state,
+            recent_operations: inner.recent.len(),
+            recent_failures: inner.recent.iter().filter(|failed| **failed).count(),
+            retry_after_secs,
+            rejected_requests: inner.rejected.values().sum(),
+            transitions: inner.transitions.iter().cloned().collect(),
+        }
+    }
+
+    /// Requests refused while the breaker was open, by route, for metrics
+    pub fn rejections(&self) -> Vec<(String, u64)> {
+        self.lock().rejected.iter().map(|(route, count)| (route.clone(), *count)).collect()
+    }
+
+    /// Transitions into each state since startup, for metrics
+    pub fn transitions_into(&self) -> Vec<(&'static str, u64)> {
+        self.lock().entered.iter().map(|(state, count)| (*state, *count)).collect()
+    }
+}
+
+impl Default for StorageBreaker {
+    fn default() -> Self {
+        Self::new(StorageBreakerConfig::default())
+    }
+}
+
+/// Whole seconds until `at`, at least one
+fn retry_after_secs(at: DateTime<Utc>, now: DateTime<Utc>) -> u64 {
+    let millis = (at - now).num_milliseconds().max(0) as u64;
+    millis.div_ceil(1000).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FAST: std::time::Duration = std::time::Duration::from_millis(3);
+    const SLOW: std::time::Duration = std::time::Duration::from_millis(2_500);
+
+    #[test]
+    fn test_slow_writes_open_the_breaker_and_probes_close_it() {
+        let breaker = StorageBreaker::new(StorageBreakerConfig { failure_percent: 50, slow_ms: 2_000, open_secs: 30, probes: 2 });
+        let start = Utc::now();
+
+        // A few slow writes among good ones are tolerated until half of the window is bad
+        for _ in 0..4 {
+            breaker.record(true, FAST, start);
+        }
+        for _ in 0..3 {
+            breaker.record(true, SLOW, start);
+        }
+        assert_eq!(breaker.status(start).state, BreakerState::Closed);
+        breaker.record(true, SLOW, start);
+        let status = breaker.status(start);
+        assert_eq!((status.state, status.retry_after_secs), (BreakerState::Open, Some(30)));
+        assert!(status.transitions[0].reason.contains("4 of the last 8"), "{:?}", status.transitions);
+
+        // Refused fast while open, and counted by route
+        let Err(KeyManagementError::StorageUnavailable { retry_after_secs }) = breaker.admit("/sign", start + Duration::seconds(10)) else {
+            panic!("an open breaker let a request through");
+        };
+        assert_eq!(retry_after_secs, 20);
+        assert_eq!(breaker.rejections(), [("/sign".to_string(), 1)]);
+
+        // Half-open: two probes pass, a third waits, and a slow probe opens the breaker again
+        let probing = start + Duration::seconds(30);
+        assert!(breaker.admit("/sign", probing).is_ok());
+        assert!(breaker.admit("/keys", probing).is_ok());
+        assert!(breaker.admit("/keys", probing).is_err());
+        breaker.record(true, SLOW, probing);
+        assert_eq!(breaker.status(probing).state, BreakerState::Open);
+
+        // Good probes close it, with every transition kept
+        let recovered = probing + Duration::seconds(30);
+        assert!(breaker.admit("/sign", recovered).is_ok());
+        breaker.record(true, FAST, recovered);
+        assert_eq!(breaker.status(recovered).state, BreakerState::HalfOpen);
+        breaker.record(true, FAST, recovered);
+        let status = breaker.status(recovered);
+        assert_eq!((status.state, status.recent_operations), (BreakerState::Closed, 0));
+        let path: Vec<BreakerState> = status.transitions.iter().map(|transition| transition.to).collect();
+        assert_eq!(path, [BreakerState::Open, BreakerState::HalfOpen, BreakerState::Open, BreakerState::HalfOpen, BreakerState::Closed]);
+        assert!(breaker.admit("/sign", recovered).is_ok());
+        assert_eq!(breaker.transitions_into(), [("closed", 1), ("half-open", 2), ("open", 2)]);
+    }
+
+    #[test]
+    fn test_disabled_breaker_never_refuses() {
+        let breaker = StorageBreaker::disabled();
+        let now = Utc::now();
+        for _ in 0..WINDOW {
+            breaker.record(false, SLOW, now);
+        }
+        assert!(breaker.admit("/sign", now).is_ok());
+        assert_eq!(breaker.status(now).state, BreakerState::Closed);
+        assert!(depends_on_storage("/sign") && !depends_on_storage("/verify"));
+    }
+}
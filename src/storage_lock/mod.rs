@@ -0,0 +1,326 @@
+//! Keystore ownership lock
+//!
+//! Two instances writing the same keystore file would silently overwrite each other, so the
+//! instance that owns a keystore holds `<keystore>.lock`. The lock file is created exclusively
+//! and records the owner's pid, instance id, and a heartbeat the owner refreshes while it runs.
+//! A second instance either refuses to start or runs as a read-only follower that periodically
+//! reloads the keystore, depending on configuration. A lock whose heartbeat is older than the
+//! stale timeout belongs to a crashed process and is broken: it is first renamed aside, which
+//! only one contender can do, and restored if it turns out to have been refreshed meanwhile.
+
+use crate::clock::Clock;
+use crate::key_storage::KeyStorage;
+use crate::models::KeyManagementError;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+/// What an instance does when another live instance holds the keystore lock
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LockConflict {
+    /// Refuse to start
+    #[default]
+    Refuse,
+    /// Start read-only and reload the keystore periodically
+    Follow,
+}
+
+impl LockConflict {
+    /// Parses `refuse` or `follow`, ignoring case and surrounding whitespace
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "refuse" => Some(Self::Refuse),
+            "follow" => Some(Self::Follow),
+            _ => None,
+        }
+    }
+}
+
+/// Contents of the lock file
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LockInfo {
+    pub pid: u32,
+    pub instance_id: Uuid,
+    pub acquired_at: DateTime<Utc>,
+    pub heartbeat_at: DateTime<Utc>,
+}
+
+impl LockInfo {
+    /// Whether the owner has missed its heartbeat for longer than `stale_after`
+    pub fn is_stale(&self, now: DateTime<Utc>, stale_after: Duration) -> bool {
+        now - self.heartbeat_at > stale_after
+    }
+}
+
+/// Path of the lock file guarding a keystore
+pub fn lock_path(storage_path: &str) -> PathBuf {
+    PathBuf::from(format!("{}.lock", storage_path))
+}
+
+fn io_error(action: &str, path: &Path, e: std::io::Error) -> KeyManagementError {
+    KeyManagementError::StorageError(format!("Failed to {} lock file {}: {}", action, path.display(), e))
+}
+
+fn read_info(path: &Path) -> Result<Option<LockInfo>, KeyManagementError> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(serde_json::from_str(&content).ok()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(io_error("read", path, e)),
+    }
+}
+
+fn serialize(info: &LockInfo) -> Vec<u8> {
+    serde_json::to_vec_pretty(info).unwrap_or_default()
+}
+
+/// Exclusive ownership of a keystore, released when dropped
+#[derive(Debug)]
+pub struct StorageLock {
+    path: PathBuf,
+    info: std::sync::Mutex<LockInfo>,
+}
+
+/// Outcome of trying to take the keystore lock
+#[derive(Debug)]
+pub enum LockAttempt {
+    Acquired(StorageLock),
+    /// Another live instance holds the lock
+    Held(LockInfo),
+}
+
+impl StorageLock {
+    /// Tries to take the lock for the keystore at `storage_path`, breaking it if its owner's
+    /// heartbeat is older than `stale_after`
+    pub fn acquire(storage_path: &str, now: DateTime<Utc>, stale_after: Duration) -> Result<LockAttempt, KeyManagementError> {
+        let path = lock_path(storage_path);
+        let info = LockInfo {
+            pid: std::process::id(),
+            instance_id: Uuid::new_v4(),
+            acquired_at: now,
+            heartbeat_at: now,
+        };
+
+        // Two attempts: the second follows breaking a stale lock
+        for _ in 0..2 {
+            match fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    file.write_all(&serialize(&info)).and_then(|_| file.sync_all()).map_err(|e| io_error("write", &path, e))?;
+                    return Ok(LockAttempt::Acquired(Self { path, info: std::sync::Mutex::new(info) }));
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(io_error("create", &path, e)),
+            }
+
+            match read_info(&path)? {
+                Some(holder) if !holder.is_stale(now, stale_after) => return Ok(LockAttempt::Held(holder)),
+                holder => Self::break_stale(&path, holder.as_ref())?,
+            }
+        }
+
+        match read_info(&path)? {
+            Some(holder) => Ok(LockAttempt::Held(holder)),
+            None => Err(KeyManagementError::StorageError(format!("Could not take lock file {}", path.display()))),
+        }
+    }
+
+    /// Moves a stale lock aside, putting it back if it is no longer the lock judged stale
+    fn break_stale(path: &Path, stale: Option<&LockInfo>) -> Result<(), KeyManagementError> {
+        let aside = PathBuf::from(format!("{}.stale-{}", path.display(), Uuid::new_v4()));
+        match fs::rename(path, &aside) {
+            Ok(()) => {}
+            // Another contender broke it first
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(io_error("break", path, e)),
+        }
+        let moved = read_info(&aside)?;
+        if moved.is_some() && moved.as_ref() != stale {
+            // The owner refreshed its heartbeat between the read and the rename; restore its lock
+            // unless a new one already took its place
+            tracing::warn!("Lock file {} was refreshed while being broken; restoring it", path.display());
+            let _ = fs::hard_link(&aside, path);
+        } else if let Some(stale) = stale {
+            tracing::warn!(
+                "Broke stale keystore lock held by pid {} (last heartbeat {})",
+                stale.pid,
+                stale.heartbeat_at.to_rfc3339(),
+            );
+        }
+        fs::remove_file(&aside).map_err(|e| io_error("remove", &aside, e))
+    }
+
+    /// Owner recorded in this lock
+    pub fn info(&self) -> LockInfo {
+        self.info.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Refreshes the heartbeat, failing if the lock file no longer belongs to this instance
+    pub fn heartbeat(&self, now: DateTime<Utc>) -> Result<(), KeyManagementError> {
+        let mut info = self.info.lock().unwrap_or_else(|e| e.into_inner());
+        match read_info(&self.path)? {
+            Some(current) if current.instance_id == info.instance_id => {}
+            _ => {
+                return Err(KeyManagementError::StorageError(format!(
+                    "Keystore lock {} was taken over by another instance",
+                    self.path.display(),
+                )));
+            }
+        }
+
+        info.heartbeat_at = now;
+        let temp_path = PathBuf::from(format!("{}.tmp", self.path.display()));
+        fs::write(&temp_path, serialize(&info))
+            .and_then(|_| fs::rename(&temp_path, &self.path))
+            .map_err(|e| io_error("refresh", &self.path, e))
+    }
+}
+
+impl Drop for StorageLock {
+    fn drop(&mut self) {
+        let instance_id = self.info.get_mut().unwrap_or_else(|e| e.into_inner()).instance_id;
+        if let Ok(Some(current)) = read_info(&self.path) {
+            if current.instance_id == instance_id {
+                let _ = fs::remove_file(&self.path);
+            }
+        }
+    }
+}
+
+/// How this instance relates to the keystore
+#[derive(Debug)]
+pub enum StorageRole {
+    /// Owns the keystore and may write it
+    Owner(StorageLock),
+    /// Another instance owns the keystore; this one serves it read-only
+    Follower(LockInfo),
+}
+
+/// Takes the keystore lock, applying `conflict` when another live instance holds it
+pub fn claim_storage(
+    storage_path: &str,
+    conflict: LockConflict,
+    now: DateTime<Utc>,
+    stale_after: Duration,
+) -> Result<StorageRole, KeyManagementError> {
+    match (StorageLock::acquire(storage_path, now, stale_after)?, conflict) {
+        (LockAttempt::Acquired(lock), _) => Ok(StorageRole::Owner(lock)),
+        (LockAttempt::Held(holder), LockConflict::Follow) => Ok(StorageRole::Follower(holder)),
+        (LockAttempt::Held(holder), LockConflict::Refuse) => Err(KeyManagementError::StorageError(format!(
+            "Keystore {} is in use by another instance (pid {}, last heartbeat {}); \
+             stop it or set INKAN_LOCK_CONFLICT=follow to start read-only",
+            storage_path,
+            holder.pid,
+            holder.heartbeat_at.to_rfc3339(),
+        ))),
+    }
+}
+
+/// Spawns a task that refreshes the lock's heartbeat every `interval`
+pub fn spawn_lock_heartbeat(lock: Arc<StorageLock>, clock: Arc<dyn Clock>, interval: std::time::Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = lock.heartbeat(clock.now()) {
+                tracing::error!("Keystore lock heartbeat failed: {}", e);
+            }
+        }
+    })
+}
+
+/// Spawns a task that reloads a followed keystore every `interval`
+pub fn spawn_follower_reload(storage: Arc<KeyStorage>, interval: std::time::Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = storage.reload_from_disk().await {
+                tracing::warn!("Failed to reload followed keystore: {}", e);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DEFAULT_LOCK_STALE_SECS;
+    use crate::key_generation::generate_test_key_pair;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_second_instance_refuses_or_follows() {
+        let dir = tempdir().unwrap();
+        let storage_path = dir.path().join("keys.json");
+        let storage_path = storage_path.to_str().unwrap();
+        let now = Utc::now();
+        let stale_after = Duration::seconds(i64::from(DEFAULT_LOCK_STALE_SECS));
+
+        let StorageRole::Owner(lock) = claim_storage(storage_path, LockConflict::Refuse, now, stale_after).unwrap() else {
+            panic!("first instance should own the keystore");
+        };
+        let owner = KeyStorage::new(storage_path);
+
+        let error = claim_storage(storage_path, LockConflict::Refuse, now, stale_after).unwrap_err();
+        assert!(error.to_string().contains(&format!("pid {}", std::process::id())), "{}", error);
+
+        let StorageRole::Follower(holder) = claim_storage(storage_path, LockConflict::Follow, now, stale_after).unwrap() else {
+            panic!("second instance should follow");
+        };
+        assert_eq!(holder, lock.info());
+
+        // The follower picks up the owner's writes, including deletions, on reload
+        let follower = KeyStorage::new(storage_path);
+        let key_pair = generate_test_key_pair("Shared Key").unwrap();
+        owner.store_key(key_pair.clone()).await.unwrap();
+        follower.reload_from_disk().await.unwrap();
+        assert_eq!(follower.key_count().await, 1);
+        owner.remove_key(key_pair.id).await.unwrap();
+        follower.reload_from_disk().await.unwrap();
+        assert_eq!(follower.key_count().await, 0);
+
+        // A live heartbeat keeps the lock; dropping the lock releases it
+        lock.heartbeat(now + Duration::seconds(20)).unwrap();
+        assert!(matches!(StorageLock::acquire(storage_path, now + Duration::seconds(40), stale_after).unwrap(), LockAttempt::Held(_)));
+        drop(lock);
+        assert!(!lock_path(storage_path).exists());
+    }
+
+    #[test]
+    fn test_stale_lock_from_crashed_instance_is_broken() {
+        let dir = tempdir().unwrap();
+        let storage_path = dir.path().join("keys.json");
+        let storage_path = storage_path.to_str().unwrap();
+        let now = Utc::now();
+        let stale_after = Duration::seconds(30);
+
+        // A crash leaves the lock file behind without releasing it
+        let LockAttempt::Acquired(crashed) = StorageLock::acquire(storage_path, now, stale_after).unwrap() else {
+            panic!("lock should be free");
+        };
+        let crashed_info = crashed.info();
+        std::mem::forget(crashed);
+
+        assert!(matches!(StorageLock::acquire(storage_path, now + Duration::seconds(30), stale_after).unwrap(), LockAttempt::Held(_)));
+
+        let LockAttempt::Acquired(successor) = StorageLock::acquire(storage_path, now + Duration::seconds(31), stale_after).unwrap() else {
+            panic!("stale lock should be broken");
+        };
+        assert_ne!(successor.info().instance_id, crashed_info.instance_id);
+        assert_eq!(read_info(&lock_path(storage_path)).unwrap(), Some(successor.info()));
+
+        // No stale-lock leftovers remain next to the keystore
+        let leftovers: Vec<_> = fs::read_dir(dir.path()).unwrap()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name().to_string_lossy().contains(".stale-"))
+            .collect();
+        assert!(leftovers.is_empty());
+    }
+}
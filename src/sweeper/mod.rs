@@ -0,0 +1,114 @@
+//! Background sweeper
+//!
+//! Periodically applies time-based state changes that no request triggers, such as scheduled
+//! revocations reaching their effective time and keys approaching expiry, and writes out
+//! pending keystore changes such as batched usage counters, retrying failed writes with backoff.
+
+use crate::clock::Clock;
+use crate::event_log::OperationEvent;
+use crate::key_storage::KeyStorage;
+use crate::models::KeyManagementError;
+use crate::notifications::{notify_expiring_keys, ExpiryNotifications};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+/// Changes applied by a single sweep
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SweepReport {
+    /// Keys whose scheduled revocation was executed
+    pub revoked: Vec<Uuid>,
+    /// Expiry notices sent, as key and threshold in days
+    pub notified: Vec<(Uuid, u32)>,
+    /// Whether pending keystore changes were written to disk
+    pub flushed: bool,
+}
+
+/// Outcome of a background task's latest run
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct TaskRun {
+    pub finished_at: DateTime<Utc>,
+    pub succeeded: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Latest run of a background task, for status reports
+#[derive(Debug, Default)]
+pub struct TaskStatus {
+    last_run: Mutex<Option<TaskRun>>,
+}
+
+impl TaskStatus {
+    pub fn record<T>(&self, finished_at: DateTime<Utc>, result: &Result<T, KeyManagementError>) {
+        *self.last_run.lock().unwrap() = Some(TaskRun {
+            finished_at,
+            succeeded: result.is_ok(),
+            error: result.as_ref().err().map(ToString::to_string),
+        });
+    }
+
+    /// The latest run, or `None` before the first one finishes
+    pub fn last_run(&self) -> Option<TaskRun> {
+        self.last_run.lock().unwrap().clone()
+    }
+}
+
+/// Runs one sweep against the keystore at the clock's current time
+pub async fn sweep(
+    storage: &KeyStorage,
+    clock: &dyn Clock,
+    notifications: Option<&ExpiryNotifications>,
+) -> Result<SweepReport, KeyManagementError> {
+    let now = clock.now();
+    let revoked = storage.execute_due_revocations(now).await?;
+    for key_id in &revoked {
+        if let Err(e) = storage.events().record(OperationEvent::sweep("scheduled_revocation", *key_id, now)).await {
+            tracing::warn!("Scheduled revocation of key {} not numbered: {}", key_id, e);
+        }
+    }
+    let notified = match notifications {
+        Some(notifications) => notify_expiring_keys(storage, notifications, now).await?,
+        None => Vec::new(),
+    };
+    // After a failed write, wait out the backoff before trying again
+    let flushed = if storage.persistence_status().write_due(now) {
+        storage.flush().await?
+    } else {
+        false
+    };
+    Ok(SweepReport { revoked, notified, flushed })
+}
+
+/// Spawns a task that sweeps the keystore every `interval`, recording each run in `status`
+pub fn spawn_sweeper(
+    storage: Arc<KeyStorage>,
+    clock: Arc<dyn Clock>,
+    notifications: Option<ExpiryNotifications>,
+    interval: Duration,
+    status: Arc<TaskStatus>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            let result = sweep(&storage, clock.as_ref(), notifications.as_ref()).await;
+            status.record(clock.now(), &result);
+            match result {
+                Ok(report) => {
+                    for key_id in &report.revoked {
+                        tracing::info!("Executed scheduled revocation of key {}", key_id);
+                    }
+                    for (key_id, days) in &report.notified {
+                        tracing::info!("Sent {}-day expiry notice for key {}", days, key_id);
+                    }
+                }
+                Err(e) => tracing::error!("Keystore sweep failed: {}", e),
+            }
+        }
+    })
+}
@@ -0,0 +1,202 @@
+//! Named defaults for key generation
+//!
+//! A template bundles the tags, description prefix, lifetime, strength and signing contexts a
+//! class of keys should carry, so a request naming it with `template` gets them without
+//! repeating them. Values in the request win over the template's; every override that drops a
+//! template value is reported as a warning. Templates are loaded from the JSON file named by
+//! `INKAN_KEY_TEMPLATES_FILE` and validated with the rest of the configuration at startup.
+
+use crate::key_generation::{MAX_ALLOWED_CONTEXTS, MAX_DESCRIPTION_LENGTH, MAX_KEY_NAME_LENGTH, MAX_TAGS, MAX_TAG_LENGTH};
+use crate::key_verification::validate_context;
+use crate::models::{GenerateKeyRequest, KeyManagementError, KeyStrength};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Defaults applied to keys generated with `template: <name>`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct KeyTemplate {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>, // What the template is for; shown by `GET /templates` only
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description_prefix: Option<String>, // Prepended to the request's description
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lifetime_days: Option<u32>, // Expiry given to keys that request none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_strength: Option<KeyStrength>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_contexts: Option<Vec<String>>, // Signing contexts the generated key is restricted to
+}
+
+/// The part of a `key:value` tag that names what it sets, or the whole tag
+fn tag_key(tag: &str) -> &str {
+    tag.split_once(':').map_or(tag, |(key, _)| key)
+}
+
+impl KeyTemplate {
+    /// Merges the template's defaults into `request`, returning a warning per overridden value
+    ///
+    /// Template tags come first. A request tag setting the same `key:` as a template tag
+    /// replaces it. The description prefix is prepended unless the description already starts
+    /// with it.
+    pub fn apply(&self, request: &mut GenerateKeyRequest, now: DateTime<Utc>) -> Vec<String> {
+        let mut warnings = Vec::new();
+
+        let requested_tags = request.tags.take().unwrap_or_default();
+        let mut tags = Vec::new();
+        for tag in &self.tags {
+            match requested_tags.iter().find(|requested| tag_key(requested) == tag_key(tag)) {
+                Some(requested) if requested != tag => {
+                    warnings.push(format!("Tag '{}' overrides '{}' from template '{}'", requested, tag, self.name));
+                }
+                Some(_) => {}
+                None => tags.push(tag.clone()),
+            }
+        }
+        tags.extend(requested_tags);
+        request.tags = (!tags.is_empty()).then_some(tags);
+
+        if let Some(prefix) = &self.description_prefix {
+            request.description = Some(match request.description.take() {
+                Some(description) if description.starts_with(prefix.as_str()) => description,
+                Some(description) => format!("{}{}", prefix, description),
+                None => prefix.trim_end().to_string(),
+            });
+        }
+
+        if let Some(days) = self.lifetime_days {
+            let template_expiry = now + Duration::days(days.into());
+            match request.expires_at {
+                None => request.expires_at = Some(template_expiry),
+                Some(expires_at) if expires_at > template_expiry => warnings.push(format!(
+                    "expires_at is later than the {}-day lifetime of template '{}'",
+                    days, self.name,
+                )),
+                Some(_) => {}
+            }
+        }
+
+        if let Some(strength) = &self.key_strength {
+            match &request.key_strength {
+                None => request.key_strength = Some(strength.clone()),
+                Some(requested) if requested != strength => warnings.push(format!(
+                    "key_strength {:?} overrides {:?} from template '{}'",
+                    requested, strength, self.name,
+                )),
+                Some(_) => {}
+            }
+        }
+
+        warnings
+    }
+
+    /// Checks the template against the limits a generation request is held to
+    fn validate(&self, max_key_lifetime_days: Option<u32>) -> Result<(), String> {
+        if self.name.trim().is_empty() || self.name.chars().count() > MAX_KEY_NAME_LENGTH {
+            return Err(format!("template names must be between 1 and {} characters", MAX_KEY_NAME_LENGTH));
+        }
+        if self.tags.len() > MAX_TAGS {
+            return Err(format!("at most {} tags are allowed", MAX_TAGS));
+        }
+        if self.tags.iter().any(|tag| tag.trim().is_empty() || tag.chars().count() > MAX_TAG_LENGTH) {
+            return Err(format!("tags must be between 1 and {} characters", MAX_TAG_LENGTH));
+        }
+        let mut seen = HashSet::new();
+        if let Some(tag) = self.tags.iter().find(|tag| !seen.insert(tag_key(tag))) {
+            return Err(format!("tag '{}' is set twice", tag));
+        }
+        if self.description_prefix.as_ref().is_some_and(|prefix| prefix.chars().count() > MAX_DESCRIPTION_LENGTH) {
+            return Err(format!("description_prefix must be at most {} characters", MAX_DESCRIPTION_LENGTH));
+        }
+        match (self.lifetime_days, max_key_lifetime_days) {
+            (Some(0), _) => return Err("lifetime_days must be at least 1".to_string()),
+            (Some(days), Some(max)) if days > max => {
+                return Err(format!("lifetime_days exceeds INKAN_MAX_KEY_LIFETIME_DAYS ({})", max));
+            }
+            _ => {}
+        }
+        if self.key_strength == Some(KeyStrength::Unknown) {
+            return Err("unknown key_strength".to_string());
+        }
+        if let Some(contexts) = &self.allowed_contexts {
+            if contexts.len() > MAX_ALLOWED_CONTEXTS {
+                return Err(format!("at most {} allowed_contexts are allowed", MAX_ALLOWED_CONTEXTS));
+            }
+            if contexts.iter().any(|context| context.is_empty() || validate_context(Some(context)).is_err()) {
+                return Err("allowed_contexts entries must be valid signing contexts".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses and validates the contents of a templates file, a JSON array of templates
+pub fn parse_templates(json: &str, max_key_lifetime_days: Option<u32>) -> Result<Vec<KeyTemplate>, KeyManagementError> {
+    let templates: Vec<KeyTemplate> = serde_json::from_str(json)
+        .map_err(|e| KeyManagementError::ValidationFailed(format!("INKAN_KEY_TEMPLATES_FILE is not a valid template list: {}", e)))?;
+    let mut names = HashSet::new();
+    for template in &templates {
+        template.validate(max_key_lifetime_days).map_err(|message| {
+            KeyManagementError::ValidationFailed(format!("Key template '{}': {}", template.name, message))
+        })?;
+        if !names.insert(template.name.as_str()) {
+            return Err(KeyManagementError::ValidationFailed(format!("Key template '{}' is defined twice", template.name)));
+        }
+    }
+    Ok(templates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn release_signing() -> KeyTemplate {
+        KeyTemplate {
+            name: "release-signing".to_string(),
+            summary: None,
+            tags: vec!["env:prod".to_string(), "team:platform".to_string(), "cost-center:4410".to_string()],
+            description_prefix: Some("[release] ".to_string()),
+            lifetime_days: Some(90),
+            key_strength: Some(KeyStrength::High),
+            allowed_contexts: Some(vec!["release".to_string()]),
+        }
+    }
+
+    #[test]
+    fn test_request_values_merge_over_template_defaults() {
+        let now = Utc::now();
+        let mut request = GenerateKeyRequest {
+            name: "CI".to_string(),
+            description: Some("Signs CI artifacts".to_string()),
+            tags: Some(vec!["team:security".to_string(), "ci".to_string()]),
+            expires_at: Some(now + Duration::days(365)),
+            ..Default::default()
+        };
+        let warnings = release_signing().apply(&mut request, now);
+
+        assert_eq!(request.tags.unwrap(), ["env:prod", "cost-center:4410", "team:security", "ci"]);
+        assert_eq!(request.description.as_deref(), Some("[release] Signs CI artifacts"));
+        assert_eq!(request.expires_at, Some(now + Duration::days(365)));
+        assert_eq!(request.key_strength, Some(KeyStrength::High));
+        assert_eq!(warnings, [
+            "Tag 'team:security' overrides 'team:platform' from template 'release-signing'",
+            "expires_at is later than the 90-day lifetime of template 'release-signing'",
+        ]);
+    }
+
+    #[test]
+    fn test_templates_are_validated_on_load() {
+        let json = serde_json::to_string(&[release_signing()]).unwrap();
+        assert_eq!(parse_templates(&json, None).unwrap(), [release_signing()]);
+        assert!(parse_templates(&json, Some(30)).is_err());
+        let duplicated = serde_json::to_string(&[release_signing(), release_signing()]).unwrap();
+        assert!(parse_templates(&duplicated, None).is_err());
+        let conflicting = KeyTemplate { tags: vec!["env:prod".to_string(), "env:dev".to_string()], ..release_signing() };
+        assert!(parse_templates(&serde_json::to_string(&[conflicting]).unwrap(), None).is_err());
+        assert!(parse_templates(r#"[{"name": "x", "trust": "high"}]"#, None).is_err());
+    }
+}
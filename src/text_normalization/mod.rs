@@ -0,0 +1,240 @@
+//! Normalization of key names, descriptions and tags
+//!
+//! Text that looks the same must be stored the same. Names, descriptions and tags are put in
+//! Unicode NFC, zero-width characters are stripped, and bidirectional overrides and control
+//! characters are refused, since they let two keys look identical or make a name display in
+//! a different order than it is stored. Zero-width joiners and non-joiners are kept where they
+//! join characters within one grapheme cluster, as in emoji sequences and Indic conjuncts, and
+//! refused anywhere else. Names and tags also have whitespace runs collapsed to
+//! one space. Tags compare case-insensitively, and lengths are counted in grapheme clusters,
+//! which is what a reader counts as characters.
+//!
+//! Homoglyphs from different scripts, such as Latin `a` and Cyrillic `а`, are not confused
+//! with one another; they are distinct text.
+
+use crate::models::{KeyPair, MetadataRevision};
+use chrono::{DateTime, Utc};
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Reason recorded in the history of keys normalized when the keystore is loaded
+pub const NORMALIZATION_REASON: &str = "unicode-normalization";
+
+/// Invisible characters stripped from text
+fn is_zero_width(c: char) -> bool {
+    matches!(c, '\u{200B}' | '\u{2060}' | '\u{FEFF}' | '\u{180E}')
+}
+
+/// Zero-width joiner and non-joiner, which change how the characters around them render
+fn is_joiner(c: char) -> bool {
+    matches!(c, '\u{200C}' | '\u{200D}')
+}
+
+/// Characters that change the display order of the text around them
+fn is_bidi_control(c: char) -> bool {
+    matches!(c, '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}' | '\u{200E}' | '\u{200F}' | '\u{061C}')
+}
+
+/// Why a text was refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextError {
+    BidiControl,
+    ControlCharacter,
+    StrayJoiner,
+}
+
+impl std::fmt::Display for TextError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TextError::BidiControl => write!(f, "must not contain bidirectional control characters"),
+            TextError::ControlCharacter => write!(f, "must not contain control characters"),
+            TextError::StrayJoiner => write!(f, "must not contain zero-width joiners outside a joined character"),
+        }
+    }
+}
+
+/// Normalizes `value`, dropping refused characters instead of failing when `strict` is off
+fn normalize(value: &str, multiline: bool, strict: bool) -> Result<String, TextError> {
+    let mut kept = String::with_capacity(value.len());
+    for c in value.chars() {
+        let refused = if is_bidi_control(c) {
+            Some(TextError::BidiControl)
+        } else if c.is_control() && !(multiline && matches!(c, '\n' | '\r' | '\t')) {
+            Some(TextError::ControlCharacter)
+        } else {
+            None
+        };
+        match refused {
+            Some(error) if strict => return Err(error),
+            Some(_) => {}
+            None if is_zero_width(c) => {}
+            None => kept.push(c),
+        }
+    }
+
+    let normalized: String = joined_only(&kept, strict)?.nfc().collect();
+    if multiline {
+        return Ok(normalized.trim().to_string());
+    }
+    Ok(normalized.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+/// Keeps joiners that have a character on both sides within their grapheme cluster
+///
+/// Any other joiner is refused when `strict` is on, and dropped otherwise.
+fn joined_only(value: &str, strict: bool) -> Result<String, TextError> {
+    if !value.chars().any(is_joiner) {
+        return Ok(value.to_string());
+    }
+
+    let mut kept = String::with_capacity(value.len());
+    for grapheme in value.graphemes(true) {
+        let chars: Vec<char> = grapheme.chars().collect();
+        for (i, &c) in chars.iter().enumerate() {
+            let joins = !chars[..i].iter().all(|&c| is_joiner(c)) && !chars[i + 1..].iter().all(|&c| is_joiner(c));
+            match c {
+                c if !is_joiner(c) || joins => kept.push(c),
+                _ if strict => return Err(TextError::StrayJoiner),
+                _ => {}
+            }
+        }
+    }
+    Ok(kept)
+}
+
+/// Normalizes a name or tag, refusing bidi controls and control characters
+pub fn normalize_line(value: &str) -> Result<String, TextError> {
+    normalize(value, false, true)
+}
+
+/// Normalizes a description, which may span lines and contain tabs
+pub fn normalize_multiline(value: &str) -> Result<String, TextError> {
+    normalize(value, true, true)
+}
+
+/// Normalizes a name or tag from a source that cannot be refused, dropping what would be
+pub fn clean_line(value: &str) -> String {
+    normalize(value, false, false).unwrap_or_default()
+}
+
+/// Cleans a key name, keeping it as it was if nothing would be left
+pub fn clean_name(name: &str) -> String {
+    Some(clean_line(name)).filter(|cleaned| !cleaned.is_empty()).unwrap_or_else(|| name.to_string())
+}
+
+/// Normalizes a description from a source that cannot be refused, dropping what would be
+pub fn clean_multiline(value: &str) -> String {
+    normalize(value, true, false).unwrap_or_default()
+}
+
+/// Cleans tags and drops empty ones and case-insensitive duplicates, keeping the first spelling
+pub fn clean_tags(tags: &[String]) -> Vec<String> {
+    let mut cleaned: Vec<String> = Vec::with_capacity(tags.len());
+    for tag in tags.iter().map(|tag| clean_line(tag)) {
+        if !tag.is_empty() && !cleaned.iter().any(|kept| same_folded(kept, &tag)) {
+            cleaned.push(tag);
+        }
+    }
+    cleaned
+}
+
+/// Length as a reader counts it, in extended grapheme clusters
+pub fn grapheme_len(value: &str) -> usize {
+    value.graphemes(true).count()
+}
+
+/// Form in which tags are compared: lowercased, then recomposed
+pub fn fold(value: &str) -> String {
+    value.to_lowercase().nfc().collect()
+}
+
+/// Whether two normalized texts are equal ignoring case; allocates only for non-ASCII text
+pub fn same_folded(a: &str, b: &str) -> bool {
+    if a.is_ascii() && b.is_ascii() {
+        return a.eq_ignore_ascii_case(b);
+    }
+    fold(a) == fold(b)
+}
+
+/// Normalizes a stored key's name, description and tags, recording the originals in its history
+///
+/// Returns whether anything changed. A name that would be left empty is kept as it was.
+pub fn normalize_stored_key(key_pair: &mut KeyPair, now: DateTime<Utc>) -> bool {
+    let name = clean_name(&key_pair.name);
+    let description = key_pair.description.as_deref().map(clean_multiline);
+    let tags = clean_tags(&key_pair.tags);
+    if name == key_pair.name && description == key_pair.description && tags == key_pair.tags {
+        return false;
+    }
+
+    key_pair.metadata_history.push(MetadataRevision {
+        changed_at: now,
+        reason: NORMALIZATION_REASON.to_string(),
+        name: std::mem::replace(&mut key_pair.name, name),
+        description: std::mem::replace(&mut key_pair.description, description),
+        tags: std::mem::replace(&mut key_pair.tags, tags),
+    });
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_generation::generate_seeded_test_key_pair;
+
+    #[test]
+    fn test_confusable_text_normalizes_to_one_form() {
+        // Precomposed and decomposed accents, zero-width joiners and odd spacing look alike
+        assert_eq!(normalize_line("Caf\u{e9} Root").unwrap(), "Caf\u{e9} Root");
+        assert_eq!(normalize_line("Cafe\u{301}\u{200B} \u{a0} Root").unwrap(), "Caf\u{e9} Root");
+        assert_eq!(normalize_line("rel\u{200D}ease"), Err(TextError::StrayJoiner));
+        assert_eq!(clean_line("rel\u{200D}ease\u{200C}"), "release");
+        // Joiners inside an emoji sequence or a conjunct are part of the character
+        assert_eq!(normalize_line("\u{1F469}\u{200D}\u{1F4BB} Root").unwrap(), "\u{1F469}\u{200D}\u{1F4BB} Root");
+        assert_eq!(normalize_line("\u{915}\u{94D}\u{200D}\u{937}").unwrap(), "\u{915}\u{94D}\u{200D}\u{937}");
+        assert_eq!(grapheme_len("\u{1F469}\u{200D}\u{1F4BB}"), 1);
+        assert_eq!(normalize_line("invoice\u{202E}fdp.exe"), Err(TextError::BidiControl));
+        assert_eq!(normalize_line("line\nbreak"), Err(TextError::ControlCharacter));
+        assert_eq!(normalize_multiline("  first\n\tsecond\u{2066}  ").unwrap_err(), TextError::BidiControl);
+        assert_eq!(normalize_multiline("  first\n\tsecond  ").unwrap(), "first\n\tsecond");
+        assert_eq!(clean_line("invoice\u{202E}fdp.exe"), "invoicefdp.exe");
+
+        // A decomposed accent or a flag is one character to a reader, however many code points
+        assert_eq!(grapheme_len("e\u{301}\u{1F1EF}\u{1F1F5}"), 2);
+    }
+
+    #[test]
+    fn test_mixed_script_tags_fold_case_but_keep_scripts_apart() {
+        assert!(same_folded("Release", "rELEASE"));
+        assert!(same_folded("Тест", "тЕСТ"));
+        assert!(same_folded("Straße", "STRAßE"));
+        // Latin "a" and Cyrillic "а" are different letters
+        assert!(!same_folded("prod-a", "prod-\u{430}"));
+        assert_eq!(
+            clean_tags(&["Prod".to_string(), "prod\u{200B}".to_string(), "\u{2060}".to_string(), "prod-\u{430}".to_string()]),
+            ["Prod", "prod-\u{430}"],
+        );
+    }
+
+    #[test]
+    fn test_stored_keys_keep_their_original_metadata() {
+        let now = Utc::now();
+        let mut key_pair = generate_seeded_test_key_pair("Root", 0);
+        key_pair.name = "Cafe\u{301}\u{202E} Root".to_string();
+        key_pair.tags = vec!["Release".to_string(), "release\u{FEFF}".to_string()];
+        let original = key_pair.clone();
+
+        assert!(normalize_stored_key(&mut key_pair, now));
+        assert_eq!(key_pair.name, "Caf\u{e9} Root");
+        assert_eq!(key_pair.tags, ["Release"]);
+        assert_eq!(key_pair.metadata_history, [MetadataRevision {
+            changed_at: now,
+            reason: NORMALIZATION_REASON.to_string(),
+            name: original.name,
+            description: original.description,
+            tags: original.tags,
+        }]);
+        assert!(!normalize_stored_key(&mut key_pair, now));
+        assert_eq!(key_pair.metadata_history.len(), 1);
+    }
+}
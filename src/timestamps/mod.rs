@@ -0,0 +1,104 @@
+//! Timestamps accepted from clients and kept in the keystore
+//!
+//! Every instant a request carries, and every expiry the keystore stores, is an RFC 3339
+//! timestamp with an explicit offset. It is converted to UTC and truncated to whole
+//! milliseconds as it is read, so an expiry sent as `2026-03-01T10:30:00.123456789+01:00` is the
+//! same instant after any number of saves and loads, and is written back as
+//! `2026-03-01T09:30:00.123Z`. A timestamp without an offset is refused rather than guessed to be
+//! UTC or local time, since guessing wrong moves the instant by hours.
+//!
+//! Truncating never moves an expiry or validity window later than the client asked. Signed
+//! validity windows and signing times are bound at millisecond precision already, see
+//! `crate::key_verification::build_signing_message`.
+//!
+//! Fields use the module through `#[serde(with = "crate::timestamps")]`, or
+//! `#[serde(default, with = "crate::timestamps::option")]` when optional.
+
+use chrono::{DateTime, NaiveDateTime, SecondsFormat, Utc};
+use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+/// Example shown to clients that send a timestamp that cannot be read
+const EXAMPLE: &str = "2026-03-01T09:30:00Z or 2026-03-01T10:30:00+01:00";
+
+/// `time` truncated to whole milliseconds; a leap second reads as the second after it
+pub fn normalize(time: DateTime<Utc>) -> DateTime<Utc> {
+    DateTime::from_timestamp_millis(time.timestamp_millis()).unwrap_or(time)
+}
+
+/// Reads an RFC 3339 timestamp with an offset as a normalized UTC instant
+pub fn parse(value: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(normalize(time.with_timezone(&Utc)));
+    }
+    let naive = ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"].iter()
+        .any(|format| NaiveDateTime::parse_from_str(value, format).is_ok());
+    if naive {
+        Err(format!("timestamp `{}` has no UTC offset; send RFC 3339 with `Z` or an offset, such as {}", value, EXAMPLE))
+    } else {
+        Err(format!("`{}` is not an RFC 3339 timestamp, such as {}", value, EXAMPLE))
+    }
+}
+
+/// How a normalized timestamp is written: RFC 3339 in UTC with `Z`, with milliseconds only when
+/// it has any
+pub fn format(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+pub fn serialize<S: Serializer>(time: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format(normalize(*time)))
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Utc>, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    parse(&value).map_err(D::Error::custom)
+}
+
+/// The same for optional timestamps
+pub mod option {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(time: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error> {
+        match time {
+            Some(time) => super::serialize(time, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|value| parse(&value).map_err(D::Error::custom))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timestamps_normalize_to_utc_milliseconds_and_refuse_naive_times() {
+        for (input, expected) in [
+            ("2026-03-01T10:30:00+01:00", "2026-03-01T09:30:00Z"),
+            ("2026-03-01T09:30:00.123456789Z", "2026-03-01T09:30:00.123Z"),
+            ("2026-03-01t04:00:00.999999-05:30", "2026-03-01T09:30:00.999Z"),
+            ("2026-03-01T09:30:00-00:00", "2026-03-01T09:30:00Z"),
+            // Offsets crossing a leap day and a year
+            ("2024-03-01T00:30:00+01:00", "2024-02-29T23:30:00Z"),
+            ("2026-12-31T23:30:00-01:00", "2027-01-01T00:30:00Z"),
+            ("2016-12-31T23:59:60Z", "2017-01-01T00:00:00Z"),
+            ("9999-12-31T23:59:59.999999999Z", "9999-12-31T23:59:59.999Z"),
+        ] {
+            let time = parse(input).unwrap();
+            assert_eq!(format(time), expected, "{}", input);
+            assert_eq!(parse(&format(time)), Ok(time), "{}", input);
+        }
+
+        for naive in ["2026-03-01T09:30:00", "2026-03-01 09:30:00.5"] {
+            assert!(parse(naive).unwrap_err().contains("has no UTC offset"), "{}", naive);
+        }
+        for invalid in ["2026-03-01", "1772357400", "2026-02-30T09:30:00Z", "next tuesday"] {
+            assert!(parse(invalid).unwrap_err().contains("is not an RFC 3339 timestamp"), "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,184 @@
+//! Offline root of trust
+//!
+//! A verifier on a host that cannot reach the keystore can start from a signed pin set instead,
+//! as served by `GET /keys/pinset`. The manifest must carry a notary signature made by the
+//! configured root public key; an unsigned, tampered, or otherwise signed manifest aborts
+//! startup. Its keys become the verifier's whole key set, held in memory only, and are replaced
+//! only on SIGHUP, after the new manifest has passed the same checks and was generated after the
+//! one it replaces.
+
+use crate::config::TrustManifestConfig;
+use crate::environment::unknown_environment;
+use crate::key_storage::KeyStorage;
+use crate::key_verification::decode_public_key;
+use crate::lifecycle::Lifecycle;
+use crate::models::{KeyManagementError, KeyPair, KeyStrength, KeyType, KeyUsage};
+use crate::pinset::{Pinset, SignedPinset};
+use crate::secret::SecretString;
+use crate::utils::public_key_to_fingerprint;
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+
+/// Reads the manifest at the configured path and checks it against the root key
+pub async fn load(config: &TrustManifestConfig) -> Result<Pinset, KeyManagementError> {
+    let content = tokio::fs::read(&config.path).await
+        .map_err(|e| KeyManagementError::StorageError(format!("Failed to read trust manifest {}: {}", config.path, e)))?;
+    let manifest: SignedPinset = serde_json::from_slice(&content)
+        .map_err(|e| KeyManagementError::ValidationFailed(format!("Trust manifest {} is not a pin set: {}", config.path, e)))?;
+    verify(manifest, &config.root_public_key)
+}
+
+/// Checks that `manifest` is signed by `root_public_key` and that every pinned key is intact
+pub fn verify(manifest: SignedPinset, root_public_key: &str) -> Result<Pinset, KeyManagementError> {
+    let refused = |reason: &str| KeyManagementError::SignatureVerificationFailed(format!("Trust manifest refused: {}", reason));
+    let Some(notary) = &manifest.notary else {
+        return Err(refused("it is not signed"));
+    };
+    if decode_public_key(&notary.public_key)?.to_bytes() != decode_public_key(root_public_key)?.to_bytes() {
+        return Err(refused("it is signed by a key other than the trust root"));
+    }
+    if !manifest.verify().map_err(|_| refused("its signature does not match its contents"))? {
+        return Err(refused("it is not signed"));
+    }
+    for pinned in &manifest.pinset.keys {
+        if public_key_to_fingerprint(&pinned.public_key).ok().as_ref() != Some(&pinned.fingerprint) {
+            return Err(refused(&format!("the fingerprint of key {} does not match its public key", pinned.key_id)));
+        }
+    }
+    Ok(manifest.pinset)
+}
+
+/// The pinned keys as public-only key pairs, created when the manifest was generated
+///
+/// The manifest does not carry each key's tags, only the ones it was filtered by, so the keys
+/// are served untagged.
+pub fn key_pairs(pinset: &Pinset) -> Vec<KeyPair> {
+    pinset.keys.iter()
+        .map(|pinned| KeyPair {
+            id: pinned.key_id,
+            name: pinned.name.clone(),
+            description: None,
+            public_key: pinned.public_key.clone(),
+            private_key: SecretString::default(),
+            salt: None,
+            created_at: pinset.generated_at,
+            last_used: None,
+            expires_at: pinned.expires_at,
+            lifecycle: Lifecycle::Active,
+            tags: Vec::new(),
+            key_type: KeyType::Ed25519,
+            key_strength: KeyStrength::default(),
+            kdf: None,
+            fingerprint: Some(pinned.fingerprint.clone()),
+            revocation_scheduled_at: None,
+            usage: KeyUsage::default(),
+            notified_thresholds: Default::default(),
+            hsm: None,
+            allowed_contexts: None,
+            metadata_history: Vec::new(),
+            environment: unknown_environment(),
+            lifecycle_history: Vec::new(),
+            envelope_history: Vec::new(),
+            exportable: false,
+            default_output_format: None,
+            default_hash_algorithm: None,
+            default_encoding: None,
+        })
+        .collect()
+}
+
+/// Loads and verifies the manifest, then makes its keys the whole key set of `storage`
+///
+/// `installed` is when the manifest already served was generated. A manifest generated no later
+/// than that is refused, so an older signed manifest cannot be replayed to bring back keys that
+/// have since been dropped.
+pub async fn install(
+    storage: &KeyStorage,
+    config: &TrustManifestConfig,
+    installed: Option<DateTime<Utc>>,
+) -> Result<Pinset, KeyManagementError> {
+    let pinset = load(config).await?;
+    if let Some(installed) = installed.filter(|installed| pinset.generated_at <= *installed) {
+        return Err(KeyManagementError::SignatureVerificationFailed(format!(
+            "Trust manifest refused: it was generated at {}, not after the installed manifest of {}",
+            crate::timestamps::format(pinset.generated_at),
+            crate::timestamps::format(installed),
+        )));
+    }
+    storage.replace_with_trusted(key_pairs(&pinset)).await;
+    Ok(pinset)
+}
+
+/// Re-reads the manifest on every SIGHUP; a manifest that fails verification or is not newer
+/// than the one installed at `installed` leaves the keys already served in place
+#[cfg(unix)]
+pub fn spawn_reload_on_hangup(
+    storage: Arc<KeyStorage>,
+    config: TrustManifestConfig,
+    mut installed: Option<DateTime<Utc>>,
+) -> Result<(), KeyManagementError> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = signal(SignalKind::hangup())
+        .map_err(|e| KeyManagementError::InternalError(format!("Failed to listen for SIGHUP: {}", e)))?;
+    tokio::spawn(async move {
+        while hangups.recv().await.is_some() {
+            match install(&storage, &config, installed).await {
+                Ok(pinset) => {
+                    tracing::warn!("Reloaded trust manifest {} on SIGHUP: {} keys", config.path, pinset.keys.len());
+                    installed = Some(pinset.generated_at);
+                }
+                Err(e) => tracing::error!("Kept the current keys; reloaded trust manifest {} was refused: {}", config.path, e),
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Only Unix hosts deliver SIGHUP; elsewhere the manifest is read once at startup
+#[cfg(not(unix))]
+pub fn spawn_reload_on_hangup(
+    _storage: Arc<KeyStorage>,
+    _config: TrustManifestConfig,
+    _installed: Option<DateTime<Utc>>,
+) -> Result<(), KeyManagementError> {
+    tracing::warn!("SIGHUP is not available on this platform; the trust manifest is read only at startup");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::KdfParams;
+    use crate::key_generation::generate_seeded_test_key_pair;
+    use crate::key_verification::load_signing_key;
+    use base64::Engine;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_manifest_must_be_signed_by_the_root_and_untouched() {
+        let root = generate_seeded_test_key_pair("Trust Root", 1);
+        let root_key = load_signing_key(root.private_key.expose_for_signing(), None, &KdfParams::default(), None).unwrap();
+        let other = generate_seeded_test_key_pair("Other Notary", 2);
+        let other_key = load_signing_key(other.private_key.expose_for_signing(), None, &KdfParams::default(), None).unwrap();
+        let pinned = generate_seeded_test_key_pair("Release Signing", 0);
+        let pinset = Pinset::new(std::slice::from_ref(&pinned), &[], crate::timestamps::normalize(Utc::now()));
+
+        let signed = pinset.clone().sign(Some((root.id, &root_key))).unwrap();
+        let trusted = verify(signed.clone(), &root.public_key).unwrap();
+        let keys = key_pairs(&trusted);
+        assert_eq!((keys[0].id, keys[0].public_key.as_str()), (pinned.id, pinned.public_key.as_str()));
+        assert!(keys[0].private_key.expose_for_signing().is_empty());
+
+        let error = |manifest: SignedPinset| verify(manifest, &root.public_key).unwrap_err().to_string();
+        assert!(error(pinset.clone().sign(None).unwrap()).contains("not signed"));
+        assert!(error(pinset.clone().sign(Some((Uuid::new_v4(), &other_key))).unwrap()).contains("other than the trust root"));
+        let mut swapped = signed.clone();
+        swapped.pinset.keys[0].public_key = other.public_key.clone();
+        swapped.pinset.keys[0].fingerprint = public_key_to_fingerprint(&other.public_key).unwrap();
+        assert!(error(swapped).contains("does not match its contents"));
+        let mut forged = signed.clone();
+        forged.notary.as_mut().unwrap().signature = base64::engine::general_purpose::STANDARD.encode([0u8; 64]);
+        assert!(error(forged).contains("does not match its contents"));
+    }
+}
@@ -0,0 +1,305 @@
+//! Usage reports
+//!
+//! `GET /reports/usage` counts the keys generated, signatures made, verifications and
+//! revocations between two UTC dates, grouped by tenant, owner or key. Reports are computed on
+//! the fly from the stored, archived and deleted keys: generations and revocations from the
+//! keys' timestamps, signatures and verifications from the per-day counts in their usage.
+//!
+//! Tenants and owners are named by `tenant:` and `owner:` tags; keys without one are reported
+//! under a `null` group.
+
+use crate::lifecycle::Lifecycle;
+use crate::models::{KeyManagementError, KeyPair};
+use crate::notifications::OWNER_TAG_PREFIX;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::sync::Mutex;
+
+/// Tag prefix naming the tenant a key is billed to, e.g. `tenant:finance`
+pub const TENANT_TAG_PREFIX: &str = "tenant:";
+/// Longest date range one report may cover, in days
+pub const MAX_REPORT_DAYS: i64 = 366;
+/// Seconds a computed report is served again before it is recomputed
+pub const REPORT_CACHE_TTL_SECS: i64 = 60;
+/// Media type of the CSV variant
+pub const CSV_CONTENT_TYPE: &str = "text/csv; charset=utf-8";
+
+/// What a report's rows count usage by
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupBy {
+    Tenant,
+    Owner,
+    #[default]
+    Key,
+}
+
+impl GroupBy {
+    /// The name of the group column
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GroupBy::Tenant => "tenant",
+            GroupBy::Owner => "owner",
+            GroupBy::Key => "key",
+        }
+    }
+
+    /// The group a key's usage is counted in, if it has one
+    pub fn group_of(&self, key_pair: &KeyPair) -> Option<String> {
+        let tagged = |prefix: &str| key_pair.tags.iter().find_map(|tag| tag.strip_prefix(prefix)).map(str::to_string);
+        match self {
+            GroupBy::Tenant => tagged(TENANT_TAG_PREFIX),
+            GroupBy::Owner => tagged(OWNER_TAG_PREFIX),
+            GroupBy::Key => Some(key_pair.id.to_string()),
+        }
+    }
+}
+
+/// Operations counted in a report
+#[derive(Debug, Clone, Copy, Default, Serialize, PartialEq)]
+pub struct UsageCounts {
+    pub generations: u64,
+    pub signs: u64,
+    pub verifies: u64,
+    pub revocations: u64,
+}
+
+impl UsageCounts {
+    fn add(&mut self, other: &UsageCounts) {
+        self.generations += other.generations;
+        self.signs += other.signs;
+        self.verifies += other.verifies;
+        self.revocations += other.revocations;
+    }
+}
+
+/// Usage of one group
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct UsageRow {
+    pub group: Option<String>,
+    #[serde(flatten)]
+    pub counts: UsageCounts,
+}
+
+/// Usage between two dates, both included
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct UsageReport {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub group_by: GroupBy,
+    pub generated_at: DateTime<Utc>,
+    /// Groups by name, the `null` group last
+    pub rows: Vec<UsageRow>,
+    pub totals: UsageCounts,
+}
+
+/// When a key was revoked, if it is revoked at `now`
+///
+/// An immediate revocation sets the key's expiry to the time it was made.
+pub fn revoked_at(key_pair: &KeyPair, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    match key_pair.revocation_scheduled_at {
+        Some(at) if at <= now => Some(at),
+        _ if key_pair.lifecycle == Lifecycle::Revoked => key_pair.expires_at,
+        _ => None,
+    }
+}
+
+/// Refuses ranges that end before they start or span more than [`MAX_REPORT_DAYS`]
+pub fn check_range(from: NaiveDate, to: NaiveDate) -> Result<(), KeyManagementError> {
+    if to < from {
+        return Err(KeyManagementError::ValidationFailed(format!("to ({}) is before from ({})", to, from)));
+    }
+    let days = (to - from).num_days() + 1;
+    if days > MAX_REPORT_DAYS {
+        return Err(KeyManagementError::ValidationFailed(format!(
+            "The report covers {} days; at most {} are allowed",
+            days, MAX_REPORT_DAYS,
+        )));
+    }
+    Ok(())
+}
+
+impl UsageReport {
+    /// Counts the usage of `keys` between `from` and `to`, both included
+    ///
+    /// A key listed twice, such as one both stored and deleted, is counted once.
+    pub fn aggregate(keys: &[KeyPair], from: NaiveDate, to: NaiveDate, group_by: GroupBy, generated_at: DateTime<Utc>) -> Self {
+        let in_range = |at: DateTime<Utc>| (from..=to).contains(&at.date_naive());
+        let mut seen = std::collections::HashSet::new();
+        let mut groups: BTreeMap<Option<String>, UsageCounts> = BTreeMap::new();
+        for key_pair in keys.iter().filter(|key_pair| seen.insert(key_pair.id)) {
+            let mut counts = UsageCounts {
+                generations: u64::from(in_range(key_pair.created_at)),
+                revocations: u64::from(revoked_at(key_pair, generated_at).is_some_and(in_range)),
+                ..UsageCounts::default()
+            };
+            for day in key_pair.usage.days.range(from..=to).map(|(_, day)| day) {
+                counts.signs += day.signs;
+                counts.verifies += day.verifies;
+            }
+            if counts != UsageCounts::default() {
+                groups.entry(group_by.group_of(key_pair)).or_default().add(&counts);
+            }
+        }
+
+        // BTreeMap orders `None` first; unassigned usage reads better as the last row
+        let unassigned = groups.remove(&None);
+        let rows: Vec<UsageRow> = groups.into_iter()
+            .chain(unassigned.map(|counts| (None, counts)))
+            .map(|(group, counts)| UsageRow { group, counts })
+            .collect();
+        let mut totals = UsageCounts::default();
+        rows.iter().for_each(|row| totals.add(&row.counts));
+        Self { from, to, group_by, generated_at, rows, totals }
+    }
+
+    /// The report as CSV: a header naming the group column, one line per row, then the totals
+    pub fn to_csv(&self) -> String {
+        let mut out = format!("{},generations,signs,verifies,revocations\r\n", self.group_by.as_str());
+        let mut line = |group: &str, counts: &UsageCounts| {
+            let _ = write!(
+                out,
+                "{},{},{},{},{}\r\n",
+                csv_field(group), counts.generations, counts.signs, counts.verifies, counts.revocations,
+            );
+        };
+        for row in &self.rows {
+            line(row.group.as_deref().unwrap_or(""), &row.counts);
+        }
+        line("total", &self.totals);
+        out
+    }
+}
+
+/// Quotes a CSV field that holds a separator, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Reports computed recently, served again until they are [`REPORT_CACHE_TTL_SECS`] old
+#[derive(Default)]
+pub struct UsageReportCache {
+    reports: Mutex<HashMap<(NaiveDate, NaiveDate, GroupBy), UsageReport>>,
+}
+
+impl UsageReportCache {
+    /// The cached report for a range and grouping, unless it is stale at `now`
+    pub fn get(&self, from: NaiveDate, to: NaiveDate, group_by: GroupBy, now: DateTime<Utc>) -> Option<UsageReport> {
+        let mut reports = self.reports.lock().unwrap_or_else(|e| e.into_inner());
+        let fresh = |report: &UsageReport| now < report.generated_at + Duration::seconds(REPORT_CACHE_TTL_SECS);
+        reports.retain(|_, report| fresh(report));
+        reports.get(&(from, to, group_by)).cloned()
+    }
+
+    pub fn insert(&self, report: UsageReport) {
+        let mut reports = self.reports.lock().unwrap_or_else(|e| e.into_inner());
+        reports.insert((report.from, report.to, report.group_by), report);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_generation::generate_test_key_pair;
+    use chrono::TimeZone;
+
+    fn at(day: u32, hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, day, hour, 0, 0).unwrap()
+    }
+
+    fn date(day: u32) -> NaiveDate {
+        at(day, 0).date_naive()
+    }
+
+    fn key(tags: &[&str], created_at: DateTime<Utc>) -> KeyPair {
+        let mut key_pair = generate_test_key_pair("Report").unwrap();
+        key_pair.tags = tags.iter().map(|tag| tag.to_string()).collect();
+        key_pair.created_at = created_at;
+        key_pair
+    }
+
+    #[test]
+    fn test_aggregates_by_owner_within_the_range() {
+        let mut release = key(&["owner:release", "tenant:acme"], at(2, 9));
+        release.usage.record_sign(at(2, 10));
+        release.usage.record_sign(at(3, 23));
+        release.usage.record_verify(at(4, 1));
+        release.usage.record_sign(at(20, 1)); // after the range
+
+        let mut web = key(&["owner:web", "tenant:acme"], at(1, 0));
+        web.usage.record_verify(at(5, 0));
+        web.lifecycle = Lifecycle::Revoked;
+        web.expires_at = Some(at(6, 12));
+
+        let before = key(&["owner:web"], at(1, 0) - Duration::days(30)); // outside the range, unused
+        let mut untagged = key(&[], at(10, 0));
+        untagged.usage.record_sign(at(10, 1));
+
+        let keys = [release.clone(), web, before, untagged, release];
+        let report = UsageReport::aggregate(&keys, date(1), date(10), GroupBy::Owner, at(15, 0));
+        let row = |group: Option<&str>, generations, signs, verifies, revocations| UsageRow {
+            group: group.map(str::to_string),
+            counts: UsageCounts { generations, signs, verifies, revocations },
+        };
+        assert_eq!(report.rows, [
+            row(Some("release"), 1, 2, 1, 0),
+            row(Some("web"), 1, 0, 1, 1),
+            row(None, 1, 1, 0, 0),
+        ]);
+        assert_eq!(report.totals, UsageCounts { generations: 3, signs: 3, verifies: 2, revocations: 1 });
+
+        let by_tenant = UsageReport::aggregate(&keys, date(1), date(10), GroupBy::Tenant, at(15, 0));
+        assert_eq!(by_tenant.rows, [row(Some("acme"), 2, 2, 2, 1), row(None, 1, 1, 0, 0)]);
+    }
+
+    #[test]
+    fn test_scheduled_revocations_count_once_due() {
+        let mut key_pair = key(&[], at(1, 0));
+        key_pair.revocation_scheduled_at = Some(at(9, 0));
+        assert_eq!(revoked_at(&key_pair, at(8, 0)), None);
+        assert_eq!(revoked_at(&key_pair, at(9, 0)), Some(at(9, 0)));
+    }
+
+    #[test]
+    fn test_csv_quotes_fields_and_ends_with_totals() {
+        let report = UsageReport {
+            from: date(1),
+            to: date(31),
+            group_by: GroupBy::Owner,
+            generated_at: at(31, 0),
+            rows: vec![
+                UsageRow { group: Some("ops, \"core\"".to_string()), counts: UsageCounts { generations: 1, signs: 2, verifies: 3, revocations: 0 } },
+                UsageRow { group: None, counts: UsageCounts { generations: 0, signs: 1, verifies: 0, revocations: 1 } },
+            ],
+            totals: UsageCounts { generations: 1, signs: 3, verifies: 3, revocations: 1 },
+        };
+        assert_eq!(
+            report.to_csv(),
+            "owner,generations,signs,verifies,revocations\r\n\"ops, \"\"core\"\"\",1,2,3,0\r\n,0,1,0,1\r\ntotal,1,3,3,1\r\n",
+        );
+    }
+
+    #[test]
+    fn test_range_is_bounded() {
+        assert!(check_range(date(1), date(1)).is_ok());
+        assert!(check_range(date(2), date(1)).is_err());
+        assert!(check_range(date(1), date(1) + Duration::days(MAX_REPORT_DAYS - 1)).is_ok());
+        assert!(check_range(date(1), date(1) + Duration::days(MAX_REPORT_DAYS)).is_err());
+    }
+
+    #[test]
+    fn test_cached_reports_expire() {
+        let cache = UsageReportCache::default();
+        let report = UsageReport::aggregate(&[], date(1), date(2), GroupBy::Key, at(5, 0));
+        cache.insert(report.clone());
+        assert_eq!(cache.get(date(1), date(2), GroupBy::Key, at(5, 0) + Duration::seconds(59)), Some(report));
+        assert_eq!(cache.get(date(1), date(2), GroupBy::Owner, at(5, 0)), None);
+        assert_eq!(cache.get(date(1), date(2), GroupBy::Key, at(5, 0) + Duration::seconds(REPORT_CACHE_TTL_SECS)), None);
+    }
+}
@@ -0,0 +1,199 @@
+//! Key usage recorded off the keystore lock
+//!
+//! Every signature and verification bumps its key's counters and last use. Taking the keystore
+//! lock for each of them serialized parallel signing on one key behind every other keystore
+//! access. Instead, usage is counted here in atomics, one entry per key and UTC day, and the
+//! keystore folds the entries into its keys when the background flusher runs. Keys the
+//! keystore serves in the meantime have the pending usage merged in, so reads never lag.
+//!
+//! The entry map itself sits behind a read-write lock that recording only takes for reading, so
+//! recorders never wait on each other; it is taken for writing only to add a key's first entry
+//! of a day and to drain the map when folding, which cannot lose an update still in flight.
+
+use crate::models::{DayUsage, KeyPair, KeyUsage};
+use chrono::{DateTime, NaiveDate, Utc};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::RwLock;
+use uuid::Uuid;
+
+/// Stands for no timestamp in the epoch nanosecond fields
+const NONE: i64 = i64::MIN;
+
+/// What a key was used for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UsageEvent {
+    Sign,
+    Verify,
+    Use, // Marks the key used without counting anything
+}
+
+/// Usage of one key on one day not yet folded into the key
+#[derive(Debug)]
+struct PendingUsage {
+    signs: AtomicU64,
+    verifies: AtomicU64,
+    last_used_ns: AtomicI64,
+    last_sign_ns: AtomicI64,
+}
+
+impl Default for PendingUsage {
+    fn default() -> Self {
+        Self {
+            signs: AtomicU64::new(0),
+            verifies: AtomicU64::new(0),
+            last_used_ns: AtomicI64::new(NONE),
+            last_sign_ns: AtomicI64::new(NONE),
+        }
+    }
+}
+
+impl PendingUsage {
+    fn record(&self, event: UsageEvent, at: DateTime<Utc>) {
+        let at = at.timestamp_nanos_opt().unwrap_or(i64::MAX);
+        match event {
+            UsageEvent::Sign => {
+                self.signs.fetch_add(1, Ordering::Relaxed);
+                self.last_sign_ns.fetch_max(at, Ordering::Relaxed);
+                self.last_used_ns.fetch_max(at, Ordering::Relaxed);
+            }
+            UsageEvent::Verify => {
+                self.verifies.fetch_add(1, Ordering::Relaxed);
+            }
+            UsageEvent::Use => {
+                self.last_used_ns.fetch_max(at, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Adds this usage made on `day` to a key's last use and counters
+    fn apply_to(&self, day: NaiveDate, last_used: &mut Option<DateTime<Utc>>, usage: &mut KeyUsage) {
+        let time = |ns: &AtomicI64| match ns.load(Ordering::Relaxed) {
+            NONE => None,
+            ns => Some(DateTime::from_timestamp_nanos(ns)),
+        };
+        let counts = DayUsage { signs: self.signs.load(Ordering::Relaxed), verifies: self.verifies.load(Ordering::Relaxed) };
+        if counts != DayUsage::default() {
+            usage.add_day(day, counts, time(&self.last_sign_ns));
+        }
+        *last_used = (*last_used).max(time(&self.last_used_ns));
+    }
+
+    fn events(&self) -> u64 {
+        self.signs.load(Ordering::Relaxed) + self.verifies.load(Ordering::Relaxed)
+    }
+}
+
+/// Usage drained from the shadow, ready to fold into the keys
+pub struct DrainedUsage(HashMap<(Uuid, NaiveDate), PendingUsage>);
+
+impl DrainedUsage {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Folds the usage into `keys`, returning how many signatures and verifications belonged
+    /// to keys no longer there
+    pub fn fold_into(self, keys: &mut HashMap<Uuid, KeyPair>) -> u64 {
+        let mut dropped = 0;
+        for ((key_id, day), pending) in self.0 {
+            match keys.get_mut(&key_id) {
+                Some(key_pair) => pending.apply_to(day, &mut key_pair.last_used, &mut key_pair.usage),
+                None => dropped += pending.events(),
+            }
+        }
+        dropped
+    }
+}
+
+/// Per-key, per-day usage counted in atomics
+#[derive(Debug, Default)]
+pub struct UsageShadow {
+    entries: RwLock<HashMap<(Uuid, NaiveDate), PendingUsage>>,
+}
+
+impl UsageShadow {
+    /// Records usage of a key that already has an entry for the day of `at`, returning whether
+    /// it had one
+    ///
+    /// Only the read lock is taken, so this never waits on other recorders.
+    pub fn try_record(&self, key_id: Uuid, event: UsageEvent, at: DateTime<Utc>) -> bool {
+        let entries = self.entries.read().unwrap_or_else(|e| e.into_inner());
+        match entries.get(&(key_id, at.date_naive())) {
+            Some(pending) => {
+                pending.record(event, at);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Records usage of a key, adding its entry for the day of `at` if needed
+    pub fn record(&self, key_id: Uuid, event: UsageEvent, at: DateTime<Utc>) {
+        if !self.try_record(key_id, event, at) {
+            let mut entries = self.entries.write().unwrap_or_else(|e| e.into_inner());
+            entries.entry((key_id, at.date_naive())).or_default().record(event, at);
+        }
+    }
+
+    /// Adds a key's pending usage to a copy of its last use and counters
+    pub fn merge(&self, key_id: Uuid, last_used: &mut Option<DateTime<Utc>>, usage: &mut KeyUsage) {
+        let entries = self.entries.read().unwrap_or_else(|e| e.into_inner());
+        let mut days: Vec<_> = entries.iter().filter(|((id, _), _)| *id == key_id).collect();
+        if days.is_empty() {
+            return;
+        }
+        days.sort_by_key(|((_, day), _)| *day);
+        for ((_, day), pending) in days {
+            pending.apply_to(*day, last_used, usage);
+        }
+    }
+
+    /// Whether any usage is waiting to be folded
+    pub fn is_empty(&self) -> bool {
+        self.entries.read().unwrap_or_else(|e| e.into_inner()).is_empty()
+    }
+
+    /// Takes every pending entry, leaving the shadow empty
+    ///
+    /// Taking the write lock waits out any recording still in progress, so every update lands
+    /// either in what is taken or in the emptied shadow.
+    pub fn drain(&self) -> DrainedUsage {
+        DrainedUsage(std::mem::take(&mut *self.entries.write().unwrap_or_else(|e| e.into_inner())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    #[test]
+    fn test_pending_usage_merges_and_folds_per_day() {
+        let shadow = UsageShadow::default();
+        let key_id = Uuid::new_v4();
+        let evening = Utc.with_ymd_and_hms(2024, 5, 1, 23, 59, 0).unwrap();
+        let morning = evening + Duration::minutes(2);
+        shadow.record(key_id, UsageEvent::Sign, morning);
+        shadow.record(key_id, UsageEvent::Sign, evening);
+        assert!(shadow.try_record(key_id, UsageEvent::Verify, evening));
+        assert!(!shadow.try_record(Uuid::new_v4(), UsageEvent::Sign, evening));
+
+        let (mut last_used, mut usage) = (None, KeyUsage::default());
+        shadow.merge(key_id, &mut last_used, &mut usage);
+        assert_eq!((usage.sign_count, usage.verify_count), (2, 1));
+        assert_eq!((last_used, usage.last_sign_at), (Some(morning), Some(morning)));
+        assert_eq!(usage.days[&evening.date_naive()], DayUsage { signs: 1, verifies: 1 });
+        assert_eq!(usage.days[&morning.date_naive()], DayUsage { signs: 1, verifies: 0 });
+
+        let mut key_pair = crate::key_generation::generate_test_key_pair("Busy").unwrap();
+        key_pair.id = key_id;
+        let mut keys = HashMap::from([(key_id, key_pair)]);
+        assert_eq!(shadow.drain().fold_into(&mut keys), 0);
+        assert_eq!((&keys[&key_id].usage, keys[&key_id].last_used), (&usage, last_used));
+        assert!(shadow.is_empty());
+
+        shadow.record(Uuid::new_v4(), UsageEvent::Sign, evening);
+        assert_eq!(shadow.drain().fold_into(&mut HashMap::new()), 1);
+    }
+}
@@ -2,6 +2,15 @@ use base64::Engine;
 use ed25519_dalek::{SigningKey, VerifyingKey};
 use sha2::{Digest, Sha256};
 
+/// Reduces a fingerprint to its 32 lowercase hex digits, as used in content-addressed URLs
+///
+/// Accepts the colon-separated form returned by [`public_key_to_fingerprint`] as well as the
+/// compact one, in either case. Returns `None` for anything else.
+pub fn compact_fingerprint(fingerprint: &str) -> Option<String> {
+    let compact: String = fingerprint.chars().filter(|c| *c != ':').collect::<String>().to_ascii_lowercase();
+    (compact.len() == 32 && compact.bytes().all(|b| b.is_ascii_hexdigit())).then_some(compact)
+}
+
 /// Converts a public key to a fingerprint for easy identification
 pub fn public_key_to_fingerprint(public_key_b64: &str) -> Result<String, String> {
     let public_key_bytes = base64::engine::general_purpose::STANDARD.decode(public_key_b64)
@@ -14,13 +23,13 @@ pub fn public_key_to_fingerprint(public_key_b64: &str) -> Result<String, String>
     // Take first 16 bytes and format as hex
     let fingerprint = hex::encode(&hash[..16]);
     
-    // Format as groups of 4 with colons
+    // Format as 4 groups of 8 hex chars with colons
     let mut formatted = String::new();
-    for (i, chunk) in fingerprint.as_bytes().chunks(4).enumerate() {
+    for (i, chunk) in fingerprint.as_bytes().chunks(8).enumerate() {
         if i > 0 {
             formatted.push(':');
         }
-        formatted.push_str(&hex::encode(chunk));
+        formatted.push_str(std::str::from_utf8(chunk).expect("hex output is ASCII"));
     }
     
     Ok(formatted)
@@ -76,11 +85,15 @@ pub fn validate_key_pair_compatibility(
         .map_err(|_| "Invalid private key encoding".to_string())?;
     
     // Try to create the keys
-    let public_key = VerifyingKey::from_bytes(&public_key_bytes.try_into().unwrap())
+    let public_key_bytes: [u8; 32] = public_key_bytes.try_into()
+        .map_err(|_| "Invalid public key length".to_string())?;
+    let public_key = VerifyingKey::from_bytes(&public_key_bytes)
         .map_err(|_| "Invalid public key format".to_string())?;
     
     // Create signing key from the private key bytes
-    let signing_key = SigningKey::from_keypair_bytes(&private_key_bytes.try_into().unwrap())
+    let private_key_bytes: [u8; 64] = private_key_bytes.try_into()
+        .map_err(|_| "Invalid private key length".to_string())?;
+    let signing_key = SigningKey::from_keypair_bytes(&private_key_bytes)
         .map_err(|_| "Invalid signing key".to_string())?;
     
     // Check if they correspond to each other
@@ -100,6 +113,31 @@ pub fn sanitize_key_name(name: &str) -> String {
         .to_string()
 }
 
+/// Keyed HMAC-SHA256 over a request, as checked by HMAC request authentication
+///
+/// The MAC covers `METHOD \n path \n timestamp \n body`, where `path` includes any query
+/// string and `timestamp` is in Unix seconds.
+pub fn request_mac(secret: &[u8], method: &str, path: &str, timestamp: i64, body: &[u8]) -> hmac::Hmac<Sha256> {
+    use hmac::Mac;
+    let mut mac = hmac::Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(method.to_ascii_uppercase().as_bytes());
+    mac.update(b"\n");
+    mac.update(path.as_bytes());
+    mac.update(b"\n");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b"\n");
+    mac.update(body);
+    mac
+}
+
+/// Computes the `X-Signature` header value for a request: the hex HMAC from [`request_mac`]
+///
+/// Send it with `X-Client-Id` and `X-Signature-Timestamp` set to the same `timestamp`.
+pub fn sign_request(secret: &[u8], method: &str, path: &str, timestamp: i64, body: &[u8]) -> String {
+    use hmac::Mac;
+    hex::encode(request_mac(secret, method, path, timestamp, body).finalize().into_bytes())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
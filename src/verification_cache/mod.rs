@@ -0,0 +1,210 @@
+//! Cache of recent signature verification results
+//!
+//! Viewers re-verify the same signature each time a document is opened, so identical checks
+//! arrive many times a minute. The cryptographic result for a given public key, document hash,
+//! signature and scheme never changes, so it is kept for a short TTL in a bounded LRU cache.
+//! Entries are keyed only on those inputs, never on a key id: whether a stored key is revoked or
+//! expired is looked up afresh on every request, as is whether a validity window has closed.
+
+use chrono::{DateTime, Duration, Utc};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Identifies one verification: SHA-256 over its length-prefixed inputs
+pub type CacheKey = [u8; 32];
+
+/// Builds the cache key for a verification from its scheme and inputs
+///
+/// Each part is length-prefixed, so no two different input lists share a key.
+pub fn cache_key(scheme: &str, parts: &[&[u8]]) -> CacheKey {
+    let mut hasher = Sha256::new();
+    for part in std::iter::once(scheme.as_bytes()).chain(parts.iter().copied()) {
+        hasher.update((part.len() as u64).to_be_bytes());
+        hasher.update(part);
+    }
+    hasher.finalize().into()
+}
+
+/// Hit and miss counts, for metrics
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CacheStats {
+    pub enabled: bool,
+    pub entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+struct Entry {
+    valid: bool,
+    expires_at: DateTime<Utc>,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct Entries {
+    by_key: HashMap<CacheKey, Entry>,
+    /// Keys by last use, least recent first
+    by_use: BTreeMap<u64, CacheKey>,
+    tick: u64,
+}
+
+impl Entries {
+    fn touch(&mut self, key: &CacheKey) {
+        self.tick += 1;
+        if let Some(entry) = self.by_key.get_mut(key) {
+            self.by_use.remove(&entry.last_used);
+            entry.last_used = self.tick;
+            self.by_use.insert(self.tick, *key);
+        }
+    }
+
+    fn remove(&mut self, key: &CacheKey) {
+        if let Some(entry) = self.by_key.remove(key) {
+            self.by_use.remove(&entry.last_used);
+        }
+    }
+}
+
+/// Bounded LRU cache of verification results
+pub struct VerificationCache {
+    capacity: usize,
+    ttl: Duration,
+    entries: Mutex<Entries>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl VerificationCache {
+    /// Cache holding up to `capacity` results for `ttl` each; a capacity of 0 disables it
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity,
+            ttl,
+            entries: Mutex::new(Entries::default()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Cache that never stores anything
+    pub fn disabled() -> Self {
+        Self::new(0, Duration::zero())
+    }
+
+    /// Whether results are cached at all
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    /// Returns the cached result for `key`, or runs `verify` and caches what it returns
+    ///
+    /// Errors are passed through and not cached.
+    pub fn get_or_verify<E>(&self, key: CacheKey, now: DateTime<Utc>, verify: impl FnOnce() -> Result<bool, E>) -> Result<bool, E> {
+        if !self.is_enabled() {
+            return verify();
+        }
+        if let Some(valid) = self.get(&key, now) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(valid);
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        // The lock is not held while verifying, so concurrent misses on one key may both verify
+        let valid = verify()?;
+        self.insert(key, valid, now);
+        Ok(valid)
+    }
+
+    fn get(&self, key: &CacheKey, now: DateTime<Utc>) -> Option<bool> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        match entries.by_key.get(key) {
+            Some(entry) if now < entry.expires_at => {
+                let valid = entry.valid;
+                entries.touch(key);
+                Some(valid)
+            }
+            Some(_) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn insert(&self, key: CacheKey, valid: bool, now: DateTime<Utc>) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.remove(&key);
+        while entries.by_key.len() >= self.capacity {
+            let Some((_, oldest)) = entries.by_use.pop_first() else { break };
+            entries.by_key.remove(&oldest);
+        }
+        entries.tick += 1;
+        let tick = entries.tick;
+        entries.by_key.insert(key, Entry { valid, expires_at: now + self.ttl, last_used: tick });
+        entries.by_use.insert(tick, key);
+    }
+
+    /// Current size and hit counts
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            enabled: self.is_enabled(),
+            entries: self.entries.lock().unwrap_or_else(|e| e.into_inner()).by_key.len(),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Default for VerificationCache {
+    fn default() -> Self {
+        Self::disabled()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn test_cache_evicts_least_recent_and_expires() {
+        let cache = VerificationCache::new(2, Duration::seconds(30));
+        let now = Utc::now();
+        let verified = Cell::new(0);
+        let verify = |valid: bool| {
+            let verified = &verified;
+            move || -> Result<bool, ()> {
+                verified.set(verified.get() + 1);
+                Ok(valid)
+            }
+        };
+        let (a, b, c) = (cache_key("raw", &[b"a"]), cache_key("raw", &[b"b"]), cache_key("raw", &[b"c"]));
+        assert_ne!(cache_key("raw", &[b"ab", b"c"]), cache_key("raw", &[b"a", b"bc"]));
+
+        assert_eq!(cache.get_or_verify(a, now, verify(true)), Ok(true));
+        assert_eq!(cache.get_or_verify(b, now, verify(false)), Ok(false));
+        assert_eq!(cache.get_or_verify(a, now, verify(false)), Ok(true));
+        assert_eq!(verified.get(), 2);
+
+        // b is now least recently used, so c pushes it out
+        cache.get_or_verify(c, now, verify(true)).unwrap();
+        cache.get_or_verify(a, now, verify(true)).unwrap();
+        assert_eq!(verified.get(), 3);
+        cache.get_or_verify(b, now, verify(false)).unwrap();
+        assert_eq!(verified.get(), 4);
+
+        // Errors are not cached, and entries lapse after the TTL
+        assert_eq!(cache.get_or_verify(cache_key("raw", &[b"d"]), now, || Err::<bool, _>("bad")), Err("bad"));
+        cache.get_or_verify(b, now + Duration::seconds(30), verify(false)).unwrap();
+        assert_eq!(verified.get(), 5);
+        assert_eq!(cache.stats(), CacheStats { enabled: true, entries: 2, hits: 2, misses: 6 });
+
+        let disabled = VerificationCache::disabled();
+        disabled.get_or_verify(a, now, verify(true)).unwrap();
+        disabled.get_or_verify(a, now, verify(true)).unwrap();
+        assert_eq!(verified.get(), 7);
+        assert_eq!(disabled.stats(), CacheStats::default());
+    }
+}
@@ -0,0 +1,67 @@
+//! HSM keys against a real PKCS#11 token
+//!
+//! Runs only with the `pkcs11` feature and a token to use, such as SoftHSM2:
+//!
+//! ```sh
+//! softhsm2-util --init-token --free --label inkan-test --so-pin 5678 --pin 1234
+//! INKAN_TEST_PKCS11_MODULE=/usr/lib/softhsm/libsofthsm2.so INKAN_TEST_PKCS11_PIN=1234 \
+//!     INKAN_TEST_PKCS11_SLOT=<slot printed above> cargo test --features pkcs11 --test pkcs11_softhsm
+//! ```
+//!
+//! Without `INKAN_TEST_PKCS11_MODULE` the test passes without touching a token. Builds with
+//! `verifier-only` cannot generate keys and skip it.
+#![cfg(all(feature = "pkcs11", not(feature = "verifier-only")))]
+
+use ed25519_dalek::Verifier;
+use inkan_key_management_module::config::Pkcs11Config;
+use inkan_key_management_module::key_generation::generate_hsm_key_pair;
+use inkan_key_management_module::key_verification::decode_public_key;
+use inkan_key_management_module::models::{GenerateKeyRequest, HsmKeyRef, KeyManagementError, KeyType};
+use inkan_key_management_module::pkcs11::Pkcs11Backend;
+use inkan_key_management_module::signing_backend::{load_signer, SigningBackend};
+
+#[test]
+fn test_softhsm_keys_generate_and_sign_on_the_token() {
+    let Ok(module) = std::env::var("INKAN_TEST_PKCS11_MODULE") else {
+        eprintln!("INKAN_TEST_PKCS11_MODULE is not set; skipping the PKCS#11 token test");
+        return;
+    };
+    let pin = std::env::var("INKAN_TEST_PKCS11_PIN").expect("INKAN_TEST_PKCS11_PIN");
+    let slot = std::env::var("INKAN_TEST_PKCS11_SLOT").expect("INKAN_TEST_PKCS11_SLOT").parse().expect("numeric slot");
+    let backend = Pkcs11Backend::open(&Pkcs11Config { module, pin }).unwrap();
+
+    // A fresh label per run, since keys stay on the token
+    let hsm = HsmKeyRef { slot, label: format!("inkan-test-{}", uuid::Uuid::new_v4()) };
+    let request = GenerateKeyRequest {
+        name: "SoftHSM Root".to_string(),
+        description: None,
+        password: None,
+        expires_at: None,
+        tags: None,
+        key_strength: None,
+        hsm: Some(hsm.clone()),
+        generate_password: false,
+        template: None,
+        environment: None,
+        fast: false,
+        exportable: None,
+        default_output_format: None,
+        default_hash_algorithm: None,
+        default_encoding: None,
+    };
+    let key_pair = generate_hsm_key_pair(request, hsm.clone(), &backend, chrono::Utc::now()).unwrap();
+    assert_eq!(key_pair.key_type, KeyType::Ed25519Hsm);
+    assert!(key_pair.private_key.is_empty());
+
+    // Request passwords are ignored in favor of the token PIN
+    let (signer, kdf_timing) = load_signer(&key_pair, Some("ignored"), Some(&backend as &dyn SigningBackend)).unwrap();
+    assert!(kdf_timing.is_none());
+    let message = b"root key ceremony";
+    let signature = signer.sign_message(message).unwrap();
+    decode_public_key(&key_pair.public_key).unwrap().verify(message, &signature).unwrap();
+
+    // The label names one key; a second generation under it is refused
+    assert!(matches!(backend.generate(&hsm), Err(KeyManagementError::KeyConflict(_))));
+    let missing = HsmKeyRef { slot, label: format!("inkan-missing-{}", uuid::Uuid::new_v4()) };
+    assert!(backend.signer(&missing).is_err());
+}